@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // gRPC bindings are only generated for the optional `grpc` feature (needs protoc)
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/weaver.proto")?;
+
+    Ok(())
+}
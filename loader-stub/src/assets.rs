@@ -0,0 +1,152 @@
+//! Data files bundled with the payloads (`ConfigFooter::assets_offset`).
+//!
+//! Extracted once per distinct set of assets to a directory under the user's
+//! cache dir (`%LOCALAPPDATA%` on Windows, `~/Library/Caches` on macOS,
+//! `$XDG_CACHE_HOME` or `~/.cache` on Linux, with the home directory taken
+//! from the user database when `HOME` is unset), named after the product and
+//! `ConfigFooter::assets_id`. There is no shared fallback such as the temp
+//! dir: its names are predictable, so other users could plant files there.
+//! The directory is exported to the payloads as `KILLCODE_ASSETS_DIR`.
+//!
+//! Files are extracted to a private staging directory that is renamed into
+//! place when complete. A later run reuses the directory only if it is the
+//! user's own, closed to everyone else, and still holds the bundled files;
+//! otherwise it extracts them again.
+
+use std::fs::{self, DirBuilder, File};
+use std::path::{Path, PathBuf};
+
+use weaver_abi::bundle;
+use weaver_abi::ImageChecksum;
+
+use crate::{common, install, ConfigFooter};
+
+/// Tells payloads where the assets are
+const ENV_VAR: &str = "KILLCODE_ASSETS_DIR";
+
+/// Make the assets available and export their directory, if the footer has any
+pub fn extract(footer: &ConfigFooter, self_file: &mut File) -> Result<(), String> {
+    if footer.assets_size == 0 {
+        return Ok(());
+    }
+    let files = install::read_section(self_file, footer.assets_offset, footer.assets_size)?;
+    let mut id = ImageChecksum::new();
+    id.update(&files);
+    if id.finish() != footer.assets_id {
+        return Err("assets bundle doesn't match its id".to_string());
+    }
+    let cache = cache_dir().ok_or("no per-user cache directory for the assets (set HOME or XDG_CACHE_HOME)")?;
+    let product = footer.product_name().unwrap_or("killcode");
+    let dir = cache.join(format!("{}-assets-{:016x}", product, footer.assets_id));
+
+    if fs::symlink_metadata(&dir).is_ok() {
+        check_private(&dir)?;
+    }
+    if holds(&dir, &files)? {
+        common::log_assets_ready(&dir, false);
+    } else {
+        fs::create_dir_all(&cache).map_err(|e| format!("creating {} failed: {}", cache.display(), e))?;
+        let staging = sibling(&dir, "tmp");
+        let extracted = private_dir(&staging)
+            .and_then(|()| {
+                bundle::files(&files).try_for_each(|file| {
+                    let file = file.map_err(|()| "assets bundle is corrupt".to_string())?;
+                    install::write_file(&staging.join(file.path), file.data, file.mode)
+                })
+            })
+            .and_then(|()| replace_dir(&staging, &dir, &files));
+        let _ = fs::remove_dir_all(&staging);
+        extracted?;
+        common::log_assets_ready(&dir, true);
+    }
+
+    std::env::set_var(ENV_VAR, &dir);
+    Ok(())
+}
+
+/// `dir` with `.<pid>.<suffix>` appended
+fn sibling(dir: &Path, suffix: &str) -> PathBuf {
+    let mut path = dir.as_os_str().to_owned();
+    path.push(format!(".{}.{}", std::process::id(), suffix));
+    PathBuf::from(path)
+}
+
+/// Create `dir`, failing if anything is already there, open only to this user
+fn private_dir(dir: &Path) -> Result<(), String> {
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    #[cfg(not(unix))]
+    let _ = &mut builder;
+    builder.create(dir).map_err(|e| format!("creating {} failed: {}", dir.display(), e))
+}
+
+/// Move `staging` to `dir`, setting aside a stale copy that is in the way
+fn replace_dir(staging: &Path, dir: &Path, files: &[u8]) -> Result<(), String> {
+    let stale = sibling(dir, "old");
+    if fs::rename(dir, &stale).is_ok() {
+        let _ = fs::remove_dir_all(&stale);
+    }
+    match fs::rename(staging, dir) {
+        // Losing the race to another copy extracting the same assets is fine
+        Err(_) if check_private(dir).is_ok() && holds(dir, files).unwrap_or(false) => Ok(()),
+        result => result.map_err(|e| format!("moving assets to {} failed: {}", dir.display(), e)),
+    }
+}
+
+/// Refuse a directory someone else could have prepared or can still change
+#[cfg(unix)]
+fn check_private(dir: &Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = fs::symlink_metadata(dir).map_err(|e| format!("checking {} failed: {}", dir.display(), e))?;
+    if !metadata.is_dir() || metadata.uid() != nix::unistd::geteuid().as_raw() || metadata.mode() & 0o077 != 0 {
+        return Err(format!("{} isn't a directory of this user's with mode 0700; remove it", dir.display()));
+    }
+    Ok(())
+}
+
+/// `%LOCALAPPDATA%` is only open to its user
+#[cfg(not(unix))]
+fn check_private(dir: &Path) -> Result<(), String> {
+    let metadata = fs::symlink_metadata(dir).map_err(|e| format!("checking {} failed: {}", dir.display(), e))?;
+    if !metadata.is_dir() {
+        return Err(format!("{} isn't a directory; remove it", dir.display()));
+    }
+    Ok(())
+}
+
+/// Whether `dir` has every bundled file with the bundled contents
+fn holds(dir: &Path, files: &[u8]) -> Result<bool, String> {
+    for file in bundle::files(files) {
+        let file = file.map_err(|()| "assets bundle is corrupt".to_string())?;
+        let path = dir.join(file.path);
+        let is_file = fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_file());
+        if !is_file || fs::read(&path).ok().as_deref() != Some(file.data) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        var("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").or_else(home_dir).map(|home| home.join("Library/Caches"))
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").or_else(home_dir).map(|home| home.join(".cache")))
+    }
+}
+
+/// The effective user's home directory from the user database, for services started without `HOME`
+#[cfg(unix)]
+fn home_dir() -> Option<PathBuf> {
+    nix::unistd::User::from_uid(nix::unistd::geteuid()).ok().flatten().map(|user| user.dir).filter(|dir| dir.is_absolute())
+}
+
+#[cfg(not(unix))]
+fn home_dir() -> Option<PathBuf> {
+    None
+}
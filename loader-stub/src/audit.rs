@@ -0,0 +1,174 @@
+//! Execution audit records (`ConfigFooter::audit_path` / `audit_url`) and
+//! health reports to Weaver (`ConfigFooter::telemetry_url`).
+//!
+//! Each record is one JSON object: `time`, `event`, `pid`, `user`, `host`,
+//! `product` (when branded) and any event-specific fields. Records are
+//! appended as lines to the audit file and POSTed one per request to the
+//! audit URL; health events additionally go to the telemetry URL. POSTs run
+//! on background threads, so a slow collector never holds up the payloads,
+//! and events that end the stub wait briefly for queued ones. Failures to
+//! write or send are ignored: auditing never stops a payload.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::mpsc::{self, Sender, SyncSender};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use crate::{common, http, machine, ConfigFooter};
+
+/// Per POST, and the most a final event waits for queued ones
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+static AUDIT: OnceLock<Audit> = OnceLock::new();
+
+struct Audit {
+    path: Option<String>,
+    /// Queue for the audit URL's POST thread
+    audit: Option<Sender<Message>>,
+    /// Queue for the telemetry URL's POST thread
+    telemetry: Option<Sender<Message>>,
+    /// `"pid":…,"user":…,"host":…` shared by every record
+    identity: String,
+}
+
+enum Message {
+    Record(String),
+    Flush(SyncSender<()>),
+}
+
+/// Start auditing if the footer asks for it, and record the start
+pub fn init(footer: &ConfigFooter) {
+    let path = footer.audit_path();
+    let (audit, telemetry) = (footer.audit_url().map(poster), footer.telemetry_url().map(poster));
+    if path.is_none() && audit.is_none() && telemetry.is_none() {
+        return;
+    }
+
+    let mut identity = format!(
+        "\"pid\":{},\"user\":{},\"host\":{}",
+        std::process::id(),
+        json_or_null(imp::user()),
+        json_or_null(machine::hostname()),
+    );
+    if let Some(product) = footer.product_name() {
+        identity.push_str(&format!(",\"product\":{}", common::json_string(product)));
+    }
+
+    let audit = Audit { path: path.map(str::to_string), audit, telemetry, identity };
+    if AUDIT.set(audit).is_ok() {
+        let args: Vec<String> = std::env::args().skip(1).map(|arg| common::json_string(&arg)).collect();
+        record("start", &[("args", format!("[{}]", args.join(",")))]);
+    }
+}
+
+/// Record `event`; `fields` are names and already-encoded JSON values
+pub fn record(event: &str, fields: &[(&str, String)]) {
+    let Some(audit) = AUDIT.get() else {
+        return;
+    };
+    let line = format_record(common::current_time(), event, &audit.identity, fields);
+
+    if let Some(path) = &audit.path {
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(format!("{}\n", line).as_bytes()));
+        if let Err(e) = appended {
+            common::log_audit_failed(&format!("writing {} failed: {}", path, e));
+        }
+    }
+    if let Some(sender) = &audit.audit {
+        let _ = sender.send(Message::Record(line));
+    }
+}
+
+/// Record a health transition, which Weaver is told about as well
+pub fn health(event: &str, fields: &[(&str, String)]) {
+    record(event, fields);
+    let Some(audit) = AUDIT.get() else {
+        return;
+    };
+    if let Some(sender) = &audit.telemetry {
+        let line = format_record(common::current_time(), event, &audit.identity, fields);
+        let _ = sender.send(Message::Record(line));
+    }
+}
+
+/// Record an event the stub exits after, giving queued POSTs time to go out
+pub fn record_final(event: &str, fields: &[(&str, String)]) {
+    record(event, fields);
+    let Some(audit) = AUDIT.get() else {
+        return;
+    };
+    for sender in [&audit.audit, &audit.telemetry].into_iter().flatten() {
+        let (done, flushed) = mpsc::sync_channel(1);
+        if sender.send(Message::Flush(done)).is_ok() {
+            let _ = flushed.recv_timeout(TIMEOUT);
+        }
+    }
+}
+
+/// Start a thread POSTing queued records to `url`, in order
+fn poster(url: &str) -> Sender<Message> {
+    let url = url.to_string();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for message in receiver {
+            match message {
+                Message::Record(record) => {
+                    if let Err(e) = http::post_json(&url, &record, TIMEOUT) {
+                        common::log_audit_failed(&e);
+                    }
+                }
+                Message::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    });
+    sender
+}
+
+fn format_record(time: i64, event: &str, identity: &str, fields: &[(&str, String)]) -> String {
+    let mut line = format!("{{\"time\":{},\"event\":{},{}", time, common::json_string(event), identity);
+    for (name, value) in fields {
+        line.push_str(&format!(",{}:{}", common::json_string(name), value));
+    }
+    line.push('}');
+    line
+}
+
+fn json_or_null(value: Option<String>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| common::json_string(&value))
+}
+
+#[cfg(unix)]
+mod imp {
+    use nix::unistd::{Uid, User};
+
+    pub(super) fn user() -> Option<String> {
+        let uid = Uid::effective();
+        Some(User::from_uid(uid).ok().flatten().map_or_else(|| uid.to_string(), |user| user.name))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    pub(super) fn user() -> Option<String> {
+        std::env::var("USERNAME").ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_record() {
+        let line = format_record(1700000000, "base_exit", "\"pid\":42,\"user\":\"a\\\"b\"", &[("code", "3".to_string())]);
+        assert_eq!(line, r#"{"time":1700000000,"event":"base_exit","pid":42,"user":"a\"b","code":3}"#);
+    }
+}
@@ -1,6 +1,77 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::{HealthStatus, HEALTH_CHECK_INTERVAL};
+use weaver_abi::footer::{CWD_BINARY_DIR, CWD_PATH, PREREQ_ABORT, PREREQ_RESTART};
+use weaver_abi::health::{HealthSnapshot, SlotName, MAX_HEALTH_SLOTS, OVERLOAD_SLOT};
+use weaver_abi::kill_policy::{
+    self, PolicyError, PolicyOp, ACTION_EXIT, ACTION_KILL_BASE, ACTION_KILL_OVERLOAD, ACTION_LOG, FIELD_ALIVE,
+    FIELD_FAILURES, FIELD_KILL_REQUESTED, FIELD_SINCE_HEARTBEAT, FIELD_SINCE_SUCCESS, MAX_KILL_POLICY_DEPTH, OP_AND,
+    OP_AT_LEAST, OP_FLAGS, OP_GREATER, OP_NOT, OP_RULE,
+};
+
+use crate::logging::{error, info};
+use crate::{audit, control, readiness, ConfigFooter, HealthSlot, HealthTable, FORCE_KILL_DELAY_MS, HEALTH_CHECK_INTERVAL};
+
+/// Name base or the overload runs under (memfd, argv[0], temp file)
+///
+/// `base`/`overload` by default; a branded binary names base after the
+/// product and the overload `<product>-helper`.
+pub fn payload_name(footer: &ConfigFooter, is_base: bool) -> String {
+    match (footer.product_name(), is_base) {
+        (Some(product), true) => product.to_string(),
+        (Some(product), false) => format!("{}-helper", product),
+        (None, true) => "base".to_string(),
+        (None, false) => "overload".to_string(),
+    }
+}
+
+/// Directory base or the overload should start in, `None` to inherit the stub's
+pub fn payload_cwd(footer: &ConfigFooter, is_base: bool) -> Option<PathBuf> {
+    match footer.cwd(is_base) {
+        (CWD_BINARY_DIR, _) => std::env::current_exe().ok()?.parent().map(Path::to_path_buf),
+        (CWD_PATH, Some(path)) => Some(PathBuf::from(path)),
+        _ => None,
+    }
+}
+
+/// Set a forked child's umask and working directory before exec (unix only)
+#[cfg(unix)]
+pub fn enter_cwd_and_umask(cwd: Option<&Path>, umask: Option<u16>) -> Result<(), String> {
+    if let Some(mask) = umask {
+        nix::sys::stat::umask(nix::sys::stat::Mode::from_bits_truncate(mask.into()));
+    }
+    if let Some(dir) = cwd {
+        nix::unistd::chdir(dir).map_err(|e| format!("chdir to {} failed: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Per-user directory for locks and sockets: `$XDG_RUNTIME_DIR`, else
+/// `$TMPDIR` or /tmp (unix only)
+#[cfg(unix)]
+pub fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// `text` as a quoted JSON string
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
 
 /// Get current Unix timestamp in seconds
 pub fn current_time() -> i64 {
@@ -10,83 +81,430 @@ pub fn current_time() -> i64 {
         .as_secs() as i64
 }
 
-/// Initialize health status struct with default values
-pub unsafe fn init_health_status(health_ptr: *mut HealthStatus) {
-    (*health_ptr).last_success = current_time();
-    (*health_ptr).is_alive = 1;
-    (*health_ptr).consecutive_failures = 0;
-    (*health_ptr).should_kill_base = 0;
-    (*health_ptr).parent_requests_kill = 0;
-    (*health_ptr).base_pid = 0;
+/// Health protocol version this stub writes into the region header and
+/// accepts when evaluating it
+pub fn health_protocol_version() -> u32 {
+    weaver_abi::PROTOCOL_VERSION
+}
+
+/// Initialize the health table with the overload's slot and tell the
+/// overload which slot is its own
+pub unsafe fn init_health_table(table: *mut HealthTable) {
+    // A single short constant name always fits
+    let _ = (*table).init(&[OVERLOAD_SLOT], current_time());
+    (*table).set_stub_pid(std::process::id() as i32);
+    std::env::set_var("KILLCODE_HEALTH_SLOT", OVERLOAD_SLOT);
+}
+
+/// Pass base's PID to every overload run started from now on in `KILLCODE_BASE_PID`
+///
+/// A sidecar overload is already running when base starts and reads it from
+/// the health region instead.
+pub fn export_base_pid(pid: impl std::fmt::Display) {
+    std::env::set_var("KILLCODE_BASE_PID", pid.to_string());
 }
 
 /// Check if health monitoring should be enabled
-pub fn should_enable_health_monitoring(sync_mode: bool, grace_period: u32, network_failure_kill_count: u32) -> bool {
-    !sync_mode && (grace_period > 0 || network_failure_kill_count > 0)
+///
+/// A ready flag readiness probe needs the region even without health checks.
+pub fn should_enable_health_monitoring(sync_mode: bool, policy: &KillPolicy, footer: &ConfigFooter) -> bool {
+    !sync_mode && (policy.is_active() || readiness::uses_health_region(footer))
+}
+
+/// Whether the last health check found each slot invalid, so only the
+/// transition is reported
+static HEALTH_REGION_INVALID: [AtomicBool; MAX_HEALTH_SLOTS] = [const { AtomicBool::new(false) }; MAX_HEALTH_SLOTS];
+
+/// `log` rules that held at the last health check, one bit per rule for
+/// each slot, so a rule is logged once each time it starts holding
+static POLICY_RULES_LOGGED: [AtomicU32; MAX_HEALTH_SLOTS] = [const { AtomicU32::new(0) }; MAX_HEALTH_SLOTS];
+
+/// One rule of a [`KillPolicy`]: a postfix condition and what to do when it holds
+struct PolicyRule {
+    condition: Vec<PolicyOp>,
+    action: u32,
+    /// Reported as the kill reason in logs and audit records
+    reason: &'static str,
+}
+
+/// The rules the health monitor runs against every slot of the health table
+pub struct KillPolicy {
+    rules: Vec<PolicyRule>,
+    /// Whether any rule can hold, so monitoring is worth starting
+    active: bool,
+}
+
+impl KillPolicy {
+    /// The footer's own policy, or the fixed checks built from its
+    /// `grace_period`, `network_failure_kill_count` and `heartbeat_timeout`
+    ///
+    /// A policy that doesn't validate is logged and replaced by the fixed checks.
+    pub fn from_footer(footer: &ConfigFooter) -> Self {
+        if footer.has_kill_policy() {
+            let ops: Vec<PolicyOp> = footer.kill_policy().collect();
+            match kill_policy::validate(&ops) {
+                Ok(()) => return Self::from_ops(&ops),
+                Err(e) => log_kill_policy_invalid(&e),
+            }
+        }
+        Self::fixed(footer.grace_period, footer.network_failure_kill_count, footer.heartbeat_timeout)
+    }
+
+    /// Split validated ops into rules
+    fn from_ops(ops: &[PolicyOp]) -> Self {
+        let rules: Vec<PolicyRule> = ops
+            .split_inclusive(|op| op.code == OP_RULE)
+            .map(|rule| PolicyRule {
+                condition: rule[..rule.len() - 1].to_vec(),
+                action: rule[rule.len() - 1].operand,
+                reason: "kill_policy",
+            })
+            .collect();
+        KillPolicy { active: !rules.is_empty(), rules }
+    }
+
+    /// The checks every stub ran before kill policies, in the same order
+    fn fixed(grace_period: u32, network_failure_kill_count: u32, heartbeat_timeout: u32) -> Self {
+        let rule = |condition: &[PolicyOp], action, reason| PolicyRule { condition: condition.to_vec(), action, reason };
+        let mut rules = Vec::new();
+        if grace_period > 0 {
+            let condition = [PolicyOp::new(OP_GREATER, FIELD_SINCE_SUCCESS, grace_period)];
+            rules.push(rule(&condition, ACTION_KILL_BASE, "grace_period_exceeded"));
+        }
+        if network_failure_kill_count > 0 {
+            let condition = [PolicyOp::new(OP_AT_LEAST, FIELD_FAILURES, network_failure_kill_count)];
+            rules.push(rule(&condition, ACTION_KILL_OVERLOAD, "network_failures"));
+        }
+        rules.push(rule(&[PolicyOp::new(OP_AT_LEAST, FIELD_KILL_REQUESTED, 1)], ACTION_KILL_BASE, "overload_requested"));
+        let dead = [PolicyOp::new(OP_AT_LEAST, FIELD_ALIVE, 1), PolicyOp::new(OP_NOT, 0, 0)];
+        rules.push(rule(&dead, ACTION_KILL_BASE, "heartbeat_lost"));
+        if heartbeat_timeout > 0 {
+            let condition = [PolicyOp::new(OP_GREATER, FIELD_SINCE_HEARTBEAT, heartbeat_timeout)];
+            rules.push(rule(&condition, ACTION_KILL_BASE, "heartbeat_timeout"));
+        }
+        KillPolicy {
+            rules,
+            active: grace_period > 0 || network_failure_kill_count > 0 || heartbeat_timeout > 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// When base started (Unix seconds, 0 = not yet)
+static BASE_STARTED_AT: AtomicI64 = AtomicI64::new(0);
+
+/// Note that base is running, so the health monitor's warmup starts now
+pub fn record_base_started() {
+    BASE_STARTED_AT.store(current_time(), Ordering::Relaxed);
+}
+
+/// When the health monitor starts counting ages and failures for each slot
+///
+/// A slot is armed once base has run for `monitor_warmup` seconds, or with
+/// `arm_after_success` at the payload's first successful check after base
+/// started (or after the warmup, if one is set, whichever comes first).
+/// Until then its ages and failure count read as 0, so only kill requests,
+/// `is_alive` and custom flags can trip it. Ages count from the moment the
+/// slot was armed, never from when the health region was set up.
+pub struct MonitorArming {
+    warmup: i64,
+    after_first_success: bool,
+    /// When base started, 0 until the monitor has seen it
+    base_started: i64,
+    armed_at: [Option<i64>; MAX_HEALTH_SLOTS],
+}
+
+impl MonitorArming {
+    pub fn from_footer(footer: &ConfigFooter) -> Self {
+        MonitorArming {
+            warmup: footer.monitor_warmup as i64,
+            after_first_success: footer.arm_after_success != 0,
+            base_started: 0,
+            armed_at: [None; MAX_HEALTH_SLOTS],
+        }
+    }
+
+    /// When the slot at `index` was armed, arming it now if it is due
+    fn armed_at(&mut self, index: usize, slot: SlotName, status: &HealthSnapshot, now: i64) -> Option<i64> {
+        if let Some(at) = self.armed_at[index] {
+            return Some(at);
+        }
+        if self.base_started == 0 {
+            self.base_started = BASE_STARTED_AT.load(Ordering::Relaxed);
+            if self.base_started == 0 {
+                return None;
+            }
+        }
+
+        let warmed_up = self.base_started + self.warmup;
+        let succeeded = self.after_first_success && status.last_success > self.base_started;
+        let warmup_over = (!self.after_first_success || self.warmup > 0) && now >= warmed_up;
+        let at = match (succeeded, warmup_over) {
+            (true, true) => status.last_success.min(warmed_up),
+            (true, false) => status.last_success,
+            (false, true) => warmed_up,
+            (false, false) => return None,
+        };
+        log_health_slot_armed(slot, at - self.base_started, succeeded && at == status.last_success);
+        self.armed_at[index] = Some(at);
+        Some(at)
+    }
 }
 
 /// Result of health check evaluation
+#[derive(Debug, PartialEq, Eq)]
 pub enum HealthCheckResult {
     /// Everything is fine, continue monitoring
     Ok,
-    /// Grace period exceeded, kill base
-    GracePeriodExceeded { time_since_success: i64, grace_period: u32 },
-    /// Network failure threshold exceeded, signal overload to kill
-    NetworkFailureThreshold { failures: i32, threshold: u32 },
-    /// Overload requested base termination
-    OverloadRequestedKill,
-    /// Overload heartbeat lost
-    HeartbeatLost,
-}
-
-/// Evaluate health status and determine if action is needed
-/// Returns None if base process has exited, otherwise returns HealthCheckResult
-pub unsafe fn evaluate_health_status(
-    health_ptr: *const HealthStatus,
-    grace_period: u32,
-    network_failure_kill_count: u32,
+    /// A rule called for killing base
+    KillBase,
+    /// A rule called for the slot's payload to run its kill method, with
+    /// base killed directly if it doesn't
+    KillOverload,
+    /// A rule called for shutting the merged binary down
+    Exit,
+}
+
+/// Evaluate every slot of the health table, returning the index of the
+/// first one that needs action along with its result
+///
+/// Each slot is judged on its own by the same policy, and any one slot
+/// calling for action is acted on, whatever the others report.
+pub unsafe fn evaluate_health_table(
+    table: *const HealthTable,
+    policy: &KillPolicy,
+    arming: &mut MonitorArming,
+) -> (usize, HealthCheckResult) {
+    for (index, slot) in (*table).slots().enumerate() {
+        let result = evaluate_health_status(slot, index, policy, arming);
+        if result != HealthCheckResult::Ok {
+            return (index, result);
+        }
+    }
+    (0, HealthCheckResult::Ok)
+}
+
+/// Run `policy` against the health slot at `index` and determine if action is needed
+///
+/// Slots whose header doesn't match [`health_protocol_version`] and this
+/// build's layout, or whose fields are out of range, never trigger a kill.
+pub fn evaluate_health_status(
+    slot: &HealthSlot,
+    index: usize,
+    policy: &KillPolicy,
+    arming: &mut MonitorArming,
 ) -> HealthCheckResult {
-    let status = &*health_ptr;
+    let status = slot.status().snapshot();
+    let flags = slot.flags();
     let now = current_time();
-    let time_since_success = now - status.last_success;
 
-    // Check 1: Grace period
-    if grace_period > 0 && time_since_success > grace_period as i64 {
-        return HealthCheckResult::GracePeriodExceeded {
-            time_since_success,
-            grace_period,
-        };
+    // Never act on a region the overload (or something else) has corrupted
+    if let Err(e) = status.validate(now) {
+        log_health_region_invalid(index, e);
+        return HealthCheckResult::Ok;
+    }
+    HEALTH_REGION_INVALID[index].store(false, Ordering::Relaxed);
+
+    let armed_at = arming.armed_at(index, slot.name(), &status, now);
+    let since = |at: i64| armed_at.map_or(0, |armed| now - at.max(armed));
+    let field = |field| match field {
+        FIELD_SINCE_SUCCESS => since(status.last_success),
+        FIELD_FAILURES if armed_at.is_some() => status.consecutive_failures as i64,
+        FIELD_SINCE_HEARTBEAT => since(status.last_heartbeat),
+        FIELD_KILL_REQUESTED => status.should_kill_base as i64,
+        FIELD_ALIVE => status.is_alive as i64,
+        _ => 0,
+    };
+
+    let logged = &POLICY_RULES_LOGGED[index];
+    for (number, rule) in policy.rules.iter().enumerate() {
+        let bit = 1u32.checked_shl(number as u32).unwrap_or(0);
+        if !rule_holds(&rule.condition, &field, flags) {
+            logged.fetch_and(!bit, Ordering::Relaxed);
+            continue;
+        }
+        let condition = describe_condition(&rule.condition, &field, flags);
+        match rule.action {
+            ACTION_LOG => {
+                if logged.fetch_or(bit, Ordering::Relaxed) & bit == 0 {
+                    log_kill_policy_rule_holds(slot.name(), number, &condition);
+                }
+            }
+            ACTION_KILL_OVERLOAD => {
+                log_kill_policy_matched(slot.name(), rule.reason, &condition, "signaling overload to kill parent");
+                return HealthCheckResult::KillOverload;
+            }
+            ACTION_EXIT => {
+                log_kill_policy_matched(slot.name(), rule.reason, &condition, "shutting down");
+                return HealthCheckResult::Exit;
+            }
+            // ACTION_KILL_BASE
+            _ => {
+                log_kill_policy_matched(slot.name(), rule.reason, &condition, "killing base");
+                return HealthCheckResult::KillBase;
+            }
+        }
+    }
+
+    HealthCheckResult::Ok
+}
+
+/// Evaluate a validated postfix condition
+fn rule_holds(condition: &[PolicyOp], field: &impl Fn(u8) -> i64, flags: u32) -> bool {
+    let mut stack = [false; MAX_KILL_POLICY_DEPTH];
+    let mut depth = 0;
+    for op in condition {
+        match op.code {
+            OP_GREATER | OP_AT_LEAST | OP_FLAGS => {
+                stack[depth] = match op.code {
+                    OP_GREATER => field(op.field) > op.operand as i64,
+                    OP_AT_LEAST => field(op.field) >= op.operand as i64,
+                    _ => flags & op.operand != 0,
+                };
+                depth += 1;
+            }
+            OP_NOT => stack[depth - 1] = !stack[depth - 1],
+            code => {
+                depth -= 1;
+                let (left, right) = (stack[depth - 1], stack[depth]);
+                stack[depth - 1] = if code == OP_AND { left && right } else { left || right };
+            }
+        }
     }
+    depth == 1 && stack[0]
+}
 
-    // Check 2: Network failure threshold
-    if network_failure_kill_count > 0 && status.consecutive_failures >= network_failure_kill_count as i32 {
-        return HealthCheckResult::NetworkFailureThreshold {
-            failures: status.consecutive_failures,
-            threshold: network_failure_kill_count,
+/// Infix rendering of a validated postfix condition, with the values it was
+/// evaluated against, for logs
+fn describe_condition(condition: &[PolicyOp], field: &impl Fn(u8) -> i64, flags: u32) -> String {
+    let mut stack: Vec<String> = Vec::new();
+    for op in condition {
+        let term = match op.code {
+            OP_GREATER => format!("{} {} > {}", field_name(op.field), field(op.field), op.operand),
+            OP_AT_LEAST => format!("{} {} >= {}", field_name(op.field), field(op.field), op.operand),
+            OP_FLAGS => format!("flags {:#x} & {:#x}", flags, op.operand),
+            OP_NOT => format!("!({})", stack.pop().unwrap_or_default()),
+            _ => {
+                let right = stack.pop().unwrap_or_default();
+                let left = stack.pop().unwrap_or_default();
+                format!("({} {} {})", left, if op.code == OP_AND { "&&" } else { "||" }, right)
+            }
         };
+        stack.push(term);
     }
+    stack.pop().unwrap_or_default()
+}
 
-    // Check 3: Overload requested base kill
-    if status.should_kill_base != 0 {
-        return HealthCheckResult::OverloadRequestedKill;
+fn field_name(field: u8) -> &'static str {
+    match field {
+        FIELD_SINCE_SUCCESS => "since_success",
+        FIELD_FAILURES => "failures",
+        FIELD_SINCE_HEARTBEAT => "since_heartbeat",
+        FIELD_KILL_REQUESTED => "kill_requested",
+        _ => "alive",
     }
+}
 
-    // Check 4: Overload heartbeat lost
-    if status.is_alive == 0 {
-        return HealthCheckResult::HeartbeatLost;
+/// Signal the overload in slot `index` to execute its kill method by setting
+/// its parent_requests_kill flag
+pub unsafe fn signal_overload_to_kill(table: *mut HealthTable, index: usize) {
+    if let Some(slot) = (*table).slots().nth(index) {
+        slot.status().request_overload_kill();
     }
+}
 
-    HealthCheckResult::Ok
+/// Health check interval from the footer (seconds), or the default when unset
+pub fn health_check_interval(configured: u32) -> std::time::Duration {
+    let secs = if configured > 0 { configured } else { HEALTH_CHECK_INTERVAL };
+    std::time::Duration::from_secs(secs as u64)
 }
 
-/// Signal overload to execute kill method by setting parent_requests_kill flag
-pub unsafe fn signal_overload_to_kill(health_ptr: *mut HealthStatus) {
-    (*health_ptr).parent_requests_kill = 1;
+/// Run the overload on its delay/interval schedule until base exits
+///
+/// `run_overload` starts the overload and waits for it, returning its exit
+/// code. A run that fails to start or exits non-zero is a failed check, and
+/// `failure_threshold` failed checks in a row kill base (0 = the first one
+/// does), so a one-shot license checker can miss a few runs like a resident
+/// overload can miss a few network checks. A `restart_overload` on the
+/// control socket runs it early, and without an interval it is then the only
+/// thing that runs it again.
+pub fn run_overload_schedule(
+    start_delay: u32,
+    interval: u32,
+    failure_threshold: u32,
+    mut run_overload: impl FnMut() -> Result<i32, String>,
+    base_alive: impl Fn() -> bool,
+    kill_base: impl Fn(),
+) {
+    control::accept_restarts();
+    control::wait_for_restart(Some(std::time::Duration::from_secs(start_delay as u64)));
+    let mut failures = 0;
+    loop {
+        if !base_alive() {
+            return;
+        }
+
+        let result = run_overload();
+        match result {
+            Ok(0) => {
+                failures = 0;
+                log_scheduled_overload_passed();
+            }
+            Ok(code) => log_verification_failed(code),
+            Err(ref e) => log_overload_start_failed(e),
+        }
+        if result != Ok(0) {
+            failures += 1;
+            if failures >= failure_threshold.max(1) {
+                kill_base();
+                return;
+            }
+            log_scheduled_overload_failure_tolerated(failures, failure_threshold);
+        }
+
+        if interval == 0 && !control::is_listening() {
+            return;
+        }
+        control::wait_for_restart((interval > 0).then(|| std::time::Duration::from_secs(interval as u64)));
+    }
 }
 
-/// Get the health check interval as a Duration
-pub fn health_check_interval() -> std::time::Duration {
-    std::time::Duration::from_secs(HEALTH_CHECK_INTERVAL as u64)
+/// Run the overload to completion until it has passed `footer.prerequisite_runs()` times
+///
+/// `run_overload` starts one run and waits for it; `Err` is a run that didn't
+/// start or didn't exit 0. `prerequisite_on_failure` decides whether a failed
+/// run is the last one. Each run finds its 1-based number in
+/// `KILLCODE_PREREQUISITE_RUN`.
+pub fn run_prerequisites(footer: &ConfigFooter, mut run_overload: impl FnMut() -> Result<(), String>) -> Result<(), String> {
+    let runs = footer.prerequisite_runs();
+    let (mut passed, mut failures) = (0, 0);
+    while passed < runs {
+        if runs > 1 {
+            log_prerequisite_run(passed + 1, runs);
+        }
+        std::env::set_var("KILLCODE_PREREQUISITE_RUN", (passed + 1).to_string());
+        let result = run_overload();
+        std::env::remove_var("KILLCODE_PREREQUISITE_RUN");
+
+        match result {
+            Ok(()) => passed += 1,
+            Err(e) => {
+                failures += 1;
+                if footer.prerequisite_on_failure == PREREQ_ABORT || failures > footer.prerequisite_max_failures {
+                    return Err(e);
+                }
+                if footer.prerequisite_on_failure == PREREQ_RESTART {
+                    passed = 0;
+                }
+                log_prerequisite_retry(&e, failures, footer.prerequisite_max_failures, footer.prerequisite_retry_delay);
+                std::thread::sleep(std::time::Duration::from_secs(footer.prerequisite_retry_delay as u64));
+            }
+        }
+    }
+    Ok(())
 }
 
 /// Duration to wait for overload to execute kill method before fallback
@@ -97,100 +515,595 @@ pub fn overload_kill_wait_duration() -> std::time::Duration {
 // Log message helpers - centralized logging for consistent output
 
 pub fn log_health_monitoring_enabled(shm_name: &str) {
-    eprintln!("[KillCode] Health monitoring enabled: {}", shm_name);
+    info!("Health monitoring enabled: {} (protocol v{})", shm_name, health_protocol_version());
 }
 
 pub fn log_health_monitor_started() {
-    eprintln!("[KillCode] Health monitor started");
+    info!("Health monitor started");
 }
 
 pub fn log_sync_mode_waiting(pid: impl std::fmt::Display) {
-    eprintln!("[KillCode] Sync mode: Waiting for overload verification (PID: {})...", pid);
+    info!("Sync mode: Waiting for overload verification (PID: {})...", pid);
 }
 
 pub fn log_verification_failed(exit_code: impl std::fmt::Display) {
-    eprintln!("[KillCode] ❌ Overload verification failed (exit code: {})", exit_code);
+    audit::health("verification_failed", &[("code", exit_code.to_string())]);
+    error!("❌ Overload verification failed (exit code: {})", exit_code);
 }
 
 pub fn log_verification_successful() {
-    eprintln!("[KillCode] ✅ Overload verification successful");
+    info!("✅ Overload verification successful");
+}
+
+pub fn log_prerequisite_run(run: u32, runs: u32) {
+    info!("Prerequisite run {}/{}", run, runs);
+}
+
+pub fn log_prerequisite_retry(error: &str, failures: u32, max_failures: u32, delay: u32) {
+    audit::health("prerequisite_failed", &[("failures", failures.to_string())]);
+    error!("Prerequisite run failed ({}/{} failures allowed): {}, retrying in {}s", failures, max_failures, error, delay);
 }
 
 pub fn log_async_mode_started(pid: impl std::fmt::Display) {
-    eprintln!("[KillCode] Async mode: Overload running in background (PID: {})", pid);
+    info!("Async mode: Overload running in background (PID: {})", pid);
 }
 
 pub fn log_overload_start_failed(error: &str) {
-    eprintln!("[KillCode] Failed to start overload binary: {}", error);
+    error!("Failed to start overload binary: {}", error);
 }
 
 #[cfg(any(target_os = "windows", target_os = "macos"))]
 pub fn log_base_start_failed(error: &str) {
-    eprintln!("[KillCode] Failed to start base binary: {}", error);
+    error!("Failed to start base binary: {}", error);
+}
+
+pub fn log_overload_preloaded() {
+    info!("Library overload: loading it into base, no separate overload process");
+}
+
+pub fn log_base_first() {
+    info!("Base-first order: overload starts after base exits");
+}
+
+pub fn log_overload_after_base_failed(error: &str) {
+    error!("Overload after base failed: {}", error);
+}
+
+pub fn log_overload_scheduled(start_delay: u32, interval: u32) {
+    info!("Scheduled overload: first run after {}s, interval {}s (0 = once)", start_delay, interval);
+}
+
+pub fn log_scheduled_overload_passed() {
+    info!("✅ Scheduled overload run passed");
+}
+
+pub fn log_scheduled_overload_failure_tolerated(failures: u32, threshold: u32) {
+    info!("Scheduled overload run failed ({}/{} in a row before base is killed)", failures, threshold);
+}
+
+pub fn log_waiting_for_readiness(probe: &str, timeout: Option<std::time::Duration>) {
+    match timeout {
+        Some(timeout) => info!("Waiting up to {}s for the overload's {}", timeout.as_secs(), probe),
+        None => info!("Waiting for the overload's {}", probe),
+    }
+}
+
+pub fn log_overload_ready(after: std::time::Duration) {
+    audit::record("overload_ready", &[("ms", after.as_millis().to_string())]);
+    info!("✅ Overload ready after {}ms", after.as_millis());
+}
+
+pub fn log_readiness_failed(error: &str) {
+    audit::record_final("refused", &[("reason", json_string("overload_not_ready")), ("error", json_string(error))]);
+    error!("❌ Overload not ready, base not started: {}", error);
+}
+
+pub fn log_readiness_failed_starting_base(error: &str) {
+    audit::record("overload_not_ready", &[("error", json_string(error))]);
+    error!("Warning: Overload not ready, starting base anyway: {}", error);
 }
 
 pub fn log_starting_base() {
-    eprintln!("[KillCode] Starting base binary...");
+    info!("Starting base binary...");
 }
 
 pub fn log_base_completed_terminating_overload(pid: impl std::fmt::Display) {
-    eprintln!("[KillCode] Base binary completed, terminating overload (PID: {})", pid);
+    info!("Base binary completed, terminating overload (PID: {})", pid);
 }
 
 pub fn log_base_exited(exit_code: impl std::fmt::Display) {
-    eprintln!("[KillCode] Base binary exited with code: {}", exit_code);
+    audit::record_final("base_exit", &[("code", exit_code.to_string())]);
+    info!("Base binary exited with code: {}", exit_code);
+}
+
+pub fn log_kill_policy_matched(slot: SlotName, reason: &str, condition: &str, action: &str) {
+    audit::health(
+        "health_kill",
+        &[("reason", json_string(reason)), ("slot", json_string(slot.as_str())), ("condition", json_string(condition))],
+    );
+    error!("⚠️  {} on health slot '{}' ({}), {}", reason, slot, condition, action);
 }
 
-pub fn log_grace_period_exceeded(time_since_success: i64, grace_period: u32) {
-    eprintln!("[KillCode] ⚠️  Grace period exceeded ({} > {} seconds), killing base", time_since_success, grace_period);
+pub fn log_kill_policy_rule_holds(slot: SlotName, rule: usize, condition: &str) {
+    audit::health("policy_rule", &[("rule", rule.to_string()), ("slot", json_string(slot.as_str()))]);
+    info!("Kill policy rule {} holds on health slot '{}' ({})", rule, slot, condition);
 }
 
-pub fn log_network_failure_threshold(failures: i32, threshold: u32) {
-    eprintln!("[KillCode] ⚠️  Network failure threshold exceeded ({}/{}), signaling overload to kill parent", failures, threshold);
+pub fn log_health_slot_armed(slot: SlotName, after_secs: i64, by_success: bool) {
+    let cause = if by_success { "first successful check" } else { "warmup" };
+    audit::health("monitor_armed", &[("slot", json_string(slot.as_str())), ("after_secs", after_secs.to_string())]);
+    info!("Health monitoring armed for slot '{}' {}s after base started ({})", slot, after_secs, cause);
+}
+
+pub fn log_kill_policy_invalid(error: &PolicyError) {
+    error!("Warning: Ignoring invalid kill policy, using the fixed checks: {}", error);
 }
 
 pub fn log_fallback_kill() {
-    eprintln!("[KillCode] Fallback: Killing base directly (overload didn't respond)");
+    audit::health("fallback_kill", &[]);
+    error!("Fallback: Killing base directly (overload didn't respond)");
 }
 
-pub fn log_overload_requested_kill() {
-    eprintln!("[KillCode] ⚠️  Overload requested base termination");
+pub fn log_forwarding_shutdown(request: impl std::fmt::Display, grace: std::time::Duration) {
+    info!("Received {}, stopping children (grace: {:?})", request, grace);
 }
 
-pub fn log_heartbeat_lost() {
-    eprintln!("[KillCode] ⚠️  Overload heartbeat lost, killing base");
+pub fn log_shutdown_grace_exceeded() {
+    error!("⚠️  Children still running after grace period, killing");
 }
 
 #[cfg(target_os = "linux")]
 pub fn log_forcing_sigkill() {
-    eprintln!("[KillCode] Forcing SIGKILL on overload");
+    info!("Forcing SIGKILL on overload");
 }
 
 pub fn log_shm_map_failed(error: impl std::fmt::Display) {
-    eprintln!("[KillCode] Warning: Failed to map shared memory: {}", error);
+    error!("Warning: Failed to map shared memory: {}", error);
+}
+
+pub fn log_health_region_invalid(index: usize, error: impl std::fmt::Display) {
+    if !HEALTH_REGION_INVALID[index].swap(true, Ordering::Relaxed) {
+        audit::health("health_invalid", &[("error", json_string(&error.to_string()))]);
+    }
+    error!("Warning: Ignoring invalid health status: {}", error);
 }
 
 pub fn log_shm_create_failed(error: impl std::fmt::Display) {
-    eprintln!("[KillCode] Warning: Failed to create shared memory: {}", error);
+    error!("Warning: Failed to create shared memory: {}", error);
+}
+
+pub fn log_tampering_detected(error: &str) {
+    audit::record_final("refused", &[("reason", json_string("tampering")), ("error", json_string(error))]);
+    error!("❌ Tampering or debugging detected, refusing to run: {}", error);
+}
+
+pub fn log_already_running(pid: Option<i32>) {
+    audit::record_final("refused", &[("reason", json_string("already_running"))]);
+    match pid {
+        Some(pid) => error!("Another instance is already running (pid {}), exiting", pid),
+        None => error!("Another instance is already running, exiting"),
+    }
+}
+
+pub fn log_activated_running_instance(pid: i32) {
+    info!("Another instance is already running (pid {}), notified it and exiting", pid);
+}
+
+#[cfg(unix)]
+pub fn log_forwarding_activation(base_pid: i32) {
+    info!("Another launch was refused, forwarding SIGUSR1 to base ({})", base_pid);
+}
+
+pub fn log_control_socket_listening(endpoint: &str) {
+    info!("Control socket listening on {}", endpoint);
+}
+
+pub fn log_control_socket_failed(error: &str) {
+    error!("Warning: Control socket unavailable: {}", error);
+}
+
+pub fn log_ipc_channel_ready() {
+    info!("IPC channel between base and overload ready");
+}
+
+pub fn log_ipc_channel_failed(error: &str) {
+    error!("Warning: IPC channel unavailable: {}", error);
+}
+
+pub fn log_control_command(command: &str) {
+    info!("Control socket: {}", command);
+}
+
+pub fn log_machine_not_allowed(fingerprint: u64) {
+    let fingerprint_hex = json_string(&format!("{:016x}", fingerprint));
+    audit::record_final("refused", &[("reason", json_string("machine_not_allowed")), ("fingerprint", fingerprint_hex)]);
+    error!("❌ This machine (fingerprint {:016x}) is not licensed to run this build", fingerprint);
+}
+
+pub fn log_machine_fingerprint_failed(error: &str) {
+    audit::record_final("refused", &[("reason", json_string("fingerprint_failed")), ("error", json_string(error))]);
+    error!("❌ Could not fingerprint this machine, refusing to start: {}", error);
+}
+
+pub fn log_expired(expires_at: i64) {
+    audit::record_final("refused", &[("reason", json_string("expired"))]);
+    error!("❌ This build expired at {} (Unix time), refusing to start", expires_at);
+}
+
+pub fn log_expired_while_running(expires_at: i64) {
+    audit::record("expired", &[]);
+    error!("❌ This build expired at {} (Unix time), shutting down", expires_at);
+}
+
+pub fn log_revocation_enabled(url: &str, interval: u32) {
+    info!("Revocation checks enabled: {} every {}s", url, interval);
+}
+
+pub fn log_revoked(url: &str) {
+    audit::record("revoked", &[]);
+    error!("❌ Revoked by {}, shutting down", url);
+}
+
+pub fn log_revocation_check_failed(error: &str) {
+    error!("Warning: Revocation check failed: {}", error);
+}
+
+pub fn log_audit_failed(error: &str) {
+    error!("Warning: Audit record not delivered: {}", error);
+}
+
+pub fn log_base_crashed(exit: &crate::crash::Exit) {
+    error!("💥 Base crashed: {}", exit);
+}
+
+pub fn log_crash_report_written(path: &Path) {
+    info!("Crash report written to {}", path.display());
+}
+
+pub fn log_crash_report_failed(error: &str) {
+    error!("Warning: Crash report not delivered: {}", error);
+}
+
+pub fn log_crash_capture_failed(error: &str) {
+    error!("Warning: Can't capture base's stderr for crash reports: {}", error);
+}
+
+pub fn log_priority_failed(error: &str) {
+    error!("Warning: Payload priority not applied: {}", error);
+}
+
+pub fn log_output_capture_failed(payload: &str, error: &str) {
+    error!("Warning: Can't log the output of {}: {}", payload, error);
+}
+
+pub fn log_output_log_failed(path: &Path, error: &str) {
+    error!("Warning: Stopped writing {}: {}", path.display(), error);
+}
+
+pub fn log_installing(dir: &Path) {
+    info!("Installing to {}", dir.display());
+}
+
+pub fn log_installed(base: &Path) {
+    audit::record("installed", &[("path", json_string(&base.display().to_string()))]);
+    info!("✅ Installed {}", base.display());
+}
+
+pub fn log_running_installed(base: &Path) {
+    info!("Running installed copy {}", base.display());
+}
+
+pub fn log_install_failed(error: &str) {
+    audit::record_final("refused", &[("reason", json_string("install_failed")), ("error", json_string(error))]);
+    error!("❌ Installation failed, refusing to start: {}", error);
+}
+
+pub fn log_assets_ready(dir: &Path, extracted: bool) {
+    if extracted {
+        info!("Extracted assets to {}", dir.display());
+    } else {
+        info!("Using assets in {}", dir.display());
+    }
+}
+
+pub fn log_assets_failed(error: &str) {
+    audit::record_final("refused", &[("reason", json_string("assets_failed")), ("error", json_string(error))]);
+    error!("❌ Extracting assets failed, refusing to start: {}", error);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_unit_written(name: &str, user: bool) {
+    let scope = if user { " --user" } else { "" };
+    info!("Wrote systemd unit {0}.service; enable it with: systemctl{1} enable --now {0}", name, scope);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_integration_failed(error: &str) {
+    error!("Warning: Installed, but without the unit or shortcut: {}", error);
+}
+
+pub fn log_debugger_detected(target: &str) {
+    error!("❌ Debugger attached to {}, killing payloads", target);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_jail_enabled(flags: u32) {
+    info!("Overload jail enabled (flags: {:#x})", flags);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_jail_failed(error: &str) {
+    error!("Failed to jail overload, refusing to start it: {}", error);
+}
+
+#[cfg(unix)]
+pub fn log_privileges_failed(error: &str) {
+    error!("Failed to drop privileges, refusing to start payload: {}", error);
+}
+
+#[cfg(unix)]
+pub fn log_cwd_failed(error: &str) {
+    error!("Failed to set working directory, refusing to start payload: {}", error);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_seccomp_failed(error: &str) {
+    error!("Failed to install seccomp filter, refusing to start payload: {}", error);
+}
+
+#[cfg(unix)]
+pub fn log_not_root_privileges_kept() {
+    info!("⚠️  Not running as root, payloads keep the stub's user");
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_cgroup_unavailable(error: &str) {
+    info!("⚠️  cgroup limits unavailable ({}), falling back to rlimits without a CPU cap", error);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_limits_failed(error: &str) {
+    error!("Failed to apply resource limits, refusing to start payload: {}", error);
 }
 
 #[cfg(target_os = "macos")]
 pub fn log_overload_terminated_abnormally() {
-    eprintln!("[KillCode] ❌ Overload terminated abnormally");
+    error!("❌ Overload terminated abnormally");
+}
+
+#[cfg(target_os = "windows")]
+pub fn log_payload_cleanup_failed(dir: &std::path::Path, error: &std::io::Error) {
+    error!("Warning: Failed to remove payload directory {}: {}", dir.display(), error);
+}
+
+#[cfg(target_os = "windows")]
+pub fn log_job_create_failed(error: impl std::fmt::Display) {
+    error!("⚠️  Failed to create job object ({}), child processes may outlive the stub", error);
+}
+
+#[cfg(target_os = "windows")]
+pub fn log_job_assign_failed(error: impl std::fmt::Display) {
+    error!("⚠️  Failed to assign process to job object: {}", error);
+}
+
+#[cfg(target_os = "windows")]
+pub fn log_resource_limit_failed(resource: &str, error: impl std::fmt::Display) {
+    error!("⚠️  Failed to apply {} limit, payload runs without it: {}", resource, error);
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_killing_straggler(pid: i32) {
+    info!("Killing leftover child process {}", pid);
 }
 
 #[cfg(unix)]
 pub fn log_execv_failed() {
-    eprintln!("[KillCode] execv failed");
+    error!("execv failed");
+}
+
+#[cfg(target_os = "linux")]
+pub fn log_exec_strategy_failed(strategy: &str, e: &str) {
+    info!("{} unavailable ({}), trying the next way to load payloads", strategy, e);
 }
 
 #[cfg(unix)]
 pub fn log_base_killed_by_signal(signal: impl std::fmt::Display) {
-    eprintln!("[KillCode] Base process killed by signal: {}", signal);
+    info!("Base process killed by signal: {}", signal);
+}
+
+/// Delay between SIGTERM and SIGKILL from the footer (milliseconds), or the
+/// default when unset (unix only)
+#[cfg(unix)]
+pub fn force_kill_delay(configured_ms: u32) -> std::time::Duration {
+    let ms = if configured_ms > 0 { configured_ms } else { FORCE_KILL_DELAY_MS };
+    std::time::Duration::from_millis(ms as u64)
+}
+
+/// Make a freshly forked child the leader of its own process group, so
+/// anything it forks can be signalled with it (unix only)
+///
+/// Called in the child between fork and exec. With `take_terminal` (base, or
+/// an overload the stub waits on), a child started from an interactive shell
+/// also becomes the terminal's foreground group so it can still read stdin
+/// and receives Ctrl+C directly.
+#[cfg(unix)]
+pub unsafe fn enter_own_process_group(take_terminal: bool) {
+    let stub_group = libc::getpgrp();
+    libc::setpgid(0, 0);
+
+    if take_terminal && libc::isatty(libc::STDIN_FILENO) == 1 && libc::tcgetpgrp(libc::STDIN_FILENO) == stub_group {
+        // We are now a background group; tcsetpgrp would stop us with SIGTTOU
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+        libc::signal(libc::SIGTTOU, previous);
+    }
 }
 
-/// Short delay used when force-killing processes (unix only)
+/// Take the terminal back after a payload that held the foreground exited,
+/// so the next payload can claim it (unix only)
+///
+/// Only acts when the foreground group no longer exists, so a stub started
+/// in the background never steals the terminal from the shell.
 #[cfg(unix)]
-pub fn force_kill_delay() -> std::time::Duration {
-    std::time::Duration::from_millis(100)
+pub fn reclaim_terminal() {
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) != 1 {
+            return;
+        }
+        let foreground = libc::tcgetpgrp(libc::STDIN_FILENO);
+        if foreground > 0 && foreground != libc::getpgrp() && libc::kill(-foreground, 0) != 0 {
+            let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            libc::signal(libc::SIGTTOU, previous);
+        }
+    }
+}
+
+/// Parent-side half of [`enter_own_process_group`], closing the race where
+/// the group is signalled before the child has created it (unix only)
+#[cfg(unix)]
+pub fn place_in_own_process_group(child: i32) {
+    unsafe { libc::setpgid(child, child) };
+}
+
+/// Signal a payload and everything in its process group (unix only)
+///
+/// Falls back to the process alone if it never got its own group.
+#[cfg(unix)]
+pub fn signal_tree(pid: i32, signal: nix::sys::signal::Signal) {
+    unsafe {
+        if libc::kill(-pid, signal as libc::c_int) != 0 {
+            libc::kill(pid, signal as libc::c_int);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weaver_abi::kill_policy::OP_OR;
+
+    /// A table with just the overload's slot, initialized at `now`
+    fn health_table(now: i64) -> HealthTable {
+        let table = unsafe { std::mem::zeroed::<HealthTable>() };
+        table.init(&[OVERLOAD_SLOT], now).unwrap();
+        table
+    }
+
+    /// Arming for a base started at `base_started`
+    fn arming(base_started: i64, warmup: i64, after_first_success: bool) -> MonitorArming {
+        MonitorArming { warmup, after_first_success, base_started, armed_at: [None; MAX_HEALTH_SLOTS] }
+    }
+
+    #[test]
+    fn test_fixed_policy_keeps_the_classic_checks() {
+        let table = health_table(current_time() - 100);
+        let overload = table.slot(OVERLOAD_SLOT).unwrap();
+        let mut arming = arming(current_time() - 100, 0, false);
+
+        // Only the unconditional checks, which don't start monitoring by themselves
+        let policy = KillPolicy::fixed(0, 0, 0);
+        assert!(!policy.is_active());
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::Ok);
+
+        let policy = KillPolicy::fixed(60, 3, 0);
+        assert!(policy.is_active());
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::KillBase);
+
+        overload.status().record_success(current_time());
+        for _ in 0..3 {
+            overload.status().record_failure();
+        }
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::KillOverload);
+
+        overload.status().record_success(current_time());
+        overload.status().set_alive(false);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::KillBase);
+    }
+
+    #[test]
+    fn test_policy_rules_combine_fields_and_flags() {
+        let table = health_table(current_time() - 100);
+        let overload = table.slot(OVERLOAD_SLOT).unwrap();
+        let mut arming = arming(current_time() - 100, 0, false);
+
+        // log on flag 0; exit when (since_success > 60 && flag 1) || failures >= 5
+        let policy = KillPolicy::from_ops(&[
+            PolicyOp::new(OP_FLAGS, 0, 1 << 0),
+            PolicyOp::new(OP_RULE, 0, ACTION_LOG),
+            PolicyOp::new(OP_GREATER, FIELD_SINCE_SUCCESS, 60),
+            PolicyOp::new(OP_FLAGS, 0, 1 << 1),
+            PolicyOp::new(OP_AND, 0, 0),
+            PolicyOp::new(OP_AT_LEAST, FIELD_FAILURES, 5),
+            PolicyOp::new(OP_OR, 0, 0),
+            PolicyOp::new(OP_RULE, 0, ACTION_EXIT),
+        ]);
+        assert!(policy.is_active());
+
+        // A stale success alone isn't enough, and a log rule never acts
+        overload.set_flags(1 << 0);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::Ok);
+
+        overload.set_flags(1 << 1);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::Exit);
+
+        overload.clear_flags(1 << 1);
+        for _ in 0..5 {
+            overload.status().record_failure();
+        }
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut arming), HealthCheckResult::Exit);
+    }
+
+    #[test]
+    fn test_slots_arm_after_warmup_or_first_success() {
+        let now = current_time();
+        let table = health_table(now - 100);
+        let overload = table.slot(OVERLOAD_SLOT).unwrap();
+        let policy = KillPolicy::fixed(25, 3, 0);
+        for _ in 0..3 {
+            overload.status().record_failure();
+        }
+
+        // Base started 50s ago: nothing counts during a 60s warmup, but a kill request does
+        let mut warming = arming(now - 50, 60, false);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut warming), HealthCheckResult::Ok);
+        overload.status().request_base_kill();
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut warming), HealthCheckResult::KillBase);
+
+        // After a 20s warmup the grace period counts from when it ended, not from setup
+        table.init(&[OVERLOAD_SLOT], now - 100).unwrap();
+        let mut warmed = arming(now - 50, 20, false);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut warmed), HealthCheckResult::KillBase);
+        assert_eq!(warmed.armed_at[0], Some(now - 30));
+        let mut warmed = arming(now - 50, 25, false);
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut warmed), HealthCheckResult::Ok);
+
+        // Waiting for the first success: failures before it don't count
+        let mut waiting = arming(now - 50, 0, true);
+        for _ in 0..3 {
+            overload.status().record_failure();
+        }
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut waiting), HealthCheckResult::Ok);
+        assert_eq!(waiting.armed_at[0], None);
+        overload.status().record_success(now - 5);
+        for _ in 0..3 {
+            overload.status().record_failure();
+        }
+        assert_eq!(evaluate_health_status(overload, 0, &policy, &mut waiting), HealthCheckResult::KillOverload);
+        assert_eq!(waiting.armed_at[0], Some(now - 5));
+    }
+
+    #[test]
+    fn test_conditions_are_described_with_their_values() {
+        let field = |field| if field == FIELD_FAILURES { 4 } else { 0 };
+        let condition = [
+            PolicyOp::new(OP_AT_LEAST, FIELD_FAILURES, 3),
+            PolicyOp::new(OP_FLAGS, 0, 0x2),
+            PolicyOp::new(OP_NOT, 0, 0),
+            PolicyOp::new(OP_AND, 0, 0),
+        ];
+        assert!(rule_holds(&condition, &field, 0));
+        assert!(!rule_holds(&condition, &field, 0x2));
+        assert_eq!(describe_condition(&condition, &field, 0), "(failures 4 >= 3 && !(flags 0x0 & 0x2))");
+    }
 }
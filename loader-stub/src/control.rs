@@ -0,0 +1,425 @@
+//! Local control socket (`ConfigFooter::control_socket`).
+//!
+//! A Unix socket in the user's runtime directory (mode 0600) or a named pipe
+//! on Windows, named after the product and the stub's PID. The endpoint is
+//! logged and exported to the payloads as `KILLCODE_CONTROL_SOCKET`. Clients
+//! send one JSON object per line and get one line back:
+//!
+//! ```text
+//! {"cmd":"health"}           {"ok":true,"health":{...},"slots":{"overload":{...}}}   ("health":null without monitoring)
+//! {"cmd":"status"}           {"ok":true,"pid":...,"mode":"...","uptime_secs":...,"children":[...]}
+//! {"cmd":"restart_overload"} {"ok":true}                  (scheduled overloads only)
+//! {"cmd":"shutdown"}         {"ok":true}                  then the graceful shutdown path
+//! anything else              {"ok":false,"error":"..."}
+//! ```
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{common, shutdown, ConfigFooter, HealthSlot, HealthTable};
+
+/// Tells payloads where the control socket is
+const ENV_VAR: &str = "KILLCODE_CONTROL_SOCKET";
+/// Longest request line read; anything after it is treated as the next request
+const MAX_REQUEST: u64 = 1024;
+
+static STARTED: OnceLock<(Instant, &'static str)> = OnceLock::new();
+static HEALTH: AtomicUsize = AtomicUsize::new(0);
+static SCHEDULED: AtomicBool = AtomicBool::new(false);
+static RESTART_PENDING: Mutex<bool> = Mutex::new(false);
+static RESTART_REQUESTED: Condvar = Condvar::new();
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Health,
+    Status,
+    RestartOverload,
+    Shutdown,
+}
+
+/// Open the control socket if the footer asks for one
+///
+/// Failing to open it is logged and otherwise ignored; the payloads run either way.
+pub fn start(footer: &ConfigFooter) {
+    if footer.control_socket == 0 {
+        return;
+    }
+    let product = footer.product_name().unwrap_or("killcode");
+    match imp::listen(product, &format!("{}-{}", product, std::process::id())) {
+        Ok(endpoint) => {
+            let _ = STARTED.set((Instant::now(), mode(footer)));
+            std::env::set_var(ENV_VAR, &endpoint);
+            common::log_control_socket_listening(&endpoint);
+        }
+        Err(e) => common::log_control_socket_failed(&e),
+    }
+}
+
+pub fn is_listening() -> bool {
+    STARTED.get().is_some()
+}
+
+/// Answer `health` from this region from now on
+///
+/// # Safety
+/// `health` must stay mapped for the rest of the stub's life.
+pub unsafe fn set_health(health: *mut HealthTable) {
+    HEALTH.store(health as usize, Ordering::Release);
+}
+
+/// Accept `restart_overload`; called by the overload scheduler
+pub fn accept_restarts() {
+    SCHEDULED.store(true, Ordering::Release);
+}
+
+/// Sleep for `timeout` (for good when `None`), cut short by `restart_overload`
+pub fn wait_for_restart(timeout: Option<Duration>) {
+    let pending = RESTART_PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    let mut pending = match timeout {
+        Some(timeout) => {
+            RESTART_REQUESTED
+                .wait_timeout_while(pending, timeout, |pending| !*pending)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0
+        }
+        None => RESTART_REQUESTED.wait_while(pending, |pending| !*pending).unwrap_or_else(PoisonError::into_inner),
+    };
+    *pending = false;
+}
+
+fn mode(footer: &ConfigFooter) -> &'static str {
+    if footer.is_scheduled() {
+        "scheduled"
+    } else if footer.order == weaver_abi::footer::ORDER_BASE_FIRST {
+        "base_first"
+    } else if footer.prerequisite_runs() > 0 {
+        "sync"
+    } else {
+        "async"
+    }
+}
+
+/// Answer requests on one connection until the client hangs up
+fn serve(stream: impl Read + Write) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match (&mut reader).take(MAX_REQUEST).read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let command = parse(line.trim());
+        let response = match &command {
+            Ok(command) => respond(command),
+            Err(e) => Err(e.clone()),
+        };
+        let response = match response {
+            Ok(fields) => format!("{{\"ok\":true{}}}", fields),
+            Err(e) => format!("{{\"ok\":false,\"error\":{}}}", common::json_string(&e)),
+        };
+        let stream = reader.get_mut();
+        if writeln!(stream, "{}", response).and_then(|()| stream.flush()).is_err() {
+            return;
+        }
+
+        // Only once the client has its answer
+        if command == Ok(Command::Shutdown) {
+            shutdown::request();
+        }
+    }
+}
+
+fn parse(request: &str) -> Result<Command, String> {
+    match string_field(request, "cmd") {
+        Some("health") => Ok(Command::Health),
+        Some("status") => Ok(Command::Status),
+        Some("restart_overload") => Ok(Command::RestartOverload),
+        Some("shutdown") => Ok(Command::Shutdown),
+        Some(other) => Err(format!("unknown command: {}", other)),
+        None => Err("expected {\"cmd\":\"...\"}".to_string()),
+    }
+}
+
+/// Fields to add to `{"ok":true}`, each with a leading comma
+fn respond(command: &Command) -> Result<String, String> {
+    common::log_control_command(match command {
+        Command::Health => "health",
+        Command::Status => "status",
+        Command::RestartOverload => "restart_overload",
+        Command::Shutdown => "shutdown",
+    });
+    match command {
+        Command::Health => Ok(format!(",\"health\":{}", health())),
+        Command::Status => Ok(status()),
+        Command::RestartOverload => {
+            if !SCHEDULED.load(Ordering::Acquire) {
+                return Err("restart_overload needs a scheduled overload".to_string());
+            }
+            *RESTART_PENDING.lock().unwrap_or_else(PoisonError::into_inner) = true;
+            RESTART_REQUESTED.notify_all();
+            Ok(String::new())
+        }
+        Command::Shutdown => Ok(String::new()),
+    }
+}
+
+/// The overload's slot as `health`, and every slot by name as `slots`
+fn health() -> String {
+    let region = HEALTH.load(Ordering::Acquire) as *mut u8;
+    // SAFETY: `set_health` callers keep the region mapped
+    let Ok(table) = (unsafe { HealthTable::from_ptr(region) }) else {
+        return "null".to_string();
+    };
+    let slots: Vec<String> = table
+        .slots()
+        .map(|slot| format!("{}:{}", common::json_string(slot.name().as_str()), slot_json(slot)))
+        .collect();
+    match table.slots().next() {
+        Some(first) => format!("{},\"slots\":{{{}}}", slot_json(first), slots.join(",")),
+        None => "null".to_string(),
+    }
+}
+
+fn slot_json(slot: &HealthSlot) -> String {
+    let health = slot.status().snapshot();
+    format!(
+        "{{\"is_alive\":{},\"consecutive_failures\":{},\"last_success\":{},\"last_heartbeat\":{},\"base_pid\":{},\"should_kill_base\":{},\"flags\":{}}}",
+        health.is_alive != 0,
+        health.consecutive_failures,
+        health.last_success,
+        health.last_heartbeat,
+        health.base_pid,
+        health.should_kill_base != 0,
+        slot.flags(),
+    )
+}
+
+fn status() -> String {
+    let (started, mode) = STARTED.get().copied().unwrap_or((Instant::now(), "unknown"));
+    let children: Vec<String> = shutdown::tracked().map(|pid| pid.to_string()).collect();
+    format!(
+        ",\"pid\":{},\"mode\":\"{}\",\"uptime_secs\":{},\"children\":[{}],\"health_monitoring\":{}",
+        std::process::id(),
+        mode,
+        started.elapsed().as_secs(),
+        children.join(","),
+        HEALTH.load(Ordering::Acquire) != 0,
+    )
+}
+
+/// Value of a string field in a flat JSON object; escapes aren't needed for
+/// any command, so they aren't understood
+fn string_field<'a>(object: &'a str, key: &str) -> Option<&'a str> {
+    let body = object.strip_prefix('{')?.strip_suffix('}')?;
+    let after_key = &body[body.find(&format!("\"{}\"", key))? + key.len() + 2..];
+    let value = after_key.trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    Some(&value[..value.find('"')?])
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::thread;
+
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::sys::stat::{umask, Mode};
+    use nix::unistd::Pid;
+
+    use crate::{common, shutdown};
+
+    pub(super) fn listen(product: &str, name: &str) -> Result<String, String> {
+        let dir = common::runtime_dir();
+        remove_stale(&dir, product);
+
+        let path = dir.join(format!(".{}.sock", name));
+        // Created 0600 from the start; nothing else runs yet to race on the umask
+        let previous = umask(Mode::from_bits_truncate(0o177));
+        let listener = UnixListener::bind(&path);
+        umask(previous);
+        let listener = listener.map_err(|e| format!("bind {} failed: {}", path.display(), e))?;
+
+        let endpoint = path.display().to_string();
+        shutdown::on_exit(move || {
+            let _ = fs::remove_file(&path);
+        });
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || super::serve(stream));
+            }
+        });
+        Ok(endpoint)
+    }
+
+    /// Sockets are only removed on signal exits, so clear those left behind
+    /// by copies that are gone
+    fn remove_stale(dir: &Path, product: &str) {
+        let prefix = format!(".{}-", product);
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let pid = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|rest| rest.strip_suffix(".sock"))
+                .and_then(|pid| pid.parse::<i32>().ok());
+            if let Some(pid) = pid {
+                if kill(Pid::from_raw(pid), None) == Err(Errno::ESRCH) {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::io::{self, Read, Write};
+    use std::ptr;
+    use std::thread;
+
+    use windows_sys::Win32::Foundation::{
+        GetLastError, ERROR_BROKEN_PIPE, ERROR_PIPE_CONNECTED, HANDLE, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{
+        FlushFileBuffers, ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_WAIT,
+    };
+
+    /// One connected client; the pipe itself outlives it
+    struct Client(HANDLE);
+
+    impl Read for Client {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut read = 0;
+            if unsafe { ReadFile(self.0, buf.as_mut_ptr(), buf.len() as u32, &mut read, ptr::null_mut()) } == 0 {
+                return match unsafe { GetLastError() } {
+                    ERROR_BROKEN_PIPE => Ok(0),
+                    code => Err(io::Error::from_raw_os_error(code as i32)),
+                };
+            }
+            Ok(read as usize)
+        }
+    }
+
+    impl Write for Client {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut written = 0;
+            if unsafe { WriteFile(self.0, buf.as_ptr(), buf.len() as u32, &mut written, ptr::null_mut()) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(written as usize)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if unsafe { FlushFileBuffers(self.0) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    /// Clients are served one at a time on a single pipe instance; others
+    /// get ERROR_PIPE_BUSY and can WaitNamedPipe for their turn.
+    pub(super) fn listen(_product: &str, name: &str) -> Result<String, String> {
+        let endpoint = format!(r"\\.\pipe\{}", name);
+        let wide: Vec<u16> = endpoint.encode_utf16().chain([0]).collect();
+        // The default DACL only lets the stub's account and administrators
+        // write, and the first instance flag fails if someone squats the name
+        let pipe = unsafe {
+            CreateNamedPipeW(
+                wide.as_ptr(),
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                4096,
+                4096,
+                0,
+                ptr::null(),
+            )
+        };
+        if pipe == INVALID_HANDLE_VALUE {
+            return Err(format!("CreateNamedPipeW failed: {}", io::Error::last_os_error()));
+        }
+
+        let pipe_addr = pipe as usize;
+        thread::spawn(move || {
+            let pipe = pipe_addr as HANDLE;
+            loop {
+                let connected = unsafe { ConnectNamedPipe(pipe, ptr::null_mut()) } != 0
+                    || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+                if connected {
+                    super::serve(Client(pipe));
+                }
+                unsafe { DisconnectNamedPipe(pipe) };
+            }
+        });
+        Ok(endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse(r#"{"cmd":"health"}"#), Ok(Command::Health));
+        assert_eq!(parse(r#"{ "id": 7, "cmd" : "restart_overload" }"#), Ok(Command::RestartOverload));
+        assert_eq!(parse(r#"{"cmd":"reboot"}"#), Err("unknown command: reboot".to_string()));
+        assert!(parse(r#""cmd":"status""#).is_err());
+        assert!(parse("{}").is_err());
+    }
+
+    #[test]
+    fn test_errors_are_valid_json_strings() {
+        assert_eq!(common::json_string("bad \"cmd\"\n"), r#""bad \"cmd\"\u000a""#);
+    }
+
+    #[test]
+    fn test_restart_needs_schedule() {
+        assert!(respond(&Command::RestartOverload).is_err());
+        accept_restarts();
+        assert_eq!(respond(&Command::RestartOverload), Ok(String::new()));
+        // The pending request ends the wait right away
+        wait_for_restart(None);
+    }
+
+    #[test]
+    fn test_serve_answers_each_line() {
+        struct Session(std::io::Cursor<Vec<u8>>, Vec<u8>);
+        impl Read for Session {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.read(buf)
+            }
+        }
+        impl Write for Session {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.1.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut session = Session(std::io::Cursor::new(b"{\"cmd\":\"health\"}\n\nnope\n".to_vec()), Vec::new());
+        serve(&mut session);
+        let output = String::from_utf8(session.1).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines, [r#"{"ok":true,"health":null}"#, r#"{"ok":false,"error":"expected {\"cmd\":\"...\"}"}"#]);
+    }
+}
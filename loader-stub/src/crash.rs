@@ -0,0 +1,489 @@
+//! Crash reports for base (`ConfigFooter::crash_report`, `crash_report_url`).
+//!
+//! A crash is base dying from SIGSEGV, SIGBUS, SIGILL, SIGFPE, SIGABRT,
+//! SIGSYS or SIGTRAP (or dumping core) on Unix, or exiting with an exception
+//! code (0xC0000000 and up) on Windows. The stub's own kills (SIGTERM and
+//! SIGKILL, job termination) are not crashes. The report is one JSON object:
+//! `time`, `event`, `pid`, `host`, `product` (when branded), how base ended
+//! and the last lines of its stderr. It is written to
+//! `<binary>.crash-<time>.json` next to the merged binary and/or POSTed to
+//! the crash report URL, and recorded in the audit log as `base_crashed`.
+//!
+//! With `crash_stderr_lines` set, base's stderr goes through a pipe: the
+//! stub copies everything to its own stderr as it arrives and keeps the last
+//! lines. When base's output is logged (see `output`) that copy hands the
+//! lines over instead. With `CRASH_REPORT_CORE_DUMP`, base's `RLIMIT_CORE` soft limit is
+//! raised to the hard limit so the system writes a core wherever it is
+//! configured to; the report carries the Linux `core_pattern`. Windows has
+//! nothing the stub could turn on for another process without admin rights
+//! (WER `LocalDumps` lives in HKLM), so reports there carry the exception
+//! code only.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::sync::{Condvar, Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use weaver_abi::footer::{CRASH_REPORT_CORE_DUMP, CRASH_REPORT_FILE, MAX_CRASH_STDERR_LINES};
+
+use crate::{audit, common, http, machine, output, ConfigFooter};
+
+/// Longest stderr line kept; the rest of it is dropped
+const MAX_LINE: usize = 1024;
+/// How long a report waits for the rest of base's stderr, and for the POST
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static TAIL: Mutex<Tail> = Mutex::new(Tail::new(0));
+/// Set once the stderr copy has read everything base (and anything that
+/// inherited its stderr) wrote
+static DRAINED: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+struct Config {
+    file: bool,
+    url: Option<String>,
+    product: Option<String>,
+    /// The stderr copy is running
+    capturing: bool,
+}
+
+/// How base ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Exit status; on Windows possibly an exception code
+    Code(i64),
+    /// Killed by a signal (Unix)
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Signal { signal: i32, core_dumped: bool },
+}
+
+impl Exit {
+    #[cfg(unix)]
+    fn from_wait_status(status: &nix::sys::wait::WaitStatus) -> Option<Self> {
+        match *status {
+            nix::sys::wait::WaitStatus::Exited(_, code) => Some(Exit::Code(code as i64)),
+            nix::sys::wait::WaitStatus::Signaled(_, signal, core_dumped) => {
+                Some(Exit::Signal { signal: signal as i32, core_dumped })
+            }
+            _ => None,
+        }
+    }
+
+    fn is_crash(&self) -> bool {
+        match *self {
+            Exit::Code(code) => cfg!(target_os = "windows") && code >= 0xC000_0000,
+            Exit::Signal { signal, core_dumped } => core_dumped || imp::is_crash_signal(signal),
+        }
+    }
+
+    /// Fields describing the exit, as encoded JSON values
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match *self {
+            Exit::Code(code) if self.is_crash() => {
+                vec![("exit_code", code.to_string()), ("exception", common::json_string(&format!("{:#010X}", code)))]
+            }
+            Exit::Code(code) => vec![("exit_code", code.to_string())],
+            Exit::Signal { signal, core_dumped } => vec![
+                ("signal", common::json_string(&imp::signal_name(signal))),
+                ("core_dumped", core_dumped.to_string()),
+            ],
+        }
+    }
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Exit::Code(code) if self.is_crash() => write!(f, "exception {:#010X}", code),
+            Exit::Code(code) => write!(f, "exit code {}", code),
+            Exit::Signal { signal, core_dumped } => {
+                write!(f, "{}{}", imp::signal_name(signal), if core_dumped { " (core dumped)" } else { "" })
+            }
+        }
+    }
+}
+
+/// Start copying base's stderr if the footer asks for crash reports with it
+pub fn start(footer: &ConfigFooter) {
+    if !footer.reports_crashes() {
+        return;
+    }
+    imp::set_core_dump(footer.crash_report & CRASH_REPORT_CORE_DUMP != 0);
+    let mut capturing = false;
+    if footer.crash_stderr_lines > 0 {
+        // A logged stderr is already read by the stub, which passes it on here
+        let reader = if output::is_capturing(true) { Ok(None) } else { imp::pipe().map(Some) };
+        match reader {
+            Ok(reader) => {
+                let lines = footer.crash_stderr_lines.min(MAX_CRASH_STDERR_LINES) as usize;
+                *TAIL.lock().unwrap_or_else(PoisonError::into_inner) = Tail::new(lines);
+                if let Some(reader) = reader {
+                    thread::spawn(move || copy_stderr(reader));
+                }
+                capturing = true;
+            }
+            Err(e) => common::log_crash_capture_failed(&e),
+        }
+    }
+    let _ = CONFIG.set(Config {
+        file: footer.crash_report & CRASH_REPORT_FILE != 0,
+        url: footer.crash_report_url().map(str::to_string),
+        product: footer.product_name().map(str::to_string),
+        capturing,
+    });
+}
+
+/// Report base's exit if it was a crash
+pub fn base_exited(exit: Exit) {
+    let Some(config) = CONFIG.get() else {
+        return;
+    };
+    if !exit.is_crash() {
+        return;
+    }
+
+    let stderr = if config.capturing { wait_for_stderr() } else { Vec::new() };
+    let mut fields = exit.fields();
+    if let Some(pattern) = imp::core_pattern().filter(|_| matches!(exit, Exit::Signal { core_dumped: true, .. })) {
+        fields.push(("core_pattern", common::json_string(&pattern)));
+    }
+    audit::record("base_crashed", &fields);
+
+    let lines: Vec<String> = stderr.iter().map(|line| common::json_string(line)).collect();
+    fields.push(("stderr", format!("[{}]", lines.join(","))));
+    let time = common::current_time();
+    let report = format_report(time, machine::hostname().as_deref(), config.product.as_deref(), &fields);
+    common::log_base_crashed(&exit);
+
+    if config.file {
+        match report_path(time) {
+            Some(path) => match std::fs::write(&path, format!("{}\n", report)) {
+                Ok(()) => common::log_crash_report_written(&path),
+                Err(e) => common::log_crash_report_failed(&format!("writing {} failed: {}", path.display(), e)),
+            },
+            None => common::log_crash_report_failed("the merged binary's path is unknown"),
+        }
+    }
+    if let Some(url) = &config.url {
+        if let Err(e) = http::post_json(url, &report, TIMEOUT) {
+            common::log_crash_report_failed(&e);
+        }
+    }
+}
+
+/// Report base's exit from what `waitpid` returned, if it was a crash
+#[cfg(unix)]
+pub fn base_waited(status: &nix::Result<nix::sys::wait::WaitStatus>) {
+    if let Some(exit) = status.as_ref().ok().and_then(Exit::from_wait_status) {
+        base_exited(exit);
+    }
+}
+
+/// `<binary>.crash-<time>.json` next to the merged binary
+fn report_path(time: i64) -> Option<std::path::PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let mut name = exe.file_name()?.to_os_string();
+    name.push(format!(".crash-{}.json", time));
+    Some(exe.with_file_name(name))
+}
+
+/// Copy base's stderr through to the stub's until every writer is gone
+fn copy_stderr(mut reader: impl Read) {
+    let mut buffer = [0u8; 4096];
+    let mut stderr = io::stderr();
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                let _ = stderr.write_all(&buffer[..n]);
+                stderr_written(&buffer[..n]);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    stderr_closed();
+}
+
+/// Keep what base wrote to stderr for a report
+pub fn stderr_written(bytes: &[u8]) {
+    TAIL.lock().unwrap_or_else(PoisonError::into_inner).push(bytes);
+}
+
+/// Everything base (and anything that inherited its stderr) wrote has been read
+pub fn stderr_closed() {
+    *DRAINED.0.lock().unwrap_or_else(PoisonError::into_inner) = true;
+    DRAINED.1.notify_all();
+}
+
+/// The last lines of base's stderr, once it is all read or after [`TIMEOUT`]
+/// (a process base started may still hold it open)
+fn wait_for_stderr() -> Vec<String> {
+    let drained = DRAINED.0.lock().unwrap_or_else(PoisonError::into_inner);
+    let _ = DRAINED.1.wait_timeout_while(drained, TIMEOUT, |drained| !*drained);
+    TAIL.lock().unwrap_or_else(PoisonError::into_inner).lines()
+}
+
+fn format_report(time: i64, host: Option<&str>, product: Option<&str>, fields: &[(&str, String)]) -> String {
+    let host = host.map_or_else(|| "null".to_string(), common::json_string);
+    let mut report = format!(
+        "{{\"time\":{},\"event\":\"crash\",\"pid\":{},\"host\":{}",
+        time,
+        std::process::id(),
+        host
+    );
+    if let Some(product) = product {
+        report.push_str(&format!(",\"product\":{}", common::json_string(product)));
+    }
+    for (name, value) in fields {
+        report.push_str(&format!(",{}:{}", common::json_string(name), value));
+    }
+    report.push('}');
+    report
+}
+
+/// The last `max` lines written to base's stderr
+struct Tail {
+    max: usize,
+    lines: VecDeque<String>,
+    /// Start of a line that hasn't ended yet
+    partial: Vec<u8>,
+}
+
+impl Tail {
+    const fn new(max: usize) -> Self {
+        Tail { max, lines: VecDeque::new(), partial: Vec::new() }
+    }
+
+    fn push(&mut self, mut bytes: &[u8]) {
+        while let Some(end) = bytes.iter().position(|&b| b == b'\n') {
+            self.extend_partial(&bytes[..end]);
+            let line = String::from_utf8_lossy(&self.partial).trim_end_matches('\r').to_string();
+            self.partial.clear();
+            if self.lines.len() == self.max {
+                self.lines.pop_front();
+            }
+            if self.max > 0 {
+                self.lines.push_back(line);
+            }
+            bytes = &bytes[end + 1..];
+        }
+        self.extend_partial(bytes);
+    }
+
+    fn extend_partial(&mut self, bytes: &[u8]) {
+        let room = MAX_LINE.saturating_sub(self.partial.len());
+        self.partial.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    /// Kept lines, with an unfinished last line as the newest
+    fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.lines.iter().cloned().collect();
+        if !self.partial.is_empty() && self.max > 0 {
+            if lines.len() == self.max {
+                lines.remove(0);
+            }
+            lines.push(String::from_utf8_lossy(&self.partial).into_owned());
+        }
+        lines
+    }
+}
+
+#[cfg(unix)]
+pub use imp::enter;
+
+#[cfg(target_os = "windows")]
+pub use imp::redirect;
+
+/// Close the stub's copy of the stderr pipe once base has its own, so the
+/// copy sees EOF when base exits
+pub fn base_started() {
+    imp::close_write_end();
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::os::unix::io::{IntoRawFd, RawFd};
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+    use nix::fcntl::OFlag;
+    use nix::sys::signal::Signal;
+
+    /// Write end base's stderr goes to, -1 when there is none
+    static WRITE_END: AtomicI32 = AtomicI32::new(-1);
+    static CORE_DUMP: AtomicBool = AtomicBool::new(false);
+
+    /// Close-on-exec both ends; base gets the write end as its fd 2
+    pub(super) fn pipe() -> Result<File, String> {
+        let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC).map_err(|e| format!("pipe failed: {}", e))?;
+        WRITE_END.store(write.into_raw_fd(), Ordering::Relaxed);
+        Ok(File::from(read))
+    }
+
+    pub(super) fn set_core_dump(core_dump: bool) {
+        CORE_DUMP.store(core_dump, Ordering::Relaxed);
+    }
+
+    pub(super) fn close_write_end() {
+        let fd: RawFd = WRITE_END.swap(-1, Ordering::Relaxed);
+        if fd >= 0 {
+            unsafe { libc::close(fd) };
+        }
+    }
+
+    /// In a forked payload: point base's stderr at the pipe and allow it to
+    /// dump core. Only async-signal-safe calls.
+    pub fn enter(is_base: bool) {
+        if !is_base {
+            return;
+        }
+        let fd = WRITE_END.load(Ordering::Relaxed);
+        if fd >= 0 {
+            unsafe { libc::dup2(fd, libc::STDERR_FILENO) };
+        }
+        if CORE_DUMP.load(Ordering::Relaxed) {
+            unsafe {
+                let mut limit: libc::rlimit = std::mem::zeroed();
+                if libc::getrlimit(libc::RLIMIT_CORE, &mut limit) == 0 {
+                    limit.rlim_cur = limit.rlim_max;
+                    libc::setrlimit(libc::RLIMIT_CORE, &limit);
+                }
+            }
+        }
+    }
+
+    pub(super) fn is_crash_signal(signal: i32) -> bool {
+        matches!(
+            Signal::try_from(signal),
+            Ok(Signal::SIGSEGV
+                | Signal::SIGBUS
+                | Signal::SIGILL
+                | Signal::SIGFPE
+                | Signal::SIGABRT
+                | Signal::SIGSYS
+                | Signal::SIGTRAP)
+        )
+    }
+
+    pub(super) fn signal_name(signal: i32) -> String {
+        Signal::try_from(signal).map_or_else(|_| signal.to_string(), |signal| signal.as_str().to_string())
+    }
+
+    /// Where the kernel puts cores; `|…` hands them to a program such as systemd-coredump
+    #[cfg(target_os = "linux")]
+    pub(super) fn core_pattern() -> Option<String> {
+        let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+        Some(pattern.trim().to_string()).filter(|pattern| !pattern.is_empty())
+    }
+
+    /// macOS writes to /cores/core.<pid> when that directory is writable
+    #[cfg(target_os = "macos")]
+    pub(super) fn core_pattern() -> Option<String> {
+        Some("/cores/core.%P".to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::fs::File;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use windows_sys::Win32::Foundation::{CloseHandle, SetHandleInformation, HANDLE, HANDLE_FLAG_INHERIT};
+    use windows_sys::Win32::System::Console::{GetStdHandle, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+    use windows_sys::Win32::System::Threading::{STARTF_USESTDHANDLES, STARTUPINFOA};
+
+    /// Write end base's stderr goes to, 0 when there is none
+    static WRITE_END: AtomicUsize = AtomicUsize::new(0);
+
+    /// Neither end is inheritable yet, so an overload started before base
+    /// never holds the pipe open
+    pub(super) fn pipe() -> Result<File, String> {
+        let (mut read, mut write): (HANDLE, HANDLE) = (ptr::null_mut(), ptr::null_mut());
+        if unsafe { CreatePipe(&mut read, &mut write, ptr::null(), 0) } == 0 {
+            return Err(format!("CreatePipe failed: {}", std::io::Error::last_os_error()));
+        }
+        WRITE_END.store(write as usize, Ordering::Relaxed);
+        Ok(unsafe { File::from_raw_handle(read as _) })
+    }
+
+    pub(super) fn set_core_dump(_core_dump: bool) {}
+
+    pub(super) fn close_write_end() {
+        let handle = WRITE_END.swap(0, Ordering::Relaxed);
+        if handle != 0 {
+            unsafe { CloseHandle(handle as HANDLE) };
+        }
+    }
+
+    /// Hand base the pipe as its stderr; returns whether handles have to be
+    /// inherited for that
+    pub fn redirect(is_base: bool, startup: &mut STARTUPINFOA) -> bool {
+        let handle = WRITE_END.load(Ordering::Relaxed) as HANDLE;
+        if !is_base || handle.is_null() {
+            return false;
+        }
+        unsafe {
+            SetHandleInformation(handle, HANDLE_FLAG_INHERIT, HANDLE_FLAG_INHERIT);
+            startup.dwFlags |= STARTF_USESTDHANDLES;
+            startup.hStdInput = GetStdHandle(STD_INPUT_HANDLE);
+            startup.hStdOutput = GetStdHandle(STD_OUTPUT_HANDLE);
+            startup.hStdError = handle;
+        }
+        true
+    }
+
+    pub(super) fn is_crash_signal(_signal: i32) -> bool {
+        false
+    }
+
+    pub(super) fn signal_name(signal: i32) -> String {
+        signal.to_string()
+    }
+
+    pub(super) fn core_pattern() -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_keeps_the_last_lines() {
+        let mut tail = Tail::new(2);
+        tail.push(b"one\ntwo\r\nthr");
+        assert_eq!(tail.lines(), vec!["two", "thr"]);
+        tail.push(b"ee\nfour\n");
+        assert_eq!(tail.lines(), vec!["three", "four"]);
+
+        let mut tail = Tail::new(1);
+        tail.push(&[b'x'; MAX_LINE + 10]);
+        tail.push(b"\n");
+        assert_eq!(tail.lines()[0].len(), MAX_LINE);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_only_crash_signals_are_crashes() {
+        assert!(Exit::Signal { signal: libc::SIGSEGV, core_dumped: false }.is_crash());
+        assert!(Exit::Signal { signal: libc::SIGTERM, core_dumped: true }.is_crash());
+        assert!(!Exit::Signal { signal: libc::SIGKILL, core_dumped: false }.is_crash());
+        assert!(!Exit::Code(139).is_crash());
+
+        let exit = Exit::Signal { signal: libc::SIGABRT, core_dumped: true };
+        let report = format_report(1700000000, Some("host-1"), None, &exit.fields());
+        assert_eq!(
+            report,
+            format!(
+                r#"{{"time":1700000000,"event":"crash","pid":{},"host":"host-1","signal":"SIGABRT","core_dumped":true}}"#,
+                std::process::id()
+            )
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! File descriptors payloads are exec'd from (Linux only).
+//!
+//! memfd_create is the first choice, but old kernels don't have it and
+//! hardened containers often filter it. `EXEC_AUTO` then falls back to an
+//! unnamed `O_TMPFILE` file and finally to a file in a private temp
+//! directory that is unlinked as soon as it's open. Every strategy hands back
+//! a read-only descriptor for `execveat(fd, "", AT_EMPTY_PATH)`, so payloads
+//! are exec'd the same way and no path to them is left behind.
+
+use std::ffi::CStr;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::path::PathBuf;
+
+use nix::sys::memfd::{memfd_create, MFdFlags};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::unistd::mkdtemp;
+use weaver_abi::footer::{EXEC_MEMFD, EXEC_TEMP_FILE, EXEC_TMPFILE};
+
+use crate::common;
+
+/// Write `data` somewhere executable and return a descriptor to exec it from
+///
+/// `strategy` is the footer's `EXEC_*` value; anything but a forced strategy
+/// tries them all in order.
+pub fn load(data: &[u8], name: &CStr, strategy: u8) -> Result<OwnedFd, String> {
+    match strategy {
+        EXEC_MEMFD => memfd(data, name),
+        EXEC_TMPFILE => tmpfile(data),
+        EXEC_TEMP_FILE => temp_file(data, name),
+        _ => memfd(data, name)
+            .or_else(|e| {
+                common::log_exec_strategy_failed("memfd_create", &e);
+                tmpfile(data)
+            })
+            .or_else(|e| {
+                common::log_exec_strategy_failed("O_TMPFILE", &e);
+                temp_file(data, name)
+            }),
+    }
+}
+
+fn memfd(data: &[u8], name: &CStr) -> Result<OwnedFd, String> {
+    let fd = memfd_create(name, MFdFlags::MFD_CLOEXEC).map_err(|e| format!("memfd_create failed: {}", e))?;
+    let mut file = File::from(fd);
+    file.write_all(data).map_err(|e| format!("Failed to write binary data: {}", e))?;
+    // memfds don't count as open for writing, so this one can be exec'd as is
+    Ok(file.into())
+}
+
+fn tmpfile(data: &[u8]) -> Result<OwnedFd, String> {
+    let mut last_error = "no executable temp directory".to_string();
+    for dir in exec_dirs() {
+        let opened = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .mode(0o700)
+            .custom_flags(libc::O_TMPFILE)
+            .open(&dir);
+        let mut file = match opened {
+            Ok(file) => file,
+            Err(e) => {
+                last_error = format!("O_TMPFILE in {} failed: {}", dir.display(), e);
+                continue;
+            }
+        };
+        file.write_all(data).map_err(|e| format!("Failed to write binary data: {}", e))?;
+        // exec fails with ETXTBSY while a writable descriptor is open, and an
+        // unnamed file can only be reopened through /proc
+        let reader = File::open(format!("/proc/self/fd/{}", file.as_raw_fd()))
+            .map_err(|e| format!("Failed to reopen O_TMPFILE read-only: {}", e))?;
+        return executable(reader);
+    }
+    Err(last_error)
+}
+
+fn temp_file(data: &[u8], name: &CStr) -> Result<OwnedFd, String> {
+    let mut last_error = "no executable temp directory".to_string();
+    for dir in exec_dirs() {
+        let private = match mkdtemp(&dir.join("XXXXXXXXXX")) {
+            Ok(private) => private,
+            Err(e) => {
+                last_error = format!("mkdtemp in {} failed: {}", dir.display(), e);
+                continue;
+            }
+        };
+        let path = private.join(name.to_string_lossy().as_ref());
+        let reader = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o700)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path)
+            .and_then(|mut file| file.write_all(data))
+            .and_then(|()| File::open(&path));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_dir(&private);
+        return executable(reader.map_err(|e| format!("Failed to write {}: {}", path.display(), e))?);
+    }
+    Err(last_error)
+}
+
+/// Let the payloads' account exec the file, which only the stub can reach
+fn executable(file: File) -> Result<OwnedFd, String> {
+    file.set_permissions(Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make payload executable: {}", e))?;
+    Ok(file.into())
+}
+
+/// Writable temp directories not mounted noexec, tmpfs first
+fn exec_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("/dev/shm"), std::env::temp_dir(), PathBuf::from("/var/tmp")];
+    dirs.dedup();
+    dirs.retain(|dir| statvfs(dir).is_ok_and(|stat| !stat.flags().contains(FsFlags::ST_NOEXEC)));
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom};
+
+    #[test]
+    fn test_every_strategy_yields_readonly_copy() {
+        let data = b"\x7fELF not really";
+        for strategy in [EXEC_MEMFD, EXEC_TMPFILE, EXEC_TEMP_FILE] {
+            let fd = match load(data, c"payload", strategy) {
+                Ok(fd) => fd,
+                // Sandboxed runners may lack /dev/shm, /proc or O_TMPFILE support
+                Err(e) => {
+                    eprintln!("strategy {} unavailable: {}", strategy, e);
+                    continue;
+                }
+            };
+            let mut file = File::from(fd);
+            file.seek(SeekFrom::Start(0)).unwrap();
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, data);
+            if strategy != EXEC_MEMFD {
+                assert!(file.write_all(b"x").is_err());
+                assert_eq!(file.metadata().unwrap().permissions().mode() & 0o777, 0o755);
+            }
+        }
+    }
+}
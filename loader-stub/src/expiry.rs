@@ -0,0 +1,72 @@
+//! Expiry date (`ConfigFooter::expires_at`).
+//!
+//! An expired binary refuses to start, and one that expires while running
+//! shuts its payloads down through the graceful shutdown path. With
+//! `expiry_clock_checks` the system clock isn't taken at its word: at
+//! startup the newest timestamp among files the OS keeps touching is a
+//! lower bound for the current time, and from then on the monotonic clock
+//! keeps time too, so setting the clock back while running gains nothing.
+//! Both are heuristics against casual clock changes, not guarantees.
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::{common, shutdown, ConfigFooter};
+
+/// Longest sleep between checks, so a suspended machine is noticed soon after it resumes
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Exit if the binary has expired, and shut down when it does later
+pub fn enforce(footer: &ConfigFooter) {
+    if footer.expires_at == 0 {
+        return;
+    }
+    let expires_at = footer.expires_at;
+    let clock_checks = footer.expiry_clock_checks != 0;
+
+    let started = Instant::now();
+    let start_time = if clock_checks {
+        common::current_time().max(newest_file_time())
+    } else {
+        common::current_time()
+    };
+    if start_time >= expires_at {
+        common::log_expired(expires_at);
+        std::process::exit(1);
+    }
+
+    thread::spawn(move || loop {
+        let mut now = common::current_time();
+        if clock_checks {
+            now = now.max(start_time + started.elapsed().as_secs() as i64);
+        }
+        if now >= expires_at {
+            common::log_expired_while_running(expires_at);
+            shutdown::request();
+            return;
+        }
+        thread::sleep(Duration::from_secs((expires_at - now) as u64).min(MAX_CHECK_INTERVAL));
+    });
+}
+
+/// Newest modification time, in Unix seconds, among the merged binary and
+/// places the OS updates on its own
+fn newest_file_time() -> i64 {
+    let mut paths = vec![std::env::temp_dir()];
+    paths.extend(std::env::current_exe().ok());
+    #[cfg(unix)]
+    paths.extend(["/var/log", "/var/tmp", "/var/lib/systemd/timesync/clock"].map(PathBuf::from));
+    #[cfg(target_os = "windows")]
+    if let Some(root) = std::env::var_os("SystemRoot").map(PathBuf::from) {
+        paths.extend(["Temp", "Prefetch", r"System32\winevt\Logs"].map(|dir| root.join(dir)));
+    }
+
+    paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .filter_map(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_secs() as i64)
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,164 @@
+//! Opt-in anti-tamper and anti-debug checks (`HARDEN_*` footer flags).
+//!
+//! Checks run once before any payload starts, where a failure means the stub
+//! refuses to run at all, and then in a watchdog thread that kills base and
+//! the overload when a debugger attaches to the stub or either of them later.
+//! They raise the bar for casual patching and debugging; they don't stop
+//! someone determined to take the binary apart.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::thread;
+use std::time::Duration;
+
+use weaver_abi::footer::{HARDEN_ANTI_DEBUG, HARDEN_CHECKSUM};
+use weaver_abi::ImageChecksum;
+
+use crate::{common, shutdown, ConfigFooter};
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Checks before anything starts; `Err` describes what was detected
+pub fn check_startup(
+    self_file: &mut File,
+    footer_bytes: &[u8; ConfigFooter::SIZE],
+    footer: &ConfigFooter,
+) -> Result<(), String> {
+    if footer.hardening & HARDEN_CHECKSUM != 0 {
+        verify_image(self_file, footer_bytes, footer.image_checksum)?;
+    }
+    if footer.hardening & HARDEN_ANTI_DEBUG != 0 && imp::debugger_attached(None) {
+        return Err("a debugger is attached to the stub".to_string());
+    }
+    Ok(())
+}
+
+/// Kill the payloads and exit if a debugger shows up while they run
+pub fn start_watchdog(footer: &ConfigFooter) {
+    if footer.hardening & HARDEN_ANTI_DEBUG == 0 {
+        return;
+    }
+    thread::spawn(|| loop {
+        thread::sleep(WATCHDOG_INTERVAL);
+        let traced = if imp::debugger_attached(None) {
+            Some("the stub".to_string())
+        } else {
+            shutdown::tracked()
+                .find(|&pid| imp::debugger_attached(Some(pid)))
+                .map(|pid| format!("process {}", pid))
+        };
+        if let Some(target) = traced {
+            common::log_debugger_detected(&target);
+            shutdown::kill_all_and_exit(1);
+        }
+    });
+}
+
+fn verify_image(self_file: &mut File, footer_bytes: &[u8; ConfigFooter::SIZE], expected: u64) -> Result<(), String> {
+    let len = self_file.metadata().map_err(|e| e.to_string())?.len();
+    let mut remaining = len.saturating_sub(ConfigFooter::SIZE as u64);
+    self_file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+
+    let mut checksum = ImageChecksum::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    while remaining > 0 {
+        let chunk = remaining.min(buffer.len() as u64) as usize;
+        self_file.read_exact(&mut buffer[..chunk]).map_err(|e| e.to_string())?;
+        checksum.update(&buffer[..chunk]);
+        remaining -= chunk as u64;
+    }
+    checksum.update_footer(footer_bytes);
+
+    if checksum.finish() != expected {
+        return Err("the image checksum doesn't match".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    /// Non-zero `TracerPid` in /proc/<pid>/status, the stub itself for `None`
+    pub(super) fn debugger_attached(pid: Option<i32>) -> bool {
+        let path = match pid {
+            Some(pid) => format!("/proc/{}/status", pid),
+            None => "/proc/self/status".to_string(),
+        };
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("TracerPid:"))
+                    .and_then(|tracer| tracer.trim().parse::<i32>().ok())
+            })
+            .is_some_and(|tracer| tracer != 0)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::{mem, ptr};
+
+    /// `P_TRACED` in sys/proc.h
+    const P_TRACED: libc::c_int = 0x0000_0800;
+
+    /// `P_TRACED` in the process's kinfo_proc, the stub itself for `None`
+    pub(super) fn debugger_attached(pid: Option<i32>) -> bool {
+        let pid = pid.unwrap_or_else(|| std::process::id() as i32);
+        let mut mib = [libc::CTL_KERN, libc::KERN_PROC, libc::KERN_PROC_PID, pid];
+        unsafe {
+            let mut info: libc::kinfo_proc = mem::zeroed();
+            let mut size = mem::size_of::<libc::kinfo_proc>();
+            let found = libc::sysctl(
+                mib.as_mut_ptr(),
+                mib.len() as libc::c_uint,
+                &mut info as *mut libc::kinfo_proc as *mut libc::c_void,
+                &mut size,
+                ptr::null_mut(),
+                0,
+            ) == 0;
+            found && size > 0 && info.kp_proc.p_flag & P_TRACED != 0
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Diagnostics::Debug::{CheckRemoteDebuggerPresent, IsDebuggerPresent};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    /// IsDebuggerPresent/CheckRemoteDebuggerPresent, the stub itself for `None`
+    pub(super) fn debugger_attached(pid: Option<i32>) -> bool {
+        unsafe {
+            let mut present = 0;
+            match pid {
+                None => {
+                    IsDebuggerPresent() != 0
+                        || (CheckRemoteDebuggerPresent(GetCurrentProcess(), &mut present) != 0 && present != 0)
+                }
+                Some(pid) => {
+                    let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid as u32);
+                    if handle.is_null() {
+                        return false;
+                    }
+                    let attached = CheckRemoteDebuggerPresent(handle, &mut present) != 0 && present != 0;
+                    CloseHandle(handle);
+                    attached
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_untraced_process_is_not_flagged() {
+        // Holds unless the test runner itself is being debugged
+        assert!(!imp::debugger_attached(Some(std::process::id() as i32)));
+        assert!(!imp::debugger_attached(Some(i32::MAX)));
+    }
+}
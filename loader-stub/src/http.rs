@@ -0,0 +1,84 @@
+//! Minimal plain-HTTP client for the revocation check and audit records.
+//!
+//! Only `http://host[:port]/path` URLs: the stub carries no TLS stack, so
+//! whatever it fetches must be trusted some other way (see `revocation`).
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Responses the stub cares about are short; nothing past this is read
+const MAX_RESPONSE: u64 = 16 * 1024;
+
+/// GET `url` and return the body of a 2xx response
+pub fn get(url: &str, timeout: Duration) -> Result<String, String> {
+    request("GET", url, None, timeout)
+}
+
+/// POST a JSON body to `url`, succeeding on any 2xx response
+pub fn post_json(url: &str, body: &str, timeout: Duration) -> Result<(), String> {
+    request("POST", url, Some(body), timeout).map(drop)
+}
+
+fn request(method: &str, url: &str, body: Option<&str>, timeout: Duration) -> Result<String, String> {
+    let rest = url.strip_prefix("http://").ok_or("only http:// URLs are supported")?;
+    let (authority, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+    let has_port = authority.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    let address = if has_port { authority.to_string() } else { format!("{}:80", authority) };
+
+    let target = address
+        .to_socket_addrs()
+        .map_err(|e| format!("resolving {} failed: {}", authority, e))?
+        .next()
+        .ok_or_else(|| format!("{} has no address", authority))?;
+    let mut stream =
+        TcpStream::connect_timeout(&target, timeout).map_err(|e| format!("connecting to {} failed: {}", authority, e))?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+
+    // HTTP/1.0 so the response body is never chunked
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n", method, path, authority);
+    if let Some(body) = body {
+        request.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n", body.len()));
+    }
+    request.push_str("\r\n");
+    request.push_str(body.unwrap_or(""));
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("request to {} failed: {}", authority, e))?;
+
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE)
+        .read_to_end(&mut response)
+        .map_err(|e| format!("reading from {} failed: {}", authority, e))?;
+    parse_response(&response)
+}
+
+fn parse_response(response: &[u8]) -> Result<String, String> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response.split_once("\r\n\r\n").ok_or("malformed HTTP response")?;
+    let status = head.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("");
+    if !status.starts_with('2') {
+        return Err(format!("HTTP status {:?}", status));
+    }
+    Ok(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok").unwrap(), "ok");
+        assert_eq!(parse_response(b"HTTP/1.0 204 No Content\r\n\r\n").unwrap(), "");
+        assert!(parse_response(b"HTTP/1.1 404 Not Found\r\n\r\nrevoke 00").is_err());
+        assert!(parse_response(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_rejects_other_schemes() {
+        assert!(get("https://example.com/", Duration::from_secs(1)).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Self-extracting installer mode (`ConfigFooter::install_dir`).
+//!
+//! On first run base and the bundled data files are extracted to the install
+//! dir, next to a marker holding `ConfigFooter::install_id`; on Linux a
+//! systemd unit and a desktop entry can be written too. Later runs find the
+//! marker and run the installed base instead of the embedded one, so only a
+//! merged binary with different payloads extracts again. The overload still
+//! runs from inside the merged binary every time.
+
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use weaver_abi::{bundle, pack};
+
+use crate::{common, ConfigFooter};
+
+/// Records which build the install dir holds
+const MARKER_FILE: &str = ".weaver-install";
+
+/// Base's bytes, installed first if needed, or `None` when the merged binary
+/// isn't an installer and base runs from the embedded copy
+pub fn prepare(footer: &ConfigFooter, self_file: &mut File) -> Result<Option<Vec<u8>>, String> {
+    let Some(dir) = footer.install_dir().map(PathBuf::from) else {
+        return Ok(None);
+    };
+    let name = footer.install_name().ok_or("installer has no install name")?;
+    let base_path = dir.join(if cfg!(windows) { format!("{}.exe", name) } else { name.to_string() });
+    let marker = dir.join(MARKER_FILE);
+    let install_id = format!("{:016x}", footer.install_id);
+
+    let installed = fs::read_to_string(&marker).is_ok_and(|id| id.trim() == install_id);
+    if installed {
+        if let Ok(base) = fs::read(&base_path) {
+            common::log_running_installed(&base_path);
+            return Ok(Some(base));
+        }
+    }
+
+    common::log_installing(&dir);
+    let base = read_payload(self_file, footer, true)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("creating {} failed: {}", dir.display(), e))?;
+    write_file(&base_path, &base, bundle::MODE_EXECUTABLE)?;
+    if footer.install_files_size > 0 {
+        let files = read_section(self_file, footer.install_files_offset, footer.install_files_size)?;
+        for file in bundle::files(&files) {
+            let file = file.map_err(|()| "install bundle is corrupt".to_string())?;
+            write_file(&dir.join(file.path), file.data, file.mode)?;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    integrate(footer, &dir, name);
+
+    // Written last, so an interrupted install is redone on the next run
+    fs::write(&marker, &install_id).map_err(|e| format!("writing {} failed: {}", marker.display(), e))?;
+    common::log_installed(&base_path);
+    Ok(Some(base))
+}
+
+pub fn read_section(file: &mut File, offset: u64, size: u64) -> Result<Vec<u8>, String> {
+    let mut data = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut data))
+        .map_err(|e| format!("reading the embedded payload failed: {}", e))?;
+    Ok(data)
+}
+
+/// Base (`true`) or the overload (`false`) as uploaded, unpacked if the merge packed it
+pub fn read_payload(file: &mut File, footer: &ConfigFooter, is_base: bool) -> Result<Vec<u8>, String> {
+    let (offset, size) = if is_base { (footer.base_offset, footer.base_size) } else { (footer.overload_offset, footer.overload_size) };
+    let data = read_section(file, offset, size)?;
+    let Some(unpacked_size) = footer.unpacked_size(is_base) else {
+        return Ok(data);
+    };
+    let mut unpacked = vec![0u8; unpacked_size as usize];
+    pack::unpack(&data, &mut unpacked).map_err(|e| format!("unpacking the embedded payload failed: {:?}", e))?;
+    Ok(unpacked)
+}
+
+/// Replace `path` with `data` through a rename, so a running copy of it is
+/// left alone on Unix; also used to extract assets
+pub fn write_file(path: &Path, data: &[u8], mode: u32) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("creating {} failed: {}", parent.display(), e))?;
+    }
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".weaver-new");
+    let staging = PathBuf::from(staging);
+    fs::write(&staging, data).map_err(|e| format!("writing {} failed: {}", staging.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staging, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("setting the mode of {} failed: {}", staging.display(), e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    fs::rename(&staging, path).map_err(|e| {
+        let _ = fs::remove_file(&staging);
+        format!("replacing {} failed: {}", path.display(), e)
+    })
+}
+
+/// Write the systemd unit and desktop entry the footer asks for
+///
+/// Both run the merged binary from where this first run found it. They go
+/// system-wide as root and into the user's own directories otherwise; the
+/// unit is not enabled. Failures only warn, base is installed either way.
+#[cfg(target_os = "linux")]
+fn integrate(footer: &ConfigFooter, dir: &Path, name: &str) {
+    use weaver_abi::footer::{INSTALL_SHORTCUT, INSTALL_SYSTEMD_UNIT};
+
+    let wanted = footer.install_flags & (INSTALL_SYSTEMD_UNIT | INSTALL_SHORTCUT);
+    if wanted == 0 {
+        return;
+    }
+    let Some(exe) = std::env::current_exe().ok().and_then(|exe| exe.canonicalize().ok()) else {
+        common::log_integration_failed("the merged binary's path is unknown");
+        return;
+    };
+    let root = nix::unistd::geteuid().is_root();
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let title = footer.product_name().unwrap_or(name);
+
+    if wanted & INSTALL_SYSTEMD_UNIT != 0 {
+        let (unit_dir, target) = if root {
+            (Some(PathBuf::from("/etc/systemd/system")), "multi-user.target")
+        } else {
+            (home.as_ref().map(|home| home.join(".config/systemd/user")), "default.target")
+        };
+        let unit = format!(
+            "[Unit]\nDescription={}\nAfter=network-online.target\n\n[Service]\nExecStart=\"{}\"\nWorkingDirectory={}\nRestart=on-failure\n\n[Install]\nWantedBy={}\n",
+            title,
+            exe.display(),
+            dir.display(),
+            target
+        );
+        match unit_dir {
+            Some(unit_dir) => match write_file(&unit_dir.join(format!("{}.service", name)), unit.as_bytes(), bundle::MODE_FILE) {
+                Ok(()) => common::log_unit_written(name, !root),
+                Err(e) => common::log_integration_failed(&e),
+            },
+            None => common::log_integration_failed("no HOME to put the user unit in"),
+        }
+    }
+
+    if wanted & INSTALL_SHORTCUT != 0 {
+        let applications = if root {
+            Some(PathBuf::from("/usr/share/applications"))
+        } else {
+            home.as_ref().map(|home| home.join(".local/share/applications"))
+        };
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec=\"{}\"\nPath={}\nTerminal=false\n",
+            title,
+            exe.display(),
+            dir.display()
+        );
+        match applications {
+            Some(applications) => {
+                if let Err(e) = write_file(&applications.join(format!("{}.desktop", name)), entry.as_bytes(), bundle::MODE_FILE) {
+                    common::log_integration_failed(&e);
+                }
+            }
+            None => common::log_integration_failed("no HOME to put the desktop entry in"),
+        }
+    }
+}
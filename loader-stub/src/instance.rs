@@ -0,0 +1,156 @@
+//! Single-instance enforcement (`SINGLE_INSTANCE_*` footer values).
+//!
+//! Copies of the same merged binary share `instance_id`, from which the lock
+//! name is derived. On Unix the lock is an flock on a file in the user's
+//! runtime directory, so the limit is per user; on Windows it's a named
+//! mutex in the session's `Local\` namespace. Either way the OS drops it when
+//! the stub exits, however it exits.
+
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use weaver_abi::footer::{SINGLE_INSTANCE_OFF, SINGLE_INSTANCE_SIGNAL};
+
+use crate::{common, ConfigFooter};
+
+/// Base of this instance, which activations from later copies are forwarded to
+#[cfg(unix)]
+static BASE_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Another copy already holds the lock
+struct AlreadyRunning {
+    /// The running stub, when it could be identified
+    pid: Option<i32>,
+}
+
+/// Take the instance lock for the rest of the stub's life, or exit when
+/// another copy holds it
+pub fn enforce(footer: &ConfigFooter) {
+    if footer.single_instance == SINGLE_INSTANCE_OFF {
+        return;
+    }
+    let signal = footer.single_instance == SINGLE_INSTANCE_SIGNAL;
+    match imp::acquire(&lock_name(footer)) {
+        Ok(()) => {
+            if signal {
+                imp::forward_activations();
+            }
+        }
+        Err(running) => {
+            if let (true, Some(pid)) = (signal, running.pid) {
+                if imp::activate(pid) {
+                    common::log_activated_running_instance(pid);
+                    std::process::exit(0);
+                }
+            }
+            common::log_already_running(running.pid);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Remember base's PID so activations can be forwarded to it
+#[cfg(unix)]
+pub fn set_base_pid(pid: i32) {
+    BASE_PID.store(pid, Ordering::Relaxed);
+}
+
+fn lock_name(footer: &ConfigFooter) -> String {
+    format!("{}-{:016x}", footer.product_name().unwrap_or("killcode"), footer.instance_id)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::{File, OpenOptions};
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+    use super::{AlreadyRunning, BASE_PID};
+
+    static ACTIVATED: AtomicBool = AtomicBool::new(false);
+
+    pub(super) fn acquire(name: &str) -> Result<(), AlreadyRunning> {
+        let path = crate::common::runtime_dir().join(format!(".{}.lock", name));
+        let opened = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&path);
+        // Without a lock file there is nothing to enforce; don't refuse to run
+        let Ok(mut file) = opened else {
+            return Ok(());
+        };
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+            return Err(AlreadyRunning { pid: pid.trim().parse().ok() });
+        }
+
+        let _ = file.set_len(0);
+        let _ = file.seek(SeekFrom::Start(0));
+        let _ = write!(file, "{}", std::process::id());
+        // Held (close-on-exec, so not by the payloads) until the stub exits
+        std::mem::forget::<File>(file);
+        Ok(())
+    }
+
+    /// Tell the running copy that another launch happened
+    pub(super) fn activate(pid: i32) -> bool {
+        unsafe { libc::kill(pid, libc::SIGUSR1) == 0 }
+    }
+
+    // Async-signal-safe: only records the activation for the watcher thread
+    extern "C" fn record_activation(_: libc::c_int) {
+        ACTIVATED.store(true, Ordering::Relaxed);
+    }
+
+    /// Pass SIGUSR1 from later launches on to base
+    pub(super) fn forward_activations() {
+        let action = SigAction::new(SigHandler::Handler(record_activation), SaFlags::SA_RESTART, SigSet::empty());
+        let _ = unsafe { sigaction(Signal::SIGUSR1, &action) };
+        thread::spawn(|| loop {
+            thread::sleep(Duration::from_millis(100));
+            if ACTIVATED.swap(false, Ordering::Relaxed) {
+                let base = BASE_PID.load(Ordering::Relaxed);
+                if base > 0 {
+                    crate::common::log_forwarding_activation(base);
+                    unsafe { libc::kill(base, libc::SIGUSR1) };
+                }
+            }
+        });
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use windows_sys::Win32::Foundation::{GetLastError, ERROR_ALREADY_EXISTS};
+    use windows_sys::Win32::System::Threading::CreateMutexW;
+
+    use super::AlreadyRunning;
+
+    pub(super) fn acquire(name: &str) -> Result<(), AlreadyRunning> {
+        let name: Vec<u16> = format!("Local\\{}", name).encode_utf16().chain([0]).collect();
+        // The handle is never closed; Windows releases it when the stub exits
+        let handle = unsafe { CreateMutexW(std::ptr::null(), 0, name.as_ptr()) };
+        if !handle.is_null() && unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            return Err(AlreadyRunning { pid: None });
+        }
+        Ok(())
+    }
+
+    /// Windows has no signal to send, so `SINGLE_INSTANCE_SIGNAL` just exits
+    pub(super) fn activate(_pid: i32) -> bool {
+        false
+    }
+
+    pub(super) fn forward_activations() {}
+}
@@ -0,0 +1,138 @@
+//! Two-way channel between base and the overload (`ConfigFooter::ipc_channel`).
+//!
+//! The stub connects a pair of endpoints before either payload starts: a
+//! `socketpair(AF_UNIX, SOCK_STREAM)` on Unix, a duplex named pipe and a
+//! client handle opened on it on Windows. Each payload inherits one end and
+//! finds it in `KILLCODE_IPC_FD`, a file descriptor on Unix and a `HANDLE`
+//! value on Windows. What goes over it is up to the payloads.
+//!
+//! The stub holds both ends until it exits, so a scheduled overload that runs
+//! again gets the same channel, and neither side reads EOF when the other
+//! exits. On Windows both payloads inherit both ends.
+
+use std::sync::OnceLock;
+
+use crate::{common, ConfigFooter};
+
+/// Tells each payload which end is its own
+const ENV_VAR: &str = "KILLCODE_IPC_FD";
+
+/// Base's end, then the overload's
+static ENDS: OnceLock<(imp::End, imp::End)> = OnceLock::new();
+
+/// Connect the channel if the footer asks for one
+///
+/// Failing to is logged and otherwise ignored; the payloads run either way.
+pub fn start(footer: &ConfigFooter) {
+    if footer.ipc_channel == 0 {
+        return;
+    }
+    let product = footer.product_name().unwrap_or("killcode");
+    match imp::pair(&format!("{}-ipc-{}", product, std::process::id())) {
+        Ok(ends) => {
+            let _ = ENDS.set(ends);
+            common::log_ipc_channel_ready();
+        }
+        Err(e) => common::log_ipc_channel_failed(&e),
+    }
+}
+
+/// Whether payloads have to inherit handles (Windows)
+#[cfg(target_os = "windows")]
+pub fn is_enabled() -> bool {
+    ENDS.get().is_some()
+}
+
+/// Point `KILLCODE_IPC_FD` at the end of the payload started next
+pub fn export(is_base: bool) {
+    if let Some(&(base, overload)) = ENDS.get() {
+        std::env::set_var(ENV_VAR, (if is_base { base } else { overload }).to_string());
+    }
+}
+
+/// In a forked payload, close the other payload's end
+#[cfg(unix)]
+pub fn enter(is_base: bool) {
+    if let Some(&(base, overload)) = ENDS.get() {
+        unsafe { libc::close(if is_base { overload } else { base }) };
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io;
+
+    pub(super) type End = std::os::unix::io::RawFd;
+
+    /// Without close-on-exec, so forked payloads keep their end
+    pub(super) fn pair(_name: &str) -> Result<(End, End), String> {
+        let mut fds = [0; 2];
+        if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, fds.as_mut_ptr()) } != 0 {
+            return Err(format!("socketpair failed: {}", io::Error::last_os_error()));
+        }
+        Ok((fds[0], fds[1]))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::io;
+    use std::mem;
+    use std::ptr;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, GENERIC_READ, GENERIC_WRITE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Security::SECURITY_ATTRIBUTES;
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, FILE_FLAG_FIRST_PIPE_INSTANCE, OPEN_EXISTING, PIPE_ACCESS_DUPLEX,
+    };
+    use windows_sys::Win32::System::Pipes::{
+        CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE, PIPE_WAIT,
+    };
+
+    pub(super) type End = usize;
+
+    /// A single-instance pipe the stub connects to itself; the first
+    /// instance flag fails if someone squats the name first
+    pub(super) fn pair(name: &str) -> Result<(End, End), String> {
+        let endpoint = format!(r"\\.\pipe\{}", name);
+        let wide: Vec<u16> = endpoint.encode_utf16().chain([0]).collect();
+        let inheritable = SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: ptr::null_mut(),
+            bInheritHandle: 1,
+        };
+        let server = unsafe {
+            CreateNamedPipeW(
+                wide.as_ptr(),
+                PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                4096,
+                4096,
+                0,
+                &inheritable,
+            )
+        };
+        if server == INVALID_HANDLE_VALUE {
+            return Err(format!("CreateNamedPipeW failed: {}", io::Error::last_os_error()));
+        }
+        // Opening the client connects it; the server end needs no ConnectNamedPipe
+        let client = unsafe {
+            CreateFileW(
+                wide.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                0,
+                &inheritable,
+                OPEN_EXISTING,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if client == INVALID_HANDLE_VALUE {
+            let error = io::Error::last_os_error();
+            unsafe { CloseHandle(server) };
+            return Err(format!("CreateFileW failed: {}", error));
+        }
+        Ok((server as usize, client as usize))
+    }
+}
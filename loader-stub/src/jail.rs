@@ -0,0 +1,254 @@
+//! Minimal namespace jail for the overload process (Linux only).
+//!
+//! Applied in the forked child right before `execv`, so only the overload
+//! is affected. Unprivileged stubs enter a user namespace first and map their
+//! own uid/gid into it, which is what makes mount, PID and network namespaces
+//! available without root.
+//!
+//! A new PID namespace only applies to children of the caller, so with
+//! `JAIL_PID` the jailed child forks once more: the grandchild becomes PID 1
+//! and goes on to exec the overload, while the child stays behind to wait
+//! for it and exit with its status. Both share a process group, so the
+//! stub's signals still reach the overload.
+
+use std::fs;
+use std::path::Path;
+
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{chdir, fork, getgid, getuid, pivot_root, ForkResult};
+
+use weaver_abi::footer::{JAIL_EMPTY_ROOT, JAIL_FILESYSTEM, JAIL_NETWORK, JAIL_PID};
+
+/// Host paths covered by an empty tmpfs inside the jail
+const HIDDEN_PATHS: &[&str] = &["/root", "/home", "/srv", "/mnt", "/media", "/run/user"];
+/// Device nodes bind-mounted into an empty root
+const EMPTY_ROOT_DEVICES: &[&str] = &["null", "zero", "urandom"];
+/// Where the empty root is assembled before pivoting into it
+const EMPTY_ROOT_DIR: &str = "/tmp";
+
+/// Enter the namespaces selected by `flags` (`JAIL_*` footer flags).
+///
+/// `base_dir` is the directory of the merged binary; with `JAIL_FILESYSTEM`
+/// it stays visible but read-only.
+pub fn apply(flags: u32, base_dir: Option<&Path>) -> Result<(), String> {
+    if flags == 0 {
+        return Ok(());
+    }
+
+    let mut clone_flags = CloneFlags::empty();
+    // A PID namespace needs its own /proc, which needs a mount namespace
+    if flags & (JAIL_FILESYSTEM | JAIL_PID | JAIL_EMPTY_ROOT) != 0 {
+        clone_flags |= CloneFlags::CLONE_NEWNS;
+    }
+    if flags & JAIL_NETWORK != 0 {
+        clone_flags |= CloneFlags::CLONE_NEWNET;
+    }
+    if flags & JAIL_PID != 0 {
+        clone_flags |= CloneFlags::CLONE_NEWPID;
+    }
+
+    let uid = getuid();
+    let gid = getgid();
+    if !uid.is_root() {
+        clone_flags |= CloneFlags::CLONE_NEWUSER;
+    }
+
+    unshare(clone_flags).map_err(|e| format!("unshare failed: {}", e))?;
+
+    if !uid.is_root() {
+        map_ids(uid.as_raw(), gid.as_raw())?;
+    }
+
+    if clone_flags.contains(CloneFlags::CLONE_NEWNS) {
+        make_mounts_private()?;
+    }
+    if flags & JAIL_FILESYSTEM != 0 {
+        restrict_filesystem(base_dir)?;
+    }
+
+    if flags & JAIL_PID != 0 {
+        enter_pid_namespace()?;
+    }
+
+    if flags & JAIL_EMPTY_ROOT != 0 {
+        enter_empty_root(flags & JAIL_PID != 0)?;
+    } else if flags & JAIL_PID != 0 {
+        mount_proc(Path::new("/proc"))?;
+    }
+
+    Ok(())
+}
+
+/// Fork into the new PID namespace; only the grandchild (PID 1) returns
+///
+/// The child in between never execs: it waits for the grandchild and exits
+/// with its status, so the stub sees the overload's exit as before.
+fn enter_pid_namespace() -> Result<(), String> {
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => Ok(()),
+        Ok(ForkResult::Parent { child }) => {
+            // Signals to the group reach the overload directly; outlive it
+            // instead of reporting an exit while it still runs
+            unsafe {
+                libc::signal(libc::SIGTERM, libc::SIG_IGN);
+                libc::signal(libc::SIGINT, libc::SIG_IGN);
+            }
+            let code = loop {
+                match waitpid(child, None) {
+                    Ok(WaitStatus::Exited(_, code)) => break code,
+                    Ok(WaitStatus::Signaled(_, signal, _)) => break 128 + signal as i32,
+                    Ok(_) => continue,
+                    Err(nix::errno::Errno::EINTR) => continue,
+                    Err(_) => break 1,
+                }
+            };
+            std::process::exit(code);
+        }
+        Err(e) => Err(format!("fork into PID namespace failed: {}", e)),
+    }
+}
+
+fn mount_proc(target: &Path) -> Result<(), String> {
+    mount(
+        Some("proc"),
+        target,
+        Some("proc"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        None::<&str>,
+    )
+    .map_err(|e| format!("Failed to mount {}: {}", target.display(), e))
+}
+
+/// Replace the root with an empty tmpfs
+///
+/// The overload is exec'd from a file descriptor, so nothing from the host
+/// filesystem needs to stay visible; a dynamically linked overload fails
+/// because its interpreter is gone.
+fn enter_empty_root(with_proc: bool) -> Result<(), String> {
+    let root = Path::new(EMPTY_ROOT_DIR);
+    mount(
+        Some("tmpfs"),
+        root,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some("size=16m,mode=0755"),
+    )
+    .map_err(|e| format!("Failed to mount empty root: {}", e))?;
+
+    let mkdir = |dir: &Path| fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e));
+    mkdir(&root.join("dev"))?;
+    mkdir(&root.join("tmp"))?;
+    mkdir(&root.join("old_root"))?;
+
+    for device in EMPTY_ROOT_DEVICES {
+        let host = Path::new("/dev").join(device);
+        let target = root.join("dev").join(device);
+        fs::File::create(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+        mount(Some(&host), &target, None::<&str>, MsFlags::MS_BIND, None::<&str>)
+            .map_err(|e| format!("Failed to bind {}: {}", host.display(), e))?;
+    }
+
+    if with_proc {
+        mkdir(&root.join("proc"))?;
+        mount_proc(&root.join("proc"))?;
+    }
+
+    pivot_root(root, &root.join("old_root")).map_err(|e| format!("pivot_root failed: {}", e))?;
+    chdir("/").map_err(|e| format!("chdir failed: {}", e))?;
+    nix::mount::umount2("/old_root", nix::mount::MntFlags::MNT_DETACH)
+        .map_err(|e| format!("Failed to detach the host root: {}", e))?;
+    let _ = fs::remove_dir("/old_root");
+    Ok(())
+}
+
+/// Map the caller's uid/gid onto themselves inside the new user namespace
+fn map_ids(uid: u32, gid: u32) -> Result<(), String> {
+    // setgroups must be denied before an unprivileged process may write gid_map
+    fs::write("/proc/self/setgroups", "deny")
+        .map_err(|e| format!("Failed to deny setgroups: {}", e))?;
+    fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid))
+        .map_err(|e| format!("Failed to write uid_map: {}", e))?;
+    fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid))
+        .map_err(|e| format!("Failed to write gid_map: {}", e))?;
+    Ok(())
+}
+
+/// Keep our mounts from propagating back to the host
+fn make_mounts_private() -> Result<(), String> {
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )
+    .map_err(|e| format!("Failed to make / private: {}", e))
+}
+
+/// Mount flags of `dir` that a user namespace may not clear on remount
+///
+/// The kernel locks these on mounts inherited from the host, so a read-only
+/// remount of a nosuid /tmp or /home fails with EPERM unless it keeps them.
+fn locked_flags(dir: &Path) -> Result<MsFlags, String> {
+    let current = statvfs(dir)
+        .map_err(|e| format!("Failed to stat {}: {}", dir.display(), e))?
+        .flags();
+    let mut flags = MsFlags::empty();
+    for (fs_flag, ms_flag) in [
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+        (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+    ] {
+        if current.contains(fs_flag) {
+            flags |= ms_flag;
+        }
+    }
+    Ok(flags)
+}
+
+fn restrict_filesystem(base_dir: Option<&Path>) -> Result<(), String> {
+    if let Some(dir) = base_dir {
+        mount(
+            Some(dir),
+            dir,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            None::<&str>,
+        )
+        .map_err(|e| format!("Failed to bind {}: {}", dir.display(), e))?;
+        mount(
+            None::<&str>,
+            dir,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | locked_flags(dir)?,
+            None::<&str>,
+        )
+        .map_err(|e| format!("Failed to remount {} read-only: {}", dir.display(), e))?;
+    }
+
+    for hidden in HIDDEN_PATHS {
+        let hidden = Path::new(hidden);
+
+        // Don't cover up the directory we just exposed read-only
+        if !hidden.is_dir() || base_dir.is_some_and(|dir| dir.starts_with(hidden)) {
+            continue;
+        }
+
+        mount(
+            Some("tmpfs"),
+            hidden,
+            Some("tmpfs"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+            Some("size=1m,mode=0755"),
+        )
+        .map_err(|e| format!("Failed to hide {}: {}", hidden.display(), e))?;
+    }
+
+    Ok(())
+}
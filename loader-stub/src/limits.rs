@@ -0,0 +1,137 @@
+//! Resource caps for a payload (Linux only).
+//!
+//! Applied in the forked child right before `execv` (and before the jail, so
+//! the cgroup is still writable). Memory and CPU go into a cgroup v2 child
+//! of the stub's own cgroup, which covers everything the payload starts; when
+//! the cgroup tree isn't delegated to us, memory falls back to `RLIMIT_AS`
+//! per process and the CPU cap is dropped. The open file cap is always
+//! `RLIMIT_NOFILE`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use nix::sys::resource::{setrlimit, Resource};
+use nix::unistd::{getpid, getppid};
+
+use weaver_abi::ResourceLimits;
+
+use crate::common;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// cpu.max period in microseconds (the kernel default)
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Cap the calling process according to `limits`
+///
+/// `name` tells base and the overload apart in the cgroup tree.
+pub fn apply(name: &str, limits: &ResourceLimits) -> Result<(), String> {
+    if limits.is_empty() {
+        return Ok(());
+    }
+
+    if limits.memory_mb > 0 || limits.cpu_percent > 0 {
+        if let Err(e) = enter_cgroup(name, limits) {
+            common::log_cgroup_unavailable(&e);
+            if limits.memory_mb > 0 {
+                let bytes = limits.memory_mb as u64 * 1024 * 1024;
+                setrlimit(Resource::RLIMIT_AS, bytes, bytes)
+                    .map_err(|e| format!("Failed to set memory limit: {}", e))?;
+            }
+        }
+    }
+
+    if limits.nofile > 0 {
+        let nofile = limits.nofile as u64;
+        setrlimit(Resource::RLIMIT_NOFILE, nofile, nofile)
+            .map_err(|e| format!("Failed to set open file limit: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Remove the cgroups created for this stub's payloads
+///
+/// Only succeeds once everything in them has exited, so it is best effort.
+pub fn cleanup() {
+    let Ok(parent) = own_cgroup(std::process::id()) else {
+        return;
+    };
+    for name in ["base", "overload"] {
+        let _ = fs::remove_dir(parent.join(cgroup_name(std::process::id(), name)));
+    }
+}
+
+fn cgroup_name(stub_pid: u32, name: &str) -> String {
+    format!("weaver-{}-{}", stub_pid, name)
+}
+
+/// cgroup v2 directory of a process, from the `0::/path` line in /proc/<pid>/cgroup
+fn own_cgroup(pid: u32) -> Result<PathBuf, String> {
+    let content = fs::read_to_string(format!("/proc/{}/cgroup", pid))
+        .map_err(|e| format!("Failed to read cgroup membership: {}", e))?;
+    let path = content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or("cgroup v2 is not mounted")?;
+    Ok(PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/')))
+}
+
+/// Move the calling process into a capped child cgroup of the stub's cgroup
+fn enter_cgroup(name: &str, limits: &ResourceLimits) -> Result<(), String> {
+    let stub_pid = getppid().as_raw() as u32;
+    let parent = own_cgroup(stub_pid)?;
+
+    // Fails harmlessly when the controllers are already enabled
+    let _ = fs::write(parent.join("cgroup.subtree_control"), "+memory +cpu");
+
+    let dir = parent.join(cgroup_name(stub_pid, name));
+    if let Err(e) = fs::create_dir(&dir) {
+        // Scheduled overload runs reuse the same cgroup
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            return Err(format!("Failed to create {}: {}", dir.display(), e));
+        }
+    }
+
+    if limits.memory_mb > 0 {
+        let bytes = limits.memory_mb as u64 * 1024 * 1024;
+        fs::write(dir.join("memory.max"), bytes.to_string())
+            .map_err(|e| format!("Failed to set memory.max: {}", e))?;
+    }
+    if limits.cpu_percent > 0 {
+        fs::write(dir.join("cpu.max"), cpu_max(limits.cpu_percent, available_cpus()))
+            .map_err(|e| format!("Failed to set cpu.max: {}", e))?;
+    }
+
+    fs::write(dir.join("cgroup.procs"), getpid().to_string())
+        .map_err(|e| format!("Failed to join {}: {}", dir.display(), e))
+}
+
+fn available_cpus() -> u64 {
+    std::thread::available_parallelism().map_or(1, |n| n.get() as u64)
+}
+
+/// cpu.max value for `percent` of all `cpus` together
+fn cpu_max(percent: u32, cpus: u64) -> String {
+    let quota = CPU_PERIOD_US * cpus * percent.min(100) as u64 / 100;
+    format!("{} {}", quota.max(1000), CPU_PERIOD_US)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_max_scales_with_cpu_count() {
+        assert_eq!(cpu_max(50, 1), "50000 100000");
+        assert_eq!(cpu_max(25, 4), "100000 100000");
+        assert_eq!(cpu_max(100, 2), "200000 100000");
+        // Clamped to the whole machine and to the kernel's 1ms minimum quota
+        assert_eq!(cpu_max(150, 1), "100000 100000");
+        assert_eq!(cpu_max(1, 1), "1000 100000");
+    }
+
+    #[test]
+    fn test_no_limits_is_a_no_op() {
+        assert!(apply("base", &ResourceLimits::default()).is_ok());
+    }
+}
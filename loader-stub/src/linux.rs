@@ -1,61 +1,245 @@
-use std::ffi::CString;
-use std::fs::File;
-use std::io::Write;
+use std::ffi::{CStr, CString};
 use std::mem;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::path::PathBuf;
 use std::ptr;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use nix::fcntl::OFlag;
-use nix::sys::memfd::{memfd_create, MFdFlags};
 use nix::sys::mman::{mmap, shm_open, MapFlags, ProtFlags};
 use nix::sys::signal::{kill, Signal};
 use nix::sys::stat::Mode;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::{execv, fork, getpid, sleep, ForkResult, Pid};
+use nix::unistd::{fork, getpid, sleep, ForkResult, Pid};
 
 use crate::common::{
-    self, evaluate_health_status, force_kill_delay, health_check_interval, init_health_status,
+    self, evaluate_health_table, force_kill_delay, health_check_interval, init_health_table,
     log_async_mode_started, log_base_completed_terminating_overload, log_base_exited,
-    log_base_killed_by_signal, log_fallback_kill, log_forcing_sigkill, log_grace_period_exceeded,
-    log_health_monitor_started, log_health_monitoring_enabled, log_heartbeat_lost,
-    log_network_failure_threshold, log_overload_requested_kill, log_overload_start_failed,
+    log_base_killed_by_signal, log_fallback_kill, log_forcing_sigkill,
+    log_health_monitor_started, log_health_monitoring_enabled,
+    log_overload_start_failed, log_readiness_failed,
     log_shm_create_failed, log_shm_map_failed, log_starting_base, log_sync_mode_waiting,
     log_verification_failed, log_verification_successful, overload_kill_wait_duration,
-    should_enable_health_monitoring, signal_overload_to_kill, HealthCheckResult,
+    should_enable_health_monitoring, signal_overload_to_kill, signal_tree, HealthCheckResult, KillPolicy,
+    MonitorArming,
 };
-use crate::{ConfigFooter, HealthStatus};
+use crate::privileges::Privileges;
+use crate::readiness::Readiness;
+use crate::seccomp::Filter;
+use crate::{control, crash, exec_fd, instance, ipc, jail, limits, logging, output, priority, shutdown, ConfigFooter, HealthTable};
+use weaver_abi::footer::{ORDER_BASE_FIRST, OVERLOAD_LIBRARY, OVERLOAD_SCRIPT};
+use weaver_abi::ResourceLimits;
+
+/// Everything a payload's child applies to itself between fork and exec,
+/// plus the name it is exec'd under and how it is loaded
+#[derive(Clone)]
+struct Sandbox {
+    process_name: CString,
+    exec_strategy: u8,
+    jail_flags: u32,
+    limits: ResourceLimits,
+    priority: Option<i8>,
+    cpu_affinity: u64,
+    privileges: Privileges,
+    cwd: Option<PathBuf>,
+    umask: Option<u16>,
+    seccomp: Option<Filter>,
+    /// The payload is a `#!` script
+    script: bool,
+    /// Interpreter bundled to run the script, instead of the one its `#!` line names
+    interpreter: Option<Arc<[u8]>>,
+}
+
+impl Sandbox {
+    /// Base is never jailed; both get their own limits and the same privileges,
+    /// and the seccomp filter if the profile applies to them. Only the
+    /// overload can be a script.
+    fn for_payload(
+        footer: &ConfigFooter,
+        is_base: bool,
+        privileges: &Privileges,
+        seccomp: Option<&Filter>,
+        interpreter: Option<&Arc<[u8]>>,
+    ) -> Self {
+        let script = !is_base && footer.overload_kind == OVERLOAD_SCRIPT;
+        Sandbox {
+            process_name: CString::new(common::payload_name(footer, is_base)).unwrap_or_default(),
+            exec_strategy: footer.exec_strategy,
+            jail_flags: if is_base { 0 } else { footer.overload_jail },
+            limits: footer.limits(is_base),
+            priority: footer.priority(is_base),
+            cpu_affinity: footer.cpu_affinity(is_base),
+            privileges: privileges.clone(),
+            cwd: common::payload_cwd(footer, is_base),
+            umask: footer.umask(is_base),
+            seccomp: seccomp.filter(|filter| filter.applies_to(is_base)).cloned(),
+            script,
+            interpreter: interpreter.filter(|_| script).cloned(),
+        }
+    }
+
+    /// Apply in a forked child, refusing to exec when any part fails
+    ///
+    /// Limits, priority and jail still need root, so privileges come after
+    /// them; a priority that can't be applied only gets a warning. The
+    /// working directory is entered as the payload's account, inside its
+    /// jail; the seccomp filter goes last so it only has to allow the exec.
+    fn enter(&self, name: &str) {
+        ipc::enter(name == "base");
+        output::enter(name == "base");
+        crash::enter(name == "base");
+        if let Err(e) = limits::apply(name, &self.limits) {
+            common::log_limits_failed(&e);
+            std::process::exit(1);
+        }
+        if let Err(e) = priority::apply(self.priority, self.cpu_affinity) {
+            common::log_priority_failed(&e);
+        }
+        if self.jail_flags != 0 {
+            // Directory of the merged binary, exposed read-only inside the jail
+            let base_dir = std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()));
+            if let Err(e) = jail::apply(self.jail_flags, base_dir.as_deref()) {
+                common::log_jail_failed(&e);
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = self.privileges.apply() {
+            common::log_privileges_failed(&e);
+            std::process::exit(1);
+        }
+        if let Err(e) = common::enter_cwd_and_umask(self.cwd.as_deref(), self.umask) {
+            common::log_cwd_failed(&e);
+            std::process::exit(1);
+        }
+        if let Some(filter) = &self.seccomp {
+            if let Err(e) = filter.install() {
+                common::log_seccomp_failed(&e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// A payload written to descriptors by `exec_fd::load`, ready to exec
+#[derive(Clone, Copy)]
+enum Loaded {
+    Executable(RawFd),
+    /// A `#!` script the kernel runs through the interpreter its first line names
+    Script(RawFd),
+    /// A `#!` script run by the interpreter bundled with it
+    Interpreted { interpreter: RawFd, script: RawFd },
+}
+
+impl Loaded {
+    fn load(data: &[u8], sandbox: &Sandbox) -> Result<Self, String> {
+        let load = |data: &[u8]| exec_fd::load(data, &sandbox.process_name, sandbox.exec_strategy).map(IntoRawFd::into_raw_fd);
+        if !sandbox.script {
+            return load(data).map(Loaded::Executable);
+        }
+        let script = load(data)?;
+        match sandbox.interpreter.as_deref().map(load) {
+            Some(Ok(interpreter)) => Ok(Loaded::Interpreted { interpreter, script }),
+            Some(Err(e)) => {
+                nix::unistd::close(script).ok();
+                Err(e)
+            }
+            None => Ok(Loaded::Script(script)),
+        }
+    }
+
+    /// Close the parent's copies once the child is forked
+    fn close(self) {
+        match self {
+            Loaded::Executable(fd) | Loaded::Script(fd) => {
+                nix::unistd::close(fd).ok();
+            }
+            Loaded::Interpreted { interpreter, script } => {
+                nix::unistd::close(interpreter).ok();
+                nix::unistd::close(script).ok();
+            }
+        }
+    }
+
+    /// Exec in the forked child
+    ///
+    /// An interpreter opens its script by descriptor path after the exec, so
+    /// a script's descriptor has to stay open across it.
+    fn exec(self, name: &CStr) -> ! {
+        match self {
+            Loaded::Executable(fd) => exec_from_fd(fd, &[name]),
+            Loaded::Script(fd) => {
+                keep_open_across_exec(fd);
+                exec_from_fd(fd, &[name])
+            }
+            Loaded::Interpreted { interpreter, script } => {
+                keep_open_across_exec(script);
+                let path = CString::new(format!("/proc/self/fd/{}", script)).unwrap_or_default();
+                exec_from_fd(interpreter, &[name, &path])
+            }
+        }
+    }
+}
+
+fn keep_open_across_exec(fd: RawFd) {
+    unsafe {
+        libc::fcntl(fd, libc::F_SETFD, 0);
+    }
+}
+
+/// Exec a payload straight from the descriptor `exec_fd::load` returned
+///
+/// execveat needs no /proc, which an empty-root jail doesn't have.
+fn exec_from_fd(fd: RawFd, args: &[&CStr]) -> ! {
+    let env: Vec<CString> = std::env::vars_os()
+        .filter_map(|(key, value)| {
+            let mut entry = key.into_encoded_bytes();
+            entry.push(b'=');
+            entry.extend(value.into_encoded_bytes());
+            CString::new(entry).ok()
+        })
+        .collect();
+    let argv: Vec<*const libc::c_char> = args.iter().map(|arg| arg.as_ptr()).chain([ptr::null()]).collect();
+    let envp: Vec<*const libc::c_char> = env.iter().map(|e| e.as_ptr()).chain([ptr::null()]).collect();
+
+    unsafe {
+        libc::syscall(libc::SYS_execveat, fd, c"".as_ptr(), argv.as_ptr(), envp.as_ptr(), libc::AT_EMPTY_PATH);
+    }
+    common::log_execv_failed();
+    std::process::exit(1);
+}
 
 unsafe fn execute_binary(
     binary_data: &[u8],
     name: &str,
     is_base: bool,
     sync_mode: bool,
+    sandbox: &Sandbox,
     overload_pid_ref: &mut Option<Pid>,
 ) -> Result<i32, String> {
-    let name_c = CString::new(name).unwrap();
-    let fd = memfd_create(name_c.as_c_str(), MFdFlags::MFD_CLOEXEC)
-        .map_err(|e| format!("memfd_create failed: {}", e))?;
-
-    let mut file = File::from(fd);
-    file.write_all(binary_data)
-        .map_err(|e| format!("Failed to write binary data: {}", e))?;
-
-    let raw_fd = file.as_raw_fd();
-    mem::forget(file);
+    let name_c = &sandbox.process_name;
+    let loaded = Loaded::load(binary_data, sandbox)?;
+    ipc::export(is_base);
 
     match fork() {
         Ok(ForkResult::Parent { child }) => {
-            nix::unistd::close(raw_fd).ok();
+            loaded.close();
+            common::place_in_own_process_group(child.as_raw());
+            shutdown::track(child.as_raw());
 
             if !is_base {
                 *overload_pid_ref = Some(child);
 
                 if sync_mode {
                     log_sync_mode_waiting(child);
-                    match waitpid(child, None) {
+                    let status = waitpid(child, None);
+                    shutdown::untrack(child.as_raw());
+                    common::reclaim_terminal();
+                    match status {
                         Ok(WaitStatus::Exited(_, code)) => {
                             if code != 0 {
                                 log_verification_failed(code);
@@ -64,7 +248,7 @@ unsafe fn execute_binary(
                             log_verification_successful();
                         }
                         Ok(status) => {
-                            eprintln!("[KillCode] ❌ Overload terminated abnormally: {:?}", status);
+                            logging::error!("❌ Overload terminated abnormally: {:?}", status);
                             return Err(format!("Overload terminated abnormally: {:?}", status));
                         }
                         Err(e) => return Err(format!("waitpid failed: {}", e)),
@@ -74,26 +258,34 @@ unsafe fn execute_binary(
                 }
                 Ok(0)
             } else {
+                instance::set_base_pid(child.as_raw());
+                output::started(true);
+                crash::base_started();
                 let mut status_code = -1;
-                match waitpid(child, None) {
+                let status = waitpid(child, None);
+                shutdown::untrack(child.as_raw());
+                common::reclaim_terminal();
+                output::base_exited();
+                crash::base_waited(&status);
+                match status {
                     Ok(WaitStatus::Exited(_, code)) => status_code = code,
                     Ok(WaitStatus::Signaled(_, sig, _)) => {
                         log_base_killed_by_signal(sig);
                         status_code = -1;
                     }
-                    Err(e) => eprintln!("[KillCode] waitpid failed for base: {}", e),
+                    Err(e) => logging::error!("waitpid failed for base: {}", e),
                     _ => {}
                 }
 
                 if let Some(overload_pid) = *overload_pid_ref {
                     log_base_completed_terminating_overload(overload_pid);
-                    let _ = kill(overload_pid, Signal::SIGTERM);
+                    signal_tree(overload_pid.as_raw(), Signal::SIGTERM);
                     sleep(1);
 
                     match waitpid(overload_pid, Some(WaitPidFlag::WNOHANG)) {
                         Ok(WaitStatus::StillAlive) => {
                             log_forcing_sigkill();
-                            let _ = kill(overload_pid, Signal::SIGKILL);
+                            signal_tree(overload_pid.as_raw(), Signal::SIGKILL);
                             let _ = waitpid(overload_pid, None);
                         }
                         _ => {}
@@ -104,40 +296,245 @@ unsafe fn execute_binary(
             }
         }
         Ok(ForkResult::Child) => {
-            let fd_path = format!("/proc/self/fd/{}", raw_fd);
-            let fd_path_c = CString::new(fd_path).unwrap();
-            let args = [name_c.clone()];
-            let _ = execv(&fd_path_c, &args);
-            common::log_execv_failed();
-            std::process::exit(1);
+            common::enter_own_process_group(is_base || sync_mode);
+            sandbox.enter(name);
+            loaded.exec(name_c);
+        }
+        Err(e) => {
+            loaded.close();
+            Err(format!("fork failed: {}", e))
+        }
+    }
+}
+
+/// Run base to completion, then the overload (waited for, jailed if configured)
+fn run_base_first(
+    base_data: &[u8],
+    overload_data: &[u8],
+    footer: &ConfigFooter,
+    base_sandbox: &Sandbox,
+    overload_sandbox: &Sandbox,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let jail_flags = footer.overload_jail;
+    common::log_base_first();
+    log_starting_base();
+    let mut no_overload = None;
+    let base_exit_code = unsafe { execute_binary(base_data, "base", true, false, base_sandbox, &mut no_overload) }?;
+    log_base_exited(base_exit_code);
+
+    if jail_flags != 0 {
+        common::log_jail_enabled(jail_flags);
+    }
+    let mut overload_pid = None;
+    if let Err(e) = unsafe { execute_binary(overload_data, "overload", false, true, overload_sandbox, &mut overload_pid) } {
+        common::log_overload_after_base_failed(&e);
+    }
+
+    exit_reaping(base_exit_code);
+}
+
+/// Run base alone with the library overload in `LD_PRELOAD`
+///
+/// The library stays in memory like any payload; base inherits its
+/// descriptor and the dynamic loader opens it by descriptor path.
+fn run_preloaded(base_data: &[u8], library_data: &[u8], base_sandbox: &Sandbox) -> Result<(), Box<dyn std::error::Error>> {
+    common::log_overload_preloaded();
+    let library = exec_fd::load(library_data, c"overload", base_sandbox.exec_strategy)?.into_raw_fd();
+    keep_open_across_exec(library);
+    let mut preload = format!("/proc/self/fd/{}", library);
+    if let Some(existing) = std::env::var_os("LD_PRELOAD").filter(|existing| !existing.is_empty()) {
+        preload.push(':');
+        preload.push_str(&existing.to_string_lossy());
+    }
+    std::env::set_var("LD_PRELOAD", preload);
+
+    log_starting_base();
+    let base_pid = unsafe { spawn_base(base_data, base_sandbox) }?;
+    nix::unistd::close(library).ok();
+    let base_exit_code = wait_base_exit_code(base_pid);
+    log_base_exited(base_exit_code);
+    exit_reaping(base_exit_code);
+}
+
+/// Start base right away and run the overload on its delay/interval schedule
+fn run_scheduled(
+    base_data: &[u8],
+    overload_data: Vec<u8>,
+    footer: &ConfigFooter,
+    base_sandbox: &Sandbox,
+    overload_sandbox: Sandbox,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start_delay = footer.overload_start_delay;
+    let interval = footer.overload_interval;
+    let failure_threshold = footer.network_failure_kill_count;
+    let jail_flags = footer.overload_jail;
+    let kill_delay = force_kill_delay(footer.kill_delay_ms);
+    common::log_overload_scheduled(start_delay, interval);
+    if jail_flags != 0 {
+        common::log_jail_enabled(jail_flags);
+    }
+
+    log_starting_base();
+    let base_pid = unsafe { spawn_base(base_data, base_sandbox) }?;
+    common::export_base_pid(base_pid);
+    let running_overload = Arc::new(AtomicI32::new(0));
+
+    let running = running_overload.clone();
+    thread::spawn(move || {
+        common::run_overload_schedule(
+            start_delay,
+            interval,
+            failure_threshold,
+            || {
+                let mut pid = None;
+                unsafe { execute_binary(&overload_data, "overload", false, false, &overload_sandbox, &mut pid) }?;
+                let pid = pid.ok_or("overload did not start")?;
+                running.store(pid.as_raw(), Ordering::Relaxed);
+                let code = wait_exit_code(pid);
+                running.store(0, Ordering::Relaxed);
+                Ok(code)
+            },
+            || kill(base_pid, None).is_ok(),
+            || kill_base(base_pid.as_raw(), kill_delay),
+        )
+    });
+
+    let base_exit_code = wait_base_exit_code(base_pid);
+
+    let overload_pid = running_overload.swap(0, Ordering::Relaxed);
+    if overload_pid > 0 {
+        log_base_completed_terminating_overload(overload_pid);
+        signal_tree(overload_pid, Signal::SIGTERM);
+    }
+
+    log_base_exited(base_exit_code);
+    exit_reaping(base_exit_code);
+}
+
+/// Fork and exec base without waiting for it
+unsafe fn spawn_base(base_data: &[u8], sandbox: &Sandbox) -> Result<Pid, String> {
+    let name_c = &sandbox.process_name;
+    let loaded = Loaded::load(base_data, sandbox)?;
+    ipc::export(true);
+
+    match fork() {
+        Ok(ForkResult::Parent { child }) => {
+            loaded.close();
+            common::place_in_own_process_group(child.as_raw());
+            shutdown::track(child.as_raw());
+            instance::set_base_pid(child.as_raw());
+            output::started(true);
+            crash::base_started();
+            Ok(child)
+        }
+        Ok(ForkResult::Child) => {
+            common::enter_own_process_group(true);
+            sandbox.enter("base");
+            loaded.exec(name_c);
         }
         Err(e) => {
-            nix::unistd::close(raw_fd).ok();
+            loaded.close();
             Err(format!("fork failed: {}", e))
         }
     }
 }
 
-/// Kill base process with SIGTERM followed by SIGKILL
-fn kill_base(base_pid: i32) {
-    let _ = kill(Pid::from_raw(base_pid), Signal::SIGTERM);
-    thread::sleep(force_kill_delay());
-    let _ = kill(Pid::from_raw(base_pid), Signal::SIGKILL);
+/// Wait for a child and return its exit code (-1 if killed by a signal)
+fn wait_exit_code(child: Pid) -> i32 {
+    let status = waitpid(child, None);
+    shutdown::untrack(child.as_raw());
+    match status {
+        Ok(WaitStatus::Exited(_, code)) => code,
+        _ => -1,
+    }
+}
+
+/// Wait for base, report it if it crashed and return its exit code (-1 if
+/// killed by a signal)
+fn wait_base_exit_code(base: Pid) -> i32 {
+    let status = waitpid(base, None);
+    shutdown::untrack(base.as_raw());
+    output::base_exited();
+    crash::base_waited(&status);
+    match status {
+        Ok(WaitStatus::Exited(_, code)) => code,
+        _ => -1,
+    }
+}
+
+/// Kill base and its process group with SIGTERM followed by SIGKILL after `kill_delay`
+fn kill_base(base_pid: i32, kill_delay: Duration) {
+    signal_tree(base_pid, Signal::SIGTERM);
+    thread::sleep(kill_delay);
+    signal_tree(base_pid, Signal::SIGKILL);
+}
+
+/// Adopt orphaned grandchildren instead of letting them escape to init
+fn become_subreaper() {
+    unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+}
+
+/// Kill whatever children are left (including adopted orphans that left
+/// their process group), then exit
+fn exit_reaping(code: i32) -> ! {
+    let me = std::process::id() as i32;
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<i32>().ok()) else {
+                continue;
+            };
+            let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+                continue;
+            };
+            // "pid (comm) state ppid ..."; comm may contain spaces and parens
+            let ppid = stat
+                .rsplit_once(')')
+                .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+                .and_then(|ppid| ppid.parse::<i32>().ok());
+            if ppid == Some(me) {
+                common::log_killing_straggler(pid);
+                let _ = kill(Pid::from_raw(pid), Signal::SIGKILL);
+            }
+        }
+    }
+    limits::cleanup();
+    std::process::exit(code);
 }
 
 pub fn run(
     base_data: Vec<u8>,
     overload_data: Vec<u8>,
+    interpreter: Option<Vec<u8>>,
+    seccomp_profile: Option<Vec<u8>>,
     footer: ConfigFooter,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let sync_mode = footer.sync_mode != 0;
-    let grace_period = footer.grace_period;
-    let network_failure_kill_count = footer.network_failure_kill_count;
+    become_subreaper();
+    let privileges = Privileges::from_footer(&footer)?;
+    let seccomp = seccomp_profile.as_deref().map(Filter::from_profile).transpose()?;
+    let interpreter: Option<Arc<[u8]>> = interpreter.map(Arc::from);
+    let base_sandbox = Sandbox::for_payload(&footer, true, &privileges, seccomp.as_ref(), None);
+    let overload_sandbox = Sandbox::for_payload(&footer, false, &privileges, seccomp.as_ref(), interpreter.as_ref());
+
+    if footer.overload_kind == OVERLOAD_LIBRARY {
+        return run_preloaded(&base_data, &overload_data, &base_sandbox);
+    }
+    if footer.order == ORDER_BASE_FIRST {
+        return run_base_first(&base_data, &overload_data, &footer, &base_sandbox, &overload_sandbox);
+    }
+    if footer.is_scheduled() {
+        return run_scheduled(&base_data, overload_data, &footer, &base_sandbox, overload_sandbox);
+    }
+
+    let sync_mode = footer.prerequisite_runs() > 0;
+    let kill_policy = KillPolicy::from_footer(&footer);
+    let mut arming = MonitorArming::from_footer(&footer);
+    let check_interval = health_check_interval(footer.health_check_interval);
+    let kill_delay = force_kill_delay(footer.kill_delay_ms);
 
-    let mut health_ptr: *mut HealthStatus = ptr::null_mut();
+    let mut health_ptr: *mut HealthTable = ptr::null_mut();
     let mut _shm_fd_keeper = None;
 
-    if should_enable_health_monitoring(sync_mode, grace_period, network_failure_kill_count) {
+    if should_enable_health_monitoring(sync_mode, &kill_policy, &footer) {
         let pid = getpid();
         let shm_name = format!("/overload_health_{}", pid);
         let shm_name_c = CString::new(shm_name.clone()).unwrap();
@@ -148,12 +545,16 @@ pub fn run(
             Mode::from_bits_truncate(0o600),
         ) {
             Ok(fd) => {
-                let _ = nix::unistd::ftruncate(&fd, mem::size_of::<HealthStatus>() as libc::off_t);
+                let _ = nix::unistd::ftruncate(&fd, HealthTable::SIZE as libc::off_t);
+                if let Some(account) = privileges.account() {
+                    // The overload reopens the region by name once it runs as that account
+                    let _ = nix::unistd::fchown(&fd, Some(account.uid), Some(account.gid));
+                }
 
                 unsafe {
                     let ptr = mmap(
                         None,
-                        std::num::NonZeroUsize::new(mem::size_of::<HealthStatus>()).unwrap(),
+                        std::num::NonZeroUsize::new(HealthTable::SIZE).unwrap(),
                         ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
                         MapFlags::MAP_SHARED,
                         &fd,
@@ -162,8 +563,9 @@ pub fn run(
 
                     match ptr {
                         Ok(p) => {
-                            health_ptr = p.as_ptr() as *mut HealthStatus;
-                            init_health_status(health_ptr);
+                            health_ptr = p.as_ptr() as *mut HealthTable;
+                            init_health_table(health_ptr);
+                            control::set_health(health_ptr);
                             std::env::set_var("KILLCODE_HEALTH_SHM", &shm_name);
                             log_health_monitoring_enabled(&shm_name);
                             _shm_fd_keeper = Some(fd);
@@ -176,17 +578,49 @@ pub fn run(
         }
     }
 
+    if footer.overload_jail != 0 {
+        common::log_jail_enabled(footer.overload_jail);
+    }
+
+    // Only an overload running alongside base is terminated when base exits
     let mut overload_pid = None;
-    unsafe {
-        if let Err(e) = execute_binary(&overload_data, "overload", false, sync_mode, &mut overload_pid) {
-            log_overload_start_failed(&e);
+    let started = if sync_mode {
+        common::run_prerequisites(&footer, || {
+            unsafe { execute_binary(&overload_data, "overload", false, true, &overload_sandbox, &mut None) }.map(drop)
+        })
+    } else {
+        unsafe { execute_binary(&overload_data, "overload", false, false, &overload_sandbox, &mut overload_pid) }.map(drop)
+    };
+    if let Err(e) = started {
+        log_overload_start_failed(&e);
+        return Err(e.into());
+    }
+
+    if let (false, Some(readiness)) = (sync_mode, Readiness::from_footer(&footer)) {
+        let mut overload_exited = false;
+        let ready = readiness.wait(health_ptr, || {
+            overload_exited = overload_pid
+                .is_none_or(|pid| !matches!(waitpid(pid, Some(WaitPidFlag::WNOHANG)), Ok(WaitStatus::StillAlive)));
+            !overload_exited
+        });
+        if overload_exited {
+            overload_pid = None;
+        }
+        if let Err(e) = ready {
+            log_readiness_failed(&e);
+            if let Some(ov_pid) = overload_pid {
+                signal_tree(ov_pid.as_raw(), Signal::SIGTERM);
+                sleep(1);
+                signal_tree(ov_pid.as_raw(), Signal::SIGKILL);
+                let _ = waitpid(ov_pid, None);
+            }
             return Err(e.into());
         }
     }
 
     let monitor_handle = if !sync_mode
         && !health_ptr.is_null()
-        && (grace_period > 0 || network_failure_kill_count > 0)
+        && kill_policy.is_active()
     {
         let base_pid_cell = Arc::new(AtomicI32::new(0));
         let base_pid_clone = base_pid_cell.clone();
@@ -195,9 +629,9 @@ pub fn run(
         Some((
             thread::spawn(move || {
                 log_health_monitor_started();
-                let health_ptr = health_ptr_addr as *mut HealthStatus;
+                let health_ptr = health_ptr_addr as *mut HealthTable;
                 loop {
-                    thread::sleep(health_check_interval());
+                    thread::sleep(check_interval);
 
                     let base_pid = base_pid_clone.load(Ordering::Relaxed);
                     if base_pid <= 0 {
@@ -209,29 +643,22 @@ pub fn run(
                     }
 
                     unsafe {
-                        match evaluate_health_status(health_ptr, grace_period, network_failure_kill_count) {
+                        let (slot, result) = evaluate_health_table(health_ptr, &kill_policy, &mut arming);
+                        match result {
                             HealthCheckResult::Ok => {}
-                            HealthCheckResult::GracePeriodExceeded { time_since_success, grace_period } => {
-                                log_grace_period_exceeded(time_since_success, grace_period);
-                                kill_base(base_pid);
+                            HealthCheckResult::KillBase => {
+                                kill_base(base_pid, kill_delay);
                                 break;
                             }
-                            HealthCheckResult::NetworkFailureThreshold { failures, threshold } => {
-                                log_network_failure_threshold(failures, threshold);
-                                signal_overload_to_kill(health_ptr);
+                            HealthCheckResult::KillOverload => {
+                                signal_overload_to_kill(health_ptr, slot);
                                 thread::sleep(overload_kill_wait_duration());
                                 log_fallback_kill();
-                                kill_base(base_pid);
-                                break;
-                            }
-                            HealthCheckResult::OverloadRequestedKill => {
-                                log_overload_requested_kill();
-                                kill_base(base_pid);
+                                kill_base(base_pid, kill_delay);
                                 break;
                             }
-                            HealthCheckResult::HeartbeatLost => {
-                                log_heartbeat_lost();
-                                kill_base(base_pid);
+                            HealthCheckResult::Exit => {
+                                shutdown::request();
                                 break;
                             }
                         }
@@ -246,30 +673,34 @@ pub fn run(
 
     log_starting_base();
     let base_exit_code = unsafe {
-        let name_c = CString::new("base").unwrap();
-        let fd = memfd_create(name_c.as_c_str(), MFdFlags::MFD_CLOEXEC)
-            .map_err(|e| format!("memfd_create failed: {}", e))?;
-
-        let mut file = File::from(fd);
-        file.write_all(&base_data)
-            .map_err(|e| format!("Failed to write binary data: {}", e))?;
-        let raw_fd = file.as_raw_fd();
-        mem::forget(file);
+        let name_c = &base_sandbox.process_name;
+        let loaded = Loaded::load(&base_data, &base_sandbox)?;
+        ipc::export(true);
 
         match fork() {
             Ok(ForkResult::Parent { child }) => {
-                nix::unistd::close(raw_fd).ok();
+                loaded.close();
+                common::place_in_own_process_group(child.as_raw());
+                shutdown::track(child.as_raw());
+                instance::set_base_pid(child.as_raw());
+                common::record_base_started();
+                output::started(true);
+                crash::base_started();
 
                 if let Some((_, ref pid_cell)) = monitor_handle {
                     pid_cell.store(child.as_raw(), Ordering::Relaxed);
                 }
 
                 if !health_ptr.is_null() {
-                    (*health_ptr).base_pid = child.as_raw();
+                    (*health_ptr).set_base_pid(child.as_raw());
                 }
 
                 let mut status_code = -1;
-                match waitpid(child, None) {
+                let status = waitpid(child, None);
+                shutdown::untrack(child.as_raw());
+                output::base_exited();
+                crash::base_waited(&status);
+                match status {
                     Ok(WaitStatus::Exited(_, code)) => status_code = code,
                     Ok(WaitStatus::Signaled(_, sig, _)) => {
                         log_base_killed_by_signal(sig);
@@ -280,11 +711,11 @@ pub fn run(
 
                 if let Some(ov_pid) = overload_pid {
                     log_base_completed_terminating_overload(ov_pid);
-                    let _ = kill(ov_pid, Signal::SIGTERM);
+                    signal_tree(ov_pid.as_raw(), Signal::SIGTERM);
                     sleep(1);
                     match waitpid(ov_pid, Some(WaitPidFlag::WNOHANG)) {
                         Ok(WaitStatus::StillAlive) => {
-                            let _ = kill(ov_pid, Signal::SIGKILL);
+                            signal_tree(ov_pid.as_raw(), Signal::SIGKILL);
                             let _ = waitpid(ov_pid, None);
                         }
                         _ => {}
@@ -293,14 +724,12 @@ pub fn run(
                 Ok(status_code)
             }
             Ok(ForkResult::Child) => {
-                let fd_path = format!("/proc/self/fd/{}", raw_fd);
-                let fd_path_c = CString::new(fd_path).unwrap();
-                let args = [name_c.clone()];
-                let _ = execv(&fd_path_c, &args);
-                std::process::exit(1);
+                common::enter_own_process_group(true);
+                base_sandbox.enter("base");
+                loaded.exec(name_c);
             }
             Err(e) => {
-                nix::unistd::close(raw_fd).ok();
+                loaded.close();
                 Err(format!("fork failed: {}", e))
             }
         }
@@ -311,5 +740,84 @@ pub fn run(
     }
 
     log_base_exited(base_exit_code);
-    std::process::exit(base_exit_code);
+    exit_reaping(base_exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HealthStatus;
+    use nix::sys::mman::{mmap_anonymous, munmap};
+
+    const ITERATIONS: i64 = 200_000;
+
+    /// Map a `HealthStatus` shared with forked children, like the real
+    /// shm region is shared with the overload.
+    fn shared_health_region() -> (&'static HealthStatus, ptr::NonNull<libc::c_void>) {
+        let len = std::num::NonZeroUsize::new(HealthStatus::SIZE).unwrap();
+        let region = unsafe {
+            mmap_anonymous(
+                None,
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+            )
+        }
+        .expect("mmap failed");
+        let status = unsafe { HealthStatus::from_ptr(region.as_ptr() as *mut u8) }.unwrap();
+        status.init(0);
+        (status, region)
+    }
+
+    fn run_in_child(body: impl FnOnce()) -> Pid {
+        match unsafe { fork() }.expect("fork failed") {
+            ForkResult::Child => {
+                body();
+                unsafe { libc::_exit(0) };
+            }
+            ForkResult::Parent { child } => child,
+        }
+    }
+
+    #[test]
+    fn test_last_success_never_tears_across_processes() {
+        let (status, region) = shared_health_region();
+
+        let child = run_in_child(|| {
+            for i in 1..=ITERATIONS {
+                // Upper and lower halves always match, so a torn read is detectable
+                status.record_success((i << 32) | i);
+            }
+        });
+
+        loop {
+            let value = status.last_success();
+            assert_eq!(value >> 32, value & 0xffff_ffff, "torn read: {:#x}", value);
+            if let Ok(WaitStatus::Exited(_, code)) = waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+                assert_eq!(code, 0);
+                break;
+            }
+        }
+
+        assert_eq!(status.last_success(), (ITERATIONS << 32) | ITERATIONS);
+        unsafe { munmap(region, HealthStatus::SIZE) }.unwrap();
+    }
+
+    #[test]
+    fn test_failure_counter_is_shared_across_processes() {
+        let (status, region) = shared_health_region();
+
+        let child = run_in_child(|| {
+            for _ in 0..ITERATIONS {
+                status.record_failure();
+            }
+        });
+        for _ in 0..ITERATIONS {
+            status.record_failure();
+        }
+        assert!(matches!(waitpid(child, None), Ok(WaitStatus::Exited(_, 0))));
+
+        assert_eq!(status.consecutive_failures() as i64, ITERATIONS * 2);
+        unsafe { munmap(region, HealthStatus::SIZE) }.unwrap();
+    }
 }
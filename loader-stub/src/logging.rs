@@ -0,0 +1,176 @@
+//! The stub's own messages, filtered and routed per the footer.
+//!
+//! `info!` and `error!` replace bare `eprintln!` everywhere in the stub.
+//! The level and sink are set once by [`init`] before any payload starts;
+//! until then (and whenever a file or system log can't be opened) messages
+//! go to stderr. Payload output is never touched. Lines are prefixed with
+//! the footer's product name, or `[KillCode]` for unbranded binaries.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+use weaver_abi::footer::{LOG_ERRORS, LOG_SILENT, LOG_TARGET_FILE, LOG_TARGET_SYSTEM, LOG_VERBOSE};
+
+use crate::ConfigFooter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Info,
+}
+
+enum Sink {
+    File(File),
+    System(imp::SystemLog),
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LOG_VERBOSE);
+static SINK: OnceLock<Sink> = OnceLock::new();
+static PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Log a failure, or a reason payloads are being killed
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Error, format_args!($($arg)*))
+    };
+}
+
+/// Log progress and configuration
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logging::write($crate::logging::Level::Info, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {error, info};
+
+/// Apply the footer's `log_level`, `log_target` and `log_path`
+pub fn init(footer: &ConfigFooter) {
+    if let Some(product) = footer.product_name() {
+        let _ = PREFIX.set(product.to_string());
+    }
+    LEVEL.store(footer.log_level, Ordering::Relaxed);
+    if footer.log_level == LOG_SILENT {
+        return;
+    }
+
+    let sink = match footer.log_target {
+        LOG_TARGET_FILE => match footer.log_path().map(|path| OpenOptions::new().create(true).append(true).open(path)) {
+            Some(Ok(file)) => Some(Sink::File(file)),
+            Some(Err(e)) => {
+                error!("Failed to open log file, logging to stderr: {}", e);
+                None
+            }
+            None => None,
+        },
+        LOG_TARGET_SYSTEM => imp::SystemLog::open().map(Sink::System),
+        _ => None,
+    };
+    if let Some(sink) = sink {
+        let _ = SINK.set(sink);
+    }
+}
+
+pub fn write(level: Level, args: fmt::Arguments) {
+    let enabled = match LEVEL.load(Ordering::Relaxed) {
+        LOG_SILENT => false,
+        LOG_ERRORS => level == Level::Error,
+        _ => true,
+    };
+    if !enabled {
+        return;
+    }
+
+    let prefix = PREFIX.get().map_or("KillCode", String::as_str);
+    match SINK.get() {
+        // One write per line, so lines from base and overload children
+        // appending to the same file don't interleave
+        Some(Sink::File(file)) => {
+            let _ = (&*file).write_all(format!("[{}] {}\n", prefix, args).as_bytes());
+        }
+        Some(Sink::System(log)) => log.write(level, &args.to_string()),
+        None => eprintln!("[{}] {}", prefix, args),
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::ffi::CString;
+
+    use super::Level;
+
+    /// syslog(3) under the executable's name, so the stub isn't named
+    pub struct SystemLog;
+
+    impl SystemLog {
+        pub fn open() -> Option<Self> {
+            unsafe { libc::openlog(std::ptr::null(), libc::LOG_PID, libc::LOG_USER) };
+            Some(SystemLog)
+        }
+
+        pub fn write(&self, level: Level, message: &str) {
+            let priority = match level {
+                Level::Error => libc::LOG_ERR,
+                Level::Info => libc::LOG_INFO,
+            };
+            let Ok(message) = CString::new(message) else {
+                return;
+            };
+            unsafe { libc::syslog(priority, c"%s".as_ptr(), message.as_ptr()) };
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::System::EventLog::{
+        RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+    };
+
+    use super::Level;
+
+    /// Application Event Log, with the executable's name as the source
+    pub struct SystemLog(HANDLE);
+
+    // The handle is only used for ReportEventW, which is thread-safe
+    unsafe impl Send for SystemLog {}
+    unsafe impl Sync for SystemLog {}
+
+    impl SystemLog {
+        pub fn open() -> Option<Self> {
+            let source = std::env::current_exe()
+                .ok()
+                .and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "Application".to_string());
+            let source: Vec<u16> = source.encode_utf16().chain([0]).collect();
+            let handle = unsafe { RegisterEventSourceW(std::ptr::null(), source.as_ptr()) };
+            (!handle.is_null()).then_some(SystemLog(handle))
+        }
+
+        pub fn write(&self, level: Level, message: &str) {
+            let event_type = match level {
+                Level::Error => EVENTLOG_ERROR_TYPE,
+                Level::Info => EVENTLOG_INFORMATION_TYPE,
+            };
+            let message: Vec<u16> = message.encode_utf16().chain([0]).collect();
+            let strings = [message.as_ptr()];
+            unsafe {
+                ReportEventW(
+                    self.0,
+                    event_type,
+                    0,
+                    0,
+                    std::ptr::null_mut(),
+                    1,
+                    0,
+                    strings.as_ptr(),
+                    std::ptr::null(),
+                );
+            }
+        }
+    }
+}
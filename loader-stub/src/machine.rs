@@ -0,0 +1,247 @@
+//! Machine binding (`ConfigFooter::machine_binding`).
+//!
+//! The local fingerprint is `ImageChecksum` over `name=value\n` for each
+//! property selected by the `BIND_*` flags, in flag order: `machine-id`,
+//! `hostname` and `mac` (lowercase `aa:bb:cc:dd:ee:ff`). A machine that
+//! isn't listed, or whose properties can't be read, doesn't start the
+//! payloads; the refusal logs its fingerprint so it can be added to the list.
+
+use weaver_abi::footer::{BIND_HOSTNAME, BIND_MAC, BIND_MACHINE_ID};
+use weaver_abi::ImageChecksum;
+
+use crate::{common, ConfigFooter};
+
+/// Exit unless this machine's fingerprint is listed in the footer
+pub fn enforce(footer: &ConfigFooter) {
+    if footer.machine_binding == 0 {
+        return;
+    }
+    match fingerprint(footer.machine_binding) {
+        Ok(local) if local != 0 && footer.machine_fingerprints.contains(&local) => {}
+        Ok(local) => {
+            common::log_machine_not_allowed(local);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            common::log_machine_fingerprint_failed(&e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// This machine's hostname, for audit records
+pub fn hostname() -> Option<String> {
+    imp::hostname().ok().map(|name| name.trim().to_string())
+}
+
+fn fingerprint(binding: u8) -> Result<u64, String> {
+    let properties: [(u8, &str, fn() -> Result<String, String>); 3] = [
+        (BIND_MACHINE_ID, "machine-id", imp::machine_id),
+        (BIND_HOSTNAME, "hostname", imp::hostname),
+        (BIND_MAC, "mac", mac),
+    ];
+
+    let mut hash = ImageChecksum::new();
+    for (flag, name, read) in properties {
+        if binding & flag == 0 {
+            continue;
+        }
+        let value = read()?;
+        for part in [name.as_bytes(), b"=", value.trim().as_bytes(), b"\n"] {
+            hash.update(part);
+        }
+    }
+    Ok(hash.finish())
+}
+
+/// Lowest physical MAC address, so adding a USB adapter doesn't change it
+fn mac() -> Result<String, String> {
+    let lowest = imp::mac_addresses()?
+        .into_iter()
+        .filter(|mac| mac.iter().any(|&b| b != 0))
+        .min()
+        .ok_or("no physical network interface")?;
+    Ok(lowest.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"))
+}
+
+#[cfg(unix)]
+fn unix_hostname() -> Result<String, String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return Err(format!("gethostname failed: {}", std::io::Error::last_os_error()));
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::fs;
+
+    pub(super) fn machine_id() -> Result<String, String> {
+        fs::read_to_string("/etc/machine-id")
+            .or_else(|_| fs::read_to_string("/var/lib/dbus/machine-id"))
+            .map_err(|e| format!("reading /etc/machine-id failed: {}", e))
+    }
+
+    pub(super) fn hostname() -> Result<String, String> {
+        super::unix_hostname()
+    }
+
+    /// Interfaces backed by a device; bridges, veths and tunnels aren't
+    pub(super) fn mac_addresses() -> Result<Vec<[u8; 6]>, String> {
+        let entries = fs::read_dir("/sys/class/net").map_err(|e| format!("reading /sys/class/net failed: {}", e))?;
+        Ok(entries
+            .flatten()
+            .filter(|entry| entry.path().join("device").exists())
+            .filter_map(|entry| fs::read_to_string(entry.path().join("address")).ok())
+            .filter_map(|address| parse_mac(address.trim()))
+            .collect())
+    }
+
+    fn parse_mac(address: &str) -> Option<[u8; 6]> {
+        let mut mac = [0u8; 6];
+        let mut parts = address.split(':');
+        for byte in &mut mac {
+            *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+        }
+        parts.next().is_none().then_some(mac)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::process::Command;
+
+    use nix::ifaddrs::getifaddrs;
+
+    pub(super) fn machine_id() -> Result<String, String> {
+        let output = Command::new("/usr/sbin/ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+            .map_err(|e| format!("running ioreg failed: {}", e))?;
+        // "IOPlatformUUID" = "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX"
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("\"IOPlatformUUID\""))
+            .and_then(|line| line.rsplit('"').nth(1))
+            .map(str::to_string)
+            .ok_or_else(|| "IOPlatformUUID not found".to_string())
+    }
+
+    pub(super) fn hostname() -> Result<String, String> {
+        super::unix_hostname()
+    }
+
+    /// Built-in Ethernet and Wi-Fi are the `en` interfaces
+    pub(super) fn mac_addresses() -> Result<Vec<[u8; 6]>, String> {
+        let addresses = getifaddrs().map_err(|e| format!("getifaddrs failed: {}", e))?;
+        Ok(addresses
+            .filter(|address| address.interface_name.starts_with("en"))
+            .filter_map(|address| address.address?.as_link_addr()?.addr())
+            .collect())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::ptr;
+
+    use windows_sys::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER, GAA_FLAG_SKIP_MULTICAST,
+        GAA_FLAG_SKIP_UNICAST, IP_ADAPTER_ADDRESSES_LH,
+    };
+    use windows_sys::Win32::Networking::WinSock::AF_UNSPEC;
+    use windows_sys::Win32::System::Registry::{
+        RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ, RRF_SUBKEY_WOW6464KEY,
+    };
+    use windows_sys::Win32::System::SystemInformation::{ComputerNamePhysicalDnsHostname, GetComputerNameExW};
+
+    const IF_TYPE_ETHERNET_CSMACD: u32 = 6;
+    const IF_TYPE_IEEE80211: u32 = 71;
+
+    fn wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain([0]).collect()
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..len])
+    }
+
+    pub(super) fn machine_id() -> Result<String, String> {
+        let (key, value) = (wide(r"SOFTWARE\Microsoft\Cryptography"), wide("MachineGuid"));
+        let mut buf = [0u16; 64];
+        let mut size = std::mem::size_of_val(&buf) as u32;
+        // The 64-bit view, so 32-bit stubs read the same GUID
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                value.as_ptr(),
+                RRF_RT_REG_SZ | RRF_SUBKEY_WOW6464KEY,
+                ptr::null_mut(),
+                buf.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return Err(format!("reading MachineGuid failed: error {}", status));
+        }
+        Ok(from_wide(&buf))
+    }
+
+    pub(super) fn hostname() -> Result<String, String> {
+        let mut buf = [0u16; 256];
+        let mut size = buf.len() as u32;
+        if unsafe { GetComputerNameExW(ComputerNamePhysicalDnsHostname, buf.as_mut_ptr(), &mut size) } == 0 {
+            return Err(format!("GetComputerNameExW failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(from_wide(&buf))
+    }
+
+    /// Ethernet and Wi-Fi adapters
+    pub(super) fn mac_addresses() -> Result<Vec<[u8; 6]>, String> {
+        let flags = GAA_FLAG_SKIP_UNICAST | GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER;
+        let mut size = 16 * 1024u32;
+        let mut buf: Vec<u64> = Vec::new();
+        loop {
+            buf.resize((size as usize).div_ceil(8), 0);
+            let first = buf.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH;
+            match unsafe { GetAdaptersAddresses(AF_UNSPEC as u32, flags, ptr::null(), first, &mut size) } {
+                ERROR_SUCCESS => break,
+                ERROR_BUFFER_OVERFLOW => continue,
+                status => return Err(format!("GetAdaptersAddresses failed: error {}", status)),
+            }
+        }
+
+        let mut macs = Vec::new();
+        let mut adapter = buf.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+        while let Some(current) = unsafe { adapter.as_ref() } {
+            if matches!(current.IfType, IF_TYPE_ETHERNET_CSMACD | IF_TYPE_IEEE80211) && current.PhysicalAddressLength == 6 {
+                let mut mac = [0u8; 6];
+                mac.copy_from_slice(&current.PhysicalAddress[..6]);
+                macs.push(mac);
+            }
+            adapter = current.Next;
+        }
+        Ok(macs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable_and_depends_on_binding() {
+        let Ok(hostname_only) = fingerprint(BIND_HOSTNAME) else {
+            return;
+        };
+        assert_eq!(fingerprint(BIND_HOSTNAME), Ok(hostname_only));
+        if let Ok(both) = fingerprint(BIND_HOSTNAME | BIND_MACHINE_ID) {
+            assert_ne!(both, hostname_only);
+        }
+    }
+}
@@ -1,48 +1,61 @@
 use std::ffi::CString;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{Read, Write};
 use std::mem;
-use std::os::unix::fs::PermissionsExt;
-use std::path::PathBuf;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
-use nix::fcntl::OFlag;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
 use nix::sys::mman::{mmap, shm_open, shm_unlink, MapFlags, ProtFlags};
 use nix::sys::signal::{kill, Signal};
 use nix::sys::stat::Mode;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-use nix::unistd::{execv, fork, getpid, sleep, ForkResult, Pid};
+use nix::unistd::{execv, fork, getpid, mkdtemp, pipe, sleep, ForkResult, Pid};
 
 use crate::common::{
-    self, evaluate_health_status, force_kill_delay, health_check_interval, init_health_status,
+    self, evaluate_health_table, force_kill_delay, health_check_interval, init_health_table,
     log_async_mode_started, log_base_completed_terminating_overload, log_base_exited,
-    log_base_killed_by_signal, log_base_start_failed, log_fallback_kill, log_grace_period_exceeded,
-    log_health_monitor_started, log_health_monitoring_enabled, log_heartbeat_lost,
-    log_network_failure_threshold, log_overload_requested_kill, log_overload_start_failed,
+    log_base_killed_by_signal, log_base_start_failed, log_fallback_kill,
+    log_health_monitor_started, log_health_monitoring_enabled,
+    log_overload_start_failed, log_readiness_failed,
     log_overload_terminated_abnormally, log_shm_create_failed, log_shm_map_failed,
     log_starting_base, log_sync_mode_waiting, log_verification_failed, log_verification_successful,
     overload_kill_wait_duration, should_enable_health_monitoring, signal_overload_to_kill,
-    HealthCheckResult,
+    HealthCheckResult, KillPolicy, MonitorArming,
 };
-use crate::{ConfigFooter, HealthStatus};
+use crate::privileges::{Account, Privileges};
+use crate::readiness::Readiness;
+use crate::{control, crash, instance, ipc, logging, output, priority, shutdown, ConfigFooter, HealthTable};
+use weaver_abi::footer::{ORDER_BASE_FIRST, OVERLOAD_LIBRARY, OVERLOAD_SCRIPT};
 
 pub fn run(
     base_data: Vec<u8>,
     overload_data: Vec<u8>,
+    interpreter: Option<Vec<u8>>,
     footer: ConfigFooter,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let sync_mode = footer.sync_mode != 0;
-    let grace_period = footer.grace_period;
+    let sync_mode = footer.prerequisite_runs() > 0;
     let network_failure_kill_count = footer.network_failure_kill_count;
+    let kill_policy = KillPolicy::from_footer(&footer);
+    let mut arming = MonitorArming::from_footer(&footer);
+    let check_interval = health_check_interval(footer.health_check_interval);
+    let kill_delay = force_kill_delay(footer.kill_delay_ms);
+    let base_first = footer.order == ORDER_BASE_FIRST;
+    let scheduled = footer.is_scheduled();
+    let overload_script = footer.overload_kind == OVERLOAD_SCRIPT;
+    let overload_library = footer.overload_kind == OVERLOAD_LIBRARY;
+    let privileges = Privileges::from_footer(&footer)?;
 
     // 1. Setup Shared Memory (if async and monitoring needed)
-    let mut health_ptr: *mut HealthStatus = ptr::null_mut();
+    let mut health_ptr: *mut HealthTable = ptr::null_mut();
     let mut shm_name_str = String::new();
 
-    if should_enable_health_monitoring(sync_mode, grace_period, network_failure_kill_count) {
+    if !base_first && !scheduled && !overload_library && should_enable_health_monitoring(sync_mode, &kill_policy, &footer) {
         let pid = getpid();
         shm_name_str = format!("/overload_health_{}", pid);
         let shm_name_c = CString::new(shm_name_str.clone()).unwrap();
@@ -53,12 +66,16 @@ pub fn run(
             Mode::from_bits_truncate(0o600),
         ) {
             Ok(fd) => {
-                let _ = nix::unistd::ftruncate(&fd, mem::size_of::<HealthStatus>() as i64);
+                let _ = nix::unistd::ftruncate(&fd, HealthTable::SIZE as i64);
+                if let Some(account) = privileges.account() {
+                    // The overload reopens the region by name once it runs as that account
+                    let _ = nix::unistd::fchown(&fd, Some(account.uid), Some(account.gid));
+                }
 
                 unsafe {
                     let ptr = mmap(
                         None,
-                        std::num::NonZeroUsize::new(mem::size_of::<HealthStatus>()).unwrap(),
+                        std::num::NonZeroUsize::new(HealthTable::SIZE).unwrap(),
                         ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
                         MapFlags::MAP_SHARED,
                         &fd,
@@ -67,8 +84,9 @@ pub fn run(
 
                     match ptr {
                         Ok(p) => {
-                            health_ptr = p.as_ptr() as *mut HealthStatus;
-                            init_health_status(health_ptr);
+                            health_ptr = p.as_ptr() as *mut HealthTable;
+                            init_health_table(health_ptr);
+                            control::set_health(health_ptr);
                             std::env::set_var("KILLCODE_HEALTH_SHM", &shm_name_str);
                             log_health_monitoring_enabled(&shm_name_str);
                         }
@@ -81,27 +99,45 @@ pub fn run(
     }
 
     // 2. Prepare binaries (Write to temp files)
-    let temp_dir = std::env::temp_dir();
-    let pid = getpid();
-    let base_path = temp_dir.join(format!("base_{}", pid));
-    let overload_path = temp_dir.join(format!("overload_{}", pid));
+    // root's TMPDIR is private to root, so payloads running as another
+    // account are written to the shared /tmp instead. Either way they go
+    // into a fresh 0700 directory that only the payloads' account can enter.
+    let temp_dir = if privileges.account().is_some() { PathBuf::from("/tmp") } else { std::env::temp_dir() };
+    let payload_dir = PayloadDir::create(&temp_dir, privileges.account())?;
+    let cleanup_path = payload_dir.path.clone();
+    shutdown::on_exit(move || {
+        let _ = fs::remove_dir_all(&cleanup_path);
+    });
+    let base_path = payload_dir.path.join(common::payload_name(&footer, true));
+    let overload_path = payload_dir.path.join(common::payload_name(&footer, false));
 
-    eprintln!("[KillCode] Writing base binary ({} bytes) to: {}", base_data.len(), base_path.display());
-    eprintln!("[KillCode] Writing overload binary ({} bytes) to: {}", overload_data.len(), overload_path.display());
+    logging::info!("Writing base binary ({} bytes) to: {}", base_data.len(), base_path.display());
+    logging::info!("Writing overload binary ({} bytes) to: {}", overload_data.len(), overload_path.display());
 
-    // Helper to write and make executable
+    // Helper to write and make executable (by the payloads' account only)
     let write_binary = |path: &PathBuf, data: &[u8]| -> Result<(), std::io::Error> {
-        let mut file = File::create(path)?;
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).mode(0o700).open(path)?;
         file.write_all(data)?;
-        let mut perms = file.metadata()?.permissions();
-        perms.set_mode(0o755);
-        file.set_permissions(perms)?;
+        file.set_permissions(fs::Permissions::from_mode(0o700))?;
+        if let Some(account) = privileges.account() {
+            nix::unistd::fchown(&file, Some(account.uid), Some(account.gid))?;
+        }
         Ok(())
     };
 
     write_binary(&base_path, &base_data)?;
     write_binary(&overload_path, &overload_data)?;
 
+    // A script overload runs under the bundled interpreter instead of its #! line
+    let interpreter_path = match interpreter.filter(|_| overload_script) {
+        Some(interpreter) => {
+            let path = payload_dir.path.join("interpreter");
+            write_binary(&path, &interpreter)?;
+            Some(path)
+        }
+        None => None,
+    };
+
     // Ad-hoc codesign binaries (required on macOS arm64)
     // 
     // On Apple Silicon (M1/M2/M3), ALL executable code must be signed before
@@ -119,18 +155,72 @@ pub fn run(
             .output();
     };
     codesign(&base_path);
-    codesign(&overload_path);
+    if !overload_script {
+        codesign(&overload_path);
+    }
+    if let Some(ref path) = interpreter_path {
+        codesign(path);
+    }
+
+    let base_cwd = common::payload_cwd(&footer, true);
+    let overload_cwd = common::payload_cwd(&footer, false);
 
     // Helper to execute binary
     // Returns: Ok(Pid) if child started
-    let execute_binary = |path: &PathBuf, name: &str| -> Result<Pid, String> {
+    //
+    // Every payload runs once except a scheduled overload, so the file is
+    // unlinked as soon as the child has exec'd it; the running process keeps
+    // the image. A close-on-exec pipe tells the parent when that happened.
+    // A script is only opened by its interpreter after the exec, so it stays
+    // until the payload directory is removed.
+    let execute_binary = move |path: &PathBuf, name: &str| -> Result<Pid, String> {
+        let (exec_read, exec_write) = pipe().map_err(|e| format!("pipe failed: {}", e))?;
+        fcntl(&exec_write, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(|e| format!("fcntl failed: {}", e))?;
+        ipc::export(name == "base");
         match unsafe { fork() } {
-            Ok(ForkResult::Parent { child }) => Ok(child),
+            Ok(ForkResult::Parent { child }) => {
+                drop(exec_write);
+                common::place_in_own_process_group(child.as_raw());
+                shutdown::track(child.as_raw());
+                if name == "base" {
+                    instance::set_base_pid(child.as_raw());
+                    output::started(true);
+                    crash::base_started();
+                }
+                // EOF once the child has exec'd (or exited)
+                let _ = File::from(exec_read).read(&mut [0u8; 1]);
+                if name == "base" || !(scheduled || overload_script) {
+                    let _ = fs::remove_file(path);
+                }
+                Ok(child)
+            }
             Ok(ForkResult::Child) => {
+                drop(exec_read);
+                ipc::enter(name == "base");
+                output::enter(name == "base");
+                crash::enter(name == "base");
+                // Payloads the stub waits on in the foreground keep the terminal
+                unsafe { common::enter_own_process_group(name == "base" || sync_mode || base_first) };
+                // Before dropping privileges, which a negative nice value needs
+                if let Err(e) = priority::apply(footer.priority(name == "base"), footer.cpu_affinity(name == "base")) {
+                    common::log_priority_failed(&e);
+                }
+                if let Err(e) = privileges.apply() {
+                    common::log_privileges_failed(&e);
+                    std::process::exit(1);
+                }
+                let is_base = name == "base";
+                let cwd = if is_base { &base_cwd } else { &overload_cwd };
+                if let Err(e) = common::enter_cwd_and_umask(cwd.as_deref(), footer.umask(is_base)) {
+                    common::log_cwd_failed(&e);
+                    std::process::exit(1);
+                }
                 let path_c = CString::new(path.to_str().unwrap()).unwrap();
-                let name_c = CString::new(name).unwrap();
-                let args = [name_c];
-                let _ = execv(&path_c, &args);
+                let name_c = CString::new(common::payload_name(&footer, name == "base")).unwrap();
+                let _ = match interpreter_path.as_ref().filter(|_| !is_base) {
+                    Some(interpreter) => execv(&CString::new(interpreter.to_str().unwrap()).unwrap(), &[name_c, path_c]),
+                    None => execv(&path_c, &[name_c]),
+                };
                 common::log_execv_failed();
                 std::process::exit(1);
             }
@@ -138,46 +228,174 @@ pub fn run(
         }
     };
 
+    // Library overload: base runs alone and dyld inserts the dylib into it
+    if overload_library {
+        common::log_overload_preloaded();
+        let mut inserted = overload_path.clone().into_os_string();
+        if let Some(existing) = std::env::var_os("DYLD_INSERT_LIBRARIES").filter(|existing| !existing.is_empty()) {
+            inserted.push(":");
+            inserted.push(existing);
+        }
+        std::env::set_var("DYLD_INSERT_LIBRARIES", inserted);
+        log_starting_base();
+        let base_exit_code = match execute_binary(&base_path, "base") {
+            Ok(child) => wait_for_base_exit(child),
+            Err(e) => {
+                log_base_start_failed(&e);
+                1
+            }
+        };
+        log_base_exited(base_exit_code);
+        payload_dir.remove();
+        std::process::exit(base_exit_code);
+    }
+
+    // Base-first order: base runs to completion, then the overload, no monitoring
+    if base_first {
+        common::log_base_first();
+        log_starting_base();
+        let base_exit_code = match execute_binary(&base_path, "base") {
+            Ok(child) => wait_for_base_exit(child),
+            Err(e) => {
+                log_base_start_failed(&e);
+                1
+            }
+        };
+        log_base_exited(base_exit_code);
+
+        match execute_binary(&overload_path, "overload") {
+            Ok(child) => {
+                let code = wait_for_exit(child);
+                if code != 0 {
+                    common::log_overload_after_base_failed(&format!("exit code {}", code));
+                }
+            }
+            Err(e) => common::log_overload_after_base_failed(&e),
+        }
+
+        payload_dir.remove();
+        std::process::exit(base_exit_code);
+    }
+
+    // Scheduled overload: base starts right away, the overload runs on its delay/interval
+    if scheduled {
+        common::log_overload_scheduled(footer.overload_start_delay, footer.overload_interval);
+        log_starting_base();
+        let base_pid = match execute_binary(&base_path, "base") {
+            Ok(child) => child,
+            Err(e) => {
+                log_base_start_failed(&e);
+                return Err(e.into());
+            }
+        };
+
+        common::export_base_pid(base_pid);
+        let running_overload = Arc::new(AtomicI32::new(0));
+        let running = running_overload.clone();
+        let scheduled_path = overload_path.clone();
+        let (start_delay, interval) = (footer.overload_start_delay, footer.overload_interval);
+        thread::spawn(move || {
+            common::run_overload_schedule(
+                start_delay,
+                interval,
+                network_failure_kill_count,
+                || {
+                    let pid = execute_binary(&scheduled_path, "overload")?;
+                    running.store(pid.as_raw(), Ordering::Relaxed);
+                    let code = wait_for_exit(pid);
+                    running.store(0, Ordering::Relaxed);
+                    Ok(code)
+                },
+                || kill(base_pid, None).is_ok(),
+                || kill_base(base_pid.as_raw(), kill_delay),
+            )
+        });
+
+        let base_exit_code = wait_for_base_exit(base_pid);
+        let overload_pid = running_overload.swap(0, Ordering::Relaxed);
+        if overload_pid > 0 {
+            log_base_completed_terminating_overload(overload_pid);
+            common::signal_tree(overload_pid, Signal::SIGTERM);
+        }
+
+        payload_dir.remove();
+        log_base_exited(base_exit_code);
+        std::process::exit(base_exit_code);
+    }
+
     // 3. Start Overload
-    let overload_pid = match execute_binary(&overload_path, "overload") {
-        Ok(pid) => {
-            if sync_mode {
-                log_sync_mode_waiting(pid);
-                match waitpid(pid, None) {
-                    Ok(WaitStatus::Exited(_, code)) => {
-                        if code != 0 {
-                            log_verification_failed(code);
-                            let _ = fs::remove_file(&base_path);
-                            let _ = fs::remove_file(&overload_path);
-                            if !shm_name_str.is_empty() {
-                                let _ = shm_unlink(shm_name_str.as_str());
-                            }
-                            return Err("Overload verification failed".into());
-                        }
-                        log_verification_successful();
-                        let _ = fs::remove_file(&overload_path);
-                        None
-                    }
-                    _ => {
-                        log_overload_terminated_abnormally();
-                        return Err("Overload terminated abnormally".into());
-                    }
+    let mut overload_pid = if sync_mode {
+        let verified = common::run_prerequisites(&footer, || {
+            let pid = execute_binary(&overload_path, "overload").inspect_err(|e| log_overload_start_failed(e))?;
+            log_sync_mode_waiting(pid);
+            let status = waitpid(pid, None);
+            shutdown::untrack(pid.as_raw());
+            common::reclaim_terminal();
+            match status {
+                Ok(WaitStatus::Exited(_, 0)) => {
+                    log_verification_successful();
+                    Ok(())
+                }
+                Ok(WaitStatus::Exited(_, code)) => {
+                    log_verification_failed(code);
+                    Err(format!("Overload verification failed with code {}", code))
                 }
-            } else {
+                _ => {
+                    log_overload_terminated_abnormally();
+                    Err("Overload terminated abnormally".to_string())
+                }
+            }
+        });
+        if let Err(e) = verified {
+            if !shm_name_str.is_empty() {
+                let _ = shm_unlink(shm_name_str.as_str());
+            }
+            return Err(e.into());
+        }
+        None
+    } else {
+        match execute_binary(&overload_path, "overload") {
+            Ok(pid) => {
                 log_async_mode_started(pid);
                 Some(pid)
             }
+            Err(e) => {
+                log_overload_start_failed(&e);
+                return Err(e.into());
+            }
         }
-        Err(e) => {
-            log_overload_start_failed(&e);
+    };
+
+    // 3b. Wait for a sidecar overload to be ready
+    if let (false, Some(readiness)) = (sync_mode, Readiness::from_footer(&footer)) {
+        let mut overload_exited = false;
+        let ready = readiness.wait(health_ptr, || {
+            overload_exited = overload_pid
+                .is_none_or(|pid| !matches!(waitpid(pid, Some(WaitPidFlag::WNOHANG)), Ok(WaitStatus::StillAlive)));
+            !overload_exited
+        });
+        if overload_exited {
+            overload_pid = None;
+        }
+        if let Err(e) = ready {
+            log_readiness_failed(&e);
+            if let Some(ov_pid) = overload_pid {
+                common::signal_tree(ov_pid.as_raw(), Signal::SIGTERM);
+                sleep(1);
+                common::signal_tree(ov_pid.as_raw(), Signal::SIGKILL);
+                let _ = waitpid(ov_pid, None);
+            }
+            if !shm_name_str.is_empty() {
+                let _ = shm_unlink(shm_name_str.as_str());
+            }
             return Err(e.into());
         }
-    };
+    }
 
     // 4. Start Health Monitor Thread
     let monitor_handle = if !sync_mode
         && !health_ptr.is_null()
-        && (grace_period > 0 || network_failure_kill_count > 0)
+        && kill_policy.is_active()
     {
         let base_pid_cell = Arc::new(AtomicI32::new(0));
         let base_pid_clone = base_pid_cell.clone();
@@ -186,9 +404,9 @@ pub fn run(
         Some((
             thread::spawn(move || {
                 log_health_monitor_started();
-                let health_ptr = health_ptr_addr as *mut HealthStatus;
+                let health_ptr = health_ptr_addr as *mut HealthTable;
                 loop {
-                    thread::sleep(health_check_interval());
+                    thread::sleep(check_interval);
 
                     let base_pid = base_pid_clone.load(Ordering::Relaxed);
                     if base_pid <= 0 {
@@ -200,29 +418,22 @@ pub fn run(
                     }
 
                     unsafe {
-                        match evaluate_health_status(health_ptr, grace_period, network_failure_kill_count) {
+                        let (slot, result) = evaluate_health_table(health_ptr, &kill_policy, &mut arming);
+                        match result {
                             HealthCheckResult::Ok => {}
-                            HealthCheckResult::GracePeriodExceeded { time_since_success, grace_period } => {
-                                log_grace_period_exceeded(time_since_success, grace_period);
-                                kill_base(base_pid);
+                            HealthCheckResult::KillBase => {
+                                kill_base(base_pid, kill_delay);
                                 break;
                             }
-                            HealthCheckResult::NetworkFailureThreshold { failures, threshold } => {
-                                log_network_failure_threshold(failures, threshold);
-                                signal_overload_to_kill(health_ptr);
+                            HealthCheckResult::KillOverload => {
+                                signal_overload_to_kill(health_ptr, slot);
                                 thread::sleep(overload_kill_wait_duration());
                                 log_fallback_kill();
-                                kill_base(base_pid);
-                                break;
-                            }
-                            HealthCheckResult::OverloadRequestedKill => {
-                                log_overload_requested_kill();
-                                kill_base(base_pid);
+                                kill_base(base_pid, kill_delay);
                                 break;
                             }
-                            HealthCheckResult::HeartbeatLost => {
-                                log_heartbeat_lost();
-                                kill_base(base_pid);
+                            HealthCheckResult::Exit => {
+                                shutdown::request();
                                 break;
                             }
                         }
@@ -239,16 +450,21 @@ pub fn run(
     log_starting_base();
     let base_exit_code = match execute_binary(&base_path, "base") {
         Ok(child) => {
+            common::record_base_started();
             if let Some((_, ref pid_cell)) = monitor_handle {
                 pid_cell.store(child.as_raw(), Ordering::Relaxed);
             }
 
             if !health_ptr.is_null() {
-                unsafe { (*health_ptr).base_pid = child.as_raw(); }
+                unsafe { (*health_ptr).set_base_pid(child.as_raw()); }
             }
 
             let mut status_code = -1;
-            match waitpid(child, None) {
+            let status = waitpid(child, None);
+            shutdown::untrack(child.as_raw());
+            output::base_exited();
+            crash::base_waited(&status);
+            match status {
                 Ok(WaitStatus::Exited(_, code)) => status_code = code,
                 Ok(WaitStatus::Signaled(_, sig, _)) => {
                     log_base_killed_by_signal(sig);
@@ -259,11 +475,11 @@ pub fn run(
 
             if let Some(ov_pid) = overload_pid {
                 log_base_completed_terminating_overload(ov_pid);
-                let _ = kill(ov_pid, Signal::SIGTERM);
+                common::signal_tree(ov_pid.as_raw(), Signal::SIGTERM);
                 sleep(1);
                 match waitpid(ov_pid, Some(WaitPidFlag::WNOHANG)) {
                     Ok(WaitStatus::StillAlive) => {
-                        let _ = kill(ov_pid, Signal::SIGKILL);
+                        common::signal_tree(ov_pid.as_raw(), Signal::SIGKILL);
                         let _ = waitpid(ov_pid, None);
                     }
                     _ => {}
@@ -281,10 +497,7 @@ pub fn run(
         let _ = handle.join();
     }
 
-    let _ = fs::remove_file(&base_path);
-    if !sync_mode {
-        let _ = fs::remove_file(&overload_path);
-    }
+    payload_dir.remove();
     if !shm_name_str.is_empty() {
         let _ = shm_unlink(shm_name_str.as_str());
     }
@@ -293,9 +506,63 @@ pub fn run(
     std::process::exit(base_exit_code);
 }
 
-/// Kill base process with SIGTERM followed by SIGKILL
-fn kill_base(base_pid: i32) {
-    let _ = kill(Pid::from_raw(base_pid), Signal::SIGTERM);
-    thread::sleep(force_kill_delay());
-    let _ = kill(Pid::from_raw(base_pid), Signal::SIGKILL);
+/// Private directory (mkdtemp, mode 0700) holding one run's payload files
+///
+/// Removed on every exit path: explicitly before `process::exit`, on drop
+/// when `run` returns an error, and through `shutdown::on_exit` for signals.
+struct PayloadDir {
+    path: PathBuf,
+}
+
+impl PayloadDir {
+    fn create(parent: &Path, owner: Option<&Account>) -> nix::Result<Self> {
+        let path = mkdtemp(&parent.join("XXXXXXXXXX"))?;
+        let dir = PayloadDir { path };
+        if let Some(account) = owner {
+            // The payloads must be able to reach their files once they've dropped root
+            nix::unistd::chown(&dir.path, Some(account.uid), Some(account.gid))?;
+        }
+        Ok(dir)
+    }
+
+    fn remove(&self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+impl Drop for PayloadDir {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Wait for a child and return its exit code (-1 if killed by a signal)
+fn wait_for_exit(child: Pid) -> i32 {
+    let status = waitpid(child, None);
+    shutdown::untrack(child.as_raw());
+    common::reclaim_terminal();
+    match status {
+        Ok(WaitStatus::Exited(_, code)) => code,
+        _ => -1,
+    }
+}
+
+/// Wait for base like `wait_for_exit`, reporting it if it crashed
+fn wait_for_base_exit(base: Pid) -> i32 {
+    let status = waitpid(base, None);
+    shutdown::untrack(base.as_raw());
+    common::reclaim_terminal();
+    output::base_exited();
+    crash::base_waited(&status);
+    match status {
+        Ok(WaitStatus::Exited(_, code)) => code,
+        _ => -1,
+    }
+}
+
+/// Kill base and its process group with SIGTERM followed by SIGKILL after `kill_delay`
+fn kill_base(base_pid: i32, kill_delay: Duration) {
+    common::signal_tree(base_pid, Signal::SIGTERM);
+    thread::sleep(kill_delay);
+    common::signal_tree(base_pid, Signal::SIGKILL);
 }
@@ -1,84 +1,147 @@
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
-use std::mem;
 
+mod assets;
+mod audit;
 mod common;
+mod control;
+mod crash;
+mod expiry;
+mod hardening;
+mod http;
+mod install;
+mod instance;
+mod ipc;
+mod logging;
+mod machine;
+mod output;
+mod priority;
+mod readiness;
+mod revocation;
+mod shutdown;
 
+#[cfg(target_os = "linux")]
+mod exec_fd;
+#[cfg(target_os = "linux")]
+mod jail;
+#[cfg(target_os = "linux")]
+mod limits;
 #[cfg(target_os = "linux")]
 mod linux;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod privileges;
+#[cfg(target_os = "linux")]
+mod seccomp;
 #[cfg(target_os = "windows")]
 mod windows;
 #[cfg(target_os = "macos")]
 mod macos;
 
-const MAGIC_BYTES: &[u8; 8] = b"KILLCODE";
+/// Defaults used when the footer leaves the timing fields at 0
 const HEALTH_CHECK_INTERVAL: u32 = 5;
+const FORCE_KILL_DELAY_MS: u32 = 100;
+const SHUTDOWN_GRACE_SECS: u32 = 5;
+const REVOCATION_INTERVAL: u32 = 300;
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct ConfigFooter {
-    pub magic: [u8; 8],
-    pub base_offset: u64,
-    pub base_size: u64,
-    pub overload_offset: u64,
-    pub overload_size: u64,
-    pub grace_period: u32,
-    pub sync_mode: u8, // 0 or 1
-    pub network_failure_kill_count: u32,
-}
+pub use weaver_abi::{ConfigFooter, HealthSlot, HealthStatus, HealthTable};
 
-#[repr(C)]
-pub struct HealthStatus {
-    pub last_success: i64,          // Timestamp of last successful check (time_t)
-    pub consecutive_failures: i32,  // Counter of network failures
-    pub is_alive: i32,              // Heartbeat flag (1=alive, 0=dead)
-    pub should_kill_base: i32,      // Signal from overload to kill base
-    pub parent_requests_kill: i32,  // Signal from parent: kill yourself now
-    pub base_pid: i32,              // PID of the base process
-}
+/// Tells Weaver which footer layout this stub reads; `validate_stub` looks
+/// for it before accepting the stub
+#[used]
+static STUB_MARKER: [u8; weaver_abi::footer::STUB_MARKER_LEN] = weaver_abi::footer::stub_marker();
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Keep the marker in the executable through LTO and section GC
+    std::hint::black_box(&STUB_MARKER);
+
     // 1. Read self
     let mut self_file = File::open(std::env::current_exe()?)?;
     let file_len = self_file.metadata()?.len();
 
-    if file_len < mem::size_of::<ConfigFooter>() as u64 {
+    if file_len < ConfigFooter::SIZE as u64 {
         return Err("File too small to contain footer".into());
     }
 
     // 2. Read footer
-    self_file.seek(SeekFrom::End(-(mem::size_of::<ConfigFooter>() as i64)))?;
-    let mut footer_bytes = [0u8; mem::size_of::<ConfigFooter>()];
+    self_file.seek(SeekFrom::End(-(ConfigFooter::SIZE as i64)))?;
+    let mut footer_bytes = [0u8; ConfigFooter::SIZE];
     self_file.read_exact(&mut footer_bytes)?;
 
-    let footer: ConfigFooter = unsafe { mem::transmute(footer_bytes) };
+    let footer = ConfigFooter::from_bytes(&footer_bytes)
+        .ok_or("No footer of this stub's layout version: invalid magic, or merged by another Weaver build")?;
 
-    if &footer.magic != MAGIC_BYTES {
-        return Err("Invalid magic bytes in footer".into());
+    logging::init(&footer);
+    audit::init(&footer);
+    logging::info!("V2 Stub execution starting");
+    logging::info!("Config: sync={}, grace_period={}s, failure_threshold={}",
+             footer.sync_mode, footer.grace_period, footer.network_failure_kill_count);
+
+    if footer.hardening != 0 {
+        if let Err(e) = hardening::check_startup(&mut self_file, &footer_bytes, &footer) {
+            common::log_tampering_detected(&e);
+            std::process::exit(1);
+        }
     }
 
-    eprintln!("[KillCode] V2 Stub execution starting");
-    eprintln!("[KillCode] Config: sync={}, grace_period={}s, failure_threshold={}", 
-             footer.sync_mode, footer.grace_period, footer.network_failure_kill_count);
+    machine::enforce(&footer);
+    instance::enforce(&footer);
+    shutdown::install(footer.shutdown_grace);
+    expiry::enforce(&footer);
+    control::start(&footer);
+    ipc::start(&footer);
+    output::start(&footer);
+    crash::start(&footer);
+    revocation::start(&footer);
+    hardening::start_watchdog(&footer);
+
+    // 3. Read binaries; an installer runs base from its install dir
+    let base_data = match install::prepare(&footer, &mut self_file) {
+        Ok(Some(installed)) => installed,
+        Ok(None) => install::read_payload(&mut self_file, &footer, true)?,
+        Err(e) => {
+            common::log_install_failed(&e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = assets::extract(&footer, &mut self_file) {
+        common::log_assets_failed(&e);
+        std::process::exit(1);
+    }
+
+    let overload_data = install::read_payload(&mut self_file, &footer, false)?;
 
-    // 3. Read binaries
-    let mut base_data = vec![0u8; footer.base_size as usize];
-    self_file.seek(SeekFrom::Start(footer.base_offset))?;
-    self_file.read_exact(&mut base_data)?;
+    // Only a script overload comes with an interpreter, and only Unix stubs run scripts
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    let interpreter = if footer.interpreter_size > 0 {
+        let mut interpreter = vec![0u8; footer.interpreter_size as usize];
+        self_file.seek(SeekFrom::Start(footer.interpreter_offset))?;
+        self_file.read_exact(&mut interpreter)?;
+        Some(interpreter)
+    } else {
+        None
+    };
 
-    let mut overload_data = vec![0u8; footer.overload_size as usize];
-    self_file.seek(SeekFrom::Start(footer.overload_offset))?;
-    self_file.read_exact(&mut overload_data)?;
+    // Seccomp profiles are only honored on Linux
+    #[cfg(target_os = "linux")]
+    let seccomp_profile = if footer.seccomp_size > 0 {
+        let mut profile = vec![0u8; footer.seccomp_size as usize];
+        self_file.seek(SeekFrom::Start(footer.seccomp_offset))?;
+        self_file.read_exact(&mut profile)?;
+        Some(profile)
+    } else {
+        None
+    };
 
     // Dispatch to OS-specific implementation
     #[cfg(target_os = "linux")]
-    return linux::run(base_data, overload_data, footer);
+    return linux::run(base_data, overload_data, interpreter, seccomp_profile, footer);
 
     #[cfg(target_os = "windows")]
     return windows::run(base_data, overload_data, footer);
 
     #[cfg(target_os = "macos")]
-    return macos::run(base_data, overload_data, footer);
+    return macos::run(base_data, overload_data, interpreter, footer);
 
     #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
     return Err("Unsupported platform".into());
@@ -0,0 +1,370 @@
+//! Payload output logs (`ConfigFooter::output_log`, `output_log_dir`).
+//!
+//! A payload whose output is logged gets pipes for its stdout and stderr.
+//! The stub copies everything to its own stdout and stderr as it arrives and
+//! appends it to `base.log` or `overload.log` in the log directory, both
+//! streams into one file in the order they are read. Once a log reaches
+//! `output_log_max_size` MiB it is renamed to `base.log.1` (shifting older
+//! ones up) and a new one is started; past `output_log_max_files` the oldest
+//! is deleted. A log that can't be written is given up with a warning, and
+//! the output still reaches the console.
+//!
+//! When base's stderr is logged, crash reports get its tail from here rather
+//! than from a pipe of their own.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use weaver_abi::footer::MAX_OUTPUT_LOG_FILES;
+
+use crate::{common, crash, ConfigFooter};
+
+const BASE: usize = 0;
+const OVERLOAD: usize = 1;
+const NAMES: [&str; 2] = ["base", "overload"];
+const STDOUT: usize = 0;
+const STDERR: usize = 1;
+/// How long base's exit waits for the rest of its output
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Base's log, then the overload's
+static LOGS: [OnceLock<Mutex<RotatingLog>>; 2] = [const { OnceLock::new() }; 2];
+/// Base's streams the stub is still reading
+static BASE_OPEN: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+/// Set up the pipes and logs for the payloads the footer asks for
+///
+/// Failing to is logged and otherwise ignored; the payloads then write to
+/// the console as usual.
+pub fn start(footer: &ConfigFooter) {
+    if !footer.logs_output(true) && !footer.logs_output(false) {
+        return;
+    }
+    let dir = match log_dir(footer.output_log_dir()) {
+        Ok(dir) => dir,
+        Err(e) => {
+            common::log_output_capture_failed("payloads", &e);
+            return;
+        }
+    };
+    let max_size = footer.output_log_max_size as u64 * 1024 * 1024;
+    let max_files = footer.output_log_max_files.clamp(1, MAX_OUTPUT_LOG_FILES);
+    for payload in [BASE, OVERLOAD] {
+        if !footer.logs_output(payload == BASE) {
+            continue;
+        }
+        if let Err(e) = capture(payload, &dir, max_size, max_files) {
+            imp::close_write_ends(payload);
+            common::log_output_capture_failed(NAMES[payload], &e);
+        }
+    }
+}
+
+/// Whether base's (or the overload's) output goes through the stub
+pub fn is_capturing(is_base: bool) -> bool {
+    LOGS[index(is_base)].get().is_some()
+}
+
+/// Close the stub's copies of base's pipes once base has its own, so the
+/// copies see EOF when base exits; the overload's stay for its next run
+pub fn started(is_base: bool) {
+    if is_base {
+        imp::close_write_ends(BASE);
+    } else {
+        imp::keep_write_ends(OVERLOAD);
+    }
+}
+
+/// Give the copies of base's output a moment to catch up before the stub
+/// exits (a process base started may still hold the pipes open)
+pub fn base_exited() {
+    if !is_capturing(true) {
+        return;
+    }
+    let open = BASE_OPEN.0.lock().unwrap_or_else(PoisonError::into_inner);
+    let _ = BASE_OPEN.1.wait_timeout_while(open, DRAIN_TIMEOUT, |open| *open > 0);
+}
+
+#[cfg(unix)]
+pub use imp::enter;
+
+#[cfg(target_os = "windows")]
+pub use imp::redirect;
+
+fn index(is_base: bool) -> usize {
+    if is_base {
+        BASE
+    } else {
+        OVERLOAD
+    }
+}
+
+/// The log directory, relative to the merged binary's unless absolute, created if missing
+fn log_dir(dir: Option<&str>) -> Result<PathBuf, String> {
+    let dir = match dir.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        dir => {
+            let exe = std::env::current_exe().map_err(|e| format!("the merged binary's path is unknown: {}", e))?;
+            let exe_dir = exe.parent().ok_or("the merged binary's path has no directory")?;
+            dir.map_or_else(|| exe_dir.to_path_buf(), |dir| exe_dir.join(dir))
+        }
+    };
+    fs::create_dir_all(&dir).map_err(|e| format!("creating {} failed: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn capture(payload: usize, dir: &Path, max_size: u64, max_files: u8) -> Result<(), String> {
+    let path = dir.join(format!("{}.log", NAMES[payload]));
+    let log = RotatingLog::open(path.clone(), max_size, max_files)
+        .map_err(|e| format!("opening {} failed: {}", path.display(), e))?;
+    let readers = [imp::pipe(payload, STDOUT)?, imp::pipe(payload, STDERR)?];
+    let _ = LOGS[payload].set(Mutex::new(log));
+    if payload == BASE {
+        *BASE_OPEN.0.lock().unwrap_or_else(PoisonError::into_inner) = readers.len();
+    }
+    for (stream, reader) in readers.into_iter().enumerate() {
+        thread::spawn(move || copy(reader, payload, stream));
+    }
+    Ok(())
+}
+
+/// Copy one of a payload's streams to the console and its log until every
+/// writer is gone
+fn copy(mut reader: File, payload: usize, stream: usize) {
+    let mut buffer = [0u8; 4096];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                let bytes = &buffer[..n];
+                let _ = match stream {
+                    STDOUT => write_flushed(io::stdout().lock(), bytes),
+                    _ => write_flushed(io::stderr().lock(), bytes),
+                };
+                if let Some(log) = LOGS[payload].get() {
+                    log.lock().unwrap_or_else(PoisonError::into_inner).write(bytes);
+                }
+                if payload == BASE && stream == STDERR {
+                    crash::stderr_written(bytes);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    if payload == BASE {
+        if stream == STDERR {
+            crash::stderr_closed();
+        }
+        *BASE_OPEN.0.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        BASE_OPEN.1.notify_all();
+    }
+}
+
+fn write_flushed(mut out: impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(bytes)?;
+    out.flush()
+}
+
+/// A log file rotated by size: `<path>`, then `<path>.1` up to `<path>.<max_files - 1>`
+struct RotatingLog {
+    path: PathBuf,
+    /// Bytes at which the file is rotated (0 = never)
+    max_size: u64,
+    /// Files kept, the current one included
+    max_files: u8,
+    /// None once writing failed
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingLog {
+    /// Append to `path`, as left by the last run
+    fn open(path: PathBuf, max_size: u64, max_files: u8) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingLog { path, max_size, max_files: max_files.max(1), file: Some(file), size })
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.file.is_none() {
+            return;
+        }
+        if self.max_size > 0 && self.size > 0 && self.size + bytes.len() as u64 > self.max_size {
+            if let Err(e) = self.rotate() {
+                self.give_up(&format!("rotating failed: {}", e));
+                return;
+            }
+        }
+        let result = self.file.as_mut().map_or(Ok(()), |file| file.write_all(bytes));
+        match result {
+            Ok(()) => self.size += bytes.len() as u64,
+            Err(e) => self.give_up(&format!("writing failed: {}", e)),
+        }
+    }
+
+    /// Shift `<path>.N` to `<path>.N+1`, dropping the oldest, and start over
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.max_files > 1 {
+            let _ = fs::remove_file(self.rotated(self.max_files - 1));
+            for n in (1..self.max_files - 1).rev() {
+                let _ = fs::rename(self.rotated(n), self.rotated(n + 1));
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = Some(OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+
+    fn rotated(&self, n: u8) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        PathBuf::from(path)
+    }
+
+    fn give_up(&mut self, error: &str) {
+        self.file = None;
+        common::log_output_log_failed(&self.path, error);
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::fs::File;
+    use std::os::unix::io::IntoRawFd;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use nix::fcntl::OFlag;
+
+    /// Write ends of each payload's stdout and stderr pipes, -1 when there are none
+    static WRITE_ENDS: [[AtomicI32; 2]; 2] = [const { [const { AtomicI32::new(-1) }; 2] }; 2];
+
+    /// Close-on-exec both ends; the payload gets the write end as its fd 1 or 2
+    pub(super) fn pipe(payload: usize, stream: usize) -> Result<File, String> {
+        let (read, write) = nix::unistd::pipe2(OFlag::O_CLOEXEC).map_err(|e| format!("pipe failed: {}", e))?;
+        WRITE_ENDS[payload][stream].store(write.into_raw_fd(), Ordering::Relaxed);
+        Ok(File::from(read))
+    }
+
+    pub(super) fn close_write_ends(payload: usize) {
+        for end in &WRITE_ENDS[payload] {
+            let fd = end.swap(-1, Ordering::Relaxed);
+            if fd >= 0 {
+                unsafe { libc::close(fd) };
+            }
+        }
+    }
+
+    /// Close-on-exec already keeps them out of the other payload
+    pub(super) fn keep_write_ends(_payload: usize) {}
+
+    /// In a forked payload: point its stdout and stderr at the pipes. Only
+    /// async-signal-safe calls.
+    pub fn enter(is_base: bool) {
+        let ends = &WRITE_ENDS[super::index(is_base)];
+        for (stream, target) in [libc::STDOUT_FILENO, libc::STDERR_FILENO].into_iter().enumerate() {
+            let fd = ends[stream].load(Ordering::Relaxed);
+            if fd >= 0 {
+                unsafe { libc::dup2(fd, target) };
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use std::fs::File;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use windows_sys::Win32::Foundation::{CloseHandle, SetHandleInformation, HANDLE, HANDLE_FLAG_INHERIT};
+    use windows_sys::Win32::System::Console::{GetStdHandle, STD_INPUT_HANDLE};
+    use windows_sys::Win32::System::Pipes::CreatePipe;
+    use windows_sys::Win32::System::Threading::{STARTF_USESTDHANDLES, STARTUPINFOA};
+
+    /// Write ends of each payload's stdout and stderr pipes, 0 when there are none
+    static WRITE_ENDS: [[AtomicUsize; 2]; 2] = [const { [const { AtomicUsize::new(0) }; 2] }; 2];
+
+    /// Neither end is inheritable until its payload is created, so the other
+    /// payload never holds the pipe open
+    pub(super) fn pipe(payload: usize, stream: usize) -> Result<File, String> {
+        let (mut read, mut write): (HANDLE, HANDLE) = (ptr::null_mut(), ptr::null_mut());
+        if unsafe { CreatePipe(&mut read, &mut write, ptr::null(), 0) } == 0 {
+            return Err(format!("CreatePipe failed: {}", std::io::Error::last_os_error()));
+        }
+        WRITE_ENDS[payload][stream].store(write as usize, Ordering::Relaxed);
+        Ok(unsafe { File::from_raw_handle(read as _) })
+    }
+
+    pub(super) fn close_write_ends(payload: usize) {
+        for end in &WRITE_ENDS[payload] {
+            let handle = end.swap(0, Ordering::Relaxed);
+            if handle != 0 {
+                unsafe { CloseHandle(handle as HANDLE) };
+            }
+        }
+    }
+
+    /// Make them uninheritable again until the payload's next run
+    pub(super) fn keep_write_ends(payload: usize) {
+        for end in &WRITE_ENDS[payload] {
+            let handle = end.load(Ordering::Relaxed);
+            if handle != 0 {
+                unsafe { SetHandleInformation(handle as HANDLE, HANDLE_FLAG_INHERIT, 0) };
+            }
+        }
+    }
+
+    /// Hand the payload the pipes as its stdout and stderr; returns whether
+    /// handles have to be inherited for that
+    pub fn redirect(is_base: bool, startup: &mut STARTUPINFOA) -> bool {
+        let ends = &WRITE_ENDS[super::index(is_base)];
+        let (stdout, stderr) = (ends[0].load(Ordering::Relaxed) as HANDLE, ends[1].load(Ordering::Relaxed) as HANDLE);
+        if stdout.is_null() || stderr.is_null() {
+            return false;
+        }
+        unsafe {
+            SetHandleInformation(stdout, HANDLE_FLAG_INHERIT, HANDLE_FLAG_INHERIT);
+            SetHandleInformation(stderr, HANDLE_FLAG_INHERIT, HANDLE_FLAG_INHERIT);
+            startup.dwFlags |= STARTF_USESTDHANDLES;
+            startup.hStdInput = GetStdHandle(STD_INPUT_HANDLE);
+            startup.hStdOutput = stdout;
+            startup.hStdError = stderr;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("weaver-output-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("base.log");
+        let mut log = RotatingLog::open(path.clone(), 8, 3).unwrap();
+        for chunk in [&b"one\n"[..], b"two\n", b"three\n", b"four\n", b"five\n"] {
+            log.write(chunk);
+        }
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap_or_default();
+        assert_eq!(read(path.clone()), "five\n");
+        assert_eq!(read(log.rotated(1)), "four\n");
+        assert_eq!(read(log.rotated(2)), "three\n");
+        assert!(!log.rotated(3).exists());
+
+        // A single file is truncated instead
+        let mut log = RotatingLog::open(path.clone(), 8, 1).unwrap();
+        log.write(b"six\n");
+        assert_eq!(read(path.clone()), "six\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Per-payload scheduling priority and CPU affinity
+//! (`ConfigFooter::base_priority`, `base_cpu_affinity` and the overload's).
+//!
+//! Priorities are nice values. On Linux and macOS a forked payload sets its
+//! own before exec; only root (or CAP_SYS_NICE) can go below 0. Windows picks
+//! the priority class closest to the nice value when creating the process:
+//! idle from 15, below normal from 5, normal down to -4, above normal down to
+//! -9 and high below that (never realtime). Affinity masks hold CPUs 0 to 63;
+//! macOS has no way to pin a process, so it ignores them. Failing to apply
+//! either is logged and the payload runs anyway.
+
+#[cfg(unix)]
+pub use imp::apply;
+
+#[cfg(target_os = "windows")]
+pub use imp::{creation_flags, set_affinity};
+
+#[cfg(unix)]
+mod imp {
+    /// In a forked payload: set its nice value and pin it to `affinity` (0 = any CPU)
+    pub fn apply(priority: Option<i8>, affinity: u64) -> Result<(), String> {
+        if let Some(nice) = priority {
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice.into()) } != 0 {
+                return Err(format!("setting nice {} failed: {}", nice, std::io::Error::last_os_error()));
+            }
+        }
+        if affinity != 0 {
+            set_affinity(affinity)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_affinity(affinity: u64) -> Result<(), String> {
+        use nix::sched::{sched_setaffinity, CpuSet};
+
+        let mut cpus = CpuSet::new();
+        for cpu in (0..64).filter(|cpu| affinity & (1 << cpu) != 0) {
+            cpus.set(cpu).map_err(|e| format!("CPU {} can't be used: {}", cpu, e))?;
+        }
+        sched_setaffinity(nix::unistd::Pid::from_raw(0), &cpus)
+            .map_err(|e| format!("setting CPU affinity {:#x} failed: {}", affinity, e))
+    }
+
+    /// Thread affinity tags are only hints to the macOS scheduler
+    #[cfg(target_os = "macos")]
+    fn set_affinity(_affinity: u64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::System::Threading::{
+        SetProcessAffinityMask, ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+        IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+    };
+
+    /// `CreateProcess` flag for the priority class closest to `priority` (0 = inherited)
+    pub fn creation_flags(priority: Option<i8>) -> u32 {
+        match priority {
+            None => 0,
+            Some(15..) => IDLE_PRIORITY_CLASS,
+            Some(5..) => BELOW_NORMAL_PRIORITY_CLASS,
+            Some(-4..) => NORMAL_PRIORITY_CLASS,
+            Some(-9..) => ABOVE_NORMAL_PRIORITY_CLASS,
+            Some(_) => HIGH_PRIORITY_CLASS,
+        }
+    }
+
+    /// Pin a created, still suspended payload to `affinity` (0 = any CPU)
+    pub fn set_affinity(process: HANDLE, affinity: u64) -> Result<(), String> {
+        if affinity == 0 {
+            return Ok(());
+        }
+        if unsafe { SetProcessAffinityMask(process, affinity as usize) } == 0 {
+            return Err(format!("setting CPU affinity {:#x} failed: {}", affinity, std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Run payloads without root (Linux and macOS).
+//!
+//! The account is looked up in the stub before any fork, since NSS lookups
+//! aren't safe between fork and exec in a threaded process; the switch itself
+//! happens in each forked child as the last step before `execv`, after the
+//! limits and jail that still need root.
+
+use std::ffi::CString;
+
+use nix::unistd::{geteuid, setgid, setuid, Gid, Uid, User};
+
+use crate::common;
+use crate::ConfigFooter;
+
+/// What happens to a payload's privileges before it execs
+#[derive(Clone)]
+pub enum Privileges {
+    /// Run with the stub's own credentials
+    Keep,
+    /// setgid/initgroups/setuid to this account
+    SwitchTo(Account),
+    /// Stay uid 0 but without any capability (Linux only)
+    #[cfg(target_os = "linux")]
+    DropCapabilities,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    pub uid: Uid,
+    pub gid: Gid,
+    name: CString,
+    home: CString,
+    /// Supplementary groups, resolved up front because an empty-root jail
+    /// hides /etc/group from the child
+    #[cfg(target_os = "linux")]
+    groups: Vec<Gid>,
+}
+
+impl Privileges {
+    /// Resolve the footer's `run_as_user`/`drop_privileges` for this run
+    ///
+    /// Both only apply when the stub runs as root; otherwise payloads already
+    /// run unprivileged and the options are ignored.
+    pub fn from_footer(footer: &ConfigFooter) -> Result<Self, String> {
+        let user = footer.run_as_user();
+        if user.is_none() && footer.drop_privileges == 0 {
+            return Ok(Privileges::Keep);
+        }
+        if !geteuid().is_root() {
+            common::log_not_root_privileges_kept();
+            return Ok(Privileges::Keep);
+        }
+
+        match user {
+            Some(name) => lookup(name).map(Privileges::SwitchTo),
+            #[cfg(target_os = "linux")]
+            None => Ok(Privileges::DropCapabilities),
+            // No capabilities to drop on macOS; fall back to the unprivileged account
+            #[cfg(target_os = "macos")]
+            None => lookup("nobody").map(Privileges::SwitchTo),
+        }
+    }
+
+    /// Account payloads run as, for handing it resources created as root
+    pub fn account(&self) -> Option<&Account> {
+        match self {
+            Privileges::SwitchTo(account) => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Give up privileges in a forked child
+    pub fn apply(&self) -> Result<(), String> {
+        match self {
+            Privileges::Keep => Ok(()),
+            Privileges::SwitchTo(account) => switch_to(account),
+            #[cfg(target_os = "linux")]
+            Privileges::DropCapabilities => drop_capabilities(),
+        }
+    }
+}
+
+fn lookup(name: &str) -> Result<Account, String> {
+    let user = User::from_name(name)
+        .map_err(|e| format!("Failed to look up user {}: {}", name, e))?
+        .ok_or_else(|| format!("No such user: {}", name))?;
+    let name = CString::new(name).map_err(|_| format!("Invalid user name: {}", name))?;
+    Ok(Account {
+        uid: user.uid,
+        gid: user.gid,
+        home: CString::new(user.dir.into_os_string().into_encoded_bytes()).unwrap_or_default(),
+        #[cfg(target_os = "linux")]
+        groups: nix::unistd::getgrouplist(&name, user.gid)
+            .map_err(|e| format!("Failed to look up groups of {:?}: {}", name, e))?,
+        name,
+    })
+}
+
+fn switch_to(account: &Account) -> Result<(), String> {
+    // Groups first: after setuid we no longer may change them
+    #[cfg(target_os = "linux")]
+    nix::unistd::setgroups(&account.groups).map_err(|e| format!("setgroups failed: {}", e))?;
+    #[cfg(target_os = "macos")]
+    if unsafe { libc::initgroups(account.name.as_ptr(), account.gid.as_raw() as _) } != 0 {
+        return Err(format!("initgroups failed: {}", std::io::Error::last_os_error()));
+    }
+    setgid(account.gid).map_err(|e| format!("setgid failed: {}", e))?;
+    setuid(account.uid).map_err(|e| format!("setuid failed: {}", e))?;
+    if setuid(Uid::from_raw(0)).is_ok() {
+        return Err("still able to regain root after setuid".to_string());
+    }
+
+    unsafe {
+        libc::setenv(c"USER".as_ptr(), account.name.as_ptr(), 1);
+        libc::setenv(c"LOGNAME".as_ptr(), account.name.as_ptr(), 1);
+        if !account.home.is_empty() {
+            libc::setenv(c"HOME".as_ptr(), account.home.as_ptr(), 1);
+        }
+    }
+    Ok(())
+}
+
+/// Empty the bounding, ambient, effective, permitted and inheritable sets
+///
+/// With the bounding set empty, exec can't hand root its capabilities back.
+#[cfg(target_os = "linux")]
+fn drop_capabilities() -> Result<(), String> {
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: i32,
+    }
+    #[repr(C)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+    let last_cap = std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|value| value.trim().parse::<libc::c_ulong>().ok())
+        .unwrap_or(40);
+
+    unsafe {
+        // Needs CAP_SETPCAP, so before the capset below
+        for cap in 0..=last_cap {
+            if libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) != 0 {
+                return Err(format!("Failed to drop capability {}: {}", cap, std::io::Error::last_os_error()));
+            }
+        }
+        libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_CLEAR_ALL, 0, 0, 0);
+
+        let mut header = CapHeader { version: LINUX_CAPABILITY_VERSION_3, pid: 0 };
+        let empty = || CapData { effective: 0, permitted: 0, inheritable: 0 };
+        let data = [empty(), empty()];
+        if libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) != 0 {
+            return Err(format!("capset failed: {}", std::io::Error::last_os_error()));
+        }
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(format!("Failed to set no_new_privs: {}", std::io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
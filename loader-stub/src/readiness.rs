@@ -0,0 +1,117 @@
+//! Waiting for a sidecar overload to be ready before base starts
+//! (`ConfigFooter::readiness_probe`).
+//!
+//! The probe runs every 250 ms from the moment the overload is started: a
+//! TCP connection to `host:port`, a file that has to exist, or the ready
+//! marker the overload sets in its health slot. If the overload exits first,
+//! or the probe still fails after `readiness_timeout` seconds, the stub
+//! either kills the overload and exits with code 1 or starts base anyway.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use weaver_abi::footer::{READINESS_FILE, READINESS_FLAG, READINESS_START_BASE, READINESS_TCP};
+use weaver_abi::health::OVERLOAD_SLOT;
+
+use crate::common::{log_overload_ready, log_readiness_failed_starting_base, log_waiting_for_readiness};
+use crate::{ConfigFooter, HealthTable};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+enum Probe {
+    Tcp(String),
+    File(PathBuf),
+    Flag,
+}
+
+pub struct Readiness {
+    probe: Probe,
+    timeout: Option<Duration>,
+    start_base_anyway: bool,
+}
+
+/// Whether the probe reads the health region, which then has to exist
+pub fn uses_health_region(footer: &ConfigFooter) -> bool {
+    footer.readiness_probe == READINESS_FLAG
+}
+
+impl Readiness {
+    /// The footer's probe, `None` if base starts right away
+    pub fn from_footer(footer: &ConfigFooter) -> Option<Readiness> {
+        let probe = match (footer.readiness_probe, footer.readiness_target()) {
+            (READINESS_TCP, Some(address)) => Probe::Tcp(address.to_string()),
+            (READINESS_FILE, Some(path)) => Probe::File(resolve(path)),
+            (READINESS_FLAG, _) => Probe::Flag,
+            _ => return None,
+        };
+        Some(Readiness {
+            probe,
+            timeout: (footer.readiness_timeout > 0).then(|| Duration::from_secs(footer.readiness_timeout.into())),
+            start_base_anyway: footer.readiness_on_timeout == READINESS_START_BASE,
+        })
+    }
+
+    /// Block until the probe passes, the overload exits (`overload_running`
+    /// returns false) or the timeout runs out
+    ///
+    /// Returns `Err` when base must not start; the caller then stops the
+    /// overload.
+    pub fn wait(&self, table: *const HealthTable, mut overload_running: impl FnMut() -> bool) -> Result<(), String> {
+        log_waiting_for_readiness(&self.describe(), self.timeout);
+        let started = Instant::now();
+        let failure = loop {
+            if self.passed(table) {
+                log_overload_ready(started.elapsed());
+                return Ok(());
+            }
+            if !overload_running() {
+                break "the overload exited before it was ready".to_string();
+            }
+            if self.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                break format!("{} didn't pass within {}s", self.describe(), started.elapsed().as_secs());
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        if self.start_base_anyway {
+            log_readiness_failed_starting_base(&failure);
+            return Ok(());
+        }
+        Err(failure)
+    }
+
+    fn passed(&self, table: *const HealthTable) -> bool {
+        match &self.probe {
+            Probe::Tcp(address) => address
+                .to_socket_addrs()
+                .map(|mut addrs| addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok()))
+                .unwrap_or(false),
+            Probe::File(path) => path.exists(),
+            Probe::Flag => unsafe { table.as_ref() }
+                .and_then(|table| table.slot(OVERLOAD_SLOT))
+                .is_some_and(|slot| slot.is_ready()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match &self.probe {
+            Probe::Tcp(address) => format!("TCP probe of {}", address),
+            Probe::File(path) => format!("file probe of {}", path.display()),
+            Probe::Flag => "ready flag probe".to_string(),
+        }
+    }
+}
+
+/// A relative path is looked up next to the merged binary
+fn resolve(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        return path;
+    }
+    match std::env::current_exe().ok().as_deref().and_then(|exe| exe.parent()) {
+        Some(dir) => dir.join(path),
+        None => path,
+    }
+}
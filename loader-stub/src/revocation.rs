@@ -0,0 +1,96 @@
+//! Remote kill switch (`ConfigFooter::revocation_url`).
+//!
+//! The URL is fetched right away and then every `revocation_interval`
+//! seconds. A revocation is a 2xx response whose body starts with
+//! `revoke <signature>`: the hex Ed25519 signature, by `revocation_key`, of
+//! `revoke <url>` with the URL exactly as configured, so one revocation
+//! can't be replayed against binaries polling other URLs. Anything else,
+//! including an unreachable server, leaves the payloads running, and since
+//! only a valid signature stops them plain HTTP is enough.
+//!
+//! This runs in the stub itself, independent of any checks the overload makes.
+
+use std::thread;
+use std::time::Duration;
+
+use ed25519_compact::{PublicKey, Signature};
+
+use crate::{common, http, shutdown, ConfigFooter, REVOCATION_INTERVAL};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start polling if the footer has a revocation URL
+pub fn start(footer: &ConfigFooter) {
+    let Some(url) = footer.revocation_url() else {
+        return;
+    };
+    let url = url.to_string();
+    let key = PublicKey::new(footer.revocation_key);
+    let interval = if footer.revocation_interval > 0 { footer.revocation_interval } else { REVOCATION_INTERVAL };
+    common::log_revocation_enabled(&url, interval);
+
+    thread::spawn(move || loop {
+        match is_revoked(&url, &key) {
+            Ok(true) => {
+                common::log_revoked(&url);
+                shutdown::request();
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => common::log_revocation_check_failed(&e),
+        }
+        thread::sleep(Duration::from_secs(interval as u64));
+    });
+}
+
+fn is_revoked(url: &str, key: &PublicKey) -> Result<bool, String> {
+    check(&http::get(url, TIMEOUT)?, url, key)
+}
+
+/// Whether `body` carries a valid revocation of `url`
+fn check(body: &str, url: &str, key: &PublicKey) -> Result<bool, String> {
+    let Some(signature) = body.lines().next().and_then(|line| line.trim().strip_prefix("revoke ")) else {
+        return Ok(false);
+    };
+    let signature = decode_hex(signature.trim())
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or("malformed revocation signature")?;
+    key.verify(format!("revoke {}", url), &signature)
+        .map_err(|_| "revocation signature doesn't match revocation_key")?;
+    Ok(true)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "http://licenses.example/acme";
+    // Ed25519 key from the seed [1; 32], and its signature of "revoke http://licenses.example/acme"
+    const KEY: &str = "8a88e3dd7409f195fd52db2d3cba5d72ca6709bf1d94121bf3748801b40f6f5c";
+    const SIGNATURE: &str = "bc0e2962aa5bebd89420986939c4f7c6663b09967fe5c597f4d2b18f6ce915bc\
+                             726e99ff138bffe62d75ef420d4be22b49cc0343ef7450353c8b848d09a4af0e";
+
+    #[test]
+    fn test_signature_is_bound_to_url() {
+        let key = PublicKey::from_slice(&decode_hex(KEY).unwrap()).unwrap();
+        let body = format!("revoke {}\n", SIGNATURE);
+        assert_eq!(check(&body, URL, &key), Ok(true));
+        assert!(check(&body, "http://licenses.example/other", &key).is_err());
+        assert!(check("revoke 1234", URL, &key).is_err());
+        assert_eq!(check("ok", URL, &key), Ok(false));
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("00ff7A"), Some(vec![0, 0xff, 0x7a]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+}
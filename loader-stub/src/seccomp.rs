@@ -0,0 +1,130 @@
+//! Seccomp allowlist for payloads (Linux only).
+//!
+//! The profile Weaver stores after the payloads is turned into a classic BPF
+//! program once in the stub; each forked child then only has to install it,
+//! as the very last step before exec. `execveat` and `exit_group` are always
+//! in the allowlist, so the exec itself (or exiting after it fails) works.
+
+use weaver_abi::seccomp::{self, SeccompHeader, SECCOMP_DEFAULT_KILL, SECCOMP_DEFAULT_LOG};
+
+// Classic BPF opcodes (linux/bpf_common.h)
+/// BPF_LD | BPF_W | BPF_ABS
+const BPF_LD_W_ABS: u16 = 0x20;
+/// BPF_JMP | BPF_JEQ | BPF_K
+const BPF_JMP_JEQ_K: u16 = 0x15;
+/// BPF_JMP | BPF_JGE | BPF_K
+const BPF_JMP_JGE_K: u16 = 0x35;
+/// BPF_RET | BPF_K
+const BPF_RET_K: u16 = 0x06;
+
+// Offsets into struct seccomp_data
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+const AUDIT_ARCH_X86_64: u32 = 0xC000_003E;
+/// Syscall numbers with this bit set are the x32 ABI, which has its own table
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// A compiled profile, ready to install in a forked child
+#[derive(Clone)]
+pub struct Filter {
+    program: Vec<libc::sock_filter>,
+    header: SeccompHeader,
+}
+
+impl Filter {
+    pub fn from_profile(bytes: &[u8]) -> Result<Self, String> {
+        let (header, numbers) = SeccompHeader::parse(bytes).ok_or("Invalid seccomp profile")?;
+        Ok(Filter {
+            program: compile(&header, seccomp::syscalls(numbers)),
+            header,
+        })
+    }
+
+    pub fn applies_to(&self, is_base: bool) -> bool {
+        self.header.applies_to(is_base)
+    }
+
+    /// Install the filter on the calling process
+    ///
+    /// Sets no_new_privs first, which the kernel requires without CAP_SYS_ADMIN.
+    pub fn install(&self) -> Result<(), String> {
+        let prog = libc::sock_fprog {
+            len: self.program.len() as libc::c_ushort,
+            filter: self.program.as_ptr() as *mut libc::sock_filter,
+        };
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(format!("Failed to set no_new_privs: {}", std::io::Error::last_os_error()));
+            }
+            if libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &prog as *const libc::sock_fprog) != 0 {
+                return Err(format!("Failed to install seccomp filter: {}", std::io::Error::last_os_error()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn stmt(code: u16, k: u32) -> libc::sock_filter {
+    libc::sock_filter { code, jt: 0, jf: 0, k }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+fn compile(header: &SeccompHeader, syscalls: impl Iterator<Item = u32>) -> Vec<libc::sock_filter> {
+    let default = match header.default_action {
+        SECCOMP_DEFAULT_KILL => SECCOMP_RET_KILL_PROCESS,
+        SECCOMP_DEFAULT_LOG => SECCOMP_RET_LOG,
+        _ => SECCOMP_RET_ERRNO | libc::EPERM as u32,
+    };
+
+    // Syscalls from another architecture would be checked against the wrong table
+    let mut program = vec![
+        stmt(BPF_LD_W_ABS, DATA_ARCH),
+        jump(BPF_JMP_JEQ_K, header.audit_arch, 1, 0),
+        stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        stmt(BPF_LD_W_ABS, DATA_NR),
+    ];
+    if header.audit_arch == AUDIT_ARCH_X86_64 {
+        program.push(jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1));
+        program.push(stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS));
+    }
+    for nr in syscalls {
+        program.push(jump(BPF_JMP_JEQ_K, nr, 0, 1));
+        program.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    }
+    program.push(stmt(BPF_RET_K, default));
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weaver_abi::seccomp::{SECCOMP_APPLY_OVERLOAD, SECCOMP_MAGIC};
+
+    #[test]
+    fn test_compile_checks_arch_then_each_syscall() {
+        let header = SeccompHeader {
+            magic: *SECCOMP_MAGIC,
+            audit_arch: AUDIT_ARCH_X86_64,
+            default_action: SECCOMP_DEFAULT_KILL,
+            apply_to: SECCOMP_APPLY_OVERLOAD,
+            count: 2,
+        };
+        let program = compile(&header, [0, 60].into_iter());
+
+        // arch check (3) + load nr (1) + x32 check (2) + 2 per syscall + default
+        assert_eq!(program.len(), 3 + 1 + 2 + 2 * 2 + 1);
+        assert_eq!(program[1].k, AUDIT_ARCH_X86_64);
+        assert_eq!((program[6].code, program[6].k), (BPF_JMP_JEQ_K, 0));
+        assert_eq!((program[8].code, program[8].k), (BPF_JMP_JEQ_K, 60));
+        assert_eq!(program.last().unwrap().k, SECCOMP_RET_KILL_PROCESS);
+    }
+}
@@ -0,0 +1,220 @@
+//! Graceful shutdown: pass termination requests on to base and the overload
+//!
+//! Without this the stub dies on SIGTERM/SIGINT (or a console close) and its
+//! children are orphaned. Children are tracked by PID as they start; on a
+//! termination request they get the same signal, `shutdown_grace` seconds to
+//! exit, and are then killed outright before the stub exits.
+
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::common::{log_forwarding_shutdown, log_shutdown_grace_exceeded};
+use crate::SHUTDOWN_GRACE_SECS;
+
+/// Room for base, the overload and one scheduled overload run
+const MAX_CHILDREN: usize = 4;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static CHILDREN: [AtomicI32; MAX_CHILDREN] = [const { AtomicI32::new(0) }; MAX_CHILDREN];
+static GRACE_SECS: AtomicU32 = AtomicU32::new(SHUTDOWN_GRACE_SECS);
+static CLEANUP: Mutex<Vec<Box<dyn Fn() + Send>>> = Mutex::new(Vec::new());
+
+/// Forward termination requests to tracked children from now on
+///
+/// `grace` comes from the footer, in seconds (0 = default).
+pub fn install(grace: u32) {
+    let grace = if grace > 0 { grace } else { SHUTDOWN_GRACE_SECS };
+    GRACE_SECS.store(grace, Ordering::Relaxed);
+    imp::install();
+}
+
+/// Start forwarding to a child
+pub fn track(pid: i32) {
+    for slot in &CHILDREN {
+        if slot.compare_exchange(0, pid, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+            return;
+        }
+    }
+}
+
+/// Stop forwarding to a child once it has been reaped, so its PID can be reused safely
+pub fn untrack(pid: i32) {
+    for slot in &CHILDREN {
+        let _ = slot.compare_exchange(pid, 0, Ordering::AcqRel, Ordering::Relaxed);
+    }
+}
+
+/// Run `cleanup` when the stub exits from here rather than from its main thread
+pub fn on_exit(cleanup: impl Fn() + Send + 'static) {
+    CLEANUP.lock().unwrap_or_else(PoisonError::into_inner).push(Box::new(cleanup));
+}
+
+/// Shut down as if the stub itself had received a termination request
+pub fn request() {
+    imp::request();
+}
+
+/// Kill every tracked child outright, without a grace period, and exit
+pub fn kill_all_and_exit(code: i32) -> ! {
+    for pid in tracked() {
+        imp::kill_now(pid);
+    }
+    exit(code);
+}
+
+fn exit(code: i32) -> ! {
+    for cleanup in CLEANUP.lock().unwrap_or_else(PoisonError::into_inner).iter() {
+        cleanup();
+    }
+    std::process::exit(code);
+}
+
+pub fn tracked() -> impl Iterator<Item = i32> {
+    CHILDREN.iter().map(|slot| slot.load(Ordering::Acquire)).filter(|&pid| pid > 0)
+}
+
+fn grace() -> Duration {
+    Duration::from_secs(GRACE_SECS.load(Ordering::Relaxed) as u64)
+}
+
+/// Wait until no tracked child is alive or the grace period runs out;
+/// returns whether any are still alive
+fn wait_for_children(is_alive: impl Fn(i32) -> bool) -> bool {
+    let deadline = Instant::now() + grace();
+    while Instant::now() < deadline {
+        if !tracked().any(&is_alive) {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    tracked().any(&is_alive)
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::*;
+    use crate::common::signal_tree;
+    use nix::sys::signal::{kill, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+    use nix::unistd::Pid;
+
+    static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+    // Async-signal-safe: only records the signal for the watcher thread
+    extern "C" fn record_signal(signal: libc::c_int) {
+        PENDING_SIGNAL.store(signal, Ordering::Relaxed);
+    }
+
+    pub(super) fn install() {
+        // SA_RESTART keeps the main thread's waitpid on base going
+        let action = SigAction::new(SigHandler::Handler(record_signal), SaFlags::SA_RESTART, SigSet::empty());
+        for signal in [Signal::SIGTERM, Signal::SIGINT] {
+            let _ = unsafe { sigaction(signal, &action) };
+        }
+
+        thread::spawn(|| loop {
+            thread::sleep(POLL_INTERVAL);
+            let Ok(signal) = Signal::try_from(PENDING_SIGNAL.load(Ordering::Relaxed)) else {
+                continue;
+            };
+
+            log_forwarding_shutdown(signal, grace());
+            for pid in tracked() {
+                signal_tree(pid, signal);
+            }
+
+            // Zombies still accept signal 0, but the main thread reaps base
+            // as soon as it exits and then exits the stub itself
+            let alive = |pid| kill(Pid::from_raw(pid), None).is_ok();
+            if wait_for_children(alive) {
+                log_shutdown_grace_exceeded();
+                for pid in tracked() {
+                    kill_now(pid);
+                }
+            }
+            exit(128 + signal as i32);
+        });
+    }
+
+    pub(super) fn kill_now(pid: i32) {
+        signal_tree(pid, Signal::SIGKILL);
+    }
+
+    /// Goes through the watcher thread like any other SIGTERM
+    pub(super) fn request() {
+        let _ = kill(Pid::this(), Signal::SIGTERM);
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod imp {
+    use super::*;
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::Foundation::{CloseHandle, WAIT_TIMEOUT};
+    use windows_sys::Win32::System::Console::{
+        GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT,
+        CTRL_SHUTDOWN_EVENT,
+    };
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, TerminateProcess, WaitForSingleObject, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE,
+    };
+
+    /// Children share the stub's console and receive console events
+    /// themselves, so there is nothing to forward; the stub only stays alive
+    /// for the grace period and then terminates whatever is left.
+    ///
+    /// Windows gives a closing console about 5 seconds, so longer grace
+    /// periods only apply to Ctrl+C and Ctrl+Break.
+    unsafe extern "system" fn on_console_event(event: u32) -> BOOL {
+        if !matches!(event, CTRL_C_EVENT | CTRL_BREAK_EVENT | CTRL_CLOSE_EVENT | CTRL_SHUTDOWN_EVENT) {
+            return 0;
+        }
+
+        log_forwarding_shutdown(format!("console event {}", event), grace());
+        if wait_for_children(is_alive) {
+            log_shutdown_grace_exceeded();
+            for pid in tracked() {
+                kill_now(pid);
+            }
+        }
+        exit(1);
+    }
+
+    pub(super) fn kill_now(pid: i32) {
+        crate::windows::terminate_pid_tree(pid as u32, 1, || unsafe {
+            let handle = OpenProcess(PROCESS_TERMINATE, 0, pid as u32);
+            if !handle.is_null() {
+                TerminateProcess(handle, 1);
+                CloseHandle(handle);
+            }
+        });
+    }
+
+    /// Ctrl+C reaches every process on the console, the stub included, so
+    /// children get what a user pressing it would send them; without a
+    /// console they are terminated outright
+    pub(super) fn request() {
+        if unsafe { GenerateConsoleCtrlEvent(CTRL_C_EVENT, 0) } == 0 {
+            kill_all_and_exit(1);
+        }
+    }
+
+    fn is_alive(pid: i32) -> bool {
+        unsafe {
+            let handle = OpenProcess(PROCESS_SYNCHRONIZE, 0, pid as u32);
+            if handle.is_null() {
+                return false;
+            }
+            let running = WaitForSingleObject(handle, 0) == WAIT_TIMEOUT;
+            CloseHandle(handle);
+            running
+        }
+    }
+
+    pub(super) fn install() {
+        unsafe {
+            SetConsoleCtrlHandler(Some(on_console_event), 1);
+        }
+    }
+}
@@ -1,77 +1,109 @@
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::fs;
+use std::io;
 use std::mem;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use windows_sys::Win32::Foundation::{CloseHandle, GetLastError, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_ACCESS_DENIED, ERROR_SHARING_VIOLATION, HANDLE, INVALID_HANDLE_VALUE,
+    WAIT_TIMEOUT,
+};
+use windows_sys::Win32::System::Diagnostics::Debug::WriteProcessMemory;
 use windows_sys::Win32::System::Environment::SetEnvironmentVariableA;
+use windows_sys::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectA, JobObjectCpuRateControlInformation,
+    JobObjectExtendedLimitInformation, SetInformationJobObject, TerminateJobObject,
+    JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP, JOB_OBJECT_LIMIT_JOB_MEMORY,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
+use windows_sys::Win32::System::LibraryLoader::{GetModuleHandleA, GetProcAddress};
 use windows_sys::Win32::System::Memory::{
-    CreateFileMappingA, MapViewOfFile, UnmapViewOfFile, FILE_MAP_ALL_ACCESS, MEMORY_MAPPED_VIEW_ADDRESS,
-    PAGE_READWRITE,
+    CreateFileMappingA, MapViewOfFile, UnmapViewOfFile, VirtualAllocEx, VirtualFreeEx, FILE_MAP_ALL_ACCESS,
+    MEMORY_MAPPED_VIEW_ADDRESS, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
 };
 use windows_sys::Win32::System::Threading::{
-    CreateProcessA, GetCurrentProcessId, GetExitCodeProcess, TerminateProcess, WaitForSingleObject,
-    INFINITE, PROCESS_INFORMATION, STARTUPINFOA,
+    CreateProcessA, CreateRemoteThread, GetCurrentProcessId, GetExitCodeProcess, GetExitCodeThread, GetProcessId,
+    ResumeThread, TerminateProcess, WaitForSingleObject, CREATE_SUSPENDED, INFINITE, PROCESS_INFORMATION, STARTUPINFOA,
 };
 
 use crate::common::{
-    evaluate_health_status, health_check_interval, init_health_status, log_async_mode_started,
+    self, evaluate_health_table, health_check_interval, init_health_table, log_async_mode_started,
     log_base_completed_terminating_overload, log_base_exited, log_base_start_failed,
-    log_fallback_kill, log_grace_period_exceeded, log_health_monitor_started,
-    log_health_monitoring_enabled, log_heartbeat_lost, log_network_failure_threshold,
-    log_overload_requested_kill, log_overload_start_failed, log_shm_create_failed,
+    log_fallback_kill, log_health_monitor_started,
+    log_health_monitoring_enabled,
+    log_overload_start_failed, log_readiness_failed, log_shm_create_failed,
     log_shm_map_failed, log_starting_base, log_sync_mode_waiting, log_verification_failed,
     log_verification_successful, overload_kill_wait_duration, should_enable_health_monitoring,
-    signal_overload_to_kill, HealthCheckResult,
+    signal_overload_to_kill, HealthCheckResult, KillPolicy, MonitorArming,
 };
-use crate::{ConfigFooter, HealthStatus};
+use crate::readiness::Readiness;
+use crate::{control, crash, ipc, output, priority, shutdown, ConfigFooter, HealthTable};
+use weaver_abi::footer::{ORDER_BASE_FIRST, OVERLOAD_LIBRARY};
+use weaver_abi::ResourceLimits;
+
+/// Job of every payload started so far, by PID
+///
+/// Job handles are never closed: kill-on-close then takes each payload's
+/// whole tree down with the stub.
+static JOBS: Mutex<Vec<(u32, usize)>> = Mutex::new(Vec::new());
+
+/// How long to wait for a terminated payload to exit before moving on
+const PAYLOAD_EXIT_TIMEOUT_MS: u32 = 5000;
+/// How long to keep retrying a payload directory whose files are locked
+const REMOVE_RETRY_LIMIT: Duration = Duration::from_secs(5);
 
 pub fn run(
     base_data: Vec<u8>,
     overload_data: Vec<u8>,
     footer: ConfigFooter,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let sync_mode = footer.sync_mode != 0;
-    let grace_period = footer.grace_period;
+    let sync_mode = footer.prerequisite_runs() > 0;
     let network_failure_kill_count = footer.network_failure_kill_count;
+    let kill_policy = KillPolicy::from_footer(&footer);
+    let mut arming = MonitorArming::from_footer(&footer);
+    let check_interval = health_check_interval(footer.health_check_interval);
+    let base_first = footer.order == ORDER_BASE_FIRST;
+    let overload_library = footer.overload_kind == OVERLOAD_LIBRARY;
+    let scheduled = footer.is_scheduled();
 
     // 1. Setup Shared Memory (if async and monitoring needed)
-    let mut health_ptr: *mut HealthStatus = ptr::null_mut();
-    let mut health_shm_handle: HANDLE = ptr::null_mut();
-    let mut health_view: MEMORY_MAPPED_VIEW_ADDRESS = unsafe { mem::zeroed() };
+    let mut health_ptr: *mut HealthTable = ptr::null_mut();
+    let mut health_region = HealthRegion { handle: ptr::null_mut(), view: unsafe { mem::zeroed() } };
 
-    if should_enable_health_monitoring(sync_mode, grace_period, network_failure_kill_count) {
+    if !base_first && !scheduled && !overload_library && should_enable_health_monitoring(sync_mode, &kill_policy, &footer) {
         unsafe {
             let pid = GetCurrentProcessId();
             let shm_name = format!("Local\\OverloadHealth_{}", pid);
             let shm_name_c = CString::new(shm_name.clone()).unwrap();
 
-            health_shm_handle = CreateFileMappingA(
+            health_region.handle = CreateFileMappingA(
                 INVALID_HANDLE_VALUE,
                 ptr::null(),
                 PAGE_READWRITE,
                 0,
-                mem::size_of::<HealthStatus>() as u32,
+                HealthTable::SIZE as u32,
                 shm_name_c.as_ptr() as *const u8,
             );
 
-            if health_shm_handle != ptr::null_mut() {
-                health_view = MapViewOfFile(
-                    health_shm_handle,
+            if !health_region.handle.is_null() {
+                health_region.view = MapViewOfFile(
+                    health_region.handle,
                     FILE_MAP_ALL_ACCESS,
                     0,
                     0,
-                    mem::size_of::<HealthStatus>(),
+                    HealthTable::SIZE,
                 );
 
-                if !health_view.Value.is_null() {
-                    health_ptr = health_view.Value as *mut HealthStatus;
-                    init_health_status(health_ptr);
+                if !health_region.view.Value.is_null() {
+                    health_ptr = health_region.view.Value as *mut HealthTable;
+                    init_health_table(health_ptr);
+                    control::set_health(health_ptr);
 
                     // Set env var for overload
                     let env_name = CString::new("KILLCODE_HEALTH_SHM").unwrap();
@@ -89,17 +121,49 @@ pub fn run(
         }
     }
 
-    // 2. Prepare binaries
-    let temp_dir = std::env::temp_dir();
-    let base_path = temp_dir.join("base.exe");
-    let overload_path = temp_dir.join("overload.exe");
+    // 2. Prepare binaries in a directory of their own, so concurrent merged
+    // binaries never share (or overwrite) each other's payloads
+    let payload_dir = match PayloadDir::create() {
+        Ok(dir) => dir,
+        Err(e) => {
+            unsafe { health_region.close() };
+            return Err(e.into());
+        }
+    };
+    // Console events and shutdown requests exit from another thread
+    let cleanup_path = payload_dir.path.clone();
+    shutdown::on_exit(move || remove_payload_dir(&cleanup_path));
+    let base_path = payload_dir.path.join(format!("{}.exe", common::payload_name(&footer, true)));
+    let overload_extension = if overload_library { "dll" } else { "exe" };
+    let overload_path =
+        payload_dir.path.join(format!("{}.{}", common::payload_name(&footer, false), overload_extension));
 
     // Write binaries
-    fs::write(&base_path, &base_data)?;
-    fs::write(&overload_path, &overload_data)?;
+    if let Err(e) = fs::write(&base_path, &base_data).and_then(|_| fs::write(&overload_path, &overload_data)) {
+        payload_dir.remove();
+        unsafe { health_region.close() };
+        return Err(e.into());
+    }
+
+    let base_limits = footer.limits(true);
+    let overload_limits = footer.limits(false);
+    let (base_priority, overload_priority) = (footer.priority(true), footer.priority(false));
+    let (base_affinity, overload_affinity) = (footer.cpu_affinity(true), footer.cpu_affinity(false));
+    let working_dir = |is_base| {
+        common::payload_cwd(&footer, is_base).and_then(|dir| CString::new(dir.to_string_lossy().into_owned()).ok())
+    };
+    let (base_cwd, overload_cwd) = (working_dir(true), working_dir(false));
+    let preload_dll = match overload_path.to_str().map(CString::new) {
+        _ if !overload_library => None,
+        Some(Ok(dll)) => Some(dll),
+        _ => {
+            payload_dir.remove();
+            return Err("Invalid path".into());
+        }
+    };
 
     // Helper to execute binary
-    let execute_binary = |path: &PathBuf, is_base: bool| -> Result<(HANDLE, u32), String> {
+    let execute_binary = move |path: &PathBuf, is_base: bool| -> Result<(HANDLE, u32), String> {
         unsafe {
             let path_str = path.to_str().ok_or("Invalid path")?;
             let path_c = CString::new(path_str).map_err(|_| "Invalid path CString")?;
@@ -107,6 +171,10 @@ pub fn run(
             let mut si: STARTUPINFOA = mem::zeroed();
             si.cb = mem::size_of::<STARTUPINFOA>() as u32;
             let mut pi: PROCESS_INFORMATION = mem::zeroed();
+            let cwd = if is_base { &base_cwd } else { &overload_cwd };
+            ipc::export(is_base);
+            let logged = output::redirect(is_base, &mut si);
+            let redirected = crash::redirect(is_base, &mut si);
 
             // CreateProcessA requires a mutable command line string if the first arg is NULL,
             // OR if the first arg is provided, it uses that as the executable.
@@ -116,56 +184,200 @@ pub fn run(
                 ptr::null_mut(),
                 ptr::null(),
                 ptr::null(),
-                0,
-                0,
-                ptr::null(),
+                (ipc::is_enabled() || logged || redirected) as i32,
+                CREATE_SUSPENDED | priority::creation_flags(if is_base { base_priority } else { overload_priority }),
                 ptr::null(),
+                cwd.as_ref().map_or(ptr::null(), |dir| dir.as_ptr() as *const u8),
                 &si,
                 &mut pi,
             );
+            output::started(is_base);
 
             if success == 0 {
                 return Err(format!("CreateProcessA failed: {}", GetLastError()));
             }
 
+            // Each payload gets its own job, assigned before the first
+            // instruction runs so nothing it starts can escape it
+            let job = create_payload_job(if is_base { &base_limits } else { &overload_limits });
+            if !job.is_null() {
+                if AssignProcessToJobObject(job, pi.hProcess) == 0 {
+                    common::log_job_assign_failed(GetLastError());
+                }
+                JOBS.lock().unwrap().push((pi.dwProcessId, job as usize));
+            }
+            if let Err(e) = priority::set_affinity(pi.hProcess, if is_base { base_affinity } else { overload_affinity }) {
+                common::log_priority_failed(&e);
+            }
+            if let Some(dll) = preload_dll.as_ref().filter(|_| is_base) {
+                if let Err(e) = inject_library(pi.hProcess, dll) {
+                    TerminateProcess(pi.hProcess, 1);
+                    CloseHandle(pi.hThread);
+                    CloseHandle(pi.hProcess);
+                    return Err(e);
+                }
+            }
+            ResumeThread(pi.hThread);
             CloseHandle(pi.hThread);
+            shutdown::track(pi.dwProcessId as i32);
+            if is_base {
+                crash::base_started();
+            }
             Ok((pi.hProcess, pi.dwProcessId))
         }
     };
 
+    // Library overload: base runs alone with the DLL loaded before its first instruction
+    if overload_library {
+        common::log_overload_preloaded();
+        log_starting_base();
+        let base_exit_code = match execute_binary(&base_path, true) {
+            Ok((handle, _)) => wait_for_base_exit(handle),
+            Err(e) => {
+                log_base_start_failed(&e);
+                1
+            }
+        };
+        log_base_exited(base_exit_code);
+        payload_dir.remove();
+        std::process::exit(base_exit_code as i32);
+    }
+
+    // Base-first order: base runs to completion, then the overload, no monitoring
+    if base_first {
+        common::log_base_first();
+        log_starting_base();
+        let base_exit_code = match execute_binary(&base_path, true) {
+            Ok((handle, _)) => wait_for_base_exit(handle),
+            Err(e) => {
+                log_base_start_failed(&e);
+                1
+            }
+        };
+        log_base_exited(base_exit_code);
+
+        match execute_binary(&overload_path, false) {
+            Ok((handle, _)) => {
+                let code = wait_for_exit(handle);
+                if code != 0 {
+                    common::log_overload_after_base_failed(&format!("exit code {}", code));
+                }
+            }
+            Err(e) => common::log_overload_after_base_failed(&e),
+        }
+
+        payload_dir.remove();
+        std::process::exit(base_exit_code as i32);
+    }
+
+    // Scheduled overload: base starts right away, the overload runs on its delay/interval
+    if scheduled {
+        common::log_overload_scheduled(footer.overload_start_delay, footer.overload_interval);
+        log_starting_base();
+        let base_handle = match execute_binary(&base_path, true) {
+            Ok((handle, pid)) => {
+                common::export_base_pid(pid);
+                handle
+            }
+            Err(e) => {
+                log_base_start_failed(&e);
+                payload_dir.remove();
+                return Err(e.into());
+            }
+        };
+
+        // Handle of the scheduled overload currently running; whoever swaps it out owns it
+        let running_overload = Arc::new(AtomicUsize::new(0));
+        let running = running_overload.clone();
+        let scheduled_path = overload_path.clone();
+        let (start_delay, interval) = (footer.overload_start_delay, footer.overload_interval);
+        let base_handle_val = base_handle as usize;
+        thread::spawn(move || {
+            let base_handle = base_handle_val as HANDLE;
+            common::run_overload_schedule(
+                start_delay,
+                interval,
+                network_failure_kill_count,
+                || {
+                    let (handle, _) = execute_binary(&scheduled_path, false)?;
+                    running.store(handle as usize, Ordering::Relaxed);
+                    let code = unsafe { exit_code_after_wait(handle) };
+                    if running.swap(0, Ordering::Relaxed) != 0 {
+                        unsafe { CloseHandle(handle) };
+                    }
+                    Ok(code as i32)
+                },
+                || unsafe {
+                    let mut exit_code: u32 = 0;
+                    GetExitCodeProcess(base_handle, &mut exit_code) != 0 && exit_code == 259 // STILL_ACTIVE
+                },
+                || unsafe {
+                    terminate_tree(base_handle, 1);
+                },
+            )
+        });
+
+        // Base's handle stays open until exit so the scheduler never sees a recycled one
+        let base_exit_code = unsafe { exit_code_after_wait(base_handle) };
+        output::base_exited();
+        crash::base_exited(crash::Exit::Code(base_exit_code as i64));
+        let overload_handle = running_overload.swap(0, Ordering::Relaxed);
+        if overload_handle != 0 {
+            log_base_completed_terminating_overload("scheduled");
+            unsafe { stop_payload(overload_handle as HANDLE) };
+        }
+
+        payload_dir.remove();
+        log_base_exited(base_exit_code);
+        std::process::exit(base_exit_code as i32);
+    }
+
     // 3. Start Overload
     let mut overload_handle: HANDLE = ptr::null_mut();
     let mut overload_pid: u32 = 0;
 
-    match execute_binary(&overload_path, false) {
-        Ok((h, pid)) => {
-            overload_handle = h;
-            overload_pid = pid;
-
-            if sync_mode {
-                log_sync_mode_waiting(overload_pid);
-                unsafe {
-                    WaitForSingleObject(overload_handle, INFINITE);
-                    let mut exit_code: u32 = 0;
-                    GetExitCodeProcess(overload_handle, &mut exit_code);
-
-                    if exit_code != 0 {
-                        log_verification_failed(exit_code);
-                        CloseHandle(overload_handle);
-                        let _ = fs::remove_file(&base_path);
-                        let _ = fs::remove_file(&overload_path);
-                        return Err("Overload verification failed".into());
-                    }
-                    log_verification_successful();
-                }
-            } else {
+    if sync_mode {
+        let verified = common::run_prerequisites(&footer, || {
+            let (handle, pid) = execute_binary(&overload_path, false).inspect_err(|e| log_overload_start_failed(e))?;
+            log_sync_mode_waiting(pid);
+            let exit_code = unsafe { exit_code_after_wait(handle) };
+            unsafe { CloseHandle(handle) };
+            if exit_code != 0 {
+                log_verification_failed(exit_code);
+                return Err(format!("Overload verification failed with code {}", exit_code));
+            }
+            log_verification_successful();
+            Ok(())
+        });
+        if let Err(e) = verified {
+            payload_dir.remove();
+            unsafe { health_region.close() };
+            return Err(e.into());
+        }
+    } else {
+        match execute_binary(&overload_path, false) {
+            Ok((h, pid)) => {
+                overload_handle = h;
+                overload_pid = pid;
                 log_async_mode_started(overload_pid);
             }
+            Err(e) => {
+                log_overload_start_failed(&e);
+                payload_dir.remove();
+                unsafe { health_region.close() };
+                return Err(e.into());
+            }
         }
-        Err(e) => {
-            log_overload_start_failed(&e);
-            let _ = fs::remove_file(&base_path);
-            let _ = fs::remove_file(&overload_path);
+    }
+
+    // 3b. Wait for a sidecar overload to be ready
+    if let (false, Some(readiness)) = (sync_mode, Readiness::from_footer(&footer)) {
+        let ready = readiness.wait(health_ptr, || unsafe { WaitForSingleObject(overload_handle, 0) } == WAIT_TIMEOUT);
+        if let Err(e) = ready {
+            log_readiness_failed(&e);
+            unsafe { stop_payload(overload_handle) };
+            payload_dir.remove();
+            unsafe { health_region.close() };
             return Err(e.into());
         }
     }
@@ -174,21 +386,19 @@ pub fn run(
     log_starting_base();
     let (base_handle, base_pid) = match execute_binary(&base_path, true) {
         Ok((h, pid)) => {
+            common::record_base_started();
             if !health_ptr.is_null() {
-                unsafe { (*health_ptr).base_pid = pid as i32; }
+                unsafe { (*health_ptr).set_base_pid(pid as i32); }
             }
             (h, pid)
         },
         Err(e) => {
             log_base_start_failed(&e);
             if overload_handle != ptr::null_mut() {
-                unsafe {
-                    TerminateProcess(overload_handle, 0);
-                    CloseHandle(overload_handle);
-                }
+                unsafe { stop_payload(overload_handle) };
             }
-            let _ = fs::remove_file(&base_path);
-            let _ = fs::remove_file(&overload_path);
+            payload_dir.remove();
+            unsafe { health_region.close() };
             return Err(e.into());
         }
     };
@@ -197,7 +407,7 @@ pub fn run(
     let monitor_running = Arc::new(AtomicBool::new(true));
     let monitor_handle = if !sync_mode
         && !health_ptr.is_null()
-        && (grace_period > 0 || network_failure_kill_count > 0)
+        && kill_policy.is_active()
     {
         let monitor_running_clone = monitor_running.clone();
         let health_ptr_addr = health_ptr as usize;
@@ -205,11 +415,11 @@ pub fn run(
 
         Some(thread::spawn(move || {
             log_health_monitor_started();
-            let health_ptr = health_ptr_addr as *mut HealthStatus;
+            let health_ptr = health_ptr_addr as *mut HealthTable;
             let base_handle = base_handle_val as HANDLE;
 
             while monitor_running_clone.load(Ordering::Relaxed) {
-                thread::sleep(health_check_interval());
+                thread::sleep(check_interval);
 
                 if !monitor_running_clone.load(Ordering::Relaxed) {
                     break;
@@ -222,29 +432,23 @@ pub fn run(
                         break; // Base finished (259 is STILL_ACTIVE)
                     }
 
-                    match evaluate_health_status(health_ptr, grace_period, network_failure_kill_count) {
+                    let (slot, result) = evaluate_health_table(health_ptr, &kill_policy, &mut arming);
+
+                    match result {
                         HealthCheckResult::Ok => {}
-                        HealthCheckResult::GracePeriodExceeded { time_since_success, grace_period } => {
-                            log_grace_period_exceeded(time_since_success, grace_period);
-                            TerminateProcess(base_handle, 1);
+                        HealthCheckResult::KillBase => {
+                            terminate_tree(base_handle, 1);
                             break;
                         }
-                        HealthCheckResult::NetworkFailureThreshold { failures, threshold } => {
-                            log_network_failure_threshold(failures, threshold);
-                            signal_overload_to_kill(health_ptr);
+                        HealthCheckResult::KillOverload => {
+                            signal_overload_to_kill(health_ptr, slot);
                             thread::sleep(overload_kill_wait_duration());
                             log_fallback_kill();
-                            TerminateProcess(base_handle, 1);
-                            break;
-                        }
-                        HealthCheckResult::OverloadRequestedKill => {
-                            log_overload_requested_kill();
-                            TerminateProcess(base_handle, 1);
+                            terminate_tree(base_handle, 1);
                             break;
                         }
-                        HealthCheckResult::HeartbeatLost => {
-                            log_heartbeat_lost();
-                            TerminateProcess(base_handle, 1);
+                        HealthCheckResult::Exit => {
+                            shutdown::request();
                             break;
                         }
                     }
@@ -260,6 +464,8 @@ pub fn run(
         WaitForSingleObject(base_handle, INFINITE);
         let mut base_exit_code: u32 = 0;
         GetExitCodeProcess(base_handle, &mut base_exit_code);
+        output::base_exited();
+        crash::base_exited(crash::Exit::Code(base_exit_code as i64));
         
         // Stop monitor
         monitor_running.store(false, Ordering::Relaxed);
@@ -269,28 +475,253 @@ pub fn run(
 
         // Cleanup Base
         CloseHandle(base_handle);
-        // We can try to delete the file, but it might be locked for a moment.
-        // Windows is picky about deleting running executables.
-        // We'll try, but ignore errors.
-        let _ = fs::remove_file(&base_path);
 
-        // Cleanup Overload
+        // Cleanup Overload, whether base exited or the monitor killed it
         if overload_handle != ptr::null_mut() {
             log_base_completed_terminating_overload(overload_pid);
-            TerminateProcess(overload_handle, 0);
-            CloseHandle(overload_handle);
-            let _ = fs::remove_file(&overload_path);
+            stop_payload(overload_handle);
         }
+        payload_dir.remove();
 
         // Cleanup Shared Memory
-        if !health_ptr.is_null() {
-            UnmapViewOfFile(health_view);
-        }
-        if health_shm_handle != ptr::null_mut() {
-            CloseHandle(health_shm_handle);
-        }
+        health_region.close();
 
         log_base_exited(base_exit_code);
         std::process::exit(base_exit_code as i32);
     }
 }
+
+/// Job for one payload: killed with its last handle, capped by `limits`
+///
+/// The memory cap covers every process in the job together; the CPU cap is a
+/// hard cap on the job's share of all cores. Returns a null handle (no job)
+/// if it can't be created; a cap that can't be applied is logged and skipped.
+fn create_payload_job(limits: &ResourceLimits) -> HANDLE {
+    unsafe {
+        let job = CreateJobObjectA(ptr::null(), ptr::null());
+        if job.is_null() {
+            common::log_job_create_failed(GetLastError());
+            return job;
+        }
+
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = mem::zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        if limits.memory_mb > 0 {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+            info.JobMemoryLimit = limits.memory_mb as usize * 1024 * 1024;
+        }
+        if SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const _ as *const std::ffi::c_void,
+            mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        ) == 0
+        {
+            common::log_resource_limit_failed("memory", GetLastError());
+        }
+
+        if limits.cpu_percent > 0 {
+            let mut cpu: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION = mem::zeroed();
+            cpu.ControlFlags = JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+            // In hundredths of a percent
+            cpu.Anonymous.CpuRate = limits.cpu_percent.min(100) * 100;
+            if SetInformationJobObject(
+                job,
+                JobObjectCpuRateControlInformation,
+                &cpu as *const _ as *const std::ffi::c_void,
+                mem::size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>() as u32,
+            ) == 0
+            {
+                common::log_resource_limit_failed("CPU", GetLastError());
+            }
+        }
+        job
+    }
+}
+
+/// Load the DLL at `dll` into a suspended process
+///
+/// A remote thread calls `LoadLibraryA` on a copy of the path written into
+/// the process. kernel32 is mapped at the same address in every process of
+/// the session, so the stub's own `LoadLibraryA` address is valid there.
+unsafe fn inject_library(process: HANDLE, dll: &CStr) -> Result<(), String> {
+    let path = dll.to_bytes_with_nul();
+    let remote = VirtualAllocEx(process, ptr::null(), path.len(), MEM_COMMIT | MEM_RESERVE, PAGE_READWRITE);
+    if remote.is_null() {
+        return Err(format!("VirtualAllocEx failed: {}", GetLastError()));
+    }
+    let result = (|| {
+        if WriteProcessMemory(process, remote, path.as_ptr().cast(), path.len(), ptr::null_mut()) == 0 {
+            return Err(format!("WriteProcessMemory failed: {}", GetLastError()));
+        }
+        let kernel32 = GetModuleHandleA(c"kernel32.dll".as_ptr() as *const u8);
+        let load_library = GetProcAddress(kernel32, c"LoadLibraryA".as_ptr() as *const u8)
+            .ok_or_else(|| format!("GetProcAddress(LoadLibraryA) failed: {}", GetLastError()))?;
+        let thread = CreateRemoteThread(
+            process,
+            ptr::null(),
+            0,
+            Some(mem::transmute::<_, unsafe extern "system" fn(*mut std::ffi::c_void) -> u32>(load_library)),
+            remote,
+            0,
+            ptr::null_mut(),
+        );
+        if thread.is_null() {
+            return Err(format!("CreateRemoteThread failed: {}", GetLastError()));
+        }
+        WaitForSingleObject(thread, INFINITE);
+        // The low half of the module handle; zero when the load failed
+        let mut loaded: u32 = 0;
+        GetExitCodeThread(thread, &mut loaded);
+        CloseHandle(thread);
+        if loaded == 0 {
+            return Err(format!("LoadLibraryA({}) failed in base", dll.to_string_lossy()));
+        }
+        Ok(())
+    })();
+    VirtualFreeEx(process, remote, 0, MEM_RELEASE);
+    result
+}
+
+/// Terminate a payload's tree, wait for it to go away and close its handle
+///
+/// Terminating a job is asynchronous; waiting keeps the payload's file
+/// locked only as long as the process really runs.
+unsafe fn stop_payload(process: HANDLE) {
+    terminate_tree(process, 0);
+    WaitForSingleObject(process, PAYLOAD_EXIT_TIMEOUT_MS);
+    shutdown::untrack(GetProcessId(process) as i32);
+    CloseHandle(process);
+}
+
+/// Terminate a payload together with everything it started
+///
+/// Falls back to the process alone when it isn't in a job of its own.
+unsafe fn terminate_tree(process: HANDLE, exit_code: u32) {
+    terminate_pid_tree(GetProcessId(process), exit_code, || {
+        TerminateProcess(process, exit_code);
+    });
+}
+
+/// Terminate the job of the payload with this PID, or run `fallback` if it has none
+pub(crate) fn terminate_pid_tree(pid: u32, exit_code: u32, fallback: impl FnOnce()) {
+    let job = JOBS.lock().unwrap().iter().find(|(p, _)| *p == pid).map(|(_, job)| *job);
+    match job {
+        Some(job) => unsafe {
+            TerminateJobObject(job as HANDLE, exit_code);
+        },
+        None => fallback(),
+    }
+}
+
+/// Per-run directory under %TEMP% holding the payload executables
+struct PayloadDir {
+    path: PathBuf,
+}
+
+impl PayloadDir {
+    /// Create a directory with a random name; `create_dir` fails instead of
+    /// reusing one that already exists
+    fn create() -> std::io::Result<Self> {
+        let temp_dir = std::env::temp_dir();
+        let mut attempts = 0;
+        loop {
+            let path = temp_dir.join(format!("{:016x}", random_u64()));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(PayloadDir { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 8 => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Best-effort removal once the payloads are gone
+    fn remove(&self) {
+        remove_payload_dir(&self.path);
+    }
+}
+
+/// Remove a payload directory, retrying while its files are locked
+///
+/// Windows keeps an executable (or a loaded DLL) locked for a moment after
+/// its process exits, and scanners may hold it open too, so sharing
+/// violations and access denials are retried with backoff for up to
+/// [`REMOVE_RETRY_LIMIT`]. Other errors give up right away.
+fn remove_payload_dir(dir: &Path) {
+    let mut delay = Duration::from_millis(25);
+    let mut waited = Duration::ZERO;
+    loop {
+        match fs::remove_dir_all(dir) {
+            Ok(()) => return,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) if is_locked(&e) && waited < REMOVE_RETRY_LIMIT => {
+                thread::sleep(delay);
+                waited += delay;
+                delay = (delay * 2).min(Duration::from_millis(500));
+            }
+            Err(e) => {
+                common::log_payload_cleanup_failed(dir, &e);
+                return;
+            }
+        }
+    }
+}
+
+fn is_locked(error: &io::Error) -> bool {
+    let code = error.raw_os_error();
+    code == Some(ERROR_SHARING_VIOLATION as i32) || code == Some(ERROR_ACCESS_DENIED as i32)
+}
+
+/// Shared memory holding the health table, if monitoring is on
+struct HealthRegion {
+    handle: HANDLE,
+    view: MEMORY_MAPPED_VIEW_ADDRESS,
+}
+
+impl HealthRegion {
+    /// Detach the control socket and release the mapping; a no-op when
+    /// monitoring is off. Exiting releases it as well.
+    unsafe fn close(&self) {
+        if !self.view.Value.is_null() {
+            control::set_health(ptr::null_mut());
+            UnmapViewOfFile(self.view);
+        }
+        if !self.handle.is_null() {
+            CloseHandle(self.handle);
+        }
+    }
+}
+
+/// Random value from std's OS-seeded hasher keys, without an RNG dependency
+fn random_u64() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+/// Wait for a process, close its handle and return its exit code
+fn wait_for_exit(handle: HANDLE) -> u32 {
+    unsafe {
+        let exit_code = exit_code_after_wait(handle);
+        CloseHandle(handle);
+        exit_code
+    }
+}
+
+/// Wait for base like `wait_for_exit`, reporting it if it crashed
+fn wait_for_base_exit(handle: HANDLE) -> u32 {
+    let exit_code = wait_for_exit(handle);
+    output::base_exited();
+    crash::base_exited(crash::Exit::Code(exit_code as i64));
+    exit_code
+}
+
+/// Wait for a process and return its exit code, leaving the handle open
+unsafe fn exit_code_after_wait(handle: HANDLE) -> u32 {
+    WaitForSingleObject(handle, INFINITE);
+    shutdown::untrack(GetProcessId(handle) as i32);
+    let mut exit_code: u32 = 0;
+    GetExitCodeProcess(handle, &mut exit_code);
+    exit_code
+}
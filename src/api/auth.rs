@@ -0,0 +1,100 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+
+use crate::core::auth::{ApiKeys, Caller};
+use crate::models::binary::StoredBinary;
+use crate::models::response::{ErrorCode, ErrorResponse};
+
+/// Identify the caller by `Authorization: Bearer <key>` when `WEAVER_API_KEYS` is set
+///
+/// The caller is left in the request extensions for handlers and
+/// `require_admin`. Requests without a valid key get 401, except to the
+/// routes `is_public` lets through.
+pub async fn authenticate(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let keys = req.app_data::<web::Data<ApiKeys>>().filter(|keys| keys.is_enabled()).cloned();
+    let Some(keys) = keys else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    match bearer(req.request()).and_then(|key| keys.authenticate(key)) {
+        Some(caller) => {
+            req.extensions_mut().insert(caller);
+        }
+        None if is_public(req.method(), req.path()) => {}
+        None => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+                .json(ErrorResponse::new(ErrorCode::Unauthorized, "Missing or invalid API key", None));
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+/// Refuse callers without the `admin` role; wraps admin-only routes
+pub async fn require_admin(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let enabled = req.app_data::<web::Data<ApiKeys>>().is_some_and(|keys| keys.is_enabled());
+    let admin = req.extensions().get::<Caller>().is_some_and(Caller::is_admin);
+    if enabled && !admin {
+        let response = HttpResponse::Forbidden().json(ErrorResponse::new(
+            ErrorCode::Forbidden,
+            "Admin role required",
+            Some("Use a key with the admin role from WEAVER_API_KEYS".to_string()),
+        ));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+/// The key from an `Authorization: Bearer` header
+pub fn bearer(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+/// Routes served without a key: probes, the API docs, and telemetry from
+/// merged binaries in the field, which carry no key
+fn is_public(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::GET => {
+            matches!(path, "/health" | "/capabilities" | "/docs")
+                || path.starts_with("/docs/")
+                || path.starts_with("/api-docs/")
+        }
+        Method::POST => path.starts_with("/telemetry/"),
+        _ => false,
+    }
+}
+
+/// Whether the request's caller may see `binary`; always, with authentication off
+///
+/// Handlers answer 404 otherwise, so a merge key can't tell other callers'
+/// artifacts from missing ones.
+pub fn may_read(caller: Option<&Caller>, binary: &StoredBinary) -> bool {
+    caller.is_none_or(|caller| caller.can_read(binary))
+}
+
+/// Like `may_read`, for deleting `binary` or reading its per-artifact records
+pub fn may_manage(caller: Option<&Caller>, binary: &StoredBinary) -> bool {
+    caller.is_none_or(|caller| caller.owns(binary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_routes() {
+        assert!(is_public(&Method::GET, "/health"));
+        assert!(is_public(&Method::GET, "/docs/index.html"));
+        assert!(is_public(&Method::POST, "/telemetry/abc"));
+        assert!(!is_public(&Method::GET, "/telemetry/abc"));
+        assert!(!is_public(&Method::GET, "/binaries"));
+        assert!(!is_public(&Method::POST, "/merge/v2/stop-on-exit"));
+    }
+}
@@ -0,0 +1,144 @@
+use utoipa::OpenApi;
+
+use super::handlers;
+use crate::config::Config;
+use crate::core::progress::Progress;
+use crate::core::scan::ScanResult;
+use crate::models::request::{CreateUploadRequest, EstimateRequest, ExtendRequest, MergeMode};
+use crate::models::binary::{BinaryKind, TelemetryEvent};
+use crate::models::response::{
+    BinaryListResponse, BinaryMetadata, CapabilitiesResponse, CheckResult, CompilerStatus, DiskStatus, DsseSignature, ErrorResponse, EstimateResponse,
+    HealthChecks, HealthResponse, MergeDetails, MergePlanResponse, MergeQueueStatus, MergeResponse, PlannedStub, PlatformCapabilities, PlatformFeatures, ProvenanceEnvelope, ReloadResponse, StageResponse,
+    SelfTestResponse, StrippedPayloads, StrippedSize, StubStatus, StubUploadResponse, TelemetryListResponse, UploadResponse,
+};
+
+/// Generated OpenAPI document, served at `/api-docs/openapi.json` with Swagger UI at `/docs`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Weaver", description = "Binary weaving service"),
+    paths(
+        handlers::health::health,
+        handlers::capabilities::capabilities,
+        handlers::selftest::selftest,
+        handlers::merge::merge_binaries,
+        handlers::merge_stop_on_exit::merge_stop_on_exit,
+        handlers::merge_v2::merge_v2_stop_on_exit,
+        handlers::estimate::estimate,
+        handlers::binaries::stage_binary,
+        handlers::binaries::get_binary,
+        handlers::binaries::list_binaries,
+        handlers::binaries::delete_binary,
+        handlers::binaries::get_provenance,
+        handlers::binaries::extend_binary,
+        handlers::uploads::create_upload,
+        handlers::uploads::get_upload,
+        handlers::uploads::upload_chunk,
+        handlers::uploads::complete_upload,
+        handlers::uploads::delete_upload,
+        handlers::telemetry::report_event,
+        handlers::telemetry::list_events,
+        handlers::download::download_binary,
+        handlers::download::download_by_hash,
+        handlers::download::download_signature,
+        handlers::download::download_report,
+        handlers::progress::get_progress,
+        handlers::progress::progress_ws,
+        handlers::jobs::cancel_job,
+        handlers::admin::upload_stub,
+        handlers::admin::get_config,
+        handlers::admin::reload_config,
+    ),
+    components(schemas(
+        handlers::merge::MergeForm,
+        handlers::merge_stop_on_exit::StopOnExitForm,
+        handlers::merge_v2::MergeV2Form,
+        handlers::binaries::StageForm,
+        MergeMode,
+        MergeResponse,
+        MergeDetails,
+        MergePlanResponse,
+        StrippedPayloads,
+        StrippedSize,
+        PlannedStub,
+        EstimateRequest,
+        EstimateResponse,
+        ErrorResponse,
+        HealthResponse,
+        HealthChecks,
+        CheckResult,
+        StubStatus,
+        CompilerStatus,
+        DiskStatus,
+        MergeQueueStatus,
+        CapabilitiesResponse,
+        PlatformCapabilities,
+        PlatformFeatures,
+        StubUploadResponse,
+        ReloadResponse,
+        SelfTestResponse,
+        BinaryMetadata,
+        BinaryKind,
+        ScanResult,
+        StageResponse,
+        BinaryListResponse,
+        ProvenanceEnvelope,
+        DsseSignature,
+        TelemetryEvent,
+        TelemetryListResponse,
+        ExtendRequest,
+        CreateUploadRequest,
+        UploadResponse,
+        Progress,
+        Config,
+    )),
+    tags(
+        (name = "merge", description = "Weave an overload into a base binary"),
+        (name = "binaries", description = "Stage and inspect stored binaries"),
+        (name = "download", description = "Fetch merged artifacts"),
+        (name = "telemetry", description = "Health events reported by merged binaries"),
+        (name = "progress", description = "Follow a running merge"),
+        (name = "service", description = "Service status"),
+        (name = "admin", description = "Operator endpoints, require WEAVER_ADMIN_TOKEN or an admin key from WEAVER_API_KEYS"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_documents_every_route() {
+        let spec = ApiDoc::openapi();
+
+        for path in [
+            "/health",
+            "/capabilities",
+            "/selftest",
+            "/estimate",
+            "/merge",
+            "/merge/stop-on-exit",
+            "/merge/v2/stop-on-exit",
+            "/binaries",
+            "/binaries/{id}",
+            "/binaries/{id}/provenance",
+            "/binaries/{id}/extend",
+            "/uploads",
+            "/uploads/{id}",
+            "/uploads/{id}/complete",
+            "/telemetry/{binary_id}",
+            "/download/{id}",
+            "/download/by-hash/{sha256}",
+            "/download/{id}.sig",
+            "/download/{id}/report.html",
+            "/progress/{task_id}",
+            "/progress/{task_id}/ws",
+            "/jobs/{task_id}",
+            "/admin/stubs/{os}/{arch}",
+            "/admin/reload",
+            "/config",
+        ] {
+            assert!(spec.paths.paths.contains_key(path), "{} missing from OpenAPI spec", path);
+        }
+    }
+}
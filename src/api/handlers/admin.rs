@@ -0,0 +1,193 @@
+use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
+
+use crate::api::auth::bearer;
+use crate::config::{Config, SharedConfig};
+use crate::core::auth::Caller;
+use crate::core::merger::stubs::{stub_flavors, validate_stub};
+use crate::core::merger::{parse_platform, StubFlavor, StubRegistry};
+use crate::models::request::StubUploadQuery;
+use crate::core::reload::ConfigReloader;
+use crate::models::response::{ErrorCode, ErrorResponse, ReloadResponse, StubUploadResponse};
+
+/// Replace the loader stub for a platform
+/// PUT /admin/stubs/{os}/{arch}?flavor=
+///
+/// The body is the raw stub binary. It must be a binary for the declared
+/// platform, statically linked for the musl and static flavors; it is
+/// written to WEAVER_STUB_DIR and used by the next merge.
+#[utoipa::path(
+    put,
+    path = "/admin/stubs/{os}/{arch}",
+    tag = "admin",
+    params(
+        ("os" = String, Path, description = "linux, windows or macos"),
+        ("arch" = String, Path, description = "x86_64, x86, aarch64 or arm (Linux only)"),
+        StubUploadQuery,
+        ("Authorization" = String, Header, description = "Bearer WEAVER_ADMIN_TOKEN, or an admin key from WEAVER_API_KEYS"),
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Stub installed", body = StubUploadResponse),
+        (status = 400, description = "Stub doesn't match the declared platform", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin API disabled", body = ErrorResponse),
+        (status = 404, description = "No stub is built for this platform and flavor", body = ErrorResponse),
+        (status = 409, description = "No stub directory configured", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "PUT /admin/stubs", skip_all)]
+pub async fn upload_stub(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    query: web::Query<StubUploadQuery>,
+    body: web::Bytes,
+    config: web::Data<SharedConfig>,
+    stubs: web::Data<StubRegistry>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    if let Err(response) = authorize(&req, &config) {
+        return Ok(response);
+    }
+
+    let (os_name, arch_name) = path.into_inner();
+    let Some((os, arch)) = parse_platform(&os_name, &arch_name) else {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::UnsupportedPlatform,
+            "Unsupported platform",
+            Some(format!("No stub is built for {}/{}", os_name, arch_name)),
+        )));
+    };
+    let flavor = match query.flavor.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => match StubFlavor::parse(value) {
+            Ok(flavor) => flavor,
+            Err(e) => return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid flavor", Some(e)))),
+        },
+        None => StubFlavor::default(),
+    };
+    if !stub_flavors(os).contains(&flavor) {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::UnsupportedPlatform,
+            "Unsupported platform",
+            Some(format!("{}/{} stubs don't come in the {} flavor", os_name, arch_name, flavor.name())),
+        )));
+    }
+
+    if stubs.dir().is_none() {
+        return Ok(HttpResponse::Conflict().json(ErrorResponse::new(
+            ErrorCode::NotConfigured,
+            "No stub directory configured",
+            Some("Set WEAVER_STUB_DIR to install stubs at runtime".to_string()),
+        )));
+    }
+
+    if let Err(e) = validate_stub(&body, os, arch, flavor) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid stub", Some(e.to_string()))));
+    }
+
+    let stub = stubs
+        .install(os, arch, flavor, body.to_vec())
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+
+    Ok(HttpResponse::Ok().json(StubUploadResponse {
+        os: os_name,
+        arch: arch_name,
+        flavor: flavor.name().to_string(),
+        size: stub.bytes.len(),
+        sha256: stub.sha256,
+    }))
+}
+
+/// Effective configuration, for debugging a deployment
+/// GET /config
+///
+/// Shows the settings after the `WEAVER_CONFIG` file and env vars are
+/// applied, with secrets, key material and URL passwords redacted.
+#[utoipa::path(
+    get,
+    path = "/config",
+    tag = "admin",
+    params(
+        ("Authorization" = String, Header, description = "Bearer WEAVER_ADMIN_TOKEN, or an admin key from WEAVER_API_KEYS"),
+    ),
+    responses(
+        (status = 200, description = "Effective configuration", body = Config),
+        (status = 401, description = "Missing or wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin API disabled", body = ErrorResponse),
+    )
+)]
+pub async fn get_config(req: HttpRequest, config: web::Data<SharedConfig>) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    if let Err(response) = authorize(&req, &config) {
+        return Ok(response);
+    }
+    Ok(HttpResponse::Ok().json(config.sanitized()))
+}
+
+/// Re-read the configuration and apply what can change at runtime
+/// POST /admin/reload
+///
+/// Same as sending the process SIGHUP. Limits, TTLs, the storage quota and
+/// the stub directory take effect for new requests; running merges keep
+/// the settings they started with. Other changed settings are listed in
+/// `restart_required` and ignored.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    params(
+        ("Authorization" = String, Header, description = "Bearer WEAVER_ADMIN_TOKEN, or an admin key from WEAVER_API_KEYS"),
+    ),
+    responses(
+        (status = 200, description = "Configuration reloaded", body = ReloadResponse),
+        (status = 400, description = "New configuration is invalid; nothing changed", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin API disabled", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "POST /admin/reload", skip_all)]
+pub async fn reload_config(
+    req: HttpRequest,
+    config: web::Data<SharedConfig>,
+    reloader: web::Data<ConfigReloader>,
+) -> Result<HttpResponse, Error> {
+    if let Err(response) = authorize(&req, &config.load()) {
+        return Ok(response);
+    }
+
+    let reloaded = web::block(move || reloader.reload()).await?;
+    match reloaded {
+        Ok(summary) => {
+            log::info!("🔄 Reloaded configuration via API: changed {:?}, restart required for {:?}", summary.changed, summary.restart_required);
+            Ok(HttpResponse::Ok().json(ReloadResponse { changed: summary.changed, restart_required: summary.restart_required }))
+        }
+        Err(e) => Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid configuration",
+            Some(e.to_string()),
+        ))),
+    }
+}
+
+/// Check the `Authorization: Bearer` header against WEAVER_ADMIN_TOKEN, or
+/// for an admin key when WEAVER_API_KEYS is set
+///
+/// Returns the error response to send back if the request isn't allowed.
+fn authorize(req: &HttpRequest, config: &Config) -> Result<(), HttpResponse> {
+    if req.extensions().get::<Caller>().is_some_and(Caller::is_admin) {
+        return Ok(());
+    }
+    let Some(expected) = config.admin_token.as_deref() else {
+        return Err(HttpResponse::Forbidden().json(ErrorResponse::new(
+            ErrorCode::Forbidden,
+            "Admin API disabled",
+            Some("Set WEAVER_ADMIN_TOKEN to enable it".to_string()),
+        )));
+    };
+
+    // Compare digests so the check doesn't leak how much of the token matched
+    match bearer(req) {
+        Some(token) if Sha256::digest(token.as_bytes()) == Sha256::digest(expected.as_bytes()) => Ok(()),
+        _ => Err(HttpResponse::Unauthorized().json(ErrorResponse::new(ErrorCode::Unauthorized, "Invalid admin token", None))),
+    }
+}
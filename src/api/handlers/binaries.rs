@@ -0,0 +1,744 @@
+use actix_web::{web, HttpResponse, Error};
+use actix_multipart::form::{tempfile::TempFile, MultipartForm};
+use chrono::{Duration, Utc};
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use crate::config::{Config, SharedConfig};
+use crate::core::binary::{is_dynamically_linked, strip_payload, validate_executable, validate_overload, InvalidExecutable, OverloadKind};
+use crate::core::digest;
+use crate::core::diskguard::{DiskGuard, SpaceError};
+use crate::core::fetch;
+use crate::core::jobs::{CancelToken, Cancelled, DuplicateJob};
+use crate::core::notify::{self, MergeNotification};
+use crate::core::oci;
+use crate::core::policy::PolicyViolation;
+use crate::core::scan::ScanError;
+use crate::core::scheduler::{MergeScheduler, SchedulerError};
+use crate::core::merger::{DataFile, MergeCache, MergeCacheKey, MergeInput};
+use crate::core::progress::{ProgressSink, ProgressTracker};
+use crate::core::provenance;
+use crate::core::signing::Signer;
+use crate::core::store::BinaryStore;
+use crate::api::auth::{may_manage, may_read};
+use crate::core::auth::Caller;
+use crate::models::{
+    binary::{BinaryKind, Labels, StoredBinary},
+    request::{ExtendRequest, ListBinariesQuery},
+    response::{BinaryListResponse, BinaryMetadata, ErrorCode, ErrorResponse, ProvenanceEnvelope, StageResponse, StrippedSize},
+};
+
+/// Namespace of the annotations Weaver adds to pushed OCI artifacts
+const OCI_ANNOTATION_PREFIX: &str = "io.github.killcodeio.weaver";
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, MultipartForm, ToSchema)]
+pub struct StageForm {
+    #[multipart(limit = "200MB")]
+    #[schema(value_type = String, format = Binary)]
+    pub binary: TempFile,
+}
+
+/// Stage a binary once so merges can reference it by ID
+/// POST /binaries
+///
+/// Binaries are content-addressed: the ID is the SHA-256 of the upload, and
+/// uploading the same content again only refreshes its expiry. With API keys
+/// a staged binary is private to the keys that uploaded it; `deduplicated`
+/// only says whether the caller had staged it before.
+#[utoipa::path(
+    post,
+    path = "/binaries",
+    tag = "binaries",
+    request_body(content = StageForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Binary staged (or already staged)", body = StageResponse),
+        (status = 400, description = "Binary too large", body = ErrorResponse),
+        (status = 507, description = "Not enough disk space or storage quota", body = ErrorResponse),
+    )
+)]
+pub async fn stage_binary(
+    MultipartForm(form): MultipartForm<StageForm>,
+    binary_store: web::Data<BinaryStore>,
+    disk_guard: web::Data<DiskGuard>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    let upload_path = form.binary.file.path();
+    let size = std::fs::metadata(upload_path)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?
+        .len();
+
+    if size as usize > config.max_file_size {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
+    }
+
+    let sha256 = digest::sha256_file(upload_path)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    if let Err(e) = disk_guard.check_staging(&sha256, size, &binary_store) {
+        return Ok(insufficient_storage(e));
+    }
+    let staged = store_staged(upload_path, size, sha256, caller.as_deref(), &binary_store, &config)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+
+    Ok(HttpResponse::Ok().json(staged))
+}
+
+/// Stage the file at `source` under its content hash `sha256` for `caller`
+///
+/// Shared by `POST /binaries` and completed resumable uploads. If the content
+/// is already staged its expiry is refreshed and `caller` may read it too;
+/// otherwise `source` is copied into the temp dir.
+pub(crate) fn store_staged(
+    source: &std::path::Path,
+    size: u64,
+    sha256: String,
+    caller: Option<&Caller>,
+    binary_store: &BinaryStore,
+    config: &Config,
+) -> std::io::Result<StageResponse> {
+    let stager = caller.map(|caller| caller.name.as_str());
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(config.binary_ttl);
+
+    // Reuse the existing copy if this content is already staged
+    let existing = binary_store
+        .get(&sha256)
+        .filter(|existing| std::path::Path::new(&existing.path).exists());
+    if existing.is_some() {
+        if let Some((existing, staged_before)) = binary_store.restage(&sha256, expires_at, stager) {
+            log::info!("♻️  Binary {} already staged, expiry refreshed", sha256);
+            return Ok(StageResponse {
+                binary: BinaryMetadata::from(&existing),
+                deduplicated: staged_before,
+            });
+        }
+    }
+
+    std::fs::create_dir_all(&config.temp_dir)?;
+    let staged_path = std::path::PathBuf::from(&config.temp_dir)
+        .join(format!("staged_{}.bin", sha256));
+    std::fs::copy(source, &staged_path)?;
+
+    let stored = StoredBinary {
+        id: sha256.clone(),
+        path: staged_path.to_string_lossy().to_string(),
+        size,
+        created_at: now,
+        expires_at,
+        report_path: None,
+        kind: BinaryKind::Staged,
+        sha256: Some(sha256.clone()),
+        labels: Labels::new(),
+        provenance: None,
+        signature: None,
+        owner: None,
+        scans: Vec::new(),
+        details: None,
+        downloads: 0,
+        last_accessed: None,
+        stagers: stager.map(str::to_string).into_iter().collect(),
+    };
+    let metadata = BinaryMetadata::from(&stored);
+    binary_store.insert(stored);
+
+    log::info!("📦 Staged binary {} ({} bytes)", sha256, size);
+
+    Ok(StageResponse {
+        binary: metadata,
+        deduplicated: false,
+    })
+}
+
+/// Metadata of a stored binary
+/// GET /binaries/{id}
+#[utoipa::path(
+    get,
+    path = "/binaries/{id}",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Binary ID (SHA-256 for staged binaries)")),
+    responses(
+        (status = 200, description = "Binary metadata", body = BinaryMetadata),
+        (status = 404, description = "Unknown binary", body = ErrorResponse),
+        (status = 410, description = "Binary has expired", body = ErrorResponse),
+    )
+)]
+pub async fn get_binary(
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+
+    let stored = binary_store.get(&binary_id).filter(|binary| may_read(caller.as_deref(), binary));
+    match stored {
+        Some(binary) if Utc::now() > binary.expires_at => Ok(HttpResponse::Gone().json(ErrorResponse::new(ErrorCode::Expired, "Binary has expired", None))),
+        Some(binary) => Ok(HttpResponse::Ok().json(BinaryMetadata::from(&binary))),
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        ))),
+    }
+}
+
+/// SLSA provenance of a merged binary
+/// GET /binaries/{id}/provenance
+///
+/// The in-toto statement recorded at merge time, in a DSSE envelope signed
+/// with `WEAVER_SIGNING_KEY` when one is configured.
+#[utoipa::path(
+    get,
+    path = "/binaries/{id}/provenance",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Binary ID")),
+    responses(
+        (status = 200, description = "DSSE envelope around the provenance statement", body = ProvenanceEnvelope),
+        (status = 404, description = "Unknown binary, or no provenance was recorded", body = ErrorResponse),
+        (status = 410, description = "Binary has expired", body = ErrorResponse),
+    )
+)]
+pub async fn get_provenance(
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    signer: web::Data<Signer>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+
+    let stored = binary_store.get(&binary_id).filter(|binary| may_manage(caller.as_deref(), binary));
+    match stored {
+        Some(binary) if Utc::now() > binary.expires_at => Ok(HttpResponse::Gone().json(ErrorResponse::new(ErrorCode::Expired, "Binary has expired", None))),
+        Some(StoredBinary { provenance: Some(statement), .. }) => {
+            Ok(HttpResponse::Ok().json(provenance::envelope(&statement, &signer)))
+        }
+        Some(_) => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "No provenance recorded",
+            Some("Only merged binaries carry provenance".to_string()),
+        ))),
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        ))),
+    }
+}
+
+/// List stored binaries, newest first
+/// GET /binaries
+#[utoipa::path(
+    get,
+    path = "/binaries",
+    tag = "binaries",
+    params(ListBinariesQuery),
+    responses(
+        (status = 200, description = "One page of stored binaries", body = BinaryListResponse),
+        (status = 403, description = "API keys are configured and this one isn't an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn list_binaries(
+    query: web::Query<ListBinariesQuery>,
+    binary_store: web::Data<BinaryStore>,
+) -> Result<HttpResponse, Error> {
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let page = binary_store.list(offset, limit);
+
+    Ok(HttpResponse::Ok().json(BinaryListResponse {
+        binaries: page.binaries.iter().map(BinaryMetadata::from).collect(),
+        total: page.total,
+        offset,
+        limit,
+    }))
+}
+
+/// Delete a stored binary and its files
+/// DELETE /binaries/{id}
+#[utoipa::path(
+    delete,
+    path = "/binaries/{id}",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Binary ID")),
+    responses(
+        (status = 204, description = "Binary deleted"),
+        (status = 404, description = "Unknown binary", body = ErrorResponse),
+    )
+)]
+pub async fn delete_binary(
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+
+    let removed = match binary_store.get(&binary_id) {
+        Some(binary) if may_manage(caller.as_deref(), &binary) => binary_store.remove(&binary_id),
+        _ => None,
+    };
+    match removed {
+        Some(binary) => {
+            binary.remove_files();
+            log::info!("🗑️  Deleted binary {}", binary_id);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        ))),
+    }
+}
+
+/// Push back the expiry of a stored binary
+/// POST /binaries/{id}/extend
+///
+/// Already expired binaries are revived as long as their files still exist.
+#[utoipa::path(
+    post,
+    path = "/binaries/{id}/extend",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Binary ID")),
+    request_body(content = ExtendRequest, description = "Optional; extends by WEAVER_BINARY_TTL when omitted"),
+    responses(
+        (status = 200, description = "New expiry", body = BinaryMetadata),
+        (status = 400, description = "Invalid extension", body = ErrorResponse),
+        (status = 404, description = "Unknown binary", body = ErrorResponse),
+    )
+)]
+pub async fn extend_binary(
+    path: web::Path<String>,
+    body: Option<web::Json<ExtendRequest>>,
+    binary_store: web::Data<BinaryStore>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    let binary_id = path.into_inner();
+    let seconds = body.and_then(|b| b.seconds).unwrap_or(config.binary_ttl);
+
+    if seconds <= 0 {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Extension must be positive",
+            Some(format!("seconds: {}", seconds)),
+        )));
+    }
+
+    // Every key that staged a binary may keep it around, not just its owner
+    let allowed = binary_store.get(&binary_id).is_some_and(|binary| may_read(caller.as_deref(), &binary));
+    let extended = if allowed { binary_store.extend(&binary_id, Duration::seconds(seconds)) } else { None };
+    match extended {
+        Some(binary) => {
+            log::info!("⏳ Binary {} now expires at {}", binary_id, binary.expires_at);
+            Ok(HttpResponse::Ok().json(BinaryMetadata::from(&binary)))
+        }
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        ))),
+    }
+}
+
+/// A loaded merge input, with the file it was read from when there is one
+///
+/// Derefs to the contents; the merger splices from the file instead of
+/// writing the in-memory copy.
+pub(crate) struct MergeData {
+    bytes: Vec<u8>,
+    path: Option<PathBuf>,
+}
+
+impl From<Vec<u8>> for MergeData {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self { bytes, path: None }
+    }
+}
+
+impl MergeData {
+    fn read(path: &Path) -> std::io::Result<Self> {
+        Ok(Self { bytes: std::fs::read(path)?, path: Some(path.to_path_buf()) })
+    }
+
+    /// Strip debug info for `strip_payloads`; a stripped copy no longer
+    /// splices from the file it was read from
+    pub(crate) async fn strip(&mut self, name: &str, temp_dir: &Path, cancel: &CancelToken) -> StrippedSize {
+        let original_size = self.bytes.len() as u64;
+        if let Some(stripped) = strip_payload(name, &self.bytes, temp_dir, cancel).await {
+            *self = Self::from(stripped);
+        }
+        StrippedSize { original_size, stripped_size: self.bytes.len() as u64 }
+    }
+}
+
+impl Deref for MergeData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<'a> From<&'a MergeData> for MergeInput<'a> {
+    fn from(data: &'a MergeData) -> Self {
+        MergeInput { bytes: &data.bytes, file: data.path.as_deref() }
+    }
+}
+
+/// Contents of a merge input: uploaded with the request, referenced by ID or
+/// fetched from a URL, checked against `sha256` when one is given
+///
+/// Returns the error response to send back if none (or an unknown ID, an
+/// unusable URL or mismatching content) was given.
+pub(crate) async fn load_merge_input(
+    upload: Option<&TempFile>,
+    binary_id: Option<&str>,
+    url: Option<&str>,
+    sha256: Option<&str>,
+    binary_store: &BinaryStore,
+    caller: Option<&Caller>,
+    config: &Config,
+    name: &str,
+) -> Result<MergeData, HttpResponse> {
+    let data = read_merge_input(upload, binary_id, url, binary_store, caller, config, name).await?;
+
+    if let Some(expected) = sha256 {
+        let actual = digest::sha256_hex(&data);
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::ChecksumMismatch,
+                format!("{} checksum mismatch", name),
+                Some(format!("Expected {}, got {}", expected, actual)),
+            )));
+        }
+    }
+    Ok(data)
+}
+
+async fn read_merge_input(
+    upload: Option<&TempFile>,
+    binary_id: Option<&str>,
+    url: Option<&str>,
+    binary_store: &BinaryStore,
+    caller: Option<&Caller>,
+    config: &Config,
+    name: &str,
+) -> Result<MergeData, HttpResponse> {
+    if let Some(file) = upload {
+        return MergeData::read(file.file.path()).map_err(|e| {
+            HttpResponse::InternalServerError().json(ErrorResponse::new(
+                ErrorCode::Internal,
+                format!("Failed to read {} binary", name),
+                Some(e.to_string()),
+            ))
+        });
+    }
+
+    let Some(binary_id) = binary_id else {
+        let Some(url) = url else {
+            return Err(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                format!("Missing {} binary", name),
+                Some(format!("Upload {0}_binary, reference a staged binary with {0}_id or pass {0}_url", name)),
+            )));
+        };
+        if url.starts_with("oci://") {
+            let reference = oci::Reference::parse_allowed(url, &config.fetch_allowed_hosts, false).map_err(|e| {
+                HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid {}_url", name),
+                    Some(e),
+                ))
+            })?;
+            log::info!("🌐 Pulling {} binary from {}", name, reference);
+            return oci::pull(&reference, config).await.map(MergeData::from).map_err(|e| {
+                HttpResponse::BadGateway().json(ErrorResponse::new(
+                    ErrorCode::UpstreamFailed,
+                    format!("Failed to pull {} binary", name),
+                    Some(format!("{:#}", e)),
+                ))
+            });
+        }
+        let url = fetch::validate_input_url(url, &config.fetch_allowed_hosts).map_err(|e| {
+            HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                format!("Invalid {}_url", name),
+                Some(e),
+            ))
+        })?;
+        log::info!("🌐 Fetching {} binary from {}", name, url);
+        return fetch::fetch_input(url, &config.fetch_allowed_hosts, config.max_file_size).await.map(MergeData::from).map_err(|e| {
+            HttpResponse::BadGateway().json(ErrorResponse::new(
+                ErrorCode::UpstreamFailed,
+                format!("Failed to fetch {} binary", name),
+                Some(e.to_string()),
+            ))
+        });
+    };
+
+    let stored = binary_store.get(binary_id);
+    let Some(stored) = stored.filter(|b| Utc::now() <= b.expires_at && may_read(caller, b)) else {
+        return Err(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            format!("Unknown or expired {}_id", name),
+            Some(format!("ID: {}", binary_id)),
+        )));
+    };
+
+    MergeData::read(Path::new(&stored.path)).map_err(|e| {
+        HttpResponse::InternalServerError().json(ErrorResponse::new(
+            ErrorCode::Internal,
+            format!("Failed to read staged {} binary", name),
+            Some(e.to_string()),
+        ))
+    })
+}
+
+/// Parse the `oci_push` field of a merge request
+pub(crate) fn parse_oci_push(value: Option<&str>, config: &Config) -> Result<Option<oci::Reference>, HttpResponse> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    oci::Reference::parse_allowed(value, &config.fetch_allowed_hosts, true).map(Some).map_err(|e| {
+        HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid oci_push", Some(e)))
+    })
+}
+
+/// Push a stored merge result to `target`, returning the pushed digest reference
+///
+/// The merge itself already succeeded, so a failed push is reported with the
+/// binary ID that is still downloadable.
+pub(crate) async fn push_merged(
+    target: Option<&oci::Reference>,
+    binary_id: &str,
+    binary_store: &BinaryStore,
+    config: &Config,
+) -> Result<Option<String>, HttpResponse> {
+    let Some(target) = target else {
+        return Ok(None);
+    };
+    let bad_gateway = |e: String| {
+        log::error!("❌ Push of {} to {} failed: {}", binary_id, target, e);
+        HttpResponse::BadGateway().json(ErrorResponse::new(
+            ErrorCode::UpstreamFailed,
+            "Failed to push to OCI registry",
+            Some(format!("{}; binary {} is still available at /download/{}", e, binary_id, binary_id)),
+        ))
+    };
+    let binary = binary_store.get(binary_id).ok_or_else(|| bad_gateway("binary is no longer stored".to_string()))?;
+    let data = std::fs::read(&binary.path).map_err(|e| bad_gateway(e.to_string()))?;
+
+    let mut annotations = BTreeMap::from([
+        ("org.opencontainers.image.created".to_string(), binary.created_at.to_rfc3339()),
+        (format!("{}.binary-id", OCI_ANNOTATION_PREFIX), binary.id.clone()),
+    ]);
+    for (key, value) in &binary.labels {
+        annotations.insert(format!("{}.label.{}", OCI_ANNOTATION_PREFIX, key), value.clone());
+    }
+
+    let pushed = oci::push(target, data.into(), annotations, config).await.map_err(|e| bad_gateway(format!("{:#}", e)))?;
+    log::info!("📦 Pushed {} to {}", binary_id, pushed);
+    Ok(Some(pushed.to_string()))
+}
+
+/// 503 for a merge the scheduler couldn't fit in
+pub(crate) fn merge_refused(scheduler: &MergeScheduler, error: SchedulerError) -> HttpResponse {
+    let status = scheduler.status();
+    log::warn!("🚦 Refusing merge: {} ({} running, {} queued)", error, status.running, status.queued);
+    HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", scheduler.retry_after().to_string()))
+        .json(ErrorResponse::new(
+            ErrorCode::CapacityExhausted,
+            "Merge capacity exhausted",
+            Some(format!(
+                "{}: {} of {} merges running, {} of {} queued",
+                error, status.running, status.max_concurrent, status.queued, status.max_queued
+            )),
+        ))
+}
+
+/// Tell progress subscribers and the webhook that a merge was cancelled or
+/// timed out; 409 for the former, 504 for the latter
+pub(crate) async fn merge_cancelled(
+    reason: Cancelled,
+    task_id: Option<&str>,
+    callback_url: Option<String>,
+    progress: &dyn ProgressSink,
+    config: &Config,
+) -> HttpResponse {
+    log::info!("🛑 Merge {} stopped: {}", task_id.unwrap_or("(no task_id)"), reason);
+    if let Some(tid) = task_id {
+        let _ = ProgressTracker::publish_cancelled(progress, tid, reason).await;
+    }
+    if let Some(url) = callback_url {
+        notify::spawn_webhook(config, url, MergeNotification::failure(task_id.map(str::to_string), reason.to_string()));
+    }
+    match reason {
+        Cancelled::Requested => HttpResponse::Conflict().json(ErrorResponse::new(
+            ErrorCode::Cancelled,
+            "Merge cancelled",
+            task_id.map(|tid| format!("Task {} was cancelled with DELETE /jobs/{}", tid, tid)),
+        )),
+        Cancelled::TimedOut(_) => HttpResponse::GatewayTimeout().json(ErrorResponse::new(
+            ErrorCode::TimedOut,
+            "Merge timed out",
+            Some(reason.to_string()),
+        )),
+    }
+}
+
+/// Data files uploaded as `field`, each named by its multipart file name,
+/// which is the path it is extracted to
+pub(crate) fn read_data_files(uploads: &[TempFile], config: &Config, field: &str) -> Result<Vec<DataFile>, HttpResponse> {
+    let mut files = Vec::with_capacity(uploads.len());
+    for upload in uploads {
+        let Some(path) = upload.file_name.as_deref() else {
+            return Err(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                format!("Missing {} path", field),
+                Some(format!("Give every {} upload the path it is extracted to as its file name", field)),
+            )));
+        };
+        if upload.size > config.max_file_size {
+            return Err(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::PayloadTooLarge,
+                format!("{} file too large", field),
+                Some(format!("{}: max size {} bytes", path, config.max_file_size)),
+            )));
+        }
+        let data = std::fs::read(upload.file.path()).map_err(|e| {
+            HttpResponse::InternalServerError().json(ErrorResponse::new(
+                ErrorCode::Internal,
+                format!("Failed to read {} file", field),
+                Some(e.to_string()),
+            ))
+        })?;
+        files.push(DataFile::new(path, &data));
+    }
+    Ok(files)
+}
+
+/// 422 naming the first input that can't be run
+///
+/// The overload may also be a `#!` script, and an interpreter to bundle is
+/// only accepted along with one. A library overload needs a base it can be
+/// loaded into.
+pub(crate) fn validate_merge_inputs(
+    base_data: &[u8],
+    overload_data: &[u8],
+    interpreter: Option<&[u8]>,
+    overload_library: bool,
+) -> Result<(), HttpResponse> {
+    let invalid = |name: &str, e: InvalidExecutable| {
+        log::warn!("Rejecting {} binary: {}", name, e);
+        HttpResponse::UnprocessableEntity().json(ErrorResponse::new(
+            ErrorCode::InvalidExecutable,
+            format!("Invalid {} binary", name),
+            Some(format!("{} binary: {}", name, e)),
+        ))
+    };
+    validate_executable(base_data).map_err(|e| invalid("base", e))?;
+    if overload_library && !is_dynamically_linked(base_data) {
+        return Err(invalid("base", InvalidExecutable::StaticallyLinked));
+    }
+    let kind = validate_overload(overload_data, overload_library).map_err(|e| invalid("overload", e))?;
+    if let Some(interpreter) = interpreter {
+        if !matches!(kind, OverloadKind::Script(_)) {
+            return Err(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Interpreter needs a script overload",
+                Some("An interpreter is only bundled with an overload that starts with #!".to_string()),
+            )));
+        }
+        validate_executable(interpreter).map_err(|e| invalid("interpreter", e))?;
+    }
+    Ok(())
+}
+
+/// Response for a merge that failed, classified by the core's typed errors
+pub(crate) fn merge_failed(error: &anyhow::Error) -> HttpResponse {
+    let code = ErrorCode::from(error);
+    let mut response = match code {
+        ErrorCode::UnsupportedPlatform | ErrorCode::ArchMismatch | ErrorCode::InvalidRequest => HttpResponse::BadRequest(),
+        _ => HttpResponse::InternalServerError(),
+    };
+    response.json(ErrorResponse::new(code, "Merge failed", Some(error.to_string())))
+}
+
+/// 409 for a merge whose task ID is already in use
+pub(crate) fn duplicate_job(error: DuplicateJob) -> HttpResponse {
+    HttpResponse::Conflict().json(ErrorResponse::new(
+        ErrorCode::TaskAlreadyRunning,
+        "Task already running",
+        Some(error.to_string()),
+    ))
+}
+
+/// 507 for a merge or upload there is no room for
+pub(crate) fn insufficient_storage(error: SpaceError) -> HttpResponse {
+    log::warn!("💾 Refusing to store binary: {}", error);
+    let error_msg = match error {
+        SpaceError::DiskFull { .. } => "Not enough disk space",
+        SpaceError::QuotaExceeded { .. } => "Storage quota exceeded",
+    };
+    HttpResponse::InsufficientStorage().json(ErrorResponse::new(
+        ErrorCode::InsufficientStorage,
+        error_msg,
+        Some(error.to_string()),
+    ))
+}
+
+/// 422 for inputs a payload scanner matched, 503 when a scanner gave no verdict
+pub(crate) fn scan_rejected(error: &ScanError) -> HttpResponse {
+    log::warn!("🦠 Refusing merge: {}", error);
+    match error {
+        ScanError::Detected(_) => HttpResponse::UnprocessableEntity().json(ErrorResponse::new(
+            ErrorCode::ScanMatched,
+            "Payload scan matched",
+            Some(error.to_string()),
+        )),
+        ScanError::Failed { .. } => HttpResponse::ServiceUnavailable().json(ErrorResponse::new(
+            ErrorCode::Unavailable,
+            "Payload scan failed",
+            Some(error.to_string()),
+        )),
+    }
+}
+
+/// 403 for a merge that breaks a policy rule
+pub(crate) fn policy_violation(violation: &PolicyViolation) -> HttpResponse {
+    log::warn!("🚫 Refusing merge: {}", violation);
+    HttpResponse::Forbidden().json(ErrorResponse::new(
+        ErrorCode::PolicyViolation,
+        format!("Merge violates policy rule '{}'", violation.rule),
+        Some(violation.reason.clone()),
+    ))
+}
+
+/// Stored result of an identical earlier merge, if it is still downloadable
+pub(crate) fn lookup_cached_merge(
+    cache: &MergeCache,
+    key: &MergeCacheKey,
+    binary_store: &BinaryStore,
+) -> Option<StoredBinary> {
+    let binary_id = cache.get(key)?;
+    let stored = binary_store.get(&binary_id);
+
+    match stored {
+        Some(binary) if Utc::now() <= binary.expires_at && std::path::Path::new(&binary.path).exists() => Some(binary),
+        _ => {
+            cache.remove(key);
+            None
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use actix_web::{web, HttpResponse};
+
+use crate::core::binary::{BinaryInfo, OperatingSystem};
+use crate::core::merger::{pack, MergeStrategies, StubRegistry, STUB_PLATFORMS};
+use crate::core::signing::Signer;
+use crate::models::response::{CapabilitiesResponse, PlatformCapabilities, PlatformFeatures};
+
+/// Supported platform matrix of this deployment
+/// GET /capabilities
+///
+/// Every platform a stub is built for is listed; `available` is false when
+/// no valid stub for it is loaded. `strategies` lists the merge strategies
+/// usable for it, e.g. `compile` only where a C compiler for it is installed.
+/// `upx_version` is absent when `pack` can only pack the payloads.
+#[utoipa::path(
+    get,
+    path = "/capabilities",
+    tag = "service",
+    responses((status = 200, description = "Supported (OS, arch) pairs and their features", body = CapabilitiesResponse))
+)]
+pub async fn capabilities(
+    stubs: web::Data<StubRegistry>,
+    strategies: web::Data<MergeStrategies>,
+    signer: web::Data<Signer>,
+) -> HttpResponse {
+    let upx = pack::upx();
+    let platforms = STUB_PLATFORMS
+        .iter()
+        .map(|&(os, arch)| {
+            let available = stubs.get(os, arch).is_some();
+            PlatformCapabilities {
+                os: os.name().to_string(),
+                arch: arch.name().to_string(),
+                available,
+                strategies: strategies
+                    .usable(&BinaryInfo::new(os, arch), &stubs)
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                stub_flavors: stubs.flavors(os, arch).into_iter().map(|flavor| flavor.name().to_string()).collect(),
+                features: PlatformFeatures {
+                    health_monitoring: true,
+                    sync_mode: true,
+                    network_failure_kill: true,
+                    // Namespaces, seccomp and rlimits are Linux-only
+                    overload_jail: os == OperatingSystem::Linux,
+                    overload_schedule: true,
+                    // cgroups/rlimits on Linux, Job Objects on Windows
+                    resource_limits: matches!(os, OperatingSystem::Linux | OperatingSystem::Windows),
+                    drop_privileges: matches!(os, OperatingSystem::Linux | OperatingSystem::MacOS),
+                    seccomp: os == OperatingSystem::Linux,
+                    // Merged artifacts are never code-signed for the OS; see signing_public_key
+                    signing: false,
+                    // Every stub unpacks its payloads
+                    pack: true,
+                    // UPX output loses the code signature macOS requires
+                    upx: upx.is_some() && os != OperatingSystem::MacOS,
+                },
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(CapabilitiesResponse {
+        platforms,
+        signing_public_key: signer.minisign_public_key(),
+        upx_version: upx.map(|upx| upx.version.clone()),
+    })
+}
@@ -1,54 +1,263 @@
-use actix_web::{web, HttpResponse, Error};
-use std::collections::HashMap;
-use std::sync::Mutex;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, Error};
 use chrono::Utc;
 
-use crate::models::{binary::StoredBinary, response::ErrorResponse};
+use crate::api::auth::may_read;
+use crate::config::SharedConfig;
+use crate::core::auth::Caller;
+use crate::core::store::BinaryStore;
+use crate::models::binary::StoredBinary;
+use crate::models::response::{ErrorCode, ErrorResponse};
 
+/// Download a merged binary
+///
+/// Each download is counted in the binary's metadata and, with
+/// `renew_on_access` or for a tenant in `renew_on_access_tenants`, pushes its
+/// expiry to `binary_ttl` from now. The ETag is the binary's SHA-256, so
+/// `If-None-Match` turns a repeated download into a 304.
+#[utoipa::path(
+    get,
+    path = "/download/{id}",
+    tag = "download",
+    params(
+        ("id" = String, Path, description = "Binary ID returned by a merge"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a copy the client already has"),
+    ),
+    responses(
+        (status = 200, description = "Merged binary", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 304, description = "The client's copy is current"),
+        (status = 404, description = "Unknown binary", body = ErrorResponse),
+        (status = 410, description = "Binary has expired", body = ErrorResponse),
+        (status = 500, description = "Binary could not be read", body = ErrorResponse),
+    )
+)]
 pub async fn download_binary(
+    req: HttpRequest,
     path: web::Path<String>,
-    binary_store: web::Data<Mutex<HashMap<String, StoredBinary>>>,
+    binary_store: web::Data<BinaryStore>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
 ) -> Result<HttpResponse, Error> {
     let binary_id = path.into_inner();
     
-    let stored = {
-        let store = binary_store.lock().unwrap();
-        store.get(&binary_id).cloned()
-    };
+    let stored = binary_store.get(&binary_id).filter(|binary| may_read(caller.as_deref(), binary));
     
     match stored {
-        Some(binary) => {
-            // Check if expired
-            if Utc::now() > binary.expires_at {
-                log::warn!("Binary {} has expired", binary_id);
-                return Ok(HttpResponse::Gone().json(ErrorResponse {
-                    error: "Binary has expired".to_string(),
-                    details: None,
-                }));
-            }
-            
-            match std::fs::read(&binary.path) {
-                Ok(data) => {
-                    log::info!("📥 Downloading binary: {} ({} bytes)", binary_id, data.len());
-                    Ok(HttpResponse::Ok()
-                        .content_type("application/octet-stream")
-                        .insert_header(("Content-Disposition", format!("attachment; filename=\"merged_binary\"")))
-                        .body(data))
-                }
-                Err(e) => {
-                    log::error!("Failed to read binary {}: {}", binary_id, e);
-                    Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                        error: "Failed to read binary".to_string(),
-                        details: Some(e.to_string()),
-                    }))
-                }
+        Some(binary) => Ok(serve_binary(&req, binary, &binary_store, &config)),
+        None => {
+            Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+                ErrorCode::NotFound,
+                "Binary not found",
+                Some(format!("ID: {}", binary_id)),
+            )))
+        }
+    }
+}
+
+/// Download a stored binary by its content
+/// GET /download/by-hash/{sha256}
+///
+/// Deployment tooling can pin a digest instead of a binary ID. When several
+/// live binaries have the same content, the newest the caller may read is
+/// served.
+#[utoipa::path(
+    get,
+    path = "/download/by-hash/{sha256}",
+    tag = "download",
+    params(
+        ("sha256" = String, Path, description = "Hex SHA-256 of the binary"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a copy the client already has"),
+    ),
+    responses(
+        (status = 200, description = "Stored binary", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 304, description = "The client's copy is current"),
+        (status = 400, description = "Not a SHA-256 digest", body = ErrorResponse),
+        (status = 404, description = "No live binary with this digest", body = ErrorResponse),
+        (status = 500, description = "Binary could not be read", body = ErrorResponse),
+    )
+)]
+pub async fn download_by_hash(
+    req: HttpRequest,
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let sha256 = path.into_inner().to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid digest",
+            Some("Expected 64 hex digits of SHA-256".to_string()),
+        )));
+    }
+
+    let now = Utc::now();
+    let stored = binary_store
+        .with_sha256(&sha256)
+        .into_iter()
+        .find(|binary| binary.expires_at >= now && may_read(caller.as_deref(), binary));
+
+    match stored {
+        Some(binary) => Ok(serve_binary(&req, binary, &binary_store, &config)),
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("SHA-256: {}", sha256)),
+        ))),
+    }
+}
+
+/// Send a binary the caller may read, or 304 when `If-None-Match` has its ETag
+fn serve_binary(req: &HttpRequest, binary: StoredBinary, binary_store: &BinaryStore, config: &SharedConfig) -> HttpResponse {
+    // Check if expired
+    if Utc::now() > binary.expires_at {
+        log::warn!("Binary {} has expired", binary.id);
+        return HttpResponse::Gone().json(ErrorResponse::new(ErrorCode::Expired, "Binary has expired", None));
+    }
+
+    let etag = binary.sha256.as_ref().map(|sha256| format!("\"{}\"", sha256));
+    if let Some(etag) = &etag {
+        let if_none_match = req.headers().get(header::IF_NONE_MATCH).and_then(|value| value.to_str().ok());
+        if if_none_match.is_some_and(|candidates| lists_etag(candidates, etag)) {
+            return HttpResponse::NotModified().insert_header((header::ETAG, etag.clone())).finish();
+        }
+    }
+
+    match std::fs::read(&binary.path) {
+        Ok(data) => {
+            log::info!("📥 Downloading binary: {} ({} bytes)", binary.id, data.len());
+            binary_store.record_download(&binary.id, config.load().access_renewal(binary.owner.as_deref()));
+            let mut response = HttpResponse::Ok();
+            response
+                .content_type("application/octet-stream")
+                .insert_header(("Content-Disposition", "attachment; filename=\"merged_binary\""));
+            if let Some(etag) = etag {
+                response.insert_header((header::ETAG, etag));
             }
+            response.body(data)
         }
-        None => {
-            Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: "Binary not found".to_string(),
-                details: Some(format!("ID: {}", binary_id)),
-            }))
+        Err(e) => {
+            log::error!("Failed to read binary {}: {}", binary.id, e);
+            HttpResponse::InternalServerError().json(ErrorResponse::new(
+                ErrorCode::Internal,
+                "Failed to read binary",
+                Some(e.to_string()),
+            ))
+        }
+    }
+}
+
+/// Whether an `If-None-Match` value is `*` or lists `etag`, compared weakly as RFC 9110 asks
+fn lists_etag(candidates: &str, etag: &str) -> bool {
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag)
+}
+
+/// Download the minisign signature of a merged binary
+/// GET /download/{id}.sig
+///
+/// Verify with `minisign -Vm <binary> -x <binary>.sig -P <key>`, the key
+/// being `signing_public_key` from `/capabilities`.
+#[utoipa::path(
+    get,
+    path = "/download/{id}.sig",
+    tag = "download",
+    params(("id" = String, Path, description = "Binary ID returned by a merge")),
+    responses(
+        (status = 200, description = "minisign signature", content_type = "text/plain", body = String),
+        (status = 404, description = "Unknown binary or not signed", body = ErrorResponse),
+        (status = 410, description = "Binary has expired", body = ErrorResponse),
+    )
+)]
+pub async fn download_signature(
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+    
+    let Some(binary) = binary_store.get(&binary_id).filter(|binary| may_read(caller.as_deref(), binary)) else {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        )));
+    };
+    
+    if Utc::now() > binary.expires_at {
+        return Ok(HttpResponse::Gone().json(ErrorResponse::new(ErrorCode::Expired, "Binary has expired", None)));
+    }
+    
+    match binary.signature {
+        Some(signature) => Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .insert_header(("Content-Disposition", "attachment; filename=\"merged_binary.minisig\""))
+            .body(signature)),
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotConfigured,
+            "Binary is not signed",
+            Some("Set WEAVER_SIGNING_KEY to sign merged binaries".to_string()),
+        ))),
+    }
+}
+
+/// Download the HTML weave report of a merged binary
+/// GET /download/{id}/report.html
+#[utoipa::path(
+    get,
+    path = "/download/{id}/report.html",
+    tag = "download",
+    params(("id" = String, Path, description = "Binary ID returned by a merge")),
+    responses(
+        (status = 200, description = "HTML weave report", content_type = "text/html", body = String),
+        (status = 404, description = "Unknown binary or no report generated", body = ErrorResponse),
+        (status = 410, description = "Binary has expired", body = ErrorResponse),
+        (status = 500, description = "Report could not be read", body = ErrorResponse),
+    )
+)]
+pub async fn download_report(
+    path: web::Path<String>,
+    binary_store: web::Data<BinaryStore>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+    
+    let stored = binary_store.get(&binary_id).filter(|binary| may_read(caller.as_deref(), binary));
+    
+    let Some(binary) = stored else {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary not found",
+            Some(format!("ID: {}", binary_id)),
+        )));
+    };
+    
+    if Utc::now() > binary.expires_at {
+        return Ok(HttpResponse::Gone().json(ErrorResponse::new(ErrorCode::Expired, "Binary has expired", None)));
+    }
+    
+    let Some(report_path) = binary.report_path else {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "No report was generated for this binary",
+            Some("Pass report=true when merging".to_string()),
+        )));
+    };
+    
+    match std::fs::read_to_string(&report_path) {
+        Ok(html) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(html)),
+        Err(e) => {
+            log::error!("Failed to read report for {}: {}", binary_id, e);
+            Ok(HttpResponse::InternalServerError().json(ErrorResponse::new(
+                ErrorCode::Internal,
+                "Failed to read report",
+                Some(e.to_string()),
+            )))
         }
     }
 }
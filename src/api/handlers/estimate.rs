@@ -0,0 +1,137 @@
+use actix_web::{web, HttpResponse, Error};
+
+use crate::config::{Config, SharedConfig};
+use crate::core::auth::Caller;
+use crate::core::binary::BinaryInfo;
+use crate::core::diskguard::{estimated_compressed_size, DiskGuard};
+use crate::core::merger::{parse_platform, StubFlavor, StubRegistry};
+use crate::core::store::BinaryStore;
+use crate::models::request::EstimateRequest;
+use crate::models::response::{ErrorCode, ErrorResponse, EstimateResponse};
+use super::binaries::{load_merge_input, MergeData};
+use weaver_abi::ConfigFooter;
+
+/// Size a merge before sending it
+/// POST /estimate
+///
+/// Takes the payload sizes and base's platform, or staged binary IDs, and
+/// answers with the expected merged size and whether the size limit, storage
+/// quota and free disk space allow it right now. Nothing is reserved, so a
+/// merge that fits may still be refused if others get there first.
+#[utoipa::path(
+    post,
+    path = "/estimate",
+    tag = "merge",
+    request_body = EstimateRequest,
+    responses(
+        (status = 200, description = "Estimate, with fits=false and the problems when the merge would be refused", body = EstimateResponse),
+        (status = 400, description = "Missing size or platform, or no stub for the platform", body = ErrorResponse),
+        (status = 404, description = "Unknown or expired staged binary", body = ErrorResponse),
+    )
+)]
+pub async fn estimate(
+    body: web::Json<EstimateRequest>,
+    binary_store: web::Data<BinaryStore>,
+    disk_guard: web::Data<DiskGuard>,
+    stubs: web::Data<StubRegistry>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    let request = body.into_inner();
+
+    let base = match staged(request.base_id.as_deref(), &binary_store, caller.as_deref(), &config, "base").await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let overload = match staged(request.overload_id.as_deref(), &binary_store, caller.as_deref(), &config, "overload").await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let base_size = base.as_ref().map(|data| data.len() as u64).or(request.base_size);
+    let overload_size = overload.as_ref().map(|data| data.len() as u64).or(request.overload_size);
+    let (Some(base_size), Some(overload_size)) = (base_size, overload_size) else {
+        return Ok(invalid("Missing payload size", "Give base_size and overload_size, or base_id and overload_id"));
+    };
+
+    let base_info = match &base {
+        Some(data) => BinaryInfo::detect(data),
+        None => {
+            let platform = request.os.as_deref().zip(request.arch.as_deref()).map(|(os, arch)| (os.trim(), arch.trim()));
+            match platform.and_then(|(os, arch)| parse_platform(os, arch)) {
+                Some((os, arch)) => BinaryInfo::new(os, arch),
+                None => return Ok(invalid("Unknown platform", "Give base's os and arch as listed by GET /capabilities, or base_id")),
+            }
+        }
+    };
+
+    let flavor = match request.stub_flavor.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+        Some(name) => match StubFlavor::parse(name) {
+            Ok(flavor) => Some(flavor),
+            Err(e) => return Ok(invalid("Invalid stub_flavor", &e)),
+        },
+        None => None,
+    };
+    let stub = match stubs.select(&base_info, flavor) {
+        Ok(stub) => stub,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::from(&e),
+                "No stub for the platform",
+                Some(e.to_string()),
+            )));
+        }
+    };
+
+    let stub_size = stub.bytes.len() as u64;
+    let output_size = stub_size + base_size + overload_size + request.extra_size + ConfigFooter::SIZE as u64;
+
+    let max_file_size = config.max_file_size as u64;
+    let mut problems: Vec<String> = [("base", base_size), ("overload", overload_size)]
+        .into_iter()
+        .filter(|&(_, size)| size > max_file_size)
+        .map(|(name, size)| format!("{} is {} bytes; inputs are limited to {}", name, size, max_file_size))
+        .collect();
+    if let Err(e) = disk_guard.check(output_size, &binary_store) {
+        problems.push(e.to_string());
+    }
+    let (storage_used, storage_quota) = disk_guard.usage(&binary_store);
+
+    // The footer and any extras are counted as they are
+    let compressed_size = base.as_ref().zip(overload.as_ref()).map(|(base, overload)| {
+        [&stub.bytes[..], base, overload].into_iter().map(estimated_compressed_size).sum::<u64>()
+            + request.extra_size
+            + ConfigFooter::SIZE as u64
+    });
+
+    Ok(HttpResponse::Ok().json(EstimateResponse {
+        platform: base_info.description(),
+        stub_size,
+        output_size,
+        fits: problems.is_empty(),
+        problems,
+        max_file_size,
+        storage_used,
+        storage_quota,
+        compressed_size,
+        compression_savings: compressed_size.map(|compressed| output_size.saturating_sub(compressed)),
+    }))
+}
+
+/// Contents of a staged binary, if an ID was given
+async fn staged(
+    binary_id: Option<&str>,
+    binary_store: &BinaryStore,
+    caller: Option<&Caller>,
+    config: &Config,
+    name: &str,
+) -> Result<Option<MergeData>, HttpResponse> {
+    match binary_id.map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => load_merge_input(None, Some(id), None, None, binary_store, caller, config, name).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+fn invalid(message: &str, details: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, message, Some(details.to_string())))
+}
@@ -1,10 +1,110 @@
-use actix_web::HttpResponse;
-use crate::models::response::HealthResponse;
+use actix_web::{web, HttpResponse};
+
+use crate::config::SharedConfig;
+use crate::core::binary::OperatingSystem;
+use crate::core::diskguard::{self, DiskGuard};
+use crate::core::merger::{compile, stub_slots, StubRegistry, STUB_PLATFORMS};
+use crate::core::progress::ProgressSink;
+use crate::core::scheduler::MergeScheduler;
+use crate::core::store::BinaryStore;
+use crate::models::response::{CheckResult, CompilerStatus, DiskStatus, HealthChecks, HealthResponse, MergeQueueStatus, StubStatus};
+
+/// Service health and readiness
+///
+/// Always answers 200 so it keeps working as a liveness probe; orchestrators
+/// should gate traffic on the `ready` field.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "service",
+    responses((status = 200, description = "Health and readiness report", body = HealthResponse))
+)]
+pub async fn health(
+    config: web::Data<SharedConfig>,
+    progress: web::Data<dyn ProgressSink>,
+    stub_registry: web::Data<StubRegistry>,
+    scheduler: web::Data<MergeScheduler>,
+    disk_guard: web::Data<DiskGuard>,
+    binary_store: web::Data<BinaryStore>,
+) -> HttpResponse {
+    let config = config.load_full();
+    let stubs = check_stubs(&stub_registry);
+    let temp_dir = check_temp_dir(&config.temp_dir);
+    let progress_backend = match progress.ping().await {
+        Ok(()) => CheckResult { ok: true, detail: Some(config.progress_backend.clone()) },
+        Err(e) => CheckResult { ok: false, detail: Some(format!("{}: {}", config.progress_backend, e)) },
+    };
+    let disk = check_disk(&config.temp_dir, config.max_file_size as u64, &disk_guard, &binary_store);
+
+    let ready = stubs.iter().any(|s| s.available) && temp_dir.ok && progress_backend.ok && disk.ok;
 
-pub async fn health() -> HttpResponse {
     HttpResponse::Ok().json(HealthResponse {
-        status: "healthy".to_string(),
+        status: if ready { "healthy" } else { "degraded" }.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         uptime: "running".to_string(),
+        ready,
+        checks: HealthChecks {
+            stubs,
+            compilers: check_compilers(),
+            temp_dir,
+            progress_backend,
+            disk,
+            merges: MergeQueueStatus::from(scheduler.status()),
+        },
     })
 }
+
+fn check_stubs(stub_registry: &StubRegistry) -> Vec<StubStatus> {
+    stub_slots()
+        .map(|(os, arch, flavor)| {
+            let size = stub_registry.get_flavor(os, arch, flavor).map(|s| s.bytes.len()).unwrap_or(0);
+            StubStatus {
+                os: os.name().to_string(),
+                arch: arch.name().to_string(),
+                flavor: flavor.name().to_string(),
+                available: size > 0,
+                size,
+            }
+        })
+        .collect()
+}
+
+fn check_compilers() -> Vec<CompilerStatus> {
+    STUB_PLATFORMS
+        .iter()
+        .filter(|(os, _)| *os == OperatingSystem::Linux)
+        .map(|&(_, arch)| {
+            let path = compile::compiler_for(arch);
+            CompilerStatus {
+                arch: arch.name().to_string(),
+                available: path.is_some(),
+                path: path.map(|p| p.display().to_string()),
+            }
+        })
+        .collect()
+}
+
+fn check_temp_dir(temp_dir: &str) -> CheckResult {
+    let writable = std::fs::create_dir_all(temp_dir)
+        .and_then(|_| tempfile::NamedTempFile::new_in(temp_dir).map(drop));
+
+    match writable {
+        Ok(()) => CheckResult { ok: true, detail: None },
+        Err(e) => CheckResult { ok: false, detail: Some(format!("{} is not writable: {}", temp_dir, e)) },
+    }
+}
+
+fn check_disk(temp_dir: &str, max_file_size: u64, disk_guard: &DiskGuard, binary_store: &BinaryStore) -> DiskStatus {
+    // Base + overload inputs plus the merged output
+    let required_bytes = max_file_size.saturating_mul(3);
+    let free_bytes = diskguard::free_bytes(temp_dir);
+    let (stored_bytes, quota) = disk_guard.usage(binary_store);
+
+    DiskStatus {
+        ok: free_bytes.is_some_and(|free| free >= required_bytes),
+        free_bytes,
+        required_bytes,
+        stored_bytes,
+        quota_bytes: (quota > 0).then_some(quota),
+    }
+}
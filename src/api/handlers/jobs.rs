@@ -0,0 +1,42 @@
+use actix_web::{web, HttpResponse, Error};
+
+use crate::core::auth::Caller;
+use crate::core::jobs::JobRegistry;
+use crate::models::response::{ErrorCode, ErrorResponse};
+
+/// Cancel an in-flight merge
+/// DELETE /jobs/{task_id}
+///
+/// Cancellation is cooperative: the merge stops at its next checkpoint (or
+/// leaves the queue right away), removes its work dir, frees its slot and
+/// publishes a `cancelled` progress event. The merge request itself then
+/// answers 409. A merge key may only cancel its own merges; other callers'
+/// merges answer 404.
+#[utoipa::path(
+    delete,
+    path = "/jobs/{task_id}",
+    tag = "merge",
+    params(("task_id" = String, Path, description = "task_id sent with the merge request")),
+    responses(
+        (status = 202, description = "Cancellation requested"),
+        (status = 404, description = "No running merge with this task_id started by the caller", body = ErrorResponse),
+    )
+)]
+pub async fn cancel_job(
+    path: web::Path<String>,
+    jobs: web::Data<JobRegistry>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let task_id = path.into_inner();
+
+    if jobs.cancel(&task_id, caller.as_deref()) {
+        log::info!("🛑 Cancellation requested for merge {}", task_id);
+        Ok(HttpResponse::Accepted().finish())
+    } else {
+        Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "No running merge for task",
+            Some(format!("Task ID: {} (unknown or already finished)", task_id)),
+        )))
+    }
+}
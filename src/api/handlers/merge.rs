@@ -1,45 +1,172 @@
 use actix_web::{web, HttpResponse, Error};
 use actix_multipart::form::{tempfile::TempFile, MultipartForm};
-use std::collections::HashMap;
-use std::sync::Mutex;
+use utoipa::ToSchema;
 use uuid::Uuid;
 use chrono::{Utc, Duration};
 
 use crate::models::{
     request::MergeMode,
-    response::{MergeResponse, ErrorResponse},
-    binary::StoredBinary,
+    response::{MergeDetails, MergeResponse, ErrorCode, ErrorResponse},
+    binary::{parse_labels, BinaryKind, Labels, StoredBinary},
 };
 use crate::core;
-use crate::core::progress::{ProgressTracker, ProgressStep};
-use crate::config::Config;
+use crate::core::progress::{ProgressSink, ProgressTracker, ProgressStep};
+use crate::core::digest;
+use crate::core::provenance::MergeProvenance;
+use crate::core::report::WeaveReport;
+use crate::core::diskguard::{projected_output_size, DiskGuard};
+use crate::core::jobs::{merge_timeout, Cancelled, JobRegistry};
+use crate::core::workspace::WorkspaceManager;
+use crate::core::auth::Caller;
+use crate::api::auth::may_read;
+use crate::core::policy::{MergeFacts, Policy};
+use crate::core::scan::PayloadScanner;
+use crate::core::scheduler::{MergeScheduler, SchedulerError};
+use crate::core::signing::Signer;
+use crate::core::notify::{self, MergeNotification};
+use crate::core::binary::BinaryInfo;
+use crate::core::merger::{MergeCache, MergeCacheKey, StubAppend, StubOptions, StubRegistry};
+use crate::core::store::BinaryStore;
+use crate::config::SharedConfig;
+use super::binaries::{
+    duplicate_job, insufficient_storage, load_merge_input, lookup_cached_merge, merge_cancelled, merge_failed, merge_refused, parse_oci_push,
+    policy_violation, push_merged, scan_rejected, validate_merge_inputs,
+};
 
-#[derive(Debug, MultipartForm)]
+#[derive(Debug, MultipartForm, ToSchema)]
 pub struct MergeForm {
     #[multipart(limit = "200MB")]
-    pub base_binary: TempFile,
+    #[schema(value_type = Option<String>, format = Binary)]
+    pub base_binary: Option<TempFile>,
     #[multipart(limit = "200MB")]
-    pub overload_binary: TempFile,
+    #[schema(value_type = Option<String>, format = Binary)]
+    pub overload_binary: Option<TempFile>,
+    /// Staged binary to use instead of uploading `base_binary`
+    #[multipart(rename = "base_id")]
+    #[schema(value_type = Option<String>)]
+    pub base_id: Option<actix_multipart::form::text::Text<String>>,
+    /// Staged binary to use instead of uploading `overload_binary`
+    #[multipart(rename = "overload_id")]
+    #[schema(value_type = Option<String>)]
+    pub overload_id: Option<actix_multipart::form::text::Text<String>>,
+    /// URL or `oci://` reference Weaver fetches the base from (host must be in `WEAVER_FETCH_ALLOWED_HOSTS`)
+    #[multipart(rename = "base_url")]
+    #[schema(value_type = Option<String>)]
+    pub base_url: Option<actix_multipart::form::text::Text<String>>,
+    /// URL or `oci://` reference Weaver fetches the overload from (host must be in `WEAVER_FETCH_ALLOWED_HOSTS`)
+    #[multipart(rename = "overload_url")]
+    #[schema(value_type = Option<String>)]
+    pub overload_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Expected hex SHA-256 of the base, however it is supplied
+    #[multipart(rename = "base_sha256")]
+    #[schema(value_type = Option<String>)]
+    pub base_sha256: Option<actix_multipart::form::text::Text<String>>,
+    /// Expected hex SHA-256 of the overload, however it is supplied
+    #[multipart(rename = "overload_sha256")]
+    #[schema(value_type = Option<String>)]
+    pub overload_sha256: Option<actix_multipart::form::text::Text<String>>,
     #[multipart(rename = "mode")]
+    #[schema(value_type = Option<MergeMode>)]
     pub mode: Option<actix_multipart::form::text::Text<String>>,
     #[multipart(rename = "sync")]
+    #[schema(value_type = Option<bool>)]
     pub sync: Option<actix_multipart::form::text::Text<String>>,
     #[multipart(rename = "output_name")]
+    #[schema(value_type = Option<String>)]
     pub output_name: Option<actix_multipart::form::text::Text<String>>,
     #[multipart(rename = "task_id")]
+    #[schema(value_type = Option<String>)]
     pub task_id: Option<actix_multipart::form::text::Text<String>>,
+    #[multipart(rename = "report")]
+    #[schema(value_type = Option<bool>)]
+    pub report: Option<actix_multipart::form::text::Text<bool>>,
+    #[multipart(rename = "callback_url")]
+    #[schema(value_type = Option<String>)]
+    pub callback_url: Option<actix_multipart::form::text::Text<String>>,
+    /// JSON object of string labels stored with the merged binary, e.g. `{"customer":"acme","version":"2.1"}`
+    #[multipart(rename = "labels")]
+    #[schema(value_type = Option<String>)]
+    pub labels: Option<actix_multipart::form::text::Text<String>>,
+    /// OCI tag to push the merged binary to, e.g. `oci://registry.example/team/app:1.0`
+    #[multipart(rename = "oci_push")]
+    #[schema(value_type = Option<String>)]
+    pub oci_push: Option<actix_multipart::form::text::Text<String>>,
+    /// Re-assemble even if an identical merge is cached
+    #[multipart(rename = "force")]
+    #[schema(value_type = Option<bool>)]
+    pub force: Option<actix_multipart::form::text::Text<bool>>,
+    /// Abort the merge after this many seconds; can only shorten `WEAVER_MERGE_TIMEOUT`
+    #[multipart(rename = "timeout_secs")]
+    #[schema(value_type = Option<u64>)]
+    pub timeout_secs: Option<actix_multipart::form::text::Text<u64>>,
 }
 
+/// Merge a base and an overload binary
+#[utoipa::path(
+    post,
+    path = "/merge",
+    tag = "merge",
+    request_body(content = MergeForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Binaries merged", body = MergeResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 403, description = "The merge breaks a policy rule", body = ErrorResponse),
+        (status = 422, description = "base or overload is not a runnable executable, or the payload scanner matched one", body = ErrorResponse),
+        (status = 500, description = "Merge failed", body = ErrorResponse),
+        (status = 502, description = "base_url or overload_url could not be fetched", body = ErrorResponse),
+        (status = 409, description = "task_id is already in use, or the merge was cancelled", body = ErrorResponse),
+        (status = 503, description = "Too many merges running and queued (retry after Retry-After seconds), or the payload scanner failed", body = ErrorResponse),
+        (status = 507, description = "Not enough disk space or storage quota for the output", body = ErrorResponse),
+        (status = 504, description = "The merge ran longer than its timeout", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "POST /merge", skip_all)]
 pub async fn merge_binaries(
     MultipartForm(form): MultipartForm<MergeForm>,
-    binary_store: web::Data<Mutex<HashMap<String, StoredBinary>>>,
-    config: web::Data<Config>,
+    binary_store: web::Data<BinaryStore>,
+    config: web::Data<SharedConfig>,
+    progress: web::Data<dyn ProgressSink>,
+    merge_cache: web::Data<MergeCache>,
+    stubs: web::Data<StubRegistry>,
+    signer: web::Data<Signer>,
+    scheduler: web::Data<MergeScheduler>,
+    disk_guard: web::Data<DiskGuard>,
+    jobs: web::Data<JobRegistry>,
+    workspaces: web::Data<WorkspaceManager>,
+    scanner: web::Data<PayloadScanner>,
+    policy: web::Data<Policy>,
+    caller: Option<web::ReqData<Caller>>,
 ) -> Result<HttpResponse, Error> {
-    // Read binary data from temp files
-    let base_data = std::fs::read(&form.base_binary.file.path())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-    let overload_data = std::fs::read(&form.overload_binary.file.path())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    let config = config.load_full();
+    let started_on = Utc::now();
+
+    // Read binary data from uploads or staged binaries
+    let base_data = match load_merge_input(
+        form.base_binary.as_ref(),
+        form.base_id.as_deref().map(String::as_str),
+        form.base_url.as_deref().map(String::as_str),
+        form.base_sha256.as_deref().map(String::as_str),
+        &binary_store,
+        caller.as_deref(),
+        &config,
+        "base",
+    ).await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let overload_data = match load_merge_input(
+        form.overload_binary.as_ref(),
+        form.overload_id.as_deref().map(String::as_str),
+        form.overload_url.as_deref().map(String::as_str),
+        form.overload_sha256.as_deref().map(String::as_str),
+        &binary_store,
+        caller.as_deref(),
+        &config,
+        "overload",
+    ).await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
     
     // Parse parameters
     let mode = form.mode
@@ -57,20 +184,49 @@ pub async fn merge_binaries(
         .unwrap_or(false);
     
     let _output_name = form.output_name.as_ref().map(|t| t.to_string());
+
+    // Validate webhook target before doing any work
+    let callback_url = form.callback_url.as_ref().map(|t| t.to_string());
+    if let Some(ref url) = callback_url {
+        if let Err(e) = notify::validate_callback_url(url, &config).await {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid callback URL", Some(e))));
+        }
+    }
+
+    let labels = match form.labels.as_ref().map(|t| t.trim()).filter(|json| !json.is_empty()) {
+        Some(json) => match parse_labels(json) {
+            Ok(labels) => labels,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid labels", Some(e))));
+            }
+        },
+        None => Labels::new(),
+    };
+    let oci_push = match parse_oci_push(form.oci_push.as_deref().map(String::as_str), &config) {
+        Ok(target) => target,
+        Err(response) => return Ok(response),
+    };
     
     // Validate file sizes
     if base_data.len() > config.max_file_size {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Base binary too large".to_string(),
-            details: Some(format!("Max size: {} bytes", config.max_file_size)),
-        }));
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Base binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
     }
     
     if overload_data.len() > config.max_file_size {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Overload binary too large".to_string(),
-            details: Some(format!("Max size: {} bytes", config.max_file_size)),
-        }));
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Overload binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
+    }
+
+    // Refuse text files and broken uploads before any work is done
+    if let Err(response) = validate_merge_inputs(&base_data, &overload_data, None, false) {
+        return Ok(response);
     }
 
     log::info!("Merging binaries: mode={:?}, sync={}", mode, sync);
@@ -78,54 +234,212 @@ pub async fn merge_binaries(
 
     // Get task_id for progress tracking
     let task_id = form.task_id.as_ref().map(|t| t.to_string());
+
+    // Register the merge so DELETE /jobs/{task_id} can cancel it
+    let job = match jobs.register(task_id.as_deref(), caller.as_deref()) {
+        Ok(job) => job,
+        Err(e) => return Ok(duplicate_job(e)),
+    };
     
     // Initialize progress tracker if task_id provided
     let progress_tracker = if let Some(ref tid) = task_id {
-        match ProgressTracker::new(&config.redis_url, tid.clone()) {
-            Ok(tracker) => {
-                let _ = tracker.update(ProgressStep::Started).await;
-                Some(tracker)
+        let tracker = ProgressTracker::new(progress.clone().into_inner(), tid.clone());
+        let _ = tracker.update(ProgressStep::Started).await;
+        Some(tracker)
+    } else {
+        None
+    };
+
+    let base_info = BinaryInfo::detect(&base_data);
+    let options = StubOptions { sync_mode: sync, order: mode.footer_order(), ..StubOptions::default() };
+    let facts = MergeFacts {
+        tenant: caller.as_ref().map(|caller| caller.name.as_str()),
+        endpoint: "POST /merge",
+        base_info: &base_info,
+        strategy: None,
+        options: &options,
+        signed: signer.is_enabled(),
+    };
+    if let Err(violation) = policy.check(&facts) {
+        let error_msg = format!("Policy: {}", violation);
+        if let Some(ref tid) = task_id {
+            let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+        }
+        if let Some(url) = callback_url {
+            notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+        }
+        return Ok(policy_violation(&violation));
+    }
+
+    // Scanned before the cache lookup, so cached merges are checked against current signatures too
+    let scans = match scanner.scan(&[("base", &base_data[..]), ("overload", &overload_data[..])]).await {
+        Ok(scans) => scans,
+        Err(e) => {
+            let error_msg = format!("Payload scan: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
             }
-            Err(e) => {
-                log::warn!("Failed to create progress tracker: {}", e);
-                None
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
             }
+            return Ok(scan_rejected(&e));
         }
+    };
+
+    // Reuse an identical earlier merge unless the client forces a rebuild
+    let force = form.force.as_ref().map(|t| **t).unwrap_or(false);
+    let want_report = form.report.as_ref().map(|t| **t).unwrap_or(false);
+    let cache_key = if merge_cache.is_enabled() {
+        stubs.select(&base_info, None).ok().map(|stub| MergeCacheKey::new(&base_data, &overload_data, &stub, &options))
     } else {
         None
     };
+    if let (Some(key), false) = (&cache_key, force) {
+        let cached = lookup_cached_merge(&merge_cache, key, &binary_store)
+            .filter(|cached| (!want_report || cached.report_path.is_some()) && cached.labels == labels)
+            .filter(|cached| may_read(caller.as_deref(), cached));
+        if let Some(cached) = cached {
+            log::info!("♻️  Returning cached merge {}", cached.id);
+            
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(
+                    progress.get_ref(),
+                    tid,
+                    Some(cached.id.clone()),
+                    None,
+                    Some(cached.size),
+                ).await;
+            }
+            
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::success(task_id.clone(), &cached.id, cached.size));
+            }
+            
+            let oci_reference = match push_merged(oci_push.as_ref(), &cached.id, &binary_store, &config).await {
+                Ok(pushed) => pushed,
+                Err(response) => return Ok(response),
+            };
+            return Ok(HttpResponse::Ok().json(MergeResponse { oci_reference, ..MergeResponse::from(&cached) }));
+        }
+    }
+
+    // Wait for a merge slot; refused with 503 when the queue is saturated
+    let _merge_permit = match scheduler.acquire_cancellable(job.token()).await {
+        Ok(permit) => permit,
+        Err(SchedulerError::Cancelled) => {
+            return Ok(merge_cancelled(Cancelled::Requested, task_id.as_deref(), callback_url, progress.get_ref(), &config).await);
+        }
+        Err(e) => {
+            let error_msg = format!("Merge capacity exhausted: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+            }
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            return Ok(merge_refused(&scheduler, e));
+        }
+    };
+
+    // Fail now rather than leave a half-written artifact when the disk fills
+    let _space = match disk_guard.reserve(projected_output_size(&stubs, &base_data, &overload_data), &binary_store) {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            let error_msg = format!("Not enough storage: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+            }
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            return Ok(insufficient_storage(e));
+        }
+    };
+
+    // Queue time doesn't count; the clock starts once the merge has its slot
+    if let Some(timeout) = merge_timeout(config.merge_timeout, form.timeout_secs.as_ref().map(|t| t.0)) {
+        job.arm_timeout(timeout);
+    }
 
     // Perform the merge
     let task_id_str = task_id.as_deref().unwrap_or("");
-    match core::merge_binaries(&base_data, &overload_data, mode, sync, &config.temp_dir, task_id_str, &config.redis_url).await {
+    match core::merge_binaries(&base_data, &overload_data, mode, sync, &workspaces, task_id_str, &progress.clone().into_inner(), &stubs, job.token()).await {
         Ok(merged_path) => {
             let binary_id = Uuid::new_v4().to_string();
+            let store_span = tracing::info_span!("store", binary_id = %binary_id).entered();
             let metadata = std::fs::metadata(&merged_path).unwrap();
             let size = metadata.len();
             
             let now = Utc::now();
             let expires_at = now + Duration::seconds(config.binary_ttl);
             
+            // Optional human-readable report stored next to the artifact
+            let report_path = if form.report.as_ref().map(|t| **t).unwrap_or(false) {
+                match WeaveReport::write_for(&binary_id, std::path::Path::new(&merged_path), &base_data, &overload_data) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        log::warn!("Failed to generate weave report: {}", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            
+            let provenance = MergeProvenance {
+                binary_id: &binary_id,
+                endpoint: "POST /merge",
+                base_data: &base_data,
+                overload_data: &overload_data,
+                labels: &labels,
+                started_on,
+            }
+            .statement_for(std::path::Path::new(&merged_path))
+            .inspect_err(|e| log::warn!("Failed to record provenance: {}", e))
+            .ok();
+            let signature = signer
+                .minisign_file(std::path::Path::new(&merged_path), &binary_id)
+                .inspect_err(|e| log::warn!("Failed to sign merged binary: {}", e))
+                .ok()
+                .flatten();
+            let sha256 = digest::sha256_file(std::path::Path::new(&merged_path))
+                .inspect_err(|e| log::warn!("Failed to hash merged binary: {}", e))
+                .ok();
+            let details = MergeDetails::new(StubAppend::NAME, &base_info, &overload_data, stubs.select(&base_info, None).ok(), &options, signature.is_some());
+            
             let stored = StoredBinary {
                 id: binary_id.clone(),
                 path: merged_path,
                 size,
                 created_at: now,
                 expires_at,
+                report_path: report_path.clone(),
+                kind: BinaryKind::Merged,
+                sha256: sha256.clone(),
+                labels,
+                provenance,
+                signature: signature.clone(),
+                owner: caller.as_ref().map(|caller| caller.name.clone()),
+                scans,
+                details: Some(details.clone()),
+                downloads: 0,
+                last_accessed: None,
+                stagers: Vec::new(),
             };
             
             // Store the binary
-            {
-                let mut store = binary_store.lock().unwrap();
-                store.insert(binary_id.clone(), stored);
+            binary_store.insert(stored);
+            if let Some(key) = cache_key {
+                merge_cache.insert(key, binary_id.clone());
             }
+            drop(store_span);
             
             log::info!("✅ Merge successful! Binary ID: {}, Size: {} bytes", binary_id, size);
             
-            // Publish completion to Redis
+            // Publish completion to progress subscribers
             if let Some(ref tid) = task_id {
                 let _ = crate::core::progress::ProgressTracker::publish_complete(
-                    &config.redis_url,
+                    progress.get_ref(),
                     tid,
                     Some(binary_id.clone()),
                     None,
@@ -133,22 +447,41 @@ pub async fn merge_binaries(
                 ).await;
             }
             
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::success(task_id.clone(), &binary_id, size));
+            }
+            
+            let oci_reference = match push_merged(oci_push.as_ref(), &binary_id, &binary_store, &config).await {
+                Ok(pushed) => pushed,
+                Err(response) => return Ok(response),
+            };
+            
             Ok(HttpResponse::Ok().json(MergeResponse {
                 success: true,
                 binary_id: binary_id.clone(),
                 size,
                 download_url: format!("/download/{}", binary_id),
                 expires_at,
+                report_url: report_path.as_ref().map(|_| format!("/download/{}/report.html", binary_id)),
+                signature_url: signature.as_ref().map(|_| format!("/download/{}.sig", binary_id)),
+                oci_reference,
+                stripped: None,
+                sha256,
+                details: Some(details),
                 error: None,
             }))
         }
+        Err(e) if e.is::<Cancelled>() => {
+            let reason = e.downcast::<Cancelled>().unwrap_or(Cancelled::Requested);
+            Ok(merge_cancelled(reason, task_id.as_deref(), callback_url, progress.get_ref(), &config).await)
+        }
         Err(e) => {
             log::error!("❌ Merge failed: {}", e);
             
-            // Publish error to Redis
+            // Publish error to progress subscribers
             if let Some(ref tid) = task_id {
                 let _ = crate::core::progress::ProgressTracker::publish_complete(
-                    &config.redis_url,
+                    progress.get_ref(),
                     tid,
                     None,
                     Some(e.to_string()),
@@ -156,14 +489,11 @@ pub async fn merge_binaries(
                 ).await;
             }
             
-            Ok(HttpResponse::InternalServerError().json(MergeResponse {
-                success: false,
-                binary_id: String::new(),
-                size: 0,
-                download_url: String::new(),
-                expires_at: Utc::now(),
-                error: Some(e.to_string()),
-            }))
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), e.to_string()));
+            }
+            
+            Ok(merge_failed(&e))
         }
     }
 }
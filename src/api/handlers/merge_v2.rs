@@ -1,91 +1,1169 @@
 use actix_web::{web, HttpResponse, Error};
 use actix_multipart::form::{tempfile::TempFile, MultipartForm};
-use std::collections::HashMap;
-use std::sync::Mutex;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::models::{
-    response::{MergeResponse, ErrorResponse},
-    binary::StoredBinary,
+    response::{MergeDetails, MergePlanResponse, MergeResponse, StrippedPayloads, ErrorCode, ErrorResponse},
+    binary::{parse_labels, BinaryKind, Labels, StoredBinary},
+    request::MergeMode,
 };
 use crate::core;
-use crate::core::progress::{ProgressTracker, ProgressStep};
-use crate::core::binary::BinaryInfo;
-use crate::config::Config;
+use crate::core::progress::{ProgressSink, ProgressTracker, ProgressStep};
+use crate::core::digest;
+use crate::core::provenance::MergeProvenance;
+use crate::core::report::WeaveReport;
+use crate::core::diskguard::{projected_output_size, DiskGuard};
+use crate::core::jobs::{merge_timeout, Cancelled, JobRegistry};
+use crate::core::workspace::WorkspaceManager;
+use crate::core::auth::Caller;
+use crate::api::auth::may_read;
+use crate::core::policy::{MergeFacts, Policy};
+use crate::core::scan::PayloadScanner;
+use crate::core::scheduler::{MergeScheduler, SchedulerError};
+use crate::core::signing::Signer;
+use crate::core::notify::{self, MergeNotification};
+use crate::core::binary::{is_script, BinaryInfo, OperatingSystem};
+use crate::core::merger::{
+    check_payload_platforms, AuditLog, BundledInterpreter, CrashReport, ExecStrategy, Installer, KillPolicy, MachineBinding, MergeCache, MergeCacheKey, MergeJob, MergeStrategies, OutputLog, PlanJob, PrerequisiteFailure, Readiness, Revocation, SeccompProfile, SingleInstance, StubAppend, StubFlavor, StubLogLevel,
+    StubLogTarget, StubOptions, StubRegistry, WorkingDir,
+};
+use crate::core::store::{BinaryStore, TelemetryStore};
+use crate::config::SharedConfig;
+use super::binaries::{
+    duplicate_job, insufficient_storage, load_merge_input, lookup_cached_merge, merge_cancelled, merge_failed, merge_refused, parse_oci_push,
+    policy_violation, push_merged, read_data_files, scan_rejected, validate_merge_inputs,
+};
+use weaver_abi::footer::{JAIL_EMPTY_ROOT, JAIL_FILESYSTEM, JAIL_NETWORK, JAIL_PID};
 
-#[derive(Debug, MultipartForm)]
+#[derive(Debug, MultipartForm, ToSchema)]
 pub struct MergeV2Form {
     #[multipart(limit = "200MB")]
-    pub base_binary: TempFile,
+    #[schema(value_type = Option<String>, format = Binary)]
+    pub base_binary: Option<TempFile>,
     #[multipart(limit = "200MB")]
-    pub overload_binary: TempFile,
+    #[schema(value_type = Option<String>, format = Binary)]
+    pub overload_binary: Option<TempFile>,
+    /// Staged binary to use instead of uploading `base_binary`
+    #[multipart(rename = "base_id")]
+    #[schema(value_type = Option<String>)]
+    pub base_id: Option<actix_multipart::form::text::Text<String>>,
+    /// Staged binary to use instead of uploading `overload_binary`
+    #[multipart(rename = "overload_id")]
+    #[schema(value_type = Option<String>)]
+    pub overload_id: Option<actix_multipart::form::text::Text<String>>,
+    /// URL or `oci://` reference Weaver fetches the base from (host must be in `WEAVER_FETCH_ALLOWED_HOSTS`)
+    #[multipart(rename = "base_url")]
+    #[schema(value_type = Option<String>)]
+    pub base_url: Option<actix_multipart::form::text::Text<String>>,
+    /// URL or `oci://` reference Weaver fetches the overload from (host must be in `WEAVER_FETCH_ALLOWED_HOSTS`)
+    #[multipart(rename = "overload_url")]
+    #[schema(value_type = Option<String>)]
+    pub overload_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Expected hex SHA-256 of the base, however it is supplied
+    #[multipart(rename = "base_sha256")]
+    #[schema(value_type = Option<String>)]
+    pub base_sha256: Option<actix_multipart::form::text::Text<String>>,
+    /// Expected hex SHA-256 of the overload, however it is supplied
+    #[multipart(rename = "overload_sha256")]
+    #[schema(value_type = Option<String>)]
+    pub overload_sha256: Option<actix_multipart::form::text::Text<String>>,
+    /// Interpreter binary bundled to run a `#!` script overload, instead of the one its `#!` line names
+    #[multipart(limit = "200MB")]
+    #[schema(value_type = Option<String>, format = Binary)]
+    pub interpreter_binary: Option<TempFile>,
+    /// Staged binary to use instead of uploading `interpreter_binary`
+    #[multipart(rename = "interpreter_id")]
+    #[schema(value_type = Option<String>)]
+    pub interpreter_id: Option<actix_multipart::form::text::Text<String>>,
+    /// URL or `oci://` reference Weaver fetches the interpreter from (host must be in `WEAVER_FETCH_ALLOWED_HOSTS`)
+    #[multipart(rename = "interpreter_url")]
+    #[schema(value_type = Option<String>)]
+    pub interpreter_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Expected hex SHA-256 of the interpreter, however it is supplied
+    #[multipart(rename = "interpreter_sha256")]
+    #[schema(value_type = Option<String>)]
+    pub interpreter_sha256: Option<actix_multipart::form::text::Text<String>>,
+    /// The overload is a shared library (`.so`, `.dylib`, `.dll`) loaded into base instead of run beside it
+    #[multipart(rename = "overload_library")]
+    #[schema(value_type = Option<bool>)]
+    pub overload_library: Option<actix_multipart::form::text::Text<bool>>,
+    /// Absolute directory the merged binary installs base into on first run; later runs use the installed copy
+    #[multipart(rename = "install_dir")]
+    #[schema(value_type = Option<String>)]
+    pub install_dir: Option<actix_multipart::form::text::Text<String>>,
+    /// File name of the installed base, systemd unit and shortcut (letters, digits, `.`, `_`, `-`)
+    #[multipart(rename = "install_name")]
+    #[schema(value_type = Option<String>)]
+    pub install_name: Option<actix_multipart::form::text::Text<String>>,
+    /// Data files installed next to base, each at the relative path given as its file name
+    #[multipart(rename = "install_files", limit = "200MB")]
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub install_files: Vec<TempFile>,
+    /// Write a systemd unit running the merged binary on first run (Linux only; not enabled)
+    #[multipart(rename = "install_systemd_unit")]
+    #[schema(value_type = Option<bool>)]
+    pub install_systemd_unit: Option<actix_multipart::form::text::Text<bool>>,
+    /// Write a desktop entry launching the merged binary on first run (Linux only)
+    #[multipart(rename = "install_shortcut")]
+    #[schema(value_type = Option<bool>)]
+    pub install_shortcut: Option<actix_multipart::form::text::Text<bool>>,
+    /// Data files (configs, licenses, models) extracted on the target for the payloads, which find
+    /// them through `KILLCODE_ASSETS_DIR`; each goes to the relative path given as its file name
+    #[multipart(rename = "assets", limit = "200MB")]
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub assets: Vec<TempFile>,
     #[multipart(rename = "output_name")]
+    #[schema(value_type = Option<String>)]
     pub output_name: Option<actix_multipart::form::text::Text<String>>,
     #[multipart(rename = "task_id")]
+    #[schema(value_type = Option<String>)]
     pub task_id: Option<actix_multipart::form::text::Text<String>>,
     
     // V2 Config Options
     #[multipart(rename = "grace_period")]
+    #[schema(value_type = Option<u32>)]
     pub grace_period: Option<actix_multipart::form::text::Text<u32>>,
     #[multipart(rename = "sync_mode")]
+    #[schema(value_type = Option<bool>)]
     pub sync_mode: Option<actix_multipart::form::text::Text<bool>>,
+    /// `before` (default) starts the overload first, `after` starts base first
+    #[multipart(rename = "mode")]
+    #[schema(value_type = Option<MergeMode>)]
+    pub mode: Option<actix_multipart::form::text::Text<String>>,
+    /// Run the overload to completion this many times in a row before base starts (sync_mode repeated)
+    #[multipart(rename = "prerequisite_runs")]
+    #[schema(value_type = Option<u32>)]
+    pub prerequisite_runs: Option<actix_multipart::form::text::Text<u32>>,
+    /// What a failed prerequisite run does: `abort` (default), `retry` it or `restart` from the first run
+    #[multipart(rename = "prerequisite_on_failure")]
+    #[schema(value_type = Option<String>)]
+    pub prerequisite_on_failure: Option<actix_multipart::form::text::Text<String>>,
+    /// Failed runs `retry` and `restart` tolerate before the stub exits
+    #[multipart(rename = "prerequisite_max_failures")]
+    #[schema(value_type = Option<u32>)]
+    pub prerequisite_max_failures: Option<actix_multipart::form::text::Text<u32>>,
+    #[multipart(rename = "prerequisite_retry_delay_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub prerequisite_retry_delay_secs: Option<actix_multipart::form::text::Text<u32>>,
     #[multipart(rename = "network_failure_kill_count")]
+    #[schema(value_type = Option<u32>)]
     pub network_failure_kill_count: Option<actix_multipart::form::text::Text<u32>>,
+    #[multipart(rename = "heartbeat_timeout")]
+    #[schema(value_type = Option<u32>)]
+    pub heartbeat_timeout: Option<actix_multipart::form::text::Text<u32>>,
+    /// Seconds after base starts before health checks count ages and failures (0 = right away)
+    #[multipart(rename = "monitor_warmup_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub monitor_warmup_secs: Option<actix_multipart::form::text::Text<u32>>,
+    /// Start counting at each payload's first successful check; monitor_warmup_secs then caps the wait
+    #[multipart(rename = "arm_after_success")]
+    #[schema(value_type = Option<bool>)]
+    pub arm_after_success: Option<actix_multipart::form::text::Text<bool>>,
+    #[multipart(rename = "jail_filesystem")]
+    #[schema(value_type = Option<bool>)]
+    pub jail_filesystem: Option<actix_multipart::form::text::Text<bool>>,
+    #[multipart(rename = "jail_network")]
+    #[schema(value_type = Option<bool>)]
+    pub jail_network: Option<actix_multipart::form::text::Text<bool>>,
+    /// Run the overload as PID 1 of its own PID namespace
+    #[multipart(rename = "jail_pid")]
+    #[schema(value_type = Option<bool>)]
+    pub jail_pid: Option<actix_multipart::form::text::Text<bool>>,
+    /// pivot_root the overload into an empty tmpfs (statically linked overloads only)
+    #[multipart(rename = "jail_empty_root")]
+    #[schema(value_type = Option<bool>)]
+    pub jail_empty_root: Option<actix_multipart::form::text::Text<bool>>,
+    #[multipart(rename = "overload_start_delay_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub overload_start_delay_secs: Option<actix_multipart::form::text::Text<u32>>,
+    #[multipart(rename = "overload_interval_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub overload_interval_secs: Option<actix_multipart::form::text::Text<u32>>,
+    #[multipart(rename = "health_check_interval_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub health_check_interval_secs: Option<actix_multipart::form::text::Text<u32>>,
+    /// Health monitor rules as JSON, replacing the grace period, failure count and heartbeat checks:
+    /// `{"rules": [{"when": <condition>, "action": "log"|"kill_base"|"kill_overload"|"exit"}]}`
+    #[multipart(rename = "kill_policy")]
+    #[schema(value_type = Option<String>)]
+    pub kill_policy: Option<actix_multipart::form::text::Text<String>>,
+    #[multipart(rename = "kill_delay_ms")]
+    #[schema(value_type = Option<u32>)]
+    pub kill_delay_ms: Option<actix_multipart::form::text::Text<u32>>,
+    #[multipart(rename = "shutdown_grace_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub shutdown_grace_secs: Option<actix_multipart::form::text::Text<u32>>,
+    /// Memory cap for base and its children in MiB (Linux and Windows)
+    #[multipart(rename = "base_memory_limit_mb")]
+    #[schema(value_type = Option<u32>)]
+    pub base_memory_limit_mb: Option<actix_multipart::form::text::Text<u32>>,
+    /// CPU cap for base, 1-100 percent of the machine (Linux and Windows)
+    #[multipart(rename = "base_cpu_percent")]
+    #[schema(value_type = Option<u32>)]
+    pub base_cpu_percent: Option<actix_multipart::form::text::Text<u32>>,
+    /// Memory cap for the overload and its children in MiB (Linux and Windows)
+    #[multipart(rename = "overload_memory_limit_mb")]
+    #[schema(value_type = Option<u32>)]
+    pub overload_memory_limit_mb: Option<actix_multipart::form::text::Text<u32>>,
+    /// CPU cap for the overload, 1-100 percent of the machine (Linux and Windows)
+    #[multipart(rename = "overload_cpu_percent")]
+    #[schema(value_type = Option<u32>)]
+    pub overload_cpu_percent: Option<actix_multipart::form::text::Text<u32>>,
+    /// Open file limit for base (Linux only)
+    #[multipart(rename = "base_nofile_limit")]
+    #[schema(value_type = Option<u32>)]
+    pub base_nofile_limit: Option<actix_multipart::form::text::Text<u32>>,
+    /// Open file limit for the overload (Linux only)
+    #[multipart(rename = "overload_nofile_limit")]
+    #[schema(value_type = Option<u32>)]
+    pub overload_nofile_limit: Option<actix_multipart::form::text::Text<u32>>,
+    /// Account payloads switch to when the merged binary runs as root (Linux and macOS)
+    #[multipart(rename = "run_as_user")]
+    #[schema(value_type = Option<String>)]
+    pub run_as_user: Option<actix_multipart::form::text::Text<String>>,
+    /// Strip root's privileges from payloads even without `run_as_user` (Linux and macOS)
+    #[multipart(rename = "drop_privileges")]
+    #[schema(value_type = Option<bool>)]
+    pub drop_privileges: Option<actix_multipart::form::text::Text<bool>>,
+    /// Seccomp allowlist as JSON: `{"default_action": "errno"|"kill"|"log", "apply_to": "overload"|"base"|"both", "allow": [syscall names]}` (Linux only)
+    #[multipart(rename = "seccomp_profile")]
+    #[schema(value_type = Option<String>)]
+    pub seccomp_profile: Option<actix_multipart::form::text::Text<String>>,
+    /// Refuse to run when the merged binary was modified or a debugger is attached, and kill the payloads if one attaches later
+    #[multipart(rename = "hardening")]
+    #[schema(value_type = Option<bool>)]
+    pub hardening: Option<actix_multipart::form::text::Text<bool>>,
+    /// How much the stub itself logs: `verbose` (default), `errors` or `silent`
+    #[multipart(rename = "stub_log_level")]
+    #[schema(value_type = Option<String>)]
+    pub stub_log_level: Option<actix_multipart::form::text::Text<String>>,
+    /// Where the stub logs: `stderr` (default), `system` (syslog or Windows Event Log) or `file`
+    #[multipart(rename = "stub_log_target")]
+    #[schema(value_type = Option<String>)]
+    pub stub_log_target: Option<actix_multipart::form::text::Text<String>>,
+    /// Log file on the machine running the merged binary, for `stub_log_target=file`
+    #[multipart(rename = "stub_log_path")]
+    #[schema(value_type = Option<String>)]
+    pub stub_log_path: Option<actix_multipart::form::text::Text<String>>,
+    /// Product name used instead of "KillCode" in stub logs and as the payload process names
+    #[multipart(rename = "product_name")]
+    #[schema(value_type = Option<String>)]
+    pub product_name: Option<actix_multipart::form::text::Text<String>>,
+    /// How Linux stubs load payloads: `auto` (default: memfd, then O_TMPFILE, then a temp file), `memfd`, `tmpfile` or `tempfile`
+    #[multipart(rename = "exec_strategy")]
+    #[schema(value_type = Option<String>)]
+    pub exec_strategy: Option<actix_multipart::form::text::Text<String>>,
+    /// Directory base starts in: `inherit` (default), `binary_dir` (where the merged binary is installed) or an absolute path
+    #[multipart(rename = "base_working_dir")]
+    #[schema(value_type = Option<String>)]
+    pub base_working_dir: Option<actix_multipart::form::text::Text<String>>,
+    /// Directory the overload starts in: `inherit` (default), `binary_dir` or an absolute path
+    #[multipart(rename = "overload_working_dir")]
+    #[schema(value_type = Option<String>)]
+    pub overload_working_dir: Option<actix_multipart::form::text::Text<String>>,
+    /// Octal umask for base, e.g. `027` (Linux and macOS)
+    #[multipart(rename = "base_umask")]
+    #[schema(value_type = Option<String>)]
+    pub base_umask: Option<actix_multipart::form::text::Text<String>>,
+    /// Octal umask for the overload, e.g. `077` (Linux and macOS)
+    #[multipart(rename = "overload_umask")]
+    #[schema(value_type = Option<String>)]
+    pub overload_umask: Option<actix_multipart::form::text::Text<String>>,
+    /// Priority for base: `idle`, `below_normal`, `normal`, `above_normal`, `high` or a nice value from -20 to 19
+    #[multipart(rename = "base_priority")]
+    #[schema(value_type = Option<String>)]
+    pub base_priority: Option<actix_multipart::form::text::Text<String>>,
+    /// Priority for the overload, e.g. `idle` for a background scanner
+    #[multipart(rename = "overload_priority")]
+    #[schema(value_type = Option<String>)]
+    pub overload_priority: Option<actix_multipart::form::text::Text<String>>,
+    /// CPUs base may run on, e.g. `0-3,6` (Linux and Windows)
+    #[multipart(rename = "base_cpu_affinity")]
+    #[schema(value_type = Option<String>)]
+    pub base_cpu_affinity: Option<actix_multipart::form::text::Text<String>>,
+    /// CPUs the overload may run on, e.g. `7` (Linux and Windows)
+    #[multipart(rename = "overload_cpu_affinity")]
+    #[schema(value_type = Option<String>)]
+    pub overload_cpu_affinity: Option<actix_multipart::form::text::Text<String>>,
+    /// What a second copy does while one runs: `off` (default), `exit` or `signal` (SIGUSR1 to the running base, then exit)
+    #[multipart(rename = "single_instance")]
+    #[schema(value_type = Option<String>)]
+    pub single_instance: Option<actix_multipart::form::text::Text<String>>,
+    /// Accept health/status/restart_overload/shutdown commands on a local socket (named pipe on Windows)
+    #[multipart(rename = "control_socket")]
+    #[schema(value_type = Option<bool>)]
+    pub control_socket: Option<actix_multipart::form::text::Text<bool>>,
+    /// Give base and the overload a two-way channel; each finds its end in KILLCODE_IPC_FD
+    #[multipart(rename = "ipc_channel")]
+    #[schema(value_type = Option<bool>)]
+    pub ipc_channel: Option<actix_multipart::form::text::Text<bool>>,
+    /// Plain http:// URL the stub polls; a body of `revoke <hex Ed25519 signature of "revoke <url>">` terminates the payloads
+    #[multipart(rename = "revocation_url")]
+    #[schema(value_type = Option<String>)]
+    pub revocation_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Base64 Ed25519 public key (raw or DER SubjectPublicKeyInfo) revocations are signed with
+    #[multipart(rename = "revocation_key")]
+    #[schema(value_type = Option<String>)]
+    pub revocation_key: Option<actix_multipart::form::text::Text<String>>,
+    /// Seconds between revocation checks (0 = loader default of 300s)
+    #[multipart(rename = "revocation_interval")]
+    #[schema(value_type = Option<u32>)]
+    pub revocation_interval: Option<actix_multipart::form::text::Text<u32>>,
+    /// Moment the merged binary stops running: RFC 3339 (`2026-12-31T23:59:59Z`) or Unix seconds
+    #[multipart(rename = "expires_at")]
+    #[schema(value_type = Option<String>)]
+    pub expires_at: Option<actix_multipart::form::text::Text<String>>,
+    /// Check file timestamps and the monotonic clock so setting the system clock back doesn't extend `expires_at`
+    #[multipart(rename = "expiry_clock_checks")]
+    #[schema(value_type = Option<bool>)]
+    pub expiry_clock_checks: Option<actix_multipart::form::text::Text<bool>>,
+    /// Machine properties hashed into the fingerprint: any of `machine_id` (default), `hostname`, `mac`, comma-separated
+    #[multipart(rename = "machine_binding")]
+    #[schema(value_type = Option<String>)]
+    pub machine_binding: Option<actix_multipart::form::text::Text<String>>,
+    /// Hex fingerprints of the machines allowed to run the merged binary, as logged by the stub on refused machines
+    #[multipart(rename = "machine_fingerprints")]
+    #[schema(value_type = Option<String>)]
+    pub machine_fingerprints: Option<actix_multipart::form::text::Text<String>>,
+    /// File on the target machine the stub appends JSON audit records to (start, user, host, base exit code, health events)
+    #[multipart(rename = "audit_path")]
+    #[schema(value_type = Option<String>)]
+    pub audit_path: Option<actix_multipart::form::text::Text<String>>,
+    /// Plain http:// URL the stub POSTs each audit record to
+    #[multipart(rename = "audit_url")]
+    #[schema(value_type = Option<String>)]
+    pub audit_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Write `<binary>.crash-<time>.json` next to the merged binary when base crashes (signal, core dump or Windows exception)
+    #[multipart(rename = "crash_report_file")]
+    #[schema(value_type = Option<bool>)]
+    pub crash_report_file: Option<actix_multipart::form::text::Text<bool>>,
+    /// Plain http:// URL the stub POSTs a crash report to when base crashes
+    #[multipart(rename = "crash_report_url")]
+    #[schema(value_type = Option<String>)]
+    pub crash_report_url: Option<actix_multipart::form::text::Text<String>>,
+    /// Last lines of base's stderr kept for a crash report (at most 1000, 0 = stderr is left alone)
+    #[multipart(rename = "crash_stderr_lines")]
+    #[schema(value_type = Option<u32>)]
+    pub crash_stderr_lines: Option<actix_multipart::form::text::Text<u32>>,
+    /// Let base dump core when it crashes (Unix; lifts the soft core size limit to the hard one)
+    #[multipart(rename = "crash_core_dump")]
+    #[schema(value_type = Option<bool>)]
+    pub crash_core_dump: Option<actix_multipart::form::text::Text<bool>>,
+    /// Payloads whose stdout and stderr the stub tees into rotating logs: `base`, `overload` or both, comma-separated
+    #[multipart(rename = "output_log")]
+    #[schema(value_type = Option<String>)]
+    pub output_log: Option<actix_multipart::form::text::Text<String>>,
+    /// Directory on the target machine for `base.log` and `overload.log`, relative to the merged binary's unless absolute
+    #[multipart(rename = "output_log_dir")]
+    #[schema(value_type = Option<String>)]
+    pub output_log_dir: Option<actix_multipart::form::text::Text<String>>,
+    /// Size in MiB at which a log is rotated (default 10)
+    #[multipart(rename = "output_log_max_size_mb")]
+    #[schema(value_type = Option<u32>)]
+    pub output_log_max_size_mb: Option<actix_multipart::form::text::Text<u32>>,
+    /// Log files kept per payload, the current one included (default 5, at most 100)
+    #[multipart(rename = "output_log_max_files")]
+    #[schema(value_type = Option<u32>)]
+    pub output_log_max_files: Option<actix_multipart::form::text::Text<u32>>,
+    /// Probe a sidecar overload must pass before base starts: `tcp:host:port`, `file:path` or `ready_flag`
+    #[multipart(rename = "readiness_probe")]
+    #[schema(value_type = Option<String>)]
+    pub readiness_probe: Option<actix_multipart::form::text::Text<String>>,
+    /// Seconds to wait for the readiness probe (default 60)
+    #[multipart(rename = "readiness_timeout_secs")]
+    #[schema(value_type = Option<u32>)]
+    pub readiness_timeout_secs: Option<actix_multipart::form::text::Text<u32>>,
+    /// When the probe doesn't pass in time or the overload exits first: `abort` (default; kill the overload, exit 1) or `start_base`
+    #[multipart(rename = "readiness_on_timeout")]
+    #[schema(value_type = Option<String>)]
+    pub readiness_on_timeout: Option<actix_multipart::form::text::Text<String>>,
+    /// This service's http:// base URL as the target machines reach it; the stub reports health events to `/telemetry/{binary_id}` there
+    #[multipart(rename = "phone_home_url")]
+    #[schema(value_type = Option<String>)]
+    pub phone_home_url: Option<actix_multipart::form::text::Text<String>>,
+    #[multipart(rename = "report")]
+    #[schema(value_type = Option<bool>)]
+    pub report: Option<actix_multipart::form::text::Text<bool>>,
+    /// Remove debug info and static symbol tables from base and overload before merging; the response lists their original sizes
+    #[multipart(rename = "strip_payloads")]
+    #[schema(value_type = Option<bool>)]
+    pub strip_payloads: Option<actix_multipart::form::text::Text<bool>>,
+    #[multipart(rename = "callback_url")]
+    #[schema(value_type = Option<String>)]
+    pub callback_url: Option<actix_multipart::form::text::Text<String>>,
+    /// JSON object of string labels stored with the merged binary, e.g. `{"customer":"acme","version":"2.1"}`
+    #[multipart(rename = "labels")]
+    #[schema(value_type = Option<String>)]
+    pub labels: Option<actix_multipart::form::text::Text<String>>,
+    /// OCI tag to push the merged binary to, e.g. `oci://registry.example/team/app:1.0`
+    #[multipart(rename = "oci_push")]
+    #[schema(value_type = Option<String>)]
+    pub oci_push: Option<actix_multipart::form::text::Text<String>>,
+    /// Also write `labels` into the merged binary as an inert JSON segment the stub ignores
+    #[multipart(rename = "embed_labels")]
+    #[schema(value_type = Option<bool>)]
+    pub embed_labels: Option<actix_multipart::form::text::Text<bool>>,
+    /// Re-assemble even if an identical merge is cached
+    #[multipart(rename = "force")]
+    #[schema(value_type = Option<bool>)]
+    pub force: Option<actix_multipart::form::text::Text<bool>>,
+    /// Abort the merge after this many seconds; can only shorten `WEAVER_MERGE_TIMEOUT`
+    #[multipart(rename = "timeout_secs")]
+    #[schema(value_type = Option<u64>)]
+    pub timeout_secs: Option<actix_multipart::form::text::Text<u64>>,
+    /// How to weave the binaries: `append` (loader stub plus appended payloads) or `compile`
+    /// (C loader with the payloads linked in; sync_mode, mode and pack only). Default: the first usable one
+    #[multipart(rename = "strategy")]
+    #[schema(value_type = Option<String>)]
+    pub strategy: Option<actix_multipart::form::text::Text<String>>,
+    /// Linux stub build: `glibc`, `musl` (static, for Alpine and other musl images) or `static`
+    /// (glibc linked statically). Default: the first one that can run where base does
+    #[multipart(rename = "stub_flavor")]
+    #[schema(value_type = Option<String>)]
+    pub stub_flavor: Option<actix_multipart::form::text::Text<String>>,
+    /// Compress the merged binary: payloads are packed for the stub to unpack, and `upx` compresses the stub when installed
+    /// (`compile` strategy: the whole output, upx required)
+    #[multipart(rename = "pack")]
+    #[schema(value_type = Option<bool>)]
+    pub pack: Option<actix_multipart::form::text::Text<bool>>,
+    /// Run detection, compatibility checks and stub selection, and return the plan
+    /// (`MergePlanResponse`) instead of assembling anything
+    #[multipart(rename = "dry_run")]
+    #[schema(value_type = Option<bool>)]
+    pub dry_run: Option<actix_multipart::form::text::Text<bool>>,
 }
 
 /// V2 merge endpoint with advanced health monitoring
 /// POST /merge/v2/stop-on-exit
+#[utoipa::path(
+    post,
+    path = "/merge/v2/stop-on-exit",
+    tag = "merge",
+    request_body(content = MergeV2Form, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Binaries merged, or the plan when dry_run is set", body = MergeResponse),
+        (status = 400, description = "Invalid input or architecture mismatch", body = ErrorResponse),
+        (status = 403, description = "The merge breaks a policy rule", body = ErrorResponse),
+        (status = 422, description = "base or overload is not a runnable executable, or the payload scanner matched an input", body = ErrorResponse),
+        (status = 500, description = "Merge failed", body = ErrorResponse),
+        (status = 502, description = "base_url or overload_url could not be fetched", body = ErrorResponse),
+        (status = 409, description = "task_id is already in use, or the merge was cancelled", body = ErrorResponse),
+        (status = 503, description = "Too many merges running and queued (retry after Retry-After seconds), or the payload scanner failed", body = ErrorResponse),
+        (status = 507, description = "Not enough disk space or storage quota for the output", body = ErrorResponse),
+        (status = 504, description = "The merge ran longer than its timeout", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(name = "POST /merge/v2/stop-on-exit", skip_all)]
 pub async fn merge_v2_stop_on_exit(
     MultipartForm(form): MultipartForm<MergeV2Form>,
-    binary_store: web::Data<Mutex<HashMap<String, StoredBinary>>>,
-    config: web::Data<Config>,
+    binary_store: web::Data<BinaryStore>,
+    telemetry_store: web::Data<TelemetryStore>,
+    config: web::Data<SharedConfig>,
+    progress: web::Data<dyn ProgressSink>,
+    merge_cache: web::Data<MergeCache>,
+    stubs: web::Data<StubRegistry>,
+    strategies: web::Data<MergeStrategies>,
+    signer: web::Data<Signer>,
+    scheduler: web::Data<MergeScheduler>,
+    disk_guard: web::Data<DiskGuard>,
+    jobs: web::Data<JobRegistry>,
+    workspaces: web::Data<WorkspaceManager>,
+    scanner: web::Data<PayloadScanner>,
+    policy: web::Data<Policy>,
+    caller: Option<web::ReqData<Caller>>,
 ) -> Result<HttpResponse, Error> {
-    // Read binary data from temp files
-    let base_data = std::fs::read(&form.base_binary.file.path())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-    let overload_data = std::fs::read(&form.overload_binary.file.path())
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    let config = config.load_full();
+    let started_on = chrono::Utc::now();
+
+    // Read binary data from uploads or staged binaries
+    let mut base_data = match load_merge_input(
+        form.base_binary.as_ref(),
+        form.base_id.as_deref().map(String::as_str),
+        form.base_url.as_deref().map(String::as_str),
+        form.base_sha256.as_deref().map(String::as_str),
+        &binary_store,
+        caller.as_deref(),
+        &config,
+        "base",
+    ).await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let mut overload_data = match load_merge_input(
+        form.overload_binary.as_ref(),
+        form.overload_id.as_deref().map(String::as_str),
+        form.overload_url.as_deref().map(String::as_str),
+        form.overload_sha256.as_deref().map(String::as_str),
+        &binary_store,
+        caller.as_deref(),
+        &config,
+        "overload",
+    ).await {
+        Ok(data) => data,
+        Err(response) => return Ok(response),
+    };
+    let wants_interpreter = form.interpreter_binary.is_some() || form.interpreter_id.is_some() || form.interpreter_url.is_some();
+    let interpreter = if wants_interpreter {
+        match load_merge_input(
+            form.interpreter_binary.as_ref(),
+            form.interpreter_id.as_deref().map(String::as_str),
+            form.interpreter_url.as_deref().map(String::as_str),
+            form.interpreter_sha256.as_deref().map(String::as_str),
+            &binary_store,
+            caller.as_deref(),
+            &config,
+            "interpreter",
+        ).await {
+            Ok(data) => Some(BundledInterpreter::new(&data)),
+            Err(response) => return Ok(response),
+        }
+    } else {
+        None
+    };
     
     // Validate file sizes
     if base_data.len() > config.max_file_size {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Base binary too large".to_string(),
-            details: Some(format!("Max size: {} bytes", config.max_file_size)),
-        }));
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Base binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
     }
     
     if overload_data.len() > config.max_file_size {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Overload binary too large".to_string(),
-            details: Some(format!("Max size: {} bytes", config.max_file_size)),
-        }));
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Overload binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
+    }
+
+    if interpreter.as_ref().is_some_and(|interpreter| interpreter.data.len() > config.max_file_size) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Interpreter binary too large",
+            Some(format!("Max size: {} bytes", config.max_file_size)),
+        )));
+    }
+
+    // Refuse text files and broken uploads before any work is done
+    let overload_library = form.overload_library.as_ref().map(|t| **t).unwrap_or(false);
+    let interpreter_data = interpreter.as_ref().map(|interpreter| &interpreter.data[..]);
+    if let Err(response) = validate_merge_inputs(&base_data, &overload_data, interpreter_data, overload_library) {
+        return Ok(response);
     }
 
+    // Validate webhook target before doing any work
+    let callback_url = form.callback_url.as_ref().map(|t| t.to_string());
+    if let Some(ref url) = callback_url {
+        if let Err(e) = notify::validate_callback_url(url, &config).await {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid callback URL", Some(e))));
+        }
+    }
+
+    let labels = match form.labels.as_ref().map(|t| t.trim()).filter(|json| !json.is_empty()) {
+        Some(json) => match parse_labels(json) {
+            Ok(labels) => labels,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid labels", Some(e))));
+            }
+        },
+        None => Labels::new(),
+    };
+    let oci_push = match parse_oci_push(form.oci_push.as_deref().map(String::as_str), &config) {
+        Ok(target) => target,
+        Err(response) => return Ok(response),
+    };
+
     // Extract V2 config options
     let grace_period = form.grace_period.as_ref().map(|t| **t).unwrap_or(0);
     let sync_mode = form.sync_mode.as_ref().map(|t| **t).unwrap_or(false);
+    let prerequisite_runs = form.prerequisite_runs.as_ref().map(|t| **t).unwrap_or(0);
+    let prerequisite_max_failures = form.prerequisite_max_failures.as_ref().map(|t| **t).unwrap_or(0);
+    let prerequisite_retry_delay = form.prerequisite_retry_delay_secs.as_ref().map(|t| **t).unwrap_or(0);
     let network_failure_kill_count = form.network_failure_kill_count.as_ref().map(|t| **t).unwrap_or(0);
+    let heartbeat_timeout = form.heartbeat_timeout.as_ref().map(|t| **t).unwrap_or(0);
+    let monitor_warmup = form.monitor_warmup_secs.as_ref().map(|t| **t).unwrap_or(0);
+    let arm_after_success = form.arm_after_success.as_ref().map(|t| **t).unwrap_or(false);
+    let jail_filesystem = form.jail_filesystem.as_ref().map(|t| **t).unwrap_or(false);
+    let jail_network = form.jail_network.as_ref().map(|t| **t).unwrap_or(false);
+    let jail_pid = form.jail_pid.as_ref().map(|t| **t).unwrap_or(false);
+    let jail_empty_root = form.jail_empty_root.as_ref().map(|t| **t).unwrap_or(false);
+    let overload_start_delay = form.overload_start_delay_secs.as_ref().map(|t| **t).unwrap_or(0);
+    let overload_interval = form.overload_interval_secs.as_ref().map(|t| **t).unwrap_or(0);
+    let health_check_interval = form.health_check_interval_secs.as_ref().map(|t| **t).unwrap_or(0);
+    let kill_delay_ms = form.kill_delay_ms.as_ref().map(|t| **t).unwrap_or(0);
+    let shutdown_grace = form.shutdown_grace_secs.as_ref().map(|t| **t).unwrap_or(0);
+    let base_memory_limit_mb = form.base_memory_limit_mb.as_ref().map(|t| **t).unwrap_or(0);
+    let base_cpu_percent = form.base_cpu_percent.as_ref().map(|t| **t).unwrap_or(0);
+    let overload_memory_limit_mb = form.overload_memory_limit_mb.as_ref().map(|t| **t).unwrap_or(0);
+    let overload_cpu_percent = form.overload_cpu_percent.as_ref().map(|t| **t).unwrap_or(0);
+    let base_nofile_limit = form.base_nofile_limit.as_ref().map(|t| **t).unwrap_or(0);
+    let overload_nofile_limit = form.overload_nofile_limit.as_ref().map(|t| **t).unwrap_or(0);
+
+    let run_as_user = form.run_as_user.as_ref().map(|t| t.trim().to_string()).filter(|name| !name.is_empty());
+    let drop_privileges = form.drop_privileges.as_ref().map(|t| **t).unwrap_or(false);
+    let hardening = form.hardening.as_ref().map(|t| **t).unwrap_or(false);
+    let pack = form.pack.as_ref().map(|t| **t).unwrap_or(false);
+    let control_socket = form.control_socket.as_ref().map(|t| **t).unwrap_or(false);
+    let ipc_channel = form.ipc_channel.as_ref().map(|t| **t).unwrap_or(false);
+    let embedded_labels = if form.embed_labels.as_ref().map(|t| **t).unwrap_or(false) {
+        labels.clone()
+    } else {
+        Labels::new()
+    };
+
+    let product_name = form.product_name.as_ref().map(|t| t.trim().to_string()).filter(|name| !name.is_empty());
+    if let Some(ref name) = product_name {
+        if let Err(e) = core::merger::v2::validate_product_name(name) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid product_name", Some(e))));
+        }
+    }
+
+    let mode = match form.mode.as_ref().map(|t| t.trim()).filter(|value| !value.is_empty()) {
+        Some(value) => match MergeMode::parse(value) {
+            Ok(mode) => mode,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid mode",
+                    Some(e),
+                )));
+            }
+        },
+        None => MergeMode::default(),
+    };
+
+    let exec_strategy = match form.exec_strategy.as_ref().map(|t| t.trim()).filter(|value| !value.is_empty()) {
+        Some(value) => match ExecStrategy::parse(value) {
+            Ok(strategy) => strategy,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid exec_strategy",
+                    Some(e),
+                )));
+            }
+        },
+        None => ExecStrategy::default(),
+    };
+
+    let stub_flavor = match form.stub_flavor.as_ref().map(|t| t.trim()).filter(|value| !value.is_empty()) {
+        Some(value) => match StubFlavor::parse(value) {
+            Ok(flavor) => Some(flavor),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid stub_flavor",
+                    Some(e),
+                )));
+            }
+        },
+        None => None,
+    };
+
+    let single_instance = match form.single_instance.as_ref().map(|t| t.trim()).filter(|mode| !mode.is_empty()) {
+        Some(mode) => match SingleInstance::parse(mode) {
+            Ok(mode) => mode,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid single_instance",
+                    Some(e),
+                )));
+            }
+        },
+        None => SingleInstance::default(),
+    };
+
+    let prerequisite_on_failure = match form.prerequisite_on_failure.as_ref().map(|t| t.trim()).filter(|policy| !policy.is_empty()) {
+        Some(policy) => match PrerequisiteFailure::parse(policy) {
+            Ok(policy) => policy,
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid prerequisite_on_failure",
+                    Some(e),
+                )));
+            }
+        },
+        None => PrerequisiteFailure::default(),
+    };
+
+    let expires_at = match form.expires_at.as_ref().map(|t| t.trim()).filter(|value| !value.is_empty()) {
+        Some(value) => match core::merger::v2::parse_expires_at(value) {
+            Ok(expires_at) => Some(expires_at),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid expires_at",
+                    Some(e),
+                )));
+            }
+        },
+        None => None,
+    };
+    let expiry_clock_checks = form.expiry_clock_checks.as_ref().map(|t| **t).unwrap_or(false);
+
+    let machine_binding = match MachineBinding::parse(
+        form.machine_binding.as_ref().map_or("", |t| t.as_str()),
+        form.machine_fingerprints.as_ref().map_or("", |t| t.as_str()),
+    ) {
+        Ok(binding) => binding,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid machine binding",
+                Some(e),
+            )));
+        }
+    };
+
+    let install_files = match read_data_files(&form.install_files, &config, "install_files") {
+        Ok(files) => files,
+        Err(response) => return Ok(response),
+    };
+    let installer = match Installer::parse(
+        form.install_dir.as_ref().map_or("", |t| t.as_str()),
+        form.install_name.as_ref().map_or("", |t| t.as_str()),
+        install_files,
+        form.install_systemd_unit.as_ref().map(|t| **t).unwrap_or(false),
+        form.install_shortcut.as_ref().map(|t| **t).unwrap_or(false),
+    ) {
+        Ok(installer) => installer,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid installer settings",
+                Some(e),
+            )));
+        }
+    };
+
+    let assets = match read_data_files(&form.assets, &config, "assets") {
+        Ok(files) => files,
+        Err(response) => return Ok(response),
+    };
+    if let Err(e) = core::merger::v2::validate_assets(&assets) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid assets", Some(e))));
+    }
+
+    let audit = match AuditLog::parse(
+        form.audit_path.as_ref().map_or("", |t| t.as_str()),
+        form.audit_url.as_ref().map_or("", |t| t.as_str()),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid audit settings",
+                Some(e),
+            )));
+        }
+    };
+    let crash_report = match CrashReport::parse(
+        form.crash_report_file.as_ref().map(|t| **t).unwrap_or(false),
+        form.crash_report_url.as_ref().map_or("", |t| t.as_str()),
+        form.crash_stderr_lines.as_ref().map(|t| **t).unwrap_or(0),
+        form.crash_core_dump.as_ref().map(|t| **t).unwrap_or(false),
+    ) {
+        Ok(crash_report) => crash_report,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid crash report settings",
+                Some(e),
+            )));
+        }
+    };
+    let output_log = match OutputLog::parse(
+        form.output_log.as_ref().map_or("", |t| t.as_str()),
+        form.output_log_dir.as_ref().map_or("", |t| t.as_str()),
+        form.output_log_max_size_mb.as_ref().map(|t| **t).unwrap_or(0),
+        form.output_log_max_files.as_ref().map(|t| **t).unwrap_or(0),
+    ) {
+        Ok(output_log) => output_log,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid output log settings",
+                Some(e),
+            )));
+        }
+    };
+    let readiness = match Readiness::parse(
+        form.readiness_probe.as_ref().map_or("", |t| t.as_str()),
+        form.readiness_timeout_secs.as_ref().map(|t| **t).unwrap_or(0),
+        form.readiness_on_timeout.as_ref().map_or("", |t| t.as_str()),
+    ) {
+        Ok(readiness) => readiness,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid readiness probe settings",
+                Some(e),
+            )));
+        }
+    };
+
+    // The stub reports under the ID the merged binary is about to get
+    let merged_id = Uuid::new_v4().to_string();
+    let telemetry_url = match form.phone_home_url.as_ref().map(|t| t.trim()).filter(|url| !url.is_empty()) {
+        Some(base) => match core::merger::v2::telemetry_url(base, &merged_id) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid phone-home URL",
+                    Some(e),
+                )));
+            }
+        },
+        None => None,
+    };
+
+    let revocation = match Revocation::parse(
+        form.revocation_url.as_ref().map_or("", |t| t.as_str()),
+        form.revocation_key.as_ref().map_or("", |t| t.as_str()),
+        form.revocation_interval.as_ref().map(|t| **t).unwrap_or(0),
+    ) {
+        Ok(revocation) => revocation,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid revocation settings",
+                Some(e),
+            )));
+        }
+    };
+
+    let base_working_dir = form.base_working_dir.as_ref().map(|t| t.trim()).filter(|dir| !dir.is_empty());
+    let overload_working_dir = form.overload_working_dir.as_ref().map(|t| t.trim()).filter(|dir| !dir.is_empty());
+    let base_umask = form.base_umask.as_ref().map(|t| t.trim()).filter(|umask| !umask.is_empty());
+    let overload_umask = form.overload_umask.as_ref().map(|t| t.trim()).filter(|umask| !umask.is_empty());
+    let process_options = (
+        base_working_dir.map_or(Ok(WorkingDir::default()), WorkingDir::parse),
+        overload_working_dir.map_or(Ok(WorkingDir::default()), WorkingDir::parse),
+        base_umask.map(core::merger::v2::parse_umask).transpose(),
+        overload_umask.map(core::merger::v2::parse_umask).transpose(),
+    );
+    let (base_working_dir, overload_working_dir, base_umask, overload_umask) = match process_options {
+        (Ok(base_dir), Ok(overload_dir), Ok(base_umask), Ok(overload_umask)) => {
+            (base_dir, overload_dir, base_umask, overload_umask)
+        }
+        (Err(e), ..) | (_, Err(e), ..) | (.., Err(e), _) | (.., Err(e)) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid working directory or umask",
+                Some(e),
+            )));
+        }
+    };
+
+    let base_priority = form.base_priority.as_ref().map(|t| t.trim()).filter(|priority| !priority.is_empty());
+    let overload_priority = form.overload_priority.as_ref().map(|t| t.trim()).filter(|priority| !priority.is_empty());
+    let base_cpu_affinity = form.base_cpu_affinity.as_ref().map(|t| t.trim()).filter(|cpus| !cpus.is_empty());
+    let overload_cpu_affinity = form.overload_cpu_affinity.as_ref().map(|t| t.trim()).filter(|cpus| !cpus.is_empty());
+    let scheduling = (
+        base_priority.map(core::merger::v2::parse_priority).transpose(),
+        overload_priority.map(core::merger::v2::parse_priority).transpose(),
+        base_cpu_affinity.map_or(Ok(0), core::merger::v2::parse_cpu_affinity),
+        overload_cpu_affinity.map_or(Ok(0), core::merger::v2::parse_cpu_affinity),
+    );
+    let (base_priority, overload_priority, base_cpu_affinity, overload_cpu_affinity) = match scheduling {
+        (Ok(base_priority), Ok(overload_priority), Ok(base_cpus), Ok(overload_cpus)) => {
+            (base_priority, overload_priority, base_cpus, overload_cpus)
+        }
+        (Err(e), ..) | (_, Err(e), ..) | (.., Err(e), _) | (.., Err(e)) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid priority or CPU affinity",
+                Some(e),
+            )));
+        }
+    };
+
+    let log_level = match form.stub_log_level.as_ref().map(|t| t.trim()).filter(|level| !level.is_empty()) {
+        Some(level) => StubLogLevel::parse(level),
+        None => Ok(StubLogLevel::default()),
+    };
+    let log_target = form.stub_log_target.as_ref().map(|t| t.trim()).filter(|target| !target.is_empty());
+    let log_path = form.stub_log_path.as_ref().map(|t| t.trim()).filter(|path| !path.is_empty());
+    let log_target = StubLogTarget::parse(log_target.unwrap_or("stderr"), log_path);
+    let (log_level, log_target) = match (log_level, log_target) {
+        (Ok(level), Ok(target)) => (level, target),
+        (Err(e), _) | (_, Err(e)) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid stub logging options",
+                Some(e),
+            )));
+        }
+    };
+
+    if let Some(ref name) = run_as_user {
+        if let Err(e) = core::merger::v2::validate_run_as_user(name) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(ErrorCode::InvalidRequest, "Invalid run_as_user", Some(e))));
+        }
+    }
+
+    let kill_policy = match form.kill_policy.as_ref().map(|t| t.trim()).filter(|json| !json.is_empty()) {
+        Some(json) => match KillPolicy::from_json(json) {
+            Ok(policy) => Some(policy),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid kill policy",
+                    Some(e),
+                )));
+            }
+        },
+        None => None,
+    };
+
+    let seccomp_profile = match form.seccomp_profile.as_ref().map(|t| t.trim()).filter(|json| !json.is_empty()) {
+        Some(json) => match SeccompProfile::from_json(json) {
+            Ok(profile) => Some(profile),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                    ErrorCode::InvalidRequest,
+                    "Invalid seccomp profile",
+                    Some(e),
+                )));
+            }
+        },
+        None => None,
+    };
+
+    if base_cpu_percent > 100 || overload_cpu_percent > 100 {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid CPU limit",
+            Some("CPU limits are a percentage between 1 and 100".to_string()),
+        )));
+    }
+
+    let mut overload_jail = 0;
+    if jail_filesystem {
+        overload_jail |= JAIL_FILESYSTEM;
+    }
+    if jail_network {
+        overload_jail |= JAIL_NETWORK;
+    }
+    if jail_pid {
+        overload_jail |= JAIL_PID;
+    }
+    if jail_empty_root {
+        overload_jail |= JAIL_EMPTY_ROOT;
+    }
 
     log::info!("🔪 V2 Merging binaries with advanced health monitoring");
     log::info!("Base size: {} bytes, Overload size: {} bytes", base_data.len(), overload_data.len());
-    log::info!("Config: grace_period={}s, sync_mode={}, network_failure_kill_count={}", 
-               grace_period, sync_mode, network_failure_kill_count);
+    log::info!("Config: grace_period={}s, sync_mode={}, network_failure_kill_count={}, heartbeat_timeout={}s, jail_filesystem={}, jail_network={}, jail_pid={}, jail_empty_root={}", 
+               grace_period, sync_mode, network_failure_kill_count, heartbeat_timeout, jail_filesystem, jail_network, jail_pid, jail_empty_root);
+    if health_check_interval > 0 || kill_delay_ms > 0 || shutdown_grace > 0 {
+        log::info!("Timing: health_check_interval={}s, kill_delay={}ms, shutdown_grace={}s", health_check_interval, kill_delay_ms, shutdown_grace);
+    }
+    if let Some(ref policy) = kill_policy {
+        log::info!("Kill policy: {} rules", policy.rules.len());
+    }
+    if base_memory_limit_mb > 0 || base_cpu_percent > 0 || overload_memory_limit_mb > 0 || overload_cpu_percent > 0
+        || base_nofile_limit > 0 || overload_nofile_limit > 0
+    {
+        log::info!("Limits: base={}MiB/{}%/{} files, overload={}MiB/{}%/{} files",
+                   base_memory_limit_mb, base_cpu_percent, base_nofile_limit,
+                   overload_memory_limit_mb, overload_cpu_percent, overload_nofile_limit);
+    }
+    if run_as_user.is_some() || drop_privileges {
+        log::info!("Privileges: run_as_user={}, drop_privileges={}", run_as_user.as_deref().unwrap_or("-"), drop_privileges);
+    }
+    if let Some(ref profile) = seccomp_profile {
+        log::info!("Seccomp: {} syscalls allowed, default={:?}, apply_to={:?}", profile.allow.len(), profile.default_action, profile.apply_to);
+    }
+    if hardening {
+        log::info!("Hardening: checksum and anti-debug checks enabled");
+    }
+    if pack {
+        log::info!("Packing: on, upx {}", core::merger::pack::upx().map_or("not installed", |upx| upx.version.as_str()));
+    }
+    if log_level != StubLogLevel::default() || log_target != StubLogTarget::default() {
+        log::info!("Stub logging: level={:?}, target={:?}", log_level, log_target);
+    }
+    if let Some(ref name) = product_name {
+        log::info!("Branding: product_name={}", name);
+    }
+    if exec_strategy != ExecStrategy::default() {
+        log::info!("Exec strategy: {:?}", exec_strategy);
+    }
+    if base_working_dir != WorkingDir::default() || overload_working_dir != WorkingDir::default() {
+        log::info!("Working directories: base={:?}, overload={:?}", base_working_dir, overload_working_dir);
+    }
+    if prerequisite_runs > 0 {
+        log::info!("Prerequisite runs: {}, on_failure={:?}, max_failures={}, retry_delay={}s",
+                   prerequisite_runs, prerequisite_on_failure, prerequisite_max_failures, prerequisite_retry_delay);
+    }
+    if monitor_warmup > 0 || arm_after_success {
+        log::info!("Monitor arming: warmup={}s, after_first_success={}", monitor_warmup, arm_after_success);
+    }
+    if single_instance != SingleInstance::default() {
+        log::info!("Single instance: {:?}", single_instance);
+    }
+    if control_socket {
+        log::info!("Control socket enabled");
+    }
+    if ipc_channel {
+        log::info!("IPC channel enabled");
+    }
+    if let Some(expires_at) = expires_at {
+        log::info!("Expires at {} (clock checks: {})", expires_at, expiry_clock_checks);
+    }
+    if let Some(ref binding) = machine_binding {
+        log::info!("Machine binding: properties={:#x}, {} machine(s)", binding.properties, binding.fingerprints.len());
+    }
+    if let Some(ref revocation) = revocation {
+        log::info!("Revocation: {} every {}s", revocation.url, revocation.interval);
+    }
+    if let Some(ref audit) = audit {
+        log::info!("Audit log: path={:?}, url={:?}", audit.path, audit.url);
+    }
+    if let Some(ref crash_report) = crash_report {
+        log::info!(
+            "Crash report: file={}, url={:?}, stderr_lines={}, core_dump={}",
+            crash_report.file,
+            crash_report.url,
+            crash_report.stderr_lines,
+            crash_report.core_dump
+        );
+    }
+    if let Some(ref output_log) = output_log {
+        log::info!(
+            "Output log: base={}, overload={}, dir={:?}, {} MiB x {} files",
+            output_log.base,
+            output_log.overload,
+            output_log.dir,
+            output_log.max_size_mb,
+            output_log.max_files
+        );
+    }
+    if let Some(ref readiness) = readiness {
+        log::info!(
+            "Readiness probe: {:?}, timeout={}s, start_base_on_timeout={}",
+            readiness.probe,
+            readiness.timeout_secs,
+            readiness.start_base_on_timeout
+        );
+    }
+    if let Some(ref url) = telemetry_url {
+        log::info!("Phone home: {}", url);
+    }
+    if base_umask.is_some() || overload_umask.is_some() {
+        log::info!("umask: base={:?}, overload={:?}", base_umask, overload_umask);
+    }
+    if base_priority.is_some() || overload_priority.is_some() || base_cpu_affinity != 0 || overload_cpu_affinity != 0 {
+        log::info!(
+            "Scheduling: base nice={:?} cpus={:#x}, overload nice={:?} cpus={:#x}",
+            base_priority,
+            base_cpu_affinity,
+            overload_priority,
+            overload_cpu_affinity
+        );
+    }
+    if let Some(ref installer) = installer {
+        log::info!("Installer: {}/{} with {} file(s), systemd_unit={}, shortcut={}",
+                   installer.dir, installer.name, installer.files.len(), installer.systemd_unit, installer.shortcut);
+    }
+    if !assets.is_empty() {
+        log::info!("Assets: {} file(s), {} bytes", assets.len(), assets.iter().map(|file| file.data.len()).sum::<usize>());
+    }
+    if overload_start_delay > 0 || overload_interval > 0 {
+        log::info!("Schedule: overload_start_delay={}s, overload_interval={}s", overload_start_delay, overload_interval);
+    }
+
+    let options = StubOptions {
+        grace_period,
+        sync_mode,
+        prerequisite_runs,
+        prerequisite_on_failure,
+        prerequisite_max_failures,
+        prerequisite_retry_delay,
+        network_failure_kill_count,
+        heartbeat_timeout,
+        monitor_warmup,
+        arm_after_success,
+        overload_jail,
+        overload_start_delay,
+        overload_interval,
+        health_check_interval,
+        kill_delay_ms,
+        shutdown_grace,
+        base_memory_limit_mb,
+        base_cpu_percent,
+        overload_memory_limit_mb,
+        overload_cpu_percent,
+        base_nofile_limit,
+        overload_nofile_limit,
+        run_as_user,
+        drop_privileges,
+        seccomp_profile,
+        hardening,
+        log_level,
+        log_target,
+        product_name,
+        exec_strategy,
+        base_working_dir,
+        overload_working_dir,
+        base_umask,
+        overload_umask,
+        base_priority,
+        overload_priority,
+        base_cpu_affinity,
+        overload_cpu_affinity,
+        single_instance,
+        control_socket,
+        ipc_channel,
+        kill_policy,
+        revocation,
+        expires_at,
+        expiry_clock_checks,
+        machine_binding,
+        audit,
+        crash_report,
+        output_log,
+        readiness,
+        telemetry_url,
+        embedded_labels,
+        interpreter,
+        overload_library,
+        installer,
+        assets,
+        stub_flavor,
+        pack,
+        order: mode.footer_order(),
+        ..StubOptions::default()
+    };
+    if let Err(e) = core::merger::v2::validate_prerequisites(&options) {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid prerequisite options",
+            Some(e),
+        )));
+    }
 
     // Get task_id for progress tracking
     let task_id = form.task_id.as_ref().map(|t| t.to_string());
+
+    // Register the merge so DELETE /jobs/{task_id} can cancel it
+    let job = match jobs.register(task_id.as_deref(), caller.as_deref()) {
+        Ok(job) => job,
+        Err(e) => return Ok(duplicate_job(e)),
+    };
     
     // Initialize progress tracker if task_id provided
     let progress_tracker = if let Some(ref tid) = task_id {
-        match ProgressTracker::new(&config.redis_url, tid.clone()) {
-            Ok(tracker) => {
-                let _ = tracker.update(ProgressStep::Started).await;
-                Some(tracker)
-            }
-            Err(e) => {
-                log::warn!("Failed to create progress tracker: {}", e);
-                None
-            }
-        }
+        let tracker = ProgressTracker::new(progress.clone().into_inner(), tid.clone());
+        let _ = tracker.update(ProgressStep::Started).await;
+        Some(tracker)
     } else {
         None
     };
@@ -95,19 +1173,14 @@ pub async fn merge_v2_stop_on_exit(
     
     log::info!("🔍 Detected base binary: {}", base_info.description());
     
-    // Validate compatibility
-    let overload_info = BinaryInfo::detect(&overload_data);
-    if !base_info.is_compatible_with(&overload_info) {
-        let error_msg = format!(
-            "❌ Binary mismatch! Base is {} but overload is {}",
-            base_info.description(),
-            overload_info.description()
-        );
+    // Validate compatibility; a script overload runs on any Unix base
+    if let Err(e) = check_payload_platforms(&base_info, &overload_data, options.interpreter.as_ref().map(|interpreter| &interpreter.data[..])) {
+        let error_msg = format!("❌ {}", e);
         log::error!("{}", error_msg);
         
         if let Some(ref tid) = task_id {
             let _ = ProgressTracker::publish_complete(
-                &config.redis_url,
+                progress.get_ref(),
                 tid,
                 None,
                 Some(error_msg.clone()),
@@ -115,10 +1188,191 @@ pub async fn merge_v2_stop_on_exit(
             ).await;
         }
         
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "Binary architecture mismatch".to_string(),
-            details: Some(error_msg),
-        }));
+        if let Some(url) = callback_url {
+            notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg.clone()));
+        }
+        
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::from(&e),
+            "Incompatible binaries",
+            Some(error_msg),
+        )));
+    }
+
+    // Stripped before the cache key, scan and provenance see the payloads
+    let stripped = if form.strip_payloads.as_ref().map(|t| **t).unwrap_or(false) {
+        Some(StrippedPayloads {
+            base: base_data.strip("base", workspaces.root(), job.token()).await,
+            overload: overload_data.strip("overload", workspaces.root(), job.token()).await,
+        })
+    } else {
+        None
+    };
+
+    let requested_strategy = form.strategy.as_ref().map(|t| t.trim()).filter(|name| !name.is_empty());
+    let strategy = match strategies.select(requested_strategy, &base_info, &stubs) {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::from(&e),
+                "Invalid merge strategy",
+                Some(e.to_string()),
+            )));
+        }
+    };
+
+    // Syscall names only resolve once the target architecture is known
+    if let Some(profile) = options.seccomp_profile.as_ref().filter(|_| base_info.os == OperatingSystem::Linux) {
+        if let Err(e) = profile.compile(base_info.arch) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid seccomp profile",
+                Some(e),
+            )));
+        }
+    }
+
+    let facts = MergeFacts {
+        tenant: caller.as_ref().map(|caller| caller.name.as_str()),
+        endpoint: "POST /merge/v2/stop-on-exit",
+        base_info: &base_info,
+        strategy: Some(strategy.name()),
+        options: &options,
+        signed: signer.is_enabled(),
+    };
+    if let Err(violation) = policy.check(&facts) {
+        let error_msg = format!("Policy: {}", violation);
+        if let Some(ref tid) = task_id {
+            let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+        }
+        if let Some(url) = callback_url {
+            notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+        }
+        return Ok(policy_violation(&violation));
+    }
+
+    if form.dry_run.as_ref().map(|t| **t).unwrap_or(false) {
+        let plan = strategy.plan(&PlanJob {
+            base: &base_data,
+            overload: &overload_data,
+            base_info: &base_info,
+            stubs: &stubs,
+            options: &options,
+        });
+        if let Some(ref tid) = task_id {
+            let error = plan.as_ref().err().map(|e| e.to_string());
+            let size = plan.as_ref().ok().map(|plan| plan.output_size);
+            let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, error, size).await;
+        }
+        return Ok(match plan {
+            Ok(plan) => {
+                let overload = if is_script(&overload_data) { "script".to_string() } else { BinaryInfo::detect(&overload_data).description() };
+                HttpResponse::Ok().json(MergePlanResponse::new(strategy.name(), base_info.description(), overload, plan))
+            }
+            Err(e) => merge_failed(&e),
+        });
+    }
+
+    // Everything that ends up in the merged binary is scanned, before the cache
+    // lookup so cached merges are checked against current signatures too
+    let mut scan_inputs: Vec<(&str, &[u8])> = vec![("base", &base_data[..]), ("overload", &overload_data[..])];
+    scan_inputs.extend(options.interpreter.as_ref().map(|interpreter| ("interpreter", &interpreter.data[..])));
+    scan_inputs.extend(
+        options.installer.iter().flat_map(|installer| &installer.files).chain(&options.assets).map(|file| (file.path.as_str(), &file.data[..])),
+    );
+    let scans = match scanner.scan(&scan_inputs).await {
+        Ok(scans) => scans,
+        Err(e) => {
+            let error_msg = format!("Payload scan: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+            }
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            return Ok(scan_rejected(&e));
+        }
+    };
+
+    // Reuse an identical earlier merge unless the client forces a rebuild
+    let force = form.force.as_ref().map(|t| **t).unwrap_or(false);
+    let want_report = form.report.as_ref().map(|t| **t).unwrap_or(false);
+    let cache_key = if merge_cache.is_enabled() {
+        stubs
+            .select(&base_info, options.stub_flavor)
+            .ok()
+            .map(|stub| MergeCacheKey::new(&base_data, &overload_data, &stub, &options).strategy(strategy.name()))
+    } else {
+        None
+    };
+    if let (Some(key), false) = (&cache_key, force) {
+        let cached = lookup_cached_merge(&merge_cache, key, &binary_store)
+            .filter(|cached| (!want_report || cached.report_path.is_some()) && cached.labels == labels)
+            .filter(|cached| may_read(caller.as_deref(), cached));
+        if let Some(cached) = cached {
+            log::info!("♻️  Returning cached merge {}", cached.id);
+            
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(
+                    progress.get_ref(),
+                    tid,
+                    Some(cached.id.clone()),
+                    None,
+                    Some(cached.size),
+                ).await;
+            }
+            
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::success(task_id.clone(), &cached.id, cached.size));
+            }
+            
+            let oci_reference = match push_merged(oci_push.as_ref(), &cached.id, &binary_store, &config).await {
+                Ok(pushed) => pushed,
+                Err(response) => return Ok(response),
+            };
+            return Ok(HttpResponse::Ok().json(MergeResponse { oci_reference, stripped, ..MergeResponse::from(&cached) }));
+        }
+    }
+
+    // Wait for a merge slot; refused with 503 when the queue is saturated
+    let _merge_permit = match scheduler.acquire_cancellable(job.token()).await {
+        Ok(permit) => permit,
+        Err(SchedulerError::Cancelled) => {
+            return Ok(merge_cancelled(Cancelled::Requested, task_id.as_deref(), callback_url, progress.get_ref(), &config).await);
+        }
+        Err(e) => {
+            let error_msg = format!("Merge capacity exhausted: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+            }
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            return Ok(merge_refused(&scheduler, e));
+        }
+    };
+
+    // Fail now rather than leave a half-written artifact when the disk fills
+    let interpreter_size = options.interpreter.as_ref().map_or(0, |interpreter| interpreter.data.len() as u64);
+    let data_files = options.installer.iter().flat_map(|installer| &installer.files).chain(&options.assets);
+    let data_files_size: u64 = data_files.map(|file| file.data.len() as u64).sum();
+    let _space = match disk_guard.reserve(projected_output_size(&stubs, &base_data, &overload_data) + interpreter_size + data_files_size, &binary_store) {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            let error_msg = format!("Not enough storage: {}", e);
+            if let Some(ref tid) = task_id {
+                let _ = ProgressTracker::publish_complete(progress.get_ref(), tid, None, Some(error_msg.clone()), None).await;
+            }
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            return Ok(insufficient_storage(e));
+        }
+    };
+
+    // Queue time doesn't count; the clock starts once the merge has its slot
+    if let Some(timeout) = merge_timeout(config.merge_timeout, form.timeout_secs.as_ref().map(|t| t.0)) {
+        job.arm_timeout(timeout);
     }
 
     // Report: Merging binaries
@@ -126,34 +1380,34 @@ pub async fn merge_v2_stop_on_exit(
         let _ = tracker.update(ProgressStep::WritingBinaries).await;
     }
 
-    // Create work directory
-    let work_dir = format!("/tmp/weaver/merge_{}", Uuid::new_v4());
-    std::fs::create_dir_all(&work_dir)
+    // Create work directory; removed on every way out of this handler
+    let work_dir = workspaces.create()
         .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-    let work_dir_path = std::path::Path::new(&work_dir);
+    let work_dir_path = work_dir.path();
 
     // Perform V2 merge with health monitoring
-    let merge_result = core::merger::merge_v2_stop_on_exit(
-        &base_data,
-        &overload_data,
-        work_dir_path,
-        &base_info,
-        task_id.as_deref().unwrap_or(""),
-        &config.redis_url,
-        grace_period,
-        sync_mode,
-        network_failure_kill_count,
-    ).await;
+    log::info!("Merge strategy: {}", strategy.name());
+    let merge_result = strategy.merge(MergeJob {
+        base: (&base_data).into(),
+        overload: (&overload_data).into(),
+        work_path: work_dir_path,
+        base_info: &base_info,
+        task_id: task_id.as_deref().unwrap_or(""),
+        progress: &progress.clone().into_inner(),
+        stubs: &stubs,
+        options: &options,
+        cancel: job.token(),
+    }).await;
 
     match merge_result {
         Ok(merged_path) => {
-            let merged_id = Uuid::new_v4().to_string();
+            let store_span = tracing::info_span!("store", binary_id = %merged_id).entered();
             
-            // Copy to permanent location with UUID
-            let final_path = std::path::PathBuf::from(&config.temp_dir)
+            // Move to permanent location with UUID; the work dir is on the same filesystem
+            let final_path = workspaces.root()
                 .join(format!("merged_{}.bin", merged_id));
             
-            std::fs::copy(&merged_path, &final_path)
+            std::fs::rename(&merged_path, &final_path)
                 .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
             
             let metadata = std::fs::metadata(&final_path)
@@ -163,6 +1417,43 @@ pub async fn merge_v2_stop_on_exit(
             let now = chrono::Utc::now();
             let expires_at = now + chrono::Duration::seconds(config.binary_ttl);
             
+            // Optional human-readable report stored next to the artifact
+            let report_path = if form.report.as_ref().map(|t| **t).unwrap_or(false) {
+                match WeaveReport::write_for(&merged_id, &final_path, &base_data, &overload_data) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        log::warn!("Failed to generate weave report: {}", e);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            
+            let provenance = MergeProvenance {
+                binary_id: &merged_id,
+                endpoint: "POST /merge/v2/stop-on-exit",
+                base_data: &base_data,
+                overload_data: &overload_data,
+                labels: &labels,
+                started_on,
+            }
+            .statement_for(&final_path)
+            .inspect_err(|e| log::warn!("Failed to record provenance: {}", e))
+            .ok();
+            let signature = signer
+                .minisign_file(&final_path, &merged_id)
+                .inspect_err(|e| log::warn!("Failed to sign merged binary: {}", e))
+                .ok()
+                .flatten();
+            let sha256 = digest::sha256_file(&final_path)
+                .inspect_err(|e| log::warn!("Failed to hash merged binary: {}", e))
+                .ok();
+            let stub = (strategy.name() == StubAppend::NAME)
+                .then(|| stubs.select(&base_info, options.stub_flavor).ok())
+                .flatten();
+            let details = MergeDetails::new(strategy.name(), &base_info, &overload_data, stub, &options, signature.is_some());
+            
             // Store in memory
             let stored = StoredBinary {
                 id: merged_id.clone(),
@@ -170,19 +1461,38 @@ pub async fn merge_v2_stop_on_exit(
                 size,
                 created_at: now,
                 expires_at: expires_at.clone(),
+                report_path: report_path.clone(),
+                kind: BinaryKind::Merged,
+                sha256: sha256.clone(),
+                labels,
+                provenance,
+                signature: signature.clone(),
+                owner: caller.as_ref().map(|caller| caller.name.clone()),
+                scans,
+                details: Some(details.clone()),
+                downloads: 0,
+                last_accessed: None,
+                stagers: Vec::new(),
             };
             
-            binary_store.lock().unwrap().insert(merged_id.clone(), stored);
+            binary_store.insert(stored);
+            if options.telemetry_url.is_some() {
+                telemetry_store.register(&merged_id);
+            }
+            if let Some(key) = cache_key {
+                merge_cache.insert(key, merged_id.clone());
+            }
+            drop(store_span);
             
             log::info!("✅ Stored merged binary at: {}", final_path.display());
 
-            // Cleanup work directory
-            let _ = std::fs::remove_dir_all(&work_dir);
+            // Cleanup work directory before the OCI push
+            drop(work_dir);
 
             // Report completion
             if let Some(ref tid) = task_id {
                 let _ = ProgressTracker::publish_complete(
-                    &config.redis_url,
+                    progress.get_ref(),
                     tid,
                     Some(merged_id.clone()),
                     None,
@@ -192,22 +1502,42 @@ pub async fn merge_v2_stop_on_exit(
 
             log::info!("✅ V2 merge completed: {} bytes", size);
 
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::success(task_id.clone(), &merged_id, size));
+            }
+            
+            let oci_reference = match push_merged(oci_push.as_ref(), &merged_id, &binary_store, &config).await {
+                Ok(pushed) => pushed,
+                Err(response) => return Ok(response),
+            };
+            
             Ok(HttpResponse::Ok().json(MergeResponse {
                 success: true,
                 binary_id: merged_id.clone(),
                 size,
                 download_url: format!("/download/{}", merged_id),
                 expires_at,
+                report_url: report_path.as_ref().map(|_| format!("/download/{}/report.html", merged_id)),
+                signature_url: signature.as_ref().map(|_| format!("/download/{}.sig", merged_id)),
+                oci_reference,
+                stripped,
+                sha256,
+                details: Some(details),
                 error: None,
             }))
         }
+        Err(e) if e.is::<Cancelled>() => {
+            let reason = e.downcast::<Cancelled>().unwrap_or(Cancelled::Requested);
+            drop(work_dir);
+            Ok(merge_cancelled(reason, task_id.as_deref(), callback_url, progress.get_ref(), &config).await)
+        }
         Err(e) => {
             let error_msg = format!("Merge failed: {}", e);
             log::error!("❌ {}", error_msg);
 
             if let Some(ref tid) = task_id {
                 let _ = ProgressTracker::publish_complete(
-                    &config.redis_url,
+                    progress.get_ref(),
                     tid,
                     None,
                     Some(error_msg.clone()),
@@ -216,12 +1546,13 @@ pub async fn merge_v2_stop_on_exit(
             }
 
             // Cleanup
-            let _ = std::fs::remove_dir_all(&work_dir);
+            drop(work_dir);
 
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: "Merge failed".to_string(),
-                details: Some(error_msg),
-            }))
+            if let Some(url) = callback_url {
+                notify::spawn_webhook(&config, url, MergeNotification::failure(task_id.clone(), error_msg));
+            }
+            
+            Ok(merge_failed(&e))
         }
     }
 }
@@ -1,5 +1,14 @@
 pub mod health;
+pub mod capabilities;
+pub mod selftest;
 pub mod merge;
 pub mod merge_stop_on_exit;
 pub mod merge_v2;
+pub mod estimate;
 pub mod download;
+pub mod binaries;
+pub mod uploads;
+pub mod telemetry;
+pub mod progress;
+pub mod jobs;
+pub mod admin;
@@ -0,0 +1,128 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use actix_ws::Message;
+use futures_util::StreamExt;
+
+use crate::core::progress::{Progress, ProgressSink, ProgressTracker};
+use crate::models::response::{ErrorCode, ErrorResponse};
+
+/// Latest cached progress of a merge
+/// GET /progress/{task_id}
+#[utoipa::path(
+    get,
+    path = "/progress/{task_id}",
+    tag = "progress",
+    params(("task_id" = String, Path, description = "task_id sent with the merge request")),
+    responses(
+        (status = 200, description = "Latest progress", body = Progress),
+        (status = 404, description = "No progress recorded", body = ErrorResponse),
+        (status = 503, description = "Progress backend unavailable", body = ErrorResponse),
+    )
+)]
+pub async fn get_progress(
+    path: web::Path<String>,
+    progress: web::Data<dyn ProgressSink>,
+) -> Result<HttpResponse, Error> {
+    let task_id = path.into_inner();
+
+    match ProgressTracker::get(progress.get_ref(), &task_id).await {
+        Ok(Some(progress)) => Ok(HttpResponse::Ok().json(progress)),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "No progress recorded for task",
+            Some(format!("Task ID: {}", task_id)),
+        ))),
+        Err(e) => {
+            log::error!("Failed to read progress for {}: {}", task_id, e);
+            Ok(HttpResponse::ServiceUnavailable().json(ErrorResponse::new(
+                ErrorCode::Unavailable,
+                "Progress store unavailable",
+                Some(e.to_string()),
+            )))
+        }
+    }
+}
+
+/// Live merge progress over WebSocket
+/// GET /progress/{task_id}/ws
+///
+/// Bridges the task's progress channel to the socket. The cached
+/// progress (if any) is sent first, then every update as it is published.
+/// The socket is closed after the completion event.
+#[utoipa::path(
+    get,
+    path = "/progress/{task_id}/ws",
+    tag = "progress",
+    params(("task_id" = String, Path, description = "task_id sent with the merge request")),
+    responses(
+        (status = 101, description = "Switching to WebSocket; each text frame is a progress JSON payload"),
+        (status = 503, description = "Progress backend unavailable"),
+    )
+)]
+pub async fn progress_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    path: web::Path<String>,
+    progress: web::Data<dyn ProgressSink>,
+) -> Result<HttpResponse, Error> {
+    let task_id = path.into_inner();
+
+    // Subscribe before upgrading so a backend outage is reported as a plain HTTP error
+    let mut updates = ProgressTracker::subscribe(progress.get_ref(), &task_id)
+        .await
+        .map_err(|e| actix_web::error::ErrorServiceUnavailable(e))?;
+    let cached = ProgressTracker::get(progress.get_ref(), &task_id).await.ok().flatten();
+
+    let (response, mut session, mut client_messages) = actix_ws::handle(&req, body)?;
+
+    log::info!("📡 Progress WebSocket opened for task {}", task_id);
+
+    actix_web::rt::spawn(async move {
+        if let Some(progress) = cached {
+            if let Ok(json) = serde_json::to_string(&progress) {
+                if session.text(json).await.is_err() {
+                    return;
+                }
+            }
+        }
+
+        loop {
+            tokio::select! {
+                update = updates.next() => {
+                    let Some(payload) = update else { break };
+
+                    let complete = is_completion_event(&payload);
+                    if session.text(payload).await.is_err() {
+                        return;
+                    }
+                    if complete {
+                        break;
+                    }
+                }
+                message = client_messages.next() => {
+                    match message {
+                        Some(Ok(Message::Ping(bytes))) => {
+                            if session.pong(&bytes).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                        Some(Ok(_)) => {}
+                    }
+                }
+            }
+        }
+
+        log::info!("📡 Progress WebSocket closed for task {}", task_id);
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
+
+/// Completion events are published by `ProgressTracker::publish_complete`
+fn is_completion_event(payload: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|v| v.get("complete").and_then(|c| c.as_bool()))
+        .unwrap_or(false)
+}
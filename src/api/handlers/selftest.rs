@@ -0,0 +1,34 @@
+use actix_web::{web, HttpResponse};
+
+use crate::core::merger::StubRegistry;
+use crate::core::progress::ProgressSink;
+use crate::core::selftest;
+use crate::core::workspace::WorkspaceManager;
+use crate::models::response::SelfTestResponse;
+
+/// Merge and run a known-good pair on the host platform
+/// POST /selftest
+#[utoipa::path(
+    post,
+    path = "/selftest",
+    tag = "service",
+    responses(
+        (status = 200, description = "Self-test passed", body = SelfTestResponse),
+        (status = 503, description = "Self-test failed", body = SelfTestResponse),
+    )
+)]
+#[tracing::instrument(name = "POST /selftest", skip_all)]
+pub async fn selftest(
+    progress: web::Data<dyn ProgressSink>,
+    stubs: web::Data<StubRegistry>,
+    workspaces: web::Data<WorkspaceManager>,
+) -> HttpResponse {
+    let report = selftest::run(&workspaces, &stubs, &progress.into_inner()).await;
+    let response = SelfTestResponse::from(&report);
+
+    if report.passed {
+        HttpResponse::Ok().json(response)
+    } else {
+        HttpResponse::ServiceUnavailable().json(response)
+    }
+}
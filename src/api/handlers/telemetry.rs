@@ -0,0 +1,115 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::Utc;
+
+use crate::core::auth::Caller;
+use crate::core::store::{BinaryStore, TelemetryStore};
+use crate::models::{
+    binary::TelemetryEvent,
+    request::ListBinariesQuery,
+    response::{ErrorCode, ErrorResponse, TelemetryListResponse},
+};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+/// Longest event name accepted from a stub
+const MAX_EVENT_NAME_LEN: usize = 64;
+/// Largest record accepted from a stub; its health records take a few hundred bytes
+pub const MAX_RECORD_SIZE: usize = 4096;
+
+/// Report a health event from a merged binary
+/// POST /telemetry/{binary_id}
+///
+/// Called by stubs of binaries merged with `phone_home_url`, so it needs no
+/// credentials; reports for any other binary are rejected.
+#[utoipa::path(
+    post,
+    path = "/telemetry/{binary_id}",
+    tag = "telemetry",
+    params(("binary_id" = String, Path, description = "ID of the merged binary")),
+    request_body(content = Object, description = "Stub record with at least an `event` name", content_type = "application/json"),
+    responses(
+        (status = 204, description = "Event stored"),
+        (status = 400, description = "Record is not an object with an event name", body = ErrorResponse),
+        (status = 413, description = "Record is larger than 4 KiB"),
+        (status = 404, description = "Binary wasn't merged with phone-home", body = ErrorResponse),
+    )
+)]
+pub async fn report_event(
+    req: HttpRequest,
+    path: web::Path<String>,
+    record: web::Json<serde_json::Value>,
+    telemetry: web::Data<TelemetryStore>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+    let record = record.into_inner();
+
+    let event = record.get("event").and_then(|event| event.as_str()).unwrap_or("");
+    if event.is_empty() || event.len() > MAX_EVENT_NAME_LEN {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid telemetry record",
+            Some(format!("Expected an object with an event name of 1 to {} bytes", MAX_EVENT_NAME_LEN)),
+        )));
+    }
+
+    let event = TelemetryEvent {
+        received_at: Utc::now(),
+        remote_addr: req.peer_addr().map(|addr| addr.ip().to_string()),
+        event: event.to_string(),
+        record,
+    };
+    log::info!("📟 Telemetry from {}: {}", binary_id, event.event);
+    if !telemetry.record(&binary_id, event) {
+        return Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary does not report telemetry",
+            Some(format!("ID: {}", binary_id)),
+        )));
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Health events a merged binary reported, newest first
+/// GET /telemetry/{binary_id}
+#[utoipa::path(
+    get,
+    path = "/telemetry/{binary_id}",
+    tag = "telemetry",
+    params(("binary_id" = String, Path, description = "ID of the merged binary"), ListBinariesQuery),
+    responses(
+        (status = 200, description = "One page of reported events", body = TelemetryListResponse),
+        (status = 404, description = "Binary wasn't merged with phone-home", body = ErrorResponse),
+    )
+)]
+pub async fn list_events(
+    path: web::Path<String>,
+    query: web::Query<ListBinariesQuery>,
+    telemetry: web::Data<TelemetryStore>,
+    binary_store: web::Data<BinaryStore>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let binary_id = path.into_inner();
+    let offset = query.offset.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Events outlive the binary; once it is gone only admins can read them
+    let allowed = match (caller.as_deref(), binary_store.get(&binary_id)) {
+        (None, _) => true,
+        (Some(caller), Some(binary)) => caller.owns(&binary),
+        (Some(caller), None) => caller.is_admin(),
+    };
+    match telemetry.list(&binary_id, offset, limit).filter(|_| allowed) {
+        Some(page) => Ok(HttpResponse::Ok().json(TelemetryListResponse {
+            binary_id,
+            events: page.events,
+            total: page.total,
+            offset,
+            limit,
+        })),
+        None => Ok(HttpResponse::NotFound().json(ErrorResponse::new(
+            ErrorCode::NotFound,
+            "Binary does not report telemetry",
+            Some(format!("ID: {}", binary_id)),
+        ))),
+    }
+}
@@ -0,0 +1,324 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use std::io::{Seek, SeekFrom, Write};
+use uuid::Uuid;
+
+use crate::config::SharedConfig;
+use crate::core::auth::Caller;
+use crate::core::digest;
+use crate::core::diskguard::DiskGuard;
+use crate::core::store::{BinaryStore, UploadError, UploadStore};
+use crate::models::{
+    binary::UploadSession,
+    request::CreateUploadRequest,
+    response::{ErrorCode, ErrorResponse, StageResponse, UploadResponse},
+};
+use super::binaries::{insufficient_storage, store_staged};
+
+/// Request and response header carrying the byte offset of a chunk
+const UPLOAD_OFFSET: &str = "Upload-Offset";
+
+/// Start a resumable upload
+/// POST /uploads
+///
+/// Chunks are then sent with `PATCH /uploads/{id}` and the upload is turned
+/// into a staged binary by `POST /uploads/{id}/complete`. Unfinished uploads
+/// are dropped after `WEAVER_BINARY_TTL` seconds.
+#[utoipa::path(
+    post,
+    path = "/uploads",
+    tag = "binaries",
+    request_body = CreateUploadRequest,
+    responses(
+        (status = 201, description = "Upload created", body = UploadResponse),
+        (status = 400, description = "Invalid size or checksum", body = ErrorResponse),
+        (status = 507, description = "Not enough disk space or storage quota", body = ErrorResponse),
+    )
+)]
+pub async fn create_upload(
+    body: web::Json<CreateUploadRequest>,
+    uploads: web::Data<UploadStore>,
+    binary_store: web::Data<BinaryStore>,
+    disk_guard: web::Data<DiskGuard>,
+    config: web::Data<SharedConfig>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    let body = body.into_inner();
+
+    if body.size == 0 || body.size as usize > config.max_file_size {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Invalid upload size",
+            Some(format!("Size must be 1 to {} bytes", config.max_file_size)),
+        )));
+    }
+    let sha256 = body.sha256.map(|sha256| sha256.to_ascii_lowercase());
+    if let Some(ref sha256) = sha256 {
+        if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+                ErrorCode::InvalidRequest,
+                "Invalid sha256",
+                Some("Expected 64 hex characters".to_string()),
+            )));
+        }
+    }
+
+    // The upload is written to the temp dir and then staged, so check up front
+    if let Err(e) = disk_guard.check(body.size, &binary_store) {
+        return Ok(insufficient_storage(e));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    std::fs::create_dir_all(&config.temp_dir)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    let path = std::path::PathBuf::from(&config.temp_dir).join(format!("upload_{}.part", id));
+    std::fs::File::create(&path)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+
+    let now = Utc::now();
+    let upload = UploadSession {
+        id: id.clone(),
+        path,
+        size: body.size,
+        offset: 0,
+        sha256,
+        created_at: now,
+        expires_at: now + Duration::seconds(config.binary_ttl),
+        busy: false,
+    };
+    let response = UploadResponse::from(&upload);
+    uploads.insert(upload);
+
+    log::info!("📤 Upload {} started ({} bytes)", id, body.size);
+
+    Ok(HttpResponse::Created()
+        .insert_header(("Location", response.upload_url.clone()))
+        .insert_header((UPLOAD_OFFSET, "0"))
+        .json(response))
+}
+
+/// Progress of a resumable upload
+/// GET /uploads/{id}
+///
+/// After a dropped connection, resume by sending the rest from `offset`.
+#[utoipa::path(
+    get,
+    path = "/uploads/{id}",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Upload ID")),
+    responses(
+        (status = 200, description = "Upload state", body = UploadResponse),
+        (status = 404, description = "Unknown or expired upload", body = ErrorResponse),
+    )
+)]
+pub async fn get_upload(
+    path: web::Path<String>,
+    uploads: web::Data<UploadStore>,
+) -> Result<HttpResponse, Error> {
+    let upload_id = path.into_inner();
+
+    match uploads.get(&upload_id) {
+        Some(upload) => Ok(HttpResponse::Ok()
+            .insert_header((UPLOAD_OFFSET, upload.offset.to_string()))
+            .json(UploadResponse::from(&upload))),
+        None => Ok(upload_not_found(&upload_id)),
+    }
+}
+
+/// Append a chunk to a resumable upload
+/// PATCH /uploads/{id}
+///
+/// The raw body is written at the `Upload-Offset` header, which must equal
+/// the bytes received so far.
+#[utoipa::path(
+    patch,
+    path = "/uploads/{id}",
+    tag = "binaries",
+    params(
+        ("id" = String, Path, description = "Upload ID"),
+        ("Upload-Offset" = u64, Header, description = "Offset of this chunk, the upload's current `offset`"),
+    ),
+    request_body(content = Vec<u8>, description = "Chunk bytes", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Chunk stored", body = UploadResponse),
+        (status = 400, description = "Missing offset or chunk past the declared size", body = ErrorResponse),
+        (status = 404, description = "Unknown or expired upload", body = ErrorResponse),
+        (status = 409, description = "Offset doesn't match, or another chunk is in flight", body = ErrorResponse),
+    )
+)]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Bytes,
+    uploads: web::Data<UploadStore>,
+) -> Result<HttpResponse, Error> {
+    let upload_id = path.into_inner();
+
+    let Some(offset) = req
+        .headers()
+        .get(UPLOAD_OFFSET)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+    else {
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::InvalidRequest,
+            "Missing Upload-Offset header",
+            None,
+        )));
+    };
+
+    let upload = match uploads.claim(&upload_id, offset) {
+        Ok(upload) => upload,
+        Err(e) => return Ok(upload_error(&upload_id, e)),
+    };
+    let end = offset + body.len() as u64;
+    if end > upload.size {
+        uploads.release(&upload_id, offset);
+        return Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::PayloadTooLarge,
+            "Chunk exceeds the upload size",
+            Some(format!("Declared size {}, chunk ends at {}", upload.size, end)),
+        )));
+    }
+
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&upload.path)
+        .and_then(|mut file| {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&body)
+        });
+    if let Err(e) = written {
+        uploads.release(&upload_id, offset);
+        log::error!("Failed to write chunk of upload {}: {}", upload_id, e);
+        return Err(actix_web::error::ErrorInternalServerError(e));
+    }
+
+    let upload = uploads.release(&upload_id, end).unwrap_or(UploadSession { offset: end, ..upload });
+    Ok(HttpResponse::Ok()
+        .insert_header((UPLOAD_OFFSET, end.to_string()))
+        .json(UploadResponse::from(&upload)))
+}
+
+/// Turn a fully received upload into a staged binary
+/// POST /uploads/{id}/complete
+///
+/// The result is the same as staging the file with `POST /binaries`; its ID
+/// can be passed as `base_id`/`overload_id` to any merge endpoint.
+#[utoipa::path(
+    post,
+    path = "/uploads/{id}/complete",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Upload ID")),
+    responses(
+        (status = 200, description = "Binary staged (or already staged)", body = StageResponse),
+        (status = 400, description = "Content doesn't match the declared sha256; the upload is discarded", body = ErrorResponse),
+        (status = 404, description = "Unknown or expired upload", body = ErrorResponse),
+        (status = 409, description = "Upload is incomplete or a chunk is in flight", body = ErrorResponse),
+        (status = 507, description = "Not enough disk space or storage quota; the upload is kept", body = ErrorResponse),
+    )
+)]
+pub async fn complete_upload(
+    path: web::Path<String>,
+    uploads: web::Data<UploadStore>,
+    binary_store: web::Data<BinaryStore>,
+    disk_guard: web::Data<DiskGuard>,
+    config: web::Data<SharedConfig>,
+    caller: Option<web::ReqData<Caller>>,
+) -> Result<HttpResponse, Error> {
+    let config = config.load_full();
+    let upload_id = path.into_inner();
+
+    let upload = match uploads.take_complete(&upload_id) {
+        Ok(upload) => upload,
+        Err(e) => return Ok(upload_error(&upload_id, e)),
+    };
+
+    let sha256 = digest::sha256_file(&upload.path);
+    if let Ok(ref sha256) = sha256 {
+        if let Err(e) = disk_guard.check_staging(sha256, upload.size, &binary_store) {
+            // Keep the upload so it can be completed once space frees up
+            uploads.insert(upload);
+            return Ok(insufficient_storage(e));
+        }
+    }
+
+    let staged = sha256.and_then(|sha256| {
+        if upload.sha256.as_ref().is_some_and(|expected| *expected != sha256) {
+            return Ok(Err(sha256));
+        }
+        store_staged(&upload.path, upload.size, sha256, caller.as_deref(), &binary_store, &config).map(Ok)
+    });
+    let _ = std::fs::remove_file(&upload.path);
+
+    match staged {
+        Ok(Ok(staged)) => {
+            log::info!("📥 Upload {} completed as {}", upload_id, staged.binary.id);
+            Ok(HttpResponse::Ok().json(staged))
+        }
+        Ok(Err(actual)) => Ok(HttpResponse::BadRequest().json(ErrorResponse::new(
+            ErrorCode::ChecksumMismatch,
+            "Checksum mismatch",
+            Some(format!("Expected {}, received {}", upload.sha256.unwrap_or_default(), actual)),
+        ))),
+        Err(e) => {
+            log::error!("Failed to stage upload {}: {}", upload_id, e);
+            Err(actix_web::error::ErrorInternalServerError(e))
+        }
+    }
+}
+
+/// Abort a resumable upload
+/// DELETE /uploads/{id}
+#[utoipa::path(
+    delete,
+    path = "/uploads/{id}",
+    tag = "binaries",
+    params(("id" = String, Path, description = "Upload ID")),
+    responses(
+        (status = 204, description = "Upload discarded"),
+        (status = 404, description = "Unknown upload", body = ErrorResponse),
+        (status = 409, description = "A chunk is in flight", body = ErrorResponse),
+    )
+)]
+pub async fn delete_upload(
+    path: web::Path<String>,
+    uploads: web::Data<UploadStore>,
+) -> Result<HttpResponse, Error> {
+    let upload_id = path.into_inner();
+
+    match uploads.discard(&upload_id) {
+        Ok(upload) => {
+            let _ = std::fs::remove_file(&upload.path);
+            log::info!("🗑️  Upload {} discarded", upload_id);
+            Ok(HttpResponse::NoContent().finish())
+        }
+        Err(e) => Ok(upload_error(&upload_id, e)),
+    }
+}
+
+fn upload_not_found(upload_id: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorResponse::new(
+        ErrorCode::NotFound,
+        "Upload not found",
+        Some(format!("ID: {} (unknown, completed or expired)", upload_id)),
+    ))
+}
+
+fn upload_error(upload_id: &str, error: UploadError) -> HttpResponse {
+    match error {
+        UploadError::NotFound => upload_not_found(upload_id),
+        UploadError::Busy => HttpResponse::Conflict().json(ErrorResponse::new(
+            ErrorCode::Conflict,
+            "Another chunk is being written",
+            Some("Retry once it finishes".to_string()),
+        )),
+        UploadError::WrongOffset(offset) => HttpResponse::Conflict()
+            .insert_header((UPLOAD_OFFSET, offset.to_string()))
+            .json(ErrorResponse::new(
+                ErrorCode::Conflict,
+                "Offset mismatch",
+                Some(format!("Upload has {} bytes; continue from there", offset)),
+            )),
+    }
+}
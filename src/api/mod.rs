@@ -1,3 +1,5 @@
+pub mod auth;
+pub mod docs;
 pub mod handlers;
 pub mod routes;
 
@@ -1,13 +1,54 @@
-use actix_web::web;
+use actix_web::middleware::from_fn;
+use actix_web::{web, HttpResponse};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
+use super::auth::require_admin;
+use super::docs::ApiDoc;
 use super::handlers;
 
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg
         .app_data(web::PayloadConfig::default().limit(500_000_000)) // 500MB global payload limit for large overload binaries
         .route("/health", web::get().to(handlers::health::health))
+        .route("/capabilities", web::get().to(handlers::capabilities::capabilities))
+        .route("/selftest", web::post().to(handlers::selftest::selftest))
         .route("/merge", web::post().to(handlers::merge::merge_binaries))
         .route("/merge/stop-on-exit", web::post().to(handlers::merge_stop_on_exit::merge_stop_on_exit))
         .route("/merge/v2/stop-on-exit", web::post().to(handlers::merge_v2::merge_v2_stop_on_exit))
-        .route("/download/{id}", web::get().to(handlers::download::download_binary));
+        .route("/estimate", web::post().to(handlers::estimate::estimate))
+        // Listing shows every caller's binaries
+        .route("/binaries", web::get().to(handlers::binaries::list_binaries).wrap(from_fn(require_admin)))
+        .route("/binaries", web::post().to(handlers::binaries::stage_binary))
+        .route("/binaries/{id}", web::get().to(handlers::binaries::get_binary))
+        .route("/binaries/{id}", web::delete().to(handlers::binaries::delete_binary))
+        .route("/binaries/{id}/provenance", web::get().to(handlers::binaries::get_provenance))
+        .route("/binaries/{id}/extend", web::post().to(handlers::binaries::extend_binary))
+        .route("/uploads", web::post().to(handlers::uploads::create_upload))
+        .route("/uploads/{id}", web::get().to(handlers::uploads::get_upload))
+        .route("/uploads/{id}", web::patch().to(handlers::uploads::upload_chunk))
+        .route("/uploads/{id}", web::delete().to(handlers::uploads::delete_upload))
+        .route("/uploads/{id}/complete", web::post().to(handlers::uploads::complete_upload))
+        // Unauthenticated and keyed by an ID shipped in every merged binary, so records stay small
+        .service(
+            web::resource("/telemetry/{binary_id}")
+                .app_data(web::JsonConfig::default().limit(handlers::telemetry::MAX_RECORD_SIZE))
+                .route(web::post().to(handlers::telemetry::report_event))
+                .route(web::get().to(handlers::telemetry::list_events)),
+        )
+        // Before /download/{id}, which would otherwise match "<id>.sig"
+        .route("/download/{id}.sig", web::get().to(handlers::download::download_signature))
+        .route("/download/by-hash/{sha256}", web::get().to(handlers::download::download_by_hash))
+        .route("/download/{id}", web::get().to(handlers::download::download_binary))
+        .route("/download/{id}/report.html", web::get().to(handlers::download::download_report))
+        .route("/progress/{task_id}", web::get().to(handlers::progress::get_progress))
+        .route("/progress/{task_id}/ws", web::get().to(handlers::progress::progress_ws))
+        .route("/jobs/{task_id}", web::delete().to(handlers::jobs::cancel_job))
+        .route("/admin/stubs/{os}/{arch}", web::put().to(handlers::admin::upload_stub).wrap(from_fn(require_admin)))
+        .route("/admin/reload", web::post().to(handlers::admin::reload_config).wrap(from_fn(require_admin)))
+        .route("/config", web::get().to(handlers::admin::get_config).wrap(from_fn(require_admin)))
+        .route("/docs", web::get().to(|| async {
+            HttpResponse::PermanentRedirect().insert_header(("Location", "/docs/")).finish()
+        }))
+        .service(SwaggerUi::new("/docs/{_:.*}").url("/api-docs/openapi.json", ApiDoc::openapi()));
 }
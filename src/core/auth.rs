@@ -0,0 +1,181 @@
+use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+use crate::models::binary::{BinaryKind, StoredBinary};
+
+/// What an API key may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Merge, stage, and read or delete its own artifacts
+    Merge,
+    /// Everything, including stubs and listing every stored binary
+    Admin,
+}
+
+impl Role {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "merge" => Ok(Role::Merge),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("unknown role '{}', expected merge or admin", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Merge => "merge",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// The key a request was made with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    /// Name the key was configured under; recorded as the owner of its artifacts
+    pub name: String,
+    pub role: Role,
+}
+
+impl Caller {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// Whether the caller may read `binary` or merge with it
+    ///
+    /// A staged binary is only readable by the keys that uploaded it. Its ID is
+    /// its SHA-256, which may well be published, so knowing it proves nothing.
+    pub fn can_read(&self, binary: &StoredBinary) -> bool {
+        self.owns(binary) || (binary.kind == BinaryKind::Staged && binary.stagers.contains(&self.name))
+    }
+
+    /// Whether the caller may delete `binary` or see its provenance and telemetry
+    pub fn owns(&self, binary: &StoredBinary) -> bool {
+        self.is_owner(binary.owner.as_deref())
+    }
+
+    /// Whether the caller may act on something `owner` created, such as a running merge
+    pub fn is_owner(&self, owner: Option<&str>) -> bool {
+        self.is_admin() || owner == Some(self.name.as_str())
+    }
+}
+
+struct ApiKey {
+    caller: Caller,
+    digest: [u8; 32],
+}
+
+/// Keys from `WEAVER_API_KEYS`, plus `WEAVER_ADMIN_TOKEN` as an admin key
+///
+/// With no keys configured authentication is off and every request is
+/// served as before; only the admin API still needs `WEAVER_ADMIN_TOKEN`.
+pub struct ApiKeys {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeys {
+    #[cfg(feature = "server")]
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::parse(&config.api_keys, config.admin_token.as_deref())
+    }
+
+    /// Parse `name:role:key` entries
+    ///
+    /// A malformed entry is an error rather than skipped, so a typo can't
+    /// quietly leave the service open.
+    pub fn parse(entries: &[String], admin_token: Option<&str>) -> Result<Self> {
+        let mut keys = Vec::new();
+        for entry in entries {
+            let mut parts = entry.splitn(3, ':');
+            let (Some(name), Some(role), Some(key)) = (parts.next(), parts.next(), parts.next()) else {
+                bail!("API key entry '{}' isn't name:role:key", entry.split(':').next().unwrap_or(""));
+            };
+            let role = Role::parse(role).map_err(|e| anyhow!("API key '{}': {}", name, e))?;
+            if name.trim().is_empty() || key.is_empty() {
+                bail!("API key entry for '{}' has an empty name or key", name);
+            }
+            keys.push(ApiKey { caller: Caller { name: name.trim().to_string(), role }, digest: Sha256::digest(key.as_bytes()).into() });
+        }
+        if !keys.is_empty() {
+            if let Some(token) = admin_token {
+                let caller = Caller { name: "admin".to_string(), role: Role::Admin };
+                keys.push(ApiKey { caller, digest: Sha256::digest(token.as_bytes()).into() });
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Who `key` belongs to, if anyone
+    pub fn authenticate(&self, key: &str) -> Option<Caller> {
+        // Compare digests so the check doesn't leak how much of a key matched
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        self.keys.iter().find(|known| known.digest == digest).map(|known| known.caller.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(entries: &[&str], admin_token: Option<&str>) -> Result<ApiKeys> {
+        ApiKeys::parse(&entries.iter().map(|entry| entry.to_string()).collect::<Vec<_>>(), admin_token)
+    }
+
+    #[test]
+    fn test_api_key_parsing() {
+        let keys = keys(&["ci:merge:s3cret", "ops:ADMIN:with:colons"], Some("root-token")).unwrap();
+        assert!(keys.is_enabled());
+        assert_eq!(keys.authenticate("s3cret"), Some(Caller { name: "ci".to_string(), role: Role::Merge }));
+        assert_eq!(keys.authenticate("with:colons").map(|caller| caller.role), Some(Role::Admin));
+        assert_eq!(keys.authenticate("root-token").map(|caller| caller.name), Some("admin".to_string()));
+        assert_eq!(keys.authenticate("s3cre"), None);
+
+        assert!(keys(&["ci:owner:s3cret"], None).is_err());
+        assert!(keys(&["ci:merge"], None).is_err());
+        assert!(keys(&["ci:merge:"], None).is_err());
+        // The admin token alone keeps authentication off, as it always has
+        assert!(!keys(&[], Some("root-token")).unwrap().is_enabled());
+    }
+
+    #[test]
+    fn test_staged_binaries_are_read_by_their_stagers() {
+        let caller = |name: &str, role| Caller { name: name.to_string(), role };
+        let (ci, dev, admin) = (caller("ci", Role::Merge), caller("dev", Role::Merge), caller("ops", Role::Admin));
+        let staged = StoredBinary {
+            id: "a".repeat(64),
+            path: "/tmp/staged".to_string(),
+            size: 1,
+            created_at: chrono::Utc::now(),
+            expires_at: chrono::Utc::now(),
+            report_path: None,
+            kind: BinaryKind::Staged,
+            sha256: Some("a".repeat(64)),
+            labels: Default::default(),
+            provenance: None,
+            signature: None,
+            owner: None,
+            scans: Vec::new(),
+            details: None,
+            downloads: 0,
+            last_accessed: None,
+            stagers: vec!["ci".to_string()],
+        };
+
+        assert!(ci.can_read(&staged));
+        assert!(!dev.can_read(&staged));
+        assert!(admin.can_read(&staged));
+        // Only admins delete staged binaries
+        assert!(!ci.owns(&staged));
+    }
+}
@@ -0,0 +1,160 @@
+use goblin::Object;
+use std::fmt;
+
+/// C library an ELF binary is linked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Libc {
+    Glibc,
+    Musl,
+    /// No program interpreter; the binary carries whatever it needs
+    Static,
+}
+
+/// How a 32-bit ARM binary passes floating point values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatAbi {
+    /// Arguments in VFP registers (`gnueabihf`, `musleabihf`)
+    Hard,
+    /// Arguments in core registers (`gnueabi`, `musleabi`)
+    Soft,
+}
+
+/// Calling convention details that arch and OS don't capture
+///
+/// Only known for ELF binaries; `None` means the binary doesn't say, or the
+/// distinction doesn't apply to it (float ABI outside 32-bit ARM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Abi {
+    pub libc: Option<Libc>,
+    pub float: Option<FloatAbi>,
+}
+
+// From the ARM ELF ABI; goblin doesn't define these
+const EF_ARM_ABI_FLOAT_SOFT: u32 = 0x200;
+const EF_ARM_ABI_FLOAT_HARD: u32 = 0x400;
+
+impl Abi {
+    pub const UNKNOWN: Abi = Abi { libc: None, float: None };
+
+    pub fn detect(data: &[u8]) -> Self {
+        let Ok(Object::Elf(elf)) = Object::parse(data) else {
+            return Self::UNKNOWN;
+        };
+        let interpreter = elf.interpreter.unwrap_or("");
+
+        let libc = if elf.interpreter.is_none() {
+            // Relocatable objects have no interpreter either, nor an entry point
+            (elf.entry != 0).then_some(Libc::Static)
+        } else if interpreter.contains("ld-musl") {
+            Some(Libc::Musl)
+        } else if interpreter.contains("ld-linux") {
+            Some(Libc::Glibc)
+        } else {
+            None
+        };
+
+        let float = if elf.header.e_machine != goblin::elf::header::EM_ARM {
+            None
+        } else if elf.header.e_flags & EF_ARM_ABI_FLOAT_HARD != 0 {
+            Some(FloatAbi::Hard)
+        } else if elf.header.e_flags & EF_ARM_ABI_FLOAT_SOFT != 0 {
+            Some(FloatAbi::Soft)
+        } else if interpreter.contains("armhf") {
+            Some(FloatAbi::Hard)
+        } else if interpreter.contains("ld-linux.so.3") || interpreter.contains("ld-musl-arm.so") {
+            Some(FloatAbi::Soft)
+        } else {
+            None
+        };
+
+        Self { libc, float }
+    }
+
+    /// Whether binaries with these ABIs can run on the same system
+    ///
+    /// Static binaries run anywhere their arch and OS do. Dynamic ones need the
+    /// same C library and, on ARM, the same float ABI; whatever isn't known is
+    /// given the benefit of the doubt.
+    pub fn is_compatible_with(&self, other: &Abi) -> bool {
+        fn agree<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+            a.is_none() || b.is_none() || a == b
+        }
+
+        if self.libc == Some(Libc::Static) || other.libc == Some(Libc::Static) {
+            return true;
+        }
+        agree(self.libc, other.libc) && agree(self.float, other.float)
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.libc.is_some() || self.float.is_some()
+    }
+}
+
+impl Libc {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Libc::Glibc => "glibc",
+            Libc::Musl => "musl",
+            Libc::Static => "static",
+        }
+    }
+}
+
+impl FloatAbi {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FloatAbi::Hard => "hard-float",
+            FloatAbi::Soft => "soft-float",
+        }
+    }
+}
+
+impl fmt::Display for Abi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<_> = self.libc.map(|l| l.name()).into_iter().chain(self.float.map(|f| f.name())).collect();
+        if parts.is_empty() {
+            write!(f, "unknown ABI")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIBC_HF: Abi = Abi { libc: Some(Libc::Glibc), float: Some(FloatAbi::Hard) };
+    const GLIBC_SF: Abi = Abi { libc: Some(Libc::Glibc), float: Some(FloatAbi::Soft) };
+    const MUSL_HF: Abi = Abi { libc: Some(Libc::Musl), float: Some(FloatAbi::Hard) };
+    const STATIC_HF: Abi = Abi { libc: Some(Libc::Static), float: Some(FloatAbi::Hard) };
+    const STATIC_SF: Abi = Abi { libc: Some(Libc::Static), float: Some(FloatAbi::Soft) };
+
+    #[test]
+    fn test_abi_compatibility() {
+        assert!(GLIBC_HF.is_compatible_with(&GLIBC_HF));
+        assert!(!GLIBC_HF.is_compatible_with(&GLIBC_SF), "hard- and soft-float can't share a system");
+        assert!(!GLIBC_HF.is_compatible_with(&MUSL_HF), "glibc and musl can't share a system");
+        assert!(GLIBC_HF.is_compatible_with(&STATIC_SF), "static binaries run anywhere");
+        assert!(MUSL_HF.is_compatible_with(&Abi::UNKNOWN));
+        assert!(MUSL_HF.is_compatible_with(&Abi { libc: Some(Libc::Musl), float: None }));
+    }
+
+    #[test]
+    fn test_abi_detection() {
+        assert_eq!(Abi::detect(b"not a binary"), Abi::UNKNOWN);
+
+        // Test binaries are linked statically, so only the float ABI tells them apart
+        for (compiler, expected) in [("arm-linux-gnueabihf-gcc", STATIC_HF), ("arm-linux-gnueabi-gcc", STATIC_SF)] {
+            let binary = match crate::test_utils::build_real_test_binary(compiler) {
+                Ok(data) => data,
+                Err(e) => {
+                    println!("⚠️  Skipping {} - failed to build binary: {}", compiler, e);
+                    continue;
+                }
+            };
+            assert_eq!(Abi::detect(&binary), expected, "{}", compiler);
+        }
+    }
+}
@@ -1,6 +1,8 @@
+pub mod abi;
 pub mod arch;
 pub mod os;
 
+use abi::Abi;
 use arch::Architecture;
 use os::OperatingSystem;
 use std::fmt;
@@ -9,17 +11,30 @@ use std::fmt;
 pub struct BinaryInfo {
     pub arch: Architecture,
     pub os: OperatingSystem,
+    pub abi: Abi,
 }
 
 impl BinaryInfo {
+    /// A platform with no particular ABI, compatible with any binary for it
+    pub const fn new(os: OperatingSystem, arch: Architecture) -> Self {
+        Self { arch, os, abi: Abi::UNKNOWN }
+    }
+
+    #[tracing::instrument(name = "detect", skip_all, fields(size = data.len()))]
     pub fn detect(data: &[u8]) -> Self {
         Self {
             arch: Architecture::detect(data),
             os: OperatingSystem::detect(data),
+            abi: Abi::detect(data),
         }
     }
 
     pub fn is_compatible_with(&self, other: &BinaryInfo) -> bool {
+        self.is_same_platform(other) && self.abi.is_compatible_with(&other.abi)
+    }
+
+    /// Same arch and OS, whatever the ABI
+    pub fn is_same_platform(&self, other: &BinaryInfo) -> bool {
         self.arch == other.arch && self.os == other.os
     }
 
@@ -28,7 +43,11 @@ impl BinaryInfo {
     }
 
     pub fn description(&self) -> String {
-        format!("{} on {}", self.arch.name(), self.os.name())
+        if self.abi.is_known() {
+            format!("{} on {} ({})", self.arch.name(), self.os.name(), self.abi)
+        } else {
+            format!("{} on {}", self.arch.name(), self.os.name())
+        }
     }
 }
 
@@ -62,22 +81,24 @@ mod tests {
 
     #[test]
     fn test_compatibility_check() {
-        let info1 = BinaryInfo {
-            arch: Architecture::X86_64,
-            os: OperatingSystem::Linux,
-        };
-        
-        let info2 = BinaryInfo {
-            arch: Architecture::X86_64,
-            os: OperatingSystem::Linux,
-        };
-        
-        let info3 = BinaryInfo {
-            arch: Architecture::ARM,
-            os: OperatingSystem::Linux,
-        };
+        let info1 = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
+        let info2 = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
+        let info3 = BinaryInfo::new(OperatingSystem::Linux, Architecture::ARM);
         
         assert!(info1.is_compatible_with(&info2), "Same arch/OS should be compatible");
         assert!(!info1.is_compatible_with(&info3), "Different arch should not be compatible");
+
+        let hard_float = BinaryInfo {
+            abi: Abi { libc: Some(abi::Libc::Glibc), float: Some(abi::FloatAbi::Hard) },
+            ..info3
+        };
+        let soft_float = BinaryInfo {
+            abi: Abi { libc: Some(abi::Libc::Glibc), float: Some(abi::FloatAbi::Soft) },
+            ..info3
+        };
+        assert!(hard_float.is_same_platform(&soft_float));
+        assert!(!hard_float.is_compatible_with(&soft_float), "Different float ABIs should not be compatible");
+        assert!(hard_float.is_compatible_with(&info3), "An unknown ABI should be compatible");
+        assert_eq!(soft_float.description(), "ARM (32-bit) on Linux (glibc, soft-float)");
     }
 }
@@ -1,3 +1,7 @@
 pub mod detector;
+pub mod script;
+pub mod validate;
 
-pub use detector::{arch::Architecture, os::OperatingSystem, BinaryInfo};
+pub use detector::{abi::{Abi, FloatAbi, Libc}, arch::Architecture, os::OperatingSystem, BinaryInfo};
+pub use script::{is_script, InvalidShebang, Shebang};
+pub use validate::{is_dynamically_linked, validate_executable, validate_library, validate_overload, InvalidExecutable, OverloadKind};
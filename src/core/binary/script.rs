@@ -0,0 +1,113 @@
+use std::fmt;
+
+/// Longest `#!` line the Linux kernel reads, newline included
+pub const MAX_SHEBANG_LEN: usize = 256;
+
+/// The `#!` line of a script payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shebang {
+    /// Absolute path of the interpreter
+    pub interpreter: String,
+    /// Everything after the interpreter, passed to it as one argument
+    pub argument: Option<String>,
+}
+
+/// Why a file starting with `#!` can't be run as a script
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidShebang {
+    /// No newline within `MAX_SHEBANG_LEN` bytes
+    TooLong,
+    NotUtf8,
+    NoInterpreter,
+    /// The kernel doesn't search `PATH`; `#!/usr/bin/env python3` is the usual fix
+    RelativeInterpreter(String),
+}
+
+impl fmt::Display for InvalidShebang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidShebang::TooLong => write!(f, "#! line is longer than {} bytes", MAX_SHEBANG_LEN),
+            InvalidShebang::NotUtf8 => write!(f, "#! line is not valid UTF-8"),
+            InvalidShebang::NoInterpreter => write!(f, "#! line names no interpreter"),
+            InvalidShebang::RelativeInterpreter(path) => {
+                write!(f, "#! interpreter {} is not an absolute path", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidShebang {}
+
+impl Shebang {
+    /// `None` if `data` isn't a script at all
+    pub fn parse(data: &[u8]) -> Option<Result<Shebang, InvalidShebang>> {
+        let line = data.strip_prefix(b"#!")?;
+        Some(Self::parse_line(line))
+    }
+
+    fn parse_line(line: &[u8]) -> Result<Shebang, InvalidShebang> {
+        let end = match line.iter().position(|&b| b == b'\n') {
+            Some(end) if end + 3 <= MAX_SHEBANG_LEN => end,
+            None if line.len() + 2 <= MAX_SHEBANG_LEN => line.len(),
+            _ => return Err(InvalidShebang::TooLong),
+        };
+        let line = std::str::from_utf8(&line[..end]).map_err(|_| InvalidShebang::NotUtf8)?;
+        let line = line.trim_end_matches('\r').trim();
+
+        let (interpreter, argument) = match line.split_once([' ', '\t']) {
+            Some((interpreter, argument)) => (interpreter, Some(argument.trim())),
+            None => (line, None),
+        };
+        if interpreter.is_empty() {
+            return Err(InvalidShebang::NoInterpreter);
+        }
+        if !interpreter.starts_with('/') {
+            return Err(InvalidShebang::RelativeInterpreter(interpreter.to_string()));
+        }
+        Ok(Shebang {
+            interpreter: interpreter.to_string(),
+            argument: argument.filter(|argument| !argument.is_empty()).map(str::to_string),
+        })
+    }
+}
+
+/// Whether `data` is a `#!` script rather than an executable
+pub fn is_script(data: &[u8]) -> bool {
+    data.starts_with(b"#!")
+}
+
+impl fmt::Display for Shebang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.argument {
+            Some(argument) => write!(f, "#!{} {}", self.interpreter, argument),
+            None => write!(f, "#!{}", self.interpreter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shebang_parsing() {
+        assert_eq!(Shebang::parse(b"\x7fELF"), None);
+        assert_eq!(
+            Shebang::parse(b"#!/bin/sh\necho hi\n"),
+            Some(Ok(Shebang { interpreter: "/bin/sh".into(), argument: None }))
+        );
+        assert_eq!(
+            Shebang::parse(b"#! /usr/bin/env  python3 -u\r\nprint()\n"),
+            Some(Ok(Shebang { interpreter: "/usr/bin/env".into(), argument: Some("python3 -u".into()) }))
+        );
+        assert_eq!(Shebang::parse(b"#!\n"), Some(Err(InvalidShebang::NoInterpreter)));
+        assert_eq!(Shebang::parse(b"#!python3\n"), Some(Err(InvalidShebang::RelativeInterpreter("python3".into()))));
+
+        let mut long = b"#!/bin/".to_vec();
+        long.resize(MAX_SHEBANG_LEN + 1, b'x');
+        assert_eq!(Shebang::parse(&long), Some(Err(InvalidShebang::TooLong)));
+        long.truncate(MAX_SHEBANG_LEN - 1);
+        long.push(b'\n');
+        assert!(Shebang::parse(&long).unwrap().is_ok());
+    }
+}
@@ -0,0 +1,317 @@
+//! Removing debug info and symbol tables from payloads (`strip_payloads`)
+//!
+//! ELF files are rewritten here: the non-allocated `.debug*`/`.zdebug*`
+//! sections and the static symbol table (`.symtab` and its string table) are
+//! dropped, while everything the loader maps stays byte for byte where it
+//! was. Mach-O and PE files go through `llvm-strip --strip-debug` when it is
+//! installed. Scripts are left alone, and a payload that can't be stripped is
+//! merged as it is.
+
+use std::io::Write;
+use std::path::Path;
+
+use super::is_script;
+use crate::core::jobs::CancelToken;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_SYMTAB: u32 = 2;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_INFO_LINK: u64 = 0x40;
+const SHN_LORESERVE: u16 = 0xff00;
+
+/// Strip `data` for `strip_payloads`, `None` when it stays as it is
+///
+/// Failures are logged rather than returned; the payload is then merged
+/// unstripped. `llvm-strip` is killed when `cancel` fires.
+pub async fn strip_payload(name: &str, data: &[u8], temp_dir: &Path, cancel: &CancelToken) -> Option<Vec<u8>> {
+    let stripped = if data.starts_with(ELF_MAGIC) {
+        strip_elf(data)
+    } else if is_script(data) {
+        Ok(None)
+    } else {
+        llvm_strip(data, temp_dir, cancel).await
+    };
+    match stripped {
+        Ok(Some(stripped)) => {
+            log::info!("✂️  Stripped {}: {} -> {} bytes", name, data.len(), stripped.len());
+            Some(stripped)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            log::warn!("⚠️  Not stripping {}: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Run `llvm-strip --strip-debug` on a Mach-O or PE payload
+async fn llvm_strip(data: &[u8], temp_dir: &Path, cancel: &CancelToken) -> Result<Option<Vec<u8>>, String> {
+    let mut input = tempfile::NamedTempFile::new_in(temp_dir).map_err(|e| e.to_string())?;
+    input.write_all(data).map_err(|e| e.to_string())?;
+    let output = tempfile::NamedTempFile::new_in(temp_dir).map_err(|e| e.to_string())?;
+    let run = tokio::process::Command::new("llvm-strip")
+        .arg("--strip-debug")
+        .arg(input.path())
+        .arg("-o")
+        .arg(output.path())
+        .kill_on_drop(true)
+        .output();
+    let result = cancel.run_until_cancelled(run).await.map_err(|cancelled| cancelled.to_string())?;
+    match result {
+        Ok(result) if result.status.success() => {}
+        Ok(result) => return Err(String::from_utf8_lossy(&result.stderr).trim().to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err("llvm-strip isn't installed; only ELF payloads are stripped without it".to_string());
+        }
+        Err(e) => return Err(format!("can't run llvm-strip: {}", e)),
+    }
+    let stripped = std::fs::read(output.path()).map_err(|e| e.to_string())?;
+    Ok((stripped.len() < data.len()).then_some(stripped))
+}
+
+/// One section header of the input, as far as stripping cares
+struct Section {
+    /// Where its header starts in the input
+    header: usize,
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+impl Section {
+    fn file_end(&self) -> u64 {
+        if self.kind == SHT_NOBITS { self.offset } else { self.offset + self.size }
+    }
+
+    fn info_is_section(&self) -> bool {
+        self.flags & SHF_INFO_LINK != 0 || self.kind == SHT_REL || self.kind == SHT_RELA
+    }
+}
+
+/// Field reader and writer for one ELF class and byte order
+#[derive(Clone, Copy)]
+struct Elf {
+    is_64: bool,
+    little_endian: bool,
+}
+
+impl Elf {
+    fn read(self, data: &[u8], at: usize, width: usize) -> Result<u64, String> {
+        let bytes = data.get(at..at + width).ok_or("truncated ELF file")?;
+        let mut value = 0u64;
+        for i in 0..width {
+            let byte = if self.little_endian { bytes[width - 1 - i] } else { bytes[i] };
+            value = value << 8 | byte as u64;
+        }
+        Ok(value)
+    }
+
+    fn write(self, data: &mut [u8], at: usize, width: usize, value: u64) {
+        for i in 0..width {
+            let byte = (value >> (8 * i)) as u8;
+            data[at + if self.little_endian { i } else { width - 1 - i }] = byte;
+        }
+    }
+
+    /// Width of an address-sized field
+    fn word(self) -> usize {
+        if self.is_64 { 8 } else { 4 }
+    }
+
+    fn section(self, data: &[u8], header: usize) -> Result<Section, String> {
+        let w = self.word();
+        let field = |at: usize, width: usize| self.read(data, header + at, width);
+        // name, type and flags start both layouts; the rest shifts with the word size
+        Ok(Section {
+            header,
+            name: field(0, 4)? as u32,
+            kind: field(4, 4)? as u32,
+            flags: field(8, w)?,
+            offset: field(8 + 2 * w, w)?,
+            size: field(8 + 3 * w, w)?,
+            link: field(8 + 4 * w, 4)? as u32,
+            info: field(12 + 4 * w, 4)? as u32,
+            align: field(16 + 4 * w, w)?,
+        })
+    }
+}
+
+/// Drop the debug sections and static symbol table from an ELF file
+///
+/// Returns `None` when there is nothing to drop, and an error for layouts
+/// this can't rewrite safely (data appended after the image, or a kept
+/// section that refers to a dropped one).
+pub fn strip_elf(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    if !data.starts_with(ELF_MAGIC) || data.len() < 0x34 {
+        return Err("not an ELF file".to_string());
+    }
+    let elf = Elf { is_64: data[4] == 2, little_endian: data[5] == 1 };
+    let w = elf.word();
+    // e_phoff, e_shoff, then e_flags and the 16-bit fields
+    let (phoff_at, shoff_at, sizes_at) = if elf.is_64 { (0x20, 0x28, 0x34) } else { (0x1c, 0x20, 0x28) };
+    let phoff = elf.read(data, phoff_at, w)?;
+    let shoff = elf.read(data, shoff_at, w)?;
+    let ehsize = elf.read(data, sizes_at, 2)?;
+    let phentsize = elf.read(data, sizes_at + 2, 2)?;
+    let phnum = elf.read(data, sizes_at + 4, 2)?;
+    let shentsize = elf.read(data, sizes_at + 6, 2)? as usize;
+    let shnum = elf.read(data, sizes_at + 8, 2)? as usize;
+    let shstrndx = elf.read(data, sizes_at + 10, 2)? as u16;
+
+    if shoff == 0 {
+        return Ok(None);
+    }
+    if shnum == 0 || shstrndx >= SHN_LORESERVE {
+        return Err("extended section numbering isn't supported".to_string());
+    }
+    if shentsize != if elf.is_64 { 64 } else { 40 } {
+        return Err(format!("unexpected section header size {}", shentsize));
+    }
+    let sections = (0..shnum)
+        .map(|i| elf.section(data, shoff as usize + i * shentsize))
+        .collect::<Result<Vec<_>, _>>()?;
+    let shstrtab = sections.get(shstrndx as usize).ok_or("section name table is missing")?;
+    let name = |section: &Section| -> &str {
+        let start = (shstrtab.offset + section.name as u64) as usize;
+        let bytes = data.get(start..).unwrap_or_default();
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(0);
+        std::str::from_utf8(&bytes[..end]).unwrap_or("")
+    };
+
+    // Segments, as the loader sees them
+    let mut segments_end = ehsize.max(phoff + phnum * phentsize);
+    for i in 0..phnum {
+        let header = (phoff + i * phentsize) as usize;
+        let (offset, filesz) = if elf.is_64 {
+            (elf.read(data, header + 8, 8)?, elf.read(data, header + 0x20, 8)?)
+        } else {
+            (elf.read(data, header + 4, 4)?, elf.read(data, header + 0x10, 4)?)
+        };
+        segments_end = segments_end.max(offset + filesz);
+    }
+    let image_end = sections.iter().map(Section::file_end).fold(segments_end, u64::max).max(shoff + (shnum * shentsize) as u64);
+    if (data.len() as u64) > image_end {
+        return Err(format!("{} bytes follow the ELF image", data.len() as u64 - image_end));
+    }
+
+    let mut removed: Vec<bool> = sections
+        .iter()
+        .enumerate()
+        .map(|(i, section)| {
+            let name = name(section);
+            i > 0
+                && section.flags & SHF_ALLOC == 0
+                && (section.kind == SHT_SYMTAB || name.starts_with(".debug") || name.starts_with(".zdebug"))
+        })
+        .collect();
+    // A symbol table's strings go with it unless something else still uses them
+    for i in 0..shnum {
+        let strings = sections[i].link as usize;
+        if !removed[i] || sections[i].kind != SHT_SYMTAB || strings == shstrndx as usize || strings >= shnum {
+            continue;
+        }
+        let shared = (0..shnum).any(|j| !removed[j] && j != strings && sections[j].link as usize == strings);
+        if !shared && sections[strings].flags & SHF_ALLOC == 0 {
+            removed[strings] = true;
+        }
+    }
+    if !removed.contains(&true) {
+        return Ok(None);
+    }
+    // The loader never reads section headers, so a loaded section only loses
+    // the link (a static binary's `.rela.plt` names `.symtab`, for one)
+    let refers = |index: u32| removed.get(index as usize).copied().unwrap_or(false);
+    for (i, section) in sections.iter().enumerate().filter(|(i, _)| !removed[*i]) {
+        let dangling = refers(section.link) || (section.info_is_section() && refers(section.info));
+        if dangling && section.flags & SHF_ALLOC == 0 {
+            return Err(format!("section {} ({}) refers to a stripped section", i, name(section)));
+        }
+    }
+
+    // What the loader maps stays in place; kept sections behind it move up
+    let keep_end = sections
+        .iter()
+        .enumerate()
+        .filter(|(i, section)| !removed[*i] && section.flags & SHF_ALLOC != 0)
+        .map(|(_, section)| section.file_end())
+        .fold(segments_end, u64::max);
+    let mut out = data[..keep_end as usize].to_vec();
+    let mut new_index = vec![0u32; shnum];
+    let mut kept = Vec::new();
+    for (i, section) in sections.iter().enumerate().filter(|(i, _)| !removed[*i]) {
+        let mut offset = section.offset;
+        if i > 0 && section.kind != SHT_NOBITS && section.size > 0 && section.offset >= keep_end {
+            let align = section.align.max(1) as usize;
+            out.resize(out.len().div_ceil(align) * align, 0);
+            offset = out.len() as u64;
+            out.extend_from_slice(&data[section.offset as usize..section.file_end() as usize]);
+        } else if i > 0 && section.file_end() > keep_end && section.kind != SHT_NOBITS {
+            return Err(format!("section {} ({}) straddles the end of the loaded image", i, name(section)));
+        }
+        new_index[i] = kept.len() as u32;
+        kept.push((section, offset));
+    }
+
+    out.resize(out.len().div_ceil(w) * w, 0);
+    let new_shoff = out.len();
+    let remap = |index: u32| if refers(index) { 0 } else { new_index.get(index as usize).copied().unwrap_or(index) };
+    for (section, offset) in &kept {
+        let at = out.len();
+        out.extend_from_slice(&data[section.header..section.header + shentsize]);
+        elf.write(&mut out, at + 8 + 2 * w, w, *offset);
+        elf.write(&mut out, at + 8 + 4 * w, 4, remap(section.link) as u64);
+        if section.info_is_section() {
+            elf.write(&mut out, at + 12 + 4 * w, 4, remap(section.info) as u64);
+        }
+    }
+    elf.write(&mut out, shoff_at, w, new_shoff as u64);
+    elf.write(&mut out, sizes_at + 8, 2, kept.len() as u64);
+    elf.write(&mut out, sizes_at + 10, 2, remap(shstrndx as u32) as u64);
+
+    Ok((out.len() < data.len()).then_some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::build_real_test_binary;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_stripped_elf_still_runs() {
+        let binary = match build_real_test_binary("gcc") {
+            Ok(binary) => binary,
+            Err(e) => {
+                eprintln!("Skipping: {}", e);
+                return;
+            }
+        };
+        let stripped = strip_elf(&binary).unwrap().expect("a static binary has a symbol table");
+        assert!(stripped.len() < binary.len());
+        // Nothing is left to strip the second time
+        assert_eq!(strip_elf(&stripped), Ok(None));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stripped");
+        std::fs::write(&path, &stripped).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let output = std::process::Command::new(&path).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "Test\n");
+    }
+
+    #[test]
+    fn test_elf_with_appended_data_is_refused() {
+        let Ok(mut binary) = build_real_test_binary("gcc") else { return };
+        binary.extend_from_slice(b"payload");
+        assert!(strip_elf(&binary).unwrap_err().contains("7 bytes follow"));
+        assert!(strip_elf(b"#!/bin/sh\n").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use goblin::elf::header::{ET_CORE, ET_DYN, ET_EXEC, ET_REL};
+use goblin::mach::{
+    header::{MH_DYLIB, MH_EXECUTE},
+    Mach,
+};
+use goblin::Object;
+use std::fmt;
+
+use super::script::{InvalidShebang, Shebang};
+
+/// Smaller than the headers of any format the stubs can load
+pub const MIN_EXECUTABLE_SIZE: usize = 64;
+
+/// Why a payload can't be merged
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidExecutable {
+    TooSmall { size: usize },
+    /// Not ELF, PE or Mach-O, e.g. a text file or an archive
+    UnknownFormat,
+    /// Looks like an executable format but its headers don't parse
+    Corrupt(String),
+    /// A valid file of the right format that can't be run on its own
+    NotExecutable(&'static str),
+    NoEntryPoint,
+    /// Starts with `#!` but the line can't be run
+    BadShebang(InvalidShebang),
+    /// A library overload that is some other kind of file
+    NotLibrary(&'static str),
+    /// A base without a dynamic loader, which can't take a library overload
+    StaticallyLinked,
+}
+
+/// What an overload that passed `validate_overload` is
+#[derive(Debug, Clone, PartialEq)]
+pub enum OverloadKind {
+    Executable,
+    Script(Shebang),
+    Library,
+}
+
+impl fmt::Display for InvalidExecutable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidExecutable::TooSmall { size } => {
+                write!(f, "only {} bytes; an executable is at least {}", size, MIN_EXECUTABLE_SIZE)
+            }
+            InvalidExecutable::UnknownFormat => write!(f, "not an ELF, PE or Mach-O executable"),
+            InvalidExecutable::Corrupt(reason) => write!(f, "corrupt or truncated headers: {}", reason),
+            InvalidExecutable::NotExecutable(kind) => write!(f, "{}, not an executable", kind),
+            InvalidExecutable::NoEntryPoint => write!(f, "has no entry point"),
+            InvalidExecutable::BadShebang(reason) => write!(f, "{}", reason),
+            InvalidExecutable::NotLibrary(kind) => write!(f, "{}, not a shared library", kind),
+            InvalidExecutable::StaticallyLinked => {
+                write!(f, "statically linked, so no library can be loaded into it")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidExecutable {}
+
+/// Check that `data` is a complete, runnable ELF, PE or Mach-O executable
+///
+/// Run on merge inputs before any work is done, so a text file or a broken
+/// upload is refused up front instead of producing a binary that can't start.
+pub fn validate_executable(data: &[u8]) -> Result<(), InvalidExecutable> {
+    if data.len() < MIN_EXECUTABLE_SIZE {
+        return Err(InvalidExecutable::TooSmall { size: data.len() });
+    }
+
+    match Object::parse(data) {
+        Ok(Object::Elf(elf)) => {
+            match elf.header.e_type {
+                ET_EXEC | ET_DYN => {}
+                ET_REL => return Err(InvalidExecutable::NotExecutable("relocatable object file")),
+                ET_CORE => return Err(InvalidExecutable::NotExecutable("core dump")),
+                _ => return Err(InvalidExecutable::NotExecutable("ELF file of unknown type")),
+            }
+            if elf.entry == 0 {
+                return Err(InvalidExecutable::NoEntryPoint);
+            }
+        }
+        Ok(Object::PE(pe)) => {
+            if pe.is_lib {
+                return Err(InvalidExecutable::NotExecutable("DLL"));
+            }
+            if pe.entry == 0 {
+                return Err(InvalidExecutable::NoEntryPoint);
+            }
+        }
+        Ok(Object::Mach(Mach::Binary(macho))) => {
+            if macho.header.filetype != MH_EXECUTE {
+                return Err(InvalidExecutable::NotExecutable("Mach-O file that isn't MH_EXECUTE"));
+            }
+            if macho.entry == 0 {
+                return Err(InvalidExecutable::NoEntryPoint);
+            }
+        }
+        Ok(Object::Mach(Mach::Fat(_))) => {
+            return Err(InvalidExecutable::NotExecutable("universal Mach-O; upload a single-architecture slice"));
+        }
+        Ok(Object::Archive(_)) => return Err(InvalidExecutable::NotExecutable("static library archive")),
+        Ok(_) => return Err(InvalidExecutable::UnknownFormat),
+        // goblin recognized the magic but the rest doesn't hold together
+        Err(e) if has_executable_magic(data) => return Err(InvalidExecutable::Corrupt(e.to_string())),
+        Err(_) => return Err(InvalidExecutable::UnknownFormat),
+    }
+    Ok(())
+}
+
+/// Check an overload: a shared library when `library` is set, otherwise an
+/// executable or a `#!` script
+pub fn validate_overload(data: &[u8], library: bool) -> Result<OverloadKind, InvalidExecutable> {
+    if library {
+        return validate_library(data).map(|()| OverloadKind::Library);
+    }
+    match Shebang::parse(data) {
+        Some(shebang) => shebang.map(OverloadKind::Script).map_err(InvalidExecutable::BadShebang),
+        None => validate_executable(data).map(|()| OverloadKind::Executable),
+    }
+}
+
+/// Check that `data` is an ELF shared object, a DLL or a Mach-O dylib
+pub fn validate_library(data: &[u8]) -> Result<(), InvalidExecutable> {
+    if data.len() < MIN_EXECUTABLE_SIZE {
+        return Err(InvalidExecutable::TooSmall { size: data.len() });
+    }
+
+    match Object::parse(data) {
+        // PIE executables are ET_DYN too, but they have an interpreter and no soname
+        Ok(Object::Elf(elf)) if elf.header.e_type == ET_DYN && (elf.interpreter.is_none() || elf.soname.is_some()) => Ok(()),
+        Ok(Object::Elf(_)) => Err(InvalidExecutable::NotLibrary("ELF executable or object file")),
+        Ok(Object::PE(pe)) if pe.is_lib => Ok(()),
+        Ok(Object::PE(_)) => Err(InvalidExecutable::NotLibrary("PE executable")),
+        Ok(Object::Mach(Mach::Binary(macho))) if macho.header.filetype == MH_DYLIB => Ok(()),
+        Ok(Object::Mach(Mach::Binary(_))) => Err(InvalidExecutable::NotLibrary("Mach-O file that isn't MH_DYLIB")),
+        Ok(Object::Mach(Mach::Fat(_))) => {
+            Err(InvalidExecutable::NotLibrary("universal Mach-O; upload a single-architecture slice"))
+        }
+        Ok(Object::Archive(_)) => Err(InvalidExecutable::NotLibrary("static library archive")),
+        Ok(_) => Err(InvalidExecutable::UnknownFormat),
+        Err(e) if has_executable_magic(data) => Err(InvalidExecutable::Corrupt(e.to_string())),
+        Err(_) => Err(InvalidExecutable::UnknownFormat),
+    }
+}
+
+/// Whether a validated executable starts through a dynamic loader that can
+/// load a library overload into it
+///
+/// PE and Mach-O executables always do; an ELF one needs a `PT_INTERP`.
+pub fn is_dynamically_linked(data: &[u8]) -> bool {
+    match Object::parse(data) {
+        Ok(Object::Elf(elf)) => elf.interpreter.is_some(),
+        _ => true,
+    }
+}
+
+fn has_executable_magic(data: &[u8]) -> bool {
+    const MACHO_MAGICS: [[u8; 4]; 4] =
+        [[0xfe, 0xed, 0xfa, 0xce], [0xfe, 0xed, 0xfa, 0xcf], [0xce, 0xfa, 0xed, 0xfe], [0xcf, 0xfa, 0xed, 0xfe]];
+    data.starts_with(b"\x7fELF") || data.starts_with(b"MZ") || MACHO_MAGICS.iter().any(|magic| data.starts_with(magic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::binary::Architecture;
+    use crate::core::selftest::payload;
+
+    #[test]
+    fn test_rejects_non_executables() {
+        assert_eq!(validate_executable(b"#!/bin/sh"), Err(InvalidExecutable::TooSmall { size: 9 }));
+        assert_eq!(validate_executable(&[b'x'; 256]), Err(InvalidExecutable::UnknownFormat));
+
+        let elf = payload(Architecture::X86_64, b"hello\n").unwrap();
+        assert_eq!(validate_executable(&elf), Ok(()));
+        assert!(matches!(validate_executable(&elf[..MIN_EXECUTABLE_SIZE]), Err(InvalidExecutable::Corrupt(_))));
+
+        let mut no_entry = elf.clone();
+        no_entry[24..32].fill(0); // e_entry
+        assert_eq!(validate_executable(&no_entry), Err(InvalidExecutable::NoEntryPoint));
+
+        let mut object = elf;
+        object[16..18].copy_from_slice(&ET_REL.to_le_bytes()); // e_type
+        assert_eq!(
+            validate_executable(&object),
+            Err(InvalidExecutable::NotExecutable("relocatable object file"))
+        );
+    }
+
+    #[test]
+    fn test_overload_may_be_a_script() {
+        let Ok(OverloadKind::Script(shebang)) = validate_overload(b"#!/bin/sh\nexit 0\n", false) else {
+            panic!("expected a script");
+        };
+        assert_eq!(shebang.interpreter, "/bin/sh");
+        assert_eq!(
+            validate_overload(b"#!sh\n", false),
+            Err(InvalidExecutable::BadShebang(InvalidShebang::RelativeInterpreter("sh".into())))
+        );
+        assert_eq!(validate_overload(&[b'x'; 256], false), Err(InvalidExecutable::UnknownFormat));
+        let elf = payload(Architecture::X86_64, b"hello\n").unwrap();
+        assert_eq!(validate_overload(&elf, false), Ok(OverloadKind::Executable));
+    }
+
+    #[test]
+    fn test_library_overload() {
+        let elf = payload(Architecture::X86_64, b"hello\n").unwrap();
+        assert!(!is_dynamically_linked(&elf));
+        assert_eq!(
+            validate_overload(&elf, true),
+            Err(InvalidExecutable::NotLibrary("ELF executable or object file"))
+        );
+        assert!(validate_overload(b"#!/bin/sh\n", true).is_err());
+
+        // A shared object without an interpreter
+        let mut shared = elf;
+        shared[16..18].copy_from_slice(&ET_DYN.to_le_bytes()); // e_type
+        assert_eq!(validate_overload(&shared, true), Ok(OverloadKind::Library));
+    }
+}
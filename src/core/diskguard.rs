@@ -0,0 +1,221 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+use crate::core::binary::BinaryInfo;
+use crate::core::merger::StubRegistry;
+use crate::core::store::BinaryStore;
+
+/// Checks free space and the stored-binary quota before anything is written
+///
+/// Merges reserve their projected output while they run, so concurrent
+/// merges can't each see the same free space and overcommit the disk.
+pub struct DiskGuard {
+    temp_dir: String,
+    /// Most bytes of stored binaries, 0 for no limit
+    quota: AtomicU64,
+    /// Projected output of merges still running
+    reserved: Mutex<u64>,
+}
+
+/// Space held for a running merge, released when dropped
+pub struct SpaceReservation<'a> {
+    guard: &'a DiskGuard,
+    bytes: u64,
+}
+
+/// Why there is no room for a new binary
+#[derive(Debug, PartialEq)]
+pub enum SpaceError {
+    /// The temp dir's filesystem doesn't have `required` bytes free
+    DiskFull { free: u64, required: u64 },
+    /// Storing `required` more bytes would exceed `WEAVER_STORAGE_QUOTA`
+    QuotaExceeded { used: u64, quota: u64, required: u64 },
+}
+
+impl fmt::Display for SpaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpaceError::DiskFull { free, required } => {
+                write!(f, "{} bytes needed but only {} free in the temp dir", required, free)
+            }
+            SpaceError::QuotaExceeded { used, quota, required } => {
+                write!(f, "storing {} more bytes would exceed the quota ({} of {} used)", required, used, quota)
+            }
+        }
+    }
+}
+
+/// Rough size of a merged artifact: the platform's stub plus both payloads
+pub fn projected_output_size(stubs: &StubRegistry, base_data: &[u8], overload_data: &[u8]) -> u64 {
+    let stub_size = stubs.select(&BinaryInfo::detect(base_data), None).map(|stub| stub.bytes.len()).unwrap_or(0);
+    (stub_size + base_data.len() + overload_data.len()) as u64
+}
+
+/// Rough size of `data` once compressed for download
+///
+/// Sums the order-0 entropy of 64KB blocks, which is what a compressor gets
+/// from skewed byte frequencies alone; repeated code and tables usually do
+/// better, so this errs towards too large.
+pub fn estimated_compressed_size(data: &[u8]) -> u64 {
+    const BLOCK: usize = 64 * 1024;
+    let bits: f64 = data
+        .chunks(BLOCK)
+        .map(|block| {
+            let mut counts = [0u32; 256];
+            for &byte in block {
+                counts[byte as usize] += 1;
+            }
+            let len = block.len() as f64;
+            counts
+                .iter()
+                .filter(|&&count| count > 0)
+                .map(|&count| {
+                    let p = count as f64 / len;
+                    -(count as f64) * p.log2()
+                })
+                .sum::<f64>()
+        })
+        .sum();
+    (bits / 8.0).ceil() as u64
+}
+
+/// Free bytes on the filesystem holding `dir`, if it can be read
+pub fn free_bytes(dir: &str) -> Option<u64> {
+    nix::sys::statvfs::statvfs(dir)
+        .ok()
+        .map(|stat| stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+impl Drop for SpaceReservation<'_> {
+    fn drop(&mut self) {
+        let mut reserved = self.guard.reserved.lock().unwrap();
+        *reserved = reserved.saturating_sub(self.bytes);
+    }
+}
+
+impl DiskGuard {
+    pub fn new(temp_dir: impl Into<String>, quota: u64) -> Self {
+        Self { temp_dir: temp_dir.into(), quota: AtomicU64::new(quota), reserved: Mutex::new(0) }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.temp_dir.clone(), config.storage_quota)
+    }
+
+    /// Change the quota; binaries already stored over a lower one are kept
+    pub fn set_quota(&self, quota: u64) {
+        self.quota.store(quota, Ordering::SeqCst);
+    }
+
+    /// Hold space for a merge producing about `output_size` bytes
+    pub fn reserve(&self, output_size: u64, binary_store: &BinaryStore) -> Result<SpaceReservation<'_>, SpaceError> {
+        let mut reserved = self.reserved.lock().unwrap();
+        self.check_locked(*reserved, output_size, binary_store)?;
+        *reserved += output_size;
+        Ok(SpaceReservation { guard: self, bytes: output_size })
+    }
+
+    /// Check there is room to store a `size`-byte upload
+    pub fn check(&self, size: u64, binary_store: &BinaryStore) -> Result<(), SpaceError> {
+        let reserved = self.reserved.lock().unwrap();
+        self.check_locked(*reserved, size, binary_store)
+    }
+
+    /// Like `check`, but content that is already staged needs no space
+    pub fn check_staging(&self, sha256: &str, size: u64, binary_store: &BinaryStore) -> Result<(), SpaceError> {
+        let staged = binary_store.get(sha256).is_some_and(|binary| std::path::Path::new(&binary.path).exists());
+        if staged { Ok(()) } else { self.check(size, binary_store) }
+    }
+
+    /// Bytes of stored binaries plus running merges, and the quota (0 = none)
+    pub fn usage(&self, binary_store: &BinaryStore) -> (u64, u64) {
+        (binary_store.total_size() + *self.reserved.lock().unwrap(), self.quota.load(Ordering::SeqCst))
+    }
+
+    fn check_locked(&self, reserved: u64, required: u64, binary_store: &BinaryStore) -> Result<(), SpaceError> {
+        let quota = self.quota.load(Ordering::SeqCst);
+        if quota > 0 {
+            let used = binary_store.total_size() + reserved;
+            if used + required > quota {
+                return Err(SpaceError::QuotaExceeded { used, quota, required });
+            }
+        }
+
+        // An unreadable temp dir is reported by /health; the write itself will fail
+        if let Some(free) = free_bytes(&self.temp_dir) {
+            // Running merges haven't written all of their output yet
+            let required = required + reserved;
+            if free < required {
+                return Err(SpaceError::DiskFull { free, required });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use crate::models::binary::{BinaryKind, Labels, StoredBinary};
+
+    fn stored(dir: &std::path::Path, id: &str, size: u64) -> StoredBinary {
+        let path = dir.join(id);
+        std::fs::write(&path, vec![0; size as usize]).unwrap();
+        StoredBinary {
+            id: id.to_string(),
+            path: path.to_string_lossy().to_string(),
+            size,
+            created_at: Utc::now(),
+            expires_at: Utc::now(),
+            report_path: None,
+            kind: BinaryKind::Staged,
+            sha256: Some(id.to_string()),
+            labels: Labels::new(),
+            provenance: None,
+            signature: None,
+            owner: None,
+            scans: Vec::new(),
+            details: None,
+            downloads: 0,
+            last_accessed: None,
+            stagers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_quota_counts_stored_and_reserved() {
+        let dir = tempfile::tempdir().unwrap();
+        let guard = DiskGuard::new(dir.path().to_string_lossy(), 100);
+        let store = BinaryStore::new();
+        store.insert(stored(dir.path(), "a", 40));
+
+        let running = guard.reserve(50, &store).unwrap();
+        assert_eq!(guard.usage(&store), (90, 100));
+        assert_eq!(
+            guard.check(20, &store),
+            Err(SpaceError::QuotaExceeded { used: 90, quota: 100, required: 20 })
+        );
+        // Already-staged content is free
+        assert!(guard.check_staging("a", 20, &store).is_ok());
+
+        drop(running);
+        assert!(guard.check(20, &store).is_ok());
+        assert!(DiskGuard::new(dir.path().to_string_lossy(), 0).check(1000, &store).is_ok());
+    }
+
+    #[test]
+    fn test_compressed_size_estimate() {
+        assert_eq!(estimated_compressed_size(&[]), 0);
+        assert_eq!(estimated_compressed_size(&[0; 100_000]), 0);
+        // Two equally likely bytes take a bit each
+        let two_symbols: Vec<u8> = (0..80_000).map(|i| if i % 2 == 0 { b'a' } else { b'b' }).collect();
+        assert_eq!(estimated_compressed_size(&two_symbols), 10_000);
+        let every_byte: Vec<u8> = (0..=255u8).cycle().take(256 * 100).collect();
+        assert_eq!(estimated_compressed_size(&every_byte), every_byte.len() as u64);
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{bail, Result};
+use reqwest::Url;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+const MAX_REDIRECTS: usize = 5;
+
+/// Whether `host` matches an entry of `WEAVER_FETCH_ALLOWED_HOSTS`
+///
+/// Entries are exact host names, or `*.example.com` for any subdomain.
+pub fn host_allowed(host: &str, allowed_hosts: &[String]) -> bool {
+    let host = host.to_ascii_lowercase();
+    allowed_hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+        None => host == *allowed,
+    })
+}
+
+/// Check a client-supplied input URL before accepting the merge
+pub fn validate_input_url(url: &str, allowed_hosts: &[String]) -> Result<Url, String> {
+    if allowed_hosts.is_empty() {
+        return Err("URL inputs are disabled; set WEAVER_FETCH_ALLOWED_HOSTS".to_string());
+    }
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Unsupported URL scheme: {}", parsed.scheme()));
+    }
+    match parsed.host_str() {
+        Some(host) if host_allowed(host, allowed_hosts) => Ok(parsed),
+        Some(host) => Err(format!("Host {} is not in WEAVER_FETCH_ALLOWED_HOSTS", host)),
+        None => Err("URL has no host".to_string()),
+    }
+}
+
+/// Download a merge input, refusing anything over `max_size` bytes
+///
+/// Redirects are followed only to allow-listed hosts.
+pub async fn fetch_input(url: Url, allowed_hosts: &[String], max_size: usize) -> Result<Vec<u8>> {
+    let redirect_hosts = allowed_hosts.to_vec();
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            let allowed = attempt.url().host_str().is_some_and(|host| host_allowed(host, &redirect_hosts));
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if !allowed {
+                attempt.error("redirected to a host outside WEAVER_FETCH_ALLOWED_HOSTS")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()?;
+
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response.content_length().is_some_and(|length| length as usize > max_size) {
+        bail!("{} bytes exceeds the {} byte limit", response.content_length().unwrap_or_default(), max_size);
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if data.len() + chunk.len() > max_size {
+            bail!("response exceeds the {} byte limit", max_size);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_list() {
+        let allowed = vec!["artifacts.example.com".to_string(), "*.registry.example".to_string()];
+        assert!(host_allowed("Artifacts.example.com", &allowed));
+        assert!(host_allowed("eu.registry.example", &allowed));
+        assert!(!host_allowed("registry.example", &allowed));
+        assert!(!host_allowed("evilregistry.example", &allowed));
+        assert!(!host_allowed("artifacts.example.com.evil", &allowed));
+
+        assert!(validate_input_url("https://eu.registry.example/app.bin", &allowed).is_ok());
+        assert!(validate_input_url("ftp://artifacts.example.com/app.bin", &allowed).is_err());
+        assert!(validate_input_url("https://other.example/app.bin", &allowed).is_err());
+        assert!(validate_input_url("https://artifacts.example.com/app.bin", &[]).is_err());
+    }
+}
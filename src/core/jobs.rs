@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::core::auth::Caller;
+
+/// Cooperative cancellation flag checked by the merger between steps
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Default)]
+struct CancelState {
+    /// Set once; later cancellations keep the first reason
+    reason: OnceLock<Cancelled>,
+    /// When the merge times out, and its timeout in seconds
+    deadline: OnceLock<(Instant, u64)>,
+    notify: Notify,
+}
+
+/// Error a cancelled merge stops with; check with `anyhow::Error::is`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cancelled {
+    /// Cancelled with `DELETE /jobs/{task_id}`
+    Requested,
+    /// Ran longer than its timeout, in seconds
+    TimedOut(u64),
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cancelled::Requested => write!(f, "merge was cancelled"),
+            Cancelled::TimedOut(secs) => write!(f, "merge timed out after {} seconds", secs),
+        }
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self, reason: Cancelled) {
+        let _ = self.inner.reason.set(reason);
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Time out with `Cancelled::TimedOut` `timeout` from now
+    ///
+    /// The deadline is noticed by `check`, so it also fires while the merger
+    /// is busy writing and nothing else gets to run on its thread, and by
+    /// `cancelled`, which sleeps until it.
+    pub fn set_deadline(&self, timeout: Duration) {
+        let _ = self.inner.deadline.set((Instant::now() + timeout, timeout.as_secs()));
+        // Waiters started before the deadline was set sleep until it from now on
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// `Err` with the reason once `cancel` was called or the deadline passed
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if let Some(&(deadline, secs)) = self.inner.deadline.get() {
+            if Instant::now() >= deadline {
+                self.cancel(Cancelled::TimedOut(secs));
+            }
+        }
+        match self.inner.reason.get() {
+            Some(reason) => Err(*reason),
+            None => Ok(()),
+        }
+    }
+
+    /// Resolves when the token is cancelled or its deadline passes
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            match self.inner.deadline.get() {
+                Some(&(deadline, secs)) => tokio::select! {
+                    () = notified => {}
+                    () = tokio::time::sleep_until(deadline.into()) => self.cancel(Cancelled::TimedOut(secs)),
+                },
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Run `future` unless the token is cancelled first, in which case it is dropped
+    ///
+    /// External tools run through this with `kill_on_drop`, so a hung one is
+    /// killed when the merge is cancelled or times out.
+    pub async fn run_until_cancelled<F: Future>(&self, future: F) -> Result<F::Output, Cancelled> {
+        tokio::select! {
+            output = future => Ok(output),
+            () = self.cancelled() => Err(self.check().unwrap_err()),
+        }
+    }
+}
+
+/// Timeout for one merge: the request's `timeout_secs`, capped at `WEAVER_MERGE_TIMEOUT`
+///
+/// 0 means no limit, for both.
+pub fn merge_timeout(configured: u64, requested: Option<u64>) -> Option<Duration> {
+    let secs = match (configured, requested.filter(|&secs| secs > 0)) {
+        (0, requested) => requested?,
+        (configured, Some(requested)) => requested.min(configured),
+        (configured, None) => configured,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+/// A merge with this task ID is already running
+#[derive(Debug)]
+pub struct DuplicateJob(pub String);
+
+impl fmt::Display for DuplicateJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a merge with task_id {} is already running", self.0)
+    }
+}
+
+/// A running merge and the API key that started it
+struct Job {
+    token: CancelToken,
+    owner: Option<String>,
+}
+
+/// Running merges that can be cancelled by task ID
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+/// Registration of one merge, removed when dropped
+pub struct JobGuard<'a> {
+    registry: &'a JobRegistry,
+    task_id: Option<String>,
+    token: CancelToken,
+}
+
+impl JobGuard<'_> {
+    pub fn token(&self) -> &CancelToken {
+        &self.token
+    }
+
+    /// Stop the merge with `Cancelled::TimedOut` unless it finishes within `timeout`
+    pub fn arm_timeout(&self, timeout: Duration) {
+        self.token.set_deadline(timeout);
+    }
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(ref task_id) = self.task_id {
+            self.registry.jobs.lock().unwrap().remove(task_id);
+        }
+    }
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track a merge started by `caller`; without a task ID it can't be
+    /// cancelled but still gets a token
+    pub fn register(&self, task_id: Option<&str>, caller: Option<&Caller>) -> Result<JobGuard<'_>, DuplicateJob> {
+        let token = CancelToken::new();
+        if let Some(task_id) = task_id {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(task_id) {
+                return Err(DuplicateJob(task_id.to_string()));
+            }
+            let owner = caller.map(|caller| caller.name.clone());
+            jobs.insert(task_id.to_string(), Job { token: token.clone(), owner });
+        }
+        Ok(JobGuard { registry: self, task_id: task_id.map(str::to_string), token })
+    }
+
+    /// Ask the merge running as `task_id` to stop; false if there is none
+    ///
+    /// A caller may only cancel its own merges, unless it is an admin; with
+    /// authentication off (`caller` is `None`) anyone may. Other callers'
+    /// merges look like missing ones.
+    pub fn cancel(&self, task_id: &str, caller: Option<&Caller>) -> bool {
+        match self.jobs.lock().unwrap().get(task_id) {
+            Some(job) if caller.is_none_or(|caller| caller.is_owner(job.owner.as_deref())) => {
+                job.token.cancel(Cancelled::Requested);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::auth::Role;
+
+    #[tokio::test]
+    async fn test_cancel_registered_job() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register(Some("t1"), None).unwrap();
+        assert!(jobs.register(Some("t1"), None).is_err());
+        assert!(!jobs.cancel("t2", None));
+
+        let token = job.token().clone();
+        let waiter = tokio::spawn(async move { token.cancelled().await });
+        assert!(jobs.cancel("t1", None));
+        waiter.await.unwrap();
+        assert_eq!(job.token().check(), Err(Cancelled::Requested));
+
+        drop(job);
+        assert!(!jobs.cancel("t1", None));
+        assert!(jobs.register(Some("t1"), None).is_ok());
+    }
+
+    #[test]
+    fn test_only_owner_or_admin_cancels() {
+        let caller = |name: &str, role| Caller { name: name.to_string(), role };
+        let (ci, dev, admin) = (caller("ci", Role::Merge), caller("dev", Role::Merge), caller("ops", Role::Admin));
+        let jobs = JobRegistry::new();
+        let job = jobs.register(Some("t1"), Some(&ci)).unwrap();
+
+        assert!(!jobs.cancel("t1", Some(&dev)));
+        assert!(job.token().check().is_ok());
+        assert!(jobs.cancel("t1", Some(&ci)));
+        assert!(jobs.cancel("t1", Some(&admin)));
+    }
+
+    #[test]
+    fn test_timeout_cancels_job() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register(None, None).unwrap();
+        job.arm_timeout(Duration::from_millis(10));
+        assert!(job.token().check().is_ok());
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(job.token().check(), Err(Cancelled::TimedOut(0)));
+        // An explicit cancel afterwards doesn't change the reason
+        job.token().cancel(Cancelled::Requested);
+        assert_eq!(job.token().check(), Err(Cancelled::TimedOut(0)));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_wakes_waiters() {
+        let jobs = JobRegistry::new();
+        let job = jobs.register(None, None).unwrap();
+        let token = job.token().clone();
+        let waiter = tokio::spawn(async move { token.run_until_cancelled(std::future::pending::<()>()).await });
+        tokio::task::yield_now().await;
+        // Armed after the waiter started waiting
+        job.arm_timeout(Duration::from_millis(10));
+        let result = tokio::time::timeout(Duration::from_secs(5), waiter).await.expect("deadline didn't wake the waiter");
+        assert_eq!(result.unwrap(), Err(Cancelled::TimedOut(0)));
+    }
+
+    #[test]
+    fn test_merge_timeout() {
+        assert_eq!(merge_timeout(600, None), Some(Duration::from_secs(600)));
+        assert_eq!(merge_timeout(600, Some(60)), Some(Duration::from_secs(60)));
+        assert_eq!(merge_timeout(600, Some(6000)), Some(Duration::from_secs(600)));
+        assert_eq!(merge_timeout(600, Some(0)), Some(Duration::from_secs(600)));
+        assert_eq!(merge_timeout(0, Some(60)), Some(Duration::from_secs(60)));
+        assert_eq!(merge_timeout(0, None), None);
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::strategy::StubAppend;
+use super::stubs::Stub;
+use super::v2::{DataFile, Installer, StubOptions};
+use crate::core::digest::sha256_hex;
+
+/// Everything that determines the bytes of a merged artifact
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MergeCacheKey {
+    base_sha256: String,
+    overload_sha256: String,
+    /// With the bundled interpreter and data file contents stripped; they are only keyed by SHA-256
+    options: StubOptions,
+    interpreter_sha256: Option<String>,
+    /// Hash of the stub that would be prepended, so a stub upgrade invalidates old entries
+    stub_sha256: String,
+    /// Name of the `MergeStrategy` that builds the output
+    strategy: &'static str,
+}
+
+impl MergeCacheKey {
+    pub fn new(base_data: &[u8], overload_data: &[u8], stub: &Stub, options: &StubOptions) -> Self {
+        Self {
+            base_sha256: sha256_hex(base_data),
+            overload_sha256: sha256_hex(overload_data),
+            options: StubOptions {
+                interpreter: None,
+                installer: options.installer.as_ref().map(Installer::without_data),
+                assets: options.assets.iter().map(DataFile::without_data).collect(),
+                ..options.clone()
+            },
+            interpreter_sha256: options.interpreter.as_ref().map(|interpreter| interpreter.sha256.clone()),
+            stub_sha256: stub.sha256.clone(),
+            strategy: StubAppend::NAME,
+        }
+    }
+
+    /// Key the output of `strategy` instead of the default one
+    pub fn strategy(self, strategy: &'static str) -> Self {
+        Self { strategy, ..self }
+    }
+}
+
+struct CachedMerge {
+    binary_id: String,
+    cached_at: DateTime<Utc>,
+}
+
+/// Maps identical merge requests to the binary ID of an earlier result
+///
+/// Entries only point into the binary store; callers must still check the
+/// artifact exists and hasn't expired there.
+pub struct MergeCache {
+    ttl: Mutex<Duration>,
+    entries: Mutex<HashMap<MergeCacheKey, CachedMerge>>,
+}
+
+impl MergeCache {
+    /// `ttl_secs == 0` disables caching
+    pub fn new(ttl_secs: i64) -> Self {
+        Self {
+            ttl: Mutex::new(Duration::seconds(ttl_secs.max(0))),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Change the TTL, applying it to entries already cached; 0 disables caching
+    pub fn set_ttl(&self, ttl_secs: i64) {
+        *self.ttl.lock().unwrap() = Duration::seconds(ttl_secs.max(0));
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.lock().unwrap().is_zero()
+    }
+
+    /// Binary ID of a cached merge, if still within the TTL
+    pub fn get(&self, key: &MergeCacheKey) -> Option<String> {
+        let now = Utc::now();
+        let ttl = *self.ttl.lock().unwrap();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| now - entry.cached_at < ttl);
+        entries.get(key).map(|entry| entry.binary_id.clone())
+    }
+
+    pub fn insert(&self, key: MergeCacheKey, binary_id: String) {
+        if !self.is_enabled() {
+            return;
+        }
+        self.entries.lock().unwrap().insert(key, CachedMerge {
+            binary_id,
+            cached_at: Utc::now(),
+        });
+    }
+
+    pub fn remove(&self, key: &MergeCacheKey) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(base: &str, options: StubOptions) -> MergeCacheKey {
+        MergeCacheKey {
+            base_sha256: base.to_string(),
+            overload_sha256: "overload".to_string(),
+            options,
+            interpreter_sha256: None,
+            stub_sha256: "stub".to_string(),
+            strategy: StubAppend::NAME,
+        }
+    }
+
+    #[test]
+    fn test_hit_requires_identical_inputs_and_options() {
+        let cache = MergeCache::new(3600);
+        cache.insert(key("base", StubOptions::default()), "merged-1".to_string());
+
+        assert_eq!(cache.get(&key("base", StubOptions::default())).as_deref(), Some("merged-1"));
+        assert!(cache.get(&key("other", StubOptions::default())).is_none());
+
+        let sync = StubOptions { sync_mode: true, ..StubOptions::default() };
+        assert!(cache.get(&key("base", sync)).is_none());
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_cache() {
+        let cache = MergeCache::new(0);
+        assert!(!cache.is_enabled());
+
+        cache.insert(key("base", StubOptions::default()), "merged-1".to_string());
+        assert!(cache.get(&key("base", StubOptions::default())).is_none());
+    }
+}
@@ -0,0 +1,302 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+
+use super::error::MergeError;
+use super::pack;
+use super::strategy::{MergeJob, MergePlan, MergeStrategy, PlanJob};
+use super::stubs::StubRegistry;
+use super::v2::StubOptions;
+use crate::core::binary::{is_script, Architecture, BinaryInfo, OperatingSystem};
+use crate::core::progress::{ProgressStep, ProgressTracker};
+use weaver_abi::footer::ORDER_BASE_FIRST;
+
+/// C loader the payloads are linked into; `@...@` markers are filled in per merge
+///
+/// The payloads are pulled in with `.incbin`, so the output is an ordinary
+/// executable with both binaries in `.rodata` and nothing appended. At run
+/// time each is copied into a memfd and started with `fexecve`.
+const LOADER_TEMPLATE: &str = r#"#define _GNU_SOURCE
+#include <signal.h>
+#include <stdio.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define SYNC_MODE @SYNC_MODE@
+#define BASE_FIRST @BASE_FIRST@
+
+__asm__(
+    ".section .rodata\n"
+    ".balign 16\n"
+    "weaver_base_start:\n"
+    ".incbin \"@BASE_PATH@\"\n"
+    "weaver_base_end:\n"
+    ".balign 16\n"
+    "weaver_overload_start:\n"
+    ".incbin \"@OVERLOAD_PATH@\"\n"
+    "weaver_overload_end:\n"
+    ".previous\n");
+
+extern const unsigned char weaver_base_start[], weaver_base_end[];
+extern const unsigned char weaver_overload_start[], weaver_overload_end[];
+
+static int load(const char *name, const unsigned char *start, const unsigned char *end) {
+    int fd = memfd_create(name, MFD_CLOEXEC);
+    if (fd < 0) {
+        return -1;
+    }
+    while (start < end) {
+        ssize_t written = write(fd, start, (size_t)(end - start));
+        if (written <= 0) {
+            close(fd);
+            return -1;
+        }
+        start += written;
+    }
+    return fd;
+}
+
+static pid_t spawn(int fd, char **argv, char **envp) {
+    pid_t pid = fork();
+    if (pid == 0) {
+        fexecve(fd, argv, envp);
+        perror("weaver: fexecve");
+        _exit(127);
+    }
+    return pid;
+}
+
+static int wait_code(pid_t pid) {
+    int status;
+    if (pid < 0 || waitpid(pid, &status, 0) < 0) {
+        return 1;
+    }
+    return WIFEXITED(status) ? WEXITSTATUS(status) : 128 + WTERMSIG(status);
+}
+
+int main(int argc, char **argv, char **envp) {
+    char *overload_argv[] = {argv[0], NULL};
+    int base = load("base", weaver_base_start, weaver_base_end);
+    int overload = load("overload", weaver_overload_start, weaver_overload_end);
+    (void)argc;
+    if (base < 0 || overload < 0) {
+        perror("weaver: memfd_create");
+        return 1;
+    }
+
+    if (BASE_FIRST) {
+        int code = wait_code(spawn(base, argv, envp));
+        wait_code(spawn(overload, overload_argv, envp));
+        return code;
+    }
+
+    pid_t overload_pid = spawn(overload, overload_argv, envp);
+    if (SYNC_MODE) {
+        if (wait_code(overload_pid) != 0) {
+            fprintf(stderr, "weaver: overload failed, not starting base\n");
+            return 1;
+        }
+        overload_pid = -1;
+    }
+    int code = wait_code(spawn(base, argv, envp));
+    if (overload_pid > 0) {
+        kill(overload_pid, SIGTERM);
+        waitpid(overload_pid, NULL, 0);
+    }
+    return code;
+}
+"#;
+
+/// Weave by compiling a small C loader with the payloads linked in as data
+///
+/// Only Linux targets, and only those a C compiler on `PATH` can build for.
+/// The loader runs the overload before or beside base like the stub does,
+/// but honors no other `StubOptions` besides `pack`.
+pub struct CompileLoader;
+
+impl CompileLoader {
+    pub const NAME: &'static str = "compile";
+}
+
+/// First C compiler on `PATH` that builds for `arch`
+///
+/// Cross compilers are tried first; the plain `cc`/`gcc` only when the host
+/// has the same architecture.
+pub fn compiler_for(arch: Architecture) -> Option<PathBuf> {
+    let (cross, native) = match arch {
+        Architecture::X86_64 => ("x86_64-linux-gnu-gcc", cfg!(target_arch = "x86_64")),
+        Architecture::X86 => ("i686-linux-gnu-gcc", cfg!(target_arch = "x86")),
+        Architecture::AArch64 => ("aarch64-linux-gnu-gcc", cfg!(target_arch = "aarch64")),
+        Architecture::ARM => ("arm-linux-gnueabihf-gcc", cfg!(target_arch = "arm")),
+        _ => return None,
+    };
+    let candidates: &[&str] = if native && cfg!(target_os = "linux") { &[cross, "cc", "gcc"] } else { &[cross] };
+    let path = std::env::var_os("PATH")?;
+    candidates
+        .iter()
+        .flat_map(|name| std::env::split_paths(&path).map(move |dir| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// `path` as a string literal inside the generated `__asm__` block
+fn asm_string(path: &Path) -> Result<String> {
+    let path = path.to_str().context("Work dir path is not UTF-8")?;
+    // Escaped once for the assembler's string and again for the C string around it
+    Ok(path.replace('\\', "\\\\\\\\").replace('"', "\\\\\\\""))
+}
+
+fn loader_source(base_path: &Path, overload_path: &Path, options: &StubOptions) -> Result<String> {
+    Ok(LOADER_TEMPLATE
+        .replace("@SYNC_MODE@", if options.sync_mode { "1" } else { "0" })
+        .replace("@BASE_FIRST@", if options.order == ORDER_BASE_FIRST { "1" } else { "0" })
+        .replace("@BASE_PATH@", &asm_string(base_path)?)
+        .replace("@OVERLOAD_PATH@", &asm_string(overload_path)?))
+}
+
+#[async_trait]
+impl MergeStrategy for CompileLoader {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn supports(&self, base_info: &BinaryInfo, _stubs: &StubRegistry) -> bool {
+        base_info.os == OperatingSystem::Linux && compiler_for(base_info.arch).is_some()
+    }
+
+    async fn merge(&self, job: MergeJob<'_>) -> Result<String> {
+        log::info!("🛠️  Merging binaries by compiling a C loader...");
+        job.cancel.check()?;
+        let compiler = check_job(job.base_info, job.overload.bytes, job.options)?;
+
+        let tracker = (!job.task_id.is_empty()).then(|| ProgressTracker::new(job.progress.clone(), job.task_id.to_string()));
+        if let Some(ref tracker) = tracker {
+            let _ = tracker.update(ProgressStep::WritingBinaries).await;
+        }
+        let base_path = job.work_path.join("base.bin");
+        let overload_path = job.work_path.join("overload.bin");
+        tokio::fs::write(&base_path, job.base.bytes).await.context("Failed to write base")?;
+        tokio::fs::write(&overload_path, job.overload.bytes).await.context("Failed to write overload")?;
+
+        if let Some(ref tracker) = tracker {
+            let _ = tracker.update(ProgressStep::CreatingLoader).await;
+        }
+        let source_path = job.work_path.join("loader.c");
+        tokio::fs::write(&source_path, loader_source(&base_path, &overload_path, job.options)?)
+            .await
+            .context("Failed to write loader source")?;
+        job.cancel.check()?;
+
+        if let Some(ref tracker) = tracker {
+            let _ = tracker.update(ProgressStep::CompilingLoader).await;
+        }
+        let output_path = job.work_path.join("merged_binary");
+        log::info!("Compiling loader with {}", compiler.display());
+        let compile = tokio::process::Command::new(&compiler)
+            .args(["-O2", "-s", "-o"])
+            .arg(&output_path)
+            .arg(&source_path)
+            .kill_on_drop(true)
+            .output();
+        let output = job
+            .cancel
+            .run_until_cancelled(compile)
+            .await?
+            .with_context(|| format!("Failed to run {}", compiler.display()))?;
+        if !output.status.success() {
+            anyhow::bail!("{} failed: {}", compiler.display(), String::from_utf8_lossy(&output.stderr).trim());
+        }
+        if job.options.pack {
+            job.cancel.check()?;
+            pack::pack_executable(&output_path, job.cancel).await?;
+        }
+
+        if let Some(ref tracker) = tracker {
+            let _ = tracker.update(ProgressStep::Finalizing).await;
+        }
+        Ok(output_path.to_string_lossy().into_owned())
+    }
+
+    fn plan(&self, job: &PlanJob<'_>) -> Result<MergePlan> {
+        check_job(job.base_info, job.overload, job.options)?;
+        Ok(MergePlan {
+            stub: None,
+            output_size: (job.base.len() + job.overload.len()) as u64,
+            warnings: vec!["The compiled loader's own size isn't known before it is built; output_size leaves it out".to_string()],
+        })
+    }
+}
+
+/// The compiler for a job this strategy can merge
+fn check_job(base_info: &BinaryInfo, overload: &[u8], options: &StubOptions) -> Result<PathBuf, MergeError> {
+    let honored = StubOptions { sync_mode: options.sync_mode, order: options.order, pack: options.pack, ..StubOptions::default() };
+    if *options != honored {
+        return Err(MergeError::UnsupportedByStrategy {
+            strategy: CompileLoader::NAME,
+            reason: "only sync_mode, mode and pack apply; use the append strategy for the other options".to_string(),
+        });
+    }
+    if options.pack && pack::upx().is_none() {
+        return Err(MergeError::UnsupportedByStrategy {
+            strategy: CompileLoader::NAME,
+            reason: "pack needs upx on the server's PATH".to_string(),
+        });
+    }
+    if is_script(overload) {
+        return Err(MergeError::UnsupportedByStrategy {
+            strategy: CompileLoader::NAME,
+            reason: "script overloads need the append strategy".to_string(),
+        });
+    }
+    compiler_for(base_info.arch)
+        .ok_or_else(|| MergeError::UnsupportedPlatform(format!("No C compiler on PATH for {}", base_info.description())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::selftest::payload;
+
+    #[test]
+    fn test_loader_source_embeds_escaped_paths() {
+        let options = StubOptions { sync_mode: true, ..StubOptions::default() };
+        let source = loader_source(Path::new("/tmp/a\"b/base.bin"), Path::new("/tmp/overload.bin"), &options).unwrap();
+        assert!(source.contains("#define SYNC_MODE 1"));
+        assert!(source.contains("#define BASE_FIRST 0"));
+        assert!(source.contains(r#".incbin \"/tmp/a\\\"b/base.bin\"\n"#));
+        assert!(!source.contains('@'));
+    }
+
+    #[tokio::test]
+    async fn test_compiled_loader_runs_both_payloads() {
+        let Some(info) = crate::core::selftest::host_platform() else { return };
+        if compiler_for(info.arch).is_none() {
+            eprintln!("Skipping: no C compiler for {}", info.description());
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let base = payload(info.arch, b"base\n").unwrap();
+        let overload = payload(info.arch, b"overload\n").unwrap();
+        let progress: crate::core::progress::SharedProgressSink =
+            std::sync::Arc::new(crate::core::progress::MemoryProgressSink::new());
+        let options = StubOptions { sync_mode: true, ..StubOptions::default() };
+        let merged = CompileLoader
+            .merge(MergeJob {
+                base: (&base).into(),
+                overload: (&overload).into(),
+                work_path: dir.path(),
+                base_info: &info,
+                task_id: "",
+                progress: &progress,
+                stubs: &StubRegistry::load(None),
+                options: &options,
+                cancel: &crate::core::jobs::CancelToken::new(),
+            })
+            .await
+            .unwrap();
+
+        let output = std::process::Command::new(&merged).output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "overload\nbase\n");
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+/// Merge failures a client can act on; everything else stays a plain `anyhow` error
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// No loader stub exists for this OS/architecture at all
+    UnsupportedPlatform(String),
+    /// Base and overload target different platforms
+    ArchMismatch { base: String, overload: String },
+    /// Base and overload target the same platform but need different C libraries or float ABIs
+    AbiMismatch { base: String, overload: String },
+    /// The stub for base's platform can't run where base does
+    StubAbiMismatch { base: String, stub: String },
+    /// The interpreter bundled for a script overload targets another platform than base
+    InterpreterMismatch { base: String, interpreter: String },
+    /// The stub selected for base's platform isn't a runnable binary for it
+    StubMismatch { expected: String, reason: String },
+    /// The platform is supported but this build has no stub for it
+    StubMissing { os: String, arch: String },
+    /// The request names a merge strategy this server doesn't have
+    UnknownStrategy { requested: String, available: Vec<&'static str> },
+    /// The chosen merge strategy can't honor something the request asks for
+    UnsupportedByStrategy { strategy: &'static str, reason: String },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::UnsupportedPlatform(reason) => write!(f, "{}", reason),
+            MergeError::ArchMismatch { base, overload } => write!(
+                f,
+                "Binary mismatch! Base is {} but overload is {}. Both binaries must have the same architecture and OS.",
+                base, overload
+            ),
+            MergeError::AbiMismatch { base, overload } => write!(
+                f,
+                "ABI mismatch! Base is {} but overload is {}. Dynamically linked binaries must use the same C library and float ABI; link one of them statically.",
+                base, overload
+            ),
+            MergeError::StubAbiMismatch { base, stub } => write!(
+                f,
+                "ABI mismatch! Base is {} but the loader stub is {}. Add a stub built for base's ABI to WEAVER_STUB_DIR.",
+                base, stub
+            ),
+            MergeError::InterpreterMismatch { base, interpreter } => write!(
+                f,
+                "Interpreter mismatch! Base is {} but the bundled interpreter is {}.",
+                base, interpreter
+            ),
+            MergeError::StubMismatch { expected, reason } => write!(
+                f,
+                "Loader stub mismatch! The stub for {} {}, so the merged binary could not run. Replace it in WEAVER_STUB_DIR.",
+                expected, reason
+            ),
+            MergeError::StubMissing { os, arch } => write!(
+                f,
+                "No stub available for {}/{}. Add it to WEAVER_STUB_DIR or use a production build.",
+                os, arch
+            ),
+            MergeError::UnknownStrategy { requested, available } => write!(
+                f,
+                "Unknown merge strategy {}. Available: {}",
+                requested,
+                available.join(", ")
+            ),
+            MergeError::UnsupportedByStrategy { strategy, reason } => {
+                write!(f, "The {} merge strategy can't do this: {}", strategy, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
@@ -0,0 +1,196 @@
+use serde::Deserialize;
+
+use weaver_abi::kill_policy::{
+    self, PolicyOp, ACTION_EXIT, ACTION_KILL_BASE, ACTION_KILL_OVERLOAD, ACTION_LOG, FIELD_ALIVE, FIELD_FAILURES,
+    FIELD_KILL_REQUESTED, FIELD_SINCE_HEARTBEAT, FIELD_SINCE_SUCCESS, MAX_KILL_POLICY_OPS, OP_AND, OP_AT_LEAST,
+    OP_FLAGS, OP_GREATER, OP_NOT, OP_OR, OP_RULE,
+};
+
+/// What the stub does when a rule holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Log the rule and keep monitoring
+    Log,
+    /// Terminate base
+    KillBase,
+    /// Ask the payload to run its kill method, then terminate base
+    KillOverload,
+    /// Shut the merged binary down like SIGTERM would
+    Exit,
+}
+
+/// How a counter or age is compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Threshold {
+    Over(u32),
+    AtLeast(u32),
+}
+
+/// Condition over one health slot, e.g. `{"since_success": {"over": 300}}`
+/// or `{"any": [{"failures": {"at_least": 3}}, {"flag": 0}]}`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    /// Seconds since the payload last reported success
+    SinceSuccess(Threshold),
+    /// Consecutive failed checks
+    Failures(Threshold),
+    /// Seconds since the payload's last heartbeat
+    SinceHeartbeat(Threshold),
+    /// The payload asked for base to be killed
+    KillRequested(bool),
+    /// The payload says it is alive
+    Alive(bool),
+    /// Custom flag bit 0-31 the payload set in its slot
+    Flag(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRule {
+    pub when: Condition,
+    pub action: PolicyAction,
+}
+
+/// Kill policy replacing the stub's fixed health checks
+///
+/// Accepted as JSON, e.g.
+/// `{"rules": [{"when": {"any": [{"since_success": {"over": 300}}, {"failures": {"at_least": 3}}]}, "action": "kill_base"}]}`.
+/// Rules run in order on every health check; `log` rules are reported and
+/// the first other rule that holds is acted on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KillPolicy {
+    pub rules: Vec<PolicyRule>,
+}
+
+impl KillPolicy {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let policy: KillPolicy = serde_json::from_str(json).map_err(|e| format!("Invalid kill policy: {}", e))?;
+        if policy.rules.is_empty() {
+            return Err("Kill policy must have at least one rule".to_string());
+        }
+        // Catches policies that don't fit the footer before the merge starts
+        policy.compile()?;
+        Ok(policy)
+    }
+
+    /// Ops for `ConfigFooter::kill_policy`
+    pub fn compile(&self) -> Result<[u64; MAX_KILL_POLICY_OPS], String> {
+        let mut ops = Vec::new();
+        for rule in &self.rules {
+            compile_condition(&rule.when, &mut ops)?;
+            let action = match rule.action {
+                PolicyAction::Log => ACTION_LOG,
+                PolicyAction::KillBase => ACTION_KILL_BASE,
+                PolicyAction::KillOverload => ACTION_KILL_OVERLOAD,
+                PolicyAction::Exit => ACTION_EXIT,
+            };
+            ops.push(PolicyOp::new(OP_RULE, 0, action));
+        }
+        kill_policy::validate(&ops).map_err(|e| format!("Kill policy does not fit the footer: {}", e))?;
+
+        let mut footer = [0; MAX_KILL_POLICY_OPS];
+        for (slot, op) in footer.iter_mut().zip(&ops) {
+            *slot = op.encode();
+        }
+        Ok(footer)
+    }
+
+    /// Whether any rule does more than log
+    pub fn can_kill(&self) -> bool {
+        self.rules.iter().any(|rule| rule.action != PolicyAction::Log)
+    }
+}
+
+/// Append `condition` in postfix form
+fn compile_condition(condition: &Condition, ops: &mut Vec<PolicyOp>) -> Result<(), String> {
+    let compare = |field, threshold: &Threshold| match *threshold {
+        Threshold::Over(value) => PolicyOp::new(OP_GREATER, field, value),
+        Threshold::AtLeast(value) => PolicyOp::new(OP_AT_LEAST, field, value),
+    };
+    match condition {
+        Condition::All(conditions) | Condition::Any(conditions) => {
+            let join = if matches!(condition, Condition::All(_)) { OP_AND } else { OP_OR };
+            if conditions.is_empty() {
+                return Err("Kill policy \"all\" and \"any\" need at least one condition".to_string());
+            }
+            for (i, condition) in conditions.iter().enumerate() {
+                compile_condition(condition, ops)?;
+                if i > 0 {
+                    ops.push(PolicyOp::new(join, 0, 0));
+                }
+            }
+        }
+        Condition::Not(condition) => {
+            compile_condition(condition, ops)?;
+            ops.push(PolicyOp::new(OP_NOT, 0, 0));
+        }
+        Condition::SinceSuccess(threshold) => ops.push(compare(FIELD_SINCE_SUCCESS, threshold)),
+        Condition::Failures(threshold) => ops.push(compare(FIELD_FAILURES, threshold)),
+        Condition::SinceHeartbeat(threshold) => ops.push(compare(FIELD_SINCE_HEARTBEAT, threshold)),
+        Condition::KillRequested(requested) | Condition::Alive(requested) => {
+            let field = if matches!(condition, Condition::Alive(_)) { FIELD_ALIVE } else { FIELD_KILL_REQUESTED };
+            ops.push(PolicyOp::new(OP_AT_LEAST, field, 1));
+            if !requested {
+                ops.push(PolicyOp::new(OP_NOT, 0, 0));
+            }
+        }
+        Condition::Flag(bit) if *bit < 32 => ops.push(PolicyOp::new(OP_FLAGS, 0, 1 << bit)),
+        Condition::Flag(bit) => return Err(format!("Kill policy flag {} is out of range (0-31)", bit)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_compiles_to_postfix_ops() {
+        let policy = KillPolicy::from_json(
+            r#"{"rules": [
+                {"when": {"flag": 2}, "action": "log"},
+                {"when": {"all": [{"since_success": {"over": 300}}, {"alive": false}, {"failures": {"at_least": 3}}]}, "action": "exit"}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(policy.can_kill());
+
+        let footer = policy.compile().unwrap();
+        let ops: Vec<PolicyOp> = kill_policy::ops(&footer).collect();
+        assert_eq!(
+            ops,
+            vec![
+                PolicyOp::new(OP_FLAGS, 0, 1 << 2),
+                PolicyOp::new(OP_RULE, 0, ACTION_LOG),
+                PolicyOp::new(OP_GREATER, FIELD_SINCE_SUCCESS, 300),
+                PolicyOp::new(OP_AT_LEAST, FIELD_ALIVE, 1),
+                PolicyOp::new(OP_NOT, 0, 0),
+                PolicyOp::new(OP_AND, 0, 0),
+                PolicyOp::new(OP_AT_LEAST, FIELD_FAILURES, 3),
+                PolicyOp::new(OP_AND, 0, 0),
+                PolicyOp::new(OP_RULE, 0, ACTION_EXIT),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_policy_rejects_bad_input() {
+        assert!(KillPolicy::from_json("not json").is_err());
+        assert!(KillPolicy::from_json(r#"{"rules": []}"#).is_err());
+        assert!(KillPolicy::from_json(r#"{"rules": [{"when": {"any": []}, "action": "log"}]}"#).is_err());
+        assert!(KillPolicy::from_json(r#"{"rules": [{"when": {"flag": 32}, "action": "log"}]}"#).is_err());
+        assert!(KillPolicy::from_json(r#"{"rules": [{"when": {"flag": 1}, "action": "reboot"}]}"#).is_err());
+
+        // Too many ops for the footer
+        let rule = r#"{"when": {"flag": 1}, "action": "log"}"#;
+        let rules = vec![rule; MAX_KILL_POLICY_OPS / 2 + 1].join(",");
+        assert!(KillPolicy::from_json(&format!(r#"{{"rules": [{}]}}"#, rules)).is_err());
+    }
+}
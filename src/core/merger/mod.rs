@@ -1,90 +1,155 @@
+pub mod cache;
+pub mod compile;
+pub mod error;
+pub mod kill_policy;
+pub mod pack;
+pub mod seccomp;
+pub mod splice;
+pub mod strategy;
+pub mod stubs;
 pub mod v2;
 
+pub use cache::{MergeCache, MergeCacheKey};
+pub use compile::CompileLoader;
+pub use error::MergeError;
+pub use kill_policy::KillPolicy;
+pub use seccomp::SeccompProfile;
+pub use splice::MergeInput;
+pub use strategy::{MergeJob, MergePlan, MergeStrategies, MergeStrategy, PlanJob, StubAppend};
+pub use stubs::{parse_platform, stub_slots, Stub, StubFlavor, StubRegistry, StubSource, STUB_PLATFORMS};
+pub use v2::{
+    AuditLog, BundledInterpreter, CrashReport, DataFile, ExecStrategy, Installer, MachineBinding, OutputLog, PrerequisiteFailure, Readiness, ReadinessProbe,
+    Revocation, SingleInstance,
+    StubLogLevel, StubLogTarget, StubOptions, WorkingDir,
+};
+
 use anyhow::Result;
 use std::fs;
 use std::path::PathBuf;
-use tempfile::TempDir;
 
-use crate::core::binary::{BinaryInfo, OperatingSystem};
+use crate::core::binary::{is_script, BinaryInfo, OperatingSystem};
+use crate::core::jobs::CancelToken;
+use crate::core::progress::SharedProgressSink;
+use crate::core::workspace::WorkspaceManager;
 use crate::models::request::MergeMode;
 
+/// Check that the overload, and the interpreter bundled for it, can run where base does
+///
+/// A `#!` script has no platform of its own; it runs under any Unix stub,
+/// through its own interpreter or the bundled one.
+pub fn check_payload_platforms(base_info: &BinaryInfo, overload: &[u8], interpreter: Option<&[u8]>) -> Result<(), MergeError> {
+    if is_script(overload) {
+        if base_info.os == OperatingSystem::Windows {
+            return Err(MergeError::UnsupportedPlatform(
+                "Script overloads need a Linux or macOS base; Windows has no #! interpreters".to_string(),
+            ));
+        }
+    } else {
+        let overload_info = BinaryInfo::detect(overload);
+        if !base_info.is_same_platform(&overload_info) {
+            return Err(MergeError::ArchMismatch {
+                base: base_info.description(),
+                overload: overload_info.description(),
+            });
+        }
+        if !base_info.is_compatible_with(&overload_info) {
+            return Err(MergeError::AbiMismatch {
+                base: base_info.description(),
+                overload: overload_info.description(),
+            });
+        }
+    }
+
+    if let Some(interpreter) = interpreter {
+        let interpreter_info = BinaryInfo::detect(interpreter);
+        if !base_info.is_compatible_with(&interpreter_info) {
+            return Err(MergeError::InterpreterMismatch {
+                base: base_info.description(),
+                interpreter: interpreter_info.description(),
+            });
+        }
+    }
+    Ok(())
+}
+
 /// Main entry point for binary merging
 /// 
 /// This function:
 /// 1. Detects the architecture and OS of both binaries
-/// 2. Validates they are compatible
+/// 2. Validates they are compatible (a script overload runs on any Unix base)
 /// 3. Routes to the unified V2 merger
-pub async fn merge_binaries(
-    base_data: &[u8],
-    overload_data: &[u8],
+#[tracing::instrument(skip_all, fields(task_id = %task_id, mode = ?mode, sync = sync))]
+pub async fn merge_binaries<'a>(
+    base: impl Into<MergeInput<'a>>,
+    overload: impl Into<MergeInput<'a>>,
     mode: MergeMode,
     sync: bool,
-    temp_dir: &str,
+    workspaces: &WorkspaceManager,
     task_id: &str,
-    redis_url: &str,
+    progress: &SharedProgressSink,
+    stubs: &StubRegistry,
+    cancel: &CancelToken,
 ) -> Result<String> {
+    let (base, overload) = (base.into(), overload.into());
+
     // Comprehensive binary detection
-    let base_info = BinaryInfo::detect(base_data);
-    let overload_info = BinaryInfo::detect(overload_data);
+    let base_info = BinaryInfo::detect(base.bytes);
     
     log::info!("🔍 Detected binaries:");
     log::info!("  Base: {}", base_info.description());
-    log::info!("  Overload: {}", overload_info.description());
+    if is_script(overload.bytes) {
+        log::info!("  Overload: script");
+    } else {
+        log::info!("  Overload: {}", BinaryInfo::detect(overload.bytes).description());
+    }
     
     // Validate compatibility
-    if !base_info.is_compatible_with(&overload_info) {
-        anyhow::bail!(
-            "❌ Binary mismatch! Base is {} but overload is {}. Both binaries must have the same architecture and OS.",
-            base_info.description(),
-            overload_info.description()
-        );
-    }
+    check_payload_platforms(&base_info, overload.bytes, None)?;
     
     if !base_info.is_supported() {
-        anyhow::bail!(
-            "❌ Unsupported binary: {}. Supported: x86/x86-64/ARM/ARM64 on Linux/Windows/macOS",
+        return Err(MergeError::UnsupportedPlatform(format!(
+            "Unsupported binary: {}. Supported: x86/x86-64/ARM/ARM64 on Linux/Windows/macOS",
             base_info.description()
-        );
+        )).into());
     }
     
     log::info!("✅ Binary validation passed: {}", base_info.description());
     
-    // Create temp directory
-    fs::create_dir_all(temp_dir)?;
-    let work_dir = TempDir::new_in(temp_dir)?;
+    // Removed when this returns, whether the merge succeeded or not
+    let work_dir = workspaces.create()?;
     let work_path = work_dir.path();
     
     log::info!("Working directory: {}", work_path.display());
     
-    // Handle MergeMode by swapping binaries if necessary
-    // If mode is Before, we treat overload as the "base" (primary) in some contexts,
-    // but for loader-stub, it runs both. 
-    // However, to respect the "Before" semantics (Overload runs "before" Base?), 
-    // we might want to swap them if the stub executes them in order.
-    // For now, we'll pass them as is, but log the mode.
+    // The stub reads the execution order from the footer
     log::info!("Merge mode: {:?} (Using unified V2 loader-stub)", mode);
 
     // Use V2 merger for all platforms
-    // Default settings for basic merge: grace_period=0, network_failure_kill_count=0
-    let merged_path_str = v2::merge_v2(
-        base_data,
-        overload_data,
+    // Default settings for basic merge: no health monitoring, only sync mode
+    let options = StubOptions {
+        sync_mode: sync,
+        order: mode.footer_order(),
+        ..StubOptions::default()
+    };
+    let merged_path_str = StubAppend.merge(MergeJob {
+        base,
+        overload,
         work_path,
-        &base_info,
+        base_info: &base_info,
         task_id,
-        redis_url,
-        0, // grace_period
-        sync, // sync_mode
-        0, // network_failure_kill_count
-    ).await?;
+        progress,
+        stubs,
+        options: &options,
+        cancel,
+    }).await?;
     
     let merged_path = PathBuf::from(merged_path_str);
 
-    // Copy to permanent location with UUID
-    let final_path = PathBuf::from(temp_dir)
+    // Move to permanent location with UUID; the work dir is on the same filesystem
+    let final_path = workspaces.root()
         .join(format!("merged_{}.bin", uuid::Uuid::new_v4()));
     
-    fs::copy(&merged_path, &final_path)?;
+    fs::rename(&merged_path, &final_path)?;
     
     log::info!("✅ Final merged binary: {}", final_path.display());
     
@@ -93,49 +158,51 @@ pub async fn merge_binaries(
 
 /// Stop-on-exit merge entry point
 /// Now uses the V2 implementation with default settings
-pub async fn merge_stop_on_exit(
-    base_data: &[u8],
-    overload_data: &[u8],
+pub async fn merge_stop_on_exit<'a>(
+    base: impl Into<MergeInput<'a>>,
+    overload: impl Into<MergeInput<'a>>,
     work_path: &std::path::Path,
     base_info: &BinaryInfo,
     task_id: &str,
-    redis_url: &str,
+    progress: &SharedProgressSink,
+    stubs: &StubRegistry,
+    cancel: &CancelToken,
 ) -> Result<String> {
     // Use V2 with defaults: grace_period=0, sync_mode=false, network_failure_kill_count=0
-    v2::merge_v2(
-        base_data,
-        overload_data,
+    StubAppend.merge(MergeJob {
+        base: base.into(),
+        overload: overload.into(),
         work_path,
         base_info,
         task_id,
-        redis_url,
-        0,
-        false,
-        0
-    ).await
+        progress,
+        stubs,
+        options: &StubOptions::default(),
+        cancel,
+    }).await
 }
 
 /// V2 merge entry point with advanced health monitoring
-pub async fn merge_v2_stop_on_exit(
-    base_data: &[u8],
-    overload_data: &[u8],
+pub async fn merge_v2_stop_on_exit<'a>(
+    base: impl Into<MergeInput<'a>>,
+    overload: impl Into<MergeInput<'a>>,
     work_path: &std::path::Path,
     base_info: &BinaryInfo,
     task_id: &str,
-    redis_url: &str,
-    grace_period: u32,
-    sync_mode: bool,
-    network_failure_kill_count: u32,
+    progress: &SharedProgressSink,
+    stubs: &StubRegistry,
+    options: &StubOptions,
+    cancel: &CancelToken,
 ) -> Result<String> {
-    v2::merge_v2(
-        base_data,
-        overload_data,
+    StubAppend.merge(MergeJob {
+        base: base.into(),
+        overload: overload.into(),
         work_path,
         base_info,
         task_id,
-        redis_url,
-        grace_period,
-        sync_mode,
-        network_failure_kill_count
-    ).await
+        progress,
+        stubs,
+        options,
+        cancel,
+    }).await
 }
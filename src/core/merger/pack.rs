@@ -0,0 +1,298 @@
+//! Shrinking merged binaries after assembly (`StubOptions::pack`)
+//!
+//! An append-strategy binary keeps its layout: base and the overload are
+//! replaced by LZ4 blocks the stub unpacks before running them (see
+//! `weaver_abi::pack`), the sections behind them move up and the footer is
+//! rewritten and resealed. When `upx` is on the server's `PATH` the stub in
+//! front is compressed with it too, unless that would break a code signature:
+//! on macOS, where Apple Silicon refuses unsigned code, and for Windows stubs
+//! carrying an Authenticode signature. A compiled loader is an ordinary
+//! executable and goes through `upx` as a whole.
+
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::core::binary::{BinaryInfo, OperatingSystem};
+use crate::core::jobs::CancelToken;
+use weaver_abi::footer::{ConfigFooter, ImageChecksum, HARDEN_CHECKSUM, PACKING_LZ4, PACKING_NONE};
+use weaver_abi::pack;
+
+/// `upx` as found on the server's `PATH`
+#[derive(Debug)]
+pub struct Upx {
+    pub path: PathBuf,
+    /// First line of `upx --version`, e.g. `upx 4.2.4`
+    pub version: String,
+}
+
+/// The server's `upx`, looked up on first use
+pub fn upx() -> Option<&'static Upx> {
+    static UPX: OnceLock<Option<Upx>> = OnceLock::new();
+    UPX.get_or_init(|| {
+        let name = if cfg!(windows) { "upx.exe" } else { "upx" };
+        let path = std::env::var_os("PATH")?;
+        let path = std::env::split_paths(&path).map(|dir| dir.join(name)).find(|candidate| candidate.is_file())?;
+        let output = std::process::Command::new(&path).arg("--version").output().ok().filter(|output| output.status.success())?;
+        let version = String::from_utf8_lossy(&output.stdout).lines().next().unwrap_or("upx").trim().to_string();
+        Some(Upx { path, version })
+    })
+    .as_ref()
+}
+
+/// Whether compressing this stub with UPX would break its code signature
+pub fn upx_breaks_signing(os: OperatingSystem, stub: &[u8]) -> bool {
+    match os {
+        OperatingSystem::MacOS => true,
+        OperatingSystem::Windows => has_authenticode_signature(stub),
+        _ => false,
+    }
+}
+
+/// Whether the PE's certificate table (data directory 4) is filled in
+fn has_authenticode_signature(pe: &[u8]) -> bool {
+    let read_u32 = |at: usize| pe.get(at..at + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+    let Some(header) = read_u32(0x3c).map(|offset| offset as usize) else {
+        return false;
+    };
+    if pe.get(header..header + 4) != Some(&b"PE\0\0"[..]) {
+        return false;
+    }
+    let optional = header + 24;
+    let directories = match pe.get(optional..optional + 2) {
+        Some([0x0b, 0x01]) => optional + 96,
+        Some([0x0b, 0x02]) => optional + 112,
+        _ => return false,
+    };
+    read_u32(directories + 4 * 8 + 4).is_some_and(|size| size > 0)
+}
+
+/// Pack the payloads of the append-strategy binary at `path` in place
+///
+/// `work_path` holds the stub while UPX compresses it. Returns the size of the
+/// binary before and after.
+pub async fn pack_payloads(path: &Path, base_info: &BinaryInfo, work_path: &Path, cancel: &CancelToken) -> Result<(u64, u64)> {
+    let merged = tokio::fs::read(path).await.context("Failed to read merged binary")?;
+    let stub = usize::try_from(read_footer(&merged)?.base_offset)
+        .ok()
+        .and_then(|end| merged.get(..end))
+        .context("Footer offsets are outside the merged binary")?;
+    let upx_stub = match upx() {
+        Some(_) if upx_breaks_signing(base_info.os, stub) => {
+            log::info!("🗜️  Leaving the {:?} stub uncompressed, UPX would break its code signature", base_info.os);
+            None
+        }
+        Some(upx) => compress_stub(upx, stub, work_path, cancel)
+            .await
+            .inspect_err(|e| log::warn!("⚠️  Leaving the stub uncompressed: {:#}", e))
+            .ok(),
+        None => None,
+    };
+    cancel.check()?;
+
+    let packed = pack_image(&merged, upx_stub.as_deref())?;
+    let sizes = (merged.len() as u64, packed.len() as u64);
+    tokio::fs::write(path, packed).await.context("Failed to write packed binary")?;
+    log::info!("🗜️  Packed merged binary: {} -> {} bytes", sizes.0, sizes.1);
+    Ok(sizes)
+}
+
+fn read_footer(merged: &[u8]) -> Result<ConfigFooter> {
+    let footer_start = merged.len().checked_sub(ConfigFooter::SIZE).context("Merged binary has no footer")?;
+    ConfigFooter::from_bytes(merged[footer_start..].try_into().expect("footer is ConfigFooter::SIZE bytes"))
+        .context("Merged binary has no footer")
+}
+
+/// `merged` with its payloads packed and `stub`, if given, in front instead of its own
+fn pack_image(merged: &[u8], stub: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut footer = read_footer(merged)?;
+    if footer.packing != PACKING_NONE {
+        anyhow::bail!("Merged binary is packed already");
+    }
+    let footer_start = (merged.len() - ConfigFooter::SIZE) as u64;
+    let section = |offset: u64, size: u64| {
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(offset + size).ok())
+            .and_then(|(start, end)| merged.get(start..end))
+            .context("Footer offsets are outside the merged binary")
+    };
+    let stub = match stub {
+        Some(stub) => stub,
+        None => section(0, footer.base_offset)?,
+    };
+    let base = section(footer.base_offset, footer.base_size)?;
+    let overload = section(footer.overload_offset, footer.overload_size)?;
+    let rest_start = footer.overload_offset + footer.overload_size;
+    let rest = section(rest_start, footer_start.checked_sub(rest_start).context("Footer offsets overlap the footer")?)?;
+    let (packed_base, packed_overload) = (pack_section(base), pack_section(overload));
+
+    // Everything behind the overload moves by the same distance
+    let base_size = packed_base.as_ref().map_or(base.len(), Vec::len) as u64;
+    let overload_size = packed_overload.as_ref().map_or(overload.len(), Vec::len) as u64;
+    let new_rest_start = stub.len() as u64 + base_size + overload_size;
+    let moved = |offset: u64, size: u64| if size > 0 { offset - rest_start + new_rest_start } else { offset };
+    footer.seccomp_offset = moved(footer.seccomp_offset, footer.seccomp_size);
+    footer.labels_offset = moved(footer.labels_offset, footer.labels_size);
+    footer.interpreter_offset = moved(footer.interpreter_offset, footer.interpreter_size);
+    footer.install_files_offset = moved(footer.install_files_offset, footer.install_files_size);
+    footer.assets_offset = moved(footer.assets_offset, footer.assets_size);
+    footer.base_offset = stub.len() as u64;
+    footer.overload_offset = footer.base_offset + base_size;
+    footer.packing = PACKING_LZ4;
+    if packed_base.is_some() {
+        (footer.base_size, footer.base_unpacked_size) = (base_size, base.len() as u64);
+    }
+    if packed_overload.is_some() {
+        (footer.overload_size, footer.overload_unpacked_size) = (overload_size, overload.len() as u64);
+    }
+
+    let mut footer_raw = [0u8; ConfigFooter::SIZE];
+    footer_raw.copy_from_slice(footer.as_bytes());
+    let parts = [stub, packed_base.as_deref().unwrap_or(base), packed_overload.as_deref().unwrap_or(overload), rest];
+    if footer.hardening & HARDEN_CHECKSUM != 0 {
+        let mut checksum = ImageChecksum::new();
+        for part in parts {
+            checksum.update(part);
+        }
+        checksum.update_footer(&footer_raw);
+        checksum.write_into(&mut footer_raw);
+    }
+    let mut packed = Vec::with_capacity(parts.iter().map(|part| part.len()).sum::<usize>() + ConfigFooter::SIZE);
+    for part in parts {
+        packed.extend_from_slice(part);
+    }
+    packed.extend_from_slice(&footer_raw);
+    Ok(packed)
+}
+
+/// Compress the ordinary executable at `path` in place with UPX
+///
+/// Used for outputs without a stub and footer, such as a compiled loader. An
+/// executable UPX can't compress is left as it is.
+pub async fn pack_executable(path: &Path, cancel: &CancelToken) -> Result<(u64, u64)> {
+    let upx = upx().context("pack needs upx on the server's PATH for this merge strategy")?;
+    let original_size = tokio::fs::metadata(path).await.context("Failed to read merged binary")?.len();
+    let packed_path = path.with_extension("upx");
+    if let Err(e) = run_upx(upx, path, &packed_path, cancel).await {
+        cancel.check()?;
+        log::warn!("⚠️  Leaving the merged binary uncompressed: {:#}", e);
+        return Ok((original_size, original_size));
+    }
+    tokio::fs::rename(&packed_path, path).await.context("Failed to replace merged binary")?;
+    let packed_size = tokio::fs::metadata(path).await.context("Failed to read packed binary")?.len();
+    log::info!("🗜️  Packed merged binary with {}: {} -> {} bytes", upx.version, original_size, packed_size);
+    Ok((original_size, packed_size))
+}
+
+/// `data` as an LZ4 block, `None` when that isn't smaller
+fn pack_section(data: &[u8]) -> Option<Vec<u8>> {
+    let mut packed = vec![0u8; pack::max_packed_len(data.len())];
+    let len = pack::pack(data, &mut packed);
+    packed.truncate(len);
+    (len < data.len()).then_some(packed)
+}
+
+async fn compress_stub(upx: &Upx, stub: &[u8], work_path: &Path, cancel: &CancelToken) -> Result<Vec<u8>> {
+    let stub_path = work_path.join("stub.bin");
+    let compressed_path = work_path.join("stub.upx");
+    tokio::fs::write(&stub_path, stub).await.context("Failed to write stub")?;
+    run_upx(upx, &stub_path, &compressed_path, cancel).await?;
+    tokio::fs::read(&compressed_path).await.context("Failed to read compressed stub")
+}
+
+/// Compress `input` into `output`, then have UPX test the result; `output`
+/// is removed again when either step fails or `cancel` fires
+async fn run_upx(upx: &Upx, input: &Path, output: &Path, cancel: &CancelToken) -> Result<()> {
+    let compress = [OsStr::new("-q"), OsStr::new("--best"), OsStr::new("-o"), output.as_os_str(), input.as_os_str()];
+    let test = [OsStr::new("-q"), OsStr::new("-t"), output.as_os_str()];
+    for (step, args) in [("compressing", &compress[..]), ("testing", &test[..])] {
+        let run = tokio::process::Command::new(&upx.path).args(args).kill_on_drop(true).output();
+        let result = match cancel.run_until_cancelled(run).await {
+            Ok(result) => result.with_context(|| format!("Failed to run {}", upx.path.display()))?,
+            Err(cancelled) => {
+                let _ = tokio::fs::remove_file(output).await;
+                return Err(cancelled.into());
+            }
+        };
+        if !result.status.success() {
+            let _ = tokio::fs::remove_file(output).await;
+            anyhow::bail!("upx failed {}: {}", step, String::from_utf8_lossy(&result.stderr).trim());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weaver_abi::footer::{FOOTER_VERSION, MAGIC_BYTES};
+
+    #[test]
+    fn test_packed_image_unpacks_to_the_payloads() {
+        let base = b"base payload ".repeat(500);
+        let overload = b"overload".repeat(300);
+        let labels = br#"{"team":"core"}"#;
+        let footer = ConfigFooter {
+            magic: *MAGIC_BYTES,
+            version: FOOTER_VERSION,
+            size: ConfigFooter::SIZE as u32,
+            base_offset: 4,
+            base_size: base.len() as u64,
+            overload_offset: 4 + base.len() as u64,
+            overload_size: overload.len() as u64,
+            labels_offset: 4 + (base.len() + overload.len()) as u64,
+            labels_size: labels.len() as u64,
+            hardening: HARDEN_CHECKSUM,
+            ..unsafe { std::mem::zeroed() }
+        };
+        let merged = [&b"stub"[..], &base, &overload, labels, footer.as_bytes()].concat();
+
+        let packed = pack_image(&merged, None).unwrap();
+        let footer = read_footer(&packed).unwrap();
+        assert!(footer.base_size < base.len() as u64 / 10 && footer.overload_size < overload.len() as u64 / 10);
+        let section = |offset: u64, size: u64| &packed[offset as usize..(offset + size) as usize];
+        let unpacked = |offset, size, unpacked_size: Option<u64>| {
+            let mut out = vec![0u8; unpacked_size.unwrap() as usize];
+            pack::unpack(section(offset, size), &mut out).unwrap();
+            out
+        };
+        assert_eq!(section(0, footer.base_offset), b"stub");
+        assert_eq!(unpacked(footer.base_offset, footer.base_size, footer.unpacked_size(true)), base);
+        assert_eq!(unpacked(footer.overload_offset, footer.overload_size, footer.unpacked_size(false)), overload);
+        assert_eq!(section(footer.labels_offset, footer.labels_size), labels);
+        // Resealed over the packed image
+        let (image, footer_raw) = packed.split_at(packed.len() - ConfigFooter::SIZE);
+        let mut checksum = ImageChecksum::new();
+        checksum.update(image);
+        checksum.update_footer(footer_raw.try_into().unwrap());
+        assert_eq!(checksum.finish(), footer.image_checksum);
+
+        assert!(pack_image(&packed, None).is_err());
+        // A compressed stub moves everything behind it
+        let restubbed = pack_image(&merged, Some(b"compressed stub")).unwrap();
+        let footer = read_footer(&restubbed).unwrap();
+        assert_eq!(footer.base_offset, 15);
+        assert_eq!(&restubbed[footer.labels_offset as usize..][..labels.len()], labels);
+    }
+
+    #[test]
+    fn test_authenticode_signature_is_detected() {
+        // DOS header pointing at a PE32+ header with an empty certificate table
+        let mut pe = vec![0u8; 0x200];
+        pe[0x3c] = 0x80;
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x98..0x9a].copy_from_slice(&[0x0b, 0x02]);
+        assert!(!has_authenticode_signature(&pe));
+        assert!(!upx_breaks_signing(OperatingSystem::Windows, &pe));
+
+        let certificate_size = 0x98 + 112 + 4 * 8 + 4;
+        pe[certificate_size..certificate_size + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert!(has_authenticode_signature(&pe));
+        assert!(upx_breaks_signing(OperatingSystem::Windows, &pe));
+        assert!(upx_breaks_signing(OperatingSystem::MacOS, b"\xcf\xfa\xed\xfe"));
+        assert!(!upx_breaks_signing(OperatingSystem::Linux, b"\x7fELF"));
+        assert!(!has_authenticode_signature(b"MZ"));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+
+use crate::core::binary::Architecture;
+use weaver_abi::seccomp::{
+    SeccompHeader, MAX_SECCOMP_SYSCALLS, SECCOMP_APPLY_BASE, SECCOMP_APPLY_OVERLOAD, SECCOMP_DEFAULT_ERRNO,
+    SECCOMP_DEFAULT_KILL, SECCOMP_DEFAULT_LOG, SECCOMP_MAGIC,
+};
+
+/// Syscalls the stub itself needs after installing the filter
+const ALWAYS_ALLOWED: [&str; 2] = ["execveat", "exit_group"];
+
+/// What a syscall outside the allowlist does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompAction {
+    /// Fail with EPERM
+    #[default]
+    Errno,
+    /// Kill the payload
+    Kill,
+    /// Allow but log to the kernel audit log
+    Log,
+}
+
+/// Payloads the filter is installed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeccompTarget {
+    #[default]
+    Overload,
+    Base,
+    Both,
+}
+
+/// Seccomp allowlist attached to a merge (Linux only)
+///
+/// Accepted as JSON, e.g.
+/// `{"default_action": "kill", "apply_to": "overload", "allow": ["read", "write", "openat"]}`.
+/// Names are resolved for the base binary's architecture at merge time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeccompProfile {
+    #[serde(default)]
+    pub default_action: SeccompAction,
+    #[serde(default)]
+    pub apply_to: SeccompTarget,
+    pub allow: Vec<String>,
+}
+
+impl SeccompProfile {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let profile: SeccompProfile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid seccomp profile: {}", e))?;
+        if profile.allow.is_empty() {
+            return Err("Seccomp profile must allow at least one syscall".to_string());
+        }
+        if profile.allow.len() > MAX_SECCOMP_SYSCALLS {
+            return Err(format!("Seccomp profile allows more than {} syscalls", MAX_SECCOMP_SYSCALLS));
+        }
+        Ok(profile)
+    }
+
+    /// Serialize for the stub, with syscall numbers for `arch`
+    pub fn compile(&self, arch: Architecture) -> Result<Vec<u8>, String> {
+        let audit_arch = audit_arch(arch).ok_or_else(|| format!("Seccomp profiles are not supported on {:?}", arch))?;
+
+        let mut numbers = BTreeSet::new();
+        for name in self.allow.iter().map(String::as_str).chain(ALWAYS_ALLOWED) {
+            let number = syscall_number(arch, name)
+                .ok_or_else(|| format!("Unknown syscall {:?} for {:?}", name, arch))?;
+            numbers.insert(number);
+        }
+        if numbers.len() > MAX_SECCOMP_SYSCALLS {
+            return Err(format!("Seccomp profile allows more than {} syscalls", MAX_SECCOMP_SYSCALLS));
+        }
+
+        let header = SeccompHeader {
+            magic: *SECCOMP_MAGIC,
+            audit_arch,
+            default_action: match self.default_action {
+                SeccompAction::Errno => SECCOMP_DEFAULT_ERRNO,
+                SeccompAction::Kill => SECCOMP_DEFAULT_KILL,
+                SeccompAction::Log => SECCOMP_DEFAULT_LOG,
+            },
+            apply_to: match self.apply_to {
+                SeccompTarget::Overload => SECCOMP_APPLY_OVERLOAD,
+                SeccompTarget::Base => SECCOMP_APPLY_BASE,
+                SeccompTarget::Both => SECCOMP_APPLY_BASE | SECCOMP_APPLY_OVERLOAD,
+            },
+            count: numbers.len() as u32,
+        };
+
+        let mut bytes = header.as_bytes().to_vec();
+        for number in numbers {
+            bytes.extend_from_slice(&number.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+}
+
+/// `AUDIT_ARCH_*` value for the architectures Linux stubs exist for
+fn audit_arch(arch: Architecture) -> Option<u32> {
+    match arch {
+        Architecture::X86_64 => Some(0xC000_003E),
+        Architecture::X86 => Some(0x4000_0003),
+        Architecture::AArch64 => Some(0xC000_00B7),
+        Architecture::ARM => Some(0x4000_0028),
+        _ => None,
+    }
+}
+
+fn syscall_number(arch: Architecture, name: &str) -> Option<u32> {
+    let number = match arch {
+        Architecture::X86_64 => name.parse::<syscalls::x86_64::Sysno>().ok()?.id(),
+        Architecture::X86 => name.parse::<syscalls::x86::Sysno>().ok()?.id(),
+        Architecture::AArch64 => name.parse::<syscalls::aarch64::Sysno>().ok()?.id(),
+        Architecture::ARM => name.parse::<syscalls::arm::Sysno>().ok()?.id(),
+        _ => return None,
+    };
+    u32::try_from(number).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_compiles_for_target_arch() {
+        let profile = SeccompProfile::from_json(r#"{"default_action": "kill", "allow": ["read", "write", "read"]}"#).unwrap();
+        assert_eq!(profile.apply_to, SeccompTarget::Overload);
+
+        let bytes = profile.compile(Architecture::X86_64).unwrap();
+        let (header, numbers) = SeccompHeader::parse(&bytes).unwrap();
+        assert_eq!(header.default_action, SECCOMP_DEFAULT_KILL);
+        // read, write, execveat and exit_group, deduplicated and sorted
+        let numbers: Vec<u32> = weaver_abi::seccomp::syscalls(numbers).collect();
+        assert_eq!(numbers, vec![0, 1, 231, 322]);
+
+        // Same names, different table
+        let bytes = profile.compile(Architecture::AArch64).unwrap();
+        let numbers: Vec<u32> = weaver_abi::seccomp::syscalls(SeccompHeader::parse(&bytes).unwrap().1).collect();
+        assert_eq!(numbers, vec![63, 64, 94, 281]);
+
+        let bytes = profile.compile(Architecture::ARM).unwrap();
+        let numbers: Vec<u32> = weaver_abi::seccomp::syscalls(SeccompHeader::parse(&bytes).unwrap().1).collect();
+        assert_eq!(numbers, vec![3, 4, 248, 387]);
+    }
+
+    #[test]
+    fn test_profile_rejects_bad_input() {
+        assert!(SeccompProfile::from_json("not json").is_err());
+        assert!(SeccompProfile::from_json(r#"{"allow": []}"#).is_err());
+        assert!(SeccompProfile::from_json(r#"{"allow": ["read"], "deny": ["write"]}"#).is_err());
+        assert!(SeccompProfile::from_json(r#"{"default_action": "trap", "allow": ["read"]}"#).is_err());
+
+        let profile = SeccompProfile::from_json(r#"{"allow": ["not_a_syscall"]}"#).unwrap();
+        assert!(profile.compile(Architecture::X86_64).is_err());
+        let profile = SeccompProfile::from_json(r#"{"allow": ["read"]}"#).unwrap();
+        assert!(profile.compile(Architecture::MIPS).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Most bytes moved per call; progress is reported between chunks
+pub const CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// A payload to merge
+///
+/// `bytes` is what detection and hashing look at. When the same content is
+/// also on disk (an upload or a staged binary), `file` points at it and the
+/// assembler copies it into the output inside the kernel instead.
+#[derive(Debug, Clone, Copy)]
+pub struct MergeInput<'a> {
+    pub bytes: &'a [u8],
+    pub file: Option<&'a Path>,
+}
+
+impl<'a> MergeInput<'a> {
+    pub fn file(bytes: &'a [u8], path: &'a Path) -> Self {
+        Self { bytes, file: Some(path) }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The backing file, if it still holds exactly `bytes.len()` bytes
+    fn open(&self) -> Option<File> {
+        let file = File::open(self.file?).ok()?;
+        let len = file.metadata().ok()?.len();
+        (len == self.len()).then_some(file)
+    }
+}
+
+impl<'a> From<&'a [u8]> for MergeInput<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self { bytes, file: None }
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for MergeInput<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Self { bytes, file: None }
+    }
+}
+
+/// Appends the parts of a merged binary to its output file
+pub struct Splicer {
+    output: File,
+    written: u64,
+    /// copy_file_range isn't usable between these files; copy through `buffer`
+    buffered: bool,
+    buffer: Vec<u8>,
+}
+
+/// Where a part is read from while it is being appended
+pub enum PartSource<'a> {
+    Memory(std::slice::Chunks<'a, u8>),
+    File(File),
+}
+
+impl Splicer {
+    pub fn new(output: File) -> Self {
+        Self { output, written: 0, buffered: false, buffer: Vec::new() }
+    }
+
+    /// Bytes appended so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn output(&self) -> &File {
+        &self.output
+    }
+
+    /// Start appending `input`, from its file when that is still intact
+    pub fn source<'a>(&self, input: &MergeInput<'a>) -> PartSource<'a> {
+        match input.open() {
+            Some(file) => PartSource::File(file),
+            None => PartSource::Memory(input.bytes.chunks(CHUNK_SIZE)),
+        }
+    }
+
+    /// Append the next chunk of `source`, returning its size (0 once it's done)
+    pub fn append_chunk(&mut self, source: &mut PartSource<'_>) -> io::Result<usize> {
+        let copied = match source {
+            PartSource::Memory(chunks) => match chunks.next() {
+                Some(chunk) => {
+                    self.output.write_all(chunk)?;
+                    chunk.len()
+                }
+                None => 0,
+            },
+            PartSource::File(file) => self.splice_chunk(file)?,
+        };
+        self.written += copied as u64;
+        Ok(copied)
+    }
+
+    fn splice_chunk(&mut self, source: &mut File) -> io::Result<usize> {
+        if !self.buffered {
+            match copy_range(source, &self.output) {
+                Ok(copied) => return Ok(copied),
+                Err(e) if is_unsupported(&e) => {
+                    log::debug!("copy_file_range unavailable ({}), using buffered copies", e);
+                    self.buffered = true;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.buffer.resize(CHUNK_SIZE, 0);
+        let read = source.read(&mut self.buffer)?;
+        self.output.write_all(&self.buffer[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn copy_range(source: &File, output: &File) -> io::Result<usize> {
+    nix::fcntl::copy_file_range(source, None, output, None, CHUNK_SIZE).map_err(io::Error::from)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn copy_range(_source: &File, _output: &File) -> io::Result<usize> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+/// Errors meaning the kernel or filesystem can't copy between these files
+fn is_unsupported(error: &io::Error) -> bool {
+    use nix::errno::Errno;
+
+    error.kind() == io::ErrorKind::Unsupported
+        || matches!(
+            error.raw_os_error().map(Errno::from_raw),
+            Some(Errno::ENOSYS | Errno::EXDEV | Errno::EINVAL | Errno::EOPNOTSUPP | Errno::EPERM)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_and_memory_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("base");
+        let base: Vec<u8> = (0..CHUNK_SIZE + 100).map(|i| i as u8).collect();
+        std::fs::write(&base_path, &base).unwrap();
+
+        let output_path = dir.path().join("merged");
+        let mut splicer = Splicer::new(File::create(&output_path).unwrap());
+        for input in [MergeInput::from(&b"stub"[..]), MergeInput::file(&base, &base_path), MergeInput::from(&b"footer"[..])] {
+            let mut source = splicer.source(&input);
+            assert_eq!(matches!(source, PartSource::File(_)), input.file.is_some());
+            while splicer.append_chunk(&mut source).unwrap() > 0 {}
+        }
+
+        let merged = std::fs::read(&output_path).unwrap();
+        assert_eq!(splicer.written(), merged.len() as u64);
+        assert_eq!(&merged[..4], b"stub");
+        assert_eq!(&merged[4..4 + base.len()], &base[..]);
+        assert_eq!(&merged[4 + base.len()..], b"footer");
+    }
+
+    #[test]
+    fn test_changed_file_falls_back_to_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("base");
+        std::fs::write(&path, b"truncated").unwrap();
+
+        let splicer = Splicer::new(File::create(dir.path().join("merged")).unwrap());
+        assert!(matches!(splicer.source(&MergeInput::file(b"the real content", &path)), PartSource::Memory(_)));
+    }
+}
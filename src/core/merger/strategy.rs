@@ -0,0 +1,252 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::compile::CompileLoader;
+use super::error::MergeError;
+use super::pack;
+use super::splice::MergeInput;
+use super::stubs::{Stub, StubRegistry};
+use super::v2::{self, StubOptions};
+use crate::core::binary::BinaryInfo;
+use crate::core::jobs::CancelToken;
+use crate::core::progress::SharedProgressSink;
+
+/// Everything a strategy gets for one merge
+///
+/// Inputs have been validated and checked against each other; the strategy
+/// writes its output somewhere under `work_path`.
+pub struct MergeJob<'a> {
+    pub base: MergeInput<'a>,
+    pub overload: MergeInput<'a>,
+    pub work_path: &'a Path,
+    pub base_info: &'a BinaryInfo,
+    pub task_id: &'a str,
+    pub progress: &'a SharedProgressSink,
+    pub stubs: &'a StubRegistry,
+    pub options: &'a StubOptions,
+    pub cancel: &'a CancelToken,
+}
+
+/// Everything a strategy gets to plan a merge without running it
+pub struct PlanJob<'a> {
+    pub base: &'a [u8],
+    pub overload: &'a [u8],
+    pub base_info: &'a BinaryInfo,
+    pub stubs: &'a StubRegistry,
+    pub options: &'a StubOptions,
+}
+
+/// What a merge would produce, worked out without producing it
+#[derive(Debug, Clone)]
+pub struct MergePlan {
+    /// Loader stub that would be prepended, for strategies that use one
+    pub stub: Option<Stub>,
+    /// Size of the merged binary; a lower bound when the strategy can't know it up front
+    pub output_size: u64,
+    /// Requested options the target ignores or that undo each other
+    pub warnings: Vec<String>,
+}
+
+/// One way of weaving base and overload into a single binary
+#[async_trait]
+pub trait MergeStrategy: Send + Sync {
+    /// Name clients select the strategy by
+    fn name(&self) -> &'static str;
+
+    /// Whether this strategy can produce a binary for base's platform on this server
+    fn supports(&self, base_info: &BinaryInfo, stubs: &StubRegistry) -> bool;
+
+    /// Weave the job's inputs and return the path of the merged binary
+    async fn merge(&self, job: MergeJob<'_>) -> Result<String>;
+
+    /// Check the job as `merge` would and predict its output, without writing anything
+    ///
+    /// The default checks nothing and predicts the payloads back to back.
+    fn plan(&self, job: &PlanJob<'_>) -> Result<MergePlan> {
+        Ok(MergePlan { stub: None, output_size: (job.base.len() + job.overload.len()) as u64, warnings: Vec::new() })
+    }
+}
+
+/// Prepend a pre-built loader stub and append the payloads behind it
+pub struct StubAppend;
+
+impl StubAppend {
+    pub const NAME: &'static str = "append";
+}
+
+#[async_trait]
+impl MergeStrategy for StubAppend {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn supports(&self, base_info: &BinaryInfo, stubs: &StubRegistry) -> bool {
+        stubs.get(base_info.os, base_info.arch).is_some()
+    }
+
+    async fn merge(&self, job: MergeJob<'_>) -> Result<String> {
+        let merged_path = v2::merge_v2(
+            job.base,
+            job.overload,
+            job.work_path,
+            job.base_info,
+            job.task_id,
+            job.progress,
+            job.stubs,
+            job.options,
+            job.cancel,
+        )
+        .await?;
+        if job.options.pack {
+            job.cancel.check()?;
+            pack::pack_payloads(Path::new(&merged_path), job.base_info, job.work_path, job.cancel).await?;
+        }
+        Ok(merged_path)
+    }
+
+    fn plan(&self, job: &PlanJob<'_>) -> Result<MergePlan> {
+        v2::plan_v2(job)
+    }
+}
+
+/// Strategies a server can merge with, in order of preference
+///
+/// A request names a strategy or gets the first one that supports its base
+/// platform.
+#[derive(Clone)]
+pub struct MergeStrategies {
+    strategies: Vec<Arc<dyn MergeStrategy>>,
+}
+
+impl Default for MergeStrategies {
+    fn default() -> Self {
+        Self::new(vec![Arc::new(StubAppend), Arc::new(CompileLoader)])
+    }
+}
+
+impl MergeStrategies {
+    pub fn new(strategies: Vec<Arc<dyn MergeStrategy>>) -> Self {
+        Self { strategies }
+    }
+
+    /// Add a strategy behind the existing ones; replaces one of the same name
+    pub fn register(&mut self, strategy: Arc<dyn MergeStrategy>) {
+        self.strategies.retain(|existing| existing.name() != strategy.name());
+        self.strategies.push(strategy);
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.strategies.iter().map(|strategy| strategy.name()).collect()
+    }
+
+    /// Names of the strategies usable for `base_info`, in order of preference
+    pub fn usable(&self, base_info: &BinaryInfo, stubs: &StubRegistry) -> Vec<&'static str> {
+        self.strategies
+            .iter()
+            .filter(|strategy| strategy.supports(base_info, stubs))
+            .map(|strategy| strategy.name())
+            .collect()
+    }
+
+    /// The strategy named `requested`, or the first one usable for base's platform
+    ///
+    /// Without a usable strategy the first one is returned anyway, so the
+    /// merge fails with its own, more specific error.
+    pub fn select(
+        &self,
+        requested: Option<&str>,
+        base_info: &BinaryInfo,
+        stubs: &StubRegistry,
+    ) -> Result<Arc<dyn MergeStrategy>, MergeError> {
+        let Some(requested) = requested else {
+            let usable = self.strategies.iter().find(|strategy| strategy.supports(base_info, stubs));
+            return usable.or(self.strategies.first()).cloned().ok_or_else(|| {
+                MergeError::UnsupportedPlatform(format!("No merge strategy supports {}", base_info.description()))
+            });
+        };
+        let strategy = self
+            .strategies
+            .iter()
+            .find(|strategy| strategy.name() == requested)
+            .ok_or_else(|| MergeError::UnknownStrategy { requested: requested.to_string(), available: self.names() })?;
+        if !strategy.supports(base_info, stubs) {
+            return Err(MergeError::UnsupportedPlatform(format!(
+                "Merge strategy {} isn't usable for {} on this server",
+                requested,
+                base_info.description()
+            )));
+        }
+        Ok(strategy.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::binary::{Architecture, OperatingSystem};
+
+    /// Usable for one OS only; "merges" by naming the base's platform
+    struct Mock(&'static str, OperatingSystem);
+
+    #[async_trait]
+    impl MergeStrategy for Mock {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn supports(&self, base_info: &BinaryInfo, _stubs: &StubRegistry) -> bool {
+            base_info.os == self.1
+        }
+
+        async fn merge(&self, job: MergeJob<'_>) -> Result<String> {
+            Ok(job.base_info.description())
+        }
+    }
+
+    #[test]
+    fn test_select_by_name_or_platform() {
+        let stubs = StubRegistry::load(None);
+        let linux = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
+        let windows = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
+        let macos = BinaryInfo::new(OperatingSystem::MacOS, Architecture::AArch64);
+        let strategies = MergeStrategies::new(vec![
+            Arc::new(Mock("windows", OperatingSystem::Windows)),
+            Arc::new(Mock("linux", OperatingSystem::Linux)),
+        ]);
+
+        assert_eq!(strategies.select(None, &windows, &stubs).unwrap().name(), "windows");
+        assert_eq!(strategies.select(None, &linux, &stubs).unwrap().name(), "linux");
+        assert_eq!(strategies.usable(&linux, &stubs), vec!["linux"]);
+        // Nothing usable: the first one reports why when it runs
+        assert_eq!(strategies.select(None, &macos, &stubs).unwrap().name(), "windows");
+        assert!(matches!(strategies.select(Some("windows"), &linux, &stubs), Err(MergeError::UnsupportedPlatform(_))));
+        assert_eq!(
+            strategies.select(Some("lief"), &linux, &stubs).err(),
+            Some(MergeError::UnknownStrategy { requested: "lief".into(), available: vec!["windows", "linux"] })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mock_strategy_runs_through_the_trait() {
+        let stubs = StubRegistry::load(None);
+        let windows = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
+        let strategy = MergeStrategies::new(vec![Arc::new(Mock("mock", OperatingSystem::Windows))])
+            .select(None, &windows, &stubs)
+            .unwrap();
+        let progress: SharedProgressSink = Arc::new(crate::core::progress::MemoryProgressSink::new());
+        let job = MergeJob {
+            base: MergeInput::from(&b"base"[..]),
+            overload: MergeInput::from(&b"overload"[..]),
+            work_path: Path::new("/nonexistent"),
+            base_info: &windows,
+            task_id: "",
+            progress: &progress,
+            stubs: &stubs,
+            options: &StubOptions::default(),
+            cancel: &CancelToken::new(),
+        };
+        assert_eq!(strategy.merge(job).await.unwrap(), windows.description());
+    }
+}
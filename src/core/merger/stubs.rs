@@ -0,0 +1,591 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use crate::core::binary::{is_script, Abi, Architecture, BinaryInfo, Libc, OperatingSystem};
+use crate::core::digest::sha256_hex;
+use crate::core::merger::MergeError;
+use weaver_abi::footer::{stub_layout, ConfigFooter, FOOTER_VERSION};
+
+/// Every (OS, arch) pair a loader stub is built for
+pub const STUB_PLATFORMS: &[(OperatingSystem, Architecture)] = &[
+    (OperatingSystem::Linux, Architecture::X86_64),
+    (OperatingSystem::Linux, Architecture::X86),
+    (OperatingSystem::Linux, Architecture::AArch64),
+    (OperatingSystem::Linux, Architecture::ARM),
+    (OperatingSystem::Windows, Architecture::X86_64),
+    (OperatingSystem::Windows, Architecture::X86),
+    (OperatingSystem::Windows, Architecture::AArch64),
+    (OperatingSystem::MacOS, Architecture::X86_64),
+    (OperatingSystem::MacOS, Architecture::AArch64),
+];
+
+/// C runtime a stub is built against
+///
+/// Linux stubs come in every flavor; Windows and macOS stubs only in the
+/// default one, filed as `Glibc`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StubFlavor {
+    /// Dynamically linked against glibc
+    #[default]
+    Glibc,
+    /// Statically linked against musl, for Alpine and other musl-based images
+    Musl,
+    /// glibc linked statically, for images with no C library at all
+    Static,
+}
+
+impl StubFlavor {
+    pub const ALL: &'static [StubFlavor] = &[StubFlavor::Glibc, StubFlavor::Musl, StubFlavor::Static];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "glibc" => Ok(StubFlavor::Glibc),
+            "musl" => Ok(StubFlavor::Musl),
+            "static" => Ok(StubFlavor::Static),
+            other => Err(format!("Unknown stub flavor {:?}, expected glibc, musl or static", other)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StubFlavor::Glibc => "glibc",
+            StubFlavor::Musl => "musl",
+            StubFlavor::Static => "static",
+        }
+    }
+}
+
+/// Flavors stubs are built in for an OS, in the order automatic selection tries them
+pub fn stub_flavors(os: OperatingSystem) -> &'static [StubFlavor] {
+    if os == OperatingSystem::Linux {
+        StubFlavor::ALL
+    } else {
+        &[StubFlavor::Glibc]
+    }
+}
+
+/// Every (OS, arch, flavor) a loader stub is built for
+pub fn stub_slots() -> impl Iterator<Item = (OperatingSystem, Architecture, StubFlavor)> {
+    STUB_PLATFORMS
+        .iter()
+        .flat_map(|&(os, arch)| stub_flavors(os).iter().map(move |&flavor| (os, arch, flavor)))
+}
+
+// Compile-time stubs, used when no stub directory is configured or a file in it is missing/invalid
+// Note: These paths point to the /stubs directory in the Docker container, so the
+// `embedded-stubs` feature is only turned on by the Docker builds; elsewhere set WEAVER_STUB_DIR
+#[cfg(feature = "embedded-stubs")]
+fn embedded_stub(os: OperatingSystem, arch: Architecture, flavor: StubFlavor) -> Option<&'static [u8]> {
+    use StubFlavor::{Glibc, Musl, Static};
+
+    let stub: &'static [u8] = match (os, arch, flavor) {
+        (OperatingSystem::Linux, Architecture::X86_64, Glibc) => include_bytes!("/stubs/linux-x86_64-stub"),
+        (OperatingSystem::Linux, Architecture::X86_64, Musl) => include_bytes!("/stubs/linux-x86_64-musl-stub"),
+        (OperatingSystem::Linux, Architecture::X86_64, Static) => include_bytes!("/stubs/linux-x86_64-static-stub"),
+        (OperatingSystem::Linux, Architecture::X86, Glibc) => include_bytes!("/stubs/linux-x86-stub"),
+        (OperatingSystem::Linux, Architecture::X86, Musl) => include_bytes!("/stubs/linux-x86-musl-stub"),
+        (OperatingSystem::Linux, Architecture::X86, Static) => include_bytes!("/stubs/linux-x86-static-stub"),
+        (OperatingSystem::Linux, Architecture::AArch64, Glibc) => include_bytes!("/stubs/linux-aarch64-stub"),
+        (OperatingSystem::Linux, Architecture::AArch64, Musl) => include_bytes!("/stubs/linux-aarch64-musl-stub"),
+        (OperatingSystem::Linux, Architecture::AArch64, Static) => include_bytes!("/stubs/linux-aarch64-static-stub"),
+        (OperatingSystem::Linux, Architecture::ARM, Glibc) => include_bytes!("/stubs/linux-arm-stub"),
+        (OperatingSystem::Linux, Architecture::ARM, Musl) => include_bytes!("/stubs/linux-arm-musl-stub"),
+        (OperatingSystem::Linux, Architecture::ARM, Static) => include_bytes!("/stubs/linux-arm-static-stub"),
+        (OperatingSystem::Windows, Architecture::X86_64, Glibc) => include_bytes!("/stubs/windows-x86_64-stub.exe"),
+        (OperatingSystem::Windows, Architecture::X86, Glibc) => include_bytes!("/stubs/windows-x86-stub.exe"),
+        (OperatingSystem::Windows, Architecture::AArch64, Glibc) => include_bytes!("/stubs/windows-aarch64-stub.exe"),
+        (OperatingSystem::MacOS, Architecture::X86_64, Glibc) => include_bytes!("/stubs/macos-x86_64-stub"),
+        (OperatingSystem::MacOS, Architecture::AArch64, Glibc) => include_bytes!("/stubs/macos-aarch64-stub"),
+        _ => return None,
+    };
+    Some(stub)
+}
+
+#[cfg(not(feature = "embedded-stubs"))]
+fn embedded_stub(_os: OperatingSystem, _arch: Architecture, _flavor: StubFlavor) -> Option<&'static [u8]> {
+    None
+}
+
+/// Lowercase (os, arch) names used in stub file names and admin routes
+pub fn platform_slug(os: OperatingSystem, arch: Architecture) -> Option<(&'static str, &'static str)> {
+    let os_name = match os {
+        OperatingSystem::Linux => "linux",
+        OperatingSystem::Windows => "windows",
+        OperatingSystem::MacOS => "macos",
+        _ => return None,
+    };
+    let arch_name = match arch {
+        Architecture::X86_64 => "x86_64",
+        Architecture::X86 => "x86",
+        Architecture::AArch64 => "aarch64",
+        Architecture::ARM => "arm",
+        _ => return None,
+    };
+    Some((os_name, arch_name))
+}
+
+/// Stub platform named by `platform_slug`, if a stub is built for it
+pub fn parse_platform(os: &str, arch: &str) -> Option<(OperatingSystem, Architecture)> {
+    STUB_PLATFORMS
+        .iter()
+        .copied()
+        .find(|&(o, a)| platform_slug(o, a) == Some((os, arch)))
+}
+
+/// File name of a platform's stub, as produced by the loader-stub build
+///
+/// The default flavor has no flavor in its name, so existing stub
+/// directories keep working.
+pub fn stub_file_name(os: OperatingSystem, arch: Architecture, flavor: StubFlavor) -> Option<String> {
+    let (os_name, arch_name) = platform_slug(os, arch)?;
+    if !stub_flavors(os).contains(&flavor) {
+        return None;
+    }
+    let extension = if os == OperatingSystem::Windows { ".exe" } else { "" };
+    let flavor = if flavor == StubFlavor::default() { String::new() } else { format!("-{}", flavor.name()) };
+    Some(format!("{}-{}{}-stub{}", os_name, arch_name, flavor, extension))
+}
+
+/// Where a loaded stub came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StubSource {
+    Embedded,
+    File { path: PathBuf, modified: Option<SystemTime> },
+}
+
+#[derive(Debug, Clone)]
+pub struct Stub {
+    pub bytes: Arc<[u8]>,
+    pub sha256: String,
+    pub source: StubSource,
+    /// C library and float ABI the stub itself needs
+    pub abi: Abi,
+}
+
+impl Stub {
+    fn new(bytes: Arc<[u8]>, source: StubSource) -> Self {
+        Self { sha256: sha256_hex(&bytes), abi: Abi::detect(&bytes), bytes, source }
+    }
+}
+
+/// Loader stubs available to this process, keyed by platform and flavor
+///
+/// Stubs in the configured directory take precedence over the embedded ones.
+/// Every stub is checked to be a binary for the platform it is filed under;
+/// invalid files are skipped with a warning.
+pub struct StubRegistry {
+    dir: RwLock<Option<PathBuf>>,
+    stubs: RwLock<HashMap<(OperatingSystem, Architecture, StubFlavor), Stub>>,
+}
+
+impl StubRegistry {
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let registry = Self { dir: RwLock::new(dir), stubs: RwLock::new(HashMap::new()) };
+        registry.reload();
+        registry
+    }
+
+    /// Embedded stubs only
+    pub fn embedded() -> Self {
+        Self::load(None)
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        self.dir.read().unwrap().clone()
+    }
+
+    /// Switch to another stub directory, or to the embedded stubs only, and reload
+    pub fn set_dir(&self, dir: Option<PathBuf>) -> usize {
+        *self.dir.write().unwrap() = dir;
+        self.reload()
+    }
+
+    /// Re-scan the stub directory, returning the number of usable stubs
+    ///
+    /// Files whose modification time hasn't changed are not re-read.
+    pub fn reload(&self) -> usize {
+        let previous = self.stubs.read().unwrap().clone();
+        let dir = self.dir();
+        let mut stubs = HashMap::new();
+
+        for slot @ (os, arch, flavor) in stub_slots() {
+            let stub = load_file(dir.as_deref(), os, arch, flavor, previous.get(&slot)).or_else(|| {
+                embedded_stub(os, arch, flavor).filter(|b| !b.is_empty()).map(|b| Stub::new(Arc::from(b), StubSource::Embedded))
+            });
+
+            if let Some(stub) = stub {
+                if previous.get(&slot).is_some_and(|p| p.sha256 != stub.sha256) {
+                    log::info!("🔄 Reloaded {} {} stub from {:?}", BinaryInfo::new(os, arch).description(), flavor.name(), stub.source);
+                }
+                stubs.insert(slot, stub);
+            }
+        }
+
+        let count = stubs.len();
+        *self.stubs.write().unwrap() = stubs;
+        count
+    }
+
+    /// Validate a stub and write it into the stub directory, replacing the current one
+    ///
+    /// The file is renamed into place, so a concurrent reload never sees a partial stub.
+    pub fn install(&self, os: OperatingSystem, arch: Architecture, flavor: StubFlavor, bytes: Vec<u8>) -> Result<Stub> {
+        let dir = self.dir().context("No stub directory configured (WEAVER_STUB_DIR)")?;
+        let file_name = stub_file_name(os, arch, flavor).context("No stub is built for this platform and flavor")?;
+        validate_stub(&bytes, os, arch, flavor)?;
+
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(file_name);
+        let mut file = tempfile::NamedTempFile::new_in(&dir)?;
+        file.write_all(&bytes)?;
+        file.persist(&path)?;
+
+        let modified = fs::metadata(&path)?.modified().ok();
+        let stub = Stub::new(Arc::from(bytes), StubSource::File { path, modified });
+        self.stubs.write().unwrap().insert((os, arch, flavor), stub.clone());
+
+        log::info!(
+            "📥 Installed {} {} stub ({} bytes, sha256 {})",
+            BinaryInfo::new(os, arch).description(),
+            flavor.name(),
+            stub.bytes.len(),
+            stub.sha256
+        );
+        Ok(stub)
+    }
+
+    /// Number of usable stubs, counting every flavor
+    pub fn available_count(&self) -> usize {
+        self.stubs.read().unwrap().len()
+    }
+
+    /// The platform's default-flavor stub
+    pub fn get(&self, os: OperatingSystem, arch: Architecture) -> Option<Stub> {
+        self.get_flavor(os, arch, StubFlavor::default())
+    }
+
+    pub fn get_flavor(&self, os: OperatingSystem, arch: Architecture, flavor: StubFlavor) -> Option<Stub> {
+        self.stubs.read().unwrap().get(&(os, arch, flavor)).cloned()
+    }
+
+    /// Flavors loaded for a platform
+    pub fn flavors(&self, os: OperatingSystem, arch: Architecture) -> Vec<StubFlavor> {
+        let stubs = self.stubs.read().unwrap();
+        stub_flavors(os).iter().copied().filter(|&flavor| stubs.contains_key(&(os, arch, flavor))).collect()
+    }
+
+    /// Stub for the platform of the base binary
+    ///
+    /// The stub must also be able to run where base does: a glibc stub can't
+    /// start on a musl system, nor a hard-float one on a soft-float system.
+    /// Without a requested flavor the first loaded one that can is used, so a
+    /// musl-linked base gets the musl stub.
+    pub fn select(&self, base_info: &BinaryInfo, flavor: Option<StubFlavor>) -> Result<Stub> {
+        if !STUB_PLATFORMS.contains(&(base_info.os, base_info.arch)) {
+            let supported: Vec<_> = STUB_PLATFORMS
+                .iter()
+                .filter(|(os, _)| *os == base_info.os)
+                .map(|&(_, arch)| arch.name())
+                .collect();
+            let reason = if supported.is_empty() {
+                format!("Unsupported OS: {:?}", base_info.os)
+            } else {
+                format!(
+                    "Unsupported {} architecture: {:?}. Supported: {}",
+                    base_info.os.name(),
+                    base_info.arch,
+                    supported.join(", ")
+                )
+            };
+            return Err(MergeError::UnsupportedPlatform(reason).into());
+        }
+
+        let (os, arch) = (base_info.os, base_info.arch);
+        let candidates = match flavor {
+            Some(flavor) if !stub_flavors(os).contains(&flavor) => {
+                return Err(MergeError::UnsupportedPlatform(format!(
+                    "{} stubs only come in the default flavor; stub_flavor={} is for Linux",
+                    os.name(),
+                    flavor.name()
+                ))
+                .into());
+            }
+            Some(flavor) => vec![flavor],
+            None => stub_flavors(os).to_vec(),
+        };
+        let stubs: Vec<Stub> = candidates.iter().filter_map(|&flavor| self.get_flavor(os, arch, flavor)).collect();
+
+        let Some(first) = stubs.first() else {
+            let arch = match flavor {
+                Some(flavor) => format!("{:?} ({} flavor)", arch, flavor.name()),
+                None => format!("{:?}", arch),
+            };
+            return Err(MergeError::StubMissing { os: format!("{:?}", os), arch }.into());
+        };
+        if let Some(stub) = stubs.iter().find(|stub| stub.abi.is_compatible_with(&base_info.abi)) {
+            return Ok(stub.clone());
+        }
+        let stub_info = BinaryInfo { abi: first.abi, ..BinaryInfo::new(os, arch) };
+        Err(MergeError::StubAbiMismatch { base: base_info.description(), stub: stub_info.description() }.into())
+    }
+}
+
+/// Check that a stub can host the payloads before anything is assembled
+///
+/// The registry validates stubs as it loads them, but a wrong or corrupt
+/// entry would otherwise only show once the merged binary fails to start.
+/// The stub must be an executable in base's object format (ELF on Linux, PE
+/// on Windows, Mach-O on macOS) with base's arch and bitness, and the same
+/// goes for a binary overload.
+pub fn preflight(stub: &[u8], base_info: &BinaryInfo, overload: &[u8]) -> Result<(), MergeError> {
+    use goblin::Object;
+
+    let mismatch = |reason: String| MergeError::StubMismatch { expected: base_info.description(), reason };
+
+    let (os, is_64, executable) = match Object::parse(stub) {
+        Ok(Object::Elf(elf)) => {
+            use goblin::elf::header::{ET_DYN, ET_EXEC};
+            (OperatingSystem::Linux, elf.is_64, matches!(elf.header.e_type, ET_EXEC | ET_DYN) && elf.entry != 0)
+        }
+        Ok(Object::PE(pe)) => (OperatingSystem::Windows, pe.is_64, !pe.is_lib),
+        Ok(Object::Mach(goblin::mach::Mach::Binary(macho))) => {
+            (OperatingSystem::MacOS, macho.is_64, macho.header.filetype == goblin::mach::header::MH_EXECUTE)
+        }
+        Ok(Object::Mach(goblin::mach::Mach::Fat(_))) => return Err(mismatch("is a fat Mach-O; stubs are single-arch".to_string())),
+        Ok(_) => return Err(mismatch("is not an ELF, PE or Mach-O binary".to_string())),
+        Err(e) => return Err(mismatch(format!("doesn't parse: {}", e))),
+    };
+
+    if os != base_info.os {
+        return Err(mismatch(format!("is a {} binary, not {}", os.binary_format(), base_info.os.binary_format())));
+    }
+    if !executable {
+        return Err(mismatch("is not an executable".to_string()));
+    }
+    if is_64 != base_info.arch.is_64bit() {
+        let bits = |is_64: bool| if is_64 { "64-bit" } else { "32-bit" };
+        return Err(mismatch(format!("is {}, base is {}", bits(is_64), bits(base_info.arch.is_64bit()))));
+    }
+
+    let stub_info = BinaryInfo::detect(stub);
+    if !stub_info.is_compatible_with(base_info) {
+        return Err(mismatch(format!("is {}", stub_info.description())));
+    }
+    if !is_script(overload) {
+        let overload_info = BinaryInfo::detect(overload);
+        if !stub_info.is_compatible_with(&overload_info) {
+            return Err(mismatch(format!("is {} but the overload is {}", stub_info.description(), overload_info.description())));
+        }
+    }
+    Ok(())
+}
+
+/// A platform's stub from `dir`, reusing `previous` if its file hasn't changed
+fn load_file(dir: Option<&Path>, os: OperatingSystem, arch: Architecture, flavor: StubFlavor, previous: Option<&Stub>) -> Option<Stub> {
+    let path = dir?.join(stub_file_name(os, arch, flavor)?);
+    let modified = fs::metadata(&path).ok()?.modified().ok();
+
+    if let Some(previous) = previous {
+        if previous.source == (StubSource::File { path: path.clone(), modified }) {
+            return Some(previous.clone());
+        }
+    }
+
+    match read_stub(&path, os, arch, flavor) {
+        Ok(bytes) => Some(Stub::new(bytes, StubSource::File { path, modified })),
+        Err(e) => {
+            log::warn!("⚠️  Ignoring stub {}: {:#}", path.display(), e);
+            None
+        }
+    }
+}
+
+fn read_stub(path: &Path, os: OperatingSystem, arch: Architecture, flavor: StubFlavor) -> Result<Arc<[u8]>> {
+    let bytes = fs::read(path).context("Failed to read stub")?;
+    validate_stub(&bytes, os, arch, flavor)?;
+    Ok(Arc::from(bytes))
+}
+
+/// Check that `bytes` is a non-empty binary for the given platform, linked
+/// statically if the flavor calls for it, that reads the footer layout this
+/// build writes
+pub fn validate_stub(bytes: &[u8], os: OperatingSystem, arch: Architecture, flavor: StubFlavor) -> Result<()> {
+    if bytes.is_empty() {
+        anyhow::bail!("stub is empty");
+    }
+
+    let detected = BinaryInfo::detect(bytes);
+    if !detected.is_same_platform(&BinaryInfo::new(os, arch)) {
+        anyhow::bail!("expected {:?}/{:?}, found {}", os, arch, detected.description());
+    }
+    if flavor != StubFlavor::Glibc && detected.abi.libc != Some(Libc::Static) {
+        anyhow::bail!("a {} stub must be statically linked, found {}", flavor.name(), detected.description());
+    }
+    let expected = (FOOTER_VERSION, ConfigFooter::SIZE as u32);
+    match stub_layout(bytes) {
+        Some(layout) if layout == expected => {}
+        Some((version, size)) => anyhow::bail!(
+            "stub reads footer layout {} ({} bytes), this build writes layout {} ({} bytes); rebuild the stub",
+            version,
+            size,
+            expected.0,
+            expected.1
+        ),
+        None => anyhow::bail!("stub has no footer layout marker; it predates layout {} or isn't a loader stub", expected.0),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::build_real_test_binary;
+    use weaver_abi::footer::{stub_marker, STUB_MARKER_PREFIX};
+
+    /// `binary` with the footer layout marker the loader stub carries
+    fn marked(binary: Vec<u8>) -> Vec<u8> {
+        [binary, stub_marker().to_vec()].concat()
+    }
+
+    #[test]
+    fn test_stub_file_names_match_loader_build() {
+        let glibc = StubFlavor::Glibc;
+        assert_eq!(stub_file_name(OperatingSystem::Linux, Architecture::X86_64, glibc).as_deref(), Some("linux-x86_64-stub"));
+        assert_eq!(stub_file_name(OperatingSystem::Windows, Architecture::AArch64, glibc).as_deref(), Some("windows-aarch64-stub.exe"));
+        assert_eq!(stub_file_name(OperatingSystem::Linux, Architecture::ARM, glibc).as_deref(), Some("linux-arm-stub"));
+        assert!(stub_file_name(OperatingSystem::FreeBSD, Architecture::X86_64, glibc).is_none());
+
+        // Only Linux stubs come in other flavors
+        assert_eq!(
+            stub_file_name(OperatingSystem::Linux, Architecture::AArch64, StubFlavor::Musl).as_deref(),
+            Some("linux-aarch64-musl-stub")
+        );
+        assert_eq!(
+            stub_file_name(OperatingSystem::Linux, Architecture::X86, StubFlavor::Static).as_deref(),
+            Some("linux-x86-static-stub")
+        );
+        assert!(stub_file_name(OperatingSystem::MacOS, Architecture::AArch64, StubFlavor::Musl).is_none());
+        assert_eq!(stub_slots().count(), 17);
+
+        assert_eq!(parse_platform("macos", "aarch64"), Some((OperatingSystem::MacOS, Architecture::AArch64)));
+        assert!(parse_platform("macos", "x86").is_none());
+        // 32-bit ARM stubs are built for Linux only
+        assert_eq!(parse_platform("linux", "arm"), Some((OperatingSystem::Linux, Architecture::ARM)));
+        assert!(parse_platform("windows", "arm").is_none());
+    }
+
+    #[test]
+    fn test_directory_stubs_are_validated_and_hot_reloaded() {
+        let binary = match build_real_test_binary("gcc").map(marked) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("⚠️  Skipping test - failed to build binary: {}", e);
+                return;
+            }
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let registry = StubRegistry::load(Some(dir.path().to_path_buf()));
+
+        // An x86-64 ELF filed as the aarch64 stub is rejected
+        fs::write(dir.path().join("linux-aarch64-stub"), &binary).unwrap();
+        fs::write(dir.path().join("linux-x86_64-stub"), &binary).unwrap();
+        registry.reload();
+
+        let stub = registry.get(OperatingSystem::Linux, Architecture::X86_64).unwrap();
+        assert!(matches!(stub.source, StubSource::File { .. }));
+        assert_eq!(stub.sha256, sha256_hex(&binary));
+
+        let aarch64 = registry.get(OperatingSystem::Linux, Architecture::AArch64);
+        assert!(aarch64.is_none_or(|stub| stub.source == StubSource::Embedded));
+
+        // So is a stub without the layout marker, or built for another footer layout
+        let (linux, x86_64, glibc) = (OperatingSystem::Linux, Architecture::X86_64, StubFlavor::Glibc);
+        let unmarked = &binary[..binary.len() - stub_marker().len()];
+        assert!(validate_stub(unmarked, linux, x86_64, glibc).is_err());
+        let mut older = [unmarked, &STUB_MARKER_PREFIX[..]].concat();
+        older.extend_from_slice(&(FOOTER_VERSION - 1).to_le_bytes());
+        older.extend_from_slice(&(ConfigFooter::SIZE as u32).to_le_bytes());
+        assert!(validate_stub(&older, linux, x86_64, glibc).is_err());
+        assert!(validate_stub(&binary, linux, x86_64, glibc).is_ok());
+    }
+
+    #[test]
+    fn test_install_validates_and_persists() {
+        let binary = match build_real_test_binary("gcc").map(marked) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("⚠️  Skipping test - failed to build binary: {}", e);
+                return;
+            }
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let registry = StubRegistry::load(Some(dir.path().to_path_buf()));
+
+        assert!(registry.install(OperatingSystem::Windows, Architecture::X86_64, StubFlavor::Glibc, binary.clone()).is_err());
+        assert!(!dir.path().join("windows-x86_64-stub.exe").exists());
+
+        let stub = registry.install(OperatingSystem::Linux, Architecture::X86_64, StubFlavor::Glibc, binary.clone()).unwrap();
+        assert_eq!(fs::read(dir.path().join("linux-x86_64-stub")).unwrap(), binary);
+
+        // A reload keeps the installed file rather than falling back to the embedded stub
+        registry.reload();
+        assert_eq!(registry.get(OperatingSystem::Linux, Architecture::X86_64).unwrap().sha256, stub.sha256);
+    }
+
+    #[test]
+    fn test_select_honors_requested_flavor() {
+        // Test binaries are linked statically, so they pass as any flavor
+        let binary = match build_real_test_binary("gcc").map(marked) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("⚠️  Skipping test - failed to build binary: {}", e);
+                return;
+            }
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let registry = StubRegistry::load(Some(dir.path().to_path_buf()));
+        let musl = registry.install(OperatingSystem::Linux, Architecture::X86_64, StubFlavor::Musl, binary.clone()).unwrap();
+        assert!(dir.path().join("linux-x86_64-musl-stub").exists());
+        assert!(registry.flavors(OperatingSystem::Linux, Architecture::X86_64).contains(&StubFlavor::Musl));
+
+        let base_info = BinaryInfo::detect(&binary);
+        assert_eq!(registry.select(&base_info, Some(StubFlavor::Musl)).unwrap().sha256, musl.sha256);
+
+        let windows = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
+        let err = registry.select(&windows, Some(StubFlavor::Musl)).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(MergeError::UnsupportedPlatform(_))));
+
+        assert_eq!(StubFlavor::parse("static"), Ok(StubFlavor::Static));
+        assert!(StubFlavor::parse("uclibc").is_err());
+    }
+
+    #[test]
+    fn test_preflight_rejects_stubs_that_cannot_host_the_payloads() {
+        let binary = match build_real_test_binary("gcc") {
+            Ok(data) => data,
+            Err(e) => {
+                println!("⚠️  Skipping test - failed to build binary: {}", e);
+                return;
+            }
+        };
+        let base_info = BinaryInfo::detect(&binary);
+        assert!(preflight(&binary, &base_info, &binary).is_ok());
+        assert!(preflight(&binary, &base_info, b"#!/bin/sh\necho overload\n").is_ok());
+
+        let stub_mismatch = |result: Result<(), MergeError>| matches!(result, Err(MergeError::StubMismatch { .. }));
+        assert!(stub_mismatch(preflight(b"not a binary", &base_info, &binary)));
+        // An x86-64 ELF can't be the stub of a Windows or 32-bit base
+        assert!(stub_mismatch(preflight(&binary, &BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64), &binary)));
+        assert!(stub_mismatch(preflight(&binary, &BinaryInfo::new(OperatingSystem::Linux, Architecture::X86), &binary)));
+        // Nor host an overload for another architecture
+        if let Ok(arm64) = build_real_test_binary("aarch64-linux-gnu-gcc") {
+            assert!(stub_mismatch(preflight(&binary, &base_info, &arm64)));
+        }
+    }
+}
@@ -1,64 +1,1362 @@
 use anyhow::{Result, Context};
+use base64::Engine;
 use std::path::Path;
+use std::fmt;
 use std::fs;
-use std::mem;
-use std::io::Write;
+use std::hash::{Hash, Hasher};
 use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
 
-use crate::core::binary::{BinaryInfo, OperatingSystem, Architecture};
-use crate::core::progress::{ProgressTracker, ProgressStep};
+use super::kill_policy::KillPolicy;
+use super::seccomp::SeccompProfile;
+use super::splice::{MergeInput, Splicer};
+use super::strategy::{MergePlan, PlanJob};
+use super::stubs::{preflight, StubFlavor, StubRegistry};
+use crate::core::binary::{is_script, validate_executable, BinaryInfo, OperatingSystem};
+use crate::core::digest::sha256_hex;
+use crate::core::jobs::CancelToken;
+use crate::core::progress::{io_percentage, ProgressTracker, ProgressStep, SharedProgressSink};
+use crate::core::report::footer_warnings;
+use crate::models::binary::Labels;
+use weaver_abi::bundle::{self, BundleEntry};
+use weaver_abi::footer::{
+    encode_padded, encode_priority, ConfigFooter, FOOTER_VERSION, ImageChecksum, AUDIT_PATH_LEN, AUDIT_URL_LEN, BIND_HOSTNAME, BIND_MAC,
+    BIND_MACHINE_ID, CRASH_REPORT_CORE_DUMP, CRASH_REPORT_FILE, CRASH_REPORT_URL_LEN, CWD_BINARY_DIR, CWD_INHERIT, CWD_PATH, CWD_PATH_LEN,
+    INSTALL_DIR_LEN, INSTALL_NAME_LEN, INSTALL_SHORTCUT, INSTALL_SYSTEMD_UNIT,
+    EXEC_AUTO, EXEC_MEMFD, EXEC_TEMP_FILE, EXEC_TMPFILE, HARDEN_ANTI_DEBUG, HARDEN_CHECKSUM, JAIL_EMPTY_ROOT, LOG_ERRORS, LOG_PATH_LEN,
+    LOG_SILENT, LOG_TARGET_FILE, LOG_TARGET_STDERR, LOG_TARGET_SYSTEM, LOG_VERBOSE, MAGIC_BYTES, MAX_CRASH_STDERR_LINES, MAX_MACHINE_FINGERPRINTS, MAX_NICE, MAX_OUTPUT_LOG_FILES, MIN_NICE,
+    ORDER_OVERLOAD_FIRST, OUTPUT_LOG_BASE, OUTPUT_LOG_DIR_LEN, OUTPUT_LOG_OVERLOAD, OVERLOAD_EXECUTABLE, OVERLOAD_LIBRARY, OVERLOAD_SCRIPT, PREREQ_ABORT, PREREQ_RESTART, PREREQ_RETRY,
+    PRODUCT_NAME_LEN, READINESS_ABORT, READINESS_FILE, READINESS_FLAG, READINESS_NONE, READINESS_START_BASE, READINESS_TARGET_LEN,
+    READINESS_TCP, REVOCATION_KEY_LEN, REVOCATION_URL_LEN, RUN_AS_USER_LEN, SINGLE_INSTANCE_EXIT, SINGLE_INSTANCE_OFF,
+    SINGLE_INSTANCE_SIGNAL, TELEMETRY_URL_LEN, UMASK_SET,
+};
+use weaver_abi::kill_policy::MAX_KILL_POLICY_OPS;
 
-// Embed the pre-compiled stubs for each OS/Architecture combination
-// Note: These paths point to the /stubs directory in the Docker container // if run cargo check or build, outside the docker compose, it'll give errs as these files won't be found and is needed on compile time to be embedded in the binary
+/// Runtime options baked into the footer and honored by the loader stub
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct StubOptions {
+    /// Seconds without a successful check before base is killed (0 = disabled)
+    pub grace_period: u32,
+    /// Wait for the overload to exit successfully before starting base
+    pub sync_mode: bool,
+    /// Successful overload runs in a row before base starts, like `sync_mode` repeated (0 = off)
+    pub prerequisite_runs: u32,
+    /// What a failed prerequisite run, or the sync mode run, leads to
+    pub prerequisite_on_failure: PrerequisiteFailure,
+    /// Failed runs tolerated by `PrerequisiteFailure::Retry`/`Restart` before giving up
+    pub prerequisite_max_failures: u32,
+    /// Seconds between a failed run and the next one
+    pub prerequisite_retry_delay: u32,
+    /// Consecutive network failures before base is killed (0 = disabled)
+    pub network_failure_kill_count: u32,
+    /// `weaver_abi::footer::JAIL_*` flags for the overload (Linux only)
+    pub overload_jail: u32,
+    /// `weaver_abi::footer::ORDER_*` value
+    pub order: u8,
+    /// Seconds after base starts before the overload first runs (0 = immediately)
+    pub overload_start_delay: u32,
+    /// Re-run the overload every this many seconds while base runs (0 = once)
+    pub overload_interval: u32,
+    /// Seconds between health checks (0 = loader default of 5s)
+    pub health_check_interval: u32,
+    /// Milliseconds between SIGTERM and SIGKILL when killing base (0 = loader default of 100ms)
+    pub kill_delay_ms: u32,
+    /// Seconds without an overload heartbeat before base is killed (0 = disabled)
+    pub heartbeat_timeout: u32,
+    /// Seconds after base starts before health checks count ages and failures (0 = right away)
+    pub monitor_warmup: u32,
+    /// Start counting at each payload's first successful check, with `monitor_warmup` as the longest wait
+    pub arm_after_success: bool,
+    /// Seconds base and the overload get to exit when the merged binary is asked to stop (0 = loader default of 5s)
+    pub shutdown_grace: u32,
+    /// Memory cap in MiB for base and its children (0 = unlimited; Linux and Windows)
+    pub base_memory_limit_mb: u32,
+    /// CPU cap for base in percent of the machine (0 = unlimited; Linux and Windows)
+    pub base_cpu_percent: u32,
+    /// Memory cap in MiB for the overload and its children (0 = unlimited; Linux and Windows)
+    pub overload_memory_limit_mb: u32,
+    /// CPU cap for the overload in percent of the machine (0 = unlimited; Linux and Windows)
+    pub overload_cpu_percent: u32,
+    /// Open file limit for base (0 = inherited, Linux only)
+    pub base_nofile_limit: u32,
+    /// Open file limit for the overload (0 = inherited, Linux only)
+    pub overload_nofile_limit: u32,
+    /// Account payloads switch to when the merged binary runs as root (Linux and macOS)
+    pub run_as_user: Option<String>,
+    /// Strip root's privileges from payloads even without `run_as_user` (Linux and macOS)
+    pub drop_privileges: bool,
+    /// Syscall allowlist installed in the payloads before exec (Linux only)
+    pub seccomp_profile: Option<SeccompProfile>,
+    /// Refuse to run when the merged binary was modified or is being debugged
+    pub hardening: bool,
+    /// How much the stub itself logs
+    pub log_level: StubLogLevel,
+    /// Where the stub's own messages go
+    pub log_target: StubLogTarget,
+    /// Replaces "KillCode" in stub logs and names the payload processes
+    pub product_name: Option<String>,
+    /// How the stub loads payloads before exec'ing them (Linux only)
+    pub exec_strategy: ExecStrategy,
+    /// Directory base starts in
+    pub base_working_dir: WorkingDir,
+    /// Directory the overload starts in
+    pub overload_working_dir: WorkingDir,
+    /// umask for base (None = inherited; Linux and macOS)
+    pub base_umask: Option<u16>,
+    /// umask for the overload (None = inherited; Linux and macOS)
+    pub overload_umask: Option<u16>,
+    /// Nice value for base (None = inherited; a Windows priority class is picked from it)
+    pub base_priority: Option<i8>,
+    /// Nice value for the overload (None = inherited)
+    pub overload_priority: Option<i8>,
+    /// CPUs base may run on, bit N for CPU N (0 = any; Linux and Windows)
+    pub base_cpu_affinity: u64,
+    /// CPUs the overload may run on (0 = any; Linux and Windows)
+    pub overload_cpu_affinity: u64,
+    /// What a second copy of the merged binary does while one is running
+    pub single_instance: SingleInstance,
+    /// Have the stub accept runtime commands on a local socket or named pipe
+    pub control_socket: bool,
+    /// Connect base and the overload with a socketpair (named pipe on Windows)
+    pub ipc_channel: bool,
+    /// Rules the health monitor runs instead of the grace period, failure
+    /// count and heartbeat checks
+    pub kill_policy: Option<KillPolicy>,
+    /// Remote kill switch the stub polls
+    pub revocation: Option<Revocation>,
+    /// Unix time from which the merged binary refuses to run
+    pub expires_at: Option<i64>,
+    /// Guard `expires_at` against a clock set back, using file timestamps and the monotonic clock
+    pub expiry_clock_checks: bool,
+    /// Machines the merged binary may run on (None = any)
+    pub machine_binding: Option<MachineBinding>,
+    /// Where the stub records starts, exits and health events (None = nowhere)
+    pub audit: Option<AuditLog>,
+    /// What the stub reports when base crashes (None = nothing)
+    pub crash_report: Option<CrashReport>,
+    /// Rotating logs the stub tees payload output into (None = none)
+    pub output_log: Option<OutputLog>,
+    /// Probe a sidecar overload must pass before base starts (None = base starts right away)
+    pub readiness: Option<Readiness>,
+    /// Weaver `/telemetry/{binary_id}` URL the stub reports health events to (see [`telemetry_url`])
+    pub telemetry_url: Option<String>,
+    /// Labels written into the merged binary as an inert JSON segment (empty = none)
+    pub embedded_labels: Labels,
+    /// Interpreter a script overload runs under instead of its `#!` line (Linux and macOS)
+    pub interpreter: Option<BundledInterpreter>,
+    /// The overload is a shared library loaded into base rather than a process of its own
+    pub overload_library: bool,
+    /// Install base and data files on first run and run the installed copy from then on
+    pub installer: Option<Installer>,
+    /// Data files extracted for the payloads, found through `KILLCODE_ASSETS_DIR`
+    pub assets: Vec<DataFile>,
+    /// Stub build to use (None = the first one that can run where base does; Linux only)
+    pub stub_flavor: Option<StubFlavor>,
+    /// Compress the merged binary after assembly (see `pack`)
+    pub pack: bool,
+}
+
+/// URL under `base` that a merged binary with ID `binary_id` reports health events to
+///
+/// `base` is this Weaver service as the machines running the binary reach
+/// it, plain `http://` like every URL the stub fetches.
+pub fn telemetry_url(base: &str, binary_id: &str) -> Result<String, String> {
+    let base = base.trim().trim_end_matches('/');
+    let host = base.strip_prefix("http://").map(|rest| rest.split('/').next().unwrap_or(""));
+    if host.is_none_or(str::is_empty) {
+        return Err(format!("Phone-home URL must be http://host[:port][/path], got {:?}", base));
+    }
+    let url = format!("{}/telemetry/{}", base, binary_id);
+    if url.len() > TELEMETRY_URL_LEN {
+        return Err(format!("Phone-home URL must be at most {} bytes", TELEMETRY_URL_LEN - (url.len() - base.len())));
+    }
+    Ok(url)
+}
+
+/// Interpreter binary written into the merged binary to run a script overload
+///
+/// Compared and hashed by its SHA-256, so options stay cheap to compare and
+/// cache keys don't need the binary itself.
+#[derive(Clone)]
+pub struct BundledInterpreter {
+    pub data: Arc<[u8]>,
+    pub sha256: String,
+}
+
+impl BundledInterpreter {
+    pub fn new(data: &[u8]) -> Self {
+        Self { data: Arc::from(data), sha256: sha256_hex(data) }
+    }
+}
+
+impl PartialEq for BundledInterpreter {
+    fn eq(&self, other: &Self) -> bool {
+        self.sha256 == other.sha256
+    }
+}
+
+impl Eq for BundledInterpreter {}
+
+impl Hash for BundledInterpreter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sha256.hash(state);
+    }
+}
+
+impl fmt::Debug for BundledInterpreter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BundledInterpreter({} bytes, sha256 {})", self.data.len(), self.sha256)
+    }
+}
+
+/// Destinations for the stub's execution audit records
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AuditLog {
+    /// File on the target machine records are appended to, one JSON object per line
+    pub path: Option<String>,
+    /// Plain `http://` URL each record is POSTed to
+    pub url: Option<String>,
+}
+
+impl AuditLog {
+    /// Empty `path` and `url` mean no audit log
+    pub fn parse(path: &str, url: &str) -> Result<Option<Self>, String> {
+        let path = Some(path.trim()).filter(|path| !path.is_empty());
+        let url = Some(url.trim()).filter(|url| !url.is_empty());
+        if let Some(path) = path {
+            encode_padded::<AUDIT_PATH_LEN>(path).ok_or_else(|| format!("Audit path must be at most {} bytes", AUDIT_PATH_LEN))?;
+        }
+        if let Some(url) = url {
+            let host = url.strip_prefix("http://").map(|rest| rest.split('/').next().unwrap_or(""));
+            if host.is_none_or(str::is_empty) {
+                return Err(format!("Audit URL must be http://host[:port]/path, got {:?}", url));
+            }
+            encode_padded::<AUDIT_URL_LEN>(url).ok_or_else(|| format!("Audit URL must be at most {} bytes", AUDIT_URL_LEN))?;
+        }
+        if path.is_none() && url.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(AuditLog { path: path.map(str::to_string), url: url.map(str::to_string) }))
+    }
+}
+
+/// Where the stub reports a crash of base, and what it captures for it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CrashReport {
+    /// Write `<binary>.crash-<time>.json` next to the merged binary
+    pub file: bool,
+    /// Plain `http://` URL each report is POSTed to
+    pub url: Option<String>,
+    /// Last lines of base's stderr kept for the report (0 = stderr is left alone)
+    pub stderr_lines: u16,
+    /// Let base dump core (Unix)
+    pub core_dump: bool,
+}
+
+impl CrashReport {
+    /// No file and an empty `url` mean no crash reports; stderr lines and
+    /// core dumps need one of them
+    pub fn parse(file: bool, url: &str, stderr_lines: u32, core_dump: bool) -> Result<Option<Self>, String> {
+        let url = Some(url.trim()).filter(|url| !url.is_empty());
+        if let Some(url) = url {
+            let host = url.strip_prefix("http://").map(|rest| rest.split('/').next().unwrap_or(""));
+            if host.is_none_or(str::is_empty) {
+                return Err(format!("Crash report URL must be http://host[:port]/path, got {:?}", url));
+            }
+            encode_padded::<CRASH_REPORT_URL_LEN>(url)
+                .ok_or_else(|| format!("Crash report URL must be at most {} bytes", CRASH_REPORT_URL_LEN))?;
+        }
+        if stderr_lines > MAX_CRASH_STDERR_LINES as u32 {
+            return Err(format!("Crash report stderr lines must be at most {}", MAX_CRASH_STDERR_LINES));
+        }
+        if !file && url.is_none() {
+            if stderr_lines > 0 || core_dump {
+                return Err("Crash report stderr lines and core dumps need a crash report file or URL".to_string());
+            }
+            return Ok(None);
+        }
+        Ok(Some(CrashReport { file, url: url.map(str::to_string), stderr_lines: stderr_lines as u16, core_dump }))
+    }
+
+    /// `CRASH_REPORT_*` flags for the footer
+    fn footer_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.file {
+            flags |= CRASH_REPORT_FILE;
+        }
+        if self.core_dump {
+            flags |= CRASH_REPORT_CORE_DUMP;
+        }
+        flags
+    }
+}
+
+/// Rotating logs on the target machine that the stub tees payload stdout and stderr into
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OutputLog {
+    /// Log base's output to `base.log`
+    pub base: bool,
+    /// Log the overload's output to `overload.log`
+    pub overload: bool,
+    /// Directory for the logs, relative to the merged binary's unless absolute (None = the merged binary's)
+    pub dir: Option<String>,
+    /// Size in MiB at which a log is rotated
+    pub max_size_mb: u32,
+    /// Files kept per payload, the current one included
+    pub max_files: u8,
+}
+
+impl OutputLog {
+    pub const DEFAULT_MAX_SIZE_MB: u32 = 10;
+    pub const DEFAULT_MAX_FILES: u8 = 5;
+
+    /// `payloads` lists `base` and `overload`, comma-separated; empty means
+    /// no output logs. A size or file count of 0 takes the default.
+    pub fn parse(payloads: &str, dir: &str, max_size_mb: u32, max_files: u32) -> Result<Option<Self>, String> {
+        let (mut base, mut overload) = (false, false);
+        for payload in payloads.split(',').map(str::trim).filter(|payload| !payload.is_empty()) {
+            match payload {
+                "base" => base = true,
+                "overload" => overload = true,
+                other => return Err(format!("Unknown output log payload {:?}, expected base or overload", other)),
+            }
+        }
+        let dir = Some(dir.trim()).filter(|dir| !dir.is_empty());
+        if let Some(dir) = dir {
+            encode_padded::<OUTPUT_LOG_DIR_LEN>(dir).ok_or_else(|| format!("Output log directory must be at most {} bytes", OUTPUT_LOG_DIR_LEN))?;
+        }
+        if max_files > MAX_OUTPUT_LOG_FILES as u32 {
+            return Err(format!("Output log max files must be at most {}", MAX_OUTPUT_LOG_FILES));
+        }
+        if !base && !overload {
+            if dir.is_some() || max_size_mb > 0 || max_files > 0 {
+                return Err("Output log settings need base, overload or both to log".to_string());
+            }
+            return Ok(None);
+        }
+        Ok(Some(OutputLog {
+            base,
+            overload,
+            dir: dir.map(str::to_string),
+            max_size_mb: if max_size_mb > 0 { max_size_mb } else { Self::DEFAULT_MAX_SIZE_MB },
+            max_files: if max_files > 0 { max_files as u8 } else { Self::DEFAULT_MAX_FILES },
+        }))
+    }
+
+    /// `OUTPUT_LOG_*` flags for the footer
+    fn footer_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.base {
+            flags |= OUTPUT_LOG_BASE;
+        }
+        if self.overload {
+            flags |= OUTPUT_LOG_OVERLOAD;
+        }
+        flags
+    }
+}
+
+/// What the stub checks after starting a sidecar overload before it starts base
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ReadinessProbe {
+    /// A TCP connection to `host:port` succeeds
+    Tcp(String),
+    /// A file exists, relative to the merged binary's directory unless absolute
+    File(String),
+    /// The overload marks its health slot ready (`Health::set_ready`)
+    ReadyFlag,
+}
+
+/// Readiness probe with its timeout and what happens when it runs out
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Readiness {
+    pub probe: ReadinessProbe,
+    /// Seconds the stub waits for the probe to pass
+    pub timeout_secs: u32,
+    /// Start base anyway when the probe times out or the overload exits
+    /// first, instead of killing the overload and exiting with code 1
+    pub start_base_on_timeout: bool,
+}
+
+impl Readiness {
+    pub const DEFAULT_TIMEOUT_SECS: u32 = 60;
+
+    /// `probe` is `tcp:host:port`, `file:path` or `ready_flag`; empty means
+    /// none. `on_timeout` is `abort` (default) or `start_base`, and a timeout
+    /// of 0 takes the default.
+    pub fn parse(probe: &str, timeout_secs: u32, on_timeout: &str) -> Result<Option<Self>, String> {
+        let start_base_on_timeout = match on_timeout.trim() {
+            "" | "abort" => false,
+            "start_base" => true,
+            other => return Err(format!("Unknown readiness timeout action {:?}, expected abort or start_base", other)),
+        };
+        let probe = match probe.trim() {
+            "" => {
+                if timeout_secs > 0 || start_base_on_timeout {
+                    return Err("Readiness timeout settings need a readiness probe".to_string());
+                }
+                return Ok(None);
+            }
+            "ready_flag" => ReadinessProbe::ReadyFlag,
+            probe => match probe.split_once(':') {
+                Some(("tcp", address)) => {
+                    let valid = address
+                        .rsplit_once(':')
+                        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|port| port > 0));
+                    if !valid {
+                        return Err(format!("TCP readiness probe must be tcp:host:port, got {:?}", probe));
+                    }
+                    ReadinessProbe::Tcp(address.to_string())
+                }
+                Some(("file", path)) if !path.is_empty() => ReadinessProbe::File(path.to_string()),
+                _ => return Err(format!("Unknown readiness probe {:?}, expected tcp:host:port, file:path or ready_flag", probe)),
+            },
+        };
+        if let ReadinessProbe::Tcp(target) | ReadinessProbe::File(target) = &probe {
+            encode_padded::<READINESS_TARGET_LEN>(target)
+                .ok_or_else(|| format!("Readiness probe target must be at most {} bytes", READINESS_TARGET_LEN))?;
+        }
+        Ok(Some(Readiness {
+            probe,
+            timeout_secs: if timeout_secs > 0 { timeout_secs } else { Self::DEFAULT_TIMEOUT_SECS },
+            start_base_on_timeout,
+        }))
+    }
+
+    /// `READINESS_*` probe and its target for the footer
+    fn footer_probe(&self) -> (u8, Option<&str>) {
+        match &self.probe {
+            ReadinessProbe::Tcp(address) => (READINESS_TCP, Some(address)),
+            ReadinessProbe::File(path) => (READINESS_FILE, Some(path)),
+            ReadinessProbe::ReadyFlag => (READINESS_FLAG, None),
+        }
+    }
+}
+
+/// Machines allowed to run a merged binary, by fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MachineBinding {
+    /// `weaver_abi::footer::BIND_*` flags hashed into each fingerprint
+    pub properties: u8,
+    pub fingerprints: Vec<u64>,
+}
+
+impl MachineBinding {
+    /// `properties` lists `machine_id`, `hostname` and `mac` (default
+    /// `machine_id`); `fingerprints` are the hex values the stub logs when it
+    /// refuses a machine, separated by commas or whitespace. With neither
+    /// there is no binding.
+    pub fn parse(properties: &str, fingerprints: &str) -> Result<Option<Self>, String> {
+        let fingerprints = fingerprints
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(|fingerprint| {
+                let digits = fingerprint.strip_prefix("0x").unwrap_or(fingerprint);
+                u64::from_str_radix(digits, 16)
+                    .ok()
+                    .filter(|&value| value != 0)
+                    .ok_or_else(|| format!("Invalid machine fingerprint {:?}, expected 16 hex digits", fingerprint))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut flags = 0;
+        for property in properties.split(',').map(str::trim).filter(|property| !property.is_empty()) {
+            flags |= match property {
+                "machine_id" => BIND_MACHINE_ID,
+                "hostname" => BIND_HOSTNAME,
+                "mac" => BIND_MAC,
+                other => {
+                    return Err(format!("Unknown machine property {:?}, expected machine_id, hostname or mac", other));
+                }
+            };
+        }
+
+        if fingerprints.is_empty() {
+            return match flags {
+                0 => Ok(None),
+                _ => Err("Machine binding needs at least one fingerprint".to_string()),
+            };
+        }
+        if fingerprints.len() > MAX_MACHINE_FINGERPRINTS {
+            return Err(format!("At most {} machine fingerprints fit the footer", MAX_MACHINE_FINGERPRINTS));
+        }
+        let properties = if flags == 0 { BIND_MACHINE_ID } else { flags };
+        Ok(Some(MachineBinding { properties, fingerprints }))
+    }
 
-// Linux stubs
-const LINUX_X86_64_STUB: &[u8] = include_bytes!("/stubs/linux-x86_64-stub");
-const LINUX_X86_STUB: &[u8] = include_bytes!("/stubs/linux-x86-stub");
-const LINUX_AARCH64_STUB: &[u8] = include_bytes!("/stubs/linux-aarch64-stub");
+    /// `ConfigFooter::machine_fingerprints`, unused slots 0
+    pub fn footer_fingerprints(&self) -> [u64; MAX_MACHINE_FINGERPRINTS] {
+        let mut slots = [0; MAX_MACHINE_FINGERPRINTS];
+        slots[..self.fingerprints.len()].copy_from_slice(&self.fingerprints);
+        slots
+    }
+}
+
+/// URL the stub polls for a signed revocation, which terminates the payloads
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Revocation {
+    /// Plain `http://` URL; responses are trusted by signature only
+    pub url: String,
+    /// Ed25519 key a revocation must be signed with
+    pub public_key: [u8; REVOCATION_KEY_LEN],
+    /// Seconds between checks (0 = loader default of 300s)
+    pub interval: u32,
+}
+
+impl Revocation {
+    /// DER prefix of an Ed25519 SubjectPublicKeyInfo, as exported by OpenSSL
+    const SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
 
-// Windows stubs
-const WINDOWS_X86_64_STUB: &[u8] = include_bytes!("/stubs/windows-x86_64-stub.exe");
-const WINDOWS_X86_STUB: &[u8] = include_bytes!("/stubs/windows-x86-stub.exe");
-const WINDOWS_AARCH64_STUB: &[u8] = include_bytes!("/stubs/windows-aarch64-stub.exe");
+    /// `public_key` is base64, either the raw 32-byte key or its DER
+    /// SubjectPublicKeyInfo (the body of an OpenSSL PEM file). With neither
+    /// URL nor key there is no revocation.
+    pub fn parse(url: &str, public_key: &str, interval: u32) -> Result<Option<Self>, String> {
+        let (url, public_key) = (url.trim(), public_key.trim());
+        match (url.is_empty(), public_key.is_empty()) {
+            (true, true) => return Ok(None),
+            (false, false) => {}
+            _ => return Err("Revocation URL and key must be set together".to_string()),
+        }
 
-// macOS stubs
-const MACOS_X86_64_STUB: &[u8] = include_bytes!("/stubs/macos-x86_64-stub");
-const MACOS_AARCH64_STUB: &[u8] = include_bytes!("/stubs/macos-aarch64-stub");
+        let host = url.strip_prefix("http://").map(|rest| rest.split('/').next().unwrap_or(""));
+        if host.is_none_or(str::is_empty) {
+            return Err(format!("Revocation URL must be http://host[:port]/path, got {:?}", url));
+        }
+        encode_padded::<REVOCATION_URL_LEN>(url).ok_or_else(|| format!("Revocation URL must be at most {} bytes", REVOCATION_URL_LEN))?;
+
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(public_key)
+            .map_err(|e| format!("Revocation key is not base64: {}", e))?;
+        let raw = der.strip_prefix(Self::SPKI_PREFIX.as_slice()).unwrap_or(&der);
+        let public_key = <[u8; REVOCATION_KEY_LEN]>::try_from(raw)
+            .map_err(|_| "Revocation key must be a 32-byte Ed25519 public key".to_string())?;
+
+        Ok(Some(Revocation { url: url.to_string(), public_key, interval }))
+    }
+}
 
-#[repr(C)]
-struct ConfigFooter {
-    magic: [u8; 8],
-    base_offset: u64,
-    base_size: u64,
-    overload_offset: u64,
-    overload_size: u64,
-    grace_period: u32,
-    sync_mode: u8,
-    network_failure_kill_count: u32,
+/// What a second copy of a merged binary does while one is running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SingleInstance {
+    /// Any number of copies can run
+    #[default]
+    Off,
+    /// Exit with code 1
+    Exit,
+    /// Send SIGUSR1 to the running copy's base and exit (Unix; exits on Windows)
+    Signal,
 }
 
+impl SingleInstance {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "off" => Ok(SingleInstance::Off),
+            "exit" => Ok(SingleInstance::Exit),
+            "signal" => Ok(SingleInstance::Signal),
+            other => Err(format!("Unknown single instance mode {:?}, expected off, exit or signal", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::SINGLE_INSTANCE_*` value
+    pub fn footer_value(self) -> u8 {
+        match self {
+            SingleInstance::Off => SINGLE_INSTANCE_OFF,
+            SingleInstance::Exit => SINGLE_INSTANCE_EXIT,
+            SingleInstance::Signal => SINGLE_INSTANCE_SIGNAL,
+        }
+    }
+}
+
+/// What the stub does when an overload run base waits for fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PrerequisiteFailure {
+    /// Exit without starting base
+    #[default]
+    Abort,
+    /// Repeat the failed run
+    Retry,
+    /// Start over from the first run
+    Restart,
+}
+
+impl PrerequisiteFailure {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "abort" => Ok(PrerequisiteFailure::Abort),
+            "retry" => Ok(PrerequisiteFailure::Retry),
+            "restart" => Ok(PrerequisiteFailure::Restart),
+            other => Err(format!("Unknown prerequisite failure policy {:?}, expected abort, retry or restart", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::PREREQ_*` value
+    pub fn footer_value(self) -> u8 {
+        match self {
+            PrerequisiteFailure::Abort => PREREQ_ABORT,
+            PrerequisiteFailure::Retry => PREREQ_RETRY,
+            PrerequisiteFailure::Restart => PREREQ_RESTART,
+        }
+    }
+}
+
+/// Check that `options` can give up on failed prerequisite runs
+///
+/// `Retry` and `Restart` need a number of failures to stop at, or a missing
+/// license server would keep base from ever starting.
+pub fn validate_prerequisites(options: &StubOptions) -> Result<(), String> {
+    if options.prerequisite_on_failure != PrerequisiteFailure::Abort && options.prerequisite_max_failures == 0 {
+        return Err("prerequisite_on_failure retry and restart need prerequisite_max_failures of at least 1".to_string());
+    }
+    Ok(())
+}
+
+/// Directory a payload starts in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum WorkingDir {
+    /// Wherever the merged binary was started from
+    #[default]
+    Inherit,
+    /// The directory the merged binary is installed in
+    BinaryDir,
+    /// An absolute path on the machine running the merged binary
+    Path(String),
+}
+
+impl WorkingDir {
+    /// `inherit`, `binary_dir` or an absolute path
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "inherit" => Ok(WorkingDir::Inherit),
+            "binary_dir" => Ok(WorkingDir::BinaryDir),
+            path => {
+                if !is_absolute_path(path) {
+                    return Err(format!("Working directory must be inherit, binary_dir or an absolute path, got {:?}", path));
+                }
+                encode_padded::<CWD_PATH_LEN>(path).ok_or_else(|| format!("Working directory must be at most {} bytes", CWD_PATH_LEN))?;
+                Ok(WorkingDir::Path(path.to_string()))
+            }
+        }
+    }
+
+    /// `weaver_abi::footer::CWD_*` value and path field
+    pub fn footer_cwd(&self) -> (u8, [u8; CWD_PATH_LEN]) {
+        match self {
+            WorkingDir::Inherit => (CWD_INHERIT, [0; CWD_PATH_LEN]),
+            WorkingDir::BinaryDir => (CWD_BINARY_DIR, [0; CWD_PATH_LEN]),
+            WorkingDir::Path(path) => (CWD_PATH, encode_padded::<CWD_PATH_LEN>(path).unwrap_or([0; CWD_PATH_LEN])),
+        }
+    }
+}
+
+/// Whether `path` is absolute on Unix or Windows, the OS of the target isn't known yet
+fn is_absolute_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let windows_drive = bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/');
+    path.starts_with('/') || path.starts_with("\\\\") || windows_drive
+}
+
+/// A data file extracted on the target machine: one of an installer's files or an asset
+///
+/// Compared and hashed by path and SHA-256, like `BundledInterpreter`.
+#[derive(Clone)]
+pub struct DataFile {
+    /// `/`-separated, relative to the directory it is extracted to
+    pub path: String,
+    pub data: Arc<[u8]>,
+    pub sha256: String,
+}
+
+impl DataFile {
+    pub fn new(path: &str, data: &[u8]) -> Self {
+        Self { path: path.to_string(), data: Arc::from(data), sha256: sha256_hex(data) }
+    }
+
+    /// The same file without its data, which compares and hashes the same
+    pub fn without_data(&self) -> Self {
+        Self { data: Arc::from(&[][..]), ..self.clone() }
+    }
+
+    /// Executables and scripts are extracted executable, everything else read-write
+    fn mode(&self) -> u32 {
+        if is_script(&self.data) || validate_executable(&self.data).is_ok() {
+            bundle::MODE_EXECUTABLE
+        } else {
+            bundle::MODE_FILE
+        }
+    }
+}
+
+impl PartialEq for DataFile {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.sha256 == other.sha256
+    }
+}
+
+impl Eq for DataFile {}
+
+impl Hash for DataFile {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.sha256.hash(state);
+    }
+}
+
+impl fmt::Debug for DataFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DataFile({}, {} bytes, sha256 {})", self.path, self.data.len(), self.sha256)
+    }
+}
+
+/// Most install files, and most assets, one merge may bundle
+pub const MAX_DATA_FILES: usize = 1024;
+
+/// Check that `files` can be extracted into one directory without leaving it
+/// or overwriting each other; `what` names them in errors
+fn validate_data_files(files: &[DataFile], what: &str) -> Result<(), String> {
+    if files.len() > MAX_DATA_FILES {
+        return Err(format!("At most {} {}s can be bundled, got {}", MAX_DATA_FILES, what.to_lowercase(), files.len()));
+    }
+    for (index, file) in files.iter().enumerate() {
+        if !bundle::is_safe_path(&file.path) {
+            return Err(format!("{} path must be relative and stay inside its directory, got {:?}", what, file.path));
+        }
+        let clashes = files[..index].iter().any(|other| {
+            let (shorter, longer) = if other.path.len() <= file.path.len() { (&other.path, &file.path) } else { (&file.path, &other.path) };
+            longer == shorter || longer.strip_prefix(shorter.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        });
+        if clashes {
+            return Err(format!("{} {:?} clashes with another one", what, file.path));
+        }
+    }
+    Ok(())
+}
+
+/// Check assets the stub extracts and exports as `KILLCODE_ASSETS_DIR`
+pub fn validate_assets(files: &[DataFile]) -> Result<(), String> {
+    validate_data_files(files, "Asset")
+}
+
+/// `files` as a `weaver_abi::bundle` entry table
+pub fn encode_bundle(files: &[DataFile]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for file in files {
+        let entry = BundleEntry { path_len: file.path.len() as u32, mode: file.mode(), size: file.data.len() as u64 };
+        encoded.extend_from_slice(entry.as_bytes());
+        encoded.extend_from_slice(file.path.as_bytes());
+        encoded.extend_from_slice(&file.data);
+    }
+    encoded
+}
+
+/// Turns the merged binary into a self-extracting installer
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Installer {
+    /// Absolute directory on the target machine base and the files go to
+    pub dir: String,
+    /// File name of the installed base (plus `.exe` on Windows), the systemd unit and the shortcut
+    pub name: String,
+    pub files: Vec<DataFile>,
+    /// Write a systemd unit running the merged binary (Linux only; not enabled)
+    pub systemd_unit: bool,
+    /// Write a desktop entry launching the merged binary (Linux only)
+    pub shortcut: bool,
+}
+
+impl Installer {
+    /// An empty `dir` means no installer, and then nothing else may be set
+    pub fn parse(dir: &str, name: &str, files: Vec<DataFile>, systemd_unit: bool, shortcut: bool) -> Result<Option<Self>, String> {
+        let (dir, name) = (dir.trim(), name.trim());
+        if dir.is_empty() {
+            if !name.is_empty() || !files.is_empty() || systemd_unit || shortcut {
+                return Err("Install name, files, systemd unit and shortcut need an install directory".to_string());
+            }
+            return Ok(None);
+        }
+        if !is_absolute_path(dir) {
+            return Err(format!("Install directory must be an absolute path, got {:?}", dir));
+        }
+        encode_padded::<INSTALL_DIR_LEN>(dir).ok_or_else(|| format!("Install directory must be at most {} bytes", INSTALL_DIR_LEN))?;
+
+        if name.is_empty() || name.len() > INSTALL_NAME_LEN {
+            return Err(format!("Install name must be 1 to {} characters", INSTALL_NAME_LEN));
+        }
+        if name.starts_with(['.', '-']) || !name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-')) {
+            return Err(format!("Invalid install name: {:?}", name));
+        }
+
+        validate_data_files(&files, "Install file")?;
+        let reserved = [name.to_string(), format!("{}.exe", name), ".weaver-install".to_string()];
+        if let Some(file) = files.iter().find(|file| reserved.contains(&file.path)) {
+            return Err(format!("Install file {:?} would overwrite base or the install marker", file.path));
+        }
+        Ok(Some(Installer { dir: dir.to_string(), name: name.to_string(), files, systemd_unit, shortcut }))
+    }
+
+    /// `weaver_abi::footer::INSTALL_*` flags
+    pub fn footer_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.systemd_unit {
+            flags |= INSTALL_SYSTEMD_UNIT;
+        }
+        if self.shortcut {
+            flags |= INSTALL_SHORTCUT;
+        }
+        flags
+    }
+
+    /// The same installer without the file data, which compares and hashes the same
+    pub fn without_data(&self) -> Self {
+        Self { files: self.files.iter().map(DataFile::without_data).collect(), ..self.clone() }
+    }
+}
+
+/// Parse an expiry date: RFC 3339 (`2026-12-31T23:59:59Z`) or Unix seconds
+pub fn parse_expires_at(value: &str) -> Result<i64, String> {
+    let timestamp = match value.parse::<i64>() {
+        Ok(seconds) => seconds,
+        Err(_) => chrono::DateTime::parse_from_rfc3339(value)
+            .map_err(|e| format!("Invalid expiry date {:?}, expected RFC 3339 or Unix seconds: {}", value, e))?
+            .timestamp(),
+    };
+    if timestamp <= 0 {
+        return Err(format!("Expiry date {:?} is not after 1970", value));
+    }
+    Ok(timestamp)
+}
+
+/// Parse an octal umask such as `022` or `0o027`
+pub fn parse_umask(value: &str) -> Result<u16, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    match u16::from_str_radix(digits, 8) {
+        Ok(umask) if umask <= 0o777 => Ok(umask),
+        _ => Err(format!("Invalid umask {:?}, expected an octal value up to 777", value)),
+    }
+}
+
+/// Parse a priority: a nice value from -20 (highest) to 19, or `idle` (19),
+/// `below_normal` (10), `normal` (0), `above_normal` (-5) or `high` (-10)
+pub fn parse_priority(value: &str) -> Result<i8, String> {
+    let nice = match value {
+        "idle" => MAX_NICE,
+        "below_normal" => 10,
+        "normal" => 0,
+        "above_normal" => -5,
+        "high" => -10,
+        nice => nice.parse().unwrap_or(i8::MIN),
+    };
+    if encode_priority(nice).is_none() {
+        return Err(format!(
+            "Invalid priority {:?}, expected idle, below_normal, normal, above_normal, high or a nice value from {} to {}",
+            value, MIN_NICE, MAX_NICE
+        ));
+    }
+    Ok(nice)
+}
+
+/// Parse a CPU list such as `0-3,6` into an affinity mask (CPUs 0 to 63)
+pub fn parse_cpu_affinity(value: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid CPU list {:?}, expected CPUs 0 to 63 such as 0-3,6", value);
+    let mut mask = 0u64;
+    for range in value.split(',').map(str::trim) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        let (first, last): (u32, u32) = (first.trim().parse().map_err(|_| invalid())?, last.trim().parse().map_err(|_| invalid())?);
+        if first > last || last > 63 {
+            return Err(invalid());
+        }
+        mask |= (first..=last).fold(0, |mask, cpu| mask | 1 << cpu);
+    }
+    Ok(mask)
+}
+
+/// How a Linux stub loads payloads before exec'ing them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ExecStrategy {
+    /// memfd_create, falling back to the others when it's unavailable
+    #[default]
+    Auto,
+    Memfd,
+    /// Unnamed `O_TMPFILE` file
+    Tmpfile,
+    /// File in a private temp directory, unlinked once open
+    TempFile,
+}
+
+impl ExecStrategy {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "auto" => Ok(ExecStrategy::Auto),
+            "memfd" => Ok(ExecStrategy::Memfd),
+            "tmpfile" => Ok(ExecStrategy::Tmpfile),
+            "tempfile" => Ok(ExecStrategy::TempFile),
+            other => Err(format!("Unknown exec strategy {:?}, expected auto, memfd, tmpfile or tempfile", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::EXEC_*` value
+    pub fn footer_value(self) -> u8 {
+        match self {
+            ExecStrategy::Auto => EXEC_AUTO,
+            ExecStrategy::Memfd => EXEC_MEMFD,
+            ExecStrategy::Tmpfile => EXEC_TMPFILE,
+            ExecStrategy::TempFile => EXEC_TEMP_FILE,
+        }
+    }
+}
+
+/// Verbosity of the stub's own `[KillCode]` messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum StubLogLevel {
+    #[default]
+    Verbose,
+    /// Only failures and the reasons payloads get killed
+    Errors,
+    Silent,
+}
+
+impl StubLogLevel {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "verbose" => Ok(StubLogLevel::Verbose),
+            "errors" => Ok(StubLogLevel::Errors),
+            "silent" => Ok(StubLogLevel::Silent),
+            other => Err(format!("Unknown log level {:?}, expected silent, errors or verbose", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::LOG_*` value
+    pub fn footer_level(self) -> u8 {
+        match self {
+            StubLogLevel::Verbose => LOG_VERBOSE,
+            StubLogLevel::Errors => LOG_ERRORS,
+            StubLogLevel::Silent => LOG_SILENT,
+        }
+    }
+}
+
+/// Destination of the stub's own messages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum StubLogTarget {
+    #[default]
+    Stderr,
+    /// syslog on Linux and macOS, the Application Event Log on Windows
+    System,
+    /// Appended to this path on the machine running the merged binary
+    File(String),
+}
+
+impl StubLogTarget {
+    /// `path` is required for, and only allowed with, the `file` target
+    pub fn parse(target: &str, path: Option<&str>) -> Result<Self, String> {
+        match (target, path) {
+            ("stderr", None) => Ok(StubLogTarget::Stderr),
+            ("system", None) => Ok(StubLogTarget::System),
+            ("file", Some(path)) => {
+                encode_padded::<LOG_PATH_LEN>(path).ok_or_else(|| format!("Log path must be 1 to {} bytes", LOG_PATH_LEN))?;
+                Ok(StubLogTarget::File(path.to_string()))
+            }
+            ("file", None) => Err("The file log target needs a log path".to_string()),
+            ("stderr" | "system", Some(_)) => Err("A log path only applies to the file log target".to_string()),
+            (other, _) => Err(format!("Unknown log target {:?}, expected stderr, system or file", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::LOG_TARGET_*` value and path field
+    pub fn footer_target(&self) -> (u8, [u8; LOG_PATH_LEN]) {
+        match self {
+            StubLogTarget::Stderr => (LOG_TARGET_STDERR, [0; LOG_PATH_LEN]),
+            StubLogTarget::System => (LOG_TARGET_SYSTEM, [0; LOG_PATH_LEN]),
+            StubLogTarget::File(path) => (LOG_TARGET_FILE, encode_padded::<LOG_PATH_LEN>(path).unwrap_or([0; LOG_PATH_LEN])),
+        }
+    }
+}
+
+/// Check an account name for `StubOptions::run_as_user`
+///
+/// Only portable POSIX user names are accepted, short enough for the footer.
+pub fn validate_run_as_user(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > RUN_AS_USER_LEN {
+        return Err(format!("User name must be 1 to {} characters", RUN_AS_USER_LEN));
+    }
+    if name.starts_with('-') || !name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-')) {
+        return Err(format!("Invalid user name: {:?}", name));
+    }
+    Ok(())
+}
+
+/// Check a name for `StubOptions::product_name`
+///
+/// It ends up in file names on Windows, so only letters, digits, spaces,
+/// `.`, `_` and `-` are accepted, without leading or trailing punctuation.
+pub fn validate_product_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > PRODUCT_NAME_LEN {
+        return Err(format!("Product name must be 1 to {} characters", PRODUCT_NAME_LEN));
+    }
+    let valid_chars = name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b' ' | b'.' | b'_' | b'-'));
+    let valid_ends = name.starts_with(|c: char| c.is_ascii_alphanumeric()) && !name.ends_with([' ', '.']);
+    if !valid_chars || !valid_ends {
+        return Err(format!("Invalid product name: {:?}", name));
+    }
+    Ok(())
+}
+
+/// Footer and trailing sections `merge_v2` writes behind the stub and payloads
+struct Layout<'a> {
+    footer: ConfigFooter,
+    seccomp_profile: Vec<u8>,
+    labels: Vec<u8>,
+    interpreter: &'a [u8],
+    install_files: Vec<u8>,
+    assets: Vec<u8>,
+}
+
+impl<'a> Layout<'a> {
+    /// Encode `options` for a stub of `stub_len` bytes; the footer is not sealed yet
+    fn new(stub_len: u64, base_info: &BinaryInfo, base_data: &[u8], overload_data: &[u8], options: &'a StubOptions) -> Result<Self> {
+        let base_len = base_data.len() as u64;
+        let overload_len = overload_data.len() as u64;
+
+        let base_offset = stub_len;
+        let overload_offset = base_offset + base_len;
+
+        if options.overload_jail != 0 && base_info.os != OperatingSystem::Linux {
+            log::warn!("⚠️  Overload jail is only supported on Linux, ignoring for {:?}", base_info.os);
+        }
+        let has_limits = options.base_memory_limit_mb > 0
+            || options.base_cpu_percent > 0
+            || options.overload_memory_limit_mb > 0
+            || options.overload_cpu_percent > 0;
+        if has_limits && !matches!(base_info.os, OperatingSystem::Linux | OperatingSystem::Windows) {
+            log::warn!("⚠️  Resource limits are only supported on Linux and Windows, ignoring for {:?}", base_info.os);
+        }
+        if (options.base_nofile_limit > 0 || options.overload_nofile_limit > 0) && base_info.os != OperatingSystem::Linux {
+            log::warn!("⚠️  Open file limits are only supported on Linux, ignoring for {:?}", base_info.os);
+        }
+        if (options.run_as_user.is_some() || options.drop_privileges) && base_info.os == OperatingSystem::Windows {
+            log::warn!("⚠️  Dropping privileges is only supported on Linux and macOS, ignoring for {:?}", base_info.os);
+        }
+        let run_as_user = match options.run_as_user.as_deref() {
+            Some(name) => {
+                validate_run_as_user(name).map_err(anyhow::Error::msg)?;
+                encode_padded::<RUN_AS_USER_LEN>(name).context("User name does not fit the footer")?
+            }
+            None => [0; RUN_AS_USER_LEN],
+        };
+        let seccomp_profile = match &options.seccomp_profile {
+            Some(_) if base_info.os != OperatingSystem::Linux => {
+                log::warn!("⚠️  Seccomp profiles are only supported on Linux, ignoring for {:?}", base_info.os);
+                Vec::new()
+            }
+            Some(profile) => profile.compile(base_info.arch).map_err(anyhow::Error::msg)?,
+            None => Vec::new(),
+        };
+        if options.exec_strategy != ExecStrategy::Auto && base_info.os != OperatingSystem::Linux {
+            log::warn!("⚠️  Exec strategies only apply to Linux, ignoring for {:?}", base_info.os);
+        }
+        if (options.base_umask.is_some() || options.overload_umask.is_some()) && base_info.os == OperatingSystem::Windows {
+            log::warn!("⚠️  umask is only supported on Linux and macOS, ignoring for {:?}", base_info.os);
+        }
+        if (options.base_cpu_affinity != 0 || options.overload_cpu_affinity != 0)
+            && !matches!(base_info.os, OperatingSystem::Linux | OperatingSystem::Windows)
+        {
+            log::warn!("⚠️  CPU affinity is only supported on Linux and Windows, ignoring for {:?}", base_info.os);
+        }
+        let (log_target, log_path) = options.log_target.footer_target();
+        if options.single_instance == SingleInstance::Signal && base_info.os == OperatingSystem::Windows {
+            log::warn!("⚠️  Single instance signal mode needs Unix signals, a second copy just exits on {:?}", base_info.os);
+        }
+        // Copies of the same payloads share a lock, whatever else the merge sets
+        let instance_id = if options.single_instance != SingleInstance::Off {
+            let mut id = ImageChecksum::new();
+            id.update(base_data);
+            id.update(overload_data);
+            id.finish()
+        } else {
+            0
+        };
+        if options.expires_at.is_some_and(|expires_at| expires_at <= chrono::Utc::now().timestamp()) {
+            log::warn!("⚠️  expires_at is already in the past, the merged binary will refuse to start");
+        }
+        let revocation_url = match &options.revocation {
+            Some(revocation) => encode_padded::<REVOCATION_URL_LEN>(&revocation.url).context("Revocation URL does not fit the footer")?,
+            None => [0; REVOCATION_URL_LEN],
+        };
+        let audit = options.audit.clone().unwrap_or_default();
+        let audit_path = match &audit.path {
+            Some(path) => encode_padded::<AUDIT_PATH_LEN>(path).context("Audit path does not fit the footer")?,
+            None => [0; AUDIT_PATH_LEN],
+        };
+        let audit_url = match &audit.url {
+            Some(url) => encode_padded::<AUDIT_URL_LEN>(url).context("Audit URL does not fit the footer")?,
+            None => [0; AUDIT_URL_LEN],
+        };
+        let crash_report = options.crash_report.clone().unwrap_or_default();
+        let crash_report_url = match &crash_report.url {
+            Some(url) => encode_padded::<CRASH_REPORT_URL_LEN>(url).context("Crash report URL does not fit the footer")?,
+            None => [0; CRASH_REPORT_URL_LEN],
+        };
+        let output_log_dir = match options.output_log.as_ref().and_then(|log| log.dir.as_ref()) {
+            Some(dir) => encode_padded::<OUTPUT_LOG_DIR_LEN>(dir).context("Output log directory does not fit the footer")?,
+            None => [0; OUTPUT_LOG_DIR_LEN],
+        };
+        let (readiness_probe, readiness_target) = match &options.readiness {
+            Some(readiness) => match readiness.footer_probe() {
+                (probe, Some(target)) => {
+                    (probe, encode_padded::<READINESS_TARGET_LEN>(target).context("Readiness probe target does not fit the footer")?)
+                }
+                (probe, None) => (probe, [0; READINESS_TARGET_LEN]),
+            },
+            None => (READINESS_NONE, [0; READINESS_TARGET_LEN]),
+        };
+        let telemetry_url = match &options.telemetry_url {
+            Some(url) => encode_padded::<TELEMETRY_URL_LEN>(url).context("Telemetry URL does not fit the footer")?,
+            None => [0; TELEMETRY_URL_LEN],
+        };
+        let (base_cwd_mode, base_cwd) = options.base_working_dir.footer_cwd();
+        let (overload_cwd_mode, overload_cwd) = options.overload_working_dir.footer_cwd();
+        let product_name = match options.product_name.as_deref() {
+            Some(name) => {
+                validate_product_name(name).map_err(anyhow::Error::msg)?;
+                encode_padded::<PRODUCT_NAME_LEN>(name).context("Product name does not fit the footer")?
+            }
+            None => [0; PRODUCT_NAME_LEN],
+        };
+        let seccomp_offset = overload_offset + overload_len;
+        let seccomp_len = seccomp_profile.len() as u64;
+        let labels = if options.embedded_labels.is_empty() {
+            Vec::new()
+        } else {
+            serde_json::to_vec(&options.embedded_labels).context("Failed to serialize labels")?
+        };
+        let labels_offset = seccomp_offset + seccomp_len;
+        let labels_len = labels.len() as u64;
+        let overload_kind = if options.overload_library {
+            OVERLOAD_LIBRARY
+        } else if is_script(overload_data) {
+            OVERLOAD_SCRIPT
+        } else {
+            OVERLOAD_EXECUTABLE
+        };
+        let overload_script = overload_kind == OVERLOAD_SCRIPT;
+        let interpreter: &[u8] = match &options.interpreter {
+            Some(interpreter) if overload_script => &interpreter.data,
+            Some(_) => {
+                log::warn!("⚠️  The overload is not a script, not bundling the interpreter");
+                &[]
+            }
+            None => &[],
+        };
+        if overload_script && interpreter.is_empty() && options.overload_jail & JAIL_EMPTY_ROOT != 0 {
+            log::warn!("⚠️  An empty-root jail hides the overload's #! interpreter; bundle a statically linked one");
+        }
+        validate_prerequisites(options).map_err(anyhow::Error::msg)?;
+        if options.overload_library
+            && (options.sync_mode
+                || options.prerequisite_runs > 0
+                || options.ipc_channel
+                || options.order != ORDER_OVERLOAD_FIRST
+                || options.overload_interval > 0
+                || options.overload_start_delay > 0
+                || options.overload_jail != 0
+                || options.overload_memory_limit_mb > 0
+                || options.overload_cpu_percent > 0
+                || options.overload_nofile_limit > 0
+                || options.overload_umask.is_some()
+                || options.overload_priority.is_some()
+                || options.overload_cpu_affinity != 0
+                || options.overload_working_dir != WorkingDir::default())
+        {
+            log::warn!("⚠️  A library overload runs inside base, ignoring the overload's ordering, schedule, jail, limits and IPC channel");
+        }
+        let kill_policy = match &options.kill_policy {
+            Some(policy) => policy.compile().map_err(anyhow::Error::msg)?,
+            None => [0; MAX_KILL_POLICY_OPS],
+        };
+        let interpreter_offset = labels_offset + labels_len;
+        let interpreter_len = interpreter.len() as u64;
+        let (install_dir, install_name) = match &options.installer {
+            Some(installer) => (
+                encode_padded::<INSTALL_DIR_LEN>(&installer.dir).context("Install directory does not fit the footer")?,
+                encode_padded::<INSTALL_NAME_LEN>(&installer.name).context("Install name does not fit the footer")?,
+            ),
+            None => ([0; INSTALL_DIR_LEN], [0; INSTALL_NAME_LEN]),
+        };
+        if options.installer.as_ref().is_some_and(|installer| installer.footer_flags() != 0) && base_info.os != OperatingSystem::Linux {
+            log::warn!("⚠️  Systemd units and shortcuts are only written on Linux, ignoring them for {:?}", base_info.os);
+        }
+        let install_files = options.installer.as_ref().map(|installer| encode_bundle(&installer.files)).unwrap_or_default();
+        let install_files_offset = interpreter_offset + interpreter_len;
+        let install_files_len = install_files.len() as u64;
+        // A merge with other payloads or data files installs again over this one
+        let install_id = if options.installer.is_some() {
+            let mut id = ImageChecksum::new();
+            id.update(base_data);
+            id.update(&install_files);
+            id.finish()
+        } else {
+            0
+        };
+        let assets = encode_bundle(&options.assets);
+        let assets_offset = install_files_offset + install_files_len;
+        let assets_len = assets.len() as u64;
+        let assets_id = if assets_len > 0 {
+            let mut id = ImageChecksum::new();
+            id.update(&assets);
+            id.finish()
+        } else {
+            0
+        };
+
+        let footer = ConfigFooter {
+            magic: *MAGIC_BYTES,
+            version: FOOTER_VERSION,
+            size: ConfigFooter::SIZE as u32,
+            base_offset,
+            base_size: base_len,
+            overload_offset,
+            overload_size: overload_len,
+            grace_period: options.grace_period,
+            sync_mode: if options.sync_mode { 1 } else { 0 },
+            network_failure_kill_count: options.network_failure_kill_count,
+            overload_jail: options.overload_jail,
+            order: options.order,
+            overload_start_delay: options.overload_start_delay,
+            overload_interval: options.overload_interval,
+            health_check_interval: options.health_check_interval,
+            kill_delay_ms: options.kill_delay_ms,
+            heartbeat_timeout: options.heartbeat_timeout,
+            shutdown_grace: options.shutdown_grace,
+            base_memory_limit_mb: options.base_memory_limit_mb,
+            base_cpu_percent: options.base_cpu_percent,
+            overload_memory_limit_mb: options.overload_memory_limit_mb,
+            overload_cpu_percent: options.overload_cpu_percent,
+            base_nofile_limit: options.base_nofile_limit,
+            overload_nofile_limit: options.overload_nofile_limit,
+            run_as_user,
+            drop_privileges: if options.drop_privileges { 1 } else { 0 },
+            seccomp_offset: if seccomp_len > 0 { seccomp_offset } else { 0 },
+            seccomp_size: seccomp_len,
+            hardening: if options.hardening { HARDEN_CHECKSUM | HARDEN_ANTI_DEBUG } else { 0 },
+            image_checksum: 0,
+            log_level: options.log_level.footer_level(),
+            log_target,
+            log_path,
+            product_name,
+            exec_strategy: options.exec_strategy.footer_value(),
+            base_cwd_mode,
+            base_cwd,
+            overload_cwd_mode,
+            overload_cwd,
+            base_umask: options.base_umask.map_or(0, |umask| UMASK_SET | umask),
+            overload_umask: options.overload_umask.map_or(0, |umask| UMASK_SET | umask),
+            single_instance: options.single_instance.footer_value(),
+            instance_id,
+            control_socket: if options.control_socket { 1 } else { 0 },
+            revocation_url,
+            revocation_interval: options.revocation.as_ref().map_or(0, |revocation| revocation.interval),
+            revocation_key: options.revocation.as_ref().map_or([0; REVOCATION_KEY_LEN], |revocation| revocation.public_key),
+            expires_at: options.expires_at.unwrap_or(0),
+            expiry_clock_checks: if options.expiry_clock_checks { 1 } else { 0 },
+            machine_binding: options.machine_binding.as_ref().map_or(0, |binding| binding.properties),
+            machine_fingerprints: options
+                .machine_binding
+                .as_ref()
+                .map_or([0; MAX_MACHINE_FINGERPRINTS], MachineBinding::footer_fingerprints),
+            audit_path,
+            audit_url,
+            telemetry_url,
+            labels_offset: if labels_len > 0 { labels_offset } else { 0 },
+            labels_size: labels_len,
+            overload_kind,
+            interpreter_offset: if interpreter_len > 0 { interpreter_offset } else { 0 },
+            interpreter_size: interpreter_len,
+            install_dir,
+            install_name,
+            install_flags: options.installer.as_ref().map_or(0, Installer::footer_flags),
+            install_id,
+            install_files_offset: if install_files_len > 0 { install_files_offset } else { 0 },
+            install_files_size: install_files_len,
+            assets_id,
+            assets_offset: if assets_len > 0 { assets_offset } else { 0 },
+            assets_size: assets_len,
+            prerequisite_runs: options.prerequisite_runs,
+            prerequisite_on_failure: options.prerequisite_on_failure.footer_value(),
+            prerequisite_max_failures: options.prerequisite_max_failures,
+            prerequisite_retry_delay: options.prerequisite_retry_delay,
+            ipc_channel: if options.ipc_channel { 1 } else { 0 },
+            kill_policy,
+            monitor_warmup: options.monitor_warmup,
+            arm_after_success: if options.arm_after_success { 1 } else { 0 },
+            crash_report: crash_report.footer_flags(),
+            crash_stderr_lines: crash_report.stderr_lines,
+            crash_report_url,
+            output_log: options.output_log.as_ref().map_or(0, OutputLog::footer_flags),
+            output_log_max_files: options.output_log.as_ref().map_or(0, |log| log.max_files),
+            output_log_max_size: options.output_log.as_ref().map_or(0, |log| log.max_size_mb),
+            output_log_dir,
+            base_priority: options.base_priority.and_then(encode_priority).unwrap_or(0),
+            overload_priority: options.overload_priority.and_then(encode_priority).unwrap_or(0),
+            base_cpu_affinity: options.base_cpu_affinity,
+            overload_cpu_affinity: options.overload_cpu_affinity,
+            readiness_timeout: options.readiness.as_ref().map_or(0, |readiness| readiness.timeout_secs),
+            readiness_probe,
+            readiness_on_timeout: match &options.readiness {
+                Some(readiness) if readiness.start_base_on_timeout => READINESS_START_BASE,
+                _ => READINESS_ABORT,
+            },
+            readiness_target,
+            // Set by `pack::pack_payloads` once the binary is assembled
+            packing: PACKING_NONE,
+            base_unpacked_size: 0,
+            overload_unpacked_size: 0,
+        };
+
+        Ok(Self { footer, seccomp_profile, labels, interpreter, install_files, assets })
+    }
+
+    /// Size of the merged binary for these sections
+    fn output_size(&self) -> u64 {
+        let sections = [&self.seccomp_profile[..], &self.labels, self.interpreter, &self.install_files, &self.assets];
+        self.footer.overload_offset + self.footer.overload_size + sections.iter().map(|section| section.len() as u64).sum::<u64>()
+            + ConfigFooter::SIZE as u64
+    }
+}
+
+/// What `merge_v2` would write for these inputs, without writing it
+pub fn plan_v2(job: &PlanJob<'_>) -> Result<MergePlan> {
+    let stub = job.stubs.select(job.base_info, job.options.stub_flavor)?;
+    preflight(&stub.bytes, job.base_info, job.overload)?;
+    let layout = Layout::new(stub.bytes.len() as u64, job.base_info, job.base, job.overload, job.options)?;
+    let mut warnings = footer_warnings(&layout.footer, job.base_info.os);
+    if job.options.pack {
+        warnings.push("pack: output_size is the size before packing".to_string());
+    }
+    Ok(MergePlan { output_size: layout.output_size(), warnings, stub: Some(stub) })
+}
+
+#[tracing::instrument(name = "assemble", skip_all, fields(
+    os = ?base_info.os,
+    arch = ?base_info.arch,
+    base_size = base.len(),
+    overload_size = overload.len(),
+))]
 pub async fn merge_v2(
-    base_data: &[u8],
-    overload_data: &[u8],
+    base: MergeInput<'_>,
+    overload: MergeInput<'_>,
     work_path: &Path,
     base_info: &BinaryInfo,
     task_id: &str,
-    redis_url: &str,
-    grace_period: u32,
-    sync_mode: bool,
-    network_failure_kill_count: u32,
+    progress: &SharedProgressSink,
+    stubs: &StubRegistry,
+    options: &StubOptions,
+    cancel: &CancelToken,
 ) -> Result<String> {
     log::info!("🧬 V2 Merging binaries with pre-compiled Rust stub...");
+    cancel.check()?;
+    let (base_data, overload_data) = (base.bytes, overload.bytes);
 
     // Initialize progress tracker
     let progress_tracker = if !task_id.is_empty() {
-        match ProgressTracker::new(redis_url, task_id.to_string()) {
-            Ok(tracker) => Some(tracker),
-            Err(e) => {
-                log::warn!("Failed to create progress tracker: {}", e);
-                None
-            }
-        }
+        Some(ProgressTracker::new(progress.clone(), task_id.to_string()))
     } else {
         None
     };
@@ -68,45 +1366,18 @@ pub async fn merge_v2(
         let _ = tracker.update(ProgressStep::DetectingPlatforms).await;
     }
 
-    // Select stub based on OS and Architecture
-    let stub_bytes = match (&base_info.os, &base_info.arch) {
-        // Linux
-        (OperatingSystem::Linux, Architecture::X86_64) => LINUX_X86_64_STUB,
-        (OperatingSystem::Linux, Architecture::X86) => LINUX_X86_STUB,
-        (OperatingSystem::Linux, Architecture::AArch64) => LINUX_AARCH64_STUB,
-        (OperatingSystem::Linux, arch) => {
-            anyhow::bail!("Unsupported Linux architecture: {:?}. Supported: x86_64, x86, aarch64", arch)
-        }
-        
-        // Windows
-        (OperatingSystem::Windows, Architecture::X86_64) => WINDOWS_X86_64_STUB,
-        (OperatingSystem::Windows, Architecture::X86) => WINDOWS_X86_STUB,
-        (OperatingSystem::Windows, Architecture::AArch64) => WINDOWS_AARCH64_STUB,
-        (OperatingSystem::Windows, arch) => {
-            anyhow::bail!("Unsupported Windows architecture: {:?}. Supported: x86_64, x86, aarch64", arch)
-        }
-        
-        // macOS
-        (OperatingSystem::MacOS, Architecture::X86_64) => MACOS_X86_64_STUB,
-        (OperatingSystem::MacOS, Architecture::AArch64) => MACOS_AARCH64_STUB,
-        (OperatingSystem::MacOS, arch) => {
-            anyhow::bail!("Unsupported macOS architecture: {:?}. Supported: x86_64, aarch64", arch)
-        }
-        
-        // Other OS
-        (os, _) => anyhow::bail!("Unsupported OS: {:?}", os),
-    };
-
-    log::info!("📦 Selected stub for {:?}/{:?} ({} bytes)", base_info.os, base_info.arch, stub_bytes.len());
+    let stub = stubs.select(base_info, options.stub_flavor)?;
+    let stub_bytes: &[u8] = &stub.bytes;
+    preflight(stub_bytes, base_info, overload_data)?;
 
-    // Check if stub is valid (not a dummy/empty stub from dev build)
-    if stub_bytes.is_empty() {
-        anyhow::bail!(
-            "Stub for {:?}/{:?} is empty. This platform may not be supported in the dev build. \
-            Use a production build for full platform support.",
-            base_info.os, base_info.arch
-        );
-    }
+    log::info!(
+        "📦 Selected stub for {:?}/{:?} ({}, {} bytes, {:?})",
+        base_info.os,
+        base_info.arch,
+        stub.abi,
+        stub_bytes.len(),
+        stub.source
+    );
 
     let output_filename = if base_info.os == OperatingSystem::Windows { "merged.exe" } else { "merged" };
     let output_path = work_path.join(output_filename);
@@ -115,52 +1386,78 @@ pub async fn merge_v2(
     let stub_len = stub_bytes.len() as u64;
     let base_len = base_data.len() as u64;
     let overload_len = overload_data.len() as u64;
+    let Layout { footer, seccomp_profile, labels, interpreter, install_files, assets } =
+        Layout::new(stub_len, base_info, base_data, overload_data, options)?;
+    let seccomp_len = seccomp_profile.len() as u64;
+    let labels_len = labels.len() as u64;
+    let interpreter_len = interpreter.len() as u64;
+    let install_files_len = install_files.len() as u64;
+    let assets_len = assets.len() as u64;
 
-    let base_offset = stub_len;
-    let overload_offset = base_offset + base_len;
-
-    // Create footer
-    let footer = ConfigFooter {
-        magic: *b"KILLCODE",
-        base_offset,
-        base_size: base_len,
-        overload_offset,
-        overload_size: overload_len,
-        grace_period,
-        sync_mode: if sync_mode { 1 } else { 0 },
-        network_failure_kill_count,
-    };
-
-    // Serialize footer
-    let footer_bytes = unsafe {
-        std::slice::from_raw_parts(
-            &footer as *const ConfigFooter as *const u8,
-            mem::size_of::<ConfigFooter>()
-        )
-    };
+    // Serialize footer, sealing everything in front of it when hardened
+    let mut footer_raw = [0u8; ConfigFooter::SIZE];
+    footer_raw.copy_from_slice(footer.as_bytes());
+    if options.hardening {
+        let mut checksum = ImageChecksum::new();
+        for part in [stub_bytes, base_data, overload_data, &seccomp_profile, &labels, interpreter, &install_files, &assets] {
+            checksum.update(part);
+        }
+        checksum.update_footer(&footer_raw);
+        checksum.write_into(&mut footer_raw);
+    }
+    let footer_bytes = &footer_raw[..];
 
-    log::info!("📦 Constructing binary: Stub ({} bytes) + Base ({} bytes) + Overload ({} bytes) + Footer ({} bytes)", 
-             stub_len, base_len, overload_len, footer_bytes.len());
+    log::info!("📦 Constructing binary: Stub ({} bytes) + Base ({} bytes) + Overload ({} bytes) + Seccomp ({} bytes) + Labels ({} bytes) + Interpreter ({} bytes) + Install files ({} bytes) + Assets ({} bytes) + Footer ({} bytes)", 
+             stub_len, base_len, overload_len, seccomp_len, labels_len, interpreter_len, install_files_len, assets_len, footer_bytes.len());
 
-    // Report: Compiling wrapper (Actually just assembling)
     if let Some(ref tracker) = progress_tracker {
-        let _ = tracker.update(ProgressStep::CompilingLoader).await;
+        let _ = tracker.update(ProgressStep::WritingBinaries).await;
     }
 
-    // Write everything to output file
-    let mut output_file = fs::File::create(&output_path)
+    cancel.check()?;
+
+    // Write everything to output file, splicing inputs that are on disk
+    let output_file = fs::File::create(&output_path)
         .context("Failed to create output file")?;
-    
-    output_file.write_all(stub_bytes).context("Failed to write stub")?;
-    output_file.write_all(base_data).context("Failed to write base binary")?;
-    output_file.write_all(overload_data).context("Failed to write overload binary")?;
-    output_file.write_all(footer_bytes).context("Failed to write footer")?;
+    let mut splicer = Splicer::new(output_file);
+    let total_size = stub_len + base_len + overload_len + seccomp_len + labels_len + interpreter_len + install_files_len + assets_len + footer_bytes.len() as u64;
+    let parts = [
+        ("stub", MergeInput::from(stub_bytes)),
+        ("base binary", base),
+        ("overload binary", overload),
+        ("seccomp profile", MergeInput::from(&seccomp_profile)),
+        ("labels", MergeInput::from(&labels)),
+        ("interpreter", MergeInput::from(interpreter)),
+        ("install files", MergeInput::from(&install_files)),
+        ("assets", MergeInput::from(&assets)),
+        ("footer", MergeInput::from(footer_bytes)),
+    ];
+    // Progress runs from WritingBinaries to Finalizing with the bytes written
+    let mut reported = ProgressStep::WritingBinaries.percentage();
+    for (name, part) in parts {
+        let start = splicer.written();
+        let mut source = splicer.source(&part);
+        while splicer.append_chunk(&mut source).with_context(|| format!("Failed to write {}", name))? > 0 {
+            cancel.check()?;
+            let Some(ref tracker) = progress_tracker else { continue };
+            let percentage = io_percentage(splicer.written(), total_size, ProgressStep::WritingBinaries, ProgressStep::Finalizing);
+            if percentage > reported {
+                reported = percentage;
+                let _ = tracker
+                    .report_io_progress(name, splicer.written(), total_size, ProgressStep::WritingBinaries, ProgressStep::Finalizing)
+                    .await;
+            }
+        }
+        if splicer.written() - start != part.len() {
+            anyhow::bail!("{} changed on disk while merging", name);
+        }
+    }
 
     // Make executable (skip for Windows if running on Linux, but doesn't hurt)
     if base_info.os != OperatingSystem::Windows {
-        let mut perms = output_file.metadata()?.permissions();
+        let mut perms = splicer.output().metadata()?.permissions();
         perms.set_mode(0o755);
-        output_file.set_permissions(perms)?;
+        splicer.output().set_permissions(perms)?;
     }
 
     // Report: Finalizing
@@ -170,3 +1467,259 @@ pub async fn merge_v2(
 
     Ok(output_path.to_string_lossy().into_owned())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_as_user_validation() {
+        assert!(validate_run_as_user("www-data").is_ok());
+        assert!(validate_run_as_user("svc_app.1").is_ok());
+        assert!(validate_run_as_user("").is_err());
+        assert!(validate_run_as_user("-rf").is_err());
+        assert!(validate_run_as_user("root:root").is_err());
+        assert!(validate_run_as_user(&"a".repeat(RUN_AS_USER_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_product_name_validation() {
+        assert!(validate_product_name("Acme Agent").is_ok());
+        assert!(validate_product_name("acme-sync_2.1").is_ok());
+        assert!(validate_product_name("").is_err());
+        assert!(validate_product_name("-flag").is_err());
+        assert!(validate_product_name("name.").is_err());
+        assert!(validate_product_name("..\\evil").is_err());
+        assert!(validate_product_name("a/b").is_err());
+        assert!(validate_product_name(&"a".repeat(PRODUCT_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_log_target_parsing() {
+        assert_eq!(StubLogTarget::parse("stderr", None), Ok(StubLogTarget::Stderr));
+        assert_eq!(StubLogTarget::parse("system", None), Ok(StubLogTarget::System));
+        assert_eq!(
+            StubLogTarget::parse("file", Some("/var/log/app.log")),
+            Ok(StubLogTarget::File("/var/log/app.log".to_string()))
+        );
+        assert!(StubLogTarget::parse("file", None).is_err());
+        assert!(StubLogTarget::parse("system", Some("/tmp/x")).is_err());
+        assert!(StubLogTarget::parse("file", Some(&"a".repeat(LOG_PATH_LEN + 1))).is_err());
+        assert!(StubLogTarget::parse("journald", None).is_err());
+        assert_eq!(StubLogLevel::parse("errors").map(StubLogLevel::footer_level), Ok(LOG_ERRORS));
+    }
+
+    #[test]
+    fn test_working_dir_and_umask_parsing() {
+        assert_eq!(WorkingDir::parse("binary_dir"), Ok(WorkingDir::BinaryDir));
+        assert_eq!(WorkingDir::parse("/opt/app"), Ok(WorkingDir::Path("/opt/app".to_string())));
+        assert!(WorkingDir::parse("C:\\Program Files\\App").is_ok());
+        assert!(WorkingDir::parse("relative/dir").is_err());
+        assert!(WorkingDir::parse(&format!("/{}", "a".repeat(CWD_PATH_LEN))).is_err());
+        assert_eq!(WorkingDir::parse("/opt/app").unwrap().footer_cwd().0, CWD_PATH);
+
+        assert_eq!(parse_umask("022"), Ok(0o022));
+        assert_eq!(parse_umask("0o027"), Ok(0o027));
+        assert_eq!(parse_umask("0"), Ok(0));
+        assert!(parse_umask("1000").is_err());
+        assert!(parse_umask("8").is_err());
+        assert!(parse_umask("").is_err());
+    }
+
+    #[test]
+    fn test_priority_and_cpu_affinity_parsing() {
+        assert_eq!(parse_priority("idle"), Ok(19));
+        assert_eq!(parse_priority("above_normal"), Ok(-5));
+        assert_eq!(parse_priority("-20"), Ok(-20));
+        assert!(parse_priority("20").is_err());
+        assert!(parse_priority("-128").is_err());
+        assert!(parse_priority("realtime").is_err());
+
+        assert_eq!(parse_cpu_affinity("0-3,6"), Ok(0b100_1111));
+        assert_eq!(parse_cpu_affinity("63"), Ok(1 << 63));
+        assert_eq!(parse_cpu_affinity(" 1 - 2 "), Ok(0b110));
+        assert!(parse_cpu_affinity("64").is_err());
+        assert!(parse_cpu_affinity("3-1").is_err());
+        assert!(parse_cpu_affinity("0,,1").is_err());
+        assert!(parse_cpu_affinity("").is_err());
+    }
+
+    #[test]
+    fn test_prerequisite_options() {
+        assert_eq!(PrerequisiteFailure::parse("restart").map(PrerequisiteFailure::footer_value), Ok(PREREQ_RESTART));
+        assert!(PrerequisiteFailure::parse("ignore").is_err());
+
+        let retry = StubOptions { prerequisite_runs: 3, prerequisite_on_failure: PrerequisiteFailure::Retry, ..StubOptions::default() };
+        assert!(validate_prerequisites(&retry).is_err());
+        assert!(validate_prerequisites(&StubOptions { prerequisite_max_failures: 2, ..retry }).is_ok());
+        assert!(validate_prerequisites(&StubOptions { prerequisite_runs: 3, ..StubOptions::default() }).is_ok());
+    }
+
+    #[test]
+    fn test_single_instance_parsing() {
+        assert_eq!(SingleInstance::parse("off").map(SingleInstance::footer_value), Ok(SINGLE_INSTANCE_OFF));
+        assert_eq!(SingleInstance::parse("signal").map(SingleInstance::footer_value), Ok(SINGLE_INSTANCE_SIGNAL));
+        assert!(SingleInstance::parse("true").is_err());
+    }
+
+    #[test]
+    fn test_machine_binding_parsing() {
+        let binding = MachineBinding::parse("", "0123456789abcdef, 0xfedcba9876543210").unwrap().unwrap();
+        assert_eq!(binding.properties, BIND_MACHINE_ID);
+        assert_eq!(binding.fingerprints, [0x0123456789abcdef, 0xfedcba9876543210]);
+        assert_eq!(binding.footer_fingerprints()[..3], [0x0123456789abcdef, 0xfedcba9876543210, 0]);
+
+        let binding = MachineBinding::parse("hostname, mac", "1\n2").unwrap().unwrap();
+        assert_eq!(binding.properties, BIND_HOSTNAME | BIND_MAC);
+        assert_eq!(MachineBinding::parse("", ""), Ok(None));
+
+        assert!(MachineBinding::parse("mac", "").is_err());
+        assert!(MachineBinding::parse("cpu", "1").is_err());
+        assert!(MachineBinding::parse("", "not-hex").is_err());
+        assert!(MachineBinding::parse("", "0").is_err());
+        assert!(MachineBinding::parse("", &"1 ".repeat(MAX_MACHINE_FINGERPRINTS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_audit_log_parsing() {
+        let audit = AuditLog::parse(" /var/log/acme-audit.jsonl ", "").unwrap().unwrap();
+        assert_eq!((audit.path.as_deref(), audit.url), (Some("/var/log/acme-audit.jsonl"), None));
+        let audit = AuditLog::parse("", "http://telemetry.example:8080/runs").unwrap().unwrap();
+        assert_eq!(audit.url.as_deref(), Some("http://telemetry.example:8080/runs"));
+        assert_eq!(AuditLog::parse("", " "), Ok(None));
+
+        assert!(AuditLog::parse("", "https://telemetry.example/runs").is_err());
+        assert!(AuditLog::parse(&"a".repeat(AUDIT_PATH_LEN + 1), "").is_err());
+    }
+
+    #[test]
+    fn test_crash_report_parsing() {
+        let report = CrashReport::parse(true, "", 50, true).unwrap().unwrap();
+        assert_eq!((report.url, report.stderr_lines), (None, 50));
+        assert_eq!(report.footer_flags(), CRASH_REPORT_FILE | CRASH_REPORT_CORE_DUMP);
+        let report = CrashReport::parse(false, " http://crash.example/reports ", 0, false).unwrap().unwrap();
+        assert_eq!(report.url.as_deref(), Some("http://crash.example/reports"));
+        assert_eq!(report.footer_flags(), 0);
+        assert_eq!(CrashReport::parse(false, " ", 0, false), Ok(None));
+
+        assert!(CrashReport::parse(false, "", 50, false).is_err());
+        assert!(CrashReport::parse(false, "", 0, true).is_err());
+        assert!(CrashReport::parse(true, "", MAX_CRASH_STDERR_LINES as u32 + 1, false).is_err());
+        assert!(CrashReport::parse(false, "https://crash.example/reports", 0, false).is_err());
+    }
+
+    #[test]
+    fn test_output_log_parsing() {
+        let log = OutputLog::parse("base, overload", " /var/log/acme ", 0, 0).unwrap().unwrap();
+        assert_eq!(log.dir.as_deref(), Some("/var/log/acme"));
+        assert_eq!((log.max_size_mb, log.max_files), (OutputLog::DEFAULT_MAX_SIZE_MB, OutputLog::DEFAULT_MAX_FILES));
+        assert_eq!(log.footer_flags(), OUTPUT_LOG_BASE | OUTPUT_LOG_OVERLOAD);
+        let log = OutputLog::parse("overload", "", 50, 3).unwrap().unwrap();
+        assert_eq!((log.dir, log.max_size_mb, log.max_files), (None, 50, 3));
+        assert_eq!(log.footer_flags(), OUTPUT_LOG_OVERLOAD);
+        assert_eq!(OutputLog::parse(" ", "", 0, 0), Ok(None));
+
+        assert!(OutputLog::parse("stdout", "", 0, 0).is_err());
+        assert!(OutputLog::parse("", "logs", 0, 0).is_err());
+        assert!(OutputLog::parse("base", "", 0, MAX_OUTPUT_LOG_FILES as u32 + 1).is_err());
+        assert!(OutputLog::parse("base", &"a".repeat(OUTPUT_LOG_DIR_LEN + 1), 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_readiness_parsing() {
+        let readiness = Readiness::parse(" tcp:127.0.0.1:8080 ", 0, "").unwrap().unwrap();
+        assert_eq!(readiness.probe, ReadinessProbe::Tcp("127.0.0.1:8080".to_string()));
+        assert_eq!((readiness.timeout_secs, readiness.start_base_on_timeout), (Readiness::DEFAULT_TIMEOUT_SECS, false));
+        let readiness = Readiness::parse("file:run/ready", 10, "start_base").unwrap().unwrap();
+        assert_eq!(readiness.footer_probe(), (READINESS_FILE, Some("run/ready")));
+        assert_eq!((readiness.timeout_secs, readiness.start_base_on_timeout), (10, true));
+        assert_eq!(Readiness::parse("ready_flag", 0, "abort").unwrap().unwrap().footer_probe(), (READINESS_FLAG, None));
+        assert_eq!(Readiness::parse("", 0, ""), Ok(None));
+
+        assert!(Readiness::parse("tcp:localhost", 0, "").is_err());
+        assert!(Readiness::parse("tcp::8080", 0, "").is_err());
+        assert!(Readiness::parse("tcp:localhost:0", 0, "").is_err());
+        assert!(Readiness::parse("file:", 0, "").is_err());
+        assert!(Readiness::parse("http:localhost", 0, "").is_err());
+        assert!(Readiness::parse("ready_flag", 0, "retry").is_err());
+        assert!(Readiness::parse("", 30, "").is_err());
+        assert!(Readiness::parse(&format!("file:{}", "a".repeat(READINESS_TARGET_LEN + 1)), 0, "").is_err());
+    }
+
+    #[test]
+    fn test_telemetry_url() {
+        const ID: &str = "0b6f1e2c-5d4a-4f3e-9c8b-7a6d5e4f3a2b";
+        assert_eq!(
+            telemetry_url("http://weaver.internal:8080/", ID),
+            Ok(format!("http://weaver.internal:8080/telemetry/{}", ID))
+        );
+        assert!(telemetry_url("https://weaver.internal", ID).is_err());
+        assert!(telemetry_url("http://", ID).is_err());
+        assert!(telemetry_url(&format!("http://{}", "w".repeat(TELEMETRY_URL_LEN)), ID).is_err());
+    }
+
+    #[test]
+    fn test_expires_at_parsing() {
+        assert_eq!(parse_expires_at("1900000000"), Ok(1_900_000_000));
+        assert_eq!(parse_expires_at("2030-03-17T17:46:40Z"), Ok(1_900_000_000));
+        assert_eq!(parse_expires_at("2030-03-17T18:46:40+01:00"), Ok(1_900_000_000));
+        assert!(parse_expires_at("2030-03-17").is_err());
+        assert!(parse_expires_at("-5").is_err());
+    }
+
+    #[test]
+    fn test_revocation_parsing() {
+        const RAW: &str = "iojj3XQJ8ZX9UtstPLpdcspnCb8dlBIb83SIAbQPb1w=";
+        const SPKI: &str = "MCowBQYDK2VwAyEAiojj3XQJ8ZX9UtstPLpdcspnCb8dlBIb83SIAbQPb1w=";
+        let revocation = Revocation::parse("http://licenses.example/acme", RAW, 60).unwrap().unwrap();
+        assert_eq!((revocation.public_key[0], revocation.interval), (0x8a, 60));
+        let from_spki = Revocation::parse("http://licenses.example:8080/acme", SPKI, 0).unwrap().unwrap();
+        assert_eq!(from_spki.public_key, revocation.public_key);
+        assert_eq!(Revocation::parse("", "", 60), Ok(None));
+
+        assert!(Revocation::parse("http://licenses.example/acme", "", 0).is_err());
+        assert!(Revocation::parse("https://licenses.example/acme", RAW, 0).is_err());
+        assert!(Revocation::parse("http:///acme", RAW, 0).is_err());
+        assert!(Revocation::parse("http://licenses.example/acme", "AAAA", 0).is_err());
+    }
+
+    #[test]
+    fn test_exec_strategy_parsing() {
+        assert_eq!(ExecStrategy::parse("auto").map(ExecStrategy::footer_value), Ok(EXEC_AUTO));
+        assert_eq!(ExecStrategy::parse("tmpfile"), Ok(ExecStrategy::Tmpfile));
+        assert_eq!(ExecStrategy::parse("tempfile").map(ExecStrategy::footer_value), Ok(EXEC_TEMP_FILE));
+        assert!(ExecStrategy::parse("memfd_create").is_err());
+    }
+
+    #[test]
+    fn test_installer_parsing() {
+        let files = || vec![DataFile::new("conf/app.toml", b"x=1"), DataFile::new("run.sh", b"#!/bin/sh\n")];
+        let installer = Installer::parse(" /opt/acme ", "acme-agent", files(), true, false).unwrap().unwrap();
+        assert_eq!((installer.dir.as_str(), installer.name.as_str()), ("/opt/acme", "acme-agent"));
+        assert_eq!(installer.footer_flags(), INSTALL_SYSTEMD_UNIT);
+
+        let encoded = encode_bundle(&installer.files);
+        let parsed: Vec<_> = bundle::files(&encoded).map(Result::unwrap).collect();
+        assert_eq!(parsed.iter().map(|file| (file.path, file.mode)).collect::<Vec<_>>(),
+                   [("conf/app.toml", bundle::MODE_FILE), ("run.sh", bundle::MODE_EXECUTABLE)]);
+
+        assert_eq!(Installer::parse("", "", Vec::new(), false, false), Ok(None));
+        assert!(Installer::parse("", "acme", Vec::new(), false, false).is_err());
+        assert!(Installer::parse("opt/acme", "acme", Vec::new(), false, false).is_err());
+        assert!(Installer::parse("/opt/acme", "", Vec::new(), false, false).is_err());
+        assert!(Installer::parse("/opt/acme", "../acme", Vec::new(), false, false).is_err());
+        assert!(Installer::parse("/opt/acme", "acme", vec![DataFile::new("../etc/passwd", b"")], false, false).is_err());
+        assert!(Installer::parse("/opt/acme", "acme", vec![DataFile::new("acme", b"")], false, false).is_err());
+        let nested = vec![DataFile::new("conf", b""), DataFile::new("conf/app.toml", b"")];
+        assert!(Installer::parse("/opt/acme", "acme", nested, false, false).is_err());
+    }
+
+    #[test]
+    fn test_assets_validation() {
+        assert!(validate_assets(&[DataFile::new("models/small.bin", b"\0"), DataFile::new("LICENSE", b"MIT")]).is_ok());
+        assert!(validate_assets(&[DataFile::new("/etc/hosts", b"")]).is_err());
+        assert!(validate_assets(&[DataFile::new("a.txt", b"1"), DataFile::new("a.txt", b"2")]).is_err());
+        let many: Vec<DataFile> = (0..=MAX_DATA_FILES).map(|i| DataFile::new(&format!("{}.txt", i), b"")).collect();
+        assert!(validate_assets(&many).is_err());
+    }
+}
@@ -1,6 +1,29 @@
 pub mod progress;
 pub mod binary;
 pub mod merger;
+pub mod digest;
+pub mod diskguard;
+pub mod report;
+#[cfg(feature = "server")]
+pub mod notify;
+#[cfg(feature = "server")]
+pub mod fetch;
+pub mod jobs;
+#[cfg(feature = "server")]
+pub mod oci;
+pub mod store;
+pub mod scheduler;
+pub mod selftest;
+pub mod signing;
+pub mod auth;
+pub mod workspace;
+#[cfg(feature = "server")]
+pub mod reload;
+pub mod scan;
+#[cfg(feature = "server")]
+pub mod policy;
+pub mod provenance;
+pub mod retention;
 
 pub use merger::merge_binaries;
 pub use binary::{Architecture, OperatingSystem, BinaryInfo};
@@ -0,0 +1,267 @@
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use serde::Serialize;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::core::fetch;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the HMAC-SHA256 of the request body (`sha256=<hex>`)
+pub const SIGNATURE_HEADER: &str = "X-Weaver-Signature";
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Payload POSTed to a merge's `callback_url` when it finishes
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeNotification {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: i64,
+}
+
+impl MergeNotification {
+    pub fn success(task_id: Option<String>, binary_id: &str, size: u64) -> Self {
+        Self {
+            success: true,
+            task_id,
+            binary_id: Some(binary_id.to_string()),
+            size: Some(size),
+            download_url: Some(format!("/download/{}", binary_id)),
+            error: None,
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    pub fn failure(task_id: Option<String>, error: String) -> Self {
+        Self {
+            success: false,
+            task_id,
+            binary_id: None,
+            size: None,
+            download_url: None,
+            error: Some(error),
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+/// Check a client-supplied callback URL before accepting the merge
+///
+/// Callbacks need `WEAVER_WEBHOOK_SECRET`, so every one is signed. With
+/// `WEAVER_WEBHOOK_ALLOWED_HOSTS` set only those hosts are accepted;
+/// otherwise any host that resolves to public addresses only, so a client
+/// can't make the service POST to loopback, link-local or private networks.
+pub async fn validate_callback_url(url: &str, config: &Config) -> Result<(), String> {
+    if config.webhook_secret.is_none() {
+        return Err("Callbacks are disabled; set WEAVER_WEBHOOK_SECRET so they can be signed".to_string());
+    }
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid callback_url: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("Unsupported callback_url scheme: {}", parsed.scheme()));
+    }
+    target_addrs(&parsed, &config.webhook_allowed_hosts).await.map(|_| ())
+}
+
+/// Addresses a callback to `url` may connect to; `None` for an allow-listed host
+async fn target_addrs(url: &Url, allowed_hosts: &[String]) -> Result<Option<Vec<SocketAddr>>, String> {
+    let host = url.host_str().ok_or("callback_url has no host")?;
+    if !allowed_hosts.is_empty() {
+        if !fetch::host_allowed(host, allowed_hosts) {
+            return Err(format!("Host {} is not in WEAVER_WEBHOOK_ALLOWED_HOSTS", host));
+        }
+        return Ok(None);
+    }
+
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Can't resolve callback_url host {}: {}", host, e))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("callback_url host {} has no addresses", host));
+    }
+    match addrs.iter().find(|addr| !is_public(addr.ip())) {
+        Some(addr) => Err(format!("callback_url host {} resolves to {}, which isn't a public address", host, addr.ip())),
+        None => Ok(Some(addrs)),
+    }
+}
+
+/// Whether `ip` is reachable on the internet rather than loopback, link-local,
+/// private, shared (CGNAT) or otherwise special
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || first == 0
+                || (first == 100 && second & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Deliver a notification in the background so the merge response isn't delayed
+///
+/// Skipped if `WEAVER_WEBHOOK_SECRET` was removed since the merge was accepted.
+pub fn spawn_webhook(config: &Config, callback_url: String, notification: MergeNotification) {
+    let Some(secret) = config.webhook_secret.clone() else {
+        log::warn!("⚠️  Not calling back {}: WEAVER_WEBHOOK_SECRET is unset", callback_url);
+        return;
+    };
+    let allowed_hosts = config.webhook_allowed_hosts.clone();
+    let max_attempts = config.webhook_max_attempts.max(1);
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = deliver(&callback_url, &notification, &secret, &allowed_hosts, max_attempts).await {
+            log::error!("❌ Webhook to {} failed: {}", callback_url, e);
+        }
+    });
+}
+
+/// POST the notification, retrying with exponential backoff on errors and non-2xx responses
+///
+/// The host is resolved and checked again, and the request pinned to the
+/// addresses checked, so a DNS change after the merge was accepted can't
+/// point it elsewhere. Redirects aren't followed.
+pub async fn deliver(
+    callback_url: &str,
+    notification: &MergeNotification,
+    secret: &str,
+    allowed_hosts: &[String],
+    max_attempts: u32,
+) -> anyhow::Result<()> {
+    let url = Url::parse(callback_url)?;
+    let mut client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).redirect(reqwest::redirect::Policy::none());
+    if let (Some(host), Some(addrs)) = (url.host_str(), target_addrs(&url, allowed_hosts).await.map_err(anyhow::Error::msg)?) {
+        client = client.resolve_to_addrs(host, &addrs);
+    }
+    let client = client.build()?;
+    let body = serde_json::to_vec(notification)?;
+
+    let mut last_error = String::new();
+    for attempt in 0..max_attempts {
+        if attempt > 0 {
+            tokio::time::sleep(backoff_delay(attempt - 1)).await;
+        }
+
+        let request = client
+            .post(url.clone())
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)))
+            .body(body.clone());
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                log::info!("📨 Webhook delivered to {} (attempt {})", callback_url, attempt + 1);
+                return Ok(());
+            }
+            Ok(response) => last_error = format!("HTTP {}", response.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+
+        log::warn!("Webhook attempt {}/{} to {} failed: {}", attempt + 1, max_attempts, callback_url, last_error);
+    }
+
+    anyhow::bail!("giving up after {} attempts: {}", max_attempts, last_error)
+}
+
+/// Hex-encoded HMAC-SHA256 of the body, keyed with the shared webhook secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+/// Delay before retry number `retry` (0-based): 1s, 2s, 4s, ... capped at 60s
+fn backoff_delay(retry: u32) -> Duration {
+    INITIAL_BACKOFF
+        .checked_mul(1u32.checked_shl(retry).unwrap_or(u32::MAX))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_matches_known_hmac() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff_delay(0), Duration::from_secs(1));
+        assert_eq!(backoff_delay(3), Duration::from_secs(8));
+        assert_eq!(backoff_delay(10), MAX_BACKOFF);
+        assert_eq!(backoff_delay(40), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_callback_url_validation() {
+        let config = Config { webhook_secret: Some("key".to_string()), ..Config::default() };
+        assert!(validate_callback_url("https://93.184.215.14/hook", &config).await.is_ok());
+        assert!(validate_callback_url("ftp://93.184.215.14/hook", &config).await.is_err());
+        assert!(validate_callback_url("not a url", &config).await.is_err());
+        for internal in ["http://127.0.0.1:8080/", "http://169.254.169.254/latest", "http://10.0.0.5/", "http://[::1]/", "http://[::ffff:192.168.1.1]/"] {
+            assert!(validate_callback_url(internal, &config).await.is_err(), "{} accepted", internal);
+        }
+        assert!(validate_callback_url("http://localhost/hook", &config).await.is_err());
+
+        // Allow-listed hosts may be internal; nothing else is accepted then
+        let config = Config { webhook_allowed_hosts: vec!["hooks.internal".to_string()], ..config };
+        assert!(validate_callback_url("http://hooks.internal/merge", &config).await.is_ok());
+        assert!(validate_callback_url("https://93.184.215.14/hook", &config).await.is_err());
+
+        let unsigned = Config { webhook_secret: None, ..config };
+        assert!(validate_callback_url("http://hooks.internal/merge", &unsigned).await.is_err());
+    }
+
+    #[test]
+    fn test_public_addresses() {
+        for public in ["93.184.215.14", "8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public(public.parse().unwrap()), "{}", public);
+        }
+        for internal in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.0.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(internal.parse().unwrap()), "{}", internal);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use actix_web::web::Bytes;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::core::digest::sha256_hex;
+use crate::core::fetch::host_allowed;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub const MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const DOCKER_MANIFEST_TYPE: &str = "application/vnd.docker.distribution.manifest.v2+json";
+/// `artifactType` of pushed merged binaries
+pub const ARTIFACT_TYPE: &str = "application/vnd.weaver.merged-binary.v1";
+const LAYER_TYPE: &str = "application/octet-stream";
+const EMPTY_CONFIG_TYPE: &str = "application/vnd.oci.empty.v1+json";
+const EMPTY_CONFIG: &[u8] = b"{}";
+
+/// `oci://registry/repository:tag` or `oci://registry/repository@sha256:<hex>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub registry: String,
+    pub repository: String,
+    /// Tag or `sha256:<hex>` manifest digest
+    pub reference: String,
+}
+
+impl Reference {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let rest = value.strip_prefix("oci://").ok_or("OCI references start with oci://")?;
+        let (registry, path) = rest.split_once('/').ok_or("expected oci://registry/repository")?;
+
+        let (repository, reference) = match path.split_once('@') {
+            Some((repository, digest)) => {
+                let hex = digest.strip_prefix("sha256:").ok_or("only sha256 digests are supported")?;
+                if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(format!("invalid digest {}", digest));
+                }
+                (repository, digest.to_ascii_lowercase())
+            }
+            // A colon after the last slash separates the tag
+            None => match path.rsplit_once(':').filter(|(_, tag)| !tag.contains('/')) {
+                Some((repository, tag)) => (repository, tag.to_string()),
+                None => (path, "latest".to_string()),
+            },
+        };
+
+        let valid_repository = !repository.is_empty()
+            && repository
+                .split('/')
+                .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"._-".contains(&b)));
+        if registry.is_empty() || !valid_repository {
+            return Err(format!("invalid repository {}/{}", registry, repository));
+        }
+        if reference.is_empty() || reference.len() > 128 {
+            return Err("tags are 1 to 128 characters".to_string());
+        }
+        Ok(Self { registry: registry.to_string(), repository: repository.to_string(), reference })
+    }
+
+    pub fn is_digest(&self) -> bool {
+        self.reference.starts_with("sha256:")
+    }
+
+    /// Registry host without its port, as matched against `WEAVER_FETCH_ALLOWED_HOSTS`
+    pub fn host(&self) -> &str {
+        self.registry.split(':').next().unwrap_or(&self.registry)
+    }
+
+    /// Parse a client-supplied reference to an allow-listed registry; pushes need a tag
+    pub fn parse_allowed(value: &str, allowed_hosts: &[String], push: bool) -> Result<Self, String> {
+        let reference = Self::parse(value)?;
+        reference.validate(allowed_hosts, push)?;
+        Ok(reference)
+    }
+
+    fn validate(&self, allowed_hosts: &[String], push: bool) -> Result<(), String> {
+        if allowed_hosts.is_empty() {
+            return Err("OCI references are disabled; set WEAVER_FETCH_ALLOWED_HOSTS".to_string());
+        }
+        if !host_allowed(self.host(), allowed_hosts) {
+            return Err(format!("Registry {} is not in WEAVER_FETCH_ALLOWED_HOSTS", self.host()));
+        }
+        if push && self.is_digest() {
+            return Err("Push to a tag, not a digest".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.is_digest() { '@' } else { ':' };
+        write!(f, "oci://{}/{}{}{}", self.registry, self.repository, separator, self.reference)
+    }
+}
+
+/// Distribution API client for one repository, handling token auth
+struct Client {
+    http: reqwest::Client,
+    base: String,
+    repository: String,
+    credentials: Option<(String, String)>,
+    /// `Authorization` header value once a challenge was answered
+    authorization: Option<String>,
+}
+
+impl Client {
+    fn new(reference: &Reference, config: &Config) -> Result<Self> {
+        // Docker Hub's API lives on a different host than its references
+        let registry = if reference.registry == "docker.io" { "registry-1.docker.io" } else { &reference.registry };
+        Ok(Self {
+            http: reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            base: format!("https://{}/v2/{}", registry, reference.repository),
+            repository: reference.repository.clone(),
+            credentials: config.oci_username.clone().zip(config.oci_password.clone()),
+            authorization: None,
+        })
+    }
+
+    /// Send a request, answering one authentication challenge if needed
+    async fn send(&mut self, actions: &str, build: impl Fn(&reqwest::Client) -> RequestBuilder) -> Result<Response> {
+        let response = self.authorized(build(&self.http)).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED || self.authorization.is_some() {
+            return Ok(response);
+        }
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| anyhow!("registry returned 401 without a challenge"))?
+            .to_string();
+        self.authorization = Some(self.authenticate(&challenge, actions).await?);
+        Ok(self.authorized(build(&self.http)).send().await?)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.authorization {
+            Some(authorization) => request.header(AUTHORIZATION, authorization),
+            None => request,
+        }
+    }
+
+    async fn authenticate(&self, challenge: &str, actions: &str) -> Result<String> {
+        let (scheme, params) = parse_challenge(challenge);
+        if scheme.eq_ignore_ascii_case("basic") {
+            let (username, password) = self.credentials.as_ref().context("registry requires WEAVER_OCI_USERNAME/PASSWORD")?;
+            let encoded = base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+            return Ok(format!("Basic {}", encoded));
+        }
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            bail!("unsupported registry auth scheme {}", scheme);
+        }
+
+        let realm = params.get("realm").context("bearer challenge without a realm")?;
+        let scope = format!("repository:{}:{}", self.repository, actions);
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = params.get("service") {
+            query.push(("service", service.as_str()));
+        }
+        let mut request = self.http.get(realm).query(&query);
+        if let Some((username, password)) = &self.credentials {
+            request = request.basic_auth(username, Some(password));
+        }
+        let body: Value = request.send().await?.error_for_status()?.json().await?;
+        let token = body["token"].as_str().or(body["access_token"].as_str()).context("token response without a token")?;
+        Ok(format!("Bearer {}", token))
+    }
+}
+
+/// Scheme and parameters of a `WWW-Authenticate` header
+fn parse_challenge(challenge: &str) -> (String, BTreeMap<String, String>) {
+    let (scheme, rest) = challenge.trim().split_once(' ').unwrap_or((challenge.trim(), ""));
+    let mut params = BTreeMap::new();
+    let mut rest = rest.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_ascii_lowercase();
+        let value = value.trim_start();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        params.insert(key, value.to_string());
+        rest = remainder.trim_start_matches(',').trim();
+    }
+    (scheme.to_string(), params)
+}
+
+/// Fetch the single layer of an OCI artifact, verified against its digest
+pub async fn pull(reference: &Reference, config: &Config) -> Result<Vec<u8>> {
+    let mut client = Client::new(reference, config)?;
+
+    let url = format!("{}/manifests/{}", client.base, reference.reference);
+    let response = client
+        .send("pull", |http| http.get(&url).header(ACCEPT, format!("{}, {}", MANIFEST_TYPE, DOCKER_MANIFEST_TYPE)))
+        .await?
+        .error_for_status()?;
+    let manifest = response.bytes().await?;
+    if reference.is_digest() && format!("sha256:{}", sha256_hex(&manifest)) != reference.reference {
+        bail!("manifest doesn't match {}", reference.reference);
+    }
+    let manifest: Value = serde_json::from_slice(&manifest).context("manifest is not JSON")?;
+    if manifest.get("manifests").is_some() {
+        bail!("{} is an image index; reference a single manifest by digest", reference);
+    }
+    let layers = manifest["layers"].as_array().context("manifest has no layers")?;
+    let [layer] = layers.as_slice() else {
+        bail!("expected an artifact with exactly one layer, found {}", layers.len());
+    };
+    let digest = layer["digest"].as_str().context("layer without a digest")?.to_string();
+    let expected = digest.strip_prefix("sha256:").context("only sha256 layers are supported")?;
+    if layer["size"].as_u64().is_some_and(|size| size as usize > config.max_file_size) {
+        bail!("layer of {} bytes exceeds the {} byte limit", layer["size"], config.max_file_size);
+    }
+
+    let url = format!("{}/blobs/{}", client.base, digest);
+    let mut response = client.send("pull", |http| http.get(&url)).await?.error_for_status()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if data.len() + chunk.len() > config.max_file_size {
+            bail!("layer exceeds the {} byte limit", config.max_file_size);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    if sha256_hex(&data) != expected {
+        bail!("layer doesn't match {}", digest);
+    }
+    Ok(data)
+}
+
+/// Push `data` as a single-layer artifact tagged `reference`, returning the
+/// pushed manifest's digest reference
+pub async fn push(reference: &Reference, data: Bytes, annotations: BTreeMap<String, String>, config: &Config) -> Result<Reference> {
+    let mut client = Client::new(reference, config)?;
+
+    let layer_digest = format!("sha256:{}", sha256_hex(&data));
+    let layer_size = data.len();
+    let config_digest = format!("sha256:{}", sha256_hex(EMPTY_CONFIG));
+    push_blob(&mut client, &config_digest, Bytes::from_static(EMPTY_CONFIG)).await?;
+    push_blob(&mut client, &layer_digest, data).await?;
+
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_TYPE,
+        "artifactType": ARTIFACT_TYPE,
+        "config": { "mediaType": EMPTY_CONFIG_TYPE, "digest": config_digest, "size": EMPTY_CONFIG.len() },
+        "layers": [{
+            "mediaType": LAYER_TYPE,
+            "digest": layer_digest,
+            "size": layer_size,
+            "annotations": { "org.opencontainers.image.title": "merged_binary" },
+        }],
+        "annotations": annotations,
+    });
+    let manifest = Bytes::from(serde_json::to_vec(&manifest)?);
+    let digest = format!("sha256:{}", sha256_hex(&manifest));
+
+    let url = format!("{}/manifests/{}", client.base, reference.reference);
+    client
+        .send("pull,push", |http| http.put(&url).header(CONTENT_TYPE, MANIFEST_TYPE).body(manifest.clone()))
+        .await?
+        .error_for_status()
+        .context("manifest upload rejected")?;
+
+    Ok(Reference { reference: digest, ..reference.clone() })
+}
+
+/// Upload a blob unless the registry already has it
+async fn push_blob(client: &mut Client, digest: &str, data: Bytes) -> Result<()> {
+    let url = format!("{}/blobs/{}", client.base, digest);
+    if client.send("pull,push", |http| http.head(&url)).await?.status().is_success() {
+        return Ok(());
+    }
+
+    let url = format!("{}/blobs/uploads/", client.base);
+    let response = client.send("pull,push", |http| http.post(&url)).await?.error_for_status()?;
+    let location = upload_location(response.headers(), &client.base)?;
+    let separator = if location.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}digest={}", location, separator, digest);
+    client
+        .send("pull,push", |http| {
+            http.put(&url).header(CONTENT_TYPE, "application/octet-stream").body(data.clone())
+        })
+        .await?
+        .error_for_status()
+        .with_context(|| format!("upload of {} rejected", digest))?;
+    Ok(())
+}
+
+/// Absolute URL of the upload session a registry answered with
+fn upload_location(headers: &HeaderMap, base: &str) -> Result<String> {
+    let location = headers.get(LOCATION).and_then(|value| value.to_str().ok()).context("upload without a Location")?;
+    if location.starts_with("http://") || location.starts_with("https://") {
+        return Ok(location.to_string());
+    }
+    let origin = base.splitn(4, '/').take(3).collect::<Vec<_>>().join("/");
+    Ok(format!("{}{}", origin, location))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reference() {
+        let tagged = Reference::parse("oci://registry.example:5000/team/app:v1.2").unwrap();
+        assert_eq!(tagged.registry, "registry.example:5000");
+        assert_eq!(tagged.host(), "registry.example");
+        assert_eq!(tagged.repository, "team/app");
+        assert_eq!(tagged.reference, "v1.2");
+        assert_eq!(Reference::parse("oci://registry.example/app").unwrap().reference, "latest");
+
+        let digest = format!("oci://registry.example/app@sha256:{}", "A".repeat(64));
+        let pinned = Reference::parse(&digest).unwrap();
+        assert!(pinned.is_digest());
+        assert_eq!(pinned.to_string(), digest.replace('A', "a"));
+
+        assert!(Reference::parse("https://registry.example/app").is_err());
+        assert!(Reference::parse("oci://registry.example/App").is_err());
+        assert!(Reference::parse("oci://registry.example/app@sha256:1234").is_err());
+        assert!(pinned.validate(&["registry.example".to_string()], true).is_err());
+        assert!(tagged.validate(&["registry.example".to_string()], true).is_ok());
+        assert!(tagged.validate(&["other.example".to_string()], false).is_err());
+    }
+
+    #[test]
+    fn test_parse_challenge() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.example/token",service="registry.example",scope="repository:app:pull""#,
+        );
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://auth.example/token");
+        assert_eq!(params["service"], "registry.example");
+        assert_eq!(params["scope"], "repository:app:pull");
+    }
+}
@@ -0,0 +1,360 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::RwLock;
+
+use crate::config::{parse_file, ConfigError};
+use crate::core::binary::BinaryInfo;
+use crate::core::merger::stubs::platform_slug;
+use crate::core::merger::{ReadinessProbe, StubOptions};
+
+/// Facts rules can test, see `MergeFacts::get`
+const FACTS: &[&str] = &[
+    "tenant",
+    "endpoint",
+    "os",
+    "arch",
+    "strategy",
+    "signed",
+    "sync_mode",
+    "grace_period",
+    "network_failure_kill_count",
+    "heartbeat_timeout",
+    "monitor_warmup",
+    "health_check_interval",
+    "shutdown_grace",
+    "overload_start_delay",
+    "overload_interval",
+    "prerequisite_runs",
+    "base_memory_limit_mb",
+    "base_cpu_percent",
+    "overload_memory_limit_mb",
+    "overload_cpu_percent",
+    "drop_privileges",
+    "run_as_user",
+    "base_priority",
+    "overload_priority",
+    "seccomp",
+    "kill_policy",
+    "arm_after_success",
+    "overload_jail",
+    "hardening",
+    "expires_at",
+    "machine_binding",
+    "telemetry",
+    "crash_report",
+    "output_log",
+    "readiness_probe",
+    "installer",
+    "overload_library",
+    "stub_flavor",
+    "pack",
+];
+
+/// A merge request as policy rules see it
+pub struct MergeFacts<'a> {
+    /// API key name, when authentication is on
+    pub tenant: Option<&'a str>,
+    /// The endpoint as recorded in provenance, such as `POST /merge` or `grpc Merge`
+    pub endpoint: &'a str,
+    pub base_info: &'a BinaryInfo,
+    /// Merge strategy; the V1 endpoints have none
+    pub strategy: Option<&'a str>,
+    pub options: &'a StubOptions,
+    /// The merged binary will be signed with `WEAVER_SIGNING_KEY`
+    pub signed: bool,
+}
+
+impl MergeFacts<'_> {
+    /// Value of the fact `name`; None for facts this merge doesn't have, such
+    /// as `tenant` without authentication
+    ///
+    /// `os` and `arch` are stub platform names such as `linux` and `x86_64`.
+    fn get(&self, name: &str) -> Option<Value> {
+        let options = self.options;
+        let value = match name {
+            "tenant" => self.tenant?.into(),
+            "endpoint" => self.endpoint.into(),
+            "os" => platform_slug(self.base_info.os, self.base_info.arch)?.0.into(),
+            "arch" => platform_slug(self.base_info.os, self.base_info.arch)?.1.into(),
+            "strategy" => self.strategy?.into(),
+            "signed" => self.signed.into(),
+            "sync_mode" => options.sync_mode.into(),
+            "grace_period" => options.grace_period.into(),
+            "network_failure_kill_count" => options.network_failure_kill_count.into(),
+            "heartbeat_timeout" => options.heartbeat_timeout.into(),
+            "monitor_warmup" => options.monitor_warmup.into(),
+            "arm_after_success" => options.arm_after_success.into(),
+            "health_check_interval" => options.health_check_interval.into(),
+            "shutdown_grace" => options.shutdown_grace.into(),
+            "overload_start_delay" => options.overload_start_delay.into(),
+            "overload_interval" => options.overload_interval.into(),
+            "prerequisite_runs" => options.prerequisite_runs.into(),
+            "base_memory_limit_mb" => options.base_memory_limit_mb.into(),
+            "base_cpu_percent" => options.base_cpu_percent.into(),
+            "overload_memory_limit_mb" => options.overload_memory_limit_mb.into(),
+            "overload_cpu_percent" => options.overload_cpu_percent.into(),
+            "drop_privileges" => options.drop_privileges.into(),
+            "run_as_user" => options.run_as_user.clone()?.into(),
+            "base_priority" => options.base_priority?.into(),
+            "overload_priority" => options.overload_priority?.into(),
+            "seccomp" => options.seccomp_profile.is_some().into(),
+            "kill_policy" => options.kill_policy.is_some().into(),
+            "overload_jail" => (options.overload_jail != 0).into(),
+            "hardening" => options.hardening.into(),
+            "expires_at" => options.expires_at?.into(),
+            "machine_binding" => options.machine_binding.is_some().into(),
+            "telemetry" => options.telemetry_url.is_some().into(),
+            "crash_report" => options.crash_report.is_some().into(),
+            "output_log" => options.output_log.is_some().into(),
+            "readiness_probe" => match options.readiness.as_ref()?.probe {
+                ReadinessProbe::Tcp(_) => "tcp",
+                ReadinessProbe::File(_) => "file",
+                ReadinessProbe::ReadyFlag => "ready_flag",
+            }
+            .into(),
+            "installer" => options.installer.is_some().into(),
+            "overload_library" => options.overload_library.into(),
+            "stub_flavor" => options.stub_flavor?.name().into(),
+            "pack" => options.pack.into(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// One policy rule; it applies to merges by `tenants` matching all of `when`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// API key names the rule applies to; empty for every caller
+    #[serde(default)]
+    pub tenants: Vec<String>,
+    /// Facts that must have these values for the rule to apply
+    #[serde(default)]
+    pub when: BTreeMap<String, Value>,
+    /// Refuse every merge the rule applies to
+    #[serde(default)]
+    pub deny: bool,
+    /// Facts that must have these values
+    #[serde(default)]
+    pub require: BTreeMap<String, Value>,
+    /// Facts that must be at least these numbers
+    #[serde(default)]
+    pub min: BTreeMap<String, f64>,
+    /// Facts that must be at most these numbers
+    #[serde(default)]
+    pub max: BTreeMap<String, f64>,
+    /// Facts that must have one of these values
+    #[serde(default)]
+    pub one_of: BTreeMap<String, Vec<Value>>,
+    /// Sent to the client instead of the generated reason
+    pub message: Option<String>,
+}
+
+impl Rule {
+    fn applies_to(&self, facts: &MergeFacts) -> bool {
+        let tenant = self.tenants.is_empty() || facts.tenant.is_some_and(|tenant| self.tenants.iter().any(|t| t == tenant));
+        tenant && self.when.iter().all(|(name, value)| facts.get(name).as_ref() == Some(value))
+    }
+
+    /// Why `facts` break the rule, if they do
+    fn violation(&self, facts: &MergeFacts) -> Option<String> {
+        if self.deny {
+            return Some("merges like this are not allowed".to_string());
+        }
+        let show = |value: &Option<Value>| value.as_ref().map_or("unset".to_string(), Value::to_string);
+        for (name, expected) in &self.require {
+            let actual = facts.get(name);
+            if actual.as_ref() != Some(expected) {
+                return Some(format!("{} must be {}, got {}", name, expected, show(&actual)));
+            }
+        }
+        for (name, &min) in &self.min {
+            let actual = facts.get(name);
+            if actual.as_ref().and_then(Value::as_f64).is_none_or(|number| number < min) {
+                return Some(format!("{} must be at least {}, got {}", name, min, show(&actual)));
+            }
+        }
+        for (name, &max) in &self.max {
+            let actual = facts.get(name);
+            if actual.as_ref().and_then(Value::as_f64).is_none_or(|number| number > max) {
+                return Some(format!("{} must be at most {}, got {}", name, max, show(&actual)));
+            }
+        }
+        for (name, allowed) in &self.one_of {
+            let actual = facts.get(name);
+            if !actual.as_ref().is_some_and(|value| allowed.contains(value)) {
+                let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                return Some(format!("{} must be one of {}, got {}", name, allowed.join(", "), show(&actual)));
+            }
+        }
+        None
+    }
+
+    fn facts(&self) -> impl Iterator<Item = &String> {
+        self.when.keys().chain(self.require.keys()).chain(self.min.keys()).chain(self.max.keys()).chain(self.one_of.keys())
+    }
+}
+
+/// The first rule a merge breaks
+#[derive(Debug, PartialEq)]
+pub struct PolicyViolation {
+    pub rule: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "policy rule '{}': {}", self.rule, self.reason)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+/// Rules from `WEAVER_POLICY_FILE`, checked before every merge
+///
+/// Without a file every merge is allowed. Rules are checked in file order
+/// and the first one broken refuses the merge.
+pub struct Policy {
+    rules: RwLock<Vec<Rule>>,
+}
+
+impl Policy {
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        Ok(Self::new(Self::load_rules(path)?))
+    }
+
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules: RwLock::new(rules) }
+    }
+
+    /// Read and check the rules in `path`; none without a file
+    pub fn load_rules(path: Option<&str>) -> Result<Vec<Rule>, ConfigError> {
+        let Some(path) = path else {
+            return Ok(Vec::new());
+        };
+        let file: PolicyFile = parse_file(Path::new(path))?;
+
+        let mut problems = Vec::new();
+        let mut names = HashSet::new();
+        for rule in &file.rules {
+            if rule.name.trim().is_empty() {
+                problems.push("a policy rule has no name".to_string());
+            } else if !names.insert(rule.name.as_str()) {
+                problems.push(format!("policy rule '{}' is defined twice", rule.name));
+            }
+            for fact in rule.facts().filter(|fact| !FACTS.contains(&fact.as_str())) {
+                problems.push(format!("policy rule '{}' tests unknown fact '{}'; known facts: {}", rule.name, fact, FACTS.join(", ")));
+            }
+            let constrained = rule.deny || !(rule.require.is_empty() && rule.min.is_empty() && rule.max.is_empty() && rule.one_of.is_empty());
+            if !constrained {
+                problems.push(format!("policy rule '{}' needs deny, require, min, max or one_of", rule.name));
+            }
+        }
+        if problems.is_empty() { Ok(file.rules) } else { Err(ConfigError::Invalid(problems)) }
+    }
+
+    /// Swap in rules from `load_rules`
+    pub fn replace(&self, rules: Vec<Rule>) {
+        *self.rules.write().unwrap() = rules;
+    }
+
+    pub fn count(&self) -> usize {
+        self.rules.read().unwrap().len()
+    }
+
+    pub fn check(&self, facts: &MergeFacts) -> Result<(), PolicyViolation> {
+        for rule in self.rules.read().unwrap().iter().filter(|rule| rule.applies_to(facts)) {
+            if let Some(reason) = rule.violation(facts) {
+                return Err(PolicyViolation { rule: rule.name.clone(), reason: rule.message.clone().unwrap_or(reason) });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::binary::{Architecture, OperatingSystem};
+
+    fn policy(toml: &str) -> Result<Vec<Rule>, ConfigError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.toml");
+        std::fs::write(&path, toml).unwrap();
+        Policy::load_rules(path.to_str())
+    }
+
+    #[test]
+    fn test_policy_rules() {
+        let policy = Policy::new(
+            policy(
+                r#"
+                [[rules]]
+                name = "acme-sync"
+                tenants = ["acme"]
+                require = { sync_mode = true }
+
+                [[rules]]
+                name = "grace"
+                min = { grace_period = 60 }
+
+                [[rules]]
+                name = "windows-signed"
+                when = { os = "windows" }
+                require = { signed = true }
+                message = "Windows builds must be signed"
+                "#,
+            )
+            .unwrap(),
+        );
+
+        let linux = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
+        let windows = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
+        let options = StubOptions { grace_period: 300, ..StubOptions::default() };
+        fn facts<'a>(tenant: Option<&'a str>, base_info: &'a BinaryInfo, options: &'a StubOptions, signed: bool) -> MergeFacts<'a> {
+            MergeFacts { tenant, endpoint: "POST /merge/v2/stop-on-exit", base_info, strategy: Some("append"), options, signed }
+        }
+
+        assert_eq!(policy.check(&facts(Some("other"), &linux, &options, false)), Ok(()));
+        let violation = policy.check(&facts(Some("acme"), &linux, &options, false)).unwrap_err();
+        assert_eq!((violation.rule.as_str(), violation.reason.as_str()), ("acme-sync", "sync_mode must be true, got false"));
+        let short = StubOptions { grace_period: 30, ..options.clone() };
+        assert_eq!(policy.check(&facts(None, &linux, &short, false)).unwrap_err().rule, "grace");
+        let violation = policy.check(&facts(None, &windows, &options, false)).unwrap_err();
+        assert_eq!(violation.reason, "Windows builds must be signed");
+        assert_eq!(policy.check(&facts(None, &windows, &options, true)), Ok(()));
+    }
+
+    #[test]
+    fn test_policy_file_is_checked() {
+        assert!(matches!(policy("[[rules]]\nname = \"x\"\nmin = { grace = 60 }\n"), Err(ConfigError::Invalid(_))));
+        assert!(matches!(policy("[[rules]]\nname = \"x\"\nwhen = { os = \"linux\" }\n"), Err(ConfigError::Invalid(_))));
+        assert!(matches!(policy("[[rules]]\nname = \"x\"\nallow = true\n"), Err(ConfigError::Parse { .. })));
+        assert!(Policy::load_rules(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_every_fact_resolves() {
+        let options = StubOptions {
+            run_as_user: Some("svc".to_string()),
+            expires_at: Some(1),
+            stub_flavor: Some(Default::default()),
+            ..StubOptions::default()
+        };
+        let base_info = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
+        let facts = MergeFacts { tenant: Some("acme"), endpoint: "grpc Merge", base_info: &base_info, strategy: Some("append"), options: &options, signed: false };
+        for name in FACTS {
+            assert!(facts.get(name).is_some(), "fact {} is not resolved", name);
+        }
+    }
+}
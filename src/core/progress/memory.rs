@@ -0,0 +1,136 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::{Progress, ProgressSink, ProgressStream, CACHE_TTL_SECS};
+
+/// Updates buffered per subscriber before the slowest one starts skipping
+const CHANNEL_CAPACITY: usize = 64;
+
+/// In-process progress for library/CLI use, no external service required
+///
+/// Only subscribers in the same process see updates.
+#[derive(Default)]
+pub struct MemoryProgressSink {
+    channels: Mutex<HashMap<String, broadcast::Sender<String>>>,
+    cache: Mutex<HashMap<String, Progress>>,
+}
+
+impl MemoryProgressSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn is_expired(progress: &Progress, now: i64) -> bool {
+    now - progress.updated_at > CACHE_TTL_SECS as i64
+}
+
+#[async_trait]
+impl ProgressSink for MemoryProgressSink {
+    async fn publish(&self, task_id: &str, payload: String) -> Result<()> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(task_id) {
+            // Forget channels whose subscribers have all gone away
+            if sender.send(payload).is_err() {
+                channels.remove(task_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn cache(&self, task_id: &str, progress: &Progress) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, p| !is_expired(p, now));
+        cache.insert(task_id.to_string(), progress.clone());
+        Ok(())
+    }
+
+    async fn latest(&self, task_id: &str) -> Result<Option<Progress>> {
+        let now = chrono::Utc::now().timestamp();
+        let cache = self.cache.lock().unwrap();
+        Ok(cache.get(task_id).filter(|p| !is_expired(p, now)).cloned())
+    }
+
+    async fn subscribe(&self, task_id: &str) -> Result<ProgressStream> {
+        let receiver = self
+            .channels
+            .lock()
+            .unwrap()
+            .entry(task_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+
+        let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(payload) => return Some((payload, receiver)),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn clear(&self, task_id: &str) -> Result<()> {
+        self.cache.lock().unwrap().remove(task_id);
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_published_payloads() {
+        let sink = MemoryProgressSink::new();
+        let mut updates = sink.subscribe("task").await.unwrap();
+
+        sink.publish("task", "first".to_string()).await.unwrap();
+        sink.publish("other", "ignored".to_string()).await.unwrap();
+        sink.publish("task", "second".to_string()).await.unwrap();
+
+        assert_eq!(updates.next().await.as_deref(), Some("first"));
+        assert_eq!(updates.next().await.as_deref(), Some("second"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_round_trip_and_clear() {
+        let sink = MemoryProgressSink::new();
+        let progress = Progress {
+            percentage: 42,
+            message: "Working".to_string(),
+            updated_at: chrono::Utc::now().timestamp(),
+        };
+
+        sink.cache("task", &progress).await.unwrap();
+        assert_eq!(sink.latest("task").await.unwrap().unwrap().percentage, 42);
+
+        sink.clear("task").await.unwrap();
+        assert!(sink.latest("task").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_progress_is_not_returned() {
+        let sink = MemoryProgressSink::new();
+        let stale = Progress {
+            percentage: 10,
+            message: "Old".to_string(),
+            updated_at: chrono::Utc::now().timestamp() - CACHE_TTL_SECS as i64 - 1,
+        };
+
+        sink.cache("task", &stale).await.unwrap();
+        assert!(sink.latest("task").await.unwrap().is_none());
+    }
+}
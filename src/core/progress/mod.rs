@@ -0,0 +1,261 @@
+pub mod memory;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "server")]
+pub mod redis;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+use crate::core::jobs::Cancelled;
+
+pub use self::memory::MemoryProgressSink;
+#[cfg(feature = "nats")]
+pub use self::nats::NatsProgressSink;
+#[cfg(feature = "server")]
+pub use self::redis::RedisProgressSink;
+
+/// How long the latest progress of a task stays readable after its last update
+pub const CACHE_TTL_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Progress {
+    pub percentage: u8,
+    pub message: String,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ProgressStep {
+    Started,
+    DetectingPlatforms,
+    ValidatingPlatforms,
+    CreatingWorkDir,
+    WritingBinaries,
+    CreatingLoader,
+    ConvertingToObjects,
+    CompilingLoader,
+    Linking,
+    Finalizing,
+    Complete,
+}
+
+impl ProgressStep {
+    pub fn percentage(&self) -> u8 {
+        match self {
+            ProgressStep::Started => 0,
+            ProgressStep::DetectingPlatforms => 10,
+            ProgressStep::ValidatingPlatforms => 20,
+            ProgressStep::CreatingWorkDir => 25,
+            ProgressStep::WritingBinaries => 35,
+            ProgressStep::CreatingLoader => 45,
+            ProgressStep::ConvertingToObjects => 60,
+            ProgressStep::CompilingLoader => 75,
+            ProgressStep::Linking => 85,
+            ProgressStep::Finalizing => 95,
+            ProgressStep::Complete => 100,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ProgressStep::Started => "Starting merge operation",
+            ProgressStep::DetectingPlatforms => "Detecting binary platforms",
+            ProgressStep::ValidatingPlatforms => "Validating platform compatibility",
+            ProgressStep::CreatingWorkDir => "Creating working directory",
+            ProgressStep::WritingBinaries => "Writing binary files",
+            ProgressStep::CreatingLoader => "Creating loader stub",
+            ProgressStep::ConvertingToObjects => "Converting binaries to object files",
+            ProgressStep::CompilingLoader => "Compiling loader",
+            ProgressStep::Linking => "Linking everything together",
+            ProgressStep::Finalizing => "Finalizing output binary",
+            ProgressStep::Complete => "Merge complete",
+        }
+    }
+}
+
+/// Percentage between `from` and `to` after `bytes_written` of `total_size` bytes
+pub fn io_percentage(bytes_written: u64, total_size: u64, from: ProgressStep, to: ProgressStep) -> u8 {
+    let (from, to) = (from.percentage() as u64, to.percentage() as u64);
+    if total_size == 0 || to <= from {
+        return from as u8;
+    }
+    (from + (to - from) * bytes_written.min(total_size) / total_size) as u8
+}
+
+/// Live stream of JSON progress payloads for one task
+pub type ProgressStream = BoxStream<'static, String>;
+
+/// Transport for merge progress
+///
+/// Every backend provides a live per-task channel (`publish`/`subscribe`)
+/// and a cache of the latest `Progress` for polling clients.
+#[async_trait]
+pub trait ProgressSink: Send + Sync {
+    /// Broadcast a JSON payload to the task's live subscribers
+    async fn publish(&self, task_id: &str, payload: String) -> Result<()>;
+
+    /// Remember the latest progress of a task for `latest`
+    async fn cache(&self, task_id: &str, progress: &Progress) -> Result<()>;
+
+    /// Latest cached progress of a task
+    async fn latest(&self, task_id: &str) -> Result<Option<Progress>>;
+
+    /// Follow the task's live channel
+    async fn subscribe(&self, task_id: &str) -> Result<ProgressStream>;
+
+    /// Drop the cached progress of a task
+    async fn clear(&self, task_id: &str) -> Result<()>;
+
+    /// Check the backend is reachable (used by `/health`)
+    async fn ping(&self) -> Result<()>;
+}
+
+pub type SharedProgressSink = Arc<dyn ProgressSink>;
+
+/// Build the backend selected by `WEAVER_PROGRESS_BACKEND`
+#[cfg(feature = "server")]
+pub async fn sink_from_config(config: &Config) -> Result<SharedProgressSink> {
+    match config.progress_backend.as_str() {
+        "redis" => Ok(Arc::new(RedisProgressSink::new(&config.redis_url)?)),
+        "memory" => Ok(Arc::new(MemoryProgressSink::new())),
+        #[cfg(feature = "nats")]
+        "nats" => Ok(Arc::new(NatsProgressSink::connect(&config.nats_url).await?)),
+        #[cfg(not(feature = "nats"))]
+        "nats" => anyhow::bail!("NATS progress backend requires building with the `nats` feature"),
+        other => anyhow::bail!("Unknown progress backend: {} (expected redis, memory or nats)", other),
+    }
+}
+
+pub struct ProgressTracker {
+    sink: SharedProgressSink,
+    task_id: String,
+}
+
+impl ProgressTracker {
+    pub fn new(sink: SharedProgressSink, task_id: String) -> Self {
+        Self { sink, task_id }
+    }
+
+    pub async fn update(&self, step: ProgressStep) -> Result<()> {
+        let progress = Progress {
+            percentage: step.percentage(),
+            message: step.message().to_string(),
+            updated_at: chrono::Utc::now().timestamp(),
+        };
+
+        let value = serde_json::to_string(&progress)?;
+
+        // 1. Publish to channel (for real-time subscribers)
+        self.sink.publish(&self.task_id, value).await?;
+
+        // 2. Also cache (for GET fallback)
+        self.sink.cache(&self.task_id, &progress).await?;
+
+        log::info!("Progress update: {}% - {}", progress.percentage, progress.message);
+
+        Ok(())
+    }
+
+    /// Report bytes written so far while between `from` and `to`
+    ///
+    /// The percentage moves from `from` to `to` in proportion to
+    /// `bytes_written`, and `segment` names the part being written.
+    pub async fn report_io_progress(
+        &self,
+        segment: &str,
+        bytes_written: u64,
+        total_size: u64,
+        from: ProgressStep,
+        to: ProgressStep,
+    ) -> Result<()> {
+        const MB: u64 = 1024 * 1024;
+        let progress = Progress {
+            percentage: io_percentage(bytes_written, total_size, from, to),
+            message: format!("Writing {} ({} of {} MB)", segment, bytes_written / MB, total_size.div_ceil(MB)),
+            updated_at: chrono::Utc::now().timestamp(),
+        };
+
+        let value = serde_json::to_string(&progress)?;
+        self.sink.publish(&self.task_id, value).await?;
+        self.sink.cache(&self.task_id, &progress).await?;
+
+        Ok(())
+    }
+
+    pub async fn get(sink: &dyn ProgressSink, task_id: &str) -> Result<Option<Progress>> {
+        sink.latest(task_id).await
+    }
+
+    /// Subscribe to the live progress channel of a task
+    pub async fn subscribe(sink: &dyn ProgressSink, task_id: &str) -> Result<ProgressStream> {
+        sink.subscribe(task_id).await
+    }
+
+    pub async fn delete(sink: &dyn ProgressSink, task_id: &str) -> Result<()> {
+        sink.clear(task_id).await
+    }
+
+    /// Final event of a merge stopped with `DELETE /jobs/{task_id}`
+    pub async fn publish_cancelled(sink: &dyn ProgressSink, task_id: &str, reason: Cancelled) -> Result<()> {
+        let timed_out = matches!(reason, Cancelled::TimedOut(_));
+        let message = serde_json::json!({
+            "percentage": 100,
+            "message": if timed_out { "Timed out" } else { "Cancelled" },
+            "updated_at": chrono::Utc::now().timestamp(),
+            "complete": true,
+            "cancelled": true,
+            "timed_out": timed_out,
+            "error": reason.to_string(),
+        });
+
+        sink.publish(task_id, serde_json::to_string(&message)?).await?;
+
+        Ok(())
+    }
+
+    pub async fn publish_complete(sink: &dyn ProgressSink, task_id: &str, binary_id: Option<String>, error: Option<String>, wrapped_size: Option<u64>) -> Result<()> {
+        // Build download_url if binary_id is present and no error
+        let download_url = if error.is_none() {
+            binary_id.as_ref().map(|id| format!("/download/{}", id))
+        } else {
+            None
+        };
+
+        let message = serde_json::json!({
+            "percentage": 100,
+            "message": if error.is_some() { "Failed" } else { "Complete" },
+            "updated_at": chrono::Utc::now().timestamp(),
+            "complete": true,
+            "binary_id": binary_id,
+            "download_url": download_url,
+            "error": error,
+            "wrapped_size": wrapped_size,
+        });
+
+        sink.publish(task_id, serde_json::to_string(&message)?).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_percentage_scales_with_bytes() {
+        let (from, to) = (ProgressStep::WritingBinaries, ProgressStep::Finalizing);
+        assert_eq!(io_percentage(0, 600, from, to), 35);
+        assert_eq!(io_percentage(300, 600, from, to), 65);
+        assert_eq!(io_percentage(600, 600, from, to), 95);
+        assert_eq!(io_percentage(900, 600, from, to), 95);
+        assert_eq!(io_percentage(0, 0, from, to), 35);
+    }
+}
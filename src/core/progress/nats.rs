@@ -0,0 +1,61 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+
+use super::{MemoryProgressSink, Progress, ProgressSink, ProgressStream};
+
+/// NATS core pub/sub on `progress.{task_id}`
+///
+/// NATS core has no key/value store, so the latest progress is cached
+/// in-process; polling must hit the instance running the merge.
+pub struct NatsProgressSink {
+    client: async_nats::Client,
+    cache: MemoryProgressSink,
+}
+
+impl NatsProgressSink {
+    pub async fn connect(nats_url: &str) -> Result<Self> {
+        let client = async_nats::connect(nats_url).await?;
+        log::info!("📡 Connected to NATS at {}", nats_url);
+        Ok(Self { client, cache: MemoryProgressSink::new() })
+    }
+}
+
+fn subject(task_id: &str) -> String {
+    format!("progress.{}", task_id)
+}
+
+#[async_trait]
+impl ProgressSink for NatsProgressSink {
+    async fn publish(&self, task_id: &str, payload: String) -> Result<()> {
+        self.client.publish(subject(task_id), payload.into()).await?;
+        Ok(())
+    }
+
+    async fn cache(&self, task_id: &str, progress: &Progress) -> Result<()> {
+        self.cache.cache(task_id, progress).await
+    }
+
+    async fn latest(&self, task_id: &str) -> Result<Option<Progress>> {
+        self.cache.latest(task_id).await
+    }
+
+    async fn subscribe(&self, task_id: &str) -> Result<ProgressStream> {
+        let subscriber = self.client.subscribe(subject(task_id)).await?;
+        let stream = subscriber.filter_map(|msg| {
+            futures_util::future::ready(String::from_utf8(msg.payload.to_vec()).ok())
+        });
+        Ok(stream.boxed())
+    }
+
+    async fn clear(&self, task_id: &str) -> Result<()> {
+        self.cache.clear(task_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        match self.client.connection_state() {
+            async_nats::connection::State::Connected => Ok(()),
+            state => anyhow::bail!("NATS connection is {:?}", state),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use tokio::sync::OnceCell;
+
+use super::{Progress, ProgressSink, ProgressStream, CACHE_TTL_SECS};
+
+/// Redis pub/sub on `progress:{task_id}`, latest progress cached under `progress_cache:{task_id}`
+///
+/// Commands share one multiplexed, auto-reconnecting connection that is
+/// opened on first use, so the service still starts while Redis is down.
+/// Subscriptions need a dedicated connection each.
+pub struct RedisProgressSink {
+    client: redis::Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisProgressSink {
+    pub fn new(redis_url: &str) -> Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(redis_url)?,
+            connection: OnceCell::new(),
+        })
+    }
+
+    /// Handle to the shared connection (cheap to clone)
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let manager = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(manager.clone())
+    }
+}
+
+fn channel(task_id: &str) -> String {
+    format!("progress:{}", task_id)
+}
+
+fn cache_key(task_id: &str) -> String {
+    format!("progress_cache:{}", task_id)
+}
+
+#[async_trait]
+impl ProgressSink for RedisProgressSink {
+    async fn publish(&self, task_id: &str, payload: String) -> Result<()> {
+        let mut conn = self.connection().await?;
+        let _: () = conn.publish(channel(task_id), payload).await?;
+        Ok(())
+    }
+
+    async fn cache(&self, task_id: &str, progress: &Progress) -> Result<()> {
+        let mut conn = self.connection().await?;
+        let value = serde_json::to_string(progress)?;
+        let _: () = conn.set_ex(cache_key(task_id), value, CACHE_TTL_SECS).await?;
+        Ok(())
+    }
+
+    async fn latest(&self, task_id: &str) -> Result<Option<Progress>> {
+        let mut conn = self.connection().await?;
+        let value: Option<String> = conn.get(cache_key(task_id)).await?;
+
+        match value {
+            Some(v) => Ok(Some(serde_json::from_str(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn subscribe(&self, task_id: &str) -> Result<ProgressStream> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(channel(task_id)).await?;
+
+        let task_id = task_id.to_string();
+        let stream = pubsub.into_on_message().filter_map(move |msg| {
+            let payload = msg.get_payload::<String>();
+            if let Err(ref e) = payload {
+                log::warn!("Invalid progress payload for task {}: {}", task_id, e);
+            }
+            futures_util::future::ready(payload.ok())
+        });
+
+        Ok(stream.boxed())
+    }
+
+    async fn clear(&self, task_id: &str) -> Result<()> {
+        let mut conn = self.connection().await?;
+        let _: () = conn.del(cache_key(task_id)).await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        let mut conn = self.connection().await?;
+        let _: String = redis::cmd("PING").query_async(&mut conn).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+use crate::core::digest::sha256_hex;
+use crate::core::report::WeaveReport;
+use crate::core::signing::Signer;
+use crate::models::binary::Labels;
+use crate::models::response::{DsseSignature, ProvenanceEnvelope};
+
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+pub const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+/// DSSE payload type of an in-toto statement
+pub const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+const BUILDER_ID: &str = "https://github.com/killcodeio/weaver";
+const BUILD_TYPE: &str = "https://github.com/killcodeio/weaver/merge/v1";
+
+/// Everything a provenance statement records about one merge
+pub struct MergeProvenance<'a> {
+    pub binary_id: &'a str,
+    /// Endpoint the merge was requested through, e.g. `POST /merge/v2`
+    pub endpoint: &'a str,
+    pub base_data: &'a [u8],
+    pub overload_data: &'a [u8],
+    pub labels: &'a Labels,
+    pub started_on: DateTime<Utc>,
+}
+
+impl MergeProvenance<'_> {
+    /// In-toto statement with a SLSA v1 provenance predicate for `merged`
+    ///
+    /// Like the weave report, the options are read back from the artifact's
+    /// footer, and the stub is identified by the digest of the bytes before
+    /// the base payload, so the statement describes what was actually built.
+    pub fn statement(&self, merged: &[u8]) -> Value {
+        let report = WeaveReport::build(self.binary_id, merged, self.base_data, self.overload_data);
+
+        let options: Map<String, Value> =
+            report.options.iter().map(|(name, value)| (name.to_string(), Value::String(value.clone()))).collect();
+        let mut dependencies: Vec<Value> =
+            report.inputs.iter().map(|input| dependency(&input.name.to_lowercase(), &input.sha256)).collect();
+        if let Some(stub) = report.layout.iter().find(|segment| segment.name == "Stub") {
+            let stub = &merged[..(stub.size as usize).min(merged.len())];
+            dependencies.push(dependency("stub", &sha256_hex(stub)));
+        }
+
+        json!({
+            "_type": STATEMENT_TYPE,
+            "subject": [{ "name": self.binary_id, "digest": { "sha256": report.output.sha256 } }],
+            "predicateType": PREDICATE_TYPE,
+            "predicate": {
+                "buildDefinition": {
+                    "buildType": BUILD_TYPE,
+                    "externalParameters": {
+                        "endpoint": self.endpoint,
+                        "options": options,
+                        "labels": self.labels,
+                    },
+                    "resolvedDependencies": dependencies,
+                },
+                "runDetails": {
+                    "builder": {
+                        "id": BUILDER_ID,
+                        "version": { "weaver": env!("CARGO_PKG_VERSION") },
+                    },
+                    "metadata": {
+                        "invocationId": self.binary_id,
+                        "startedOn": self.started_on.to_rfc3339_opts(SecondsFormat::Secs, true),
+                        "finishedOn": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    },
+                },
+            },
+        })
+    }
+
+    /// Build the statement for a stored artifact
+    pub fn statement_for(&self, merged_path: &Path) -> Result<Value> {
+        Ok(self.statement(&std::fs::read(merged_path)?))
+    }
+}
+
+fn dependency(name: &str, sha256: &str) -> Value {
+    json!({ "name": name, "digest": { "sha256": sha256 } })
+}
+
+/// Wrap `statement` in a DSSE envelope, signed when a key is configured
+pub fn envelope(statement: &Value, signer: &Signer) -> ProvenanceEnvelope {
+    let payload = statement.to_string();
+    let signatures = match (signer.key_id(), signer.sign(&pae(PAYLOAD_TYPE, payload.as_bytes()))) {
+        (Some(keyid), Some(sig)) => vec![DsseSignature { keyid, sig: base64::engine::general_purpose::STANDARD.encode(sig) }],
+        _ => Vec::new(),
+    };
+    ProvenanceEnvelope {
+        payload_type: PAYLOAD_TYPE.to_string(),
+        payload: base64::engine::general_purpose::STANDARD.encode(payload),
+        signatures,
+    }
+}
+
+/// DSSE pre-authentication encoding, the bytes that are actually signed
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut encoded = format!("DSSEv1 {} {} {} ", payload_type.len(), payload_type, payload.len()).into_bytes();
+    encoded.extend_from_slice(payload);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pae_matches_dsse_spec() {
+        assert_eq!(pae("http://example.com/HelloWorld", b"hello world"), b"DSSEv1 29 http://example.com/HelloWorld 11 hello world");
+    }
+
+    #[test]
+    fn test_statement_records_inputs_and_labels() {
+        let labels = Labels::from([("team".to_string(), "payments".to_string())]);
+        let provenance = MergeProvenance {
+            binary_id: "abc",
+            endpoint: "POST /merge",
+            base_data: b"base",
+            overload_data: b"overload",
+            labels: &labels,
+            started_on: Utc::now(),
+        };
+        let statement = provenance.statement(b"not a merged binary");
+
+        assert_eq!(statement["_type"], STATEMENT_TYPE);
+        assert_eq!(statement["subject"][0]["digest"]["sha256"], sha256_hex(b"not a merged binary"));
+        let definition = &statement["predicate"]["buildDefinition"];
+        assert_eq!(definition["externalParameters"]["labels"]["team"], "payments");
+        assert_eq!(definition["resolvedDependencies"][0]["name"], "base");
+        assert_eq!(definition["resolvedDependencies"][1]["digest"]["sha256"], sha256_hex(b"overload"));
+
+        let envelope = envelope(&statement, &Signer::disabled());
+        assert!(envelope.signatures.is_empty());
+        let payload = base64::engine::general_purpose::STANDARD.decode(envelope.payload).unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&payload).unwrap(), statement);
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::{changed_fields, Config, ConfigError, SharedConfig};
+use crate::core::diskguard::DiskGuard;
+use crate::core::merger::{MergeCache, StubRegistry};
+use crate::core::policy::Policy;
+use crate::core::scheduler::MergeScheduler;
+
+/// Outcome of a reload
+#[derive(Debug, Default)]
+pub struct ReloadSummary {
+    /// Settings now in effect with new values
+    pub changed: Vec<String>,
+    /// Settings that differ in the new configuration but need a restart
+    pub restart_required: Vec<String>,
+}
+
+/// Applies a fresh load of the configuration to the running service
+///
+/// Triggered by SIGHUP and `POST /admin/reload`. Only limits, TTLs, the
+/// stub directory and the merge policy change; see `Config::reloaded`.
+/// Merges already running keep the settings they started with.
+pub struct ConfigReloader {
+    config: Arc<SharedConfig>,
+    scheduler: Arc<MergeScheduler>,
+    disk_guard: Arc<DiskGuard>,
+    merge_cache: Arc<MergeCache>,
+    stubs: Arc<StubRegistry>,
+    policy: Arc<Policy>,
+    /// Held for a whole reload so two can't interleave their updates
+    reloading: Mutex<()>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config: Arc<SharedConfig>,
+        scheduler: Arc<MergeScheduler>,
+        disk_guard: Arc<DiskGuard>,
+        merge_cache: Arc<MergeCache>,
+        stubs: Arc<StubRegistry>,
+        policy: Arc<Policy>,
+    ) -> Self {
+        Self { config, scheduler, disk_guard, merge_cache, stubs, policy, reloading: Mutex::new(()) }
+    }
+
+    /// Re-read `WEAVER_CONFIG`, the environment and the policy file and apply the result
+    ///
+    /// Nothing changes if the new configuration or policy is invalid. Blocks while a
+    /// changed stub directory is scanned.
+    pub fn reload(&self) -> Result<ReloadSummary, ConfigError> {
+        let fresh = Config::load()?;
+        let _reloading = self.reloading.lock().unwrap();
+        let current = self.config.load_full();
+        let (config, restart_required) = current.reloaded(&fresh)?;
+        let rules = Policy::load_rules(config.policy_file.as_deref())?;
+
+        self.scheduler.resize(
+            config.max_concurrent_merges,
+            config.max_queued_merges,
+            Duration::from_secs(config.merge_queue_timeout),
+        );
+        self.disk_guard.set_quota(config.storage_quota);
+        self.merge_cache.set_ttl(config.merge_cache_ttl);
+        if config.stub_dir != current.stub_dir {
+            let available = self.stubs.set_dir(config.stub_dir.as_ref().map(PathBuf::from));
+            log::info!("🧩 Switched stub directory, {} stubs available", available);
+        }
+        self.policy.replace(rules);
+
+        let summary = ReloadSummary { changed: changed_fields(&current, &config), restart_required };
+        self.config.store(Arc::new(config));
+        Ok(summary)
+    }
+}
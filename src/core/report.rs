@@ -0,0 +1,1042 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::core::binary::{BinaryInfo, OperatingSystem, Shebang};
+use crate::core::digest::sha256_hex;
+use weaver_abi::footer::{
+    ConfigFooter, BIND_HOSTNAME, FOOTER_VERSION, BIND_MAC, BIND_MACHINE_ID, CRASH_REPORT_CORE_DUMP, CRASH_REPORT_FILE, CWD_BINARY_DIR, CWD_PATH, EXEC_AUTO, EXEC_MEMFD, EXEC_TEMP_FILE, EXEC_TMPFILE, HARDEN_ANTI_DEBUG, HARDEN_CHECKSUM,
+    INSTALL_SHORTCUT, INSTALL_SYSTEMD_UNIT, JAIL_EMPTY_ROOT, JAIL_FILESYSTEM, JAIL_NETWORK, JAIL_PID, LOG_ERRORS, LOG_SILENT, LOG_TARGET_FILE,
+    LOG_TARGET_SYSTEM, ORDER_BASE_FIRST, OVERLOAD_LIBRARY, OVERLOAD_SCRIPT, PACKING_LZ4, PACKING_NONE, PREREQ_ABORT, PREREQ_RESTART, PREREQ_RETRY, READINESS_FILE, READINESS_FLAG,
+    READINESS_NONE, READINESS_START_BASE, READINESS_TCP, SINGLE_INSTANCE_EXIT, SINGLE_INSTANCE_SIGNAL,
+};
+use weaver_abi::kill_policy::{ACTION_EXIT, ACTION_KILL_BASE, ACTION_KILL_OVERLOAD, OP_RULE};
+use weaver_abi::seccomp::{SECCOMP_DEFAULT_KILL, SECCOMP_DEFAULT_LOG};
+use weaver_abi::{pack, ImageChecksum, PolicyOp, ResourceLimits, SeccompHeader};
+
+/// Human-readable record of a single weave, rendered as self-contained HTML
+///
+/// Everything except the inputs is derived from the merged artifact itself
+/// (footer, segment hashes, stub headers), so the report describes what was
+/// actually written rather than what was requested.
+#[derive(Debug, Clone)]
+pub struct WeaveReport {
+    pub binary_id: String,
+    pub generated_at: DateTime<Utc>,
+    pub inputs: Vec<ReportInput>,
+    pub output: ReportInput,
+    pub options: Vec<(&'static str, String)>,
+    pub layout: Vec<LayoutSegment>,
+    pub warnings: Vec<String>,
+    pub checks: Vec<VerificationCheck>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportInput {
+    pub name: &'static str,
+    pub size: u64,
+    pub sha256: String,
+    pub platform: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutSegment {
+    pub name: &'static str,
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct VerificationCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl ReportInput {
+    fn from_data(name: &'static str, data: &[u8]) -> Self {
+        Self {
+            name,
+            size: data.len() as u64,
+            sha256: sha256_hex(data),
+            platform: match Shebang::parse(data) {
+                Some(Ok(shebang)) => format!("script ({})", shebang),
+                Some(Err(_)) => "script".to_string(),
+                None => BinaryInfo::detect(data).description(),
+            },
+        }
+    }
+}
+
+impl WeaveReport {
+    pub fn build(binary_id: &str, merged: &[u8], base_data: &[u8], overload_data: &[u8]) -> Self {
+        let base = ReportInput::from_data("Base", base_data);
+        let overload = ReportInput::from_data("Overload", overload_data);
+        let output = ReportInput::from_data("Merged output", merged);
+        let base_info = BinaryInfo::detect(base_data);
+
+        let mut report = Self {
+            binary_id: binary_id.to_string(),
+            generated_at: Utc::now(),
+            inputs: Vec::new(),
+            output,
+            options: Vec::new(),
+            layout: Vec::new(),
+            warnings: Vec::new(),
+            checks: Vec::new(),
+        };
+
+        let footer = merged
+            .len()
+            .checked_sub(ConfigFooter::SIZE)
+            .and_then(|start| <&[u8; ConfigFooter::SIZE]>::try_from(&merged[start..]).ok())
+            .and_then(ConfigFooter::from_bytes);
+
+        let Some(footer) = footer else {
+            report.checks.push(VerificationCheck {
+                name: "Footer present",
+                passed: false,
+                detail: format!("No KILLCODE footer of layout version {} at the end of the artifact", FOOTER_VERSION),
+            });
+            report.inputs = vec![base, overload];
+            return report;
+        };
+
+        report.checks.push(VerificationCheck {
+            name: "Footer present",
+            passed: true,
+            detail: format!("{} bytes, magic KILLCODE, layout version {}", ConfigFooter::SIZE, FOOTER_VERSION),
+        });
+
+        let footer_offset = (merged.len() - ConfigFooter::SIZE) as u64;
+        let overload_end = footer.overload_offset + footer.overload_size;
+        report.layout = vec![
+            LayoutSegment { name: "Stub", offset: 0, size: footer.base_offset },
+            LayoutSegment { name: "Base", offset: footer.base_offset, size: footer.base_size },
+            LayoutSegment { name: "Overload", offset: footer.overload_offset, size: footer.overload_size },
+        ];
+        // Optional segments follow the overload in this order when present
+        let mut end = overload_end;
+        let mut contiguous = footer.overload_offset == footer.base_offset + footer.base_size;
+        for (name, offset, size) in [
+            ("Seccomp", footer.seccomp_offset, footer.seccomp_size),
+            ("Labels", footer.labels_offset, footer.labels_size),
+            ("Interpreter", footer.interpreter_offset, footer.interpreter_size),
+            ("Install files", footer.install_files_offset, footer.install_files_size),
+            ("Assets", footer.assets_offset, footer.assets_size),
+        ] {
+            if size > 0 {
+                report.layout.push(LayoutSegment { name, offset, size });
+                contiguous &= offset == end;
+                end = offset + size;
+            }
+        }
+        report.layout.push(LayoutSegment { name: "Footer", offset: footer_offset, size: ConfigFooter::SIZE as u64 });
+        contiguous &= end == footer_offset;
+        report.checks.push(VerificationCheck {
+            name: "Segments contiguous",
+            passed: contiguous,
+            detail: if contiguous {
+                "Stub, base, overload and footer are back to back".to_string()
+            } else {
+                "Footer offsets leave gaps or overlap".to_string()
+            },
+        });
+
+        report.checks.push(segment_check("Base payload intact", merged, footer.base_offset, footer.base_size, footer.unpacked_size(true), &base.sha256));
+        report.checks.push(segment_check(
+            "Overload payload intact",
+            merged,
+            footer.overload_offset,
+            footer.overload_size,
+            footer.unpacked_size(false),
+            &overload.sha256,
+        ));
+
+        if footer.hardening & HARDEN_CHECKSUM != 0 {
+            let (image, footer_raw) = merged.split_at(footer_offset as usize);
+            let mut checksum = ImageChecksum::new();
+            checksum.update(image);
+            checksum.update_footer(footer_raw.try_into().expect("footer is ConfigFooter::SIZE bytes"));
+            let intact = checksum.finish() == footer.image_checksum;
+            report.checks.push(VerificationCheck {
+                name: "Image checksum",
+                passed: intact,
+                detail: format!("Stored {:016x}, computed {:016x}", footer.image_checksum, checksum.finish()),
+            });
+        }
+
+        let stub_info = BinaryInfo::detect(&merged[..(footer.base_offset as usize).min(merged.len())]);
+        report.checks.push(VerificationCheck {
+            name: "Stub matches base platform",
+            passed: stub_info.is_compatible_with(&base_info),
+            detail: format!("Stub is {}, base is {}", stub_info.description(), base_info.description()),
+        });
+
+        let sync_mode = footer.prerequisite_runs() > 0;
+        let monitoring = footer.grace_period > 0 || footer.network_failure_kill_count > 0 || footer.heartbeat_timeout > 0;
+        report.options = vec![
+            ("Grace period", format!("{}s", footer.grace_period)),
+            ("Sync mode", sync_mode.to_string()),
+            ("Prerequisite runs", prerequisite_description(&footer)),
+            ("Network failure kill count", footer.network_failure_kill_count.to_string()),
+            ("Heartbeat timeout", if footer.heartbeat_timeout > 0 { format!("{}s", footer.heartbeat_timeout) } else { "disabled".to_string() }),
+            ("Kill policy", kill_policy_description(&footer)),
+            ("Monitor arming", arming_description(&footer)),
+            ("Overload jail", jail_description(footer.overload_jail)),
+            ("Execution order", order_description(footer.order).to_string()),
+            ("Overload start delay", format!("{}s", footer.overload_start_delay)),
+            ("Overload interval", if footer.overload_interval > 0 { format!("every {}s", footer.overload_interval) } else { "once".to_string() }),
+            ("Health check interval", or_default(footer.health_check_interval, "s")),
+            ("Kill delay", or_default(footer.kill_delay_ms, "ms")),
+            ("Shutdown grace", or_default(footer.shutdown_grace, "s")),
+            ("Base limits", limits_description(&footer.limits(true))),
+            ("Overload limits", limits_description(&footer.limits(false))),
+            ("Run as user", footer.run_as_user().unwrap_or("unchanged").to_string()),
+            ("Drop privileges", (footer.drop_privileges != 0).to_string()),
+            ("Seccomp profile", seccomp_description(merged, &footer)),
+            ("Hardening", hardening_description(footer.hardening)),
+            ("Stub log level", log_level_description(footer.log_level).to_string()),
+            ("Stub log target", log_target_description(&footer)),
+            ("Product name", footer.product_name().unwrap_or("none").to_string()),
+            ("Exec strategy", exec_strategy_description(footer.exec_strategy).to_string()),
+            ("Base working directory", cwd_description(&footer, true)),
+            ("Overload working directory", cwd_description(&footer, false)),
+            ("Base umask", umask_description(footer.umask(true))),
+            ("Overload umask", umask_description(footer.umask(false))),
+            ("Base scheduling", scheduling_description(footer.priority(true), footer.cpu_affinity(true))),
+            ("Overload scheduling", scheduling_description(footer.priority(false), footer.cpu_affinity(false))),
+            ("Single instance", single_instance_description(footer.single_instance).to_string()),
+            ("Control socket", (footer.control_socket != 0).to_string()),
+            ("IPC channel", (footer.ipc_channel != 0).to_string()),
+            ("Revocation", revocation_description(&footer)),
+            ("Expires", expiry_description(&footer)),
+            ("Machine binding", machine_binding_description(&footer)),
+            ("Audit log", audit_description(&footer)),
+            ("Crash report", crash_report_description(&footer)),
+            ("Output log", output_log_description(&footer)),
+            ("Readiness probe", readiness_description(&footer)),
+            ("Packing", packing_description(&footer)),
+            ("Phone home", footer.telemetry_url().unwrap_or("off").to_string()),
+            ("Embedded labels", labels_description(merged, &footer)),
+            ("Overload kind", overload_kind_description(&footer)),
+            ("Installer", installer_description(merged, &footer)),
+            ("Assets", assets_description(merged, &footer)),
+        ];
+
+        report.warnings = footer_warnings(&footer, base_info.os);
+
+        report.inputs = vec![base, overload];
+        report
+    }
+
+    /// Build the report for a stored artifact and write it next to it
+    pub fn write_for(binary_id: &str, merged_path: &Path, base_data: &[u8], overload_data: &[u8]) -> Result<String> {
+        let merged = std::fs::read(merged_path)?;
+        let report = Self::build(binary_id, &merged, base_data, overload_data);
+
+        let report_path = merged_path.with_extension("report.html");
+        std::fs::write(&report_path, report.render_html())?;
+
+        Ok(report_path.to_string_lossy().into_owned())
+    }
+
+    pub fn render_html(&self) -> String {
+        let mut html = String::new();
+        let total = self.output.size.max(1);
+
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Weave report {id}</title>\n<style>{css}</style>\n</head>\n<body>\n\
+             <h1>Weave report</h1>\n<p>Binary <code>{id}</code> &middot; generated {at}</p>\n",
+            id = escape_html(&self.binary_id),
+            at = self.generated_at.to_rfc3339(),
+            css = REPORT_CSS,
+        );
+
+        html.push_str("<h2>Inputs</h2>\n<table>\n<tr><th></th><th>Platform</th><th>Size</th><th>SHA-256</th></tr>\n");
+        for input in self.inputs.iter().chain(std::iter::once(&self.output)) {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{} bytes</td><td><code>{}</code></td></tr>",
+                input.name,
+                escape_html(&input.platform),
+                input.size,
+                input.sha256,
+            );
+        }
+        html.push_str("</table>\n");
+
+        if !self.options.is_empty() {
+            html.push_str("<h2>Options</h2>\n<table>\n");
+            for (name, value) in &self.options {
+                let _ = writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", name, escape_html(value));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.layout.is_empty() {
+            html.push_str("<h2>Layout</h2>\n<div class=\"layout\">\n");
+            for segment in &self.layout {
+                let percent = (segment.size as f64 / total as f64 * 100.0).max(0.5);
+                let _ = writeln!(
+                    html,
+                    "<div class=\"segment {class}\" style=\"width:{percent:.2}%\" title=\"{name}: {size} bytes @ {offset}\">{name}</div>",
+                    class = segment.name.to_lowercase(),
+                    percent = percent,
+                    name = segment.name,
+                    size = segment.size,
+                    offset = segment.offset,
+                );
+            }
+            html.push_str("</div>\n<table>\n<tr><th>Segment</th><th>Offset</th><th>Size</th></tr>\n");
+            for segment in &self.layout {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{:#x}</td><td>{} bytes</td></tr>",
+                    segment.name, segment.offset, segment.size,
+                );
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.warnings.is_empty() {
+            html.push_str("<h2>Warnings</h2>\n<ul class=\"warnings\">\n");
+            for warning in &self.warnings {
+                let _ = writeln!(html, "<li>{}</li>", escape_html(warning));
+            }
+            html.push_str("</ul>\n");
+        }
+
+        html.push_str("<h2>Verification</h2>\n<table>\n");
+        for check in &self.checks {
+            let _ = writeln!(
+                html,
+                "<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>",
+                if check.passed { "pass" } else { "fail" },
+                if check.passed { "PASS" } else { "FAIL" },
+                check.name,
+                escape_html(&check.detail),
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        html
+    }
+}
+
+/// Options in `footer` that the target OS ignores or that undo each other
+///
+/// Shared by the weave report and dry runs, which have a footer but no merged binary.
+pub fn footer_warnings(footer: &ConfigFooter, os: OperatingSystem) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let sync_mode = footer.prerequisite_runs() > 0;
+    let monitoring = footer.grace_period > 0
+        || footer.network_failure_kill_count > 0
+        || footer.heartbeat_timeout > 0
+        || footer.has_kill_policy();
+
+    if sync_mode && monitoring {
+        warnings.push(
+            "Health monitoring is disabled in sync mode; grace period, failure threshold and heartbeat timeout have no effect".to_string(),
+        );
+    }
+    if footer.order == ORDER_BASE_FIRST && monitoring {
+        warnings.push(
+            "Health monitoring is disabled when base runs first; grace period, failure threshold and heartbeat timeout have no effect".to_string(),
+        );
+    }
+    if footer.is_scheduled() && (footer.grace_period > 0 || footer.heartbeat_timeout > 0 || footer.has_kill_policy() || sync_mode) {
+        warnings.push(
+            "A scheduled overload replaces the sidecar: sync mode, grace period, heartbeat timeout and kill policy have no effect".to_string(),
+        );
+    }
+    if footer.has_kill_policy() && !footer.is_scheduled() && (footer.grace_period > 0 || footer.network_failure_kill_count > 0 || footer.heartbeat_timeout > 0) {
+        warnings.push("The kill policy replaces the grace period, failure threshold and heartbeat timeout checks".to_string());
+    }
+    if !monitoring && (footer.monitor_warmup > 0 || footer.arm_after_success != 0) {
+        warnings.push("Monitor warmup and arm after success have no effect without health monitoring".to_string());
+    }
+    if footer.order == ORDER_BASE_FIRST && sync_mode {
+        warnings.push("Sync mode and prerequisite runs are ignored when base runs first".to_string());
+    }
+    if footer.prerequisite_on_failure == PREREQ_ABORT && (footer.prerequisite_max_failures > 0 || footer.prerequisite_retry_delay > 0) {
+        warnings.push("Prerequisite max failures and retry delay have no effect when a failed run aborts".to_string());
+    }
+    if footer.order == ORDER_BASE_FIRST && (footer.overload_start_delay > 0 || footer.overload_interval > 0) {
+        warnings.push("Overload delay and interval are ignored when base runs first".to_string());
+    }
+    let sidecar = !sync_mode && footer.order != ORDER_BASE_FIRST && !footer.is_scheduled() && footer.overload_kind != OVERLOAD_LIBRARY;
+    if footer.readiness_probe != READINESS_NONE && !sidecar {
+        warnings.push(
+            "The readiness probe only applies to a sidecar overload started before base; sync mode, base first, schedules and library overloads skip it".to_string(),
+        );
+    }
+    if footer.crash_report & CRASH_REPORT_CORE_DUMP != 0 && os == OperatingSystem::Windows {
+        warnings.push("Core dumps are Unix only; a Windows crash report carries the exception code".to_string());
+    }
+    if footer.overload_jail != 0 && os != OperatingSystem::Linux {
+        warnings.push(format!("Overload jail is only enforced on Linux, target is {}", os));
+    }
+    let (base_limits, overload_limits) = (footer.limits(true), footer.limits(false));
+    let has_caps = base_limits.memory_mb > 0
+        || base_limits.cpu_percent > 0
+        || overload_limits.memory_mb > 0
+        || overload_limits.cpu_percent > 0;
+    if has_caps && !matches!(os, OperatingSystem::Linux | OperatingSystem::Windows) {
+        warnings.push(format!("Memory and CPU limits are only enforced on Linux and Windows, target is {}", os));
+    }
+    if (base_limits.nofile > 0 || overload_limits.nofile > 0) && os != OperatingSystem::Linux {
+        warnings.push(format!("Open file limits are only enforced on Linux, target is {}", os));
+    }
+    let drops_privileges = footer.run_as_user().is_some() || footer.drop_privileges != 0;
+    if drops_privileges && !matches!(os, OperatingSystem::Linux | OperatingSystem::MacOS) {
+        warnings.push(format!("Run as user and drop privileges are only enforced on Linux and macOS, target is {}", os));
+    }
+    if footer.exec_strategy != EXEC_AUTO && os != OperatingSystem::Linux {
+        warnings.push(format!("Exec strategies only apply to Linux, target is {}", os));
+    }
+    if (footer.cpu_affinity(true) != 0 || footer.cpu_affinity(false) != 0) && !matches!(os, OperatingSystem::Linux | OperatingSystem::Windows) {
+        warnings.push(format!("CPU affinity is only applied on Linux and Windows, target is {}", os));
+    }
+    let raises_priority = [true, false].into_iter().any(|is_base| footer.priority(is_base).is_some_and(|nice| nice < 0));
+    if raises_priority && os != OperatingSystem::Windows {
+        warnings.push("A negative nice value needs root (or CAP_SYS_NICE on Linux); without it the payload keeps its priority".to_string());
+    }
+    let has_umask = footer.umask(true).is_some() || footer.umask(false).is_some();
+    if has_umask && !matches!(os, OperatingSystem::Linux | OperatingSystem::MacOS) {
+        warnings.push(format!("umask is only applied on Linux and macOS, target is {}", os));
+    }
+    if footer.single_instance == SINGLE_INSTANCE_SIGNAL && os == OperatingSystem::Windows {
+        warnings.push("Single instance signal mode needs Unix signals; on Windows a second copy just exits".to_string());
+    }
+    if footer.expires_at > 0 && footer.expires_at <= Utc::now().timestamp() {
+        warnings.push("The expiry date has already passed; the merged binary will refuse to start".to_string());
+    }
+    if footer.control_socket != 0 && !footer.is_scheduled() {
+        warnings.push("The control socket's restart_overload command needs a scheduled overload".to_string());
+    }
+    if footer.seccomp_size > 0 && os != OperatingSystem::Linux {
+        warnings.push(format!("Seccomp profiles are only enforced on Linux, target is {}", os));
+    }
+    warnings
+}
+
+/// A packed segment (`unpacked_size` set) is compared once unpacked
+fn segment_check(
+    name: &'static str,
+    merged: &[u8],
+    offset: u64,
+    size: u64,
+    unpacked_size: Option<u64>,
+    expected_sha256: &str,
+) -> VerificationCheck {
+    let segment = usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(offset + size).ok())
+        .and_then(|(start, end)| merged.get(start..end));
+
+    match (segment, unpacked_size) {
+        (Some(bytes), None) => {
+            let actual = sha256_hex(bytes);
+            VerificationCheck {
+                name,
+                passed: actual == expected_sha256,
+                detail: format!("SHA-256 {}", actual),
+            }
+        }
+        (Some(bytes), Some(unpacked_size)) => {
+            let mut unpacked = vec![0u8; unpacked_size as usize];
+            match pack::unpack(bytes, &mut unpacked) {
+                Ok(()) => {
+                    let actual = sha256_hex(&unpacked);
+                    VerificationCheck {
+                        name,
+                        passed: actual == expected_sha256,
+                        detail: format!("SHA-256 {}, unpacked from {} bytes", actual, size),
+                    }
+                }
+                Err(e) => VerificationCheck { name, passed: false, detail: format!("Packed segment doesn't unpack: {:?}", e) },
+            }
+        }
+        (None, _) => VerificationCheck {
+            name,
+            passed: false,
+            detail: format!("Segment {}+{} is outside the artifact", offset, size),
+        },
+    }
+}
+
+fn jail_description(flags: u32) -> String {
+    let mut parts = Vec::new();
+    if flags & JAIL_FILESYSTEM != 0 {
+        parts.push("filesystem");
+    }
+    if flags & JAIL_NETWORK != 0 {
+        parts.push("network");
+    }
+    if flags & JAIL_PID != 0 {
+        parts.push("pid");
+    }
+    if flags & JAIL_EMPTY_ROOT != 0 {
+        parts.push("empty root");
+    }
+    if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn order_description(order: u8) -> &'static str {
+    if order == ORDER_BASE_FIRST {
+        "base, then overload"
+    } else {
+        "overload first"
+    }
+}
+
+fn limits_description(limits: &ResourceLimits) -> String {
+    let mut parts = Vec::new();
+    if limits.memory_mb > 0 {
+        parts.push(format!("{} MiB memory", limits.memory_mb));
+    }
+    if limits.cpu_percent > 0 {
+        parts.push(format!("{}% CPU", limits.cpu_percent));
+    }
+    if limits.nofile > 0 {
+        parts.push(format!("{} open files", limits.nofile));
+    }
+    if parts.is_empty() {
+        "none".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn cwd_description(footer: &ConfigFooter, is_base: bool) -> String {
+    match footer.cwd(is_base) {
+        (CWD_BINARY_DIR, _) => "directory of the merged binary".to_string(),
+        (CWD_PATH, path) => path.unwrap_or("(missing path)").to_string(),
+        _ => "inherited".to_string(),
+    }
+}
+
+fn machine_binding_description(footer: &ConfigFooter) -> String {
+    if footer.machine_binding == 0 {
+        return "any machine".to_string();
+    }
+    let properties: Vec<&str> = [(BIND_MACHINE_ID, "machine id"), (BIND_HOSTNAME, "hostname"), (BIND_MAC, "MAC address")]
+        .into_iter()
+        .filter(|&(flag, _)| footer.machine_binding & flag != 0)
+        .map(|(_, name)| name)
+        .collect();
+    let machines = footer.machine_fingerprints.iter().filter(|&&fingerprint| fingerprint != 0).count();
+    format!("{} machine(s) by {}", machines, properties.join(" + "))
+}
+
+fn audit_description(footer: &ConfigFooter) -> String {
+    match (footer.audit_path(), footer.audit_url()) {
+        (Some(path), Some(url)) => format!("{} and {}", path, url),
+        (Some(target), None) | (None, Some(target)) => target.to_string(),
+        (None, None) => "off".to_string(),
+    }
+}
+
+fn crash_report_description(footer: &ConfigFooter) -> String {
+    if !footer.reports_crashes() {
+        return "off".to_string();
+    }
+    let mut targets = Vec::new();
+    if footer.crash_report & CRASH_REPORT_FILE != 0 {
+        targets.push("file".to_string());
+    }
+    targets.extend(footer.crash_report_url().map(str::to_string));
+    let mut description = targets.join(" and ");
+    if footer.crash_stderr_lines > 0 {
+        let _ = write!(description, ", last {} stderr lines", footer.crash_stderr_lines);
+    }
+    if footer.crash_report & CRASH_REPORT_CORE_DUMP != 0 {
+        description.push_str(", core dumps");
+    }
+    description
+}
+
+fn output_log_description(footer: &ConfigFooter) -> String {
+    let payloads = match (footer.logs_output(true), footer.logs_output(false)) {
+        (true, true) => "base and overload",
+        (true, false) => "base",
+        (false, true) => "overload",
+        (false, false) => return "off".to_string(),
+    };
+    let dir = footer.output_log_dir().unwrap_or("the merged binary's directory");
+    match footer.output_log_max_size {
+        0 => format!("{} into {}, never rotated", payloads, dir),
+        size => format!("{} into {}, rotated at {} MiB, {} files kept", payloads, dir, size, footer.output_log_max_files.max(1)),
+    }
+}
+
+fn readiness_description(footer: &ConfigFooter) -> String {
+    let target = footer.readiness_target().unwrap_or("?");
+    let probe = match footer.readiness_probe {
+        READINESS_NONE => return "off".to_string(),
+        READINESS_TCP => format!("TCP {}", target),
+        READINESS_FILE => format!("file {}", target),
+        READINESS_FLAG => "ready flag".to_string(),
+        other => format!("unknown ({})", other),
+    };
+    let on_timeout = if footer.readiness_on_timeout == READINESS_START_BASE { "start base" } else { "abort" };
+    match footer.readiness_timeout {
+        0 => format!("{}, no timeout", probe),
+        secs => format!("{}, {} after {}s", probe, on_timeout, secs),
+    }
+}
+
+fn packing_description(footer: &ConfigFooter) -> String {
+    let payloads: Vec<_> = [("base", true), ("overload", false)]
+        .into_iter()
+        .filter_map(|(name, is_base)| footer.unpacked_size(is_base).map(|size| format!("{} (unpacks to {} bytes)", name, size)))
+        .collect();
+    match footer.packing {
+        PACKING_NONE => "off".to_string(),
+        PACKING_LZ4 if payloads.is_empty() => "LZ4, nothing got smaller".to_string(),
+        PACKING_LZ4 => format!("LZ4: {}", payloads.join(", ")),
+        other => format!("unknown ({})", other),
+    }
+}
+
+fn expiry_description(footer: &ConfigFooter) -> String {
+    if footer.expires_at == 0 {
+        return "never".to_string();
+    }
+    let Some(expires_at) = DateTime::<Utc>::from_timestamp(footer.expires_at, 0) else {
+        return format!("{} (out of range)", footer.expires_at);
+    };
+    let checks = if footer.expiry_clock_checks != 0 { ", with clock checks" } else { "" };
+    format!("{}{}", expires_at.to_rfc3339(), checks)
+}
+
+fn revocation_description(footer: &ConfigFooter) -> String {
+    match footer.revocation_url() {
+        Some(url) => format!("{}, interval {}", url, or_default(footer.revocation_interval, "s")),
+        None => "off".to_string(),
+    }
+}
+
+fn single_instance_description(mode: u8) -> &'static str {
+    match mode {
+        SINGLE_INSTANCE_EXIT => "second copy exits",
+        SINGLE_INSTANCE_SIGNAL => "second copy signals the running base and exits",
+        _ => "off",
+    }
+}
+
+fn prerequisite_description(footer: &ConfigFooter) -> String {
+    let runs = footer.prerequisite_runs();
+    if runs == 0 {
+        return "none".to_string();
+    }
+    let policy = match footer.prerequisite_on_failure {
+        PREREQ_RETRY => "retry",
+        PREREQ_RESTART => "restart",
+        _ => return format!("{} successful runs, abort on failure", runs),
+    };
+    format!(
+        "{} successful runs, {} up to {} failures {}s apart",
+        runs, policy, footer.prerequisite_max_failures, footer.prerequisite_retry_delay
+    )
+}
+
+fn umask_description(umask: Option<u16>) -> String {
+    umask.map_or("inherited".to_string(), |umask| format!("{:03o}", umask))
+}
+
+fn scheduling_description(priority: Option<i8>, cpu_affinity: u64) -> String {
+    let mut parts = Vec::new();
+    if let Some(nice) = priority {
+        parts.push(format!("nice {}", nice));
+    }
+    if cpu_affinity != 0 {
+        parts.push(format!("CPUs {}", cpu_list(cpu_affinity)));
+    }
+    if parts.is_empty() {
+        return "inherited".to_string();
+    }
+    parts.join(", ")
+}
+
+/// CPUs in an affinity mask as a list such as `0-3,6`
+fn cpu_list(mask: u64) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for cpu in (0..64).filter(|cpu| mask & (1 << cpu) != 0) {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|&(first, last)| if first == last { first.to_string() } else { format!("{}-{}", first, last) })
+        .collect();
+    ranges.join(",")
+}
+
+fn exec_strategy_description(strategy: u8) -> &'static str {
+    match strategy {
+        EXEC_MEMFD => "memfd only",
+        EXEC_TMPFILE => "O_TMPFILE only",
+        EXEC_TEMP_FILE => "temp file only",
+        _ => "auto (memfd, O_TMPFILE, temp file)",
+    }
+}
+
+fn log_level_description(level: u8) -> &'static str {
+    match level {
+        LOG_ERRORS => "errors",
+        LOG_SILENT => "silent",
+        _ => "verbose",
+    }
+}
+
+fn log_target_description(footer: &ConfigFooter) -> String {
+    match footer.log_target {
+        LOG_TARGET_FILE => format!("file {}", footer.log_path().unwrap_or("(missing path)")),
+        LOG_TARGET_SYSTEM => "system log".to_string(),
+        _ => "stderr".to_string(),
+    }
+}
+
+fn hardening_description(flags: u8) -> String {
+    let mut parts = Vec::new();
+    if flags & HARDEN_CHECKSUM != 0 {
+        parts.push("checksum");
+    }
+    if flags & HARDEN_ANTI_DEBUG != 0 {
+        parts.push("anti-debug");
+    }
+    if parts.is_empty() {
+        "off".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn labels_description(merged: &[u8], footer: &ConfigFooter) -> String {
+    if footer.labels_size == 0 {
+        return "none".to_string();
+    }
+    usize::try_from(footer.labels_offset)
+        .ok()
+        .and_then(|start| merged.get(start..start.checked_add(footer.labels_size as usize)?))
+        .and_then(|labels| std::str::from_utf8(labels).ok())
+        .map_or("invalid".to_string(), str::to_string)
+}
+
+fn overload_kind_description(footer: &ConfigFooter) -> String {
+    match (footer.overload_kind, footer.interpreter_size) {
+        (OVERLOAD_LIBRARY, _) => "shared library loaded into base".to_string(),
+        (OVERLOAD_SCRIPT, 0) => "script, interpreter from its #! line".to_string(),
+        (OVERLOAD_SCRIPT, size) => format!("script, bundled interpreter ({} bytes)", size),
+        _ => "executable".to_string(),
+    }
+}
+
+fn installer_description(merged: &[u8], footer: &ConfigFooter) -> String {
+    let (Some(dir), Some(name)) = (footer.install_dir(), footer.install_name()) else {
+        return "off".to_string();
+    };
+    let files = usize::try_from(footer.install_files_offset)
+        .ok()
+        .and_then(|start| merged.get(start..start.checked_add(footer.install_files_size as usize)?))
+        .map(|bundle| weaver_abi::bundle::files(bundle).collect::<Result<Vec<_>, ()>>());
+    let mut description = match files {
+        Some(Ok(files)) => format!("{} into {} with {} file(s)", name, dir, files.len()),
+        _ => format!("{} into {}, invalid install files", name, dir),
+    };
+    if footer.install_flags & INSTALL_SYSTEMD_UNIT != 0 {
+        description.push_str(", systemd unit");
+    }
+    if footer.install_flags & INSTALL_SHORTCUT != 0 {
+        description.push_str(", shortcut");
+    }
+    description
+}
+
+fn assets_description(merged: &[u8], footer: &ConfigFooter) -> String {
+    if footer.assets_size == 0 {
+        return "none".to_string();
+    }
+    usize::try_from(footer.assets_offset)
+        .ok()
+        .and_then(|start| merged.get(start..start.checked_add(footer.assets_size as usize)?))
+        .and_then(|bundle| weaver_abi::bundle::files(bundle).map(|file| file.map(|file| file.path)).collect::<Result<Vec<_>, ()>>().ok())
+        .map_or("invalid".to_string(), |paths| format!("{} file(s): {}", paths.len(), paths.join(", ")))
+}
+
+fn arming_description(footer: &ConfigFooter) -> String {
+    match (footer.arm_after_success != 0, footer.monitor_warmup) {
+        (false, 0) => "at base start".to_string(),
+        (false, warmup) => format!("{}s after base starts", warmup),
+        (true, 0) => "at first success".to_string(),
+        (true, warmup) => format!("at first success, at most {}s after base starts", warmup),
+    }
+}
+
+fn kill_policy_description(footer: &ConfigFooter) -> String {
+    if !footer.has_kill_policy() {
+        return "none (fixed checks)".to_string();
+    }
+    let ops: Vec<PolicyOp> = footer.kill_policy().collect();
+    if weaver_abi::kill_policy::validate(&ops).is_err() {
+        return "invalid".to_string();
+    }
+    let actions: Vec<&str> = ops
+        .iter()
+        .filter(|op| op.code == OP_RULE)
+        .map(|op| match op.operand {
+            ACTION_KILL_BASE => "kill_base",
+            ACTION_KILL_OVERLOAD => "kill_overload",
+            ACTION_EXIT => "exit",
+            _ => "log",
+        })
+        .collect();
+    format!("{} rule(s): {}", actions.len(), actions.join(", "))
+}
+
+fn seccomp_description(merged: &[u8], footer: &ConfigFooter) -> String {
+    if footer.seccomp_size == 0 {
+        return "none".to_string();
+    }
+    let profile = usize::try_from(footer.seccomp_offset)
+        .ok()
+        .and_then(|start| merged.get(start..start.checked_add(footer.seccomp_size as usize)?))
+        .and_then(SeccompHeader::parse);
+    let Some((header, _)) = profile else {
+        return "invalid".to_string();
+    };
+    let action = match header.default_action {
+        SECCOMP_DEFAULT_KILL => "kill",
+        SECCOMP_DEFAULT_LOG => "log",
+        _ => "errno",
+    };
+    let targets: Vec<&str> = [(false, "overload"), (true, "base")]
+        .into_iter()
+        .filter(|&(is_base, _)| header.applies_to(is_base))
+        .map(|(_, name)| name)
+        .collect();
+    format!("{} syscalls allowed, others {}, applied to {}", header.count, action, targets.join(" and "))
+}
+
+/// Timing field value with its unit; 0 leaves the choice to the loader
+fn or_default(value: u32, unit: &str) -> String {
+    if value == 0 {
+        "loader default".to_string()
+    } else {
+        format!("{}{}", value, unit)
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const REPORT_CSS: &str = "body{font-family:sans-serif;max-width:960px;margin:2em auto;color:#222}\
+table{border-collapse:collapse;margin-bottom:1em}td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+code{font-size:0.9em}.layout{display:flex;height:2.5em;margin-bottom:1em;border:1px solid #999}\
+.segment{overflow:hidden;white-space:nowrap;padding:0.6em 0.3em;font-size:0.8em;color:#fff}\
+.stub{background:#555}.base{background:#2b6cb0}.overload{background:#c05621}.seccomp{background:#6b46c1}.labels{background:#718096}.footer{background:#2f855a}\
+.pass{color:#2f855a;font-weight:bold}.fail{color:#c53030;font-weight:bold}.warnings li{color:#b7791f}";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use weaver_abi::footer::{
+        AUDIT_PATH_LEN, AUDIT_URL_LEN, CRASH_REPORT_URL_LEN, CWD_PATH_LEN, OUTPUT_LOG_DIR_LEN, INSTALL_DIR_LEN, INSTALL_NAME_LEN, LOG_PATH_LEN, MAGIC_BYTES, ORDER_OVERLOAD_FIRST, OVERLOAD_EXECUTABLE, MAX_MACHINE_FINGERPRINTS, PRODUCT_NAME_LEN, REVOCATION_KEY_LEN,
+        READINESS_TARGET_LEN, REVOCATION_URL_LEN, RUN_AS_USER_LEN, TELEMETRY_URL_LEN,
+    };
+    use weaver_abi::kill_policy::MAX_KILL_POLICY_OPS;
+
+    fn assemble(stub: &[u8], base: &[u8], overload: &[u8]) -> Vec<u8> {
+        let footer = ConfigFooter {
+            magic: *MAGIC_BYTES,
+            version: FOOTER_VERSION,
+            size: ConfigFooter::SIZE as u32,
+            base_offset: stub.len() as u64,
+            base_size: base.len() as u64,
+            overload_offset: (stub.len() + base.len()) as u64,
+            overload_size: overload.len() as u64,
+            grace_period: 30,
+            sync_mode: 1,
+            network_failure_kill_count: 0,
+            overload_jail: 0,
+            order: ORDER_OVERLOAD_FIRST,
+            overload_start_delay: 0,
+            overload_interval: 0,
+            health_check_interval: 0,
+            kill_delay_ms: 0,
+            heartbeat_timeout: 0,
+            shutdown_grace: 0,
+            base_memory_limit_mb: 0,
+            base_cpu_percent: 0,
+            overload_memory_limit_mb: 0,
+            overload_cpu_percent: 0,
+            base_nofile_limit: 0,
+            overload_nofile_limit: 0,
+            run_as_user: [0; RUN_AS_USER_LEN],
+            drop_privileges: 0,
+            seccomp_offset: 0,
+            seccomp_size: 0,
+            hardening: 0,
+            image_checksum: 0,
+            log_level: 0,
+            log_target: 0,
+            log_path: [0; LOG_PATH_LEN],
+            product_name: [0; PRODUCT_NAME_LEN],
+            exec_strategy: 0,
+            base_cwd_mode: 0,
+            base_cwd: [0; CWD_PATH_LEN],
+            overload_cwd_mode: 0,
+            overload_cwd: [0; CWD_PATH_LEN],
+            base_umask: 0,
+            overload_umask: 0,
+            single_instance: 0,
+            instance_id: 0,
+            control_socket: 0,
+            revocation_url: [0; REVOCATION_URL_LEN],
+            revocation_interval: 0,
+            revocation_key: [0; REVOCATION_KEY_LEN],
+            expires_at: 0,
+            expiry_clock_checks: 0,
+            machine_binding: 0,
+            machine_fingerprints: [0; MAX_MACHINE_FINGERPRINTS],
+            audit_path: [0; AUDIT_PATH_LEN],
+            audit_url: [0; AUDIT_URL_LEN],
+            telemetry_url: [0; TELEMETRY_URL_LEN],
+            labels_offset: 0,
+            labels_size: 0,
+            overload_kind: OVERLOAD_EXECUTABLE,
+            interpreter_offset: 0,
+            interpreter_size: 0,
+            install_dir: [0; INSTALL_DIR_LEN],
+            install_name: [0; INSTALL_NAME_LEN],
+            install_flags: 0,
+            install_id: 0,
+            install_files_offset: 0,
+            install_files_size: 0,
+            assets_id: 0,
+            assets_offset: 0,
+            assets_size: 0,
+            prerequisite_runs: 0,
+            prerequisite_on_failure: 0,
+            prerequisite_max_failures: 0,
+            prerequisite_retry_delay: 0,
+            ipc_channel: 0,
+            kill_policy: [0; MAX_KILL_POLICY_OPS],
+            monitor_warmup: 0,
+            arm_after_success: 0,
+            crash_report: 0,
+            crash_stderr_lines: 0,
+            crash_report_url: [0; CRASH_REPORT_URL_LEN],
+            output_log: 0,
+            output_log_max_files: 0,
+            output_log_max_size: 0,
+            output_log_dir: [0; OUTPUT_LOG_DIR_LEN],
+            base_priority: 0,
+            overload_priority: 0,
+            base_cpu_affinity: 0,
+            overload_cpu_affinity: 0,
+            readiness_timeout: 0,
+            readiness_probe: 0,
+            readiness_on_timeout: 0,
+            readiness_target: [0; READINESS_TARGET_LEN],
+            packing: 0,
+            base_unpacked_size: 0,
+            overload_unpacked_size: 0,
+        };
+        [stub, base, overload, footer.as_bytes()].concat()
+    }
+
+    #[test]
+    fn test_report_verifies_intact_payloads() {
+        let merged = assemble(b"stub", b"base-bytes", b"overload-bytes");
+        let report = WeaveReport::build("id", &merged, b"base-bytes", b"overload-bytes");
+
+        let check = |name: &str| report.checks.iter().find(|c| c.name == name).unwrap().passed;
+        assert!(check("Footer present"));
+        assert!(check("Segments contiguous"));
+        assert!(check("Base payload intact"));
+        assert!(check("Overload payload intact"));
+        assert_eq!(report.layout.len(), 4);
+        assert_eq!(report.warnings.len(), 1, "sync mode with a grace period should warn");
+    }
+
+    #[test]
+    fn test_report_lists_embedded_labels() {
+        let labels = br#"{"customer":"acme"}"#;
+        let plain = assemble(b"stub", b"base-bytes", b"overload-bytes");
+        let footer_offset = plain.len() - ConfigFooter::SIZE;
+        let mut footer = ConfigFooter::from_bytes(plain[footer_offset..].try_into().unwrap()).unwrap();
+        footer.labels_offset = footer_offset as u64;
+        footer.labels_size = labels.len() as u64;
+        let merged = [&plain[..footer_offset], labels, footer.as_bytes()].concat();
+
+        let report = WeaveReport::build("id", &merged, b"base-bytes", b"overload-bytes");
+        assert!(report.checks.iter().find(|c| c.name == "Segments contiguous").unwrap().passed);
+        assert_eq!(report.layout[3].name, "Labels");
+        assert_eq!(labels_description(&merged, &footer), r#"{"customer":"acme"}"#);
+    }
+
+    #[test]
+    fn test_report_flags_missing_footer() {
+        let report = WeaveReport::build("id", b"not a merged binary", b"base", b"overload");
+
+        assert!(!report.checks[0].passed);
+        assert!(report.layout.is_empty());
+        assert!(report.render_html().contains("FAIL"));
+    }
+
+    #[test]
+    fn test_report_verifies_image_checksum() {
+        let mut merged = assemble(b"stub", b"base-bytes", b"overload-bytes");
+        let footer_offset = merged.len() - ConfigFooter::SIZE;
+        let mut footer = ConfigFooter::from_bytes(merged[footer_offset..].try_into().unwrap()).unwrap();
+        footer.hardening = HARDEN_CHECKSUM;
+        let mut footer_raw = [0u8; ConfigFooter::SIZE];
+        footer_raw.copy_from_slice(footer.as_bytes());
+        let mut checksum = ImageChecksum::new();
+        checksum.update(&merged[..footer_offset]);
+        checksum.update_footer(&footer_raw);
+        checksum.write_into(&mut footer_raw);
+        merged[footer_offset..].copy_from_slice(&footer_raw);
+
+        let passed = |merged: &[u8]| {
+            let report = WeaveReport::build("id", merged, b"base-bytes", b"overload-bytes");
+            report.checks.iter().find(|c| c.name == "Image checksum").unwrap().passed
+        };
+        assert!(passed(&merged));
+        merged[0] ^= 1;
+        assert!(!passed(&merged));
+    }
+
+    #[test]
+    fn test_jail_description_lists_every_flag() {
+        assert_eq!(jail_description(0), "none");
+        assert_eq!(
+            jail_description(JAIL_FILESYSTEM | JAIL_NETWORK | JAIL_PID | JAIL_EMPTY_ROOT),
+            "filesystem, network, pid, empty root"
+        );
+    }
+
+    #[test]
+    fn test_scheduling_description_lists_cpu_ranges() {
+        assert_eq!(scheduling_description(None, 0), "inherited");
+        assert_eq!(scheduling_description(Some(19), 0b100_1111), "nice 19, CPUs 0-3,6");
+        assert_eq!(scheduling_description(None, 1 << 63 | 1 << 62 | 1), "CPUs 0,62-63");
+    }
+}
@@ -0,0 +1,198 @@
+//! Rules that keep merged binaries past their expiry (`retention_rules`).
+//!
+//! The cleanup task removes expired binaries unless a rule keeps them: one
+//! with `keep_forever` keeps every binary it matches, one with `keep_last`
+//! the newest N. A rule matches merged binaries carrying all of its labels,
+//! merged by one of its `tenants` when it names any. A label value of `*`
+//! matches any value and counts `keep_last` per value, so `{app = "*"}` keeps
+//! the newest N of every app. Kept binaries have their expiry pushed back on
+//! every sweep, so they stay downloadable. Staged binaries only live for their
+//! TTL.
+
+#[cfg(feature = "server")]
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use utoipa::ToSchema;
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+#[cfg(feature = "server")]
+use crate::core::store::BinaryStore;
+use crate::models::binary::{BinaryKind, StoredBinary};
+
+/// Label value that matches any value, one `keep_last` count per value
+const ANY_VALUE: &str = "*";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionRule {
+    /// Labels a binary must carry, `*` for any value
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// API key names whose merges the rule keeps; empty for every caller
+    #[serde(default)]
+    pub tenants: Vec<String>,
+    /// Keep the newest N matching binaries
+    #[serde(default)]
+    pub keep_last: Option<usize>,
+    /// Keep every matching binary
+    #[serde(default)]
+    pub keep_forever: bool,
+}
+
+impl RetentionRule {
+    /// Why the rule can't work, if it can't
+    pub fn problem(&self) -> Option<String> {
+        match (self.keep_last, self.keep_forever) {
+            (None, false) => Some("keeps nothing; set keep_last or keep_forever".to_string()),
+            (Some(_), true) => Some("sets both keep_last and keep_forever".to_string()),
+            (Some(0), false) => Some("keep_last must be at least 1".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Values of the `*` labels, which `keep_last` counts per; `None` if the rule doesn't match
+    fn group<'a>(&self, binary: &'a StoredBinary) -> Option<Vec<&'a str>> {
+        if binary.kind != BinaryKind::Merged {
+            return None;
+        }
+        if !self.tenants.is_empty() && !binary.owner.as_ref().is_some_and(|owner| self.tenants.contains(owner)) {
+            return None;
+        }
+        let mut group = Vec::new();
+        for (key, expected) in &self.labels {
+            let value = binary.labels.get(key)?;
+            if expected == ANY_VALUE {
+                group.push(value.as_str());
+            } else if value != expected {
+                return None;
+            }
+        }
+        Some(group)
+    }
+}
+
+/// IDs of the binaries `rules` keep, expired or not
+pub fn retained<'a>(binaries: impl IntoIterator<Item = &'a StoredBinary>, rules: &[RetentionRule]) -> HashSet<String> {
+    let mut newest_first: Vec<&StoredBinary> = binaries.into_iter().collect();
+    newest_first.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    let mut kept = HashSet::new();
+    for rule in rules {
+        let mut counts: HashMap<Vec<&str>, usize> = HashMap::new();
+        for binary in &newest_first {
+            let Some(group) = rule.group(binary) else {
+                continue;
+            };
+            let count = counts.entry(group).or_default();
+            *count += 1;
+            if rule.keep_forever || rule.keep_last.is_some_and(|keep_last| *count <= keep_last) {
+                kept.insert(binary.id.clone());
+            }
+        }
+    }
+    kept
+}
+
+/// One run of the cleanup task: delete binaries that expired and are older
+/// than `binary_expiration_hours`, unless `retention_rules` keep them
+///
+/// Returns how many were deleted. Kept binaries are renewed until two sweeps
+/// from now.
+#[cfg(feature = "server")]
+pub fn sweep(binary_store: &BinaryStore, config: &Config) -> usize {
+    let keep_for = Duration::seconds(config.cleanup_interval as i64 * 2);
+    let purged = binary_store.purge(&config.retention_rules, Duration::hours(config.binary_expiration_hours), keep_for);
+    for binary in &purged {
+        log::info!("🗑️  Removing expired binary {}", binary.id);
+        binary.remove_files();
+    }
+    purged.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn merged(id: &str, age_secs: i64, labels: &[(&str, &str)], owner: Option<&str>) -> StoredBinary {
+        let created_at = Utc::now() - Duration::seconds(age_secs);
+        StoredBinary {
+            id: id.to_string(),
+            path: format!("/tmp/{}", id),
+            size: 1,
+            created_at,
+            expires_at: created_at,
+            report_path: None,
+            kind: BinaryKind::Merged,
+            sha256: None,
+            labels: labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            provenance: None,
+            signature: None,
+            owner: owner.map(str::to_string),
+            scans: Vec::new(),
+            details: None,
+            downloads: 0,
+            last_accessed: None,
+            stagers: Vec::new(),
+        }
+    }
+
+    fn rule(labels: &[(&str, &str)], keep_last: Option<usize>, keep_forever: bool) -> RetentionRule {
+        RetentionRule {
+            labels: labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            tenants: Vec::new(),
+            keep_last,
+            keep_forever,
+        }
+    }
+
+    fn sorted(ids: HashSet<String>) -> Vec<String> {
+        let mut ids: Vec<String> = ids.into_iter().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_keep_last_and_forever() {
+        let binaries = [
+            merged("foo-1", 40, &[("app", "foo")], None),
+            merged("foo-2", 30, &[("app", "foo")], None),
+            merged("foo-3", 20, &[("app", "foo")], None),
+            merged("bar-1", 35, &[("app", "bar")], None),
+            merged("bar-2", 25, &[("app", "bar"), ("channel", "release")], None),
+            merged("scratch", 10, &[], None),
+        ];
+
+        let rules = [rule(&[("app", "foo")], Some(2), false)];
+        assert_eq!(sorted(retained(&binaries, &rules)), ["foo-2", "foo-3"]);
+
+        // A wildcard counts per value; rules add up
+        let rules = [rule(&[("app", "*")], Some(1), false), rule(&[("channel", "release")], None, true)];
+        assert_eq!(sorted(retained(&binaries, &rules)), ["bar-2", "foo-3"]);
+        let rules = [rule(&[("app", "*")], Some(1), false), rule(&[("app", "bar")], None, true)];
+        assert_eq!(sorted(retained(&binaries, &rules)), ["bar-1", "bar-2", "foo-3"]);
+        assert!(retained(&binaries, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_tenants_and_staged_binaries() {
+        let mut staged = merged("staged", 5, &[], None);
+        staged.kind = BinaryKind::Staged;
+        let binaries = [merged("ci", 20, &[], Some("ci")), merged("dev", 10, &[], Some("dev")), staged];
+
+        let rules = [RetentionRule { tenants: vec!["ci".to_string()], ..rule(&[], None, true) }];
+        assert_eq!(sorted(retained(&binaries, &rules)), ["ci"]);
+        assert_eq!(sorted(retained(&binaries, &[rule(&[], Some(5), false)])), ["ci", "dev"]);
+    }
+
+    #[test]
+    fn test_rule_problems() {
+        assert!(rule(&[], Some(3), false).problem().is_none());
+        assert!(rule(&[], None, true).problem().is_none());
+        assert!(rule(&[], None, false).problem().is_some());
+        assert!(rule(&[], Some(0), false).problem().is_some());
+        assert!(rule(&[], Some(3), true).problem().is_some());
+    }
+}
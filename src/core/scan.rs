@@ -0,0 +1,261 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use utoipa::ToSchema;
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+
+/// Bytes per `INSTREAM` chunk sent to clamd
+const CLAMD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Verdict of one scanner on one merge input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ScanResult {
+    /// `base`, `overload`, `interpreter`, or the path of an install file or asset
+    pub input: String,
+    /// `clamd` or `yara`
+    pub scanner: String,
+    /// Signatures or rules that matched; empty when the input is clean
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matches: Vec<String>,
+}
+
+/// Why the inputs can't be merged
+#[derive(Debug)]
+pub enum ScanError {
+    /// A scanner matched at least one input; holds every result with a match
+    Detected(Vec<ScanResult>),
+    /// A scanner couldn't give a verdict; merges fail closed
+    Failed { scanner: &'static str, reason: String },
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Detected(results) => {
+                let found: Vec<String> =
+                    results.iter().map(|result| format!("{} ({}: {})", result.input, result.scanner, result.matches.join(", "))).collect();
+                write!(f, "scanner matched {}", found.join("; "))
+            }
+            ScanError::Failed { scanner, reason } => write!(f, "{} scan failed: {}", scanner, reason),
+        }
+    }
+}
+
+/// Where clamd listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClamdAddress {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl ClamdAddress {
+    /// A socket path, or `host:port` with an optional `tcp://`
+    fn parse(value: &str) -> Self {
+        let value = value.trim();
+        match value.strip_prefix("tcp://") {
+            Some(address) => ClamdAddress::Tcp(address.to_string()),
+            None if value.starts_with('/') => ClamdAddress::Unix(PathBuf::from(value)),
+            None => ClamdAddress::Tcp(value.to_string()),
+        }
+    }
+}
+
+/// Scans merge inputs with clamd and/or YARA before they are woven
+///
+/// Off unless `WEAVER_SCAN_CLAMD` or `WEAVER_SCAN_YARA_RULES` is set. With
+/// both, every input goes through both.
+pub struct PayloadScanner {
+    clamd: Option<ClamdAddress>,
+    yara_rules: Option<PathBuf>,
+    timeout: Duration,
+    temp_dir: PathBuf,
+}
+
+impl PayloadScanner {
+    #[cfg(feature = "server")]
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            clamd: config.scan_clamd.as_deref().map(ClamdAddress::parse),
+            yara_rules: config.scan_yara_rules.as_ref().map(PathBuf::from),
+            timeout: Duration::from_secs(config.scan_timeout),
+            temp_dir: PathBuf::from(&config.temp_dir),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.clamd.is_some() || self.yara_rules.is_some()
+    }
+
+    /// Names of the configured scanners, for logging
+    pub fn scanners(&self) -> Vec<&'static str> {
+        [self.clamd.as_ref().map(|_| "clamd"), self.yara_rules.as_ref().map(|_| "yara")].into_iter().flatten().collect()
+    }
+
+    /// Scan each named input, returning every verdict if none matched
+    pub async fn scan(&self, inputs: &[(&str, &[u8])]) -> Result<Vec<ScanResult>, ScanError> {
+        let mut results = Vec::new();
+        for &(input, data) in inputs {
+            if let Some(ref address) = self.clamd {
+                let matches = self.within_timeout("clamd", clamd_scan(address, data)).await?;
+                results.push(ScanResult { input: input.to_string(), scanner: "clamd".to_string(), matches });
+            }
+            if let Some(ref rules) = self.yara_rules {
+                let matches = self.within_timeout("yara", self.yara_scan(rules, data)).await?;
+                results.push(ScanResult { input: input.to_string(), scanner: "yara".to_string(), matches });
+            }
+        }
+
+        let detected: Vec<ScanResult> = results.iter().filter(|result| !result.matches.is_empty()).cloned().collect();
+        if detected.is_empty() { Ok(results) } else { Err(ScanError::Detected(detected)) }
+    }
+
+    async fn within_timeout(
+        &self,
+        scanner: &'static str,
+        scan: impl Future<Output = Result<Vec<String>, String>>,
+    ) -> Result<Vec<String>, ScanError> {
+        match tokio::time::timeout(self.timeout, scan).await {
+            Ok(result) => result.map_err(|reason| ScanError::Failed { scanner, reason }),
+            Err(_) => Err(ScanError::Failed { scanner, reason: format!("no verdict within {}s", self.timeout.as_secs()) }),
+        }
+    }
+
+    /// Run the `yara` CLI with every rule file in `rules` against `data`
+    async fn yara_scan(&self, rules: &Path, data: &[u8]) -> Result<Vec<String>, String> {
+        let rule_files = yara_rule_files(rules).map_err(|e| format!("can't read rules from {}: {}", rules.display(), e))?;
+        if rule_files.is_empty() {
+            return Err(format!("no .yar or .yara files in {}", rules.display()));
+        }
+
+        let mut target = tempfile::NamedTempFile::new_in(&self.temp_dir).map_err(|e| e.to_string())?;
+        target.write_all(data).map_err(|e| e.to_string())?;
+        let output = tokio::process::Command::new("yara")
+            .arg("--no-warnings")
+            .args(&rule_files)
+            .arg(target.path())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| format!("can't run yara: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        Ok(parse_yara_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Send `data` to clamd with `INSTREAM` and return the signature it found, if any
+async fn clamd_scan(address: &ClamdAddress, data: &[u8]) -> Result<Vec<String>, String> {
+    let reply = match address {
+        #[cfg(unix)]
+        ClamdAddress::Unix(path) => {
+            let stream = UnixStream::connect(path).await.map_err(|e| format!("can't connect to {}: {}", path.display(), e))?;
+            instream(stream, data).await
+        }
+        #[cfg(not(unix))]
+        ClamdAddress::Unix(path) => return Err(format!("Unix sockets aren't supported here: {}", path.display())),
+        ClamdAddress::Tcp(address) => {
+            let stream = TcpStream::connect(address).await.map_err(|e| format!("can't connect to {}: {}", address, e))?;
+            instream(stream, data).await
+        }
+    };
+    let reply = reply.map_err(|e| e.to_string())?;
+    parse_clamd_reply(&reply).map(|found| found.into_iter().collect())
+}
+
+async fn instream<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, data: &[u8]) -> std::io::Result<String> {
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(CLAMD_CHUNK_SIZE) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await?;
+    Ok(String::from_utf8_lossy(&reply).trim_end_matches('\0').trim().to_string())
+}
+
+/// The signature in a clamd reply such as `stream: Eicar-Signature FOUND`
+///
+/// Errors, such as a stream over clamd's `StreamMaxLength`, come back as the reply itself.
+fn parse_clamd_reply(reply: &str) -> Result<Option<String>, String> {
+    let verdict = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if verdict == "OK" {
+        Ok(None)
+    } else if let Some(signature) = verdict.strip_suffix(" FOUND") {
+        Ok(Some(signature.to_string()))
+    } else {
+        Err(format!("clamd replied {:?}", reply))
+    }
+}
+
+/// Rule names from `yara` output, one `<rule> <target>` line per match
+fn parse_yara_output(output: &str) -> Vec<String> {
+    let mut rules: Vec<String> = output.lines().filter_map(|line| line.split_whitespace().next()).map(str::to_string).collect();
+    rules.dedup();
+    rules
+}
+
+fn yara_rule_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yar" || ext == "yara"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_replies() {
+        assert_eq!(parse_clamd_reply("stream: OK"), Ok(None));
+        assert_eq!(parse_clamd_reply("stream: Win.Test.EICAR_HDB-1 FOUND"), Ok(Some("Win.Test.EICAR_HDB-1".to_string())));
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR").is_err());
+
+        let output = "Packed_UPX /tmp/weaver/.tmpA1\nSuspicious_Strings /tmp/weaver/.tmpA1\n";
+        assert_eq!(parse_yara_output(output), vec!["Packed_UPX", "Suspicious_Strings"]);
+        assert!(parse_yara_output("").is_empty());
+
+        assert_eq!(ClamdAddress::parse("/run/clamav/clamd.ctl"), ClamdAddress::Unix(PathBuf::from("/run/clamav/clamd.ctl")));
+        assert_eq!(ClamdAddress::parse("tcp://clamav:3310"), ClamdAddress::Tcp("clamav:3310".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_clamd_instream() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = ClamdAddress::Tcp(listener.local_addr().unwrap().to_string());
+        let clamd = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut command = [0u8; 10];
+            socket.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut received = Vec::new();
+            loop {
+                let len = socket.read_u32().await.unwrap() as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                socket.read_exact(&mut chunk).await.unwrap();
+                received.extend(chunk);
+            }
+            socket.write_all(b"stream: Eicar-Signature FOUND\0").await.unwrap();
+            received.len()
+        });
+
+        let data = vec![0x41u8; CLAMD_CHUNK_SIZE + 10];
+        assert_eq!(clamd_scan(&address, &data).await, Ok(vec!["Eicar-Signature".to_string()]));
+        assert_eq!(clamd.await.unwrap(), data.len());
+    }
+}
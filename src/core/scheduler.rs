@@ -0,0 +1,241 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+use crate::core::jobs::CancelToken;
+
+/// Bounds how many merges run at once and how many may wait for a slot
+///
+/// Every merge endpoint takes a permit before assembling; merges served from
+/// the cache don't need one.
+///
+/// The limits can be changed with `resize` while merges run.
+pub struct MergeScheduler {
+    permits: Arc<Semaphore>,
+    max_concurrent: AtomicUsize,
+    max_queued: AtomicUsize,
+    queue_timeout: Mutex<Duration>,
+    queued: AtomicUsize,
+    /// Slots given up by shrinking that running merges still hold; they are
+    /// dropped instead of released as those merges finish
+    surplus: Arc<AtomicUsize>,
+}
+
+/// Slot for one running merge, released when dropped
+pub struct MergePermit {
+    permit: Option<OwnedSemaphorePermit>,
+    surplus: Arc<AtomicUsize>,
+}
+
+impl Drop for MergePermit {
+    fn drop(&mut self) {
+        let retire = self.surplus.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |surplus| surplus.checked_sub(1));
+        if let (Ok(_), Some(permit)) = (retire, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+/// Why a merge couldn't get a slot
+#[derive(Debug, PartialEq)]
+pub enum SchedulerError {
+    /// All slots are busy and the queue is full
+    QueueFull,
+    /// Waited `queue_timeout` without a slot freeing up
+    Timeout,
+    /// The merge was cancelled while it waited
+    Cancelled,
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchedulerError::QueueFull => write!(f, "merge queue is full"),
+            SchedulerError::Timeout => write!(f, "timed out waiting for a merge slot"),
+            SchedulerError::Cancelled => write!(f, "cancelled while waiting for a merge slot"),
+        }
+    }
+}
+
+/// Snapshot of the scheduler for `/health`
+#[derive(Debug, Clone, Copy)]
+pub struct SchedulerStatus {
+    pub running: usize,
+    pub queued: usize,
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+}
+
+/// Counts a waiting merge for as long as it is alive
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl MergeScheduler {
+    pub fn new(max_concurrent: usize, max_queued: usize, queue_timeout: Duration) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent: AtomicUsize::new(max_concurrent),
+            max_queued: AtomicUsize::new(max_queued),
+            queue_timeout: Mutex::new(queue_timeout),
+            queued: AtomicUsize::new(0),
+            surplus: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            config.max_concurrent_merges,
+            config.max_queued_merges,
+            Duration::from_secs(config.merge_queue_timeout),
+        )
+    }
+
+    /// Change the limits; running and waiting merges are left alone
+    ///
+    /// Shrinking takes effect as running merges finish: their slots are
+    /// dropped rather than handed to the queue until the new limit holds.
+    pub fn resize(&self, max_concurrent: usize, max_queued: usize, queue_timeout: Duration) {
+        let max_concurrent = max_concurrent.max(1);
+        let previous = self.max_concurrent.swap(max_concurrent, Ordering::SeqCst);
+        if max_concurrent > previous {
+            // Cancel slots still due to be dropped before adding new ones
+            let added = max_concurrent - previous;
+            let cancelled = self
+                .surplus
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |surplus| Some(surplus.saturating_sub(added)))
+                .unwrap_or(0)
+                .min(added);
+            self.permits.add_permits(added - cancelled);
+        } else if max_concurrent < previous {
+            let removed = previous - max_concurrent;
+            let idle = self.permits.forget_permits(removed);
+            self.surplus.fetch_add(removed - idle, Ordering::SeqCst);
+        }
+        self.max_queued.store(max_queued, Ordering::SeqCst);
+        *self.queue_timeout.lock().unwrap() = queue_timeout;
+    }
+
+    /// Take a slot, waiting in the queue up to `queue_timeout` if all are busy
+    pub async fn acquire(&self) -> Result<MergePermit, SchedulerError> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(self.permit(permit));
+        }
+
+        let max_queued = self.max_queued.load(Ordering::SeqCst);
+        let reserved = self.queued.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+            (queued < max_queued).then_some(queued + 1)
+        });
+        if reserved.is_err() {
+            return Err(SchedulerError::QueueFull);
+        }
+        let _slot = QueueSlot(&self.queued);
+
+        let queue_timeout = *self.queue_timeout.lock().unwrap();
+        match tokio::time::timeout(queue_timeout, self.permits.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(self.permit(permit)),
+            // The semaphore is never closed
+            Ok(Err(_)) | Err(_) => Err(SchedulerError::Timeout),
+        }
+    }
+
+    /// Like `acquire`, but stops waiting as soon as `cancel` fires
+    pub async fn acquire_cancellable(&self, cancel: &CancelToken) -> Result<MergePermit, SchedulerError> {
+        tokio::select! {
+            permit = self.acquire() => permit,
+            () = cancel.cancelled() => Err(SchedulerError::Cancelled),
+        }
+    }
+
+    fn permit(&self, permit: OwnedSemaphorePermit) -> MergePermit {
+        MergePermit { permit: Some(permit), surplus: self.surplus.clone() }
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        let max_concurrent = self.max_concurrent.load(Ordering::SeqCst);
+        let held = max_concurrent + self.surplus.load(Ordering::SeqCst);
+        SchedulerStatus {
+            running: held.saturating_sub(self.permits.available_permits()),
+            queued: self.queued.load(Ordering::SeqCst),
+            max_concurrent,
+            max_queued: self.max_queued.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Suggested `Retry-After` for refused merges, in seconds
+    pub fn retry_after(&self) -> u64 {
+        self.queue_timeout.lock().unwrap().as_secs().clamp(1, 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_is_bounded() {
+        let scheduler = MergeScheduler::new(1, 1, Duration::from_millis(50));
+        let running = scheduler.acquire().await.unwrap();
+
+        // One waiter fits in the queue and times out; a second is refused outright
+        let (waiting, refused) = tokio::join!(scheduler.acquire(), async {
+            tokio::task::yield_now().await;
+            scheduler.acquire().await
+        });
+        assert_eq!(waiting.err(), Some(SchedulerError::Timeout));
+        assert_eq!(refused.err(), Some(SchedulerError::QueueFull));
+        assert_eq!(scheduler.status().queued, 0);
+
+        drop(running);
+        assert!(scheduler.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_waiter_gets_released_slot() {
+        let scheduler = Arc::new(MergeScheduler::new(1, 4, Duration::from_secs(5)));
+        let running = scheduler.acquire().await.unwrap();
+
+        let waiter = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire().await.is_ok() }
+        });
+        while scheduler.status().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(scheduler.status().running, 1);
+        drop(running);
+        assert!(waiter.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_resize_keeps_running_merges() {
+        let scheduler = MergeScheduler::new(2, 0, Duration::from_millis(10));
+        let first = scheduler.acquire().await.unwrap();
+        let second = scheduler.acquire().await.unwrap();
+
+        // Shrinking below the running merges waits for them to finish
+        scheduler.resize(1, 0, Duration::from_millis(10));
+        assert_eq!(scheduler.status().running, 2);
+        drop(first);
+        assert_eq!(scheduler.status().running, 1);
+        assert_eq!(scheduler.acquire().await.err(), Some(SchedulerError::QueueFull));
+        drop(second);
+        let only = scheduler.acquire().await.unwrap();
+        assert_eq!(scheduler.acquire().await.err(), Some(SchedulerError::QueueFull));
+
+        scheduler.resize(3, 0, Duration::from_millis(10));
+        let _more = (scheduler.acquire().await.unwrap(), scheduler.acquire().await.unwrap());
+        assert_eq!(scheduler.status().running, 3);
+        drop(only);
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use std::time::{Duration, Instant};
+
+use crate::core::binary::{Architecture, BinaryInfo, OperatingSystem};
+use crate::core::jobs::CancelToken;
+use crate::core::merger::{self, StubOptions, StubRegistry};
+use crate::core::progress::SharedProgressSink;
+use crate::core::workspace::WorkspaceManager;
+
+/// Output of a correctly woven pair: the overload runs to completion before base starts
+const EXPECTED_OUTPUT: &str = "overload\nbase\n";
+const RUN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of merging and running the built-in test pair
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub platform: String,
+    pub stub_sha256: Option<String>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+/// Platform this process runs on, if it is one the test payloads exist for
+pub fn host_platform() -> Option<BinaryInfo> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let arch = if cfg!(target_arch = "x86_64") {
+        Architecture::X86_64
+    } else if cfg!(target_arch = "aarch64") {
+        Architecture::AArch64
+    } else {
+        return None;
+    };
+    Some(BinaryInfo::new(OperatingSystem::Linux, arch))
+}
+
+/// Merge two tiny payloads for the host platform, run the result and check
+/// both halves ran in order
+///
+/// Catches missing or broken stubs before real merges hit them.
+#[tracing::instrument(name = "selftest", skip_all)]
+pub async fn run(workspaces: &WorkspaceManager, stubs: &StubRegistry, progress: &SharedProgressSink) -> SelfTestReport {
+    let started = Instant::now();
+    let platform = host_platform();
+    let stub_sha256 = platform
+        .as_ref()
+        .and_then(|info| stubs.get(info.os, info.arch))
+        .map(|stub| stub.sha256);
+
+    let result = match platform {
+        Some(info) => merge_and_run(&info, workspaces, stubs, progress).await,
+        None => Err(anyhow::anyhow!("No self-test payloads for this host platform")),
+    };
+
+    let (output, error) = match result {
+        Ok(output) if output == EXPECTED_OUTPUT => (Some(output), None),
+        Ok(output) => {
+            let error = format!("Expected {:?}, got {:?}", EXPECTED_OUTPUT, output);
+            (Some(output), Some(error))
+        }
+        Err(e) => (None, Some(format!("{:#}", e))),
+    };
+
+    let report = SelfTestReport {
+        passed: error.is_none(),
+        platform: platform.map_or_else(|| "unsupported".to_string(), |info| info.description()),
+        stub_sha256,
+        output,
+        error,
+        duration: started.elapsed(),
+    };
+
+    if report.passed {
+        log::info!("✅ Self-test passed on {} in {:?}", report.platform, report.duration);
+    } else {
+        log::error!("❌ Self-test failed on {}: {}", report.platform, report.error.as_deref().unwrap_or_default());
+    }
+
+    report
+}
+
+async fn merge_and_run(
+    info: &BinaryInfo,
+    workspaces: &WorkspaceManager,
+    stubs: &StubRegistry,
+    progress: &SharedProgressSink,
+) -> Result<String> {
+    let base = payload(info.arch, b"base\n").context("No base payload")?;
+    let overload = payload(info.arch, b"overload\n").context("No overload payload")?;
+
+    let work_dir = workspaces.create()?;
+
+    let options = StubOptions { sync_mode: true, ..StubOptions::default() };
+    let merged_path = merger::merge_v2_stop_on_exit(
+        &base,
+        &overload,
+        work_dir.path(),
+        info,
+        "",
+        progress,
+        stubs,
+        &options,
+        &CancelToken::new(),
+    )
+    .await
+    .context("Merge failed")?;
+
+    // Nothing from the service's environment leaks into the child, and it can't outlive the timeout
+    let child = tokio::process::Command::new(&merged_path)
+        .env_clear()
+        .current_dir(work_dir.path())
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(RUN_TIMEOUT, child)
+        .await
+        .with_context(|| format!("Merged binary did not exit within {:?}", RUN_TIMEOUT))?
+        .context("Failed to run merged binary")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Merged binary exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Minimal static Linux ELF that writes `message` to stdout and exits 0
+pub(crate) fn payload(arch: Architecture, message: &[u8]) -> Option<Vec<u8>> {
+    const HEADERS_LEN: u64 = 64 + 56;
+    const LOAD_ADDR: u64 = 0x40_0000;
+
+    let len = message.len() as u32;
+    let (machine, align, code) = match arch {
+        Architecture::X86_64 => {
+            let mut code = Vec::new();
+            code.extend_from_slice(&[0xb8, 0x01, 0x00, 0x00, 0x00]); // mov eax, 1 (write)
+            code.extend_from_slice(&[0xbf, 0x01, 0x00, 0x00, 0x00]); // mov edi, 1
+            code.extend_from_slice(&[0x48, 0x8d, 0x35, 0x10, 0x00, 0x00, 0x00]); // lea rsi, [rip + 16]
+            code.push(0xba); // mov edx, len
+            code.extend_from_slice(&len.to_le_bytes());
+            code.extend_from_slice(&[0x0f, 0x05]); // syscall
+            code.extend_from_slice(&[0xb8, 0x3c, 0x00, 0x00, 0x00]); // mov eax, 60 (exit)
+            code.extend_from_slice(&[0x31, 0xff]); // xor edi, edi
+            code.extend_from_slice(&[0x0f, 0x05]); // syscall
+            (62u16, 0x1000u64, code)
+        }
+        Architecture::AArch64 => {
+            let instructions: [u32; 8] = [
+                0xd280_0020,              // mov x0, #1
+                0x1000_00e1,              // adr x1, message (28 bytes ahead)
+                0xd280_0002 | (len << 5), // mov x2, #len
+                0xd280_0808,              // mov x8, #64 (write)
+                0xd400_0001,              // svc #0
+                0xd280_0000,              // mov x0, #0
+                0xd280_0ba8,              // mov x8, #93 (exit)
+                0xd400_0001,              // svc #0
+            ];
+            let code = instructions.iter().flat_map(|i| i.to_le_bytes()).collect();
+            (183u16, 0x10000u64, code)
+        }
+        _ => return None,
+    };
+
+    let total = HEADERS_LEN + code.len() as u64 + message.len() as u64;
+    let mut elf = Vec::with_capacity(total as usize);
+
+    // ELF header
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    elf.extend_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    elf.extend_from_slice(&machine.to_le_bytes());
+    elf.extend_from_slice(&1u32.to_le_bytes()); // EV_CURRENT
+    elf.extend_from_slice(&(LOAD_ADDR + HEADERS_LEN).to_le_bytes()); // entry
+    elf.extend_from_slice(&64u64.to_le_bytes()); // program headers right after this header
+    elf.extend_from_slice(&0u64.to_le_bytes()); // no section headers
+    elf.extend_from_slice(&0u32.to_le_bytes()); // flags
+    elf.extend_from_slice(&64u16.to_le_bytes()); // header size
+    elf.extend_from_slice(&56u16.to_le_bytes()); // program header size
+    elf.extend_from_slice(&1u16.to_le_bytes()); // one program header
+    elf.extend_from_slice(&[0; 6]); // section header size, count, string index
+
+    // Single PT_LOAD mapping the whole file read + execute
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&5u32.to_le_bytes());
+    elf.extend_from_slice(&0u64.to_le_bytes());
+    elf.extend_from_slice(&LOAD_ADDR.to_le_bytes());
+    elf.extend_from_slice(&LOAD_ADDR.to_le_bytes());
+    elf.extend_from_slice(&total.to_le_bytes());
+    elf.extend_from_slice(&total.to_le_bytes());
+    elf.extend_from_slice(&align.to_le_bytes());
+
+    elf.extend_from_slice(&code);
+    elf.extend_from_slice(message);
+    Some(elf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_payloads_are_detected_as_their_platform() {
+        for arch in [Architecture::X86_64, Architecture::AArch64] {
+            let info = BinaryInfo::detect(&payload(arch, b"base\n").unwrap());
+            assert!(info.is_same_platform(&BinaryInfo::new(OperatingSystem::Linux, arch)));
+        }
+        assert!(payload(Architecture::X86, b"base\n").is_none());
+    }
+
+    #[test]
+    fn test_host_payload_prints_its_message() {
+        let Some(info) = host_platform() else {
+            println!("⚠️  Skipping test - no payload for this host");
+            return;
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("payload");
+        std::fs::write(&path, payload(info.arch, b"hello\n").unwrap()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = std::process::Command::new(&path).output().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello\n");
+    }
+}
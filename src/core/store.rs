@@ -0,0 +1,509 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::core::retention::{retained, RetentionRule};
+use crate::models::binary::{StoredBinary, TelemetryEvent, UploadSession};
+
+/// Events kept per binary; older ones are dropped first
+const MAX_TELEMETRY_EVENTS: usize = 1000;
+/// Bytes of events kept across all binaries; the oldest events go first
+const MAX_TELEMETRY_BYTES: usize = 64 * 1024 * 1024;
+
+/// In-memory index of merged artifacts and staged uploads, shared by all workers
+#[derive(Default)]
+pub struct BinaryStore {
+    binaries: Mutex<HashMap<String, StoredBinary>>,
+}
+
+/// One page of `BinaryStore::list`
+pub struct BinaryPage {
+    pub binaries: Vec<StoredBinary>,
+    pub total: usize,
+}
+
+impl BinaryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, binary: StoredBinary) {
+        self.binaries.lock().unwrap().insert(binary.id.clone(), binary);
+    }
+
+    pub fn get(&self, id: &str) -> Option<StoredBinary> {
+        self.binaries.lock().unwrap().get(id).cloned()
+    }
+
+    /// Newest first, `limit` entries starting at `offset`
+    pub fn list(&self, offset: usize, limit: usize) -> BinaryPage {
+        let binaries = self.binaries.lock().unwrap();
+        let mut all: Vec<&StoredBinary> = binaries.values().collect();
+        all.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+
+        BinaryPage {
+            total: all.len(),
+            binaries: all.into_iter().skip(offset).take(limit).cloned().collect(),
+        }
+    }
+
+    /// Binaries whose contents hash to `sha256`, newest first
+    pub fn with_sha256(&self, sha256: &str) -> Vec<StoredBinary> {
+        let binaries = self.binaries.lock().unwrap();
+        let mut matching: Vec<StoredBinary> = binaries.values().filter(|binary| binary.sha256.as_deref() == Some(sha256)).cloned().collect();
+        matching.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        matching
+    }
+
+    /// Bytes taken by all stored binaries
+    pub fn total_size(&self) -> u64 {
+        self.binaries.lock().unwrap().values().map(|binary| binary.size).sum()
+    }
+
+    /// Forget a binary; the caller decides what to do with its files
+    pub fn remove(&self, id: &str) -> Option<StoredBinary> {
+        self.binaries.lock().unwrap().remove(id)
+    }
+
+    /// Forget binaries that expired and are at least `max_age` old, unless `rules` keep them
+    ///
+    /// Kept binaries live at least `keep_for` from now instead. Returns the
+    /// forgotten ones; deleting their files is up to the caller.
+    pub fn purge(&self, rules: &[RetentionRule], max_age: Duration, keep_for: Duration) -> Vec<StoredBinary> {
+        let now = Utc::now();
+        let mut binaries = self.binaries.lock().unwrap();
+        let kept = retained(binaries.values(), rules);
+        for binary in binaries.values_mut().filter(|binary| kept.contains(&binary.id)) {
+            binary.expires_at = binary.expires_at.max(now + keep_for);
+        }
+        let purged: Vec<String> = binaries
+            .values()
+            .filter(|binary| !kept.contains(&binary.id) && binary.expires_at <= now && binary.created_at + max_age <= now)
+            .map(|binary| binary.id.clone())
+            .collect();
+        purged.iter().filter_map(|id| binaries.remove(id)).collect()
+    }
+
+    /// Push the expiry of a binary back by `by` (counted from now if it already expired)
+    pub fn extend(&self, id: &str, by: Duration) -> Option<StoredBinary> {
+        let mut binaries = self.binaries.lock().unwrap();
+        let binary = binaries.get_mut(id)?;
+        binary.expires_at = binary.expires_at.max(Utc::now()) + by;
+        Some(binary.clone())
+    }
+
+    /// Count a download of a binary and, with `renew`, keep it at least that long from now
+    pub fn record_download(&self, id: &str, renew: Option<Duration>) -> Option<StoredBinary> {
+        let mut binaries = self.binaries.lock().unwrap();
+        let binary = binaries.get_mut(id)?;
+        let now = Utc::now();
+        binary.downloads += 1;
+        binary.last_accessed = Some(now);
+        if let Some(renew) = renew {
+            binary.expires_at = binary.expires_at.max(now + renew);
+        }
+        Some(binary.clone())
+    }
+
+    /// Stage a binary again: make sure it lives at least until `expires_at`
+    /// and let `stager` read it
+    ///
+    /// The flag is whether `stager` had staged it before; always true without
+    /// authentication, where staged binaries are shared.
+    pub fn restage(&self, id: &str, expires_at: DateTime<Utc>, stager: Option<&str>) -> Option<(StoredBinary, bool)> {
+        let mut binaries = self.binaries.lock().unwrap();
+        let binary = binaries.get_mut(id)?;
+        binary.expires_at = binary.expires_at.max(expires_at);
+        let staged_before = match stager {
+            Some(stager) if !binary.stagers.iter().any(|known| known == stager) => {
+                binary.stagers.push(stager.to_string());
+                false
+            }
+            _ => true,
+        };
+        Some((binary.clone(), staged_before))
+    }
+}
+
+/// Resumable uploads that haven't been completed yet
+#[derive(Default)]
+pub struct UploadStore {
+    uploads: Mutex<HashMap<String, UploadSession>>,
+}
+
+/// Why an upload can't take a chunk or be completed
+#[derive(Debug, PartialEq)]
+pub enum UploadError {
+    /// Unknown or expired
+    NotFound,
+    /// Another chunk is being written
+    Busy,
+    /// The request doesn't match the bytes received so far, carried here
+    WrongOffset(u64),
+}
+
+impl UploadStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking an upload, deleting the files of expired ones
+    pub fn insert(&self, upload: UploadSession) {
+        let now = Utc::now();
+        let mut uploads = self.uploads.lock().unwrap();
+        uploads.retain(|_, existing| {
+            let keep = existing.expires_at > now || existing.busy;
+            if !keep {
+                let _ = std::fs::remove_file(&existing.path);
+            }
+            keep
+        });
+        uploads.insert(upload.id.clone(), upload);
+    }
+
+    pub fn get(&self, id: &str) -> Option<UploadSession> {
+        let uploads = self.uploads.lock().unwrap();
+        uploads.get(id).filter(|upload| upload.expires_at > Utc::now()).cloned()
+    }
+
+    /// Reserve the upload for a chunk starting at `offset`
+    pub fn claim(&self, id: &str, offset: u64) -> Result<UploadSession, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads
+            .get_mut(id)
+            .filter(|upload| upload.expires_at > Utc::now())
+            .ok_or(UploadError::NotFound)?;
+        if upload.busy {
+            return Err(UploadError::Busy);
+        }
+        if upload.offset != offset {
+            return Err(UploadError::WrongOffset(upload.offset));
+        }
+        upload.busy = true;
+        Ok(upload.clone())
+    }
+
+    /// End a claim, with the data now received up to `offset`
+    pub fn release(&self, id: &str, offset: u64) -> Option<UploadSession> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads.get_mut(id)?;
+        upload.busy = false;
+        upload.offset = offset;
+        Some(upload.clone())
+    }
+
+    /// Stop tracking a fully received upload; its file is the caller's now
+    pub fn take_complete(&self, id: &str) -> Result<UploadSession, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads
+            .get(id)
+            .filter(|upload| upload.expires_at > Utc::now())
+            .ok_or(UploadError::NotFound)?;
+        if upload.busy {
+            return Err(UploadError::Busy);
+        }
+        if upload.offset != upload.size {
+            return Err(UploadError::WrongOffset(upload.offset));
+        }
+        Ok(uploads.remove(id).expect("upload was just found"))
+    }
+
+    /// Abort an upload unless a chunk is being written; the caller deletes its file
+    pub fn discard(&self, id: &str) -> Result<UploadSession, UploadError> {
+        let mut uploads = self.uploads.lock().unwrap();
+        let upload = uploads.get(id).ok_or(UploadError::NotFound)?;
+        if upload.busy {
+            return Err(UploadError::Busy);
+        }
+        Ok(uploads.remove(id).expect("upload was just found"))
+    }
+}
+
+/// Health events reported by merged binaries built with phone-home
+///
+/// Kept apart from `BinaryStore` because binaries keep reporting from the
+/// field long after their artifact has expired from the store. Memory is
+/// bounded by `MAX_TELEMETRY_EVENTS` per binary and `MAX_TELEMETRY_BYTES` in
+/// total instead.
+#[derive(Default)]
+pub struct TelemetryStore {
+    events: Mutex<TelemetryEvents>,
+}
+
+#[derive(Default)]
+struct TelemetryEvents {
+    by_binary: HashMap<String, VecDeque<TelemetryEvent>>,
+    /// Sum of `event_size` over every stored event
+    bytes: usize,
+}
+
+impl TelemetryEvents {
+    /// Drop the oldest event of any binary
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .by_binary
+            .iter_mut()
+            .filter_map(|(_, events)| Some((events.front()?.received_at, events)))
+            .min_by_key(|(received_at, _)| *received_at);
+        if let Some((_, events)) = oldest {
+            let event = events.pop_front().expect("front was just checked");
+            self.bytes -= event_size(&event);
+        }
+    }
+}
+
+/// Approximate memory an event takes
+fn event_size(event: &TelemetryEvent) -> usize {
+    event.event.len() + event.remote_addr.as_ref().map_or(0, String::len) + event.record.to_string().len()
+}
+
+/// One page of `TelemetryStore::list`
+pub struct TelemetryPage {
+    pub events: Vec<TelemetryEvent>,
+    pub total: usize,
+}
+
+impl TelemetryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept reports for `binary_id` from now on
+    pub fn register(&self, binary_id: &str) {
+        self.events.lock().unwrap().by_binary.entry(binary_id.to_string()).or_default();
+    }
+
+    /// Store a report; `false` if the binary wasn't built to send any
+    pub fn record(&self, binary_id: &str, event: TelemetryEvent) -> bool {
+        let mut events = self.events.lock().unwrap();
+        let size = event_size(&event);
+        let Some(binary_events) = events.by_binary.get_mut(binary_id) else {
+            return false;
+        };
+        let dropped = if binary_events.len() == MAX_TELEMETRY_EVENTS { binary_events.pop_front() } else { None };
+        binary_events.push_back(event);
+        events.bytes = events.bytes + size - dropped.as_ref().map_or(0, event_size);
+        while events.bytes > MAX_TELEMETRY_BYTES {
+            events.evict_oldest();
+        }
+        true
+    }
+
+    /// Newest first, `limit` events starting at `offset`; `None` for binaries that don't report
+    pub fn list(&self, binary_id: &str, offset: usize, limit: usize) -> Option<TelemetryPage> {
+        let events = self.events.lock().unwrap();
+        let binary_events = events.by_binary.get(binary_id)?;
+        Some(TelemetryPage {
+            total: binary_events.len(),
+            events: binary_events.iter().rev().skip(offset).take(limit).cloned().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::binary::BinaryKind;
+
+    fn binary(id: &str, age_secs: i64) -> StoredBinary {
+        let created_at = Utc::now() - Duration::seconds(age_secs);
+        StoredBinary {
+            id: id.to_string(),
+            path: format!("/tmp/{}", id),
+            size: 1,
+            created_at,
+            expires_at: created_at + Duration::hours(1),
+            report_path: None,
+            kind: BinaryKind::Merged,
+            sha256: None,
+            labels: Default::default(),
+            provenance: None,
+            signature: None,
+            owner: None,
+            scans: Vec::new(),
+            details: None,
+            downloads: 0,
+            last_accessed: None,
+            stagers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_list_is_newest_first_and_paginated() {
+        let store = BinaryStore::new();
+        store.insert(binary("old", 30));
+        store.insert(binary("new", 10));
+        store.insert(binary("mid", 20));
+
+        let page = store.list(1, 5);
+        assert_eq!(page.total, 3);
+        let ids: Vec<_> = page.binaries.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, ["mid", "old"]);
+    }
+
+    #[test]
+    fn test_with_sha256_is_newest_first() {
+        let store = BinaryStore::new();
+        for (id, age_secs) in [("old", 30), ("new", 10), ("other", 5)] {
+            let sha256 = if id == "other" { "b" } else { "a" };
+            store.insert(StoredBinary { sha256: Some(sha256.repeat(64)), ..binary(id, age_secs) });
+        }
+
+        let ids: Vec<_> = store.with_sha256(&"a".repeat(64)).into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, ["new", "old"]);
+        assert!(store.with_sha256(&"c".repeat(64)).is_empty());
+    }
+
+    #[test]
+    fn test_restage_adds_stagers() {
+        let store = BinaryStore::new();
+        store.insert(StoredBinary { kind: BinaryKind::Staged, stagers: vec!["ci".to_string()], ..binary("staged", 10) });
+
+        let later = Utc::now() + Duration::hours(5);
+        let (staged, staged_before) = store.restage("staged", later, Some("ci")).unwrap();
+        assert!(staged_before);
+        assert_eq!(staged.expires_at, later);
+        let (staged, staged_before) = store.restage("staged", Utc::now(), Some("dev")).unwrap();
+        assert!(!staged_before);
+        assert_eq!(staged.stagers, ["ci", "dev"]);
+        assert_eq!(staged.expires_at, later);
+        assert!(store.restage("staged", later, None).unwrap().1);
+        assert!(store.restage("missing", later, Some("ci")).is_none());
+    }
+
+    #[test]
+    fn test_extend_counts_from_now_once_expired() {
+        let store = BinaryStore::new();
+        store.insert(binary("expired", 7200));
+
+        let extended = store.extend("expired", Duration::minutes(10)).unwrap();
+        assert!(extended.expires_at > Utc::now() + Duration::minutes(9));
+        assert!(store.extend("missing", Duration::minutes(10)).is_none());
+    }
+
+    #[test]
+    fn test_purge_spares_young_and_retained_binaries() {
+        let store = BinaryStore::new();
+        store.insert(binary("live", 60));
+        store.insert(binary("expired", 7200));
+        store.insert(binary("old", 8000));
+        store.insert(StoredBinary { labels: [("channel".to_string(), "release".to_string())].into(), ..binary("release", 9000) });
+
+        let rules = [RetentionRule {
+            labels: [("channel".to_string(), "release".to_string())].into(),
+            tenants: Vec::new(),
+            keep_last: None,
+            keep_forever: true,
+        }];
+        let purged = store.purge(&rules, Duration::minutes(125), Duration::hours(2));
+        let ids: Vec<_> = purged.iter().map(|b| b.id.as_str()).collect();
+        assert_eq!(ids, ["old"]);
+        assert!(store.get("live").is_some() && store.get("expired").is_some());
+        assert!(store.get("release").unwrap().expires_at > Utc::now() + Duration::minutes(119));
+    }
+
+    #[test]
+    fn test_record_download_counts_and_renews() {
+        let store = BinaryStore::new();
+        store.insert(binary("artifact", 3000));
+        let expires_at = store.get("artifact").unwrap().expires_at;
+
+        let counted = store.record_download("artifact", None).unwrap();
+        assert_eq!((counted.downloads, counted.expires_at), (1, expires_at));
+        assert!(counted.last_accessed.is_some());
+
+        let renewed = store.record_download("artifact", Some(Duration::hours(2))).unwrap();
+        assert_eq!(renewed.downloads, 2);
+        assert!(renewed.expires_at > Utc::now() + Duration::minutes(119));
+        // Renewal never shortens a longer expiry
+        let kept = store.record_download("artifact", Some(Duration::minutes(1))).unwrap();
+        assert_eq!(kept.expires_at, renewed.expires_at);
+        assert!(store.record_download("missing", None).is_none());
+    }
+
+    fn upload(id: &str, size: u64) -> UploadSession {
+        let now = Utc::now();
+        UploadSession {
+            id: id.to_string(),
+            path: format!("/tmp/upload_{}.part", id).into(),
+            size,
+            offset: 0,
+            sha256: None,
+            created_at: now,
+            expires_at: now + Duration::hours(1),
+            busy: false,
+        }
+    }
+
+    #[test]
+    fn test_upload_chunks_must_be_sequential() {
+        let store = UploadStore::new();
+        store.insert(upload("u", 10));
+
+        assert_eq!(store.claim("u", 4).unwrap_err(), UploadError::WrongOffset(0));
+        store.claim("u", 0).unwrap();
+        assert_eq!(store.claim("u", 0).unwrap_err(), UploadError::Busy);
+        assert_eq!(store.take_complete("u").unwrap_err(), UploadError::Busy);
+        store.release("u", 6);
+
+        assert_eq!(store.take_complete("u").unwrap_err(), UploadError::WrongOffset(6));
+        store.claim("u", 6).unwrap();
+        store.release("u", 10);
+        assert_eq!(store.take_complete("u").unwrap().size, 10);
+        assert_eq!(store.claim("u", 10).unwrap_err(), UploadError::NotFound);
+    }
+
+    fn event(name: &str) -> TelemetryEvent {
+        TelemetryEvent {
+            received_at: Utc::now(),
+            remote_addr: None,
+            event: name.to_string(),
+            record: serde_json::json!({ "event": name }),
+        }
+    }
+
+    #[test]
+    fn test_telemetry_only_for_registered_binaries() {
+        let store = TelemetryStore::new();
+        assert!(!store.record("unknown", event("health_kill")));
+        assert!(store.list("unknown", 0, 10).is_none());
+
+        store.register("issued");
+        assert!(store.record("issued", event("health_invalid")));
+        assert!(store.record("issued", event("health_kill")));
+        let page = store.list("issued", 0, 1).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.events[0].event, "health_kill");
+    }
+
+    #[test]
+    fn test_telemetry_drops_oldest_events() {
+        let store = TelemetryStore::new();
+        store.register("chatty");
+        for i in 0..=MAX_TELEMETRY_EVENTS {
+            store.record("chatty", event(&i.to_string()));
+        }
+        let page = store.list("chatty", MAX_TELEMETRY_EVENTS - 1, 1).unwrap();
+        assert_eq!(page.total, MAX_TELEMETRY_EVENTS);
+        assert_eq!(page.events[0].event, "1");
+        let events = store.events.lock().unwrap();
+        assert_eq!(events.bytes, events.by_binary["chatty"].iter().map(event_size).sum::<usize>());
+    }
+
+    #[test]
+    fn test_telemetry_evicts_oldest_across_binaries() {
+        let store = TelemetryStore::new();
+        store.register("first");
+        store.register("second");
+        let aged = |name: &str, age_secs: i64| TelemetryEvent { received_at: Utc::now() - Duration::seconds(age_secs), ..event(name) };
+        store.record("first", aged("old", 30));
+        store.record("second", aged("newer", 20));
+        store.record("first", aged("newest", 10));
+
+        let mut events = store.events.lock().unwrap();
+        events.evict_oldest();
+        assert_eq!(events.by_binary["first"].iter().map(|e| e.event.as_str()).collect::<Vec<_>>(), ["newest"]);
+        assert_eq!(events.by_binary["second"].len(), 1);
+        assert_eq!(events.bytes, event_size(&event("newer")) + event_size(&event("newest")));
+    }
+}
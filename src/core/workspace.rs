@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "server")]
+use crate::config::Config;
+
+/// Name prefix of the work dirs the manager owns under the temp dir
+const WORKSPACE_PREFIX: &str = "merge_";
+
+/// Creates merge work dirs under the temp dir and reclaims abandoned ones
+///
+/// A work dir is removed when its `Workspace` is dropped, which also happens
+/// while a panic unwinds. Dirs a crashed process left behind are removed by
+/// `reclaim_stale` once they are older than `stale_after`; the age check also
+/// protects other instances sharing the same temp dir.
+pub struct WorkspaceManager {
+    root: PathBuf,
+    stale_after: Duration,
+    /// Work dirs of merges still running; never reclaimed
+    active: Mutex<HashSet<PathBuf>>,
+}
+
+/// Work dir of one merge, removed with everything in it when dropped
+pub struct Workspace<'a> {
+    manager: &'a WorkspaceManager,
+    path: PathBuf,
+}
+
+impl Workspace<'_> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Workspace<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                log::warn!("Failed to remove work dir {}: {}", self.path.display(), e);
+            }
+        }
+        // Poisoning doesn't matter for a set of paths, and this may run while unwinding
+        self.manager.active.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.path);
+    }
+}
+
+impl WorkspaceManager {
+    pub fn new(root: impl Into<PathBuf>, stale_after: Duration) -> Self {
+        Self { root: root.into(), stale_after, active: Mutex::new(HashSet::new()) }
+    }
+
+    #[cfg(feature = "server")]
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.temp_dir, Duration::from_secs(config.workspace_max_age))
+    }
+
+    /// The temp dir work dirs are created in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create a fresh, tracked work dir
+    pub fn create(&self) -> io::Result<Workspace<'_>> {
+        fs::create_dir_all(&self.root)?;
+        let path = self.root.join(format!("{}{}", WORKSPACE_PREFIX, uuid::Uuid::new_v4()));
+        fs::create_dir(&path)?;
+        self.active.lock().unwrap().insert(path.clone());
+        Ok(Workspace { manager: self, path })
+    }
+
+    /// Remove work dirs older than `stale_after` that no running merge owns
+    ///
+    /// Returns how many were removed. Blocks on the filesystem, so call it
+    /// from `spawn_blocking` on a runtime thread.
+    pub fn reclaim_stale(&self) -> usize {
+        let Ok(entries) = fs::read_dir(&self.root) else { return 0 };
+        let active = self.active.lock().unwrap();
+        let now = SystemTime::now();
+
+        let mut reclaimed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let owned = entry.file_name().to_str().is_some_and(|name| name.starts_with(WORKSPACE_PREFIX));
+            if !owned || active.contains(&path) || !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok());
+            if age.is_none_or(|age| age < self.stale_after) {
+                continue;
+            }
+            match fs::remove_dir_all(&path) {
+                Ok(()) => {
+                    log::info!("🧹 Reclaimed stale work dir {}", path.display());
+                    reclaimed += 1;
+                }
+                Err(e) => log::warn!("Failed to reclaim work dir {}: {}", path.display(), e),
+            }
+        }
+        reclaimed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_removed_on_drop_and_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = WorkspaceManager::new(dir.path(), Duration::from_secs(3600));
+
+        let workspace = manager.create().unwrap();
+        let path = workspace.path().to_path_buf();
+        fs::write(path.join("merged"), b"data").unwrap();
+        drop(workspace);
+        assert!(!path.exists());
+
+        let path = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let workspace = manager.create().unwrap();
+            let path = workspace.path().to_path_buf();
+            std::panic::resume_unwind(Box::new(path));
+        }))
+        .unwrap_err()
+        .downcast::<PathBuf>()
+        .unwrap();
+        assert!(!path.exists());
+        assert!(manager.active.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reclaim_skips_active_and_foreign_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = WorkspaceManager::new(dir.path(), Duration::ZERO);
+
+        let running = manager.create().unwrap();
+        let orphan = dir.path().join(format!("{}orphan", WORKSPACE_PREFIX));
+        fs::create_dir(&orphan).unwrap();
+        let staged = dir.path().join("staged_abc.bin");
+        fs::write(&staged, b"keep").unwrap();
+
+        assert_eq!(manager.reclaim_stale(), 1);
+        assert!(!orphan.exists());
+        assert!(running.path().exists());
+        assert!(staged.exists());
+
+        // Nothing is old enough with the default threshold
+        fs::create_dir(&orphan).unwrap();
+        assert_eq!(WorkspaceManager::new(dir.path(), Duration::from_secs(3600)).reclaim_stale(), 0);
+    }
+}
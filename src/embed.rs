@@ -0,0 +1,301 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::core::binary::{is_dynamically_linked, validate_executable, validate_overload, BinaryInfo, InvalidExecutable};
+use crate::core::digest::sha256_hex;
+use crate::core::jobs::CancelToken;
+use crate::core::merger::{self, MergeInput, MergeJob, MergeStrategies, MergeStrategy, StubOptions, StubRegistry};
+use crate::core::progress::{MemoryProgressSink, SharedProgressSink};
+use crate::core::workspace::WorkspaceManager;
+
+/// Age after which an abandoned work dir may be reclaimed, as `WEAVER_WORKSPACE_MAX_AGE` defaults to
+const DEFAULT_STALE_AFTER: Duration = Duration::from_secs(3600);
+
+/// The weaving engine for embedding in other Rust programs
+///
+/// Needs neither the HTTP service nor Redis: progress stays in memory unless
+/// another sink is set, and work dirs go under the system temp dir unless
+/// another one is set. One `Weaver` can run any number of merges at once.
+///
+/// ```no_run
+/// # async fn weave() -> anyhow::Result<()> {
+/// use weaver::{MergeRequest, Weaver};
+///
+/// let weaver = Weaver::builder().build();
+/// let merged = weaver.merge(MergeRequest::from_files("base", "overload")?.output_path("merged")).await?;
+/// println!("{} ({})", merged.path().unwrap().display(), merged.sha256);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Weaver {
+    stubs: Arc<StubRegistry>,
+    strategies: MergeStrategies,
+    workspaces: WorkspaceManager,
+    progress: SharedProgressSink,
+}
+
+/// Configures a `Weaver`; every setting has a default
+#[derive(Default)]
+pub struct WeaverBuilder {
+    stubs: Option<Arc<StubRegistry>>,
+    strategies: MergeStrategies,
+    stub_dir: Option<PathBuf>,
+    work_dir: Option<PathBuf>,
+    stale_after: Option<Duration>,
+    progress: Option<SharedProgressSink>,
+}
+
+impl WeaverBuilder {
+    /// Directory of loader stubs taking precedence over the embedded ones
+    pub fn stub_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.stub_dir = Some(dir.into());
+        self
+    }
+
+    /// Share a stub registry with the rest of the program; overrides `stub_dir`
+    pub fn stubs(mut self, stubs: Arc<StubRegistry>) -> Self {
+        self.stubs = Some(stubs);
+        self
+    }
+
+    /// Make another merge strategy available, or replace a built-in one of the same name
+    pub fn strategy(mut self, strategy: Arc<dyn MergeStrategy>) -> Self {
+        self.strategies.register(strategy);
+        self
+    }
+
+    /// Directory merges work in; defaults to the system temp dir
+    pub fn work_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.work_dir = Some(dir.into());
+        self
+    }
+
+    /// Age after which `Weaver::reclaim_stale` removes an abandoned work dir
+    pub fn stale_after(mut self, age: Duration) -> Self {
+        self.stale_after = Some(age);
+        self
+    }
+
+    /// Where merge progress is published; defaults to an in-memory sink
+    pub fn progress(mut self, sink: SharedProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    pub fn build(self) -> Weaver {
+        Weaver {
+            stubs: self.stubs.unwrap_or_else(|| Arc::new(StubRegistry::load(self.stub_dir))),
+            strategies: self.strategies,
+            workspaces: WorkspaceManager::new(
+                self.work_dir.unwrap_or_else(std::env::temp_dir),
+                self.stale_after.unwrap_or(DEFAULT_STALE_AFTER),
+            ),
+            progress: self.progress.unwrap_or_else(|| Arc::new(MemoryProgressSink::new())),
+        }
+    }
+}
+
+/// One payload of a `MergeRequest`
+#[derive(Debug, Clone)]
+struct Payload {
+    bytes: Vec<u8>,
+    /// File `bytes` were read from, copied in the kernel when possible
+    path: Option<PathBuf>,
+}
+
+impl Payload {
+    fn read(path: &Path) -> io::Result<Self> {
+        Ok(Self { bytes: fs::read(path)?, path: Some(path.to_path_buf()) })
+    }
+
+    fn input(&self) -> MergeInput<'_> {
+        match &self.path {
+            Some(path) => MergeInput::file(&self.bytes, path),
+            None => MergeInput::from(&self.bytes[..]),
+        }
+    }
+}
+
+/// Base, overload and everything else one merge needs
+#[derive(Clone)]
+pub struct MergeRequest {
+    base: Payload,
+    overload: Payload,
+    options: StubOptions,
+    strategy: Option<String>,
+    output_path: Option<PathBuf>,
+    task_id: String,
+    cancel: CancelToken,
+}
+
+impl MergeRequest {
+    pub fn new(base: impl Into<Vec<u8>>, overload: impl Into<Vec<u8>>) -> Self {
+        Self {
+            base: Payload { bytes: base.into(), path: None },
+            overload: Payload { bytes: overload.into(), path: None },
+            options: StubOptions::default(),
+            strategy: None,
+            output_path: None,
+            task_id: uuid::Uuid::new_v4().to_string(),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    pub fn from_files(base: impl AsRef<Path>, overload: impl AsRef<Path>) -> io::Result<Self> {
+        let mut request = Self::new(Vec::new(), Vec::new());
+        request.base = Payload::read(base.as_ref())?;
+        request.overload = Payload::read(overload.as_ref())?;
+        Ok(request)
+    }
+
+    /// Runtime behavior of the merged binary; defaults to `StubOptions::default()`
+    pub fn options(mut self, options: StubOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Merge strategy by name; by default the first one that supports base's platform
+    pub fn strategy(mut self, name: impl Into<String>) -> Self {
+        self.strategy = Some(name.into());
+        self
+    }
+
+    /// Write the merged binary to `path` instead of returning its bytes
+    pub fn output_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_path = Some(path.into());
+        self
+    }
+
+    /// ID progress is published under; a random UUID by default
+    pub fn task_id(mut self, task_id: impl Into<String>) -> Self {
+        self.task_id = task_id.into();
+        self
+    }
+
+    /// Token to cancel the merge with, or to give it a deadline
+    pub fn cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
+/// Where a merged binary ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergedOutput {
+    Bytes(Vec<u8>),
+    /// Written to the request's `output_path`, executable
+    File(PathBuf),
+}
+
+/// Result of a successful `Weaver::merge`
+#[derive(Debug, Clone)]
+pub struct MergedArtifact {
+    pub output: MergedOutput,
+    /// Platform of base, and so of the merged binary
+    pub platform: BinaryInfo,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl MergedArtifact {
+    /// The merged binary, if it was returned in memory
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.output {
+            MergedOutput::Bytes(bytes) => Some(bytes),
+            MergedOutput::File(_) => None,
+        }
+    }
+
+    /// The merged binary's path, if it was written to a file
+    pub fn path(&self) -> Option<&Path> {
+        match &self.output {
+            MergedOutput::Bytes(_) => None,
+            MergedOutput::File(path) => Some(path),
+        }
+    }
+}
+
+impl Weaver {
+    pub fn builder() -> WeaverBuilder {
+        WeaverBuilder::default()
+    }
+
+    pub fn stubs(&self) -> &StubRegistry {
+        &self.stubs
+    }
+
+    /// Validate the inputs, weave them and return the merged binary
+    ///
+    /// Invalid inputs fail with an `InvalidExecutable`, incompatible ones with
+    /// a `MergeError` and cancelled merges with a `Cancelled`; find them with
+    /// `anyhow::Error::downcast_ref`.
+    pub async fn merge(&self, request: MergeRequest) -> Result<MergedArtifact> {
+        let options = &request.options;
+        let (base, overload) = (&request.base.bytes[..], &request.overload.bytes[..]);
+        validate_executable(base).context("Invalid base binary")?;
+        if options.overload_library && !is_dynamically_linked(base) {
+            return Err(anyhow::Error::new(InvalidExecutable::StaticallyLinked).context("Invalid base binary"));
+        }
+        validate_overload(overload, options.overload_library).context("Invalid overload binary")?;
+        if let Some(interpreter) = &options.interpreter {
+            validate_executable(&interpreter.data).context("Invalid interpreter binary")?;
+        }
+
+        let base_info = BinaryInfo::detect(base);
+        let interpreter = options.interpreter.as_ref().map(|interpreter| &interpreter.data[..]);
+        merger::check_payload_platforms(&base_info, overload, interpreter)?;
+        let strategy = self.strategies.select(request.strategy.as_deref(), &base_info, &self.stubs)?;
+
+        let work_dir = self.workspaces.create()?;
+        let merged_path = strategy
+            .merge(MergeJob {
+                base: request.base.input(),
+                overload: request.overload.input(),
+                work_path: work_dir.path(),
+                base_info: &base_info,
+                task_id: &request.task_id,
+                progress: &self.progress,
+                stubs: &self.stubs,
+                options,
+                cancel: &request.cancel,
+            })
+            .await?;
+
+        let merged = fs::read(&merged_path).context("Failed to read merged binary")?;
+        let sha256 = sha256_hex(&merged);
+        let size = merged.len() as u64;
+        let output = match request.output_path {
+            Some(path) => {
+                // The work dir may be on another filesystem, so copy rather than rename
+                fs::copy(&merged_path, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+                MergedOutput::File(path)
+            }
+            None => MergedOutput::Bytes(merged),
+        };
+        Ok(MergedArtifact { output, platform: base_info, sha256, size })
+    }
+
+    /// Remove abandoned work dirs; see `WorkspaceManager::reclaim_stale`
+    pub fn reclaim_stale(&self) -> usize {
+        self.workspaces.reclaim_stale()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invalid_inputs_are_refused_before_merging() {
+        let dir = tempfile::tempdir().unwrap();
+        let weaver = Weaver::builder().work_dir(dir.path()).build();
+
+        let error = weaver.merge(MergeRequest::new(vec![b'x'; 256], vec![b'x'; 256])).await.unwrap_err();
+        assert_eq!(error.downcast_ref::<InvalidExecutable>(), Some(&InvalidExecutable::UnknownFormat));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,718 @@
+use futures_util::Stream;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
+
+use crate::config::SharedConfig;
+use crate::core::auth::{ApiKeys, Caller};
+use crate::core::binary::{
+    is_dynamically_linked, validate_executable, validate_overload, BinaryInfo, InvalidExecutable, OperatingSystem, OverloadKind, strip_payload,
+};
+use crate::core::merger::{self, BundledInterpreter, MergeJob, MergeStrategies, StubAppend, StubOptions, StubRegistry};
+use crate::core::policy::{MergeFacts, Policy};
+use crate::core::provenance::MergeProvenance;
+use crate::core::progress::{ProgressTracker, ProgressStep, SharedProgressSink};
+use crate::core::diskguard::{projected_output_size, DiskGuard};
+use crate::core::digest::sha256_file;
+use crate::core::jobs::{merge_timeout, Cancelled, JobRegistry};
+use crate::core::scan::{PayloadScanner, ScanError};
+use crate::core::scheduler::{MergeScheduler, SchedulerError};
+use crate::core::signing::Signer;
+use crate::core::store::{BinaryStore, TelemetryStore};
+use crate::core::workspace::WorkspaceManager;
+use crate::models::binary::{validate_labels, BinaryKind, Labels, StoredBinary};
+use crate::models::request::MergeMode;
+use crate::models::response::{ErrorCode, MergeDetails};
+use weaver_abi::footer::{JAIL_EMPTY_ROOT, JAIL_FILESYSTEM, JAIL_NETWORK, JAIL_PID};
+
+pub mod pb {
+    tonic::include_proto!("weaver.v1");
+}
+
+use pb::merge_request::Payload;
+use pb::weaver_server::{Weaver, WeaverServer};
+use pb::{DownloadChunk, DownloadRequest, MergeReply, MergeRequest, ProgressReply, ProgressRequest};
+
+/// Size of each `DownloadChunk`, well below tonic's 4MB message limit
+const DOWNLOAD_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// gRPC front-end sharing the HTTP API's store, config, progress backend and stubs
+pub struct WeaverService {
+    config: Arc<SharedConfig>,
+    binary_store: Arc<BinaryStore>,
+    telemetry_store: Arc<TelemetryStore>,
+    progress: SharedProgressSink,
+    stubs: Arc<StubRegistry>,
+    strategies: Arc<MergeStrategies>,
+    signer: Arc<Signer>,
+    scheduler: Arc<MergeScheduler>,
+    disk_guard: Arc<DiskGuard>,
+    jobs: Arc<JobRegistry>,
+    workspaces: Arc<WorkspaceManager>,
+    api_keys: Arc<ApiKeys>,
+    scanner: Arc<PayloadScanner>,
+    policy: Arc<Policy>,
+}
+
+impl WeaverService {
+    pub fn new(
+        config: Arc<SharedConfig>,
+        binary_store: Arc<BinaryStore>,
+        telemetry_store: Arc<TelemetryStore>,
+        progress: SharedProgressSink,
+        stubs: Arc<StubRegistry>,
+        strategies: Arc<MergeStrategies>,
+        signer: Arc<Signer>,
+        scheduler: Arc<MergeScheduler>,
+        disk_guard: Arc<DiskGuard>,
+        jobs: Arc<JobRegistry>,
+        workspaces: Arc<WorkspaceManager>,
+        api_keys: Arc<ApiKeys>,
+        scanner: Arc<PayloadScanner>,
+        policy: Arc<Policy>,
+    ) -> Self {
+        Self {
+            config,
+            binary_store,
+            telemetry_store,
+            progress,
+            stubs,
+            strategies,
+            signer,
+            scheduler,
+            disk_guard,
+            jobs,
+            workspaces,
+            api_keys,
+            scanner,
+            policy,
+        }
+    }
+
+    /// Who sent `request`, from its `authorization: Bearer` metadata, when `WEAVER_API_KEYS` is set
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<Option<Caller>, Status> {
+        if !self.api_keys.is_enabled() {
+            return Ok(None);
+        }
+        request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|key| self.api_keys.authenticate(key))
+            .map(Some)
+            .ok_or_else(|| Status::unauthenticated("Missing or invalid API key"))
+    }
+}
+
+/// Serve the gRPC API until the server fails
+pub async fn serve(addr: SocketAddr, service: WeaverService) -> anyhow::Result<()> {
+    log::info!("🔌 gRPC listening on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(WeaverServer::new(service))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+/// Bytes one Merge stream may still carry, across every file in it
+///
+/// The gRPC counterpart of the multipart `total_limit` HTTP uploads get:
+/// `max_file_size` caps each file and the request as a whole.
+struct StreamBudget {
+    left: usize,
+    limit: usize,
+}
+
+impl StreamBudget {
+    fn new(limit: usize) -> Self {
+        Self { left: limit, limit }
+    }
+
+    fn take(&mut self, len: usize) -> Result<(), Status> {
+        self.left = self.left.checked_sub(len).ok_or_else(|| {
+            Status::invalid_argument(format!("Merge request too large (max {} bytes in total)", self.limit))
+        })?;
+        Ok(())
+    }
+}
+
+/// Append to the last file in `files`, or start a new one when the path changes
+fn append_file_chunk(
+    files: &mut Vec<(String, Vec<u8>)>,
+    chunk: pb::DataFileChunk,
+    budget: &mut StreamBudget,
+    what: &str,
+) -> Result<(), Status> {
+    if files.last().is_none_or(|(path, _)| *path != chunk.path) {
+        if files.len() == merger::v2::MAX_DATA_FILES {
+            return Err(Status::invalid_argument(format!("Too many {}s (max {})", what.to_lowercase(), merger::v2::MAX_DATA_FILES)));
+        }
+        files.push((chunk.path, Vec::new()));
+    }
+    let (path, data) = files.last_mut().expect("just pushed");
+    append_chunk(data, &chunk.data, budget, &format!("{} {:?}", what, path))
+}
+
+fn append_chunk(buffer: &mut Vec<u8>, chunk: &[u8], budget: &mut StreamBudget, name: &str) -> Result<(), Status> {
+    if buffer.len() + chunk.len() > budget.limit {
+        return Err(Status::invalid_argument(format!("{} too large (max {} bytes)", name, budget.limit)));
+    }
+    budget.take(chunk.len())?;
+    buffer.extend_from_slice(chunk);
+    Ok(())
+}
+
+#[tonic::async_trait]
+impl Weaver for WeaverService {
+    #[tracing::instrument(name = "grpc Merge", skip_all)]
+    async fn merge(&self, request: Request<Streaming<MergeRequest>>) -> Result<Response<MergeReply>, Status> {
+        let started_on = chrono::Utc::now();
+        let caller = self.authenticate(&request)?;
+        // Settings as of the request; a reload doesn't change them mid-merge
+        let config = self.config.load_full();
+        let mut stream = request.into_inner();
+        let mut options = pb::MergeOptions::default();
+        let mut base_data = Vec::new();
+        let mut overload_data = Vec::new();
+        let mut interpreter_data = Vec::new();
+        let mut install_files: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut assets: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut budget = StreamBudget::new(config.max_file_size);
+
+        while let Some(message) = stream.message().await? {
+            match message.payload {
+                Some(Payload::Options(o)) => options = o,
+                Some(Payload::BaseChunk(chunk)) => {
+                    append_chunk(&mut base_data, &chunk, &mut budget, "Base binary")?
+                }
+                Some(Payload::OverloadChunk(chunk)) => {
+                    append_chunk(&mut overload_data, &chunk, &mut budget, "Overload binary")?
+                }
+                Some(Payload::InterpreterChunk(chunk)) => {
+                    append_chunk(&mut interpreter_data, &chunk, &mut budget, "Interpreter binary")?
+                }
+                Some(Payload::InstallFileChunk(chunk)) => {
+                    append_file_chunk(&mut install_files, chunk, &mut budget, "Install file")?
+                }
+                Some(Payload::AssetChunk(chunk)) => {
+                    append_file_chunk(&mut assets, chunk, &mut budget, "Asset")?
+                }
+                None => {}
+            }
+        }
+
+        if base_data.is_empty() || overload_data.is_empty() {
+            return Err(Status::invalid_argument("Both base and overload binaries are required"));
+        }
+        validate_executable(&base_data).map_err(|e| Status::invalid_argument(format!("Base binary: {}", e)))?;
+        if options.overload_library && !is_dynamically_linked(&base_data) {
+            return Err(Status::invalid_argument(format!("Base binary: {}", InvalidExecutable::StaticallyLinked)));
+        }
+        let kind = validate_overload(&overload_data, options.overload_library)
+            .map_err(|e| Status::invalid_argument(format!("Overload binary: {}", e)))?;
+        let interpreter = if interpreter_data.is_empty() {
+            None
+        } else if !matches!(kind, OverloadKind::Script(_)) {
+            return Err(Status::invalid_argument("An interpreter is only bundled with an overload that starts with #!"));
+        } else {
+            validate_executable(&interpreter_data).map_err(|e| Status::invalid_argument(format!("Interpreter binary: {}", e)))?;
+            Some(BundledInterpreter::new(&interpreter_data))
+        };
+
+        log::info!("🔌 gRPC merge: base {} bytes, overload {} bytes", base_data.len(), overload_data.len());
+
+        if options.base_cpu_percent > 100 || options.overload_cpu_percent > 100 {
+            return Err(Status::invalid_argument("CPU limits are a percentage between 1 and 100"));
+        }
+        let run_as_user = Some(options.run_as_user.trim().to_string()).filter(|name| !name.is_empty());
+        if let Some(ref name) = run_as_user {
+            merger::v2::validate_run_as_user(name).map_err(Status::invalid_argument)?;
+        }
+        let log_level = match options.stub_log_level.trim() {
+            "" => merger::StubLogLevel::default(),
+            level => merger::StubLogLevel::parse(level).map_err(Status::invalid_argument)?,
+        };
+        let log_path = Some(options.stub_log_path.trim()).filter(|path| !path.is_empty());
+        let log_target = match options.stub_log_target.trim() {
+            "" => "stderr",
+            target => target,
+        };
+        let log_target = merger::StubLogTarget::parse(log_target, log_path).map_err(Status::invalid_argument)?;
+        let product_name = Some(options.product_name.trim().to_string()).filter(|name| !name.is_empty());
+        if let Some(ref name) = product_name {
+            merger::v2::validate_product_name(name).map_err(Status::invalid_argument)?;
+        }
+        let mode = match options.mode.trim() {
+            "" => MergeMode::default(),
+            value => MergeMode::parse(value).map_err(Status::invalid_argument)?,
+        };
+        let exec_strategy = match options.exec_strategy.trim() {
+            "" => merger::ExecStrategy::default(),
+            value => merger::ExecStrategy::parse(value).map_err(Status::invalid_argument)?,
+        };
+        let stub_flavor = match options.stub_flavor.trim() {
+            "" => None,
+            value => Some(merger::StubFlavor::parse(value).map_err(Status::invalid_argument)?),
+        };
+        let working_dir = |value: &str| match value.trim() {
+            "" => Ok(merger::WorkingDir::default()),
+            dir => merger::WorkingDir::parse(dir).map_err(Status::invalid_argument),
+        };
+        let base_working_dir = working_dir(options.base_working_dir.as_str())?;
+        let overload_working_dir = working_dir(options.overload_working_dir.as_str())?;
+        let umask = |value: &str| match value.trim() {
+            "" => Ok(None),
+            umask => merger::v2::parse_umask(umask).map(Some).map_err(Status::invalid_argument),
+        };
+        let base_umask = umask(options.base_umask.as_str())?;
+        let overload_umask = umask(options.overload_umask.as_str())?;
+        let priority = |value: &str| match value.trim() {
+            "" => Ok(None),
+            priority => merger::v2::parse_priority(priority).map(Some).map_err(Status::invalid_argument),
+        };
+        let base_priority = priority(options.base_priority.as_str())?;
+        let overload_priority = priority(options.overload_priority.as_str())?;
+        let cpu_affinity = |value: &str| match value.trim() {
+            "" => Ok(0),
+            cpus => merger::v2::parse_cpu_affinity(cpus).map_err(Status::invalid_argument),
+        };
+        let base_cpu_affinity = cpu_affinity(options.base_cpu_affinity.as_str())?;
+        let overload_cpu_affinity = cpu_affinity(options.overload_cpu_affinity.as_str())?;
+        let single_instance = match options.single_instance.trim() {
+            "" => merger::SingleInstance::default(),
+            mode => merger::SingleInstance::parse(mode).map_err(Status::invalid_argument)?,
+        };
+        let prerequisite_on_failure = match options.prerequisite_on_failure.trim() {
+            "" => merger::PrerequisiteFailure::default(),
+            policy => merger::PrerequisiteFailure::parse(policy).map_err(Status::invalid_argument)?,
+        };
+        let expires_at = match options.expires_at.trim() {
+            "" => None,
+            value => Some(merger::v2::parse_expires_at(value).map_err(Status::invalid_argument)?),
+        };
+        let machine_binding = merger::MachineBinding::parse(&options.machine_binding, &options.machine_fingerprints)
+            .map_err(Status::invalid_argument)?;
+        let labels: Labels = std::mem::take(&mut options.labels).into_iter().collect();
+        validate_labels(&labels).map_err(Status::invalid_argument)?;
+        let audit = merger::AuditLog::parse(&options.audit_path, &options.audit_url).map_err(Status::invalid_argument)?;
+        let crash_report = merger::CrashReport::parse(
+            options.crash_report_file,
+            &options.crash_report_url,
+            options.crash_stderr_lines,
+            options.crash_core_dump,
+        )
+        .map_err(Status::invalid_argument)?;
+        let output_log = merger::OutputLog::parse(
+            &options.output_log,
+            &options.output_log_dir,
+            options.output_log_max_size_mb,
+            options.output_log_max_files,
+        )
+        .map_err(Status::invalid_argument)?;
+        let readiness = merger::Readiness::parse(
+            &options.readiness_probe,
+            options.readiness_timeout_secs,
+            &options.readiness_on_timeout,
+        )
+        .map_err(Status::invalid_argument)?;
+        let data_files_size: u64 = install_files.iter().chain(&assets).map(|(_, data)| data.len() as u64).sum();
+        let installer = merger::Installer::parse(
+            &options.install_dir,
+            &options.install_name,
+            install_files.into_iter().map(|(path, data)| merger::DataFile::new(&path, &data)).collect(),
+            options.install_systemd_unit,
+            options.install_shortcut,
+        )
+        .map_err(Status::invalid_argument)?;
+        let assets: Vec<_> = assets.into_iter().map(|(path, data)| merger::DataFile::new(&path, &data)).collect();
+        merger::v2::validate_assets(&assets).map_err(Status::invalid_argument)?;
+        // The stub reports under the ID the merged binary is about to get
+        let binary_id = Uuid::new_v4().to_string();
+        let telemetry_url = match options.phone_home_url.trim() {
+            "" => None,
+            base => Some(merger::v2::telemetry_url(base, &binary_id).map_err(Status::invalid_argument)?),
+        };
+        let revocation =
+            merger::Revocation::parse(&options.revocation_url, &options.revocation_key, options.revocation_interval)
+                .map_err(Status::invalid_argument)?;
+        let kill_policy = match options.kill_policy.trim() {
+            "" => None,
+            json => Some(merger::KillPolicy::from_json(json).map_err(Status::invalid_argument)?),
+        };
+        let seccomp_profile = match options.seccomp_profile.trim() {
+            "" => None,
+            json => Some(merger::SeccompProfile::from_json(json).map_err(Status::invalid_argument)?),
+        };
+
+        let mut overload_jail = 0;
+        if options.jail_filesystem {
+            overload_jail |= JAIL_FILESYSTEM;
+        }
+        if options.jail_network {
+            overload_jail |= JAIL_NETWORK;
+        }
+        if options.jail_pid {
+            overload_jail |= JAIL_PID;
+        }
+        if options.jail_empty_root {
+            overload_jail |= JAIL_EMPTY_ROOT;
+        }
+        let stub_options = StubOptions {
+            grace_period: options.grace_period,
+            sync_mode: options.sync_mode,
+            prerequisite_runs: options.prerequisite_runs,
+            prerequisite_on_failure,
+            prerequisite_max_failures: options.prerequisite_max_failures,
+            prerequisite_retry_delay: options.prerequisite_retry_delay_secs,
+            network_failure_kill_count: options.network_failure_kill_count,
+            overload_jail,
+            overload_start_delay: options.overload_start_delay_secs,
+            overload_interval: options.overload_interval_secs,
+            health_check_interval: options.health_check_interval_secs,
+            kill_delay_ms: options.kill_delay_ms,
+            heartbeat_timeout: options.heartbeat_timeout,
+            monitor_warmup: options.monitor_warmup_secs,
+            arm_after_success: options.arm_after_success,
+            shutdown_grace: options.shutdown_grace_secs,
+            base_memory_limit_mb: options.base_memory_limit_mb,
+            base_cpu_percent: options.base_cpu_percent,
+            overload_memory_limit_mb: options.overload_memory_limit_mb,
+            overload_cpu_percent: options.overload_cpu_percent,
+            base_nofile_limit: options.base_nofile_limit,
+            overload_nofile_limit: options.overload_nofile_limit,
+            run_as_user,
+            drop_privileges: options.drop_privileges,
+            seccomp_profile,
+            hardening: options.hardening,
+            log_level,
+            log_target,
+            product_name,
+            exec_strategy,
+            base_working_dir,
+            overload_working_dir,
+            base_umask,
+            overload_umask,
+            base_priority,
+            overload_priority,
+            base_cpu_affinity,
+            overload_cpu_affinity,
+            single_instance,
+            control_socket: options.control_socket,
+            ipc_channel: options.ipc_channel,
+            kill_policy,
+            revocation,
+            expires_at,
+            expiry_clock_checks: options.expiry_clock_checks,
+            machine_binding,
+            audit,
+            crash_report,
+            output_log,
+            readiness,
+            telemetry_url,
+            embedded_labels: if options.embed_labels { labels.clone() } else { Labels::new() },
+            interpreter,
+            overload_library: options.overload_library,
+            installer,
+            assets,
+            stub_flavor,
+            pack: options.pack,
+            order: mode.footer_order(),
+            ..StubOptions::default()
+        };
+        merger::v2::validate_prerequisites(&stub_options).map_err(Status::invalid_argument)?;
+
+        let task_id = options.task_id;
+        let job = self
+            .jobs
+            .register(Some(task_id.as_str()).filter(|tid| !tid.is_empty()), caller.as_ref())
+            .map_err(|e| Status::already_exists(e.to_string()))?;
+        if !task_id.is_empty() {
+            let tracker = ProgressTracker::new(self.progress.clone(), task_id.clone());
+            let _ = tracker.update(ProgressStep::Started).await;
+        }
+
+        let base_info = BinaryInfo::detect(&base_data);
+        let interpreter = stub_options.interpreter.as_ref().map(|interpreter| &interpreter.data[..]);
+        if let Err(e) = merger::check_payload_platforms(&base_info, &overload_data, interpreter) {
+            let error_msg = e.to_string();
+            if !task_id.is_empty() {
+                let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(error_msg.clone()), None).await;
+            }
+            return Err(Status::invalid_argument(error_msg));
+        }
+        let mut original_sizes = (0, 0);
+        if options.strip_payloads {
+            original_sizes = (base_data.len() as u64, overload_data.len() as u64);
+            if let Some(stripped) = strip_payload("base", &base_data, self.workspaces.root(), job.token()).await {
+                base_data = stripped;
+            }
+            if let Some(stripped) = strip_payload("overload", &overload_data, self.workspaces.root(), job.token()).await {
+                overload_data = stripped;
+            }
+        }
+        let requested_strategy = Some(options.strategy.trim()).filter(|name| !name.is_empty());
+        let strategy = self
+            .strategies
+            .select(requested_strategy, &base_info, &self.stubs)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        if let Some(profile) = stub_options.seccomp_profile.as_ref().filter(|_| base_info.os == OperatingSystem::Linux) {
+            profile.compile(base_info.arch).map_err(Status::invalid_argument)?;
+        }
+        let facts = MergeFacts {
+            tenant: caller.as_ref().map(|caller| caller.name.as_str()),
+            endpoint: "grpc Merge",
+            base_info: &base_info,
+            strategy: Some(strategy.name()),
+            options: &stub_options,
+            signed: self.signer.is_enabled(),
+        };
+        if let Err(violation) = self.policy.check(&facts) {
+            let error_msg = format!("Policy: {}", violation);
+            if !task_id.is_empty() {
+                let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(error_msg.clone()), None).await;
+            }
+            return Err(Status::permission_denied(error_msg));
+        }
+
+        let mut scan_inputs: Vec<(&str, &[u8])> = vec![("base", &base_data[..]), ("overload", &overload_data[..])];
+        scan_inputs.extend(stub_options.interpreter.as_ref().map(|interpreter| ("interpreter", &interpreter.data[..])));
+        scan_inputs.extend(
+            stub_options
+                .installer
+                .iter()
+                .flat_map(|installer| &installer.files)
+                .chain(&stub_options.assets)
+                .map(|file| (file.path.as_str(), &file.data[..])),
+        );
+        let scans = match self.scanner.scan(&scan_inputs).await {
+            Ok(scans) => scans,
+            Err(e) => {
+                let error_msg = format!("Payload scan: {}", e);
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(error_msg.clone()), None).await;
+                }
+                return Err(match e {
+                    ScanError::Detected(_) => Status::failed_precondition(error_msg),
+                    ScanError::Failed { .. } => Status::unavailable(error_msg),
+                });
+            }
+        };
+
+        let _merge_permit = match self.scheduler.acquire_cancellable(job.token()).await {
+            Ok(permit) => permit,
+            Err(SchedulerError::Cancelled) => {
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_cancelled(&*self.progress, &task_id, Cancelled::Requested).await;
+                }
+                return Err(Status::cancelled(Cancelled::Requested.to_string()));
+            }
+            Err(e) => {
+                let error_msg = format!("Merge capacity exhausted: {}", e);
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(error_msg.clone()), None).await;
+                }
+                return Err(Status::resource_exhausted(error_msg));
+            }
+        };
+        let output_size = projected_output_size(&self.stubs, &base_data, &overload_data) + interpreter_data.len() as u64 + data_files_size;
+        let _space = match self.disk_guard.reserve(output_size, &self.binary_store) {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                let error_msg = format!("Not enough storage: {}", e);
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(error_msg.clone()), None).await;
+                }
+                return Err(Status::resource_exhausted(error_msg));
+            }
+        };
+        if let Some(timeout) = merge_timeout(config.merge_timeout, Some(options.timeout_secs)) {
+            job.arm_timeout(timeout);
+        }
+
+        let work_dir = self.workspaces.create().map_err(|e| Status::internal(e.to_string()))?;
+
+        let merge_result = strategy.merge(MergeJob {
+            base: (&base_data).into(),
+            overload: (&overload_data).into(),
+            work_path: work_dir.path(),
+            base_info: &base_info,
+            task_id: &task_id,
+            progress: &self.progress,
+            stubs: &self.stubs,
+            options: &stub_options,
+            cancel: job.token(),
+        }).await;
+
+        let stored = merge_result.and_then(|merged_path| {
+            let final_path = self.workspaces.root()
+                .join(format!("merged_{}.bin", binary_id));
+            std::fs::rename(&merged_path, &final_path)?;
+
+            let provenance = MergeProvenance {
+                binary_id: &binary_id,
+                endpoint: "grpc Merge",
+                base_data: &base_data,
+                overload_data: &overload_data,
+                labels: &labels,
+                started_on,
+            }
+            .statement_for(&final_path)
+            .inspect_err(|e| log::warn!("Failed to record provenance: {}", e))
+            .ok();
+            let signature = self
+                .signer
+                .minisign_file(&final_path, &binary_id)
+                .inspect_err(|e| log::warn!("Failed to sign merged binary: {}", e))
+                .ok()
+                .flatten();
+            let sha256 = sha256_file(&final_path)
+                .inspect_err(|e| log::warn!("Failed to hash merged binary: {}", e))
+                .ok();
+            let stub = (strategy.name() == StubAppend::NAME)
+                .then(|| self.stubs.select(&base_info, stub_options.stub_flavor).ok())
+                .flatten();
+            let details = MergeDetails::new(strategy.name(), &base_info, &overload_data, stub, &stub_options, signature.is_some());
+
+            let now = chrono::Utc::now();
+            Ok(StoredBinary {
+                id: binary_id,
+                path: final_path.to_string_lossy().to_string(),
+                size: std::fs::metadata(&final_path)?.len(),
+                created_at: now,
+                expires_at: now + chrono::Duration::seconds(config.binary_ttl),
+                report_path: None,
+                kind: BinaryKind::Merged,
+                sha256,
+                labels,
+                provenance,
+                signature,
+                owner: caller.as_ref().map(|caller| caller.name.clone()),
+                scans,
+                details: Some(details),
+                downloads: 0,
+                last_accessed: None,
+                stagers: Vec::new(),
+            })
+        });
+
+        drop(work_dir);
+
+        match stored {
+            Ok(stored) => {
+                let reply = MergeReply {
+                    binary_id: stored.id.clone(),
+                    size: stored.size,
+                    download_url: format!("/download/{}", stored.id),
+                    expires_at: stored.expires_at.timestamp(),
+                    signature_url: stored.signature.as_ref().map(|_| format!("/download/{}.sig", stored.id)).unwrap_or_default(),
+                    base_original_size: original_sizes.0,
+                    overload_original_size: original_sizes.1,
+                    sha256: stored.sha256.clone().unwrap_or_default(),
+                    base_platform: stored.details.as_ref().map(|details| details.base.clone()).unwrap_or_default(),
+                    overload_platform: stored.details.as_ref().map(|details| details.overload.clone()).unwrap_or_default(),
+                    strategy: strategy.name().to_string(),
+                    stub_sha256: stored.details.as_ref().and_then(|details| details.stub.as_ref()).map(|stub| stub.sha256.clone()).unwrap_or_default(),
+                    packed: stub_options.pack,
+                };
+                self.binary_store.insert(stored);
+                if stub_options.telemetry_url.is_some() {
+                    self.telemetry_store.register(&reply.binary_id);
+                }
+
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_complete(
+                        &*self.progress,
+                        &task_id,
+                        Some(reply.binary_id.clone()),
+                        None,
+                        Some(reply.size),
+                    ).await;
+                }
+
+                log::info!("✅ gRPC merge completed: {} ({} bytes)", reply.binary_id, reply.size);
+                Ok(Response::new(reply))
+            }
+            Err(e) if e.is::<Cancelled>() => {
+                let reason = e.downcast::<Cancelled>().unwrap_or(Cancelled::Requested);
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_cancelled(&*self.progress, &task_id, reason).await;
+                }
+                match reason {
+                    Cancelled::Requested => Err(Status::cancelled(reason.to_string())),
+                    Cancelled::TimedOut(_) => Err(Status::deadline_exceeded(reason.to_string())),
+                }
+            }
+            Err(e) => {
+                log::error!("❌ gRPC merge failed: {}", e);
+                if !task_id.is_empty() {
+                    let _ = ProgressTracker::publish_complete(&*self.progress, &task_id, None, Some(e.to_string()), None).await;
+                }
+                let message = format!("Merge failed: {}", e);
+                Err(match ErrorCode::from(&e) {
+                    ErrorCode::UnsupportedPlatform | ErrorCode::ArchMismatch | ErrorCode::InvalidRequest => {
+                        Status::invalid_argument(message)
+                    }
+                    ErrorCode::StubMissing | ErrorCode::StubMismatch => Status::failed_precondition(message),
+                    _ => Status::internal(message),
+                })
+            }
+        }
+    }
+
+    async fn get_progress(&self, request: Request<ProgressRequest>) -> Result<Response<ProgressReply>, Status> {
+        self.authenticate(&request)?;
+        let task_id = request.into_inner().task_id;
+
+        match ProgressTracker::get(&*self.progress, &task_id).await {
+            Ok(Some(progress)) => Ok(Response::new(ProgressReply {
+                percentage: progress.percentage as u32,
+                message: progress.message,
+                updated_at: progress.updated_at,
+            })),
+            Ok(None) => Err(Status::not_found(format!("No progress recorded for task {}", task_id))),
+            Err(e) => Err(Status::unavailable(format!("Progress store unavailable: {}", e))),
+        }
+    }
+
+    type DownloadStream = Pin<Box<dyn Stream<Item = Result<DownloadChunk, Status>> + Send>>;
+
+    async fn download(&self, request: Request<DownloadRequest>) -> Result<Response<Self::DownloadStream>, Status> {
+        let caller = self.authenticate(&request)?;
+        let binary_id = request.into_inner().binary_id;
+
+        let stored = self.binary_store.get(&binary_id).filter(|binary| caller.as_ref().is_none_or(|caller| caller.can_read(binary)));
+        let Some(binary) = stored else {
+            return Err(Status::not_found(format!("Binary not found: {}", binary_id)));
+        };
+        if chrono::Utc::now() > binary.expires_at {
+            return Err(Status::failed_precondition("Binary has expired"));
+        }
+
+        let file = tokio::fs::File::open(&binary.path)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to read binary: {}", e)))?;
+
+        log::info!("📥 gRPC download: {} ({} bytes)", binary_id, binary.size);
+        self.binary_store.record_download(&binary_id, self.config.load().access_renewal(binary.owner.as_deref()));
+
+        let chunks = futures_util::stream::unfold(Some(file), |file| async move {
+            let mut file = file?;
+            let mut buffer = vec![0u8; DOWNLOAD_CHUNK_SIZE];
+            match file.read(&mut buffer).await {
+                Ok(0) => None,
+                Ok(read) => {
+                    buffer.truncate(read);
+                    Some((Ok(DownloadChunk { data: buffer }), Some(file)))
+                }
+                // Report the error once, then end the stream
+                Err(e) => Some((Err(Status::internal(e.to_string())), None)),
+            }
+        });
+
+        Ok(Response::new(Box::pin(chunks)))
+    }
+}
@@ -1,8 +1,17 @@
 // Library exports for testing and external use
+#[cfg(feature = "server")]
 pub mod api;
+#[cfg(feature = "server")]
 pub mod config;
 pub mod core;
+pub mod embed;
 pub mod models;
+#[cfg(feature = "server")]
+pub mod telemetry;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 
 #[cfg(test)]
 pub mod test_utils;
+
+pub use embed::{MergeRequest, MergedArtifact, MergedOutput, Weaver, WeaverBuilder};
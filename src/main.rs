@@ -3,17 +3,22 @@ mod api;
 mod core;
 mod models;
 mod config;
+mod telemetry;
+#[cfg(feature = "grpc")]
+mod grpc;
 
 use actix_web::{web, App, HttpServer, middleware};
+use actix_web::middleware::from_fn;
 use actix_multipart::form::MultipartFormConfig;
-use std::sync::Mutex;
-use std::collections::HashMap;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     
-    let config = config::Config::from_env();
+    let config = config::Config::load().map_err(|e| std::io::Error::other(format!("Failed to load configuration: {}", e)))?;
+    
+    let tracer_provider = telemetry::init(&config)
+        .map_err(|e| std::io::Error::other(format!("Failed to initialize tracing: {}", e)))?;
     
     log::info!("🕸️  Starting Weaver Binary Weaving Service");
     log::info!("📍 Listening on {}:{}", config.host, config.port);
@@ -21,21 +26,223 @@ async fn main() -> std::io::Result<()> {
     
     let bind_addr = (config.host.clone(), config.port);
     
+    // Progress transport (Redis, in-memory or NATS)
+    let progress_sink = core::progress::sink_from_config(&config)
+        .await
+        .map_err(|e| std::io::Error::other(format!("Failed to initialize progress backend: {}", e)))?;
+    log::info!("📡 Progress backend: {}", config.progress_backend);
+    let progress_data = web::Data::from(progress_sink);
+    
     // Shared state for storing merged binaries
-    let binary_store = web::Data::new(Mutex::new(HashMap::<String, models::StoredBinary>::new()));
+    let binary_store = web::Data::new(core::store::BinaryStore::new());
+    let telemetry_store = web::Data::new(core::store::TelemetryStore::new());
+    let upload_store = web::Data::new(core::store::UploadStore::new());
+    let merge_cache = web::Data::new(core::merger::MergeCache::new(config.merge_cache_ttl));
+    let scheduler = web::Data::new(core::scheduler::MergeScheduler::from_config(&config));
+    let disk_guard = web::Data::new(core::diskguard::DiskGuard::from_config(&config));
+    let jobs = web::Data::new(core::jobs::JobRegistry::new());
+    
+    // Reclaim work dirs of merges that crashed, now and on every sweep
+    let workspaces = web::Data::new(core::workspace::WorkspaceManager::from_config(&config));
+    let reclaimed = {
+        let workspaces = workspaces.clone();
+        tokio::task::spawn_blocking(move || workspaces.reclaim_stale()).await.unwrap_or(0)
+    };
+    if reclaimed > 0 {
+        log::info!("🧹 Reclaimed {} stale work dirs from a previous run", reclaimed);
+    }
+    if config.workspace_sweep_interval > 0 {
+        let workspaces = workspaces.clone();
+        let interval = std::time::Duration::from_secs(config.workspace_sweep_interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let workspaces = workspaces.clone();
+                let _ = tokio::task::spawn_blocking(move || workspaces.reclaim_stale()).await;
+            }
+        });
+    }
+    
+    // Key for signing provenance and merged binaries; both unsigned without one
+    let signer = core::signing::Signer::load(config.signing_key_path.as_deref())
+        .map_err(|e| std::io::Error::other(format!("Failed to load signing key: {:#}", e)))?;
+    if let Some(key_id) = signer.key_id() {
+        log::info!("🔏 Signing provenance and merged binaries with key {}", key_id);
+    }
+    let signer = web::Data::new(signer);
+
+    // API keys from WEAVER_API_KEYS; without any the API stays open
+    let api_keys = core::auth::ApiKeys::from_config(&config)
+        .map_err(|e| std::io::Error::other(format!("Invalid WEAVER_API_KEYS: {:#}", e)))?;
+    if api_keys.is_enabled() {
+        log::info!("🔑 Authentication on with {} API keys", api_keys.count());
+    }
+    let api_keys = web::Data::new(api_keys);
+
+    // Optional clamd/YARA scan of every merge input
+    let scanner = core::scan::PayloadScanner::from_config(&config);
+    if scanner.is_enabled() {
+        log::info!("🦠 Scanning merge inputs with {}", scanner.scanners().join(" and "));
+    }
+    let scanner = web::Data::new(scanner);
+
+    // Merge policy from WEAVER_POLICY_FILE; without one every merge is allowed
+    let policy = core::policy::Policy::load(config.policy_file.as_deref())
+        .map_err(|e| std::io::Error::other(format!("Invalid merge policy: {}", e)))?;
+    if policy.count() > 0 {
+        log::info!("📜 Checking merges against {} policy rules", policy.count());
+    }
+    let policy = web::Data::new(policy);
+    
+    // Loader stubs from WEAVER_STUB_DIR, falling back to the embedded ones
+    let stub_registry = web::Data::new(core::merger::StubRegistry::load(
+        config.stub_dir.as_ref().map(std::path::PathBuf::from),
+    ));
+    log::info!("🧩 {} of {} stubs available", stub_registry.available_count(), core::merger::stub_slots().count());
+    if config.stub_reload_interval > 0 {
+        // Runs without a directory too, as a reload may configure one later
+        let registry = stub_registry.clone();
+        let interval = std::time::Duration::from_secs(config.stub_reload_interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if registry.dir().is_none() {
+                    continue;
+                }
+                let registry = registry.clone();
+                let _ = tokio::task::spawn_blocking(move || registry.reload()).await;
+            }
+        });
+    }
+    let strategies = web::Data::new(core::merger::MergeStrategies::default());
     let max_upload_size = config.max_file_size;
-    let config_data = web::Data::new(config);
+    let config_data = web::Data::new(config::SharedConfig::from_pointee(config.clone()));
+
+    // Delete expired binaries, except those retention rules keep; rules apply on reload
+    if config.cleanup_interval > 0 {
+        let binary_store = binary_store.clone();
+        let config_data = config_data.clone();
+        let interval = std::time::Duration::from_secs(config.cleanup_interval);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let binary_store = binary_store.clone();
+                let config = config_data.load_full();
+                let removed = tokio::task::spawn_blocking(move || core::retention::sweep(&binary_store, &config)).await.unwrap_or(0);
+                if removed > 0 {
+                    log::info!("🧹 Removed {} expired binaries", removed);
+                }
+            }
+        });
+    }
+
+    // Limits, TTLs, the stub directory and the policy can be reloaded without a restart
+    let reloader = web::Data::new(core::reload::ConfigReloader::new(
+        config_data.clone().into_inner(),
+        scheduler.clone().into_inner(),
+        disk_guard.clone().into_inner(),
+        merge_cache.clone().into_inner(),
+        stub_registry.clone().into_inner(),
+        policy.clone().into_inner(),
+    ));
+    #[cfg(unix)]
+    {
+        let reloader = reloader.clone();
+        let mut hangups = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangups.recv().await.is_some() {
+                let reloader = reloader.clone();
+                match tokio::task::spawn_blocking(move || reloader.reload()).await {
+                    Ok(Ok(summary)) => log::info!(
+                        "🔄 Reloaded configuration on SIGHUP: changed {:?}, restart required for {:?}",
+                        summary.changed,
+                        summary.restart_required
+                    ),
+                    Ok(Err(e)) => log::error!("❌ Kept the current configuration: {}", e),
+                    Err(e) => log::error!("❌ Configuration reload failed: {}", e),
+                }
+            }
+        });
+    }
+    
+    if config.selftest_on_startup {
+        // Only logged: a failing stub shouldn't stop merges for other platforms
+        core::selftest::run(&workspaces, &stub_registry, &progress_data.clone().into_inner()).await;
+    }
+    
+    // Optional gRPC front-end on its own port, sharing the store and progress backend
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_port) = config.grpc_port {
+        let addr = format!("{}:{}", config.host, grpc_port)
+            .parse()
+            .map_err(|e| std::io::Error::other(format!("Invalid gRPC address: {}", e)))?;
+        let service = grpc::WeaverService::new(
+            config_data.clone().into_inner(),
+            binary_store.clone().into_inner(),
+            telemetry_store.clone().into_inner(),
+            progress_data.clone().into_inner(),
+            stub_registry.clone().into_inner(),
+            strategies.clone().into_inner(),
+            signer.clone().into_inner(),
+            scheduler.clone().into_inner(),
+            disk_guard.clone().into_inner(),
+            jobs.clone().into_inner(),
+            workspaces.clone().into_inner(),
+            api_keys.clone().into_inner(),
+            scanner.clone().into_inner(),
+            policy.clone().into_inner(),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(addr, service).await {
+                log::error!("❌ gRPC server stopped: {}", e);
+            }
+        });
+    }
+    #[cfg(not(feature = "grpc"))]
+    if config.grpc_port.is_some() {
+        log::warn!("WEAVER_GRPC_PORT is set but this build has no gRPC support (enable the `grpc` feature)");
+    }
     
     HttpServer::new(move || {
         App::new()
             .app_data(MultipartFormConfig::default().total_limit(max_upload_size))
             .app_data(binary_store.clone())
+            .app_data(telemetry_store.clone())
+            .app_data(upload_store.clone())
             .app_data(config_data.clone())
+            .app_data(progress_data.clone())
+            .app_data(merge_cache.clone())
+            .app_data(stub_registry.clone())
+            .app_data(strategies.clone())
+            .app_data(signer.clone())
+            .app_data(scheduler.clone())
+            .app_data(disk_guard.clone())
+            .app_data(jobs.clone())
+            .app_data(workspaces.clone())
+            .app_data(api_keys.clone())
+            .app_data(reloader.clone())
+            .app_data(scanner.clone())
+            .app_data(policy.clone())
+            .wrap(from_fn(api::auth::authenticate))
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
             .configure(api::configure_routes)
     })
     .bind(bind_addr)?
     .run()
-    .await
+    .await?;
+    
+    // Flush spans still queued in the batch exporter
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            log::warn!("Failed to flush traces: {}", e);
+        }
+    }
+    
+    Ok(())
 }
@@ -1,6 +1,71 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::path::PathBuf;
 use goblin::Object;
+use utoipa::ToSchema;
+
+use super::response::MergeDetails;
+use crate::core::scan::ScanResult;
+
+/// Where a stored binary came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryKind {
+    /// Output of a merge
+    Merged,
+    /// Uploaded via `POST /binaries` for reuse as a merge input
+    Staged,
+}
+
+/// Free-form key/value metadata attached to a merged binary
+pub type Labels = BTreeMap<String, String>;
+
+/// Most labels one binary can carry
+pub const MAX_LABELS: usize = 64;
+/// Longest label key
+pub const MAX_LABEL_KEY_LEN: usize = 64;
+/// Longest label value
+pub const MAX_LABEL_VALUE_LEN: usize = 256;
+
+/// Parse the `labels` field of a merge request: a JSON object of strings
+pub fn parse_labels(json: &str) -> Result<Labels, String> {
+    let labels: Labels = serde_json::from_str(json)
+        .map_err(|e| format!("Labels must be a JSON object with string values: {}", e))?;
+    validate_labels(&labels)?;
+    Ok(labels)
+}
+
+/// Check label count and key/value sizes
+pub fn validate_labels(labels: &Labels) -> Result<(), String> {
+    if labels.len() > MAX_LABELS {
+        return Err(format!("At most {} labels are allowed", MAX_LABELS));
+    }
+    for (key, value) in labels {
+        if key.is_empty() || key.len() > MAX_LABEL_KEY_LEN {
+            return Err(format!("Label keys must be 1 to {} bytes, got {:?}", MAX_LABEL_KEY_LEN, key));
+        }
+        if value.len() > MAX_LABEL_VALUE_LEN {
+            return Err(format!("Label {:?} is longer than {} bytes", key, MAX_LABEL_VALUE_LEN));
+        }
+    }
+    Ok(())
+}
+
+/// Health event a merged binary built with `phone_home_url` reported
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TelemetryEvent {
+    pub received_at: DateTime<Utc>,
+    /// Address the report came from, as seen by Weaver
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_addr: Option<String>,
+    /// Event name, such as `health_kill` or `verification_failed`
+    pub event: String,
+    /// The stub's record as sent: time, pid, user, host and event fields
+    #[schema(value_type = Object)]
+    pub record: serde_json::Value,
+}
 
 #[derive(Debug, Clone)]
 pub struct StoredBinary {
@@ -9,6 +74,57 @@ pub struct StoredBinary {
     pub size: u64,
     pub created_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    pub report_path: Option<String>,
+    pub kind: BinaryKind,
+    /// Hex SHA-256 of the contents; staged binaries use it as their ID
+    pub sha256: Option<String>,
+    /// Labels given with the merge request (empty for staged binaries)
+    pub labels: Labels,
+    /// In-toto provenance statement recorded at merge time
+    pub provenance: Option<serde_json::Value>,
+    /// minisign signature of the artifact, made with `WEAVER_SIGNING_KEY`
+    pub signature: Option<String>,
+    /// Name of the API key that merged it; staged binaries and merges made
+    /// without authentication have none
+    pub owner: Option<String>,
+    /// Verdicts of the payload scanners on the merge inputs; empty when scanning is off
+    pub scans: Vec<ScanResult>,
+    /// Inputs, stub and options of a merge; staged binaries have none
+    pub details: Option<MergeDetails>,
+    /// Times it was downloaded, over HTTP or gRPC
+    pub downloads: u64,
+    /// When it was last downloaded
+    pub last_accessed: Option<DateTime<Utc>>,
+    /// Names of the API keys that uploaded a staged binary; only they (and
+    /// admins) may read it. Empty for merged binaries
+    pub stagers: Vec<String>,
+}
+
+/// A resumable upload in progress, written to `path` chunk by chunk
+#[derive(Debug, Clone)]
+pub struct UploadSession {
+    pub id: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Bytes received so far; the next chunk must start here
+    pub offset: u64,
+    pub sha256: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// A chunk is being written; others are refused until it finishes
+    pub busy: bool,
+}
+
+impl StoredBinary {
+    /// Delete the artifact and its report from disk
+    pub fn remove_files(&self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to delete {}: {}", self.path, e);
+        }
+        if let Some(ref report) = self.report_path {
+            let _ = std::fs::remove_file(report);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
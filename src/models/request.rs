@@ -1,14 +1,94 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum MergeMode {
     Before,  // Overload runs before base
     After,   // Overload runs after base
 }
 
+impl MergeMode {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "before" => Ok(MergeMode::Before),
+            "after" => Ok(MergeMode::After),
+            other => Err(format!("Unknown mode {:?}, expected before or after", other)),
+        }
+    }
+
+    /// `weaver_abi::footer::ORDER_*` value the stub uses to sequence the payloads
+    pub fn footer_order(self) -> u8 {
+        match self {
+            MergeMode::Before => weaver_abi::footer::ORDER_OVERLOAD_FIRST,
+            MergeMode::After => weaver_abi::footer::ORDER_BASE_FIRST,
+        }
+    }
+
+    /// The mode a footer `order` sequences the payloads in
+    pub fn from_footer_order(order: u8) -> Self {
+        if order == weaver_abi::footer::ORDER_BASE_FIRST {
+            MergeMode::After
+        } else {
+            MergeMode::Before
+        }
+    }
+}
+
 impl Default for MergeMode {
     fn default() -> Self {
         MergeMode::Before
     }
 }
+
+/// Pagination for `GET /binaries` and `GET /telemetry/{binary_id}`
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListBinariesQuery {
+    /// Entries to skip (default 0)
+    pub offset: Option<usize>,
+    /// Page size (default 50, max 500)
+    pub limit: Option<usize>,
+}
+
+/// Query of `PUT /admin/stubs/{os}/{arch}`
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StubUploadQuery {
+    /// `glibc` (default), `musl` or `static`; only Linux stubs come in other flavors
+    pub flavor: Option<String>,
+}
+
+/// Body of `POST /binaries/{id}/extend`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExtendRequest {
+    /// Seconds to add to the expiry (default `WEAVER_BINARY_TTL`)
+    pub seconds: Option<i64>,
+}
+
+/// Body of `POST /estimate`
+///
+/// Each payload is given by size or by staged ID; the ID wins when both are.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EstimateRequest {
+    /// Base's OS (`linux`, `windows`, `macos`); not needed with `base_id`
+    pub os: Option<String>,
+    /// Base's architecture (`x86_64`, `aarch64`, ...); not needed with `base_id`
+    pub arch: Option<String>,
+    /// Linux stub build to size for: `glibc`, `musl` or `static`
+    pub stub_flavor: Option<String>,
+    pub base_size: Option<u64>,
+    pub overload_size: Option<u64>,
+    pub base_id: Option<String>,
+    pub overload_id: Option<String>,
+    /// Bytes of interpreter, install files and assets bundled along
+    #[serde(default)]
+    pub extra_size: u64,
+}
+
+/// Body of `POST /uploads`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateUploadRequest {
+    /// Total size of the binary in bytes
+    pub size: u64,
+    /// Expected hex SHA-256, checked when the upload is completed
+    pub sha256: Option<String>,
+}
@@ -1,7 +1,18 @@
 use serde::Serialize;
 use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize)]
+use super::binary::{BinaryKind, Labels, StoredBinary, TelemetryEvent, UploadSession};
+use super::request::MergeMode;
+use crate::core::binary::{is_script, BinaryInfo};
+use crate::core::diskguard::SpaceError;
+use crate::core::jobs::Cancelled;
+use crate::core::merger::{MergeError, MergePlan, Stub, StubOptions, StubSource};
+use crate::core::scan::ScanResult;
+use crate::core::scheduler::SchedulerStatus;
+use crate::core::selftest::SelfTestReport;
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct MergeResponse {
     pub success: bool,
     pub binary_id: String,
@@ -9,19 +20,634 @@ pub struct MergeResponse {
     pub download_url: String,
     pub expires_at: DateTime<Utc>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_url: Option<String>,
+    /// minisign signature of the download, when the server has a signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature_url: Option<String>,
+    /// Digest reference of the artifact pushed to `oci_push`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oci_reference: Option<String>,
+    /// Payload sizes before and after `strip_payloads`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stripped: Option<StrippedPayloads>,
+    /// Hex SHA-256 of the download
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// How the merged binary was built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<MergeDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// Inputs, stub and options a merged binary was built from
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MergeDetails {
+    pub strategy: String,
+    /// Detected platform and ABI of the base
+    pub base: String,
+    /// Detected platform and ABI of the overload, or `script`
+    pub overload: String,
+    /// Loader stub in front of the payloads; absent for strategies without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stub: Option<PlannedStub>,
+    pub grace_period: u32,
+    pub sync_mode: bool,
+    /// Whether the overload runs before or after base
+    pub order: MergeMode,
+    /// Payloads (or the compiled loader) were compressed with `pack`
+    pub packed: bool,
+    /// A minisign signature was made, served at `signature_url`
+    pub signed: bool,
+}
+
+impl MergeDetails {
+    pub fn new(strategy: &str, base_info: &BinaryInfo, overload: &[u8], stub: Option<Stub>, options: &StubOptions, signed: bool) -> Self {
+        Self {
+            strategy: strategy.to_string(),
+            base: base_info.description(),
+            overload: if is_script(overload) { "script".to_string() } else { BinaryInfo::detect(overload).description() },
+            stub: stub.map(PlannedStub::from),
+            grace_period: options.grace_period,
+            sync_mode: options.sync_mode,
+            order: MergeMode::from_footer_order(options.order),
+            packed: options.pack,
+            signed,
+        }
+    }
+}
+
+/// Sizes of both payloads around `strip_payloads`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StrippedPayloads {
+    pub base: StrippedSize,
+    pub overload: StrippedSize,
+}
+
+/// A payload's size as uploaded and as merged; equal when nothing was stripped
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StrippedSize {
+    pub original_size: u64,
+    pub stripped_size: u64,
+}
+
+/// What a `dry_run` merge would do; nothing is assembled or stored
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MergePlanResponse {
+    pub strategy: String,
+    /// Detected platform and ABI of the base
+    pub base: String,
+    /// Detected platform and ABI of the overload, or `script`
+    pub overload: String,
+    /// Loader stub the merge would use; absent for strategies without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stub: Option<PlannedStub>,
+    /// Predicted size of the merged binary in bytes
+    pub output_size: u64,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PlannedStub {
+    pub sha256: String,
+    pub size: usize,
+    pub abi: String,
+    /// `embedded`, or the path of the stub file it was loaded from
+    pub source: String,
+}
+
+impl MergePlanResponse {
+    pub fn new(strategy: &str, base: String, overload: String, plan: MergePlan) -> Self {
+        Self {
+            strategy: strategy.to_string(),
+            base,
+            overload,
+            stub: plan.stub.map(PlannedStub::from),
+            output_size: plan.output_size,
+            warnings: plan.warnings,
+        }
+    }
+}
+
+impl From<Stub> for PlannedStub {
+    fn from(stub: Stub) -> Self {
+        Self {
+            sha256: stub.sha256,
+            size: stub.bytes.len(),
+            abi: stub.abi.to_string(),
+            source: match stub.source {
+                StubSource::Embedded => "embedded".to_string(),
+                StubSource::File { path, .. } => path.display().to_string(),
+            },
+        }
+    }
+}
+
+/// Predicted outcome of a merge, from `POST /estimate`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EstimateResponse {
+    /// Platform the estimate is for, with base's ABI when it was staged
+    pub platform: String,
+    pub stub_size: u64,
+    /// Predicted size of the merged binary in bytes, before any bundled options
+    pub output_size: u64,
+    /// The merge passes the size limit, storage quota and free space checks as of now
+    pub fits: bool,
+    /// Every limit the merge would run into
+    pub problems: Vec<String>,
+    /// `WEAVER_MAX_SIZE`, per input
+    pub max_file_size: u64,
+    /// Bytes stored plus reserved by running merges, and `WEAVER_STORAGE_QUOTA` (0 for none)
+    pub storage_used: u64,
+    pub storage_quota: u64,
+    /// Predicted download size with compression; only when both payloads are staged
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compressed_size: Option<u64>,
+    /// `output_size` minus `compressed_size`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_savings: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
     pub uptime: String,
+    /// All checks passed and at least one stub is usable
+    pub ready: bool,
+    pub checks: HealthChecks,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthChecks {
+    pub stubs: Vec<StubStatus>,
+    /// Informational; only the `compile` strategy needs a C compiler
+    pub compilers: Vec<CompilerStatus>,
+    pub temp_dir: CheckResult,
+    pub progress_backend: CheckResult,
+    pub disk: DiskStatus,
+    /// Informational; a saturated queue doesn't make the service unready
+    pub merges: MergeQueueStatus,
+}
+
+/// Outcome of a single readiness check
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StubStatus {
+    pub os: String,
+    pub arch: String,
+    /// `glibc`, `musl` or `static`; Windows and macOS stubs are always `glibc`
+    pub flavor: String,
+    /// Loaded and valid
+    pub available: bool,
+    pub size: usize,
+}
+
+/// C compiler the `compile` strategy would use for a Linux architecture
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompilerStatus {
+    pub arch: String,
+    pub available: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Merge scheduler load (`WEAVER_MAX_CONCURRENT_MERGES`, `WEAVER_MAX_QUEUED_MERGES`)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MergeQueueStatus {
+    pub running: usize,
+    /// Merges waiting for a slot
+    pub queued: usize,
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+}
+
+impl From<SchedulerStatus> for MergeQueueStatus {
+    fn from(status: SchedulerStatus) -> Self {
+        Self {
+            running: status.running,
+            queued: status.queued,
+            max_concurrent: status.max_concurrent,
+            max_queued: status.max_queued,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiskStatus {
+    pub ok: bool,
+    /// Free bytes on the temp dir's filesystem (absent if it couldn't be read)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub free_bytes: Option<u64>,
+    /// Space needed for one maximum-size merge
+    pub required_bytes: u64,
+    /// Stored binaries plus the projected output of running merges
+    pub stored_bytes: u64,
+    /// `WEAVER_STORAGE_QUOTA`, absent when unlimited
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SelfTestResponse {
+    pub passed: bool,
+    pub platform: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stub_sha256: Option<String>,
+    /// Stdout of the merged binary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl From<&SelfTestReport> for SelfTestResponse {
+    fn from(report: &SelfTestReport) -> Self {
+        Self {
+            passed: report.passed,
+            platform: report.platform.clone(),
+            stub_sha256: report.stub_sha256.clone(),
+            output: report.output.clone(),
+            error: report.error.clone(),
+            duration_ms: report.duration.as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StubUploadResponse {
+    pub os: String,
+    pub arch: String,
+    pub flavor: String,
+    pub size: usize,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReloadResponse {
+    /// Settings now in effect with new values
+    pub changed: Vec<String>,
+    /// Settings that changed but keep their old values until a restart
+    pub restart_required: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CapabilitiesResponse {
+    pub platforms: Vec<PlatformCapabilities>,
+    /// minisign public key merged binaries are signed with (`minisign -Vm <file> -P <key>`),
+    /// absent when the server has no signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_public_key: Option<String>,
+    /// `upx --version` of the server's upx, which `pack` compresses stubs and compiled loaders with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upx_version: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlatformCapabilities {
+    pub os: String,
+    pub arch: String,
+    /// A valid stub for this platform is loaded, so merges targeting it can succeed
+    pub available: bool,
+    /// Merge strategies usable for this platform, in the order a request without `strategy` tries them
+    pub strategies: Vec<String>,
+    /// Stub builds loaded for this platform, for `stub_flavor` (`glibc`, `musl`, `static`)
+    pub stub_flavors: Vec<String>,
+    pub features: PlatformFeatures,
+}
+
+/// Loader features honored on a platform
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlatformFeatures {
+    pub health_monitoring: bool,
+    pub sync_mode: bool,
+    pub network_failure_kill: bool,
+    pub overload_jail: bool,
+    pub overload_schedule: bool,
+    pub resource_limits: bool,
+    pub drop_privileges: bool,
+    pub seccomp: bool,
+    pub signing: bool,
+    /// `pack` compresses the payloads
+    pub pack: bool,
+    /// `pack` also compresses the stub with upx
+    pub upx: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
+    /// Stable, machine-readable cause; `error` and `details` are for humans and may change
+    pub code: ErrorCode,
     pub error: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+    /// What the client can do about it
+    #[schema(value_type = String)]
+    pub hint: &'static str,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, error: impl Into<String>, details: Option<String>) -> Self {
+        Self { code, error: error.into(), details, hint: code.hint() }
+    }
+}
+
+/// Cause of an `ErrorResponse`
+///
+/// Codes are part of the API: add new ones, but never rename or reuse them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// A form field or header is missing or malformed
+    InvalidRequest,
+    /// A binary or chunk is over the size limit
+    PayloadTooLarge,
+    /// A merge input isn't a complete ELF, PE or Mach-O executable, or a runnable `#!` script
+    InvalidExecutable,
+    /// Content doesn't match the checksum sent with it
+    ChecksumMismatch,
+    /// No loader stub exists for the base binary's OS/architecture
+    UnsupportedPlatform,
+    /// Base and overload (or the bundled interpreter) target different platforms
+    ArchMismatch,
+    /// The platform is supported but this server has no stub for it
+    StubMissing,
+    /// The server's stub for the platform is not a runnable binary for it
+    StubMismatch,
+    NotFound,
+    /// The binary existed but its TTL ran out
+    Expired,
+    Unauthorized,
+    Forbidden,
+    /// The feature needs server configuration that is missing
+    NotConfigured,
+    /// A concurrent request changed the same resource
+    Conflict,
+    /// Another merge is running with this task_id
+    TaskAlreadyRunning,
+    /// Cancelled with `DELETE /jobs/{task_id}`
+    Cancelled,
+    /// Ran longer than its merge timeout
+    TimedOut,
+    /// No merge slot and no room in the queue
+    CapacityExhausted,
+    /// Not enough disk space or storage quota
+    InsufficientStorage,
+    /// The payload scanner matched a merge input
+    ScanMatched,
+    /// The merge options break a rule in the server's merge policy
+    PolicyViolation,
+    /// A registry or URL the request pointed at failed
+    UpstreamFailed,
+    /// A backend the server depends on is unreachable
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorCode::InvalidRequest => "Fix the field named in the error and resend the request",
+            ErrorCode::PayloadTooLarge => "Send a smaller binary, or raise WEAVER_MAX_SIZE on the server",
+            ErrorCode::InvalidExecutable => "Upload the compiled executable itself (or a #! script as the overload), not an archive or library",
+            ErrorCode::ChecksumMismatch => "Recompute the sha256 of the exact bytes sent, or resend them",
+            ErrorCode::UnsupportedPlatform => "See GET /capabilities for the platforms this server can merge",
+            ErrorCode::ArchMismatch => "Build base, overload and any bundled interpreter for the same OS, architecture and ABI",
+            ErrorCode::StubMissing => "Install the platform's stub in WEAVER_STUB_DIR, or use a build with embedded stubs",
+            ErrorCode::StubMismatch => "Replace the platform's stub in WEAVER_STUB_DIR with one built for it (PUT /admin/stubs)",
+            ErrorCode::NotFound => "Check the ID; list what exists with GET /binaries",
+            ErrorCode::Expired => "Merge or upload again, or extend the TTL before it runs out",
+            ErrorCode::Unauthorized => "Send a valid bearer token",
+            ErrorCode::Forbidden => "This endpoint is disabled on this server",
+            ErrorCode::NotConfigured => "Set the setting named in the error on the server and restart it",
+            ErrorCode::Conflict => "Re-read the current state and retry",
+            ErrorCode::TaskAlreadyRunning => "Wait for the running merge or use another task_id",
+            ErrorCode::Cancelled => "Resubmit the merge if it is still needed",
+            ErrorCode::TimedOut => "Raise timeout_secs or WEAVER_MERGE_TIMEOUT, or merge smaller binaries",
+            ErrorCode::CapacityExhausted => "Retry after the Retry-After delay",
+            ErrorCode::InsufficientStorage => "Delete unused binaries or retry later",
+            ErrorCode::ScanMatched => "Merge inputs the scanner doesn't flag, or ask the operator to review the matched rule",
+            ErrorCode::PolicyViolation => "Change the merge options to satisfy the named policy rule",
+            ErrorCode::UpstreamFailed => "Check that the URL or registry is reachable and the credentials are valid",
+            ErrorCode::Unavailable => "Retry later; the server's backend is unreachable",
+            ErrorCode::Internal => "Retry; if it keeps failing, report it with the details",
+        }
+    }
+}
+
+impl From<&MergeError> for ErrorCode {
+    fn from(error: &MergeError) -> Self {
+        match error {
+            MergeError::UnsupportedPlatform(_) => ErrorCode::UnsupportedPlatform,
+            MergeError::ArchMismatch { .. } | MergeError::AbiMismatch { .. } | MergeError::InterpreterMismatch { .. } => {
+                ErrorCode::ArchMismatch
+            }
+            MergeError::StubAbiMismatch { .. } => ErrorCode::UnsupportedPlatform,
+            MergeError::StubMissing { .. } => ErrorCode::StubMissing,
+            MergeError::StubMismatch { .. } => ErrorCode::StubMismatch,
+            MergeError::UnknownStrategy { .. } | MergeError::UnsupportedByStrategy { .. } => ErrorCode::InvalidRequest,
+        }
+    }
+}
+
+/// Classify a failed merge by the typed errors the core returns
+impl From<&anyhow::Error> for ErrorCode {
+    fn from(error: &anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<MergeError>() {
+            return ErrorCode::from(error);
+        }
+        if let Some(reason) = error.downcast_ref::<Cancelled>() {
+            return match reason {
+                Cancelled::Requested => ErrorCode::Cancelled,
+                Cancelled::TimedOut(_) => ErrorCode::TimedOut,
+            };
+        }
+        if error.is::<SpaceError>() {
+            return ErrorCode::InsufficientStorage;
+        }
+        ErrorCode::Internal
+    }
+}
+
+impl From<&StoredBinary> for MergeResponse {
+    fn from(binary: &StoredBinary) -> Self {
+        Self {
+            success: true,
+            binary_id: binary.id.clone(),
+            size: binary.size,
+            download_url: format!("/download/{}", binary.id),
+            expires_at: binary.expires_at,
+            report_url: binary.report_path.as_ref().map(|_| format!("/download/{}/report.html", binary.id)),
+            signature_url: binary.signature.as_ref().map(|_| format!("/download/{}.sig", binary.id)),
+            oci_reference: None,
+            stripped: None,
+            sha256: binary.sha256.clone(),
+            details: binary.details.clone(),
+            error: None,
+        }
+    }
+}
+
+/// Metadata of a stored binary (merged artifact or staged upload)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BinaryMetadata {
+    pub id: String,
+    pub kind: BinaryKind,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub download_url: String,
+    /// Labels given with the merge request
+    #[serde(skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature_url: Option<String>,
+    /// API key that merged it, when authentication is on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Payload scanner verdicts on the merge inputs, when scanning is on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scans: Vec<ScanResult>,
+    /// How a merged binary was built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<MergeDetails>,
+    /// Times it was downloaded
+    pub downloads: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_accessed: Option<DateTime<Utc>>,
+}
+
+impl From<&StoredBinary> for BinaryMetadata {
+    fn from(binary: &StoredBinary) -> Self {
+        Self {
+            id: binary.id.clone(),
+            kind: binary.kind,
+            size: binary.size,
+            sha256: binary.sha256.clone(),
+            created_at: binary.created_at,
+            expires_at: binary.expires_at,
+            download_url: format!("/download/{}", binary.id),
+            labels: binary.labels.clone(),
+            provenance_url: binary.provenance.as_ref().map(|_| format!("/binaries/{}/provenance", binary.id)),
+            signature_url: binary.signature.as_ref().map(|_| format!("/download/{}.sig", binary.id)),
+            owner: binary.owner.clone(),
+            scans: binary.scans.clone(),
+            details: binary.details.clone(),
+            downloads: binary.downloads,
+            last_accessed: binary.last_accessed,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StageResponse {
+    #[serde(flatten)]
+    pub binary: BinaryMetadata,
+    /// The caller had already staged the same content; its expiry was refreshed
+    pub deduplicated: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BinaryListResponse {
+    pub binaries: Vec<BinaryMetadata>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Health events a merged binary reported, newest first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TelemetryListResponse {
+    pub binary_id: String,
+    pub events: Vec<TelemetryEvent>,
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// DSSE envelope around an in-toto provenance statement
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProvenanceEnvelope {
+    #[serde(rename = "payloadType")]
+    pub payload_type: String,
+    /// Base64 of the statement JSON
+    pub payload: String,
+    /// Empty when the server has no `WEAVER_SIGNING_KEY`
+    pub signatures: Vec<DsseSignature>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DsseSignature {
+    /// Hex SHA-256 of the raw Ed25519 public key
+    pub keyid: String,
+    /// Base64 Ed25519 signature of the DSSE pre-authentication encoding
+    pub sig: String,
+}
+
+/// State of a resumable upload
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadResponse {
+    pub id: String,
+    pub size: u64,
+    /// Bytes received so far; send the next chunk from here
+    pub offset: u64,
+    pub expires_at: DateTime<Utc>,
+    pub upload_url: String,
+}
+
+impl From<&UploadSession> for UploadResponse {
+    fn from(upload: &UploadSession) -> Self {
+        Self {
+            id: upload.id.clone(),
+            size: upload.size,
+            offset: upload.offset,
+            expires_at: upload.expires_at,
+            upload_url: format!("/uploads/{}", upload.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_from_merge_errors() {
+        let mismatch = anyhow::Error::from(MergeError::ArchMismatch { base: "x86-64 on Linux".into(), overload: "ARM64 on Linux".into() });
+        assert_eq!(ErrorCode::from(&mismatch), ErrorCode::ArchMismatch);
+        let abi = anyhow::Error::from(MergeError::AbiMismatch {
+            base: "ARM (32-bit) on Linux (glibc, hard-float)".into(),
+            overload: "ARM (32-bit) on Linux (glibc, soft-float)".into(),
+        });
+        assert_eq!(ErrorCode::from(&abi), ErrorCode::ArchMismatch);
+
+        // Context added on the way up doesn't hide the cause
+        let missing = anyhow::Error::from(MergeError::StubMissing { os: "MacOS".into(), arch: "AArch64".into() })
+            .context("Merge failed");
+        assert_eq!(ErrorCode::from(&missing), ErrorCode::StubMissing);
+        let broken = anyhow::Error::from(MergeError::StubMismatch { expected: "x86-64 on Linux".into(), reason: "is not an executable".into() });
+        assert_eq!(ErrorCode::from(&broken), ErrorCode::StubMismatch);
+
+        assert_eq!(ErrorCode::from(&anyhow::Error::from(Cancelled::TimedOut(5))), ErrorCode::TimedOut);
+        assert_eq!(ErrorCode::from(&anyhow::anyhow!("disk on fire")), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn test_error_response_carries_code_and_hint() {
+        let json = serde_json::to_value(ErrorResponse::new(ErrorCode::StubMissing, "Merge failed", None)).unwrap();
+        assert_eq!(json["code"], "stub_missing");
+        assert_eq!(json["hint"], ErrorCode::StubMissing.hint());
+        assert!(json.get("details").is_none());
+    }
 }
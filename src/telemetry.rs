@@ -0,0 +1,51 @@
+use anyhow::Result;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::Config;
+
+/// Export merge pipeline spans over OTLP/gRPC when `WEAVER_OTEL_ENABLED=true`
+///
+/// The collector address comes from `OTEL_EXPORTER_OTLP_ENDPOINT`. Log output
+/// is unaffected and still goes through env_logger. The returned provider
+/// must be shut down on exit to flush pending spans.
+pub fn init(config: &Config) -> Result<Option<SdkTracerProvider>> {
+    if !config.otel_enabled {
+        return Ok(None);
+    }
+
+    let mut exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic();
+    if let Some(ref endpoint) = config.otel_endpoint {
+        exporter = exporter.with_endpoint(endpoint);
+    }
+
+    let resource = Resource::builder()
+        .with_service_name(config.otel_service_name.clone())
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")))
+        .build();
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter.build()?)
+        .with_resource(resource)
+        .build();
+
+    let tracer = provider.tracer("weaver");
+    opentelemetry::global::set_tracer_provider(provider.clone());
+
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+
+    log::info!(
+        "🔭 OpenTelemetry tracing enabled (service: {}, endpoint: {})",
+        config.otel_service_name,
+        config.otel_endpoint.as_deref().unwrap_or("default")
+    );
+
+    Ok(Some(provider))
+}
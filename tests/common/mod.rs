@@ -185,6 +185,18 @@ int main() {
     build_cross_compiled_binary("i686-w64-mingw32-gcc", "test_win32.exe", code).map(|_| ())
 }
 
+/// Ensure Windows ARM64 binary exists (build if needed, with llvm-mingw)
+pub fn ensure_win_arm64_binary() -> Result<(), String> {
+    let code = r#"
+#include <stdio.h>
+int main() {
+    printf("Windows ARM64 Test\n");
+    return 0;
+}
+"#;
+    build_cross_compiled_binary("aarch64-w64-mingw32-clang", "test_win_arm64.exe", code).map(|_| ())
+}
+
 /// Check if cross-host testing is enabled
 pub fn is_cross_host_testing_enabled() -> bool {
     std::env::var("WEAVER_ENABLE_CROSS_HOST_TESTING")
@@ -286,6 +298,21 @@ pub fn build_cross_compiled_binary(
     Ok((binary_path, data))
 }
 
+/// Progress backend for merges run directly from tests (no Redis needed)
+pub fn memory_progress_sink() -> weaver::core::progress::SharedProgressSink {
+    std::sync::Arc::new(weaver::core::progress::MemoryProgressSink::new())
+}
+
+/// Stubs from WEAVER_STUB_DIR if set, otherwise the embedded ones
+pub fn stub_registry() -> weaver::core::merger::StubRegistry {
+    weaver::core::merger::StubRegistry::load(std::env::var_os("WEAVER_STUB_DIR").map(PathBuf::from))
+}
+
+/// Work dirs for merges under `dir`
+pub fn workspace_manager(dir: &std::path::Path) -> weaver::core::workspace::WorkspaceManager {
+    weaver::core::workspace::WorkspaceManager::new(dir, std::time::Duration::from_secs(3600))
+}
+
 /// Extract task ID from API response
 pub fn extract_task_id(json: &serde_json::Value) -> Option<String> {
     json.get("task_id")
@@ -6,10 +6,7 @@ use crate::common::{load_test_binary, should_skip_cross_host_test};
 
 #[test]
 fn test_macos_binary_format() {
-    let info = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::MacOS,
-    };
+    let info = BinaryInfo::new(OperatingSystem::MacOS, Architecture::X86_64);
     
     assert_eq!(info.os.binary_format(), "Mach-O");
     assert_eq!(info.os.name(), "macOS");
@@ -20,10 +17,7 @@ fn test_macos_binary_format() {
 
 #[test]
 fn test_macos_arm64_support() {
-    let info = BinaryInfo {
-        arch: Architecture::AArch64,
-        os: OperatingSystem::MacOS,
-    };
+    let info = BinaryInfo::new(OperatingSystem::MacOS, Architecture::AArch64);
     
     assert_eq!(info.arch, Architecture::AArch64);
     assert_eq!(info.os, OperatingSystem::MacOS);
@@ -35,10 +29,7 @@ fn test_macos_arm64_support() {
 
 #[test]
 fn test_macos_x86_64_support() {
-    let info = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::MacOS,
-    };
+    let info = BinaryInfo::new(OperatingSystem::MacOS, Architecture::X86_64);
     
     assert_eq!(info.arch, Architecture::X86_64);
     assert_eq!(info.os, OperatingSystem::MacOS);
@@ -50,20 +41,11 @@ fn test_macos_x86_64_support() {
 
 #[test]
 fn test_macos_binary_compatibility() {
-    let macos_x64 = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::MacOS,
-    };
+    let macos_x64 = BinaryInfo::new(OperatingSystem::MacOS, Architecture::X86_64);
     
-    let macos_arm = BinaryInfo {
-        arch: Architecture::AArch64,
-        os: OperatingSystem::MacOS,
-    };
+    let macos_arm = BinaryInfo::new(OperatingSystem::MacOS, Architecture::AArch64);
     
-    let linux_x64 = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Linux,
-    };
+    let linux_x64 = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
     
     // Same arch and OS should be compatible
     assert!(macos_x64.is_compatible_with(&macos_x64));
@@ -82,15 +64,9 @@ fn test_macos_universal_binary_concept() {
     // macOS supports universal binaries (fat binaries with multiple architectures)
     // This test verifies we understand the concept even if not fully implemented
     
-    let x64_info = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::MacOS,
-    };
+    let x64_info = BinaryInfo::new(OperatingSystem::MacOS, Architecture::X86_64);
     
-    let arm64_info = BinaryInfo {
-        arch: Architecture::AArch64,
-        os: OperatingSystem::MacOS,
-    };
+    let arm64_info = BinaryInfo::new(OperatingSystem::MacOS, Architecture::AArch64);
     
     // Both architectures should be supported on macOS
     assert!(x64_info.is_supported());
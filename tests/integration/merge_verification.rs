@@ -101,6 +101,7 @@ int main() {
     // Step 5: Merge binaries using Weaver's merger
     println!("\n📊 Step 5: Merge binaries using Weaver merger (mode=before)");
     
+    use weaver::core::jobs::CancelToken;
     use weaver::core::merger::merge_binaries;
     use weaver::core::binary::BinaryInfo;
     use weaver::models::request::MergeMode;
@@ -135,9 +136,11 @@ int main() {
         &overload_data,
         MergeMode::Before,
         true, // sync
-        temp_path,
+        &crate::common::workspace_manager(temp_dir.path()),
         "", // task_id
-        "redis://redis:6379", // redis_url (test default)
+        &crate::common::memory_progress_sink(), // progress backend
+        &crate::common::stub_registry(),
+        &CancelToken::new(),
     ) {
         Ok(binary_id) => {
             println!("   ✅ Binaries merged successfully");
@@ -83,20 +83,11 @@ fn test_windows_pe_32bit_detection() {
 
 #[test]
 fn test_windows_binary_compatibility() {
-    let win64_info = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Windows,
-    };
+    let win64_info = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
     
-    let win32_info = BinaryInfo {
-        arch: Architecture::X86,
-        os: OperatingSystem::Windows,
-    };
+    let win32_info = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86);
     
-    let linux_info = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Linux,
-    };
+    let linux_info = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
     
     // Same arch and OS should be compatible
     assert!(win64_info.is_compatible_with(&win64_info));
@@ -2,7 +2,7 @@ use weaver::core::binary::{Architecture, OperatingSystem, BinaryInfo};
 use crate::common::{
     load_test_binary, get_test_binary_path, ensure_x86_64_binary,
     ensure_arm_binary, ensure_arm64_binary, ensure_mips_binary,
-    ensure_win64_binary, ensure_win32_binary, should_skip_cross_host_test
+    ensure_win64_binary, ensure_win32_binary, ensure_win_arm64_binary, should_skip_cross_host_test
 };
 
 #[test]
@@ -140,27 +140,38 @@ fn test_windows_pe_detection() {
     }
 }
 
+#[test]
+fn test_windows_arm64_pe_detection() {
+    if should_skip_cross_host_test("windows", "aarch64") {
+        println!("⚠️  Skipping Windows ARM64 PE test - cross-host testing disabled (set WEAVER_ENABLE_CROSS_HOST_TESTING=true)");
+        return;
+    }
+
+    if let Err(e) = ensure_win_arm64_binary() {
+        println!("{}", e);
+        println!("⚠️  Skipping Windows ARM64 PE test - llvm-mingw cross-compiler not available");
+        return;
+    }
+
+    if let Some(data) = load_test_binary("test_win_arm64.exe") {
+        let info = BinaryInfo::detect(&data);
+        assert_eq!(info.arch, Architecture::AArch64);
+        assert_eq!(info.os, OperatingSystem::Windows);
+        assert!(info.is_supported());
+    } else {
+        println!("⚠️  No Windows ARM64 PE test binary available, skipping test");
+    }
+}
+
 #[test]
 fn test_binary_compatibility() {
-    let info1 = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Linux,
-    };
+    let info1 = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
     
-    let info2 = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Linux,
-    };
+    let info2 = BinaryInfo::new(OperatingSystem::Linux, Architecture::X86_64);
     
-    let info3 = BinaryInfo {
-        arch: Architecture::ARM,
-        os: OperatingSystem::Linux,
-    };
+    let info3 = BinaryInfo::new(OperatingSystem::Linux, Architecture::ARM);
     
-    let info4 = BinaryInfo {
-        arch: Architecture::X86_64,
-        os: OperatingSystem::Windows,
-    };
+    let info4 = BinaryInfo::new(OperatingSystem::Windows, Architecture::X86_64);
     
     assert!(info1.is_compatible_with(&info2));
     assert!(!info1.is_compatible_with(&info3)); // Different arch
@@ -169,15 +180,9 @@ fn test_binary_compatibility() {
 
 #[test]
 fn test_unsupported_architectures() {
-    let mips_info = BinaryInfo {
-        arch: Architecture::MIPS,
-        os: OperatingSystem::Linux,
-    };
+    let mips_info = BinaryInfo::new(OperatingSystem::Linux, Architecture::MIPS);
     
-    let riscv_info = BinaryInfo {
-        arch: Architecture::RISCV64,
-        os: OperatingSystem::Linux,
-    };
+    let riscv_info = BinaryInfo::new(OperatingSystem::Linux, Architecture::RISCV64);
     
     assert!(!mips_info.is_supported());
     assert!(!riscv_info.is_supported());
@@ -0,0 +1,148 @@
+//! Entry tables of data files stored after the payloads.
+//!
+//! Used for an installer's files (`ConfigFooter::install_files_offset`) and
+//! for assets (`ConfigFooter::assets_offset`). Layout of a bundle:
+//! `[BundleEntry + path + data] * n`, back to back with no padding. Paths are
+//! relative to the directory the files are extracted to, `/`-separated on
+//! every OS.
+
+use core::mem;
+
+/// Longest relative path an entry can have
+pub const MAX_PATH_LEN: usize = 255;
+
+/// Mode of a data file that is a program of its own (Unix only)
+pub const MODE_EXECUTABLE: u32 = 0o755;
+/// Mode of any other data file (Unix only)
+pub const MODE_FILE: u32 = 0o644;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleEntry {
+    /// Bytes of UTF-8 path following the entry
+    pub path_len: u32,
+    /// `MODE_*` permissions the file is created with
+    pub mode: u32,
+    /// Bytes of file data following the path
+    pub size: u64,
+}
+
+impl BundleEntry {
+    pub const SIZE: usize = mem::size_of::<BundleEntry>();
+
+    /// Raw bytes as written in front of the path
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const BundleEntry as *const u8, Self::SIZE) }
+    }
+}
+
+/// Whether `path` stays inside the directory it is extracted to
+///
+/// Only `/`-separated relative paths without empty, `.` or `..` components
+/// and without characters Windows can't put in a file name.
+pub fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && path.len() <= MAX_PATH_LEN
+        && path.split('/').all(|component| {
+            !matches!(component, "" | "." | "..")
+                && !component.bytes().any(|b| b < 0x20 || matches!(b, b'\\' | b':' | b'*' | b'?' | b'"' | b'<' | b'>' | b'|'))
+        })
+}
+
+/// One file of a parsed bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleFile<'a> {
+    pub path: &'a str,
+    pub mode: u32,
+    pub data: &'a [u8],
+}
+
+/// Iterates over a bundle's files; yields `Err(())` once and stops if an
+/// entry is truncated or its path isn't safe
+pub struct BundleFiles<'a> {
+    rest: &'a [u8],
+}
+
+/// The files in a stored bundle
+pub fn files(bundle: &[u8]) -> BundleFiles<'_> {
+    BundleFiles { rest: bundle }
+}
+
+impl<'a> Iterator for BundleFiles<'a> {
+    type Item = Result<BundleFile<'a>, ()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let file = self.parse_next();
+        if file.is_err() {
+            self.rest = &[];
+        }
+        Some(file)
+    }
+}
+
+impl<'a> BundleFiles<'a> {
+    fn parse_next(&mut self) -> Result<BundleFile<'a>, ()> {
+        if self.rest.len() < BundleEntry::SIZE {
+            return Err(());
+        }
+        let entry: BundleEntry = unsafe { core::ptr::read_unaligned(self.rest.as_ptr() as *const BundleEntry) };
+        let rest = &self.rest[BundleEntry::SIZE..];
+        let path_len = entry.path_len as usize;
+        let size = usize::try_from(entry.size).map_err(|_| ())?;
+        if rest.len() < path_len || rest.len() - path_len < size {
+            return Err(());
+        }
+        let path = core::str::from_utf8(&rest[..path_len]).map_err(|_| ())?;
+        if !is_safe_path(path) {
+            return Err(());
+        }
+        self.rest = &rest[path_len + size..];
+        Ok(BundleFile { path, mode: entry.mode, data: &rest[path_len..path_len + size] })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let mut bundle = [0u8; 2 * BundleEntry::SIZE + 19];
+        let mut at = 0;
+        for (path, mode, data) in [("conf/app.toml", MODE_FILE, &b"x=1"[..]), ("run", MODE_EXECUTABLE, b"")] {
+            let entry = BundleEntry { path_len: path.len() as u32, mode, size: data.len() as u64 };
+            for part in [entry.as_bytes(), path.as_bytes(), data] {
+                bundle[at..at + part.len()].copy_from_slice(part);
+                at += part.len();
+            }
+        }
+        assert_eq!(at, bundle.len());
+
+        let mut parsed = files(&bundle);
+        assert_eq!(parsed.next(), Some(Ok(BundleFile { path: "conf/app.toml", mode: MODE_FILE, data: b"x=1" })));
+        assert_eq!(parsed.next(), Some(Ok(BundleFile { path: "run", mode: MODE_EXECUTABLE, data: b"" })));
+        assert_eq!(parsed.next(), None);
+
+        // Truncated bundles fail once, then stop
+        let mut truncated = files(&bundle[..bundle.len() - 1]);
+        assert!(truncated.next().unwrap().is_ok());
+        assert_eq!(truncated.next(), Some(Err(())));
+        assert_eq!(truncated.next(), None);
+    }
+
+    #[test]
+    fn test_paths_stay_inside_the_extraction_dir() {
+        assert!(is_safe_path("app.conf"));
+        assert!(is_safe_path("share/icons/app.png"));
+        assert!(!is_safe_path(""));
+        assert!(!is_safe_path("/etc/passwd"));
+        assert!(!is_safe_path("../outside"));
+        assert!(!is_safe_path("a/./b"));
+        assert!(!is_safe_path("a//b"));
+        assert!(!is_safe_path("dir\\..\\up"));
+        assert!(!is_safe_path("C:/Windows"));
+    }
+}
@@ -0,0 +1,966 @@
+//! Configuration footer appended to every merged binary.
+//!
+//! Layout of a merged binary:
+//! `[Stub] + [Base] + [Overload] + [Seccomp profile, optional] + [Labels, optional]
+//! + [Interpreter, optional] + [Install bundle, optional] + [Assets, optional]
+//! + [ConfigFooter]`
+//!
+//! The stub reads the last [`ConfigFooter::SIZE`] bytes of its own executable
+//! to find the payloads and its runtime options. Weaver and the stubs are
+//! built from the same `weaver-abi`, so new fields are appended at the end
+//! and [`FOOTER_VERSION`] is bumped. A stub only accepts a footer with its
+//! own version and size, and carries [`stub_marker`] so Weaver can refuse
+//! stubs built for another layout before merging with them.
+
+use core::mem;
+
+use crate::kill_policy::{self, PolicyOp, MAX_KILL_POLICY_OPS};
+
+pub const MAGIC_BYTES: &[u8; 8] = b"KILLCODE";
+
+/// Layout of `ConfigFooter`; bump it whenever a field is added, moved or resized
+pub const FOOTER_VERSION: u32 = 1;
+
+/// Start of the marker a stub embeds, followed by the `FOOTER_VERSION` and
+/// `ConfigFooter::SIZE` it reads as little-endian `u32`s
+pub const STUB_MARKER_PREFIX: &[u8; 16] = b"KILLCODE-FOOTER:";
+pub const STUB_MARKER_LEN: usize = STUB_MARKER_PREFIX.len() + 8;
+
+/// The marker of a stub that reads this crate's footer layout
+pub const fn stub_marker() -> [u8; STUB_MARKER_LEN] {
+    let mut marker = [0u8; STUB_MARKER_LEN];
+    let prefix = STUB_MARKER_PREFIX.len();
+    let version = FOOTER_VERSION.to_le_bytes();
+    let size = (ConfigFooter::SIZE as u32).to_le_bytes();
+    let mut i = 0;
+    while i < prefix {
+        marker[i] = STUB_MARKER_PREFIX[i];
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 4 {
+        marker[prefix + i] = version[i];
+        marker[prefix + 4 + i] = size[i];
+        i += 1;
+    }
+    marker
+}
+
+/// The footer version and size a stub executable's marker names, if it has one
+pub fn stub_layout(stub: &[u8]) -> Option<(u32, u32)> {
+    let start = stub.windows(STUB_MARKER_LEN).position(|window| window.starts_with(STUB_MARKER_PREFIX))?;
+    let field = |at: usize| {
+        let at = start + STUB_MARKER_PREFIX.len() + at;
+        u32::from_le_bytes([stub[at], stub[at + 1], stub[at + 2], stub[at + 3]])
+    };
+    Some((field(0), field(4)))
+}
+
+// Overload jail flags (`ConfigFooter::overload_jail`, Linux only)
+
+/// Private mount namespace: sensitive host paths are hidden and the merged
+/// binary's directory is read-only
+pub const JAIL_FILESYSTEM: u32 = 1 << 0;
+/// Private network namespace with only a loopback interface
+pub const JAIL_NETWORK: u32 = 1 << 1;
+/// Private PID namespace: the overload runs as PID 1 and sees only its own
+/// children
+pub const JAIL_PID: u32 = 1 << 2;
+/// pivot_root into an empty tmpfs holding only /dev/null, /dev/zero,
+/// /dev/urandom, /tmp and /proc; needs a statically linked overload
+pub const JAIL_EMPTY_ROOT: u32 = 1 << 3;
+
+// Execution order (`ConfigFooter::order`)
+
+/// Overload starts first; base runs alongside it (or after it in sync mode)
+pub const ORDER_OVERLOAD_FIRST: u8 = 0;
+/// Base runs to completion, then the overload runs; no health monitoring
+pub const ORDER_BASE_FIRST: u8 = 1;
+
+// What the overload is (`ConfigFooter::overload_kind`)
+
+/// An executable, run as a process of its own
+pub const OVERLOAD_EXECUTABLE: u8 = 0;
+/// A `#!` script, run through its interpreter (Linux and macOS)
+pub const OVERLOAD_SCRIPT: u8 = 1;
+/// A shared library loaded into base, which then runs alone: `LD_PRELOAD` on
+/// Linux, `DYLD_INSERT_LIBRARIES` on macOS, a remote `LoadLibrary` thread on
+/// Windows
+pub const OVERLOAD_LIBRARY: u8 = 2;
+
+// What a failed prerequisite run leads to (`ConfigFooter::prerequisite_on_failure`)
+
+/// Base never starts and the stub exits with an error
+pub const PREREQ_ABORT: u8 = 0;
+/// Repeat the failed run, keeping the runs that passed
+pub const PREREQ_RETRY: u8 = 1;
+/// Start over from the first run
+pub const PREREQ_RESTART: u8 = 2;
+
+// Hardening flags (`ConfigFooter::hardening`)
+
+/// Refuse to run when the image no longer matches `ConfigFooter::image_checksum`
+pub const HARDEN_CHECKSUM: u8 = 1 << 0;
+/// Refuse to run under a debugger, and kill the payloads if one attaches later
+pub const HARDEN_ANTI_DEBUG: u8 = 1 << 1;
+
+// Stub log verbosity (`ConfigFooter::log_level`)
+
+/// Everything, the default
+pub const LOG_VERBOSE: u8 = 0;
+/// Only failures and the reasons payloads get killed
+pub const LOG_ERRORS: u8 = 1;
+/// Nothing at all
+pub const LOG_SILENT: u8 = 2;
+
+// Where stub logs go (`ConfigFooter::log_target`)
+
+/// stderr, shared with the payloads
+pub const LOG_TARGET_STDERR: u8 = 0;
+/// Appended to `ConfigFooter::log_path`
+pub const LOG_TARGET_FILE: u8 = 1;
+/// syslog on Linux and macOS, the Application Event Log on Windows
+pub const LOG_TARGET_SYSTEM: u8 = 2;
+
+// How a Linux stub turns a payload into something it can exec (`ConfigFooter::exec_strategy`)
+
+/// memfd_create, falling back to `EXEC_TMPFILE` and then `EXEC_TEMP_FILE`
+pub const EXEC_AUTO: u8 = 0;
+/// memfd_create only
+pub const EXEC_MEMFD: u8 = 1;
+/// An unnamed `O_TMPFILE` file, on tmpfs where possible
+pub const EXEC_TMPFILE: u8 = 2;
+/// A file in a private temp directory, unlinked once it's open
+pub const EXEC_TEMP_FILE: u8 = 3;
+
+// Working directory of a payload (`ConfigFooter::base_cwd_mode`/`overload_cwd_mode`)
+
+/// Wherever the merged binary was started from
+pub const CWD_INHERIT: u8 = 0;
+/// The directory the merged binary is in
+pub const CWD_BINARY_DIR: u8 = 1;
+/// The path in `ConfigFooter::base_cwd`/`overload_cwd`
+pub const CWD_PATH: u8 = 2;
+
+// What a second copy of a merged binary does (`ConfigFooter::single_instance`)
+
+/// Nothing, any number of copies can run
+pub const SINGLE_INSTANCE_OFF: u8 = 0;
+/// Exits with code 1 while another copy runs
+pub const SINGLE_INSTANCE_EXIT: u8 = 1;
+/// Sends SIGUSR1 to the running copy, which forwards it to its base, and
+/// exits with code 0 (Unix; behaves like `SINGLE_INSTANCE_EXIT` on Windows)
+pub const SINGLE_INSTANCE_SIGNAL: u8 = 2;
+
+// Machine properties hashed into the fingerprint checked against
+// `ConfigFooter::machine_fingerprints` (`ConfigFooter::machine_binding`)
+
+/// /etc/machine-id on Linux, IOPlatformUUID on macOS, MachineGuid on Windows
+pub const BIND_MACHINE_ID: u8 = 1 << 0;
+pub const BIND_HOSTNAME: u8 = 1 << 1;
+/// Lowest MAC address among the physical network interfaces
+pub const BIND_MAC: u8 = 1 << 2;
+
+// What an installer does besides extracting files (`ConfigFooter::install_flags`)
+
+/// Write a systemd unit running the merged binary (Linux only; not enabled)
+pub const INSTALL_SYSTEMD_UNIT: u8 = 1 << 0;
+/// Write a desktop entry launching the merged binary (Linux only)
+pub const INSTALL_SHORTCUT: u8 = 1 << 1;
+
+// Where a crash report for base goes (`ConfigFooter::crash_report`); a set
+// `crash_report_url` gets one as well
+
+/// Write `<binary>.crash-<time>.json` next to the merged binary
+pub const CRASH_REPORT_FILE: u8 = 1 << 0;
+/// Let base dump core (Unix; lifts `RLIMIT_CORE`)
+pub const CRASH_REPORT_CORE_DUMP: u8 = 1 << 1;
+
+/// Most stderr lines `ConfigFooter::crash_stderr_lines` can keep
+pub const MAX_CRASH_STDERR_LINES: u16 = 1000;
+
+// What the stub waits for between starting the overload and starting base
+// (`ConfigFooter::readiness_probe`)
+
+/// Nothing, base starts right after the overload
+pub const READINESS_NONE: u8 = 0;
+/// A TCP connection to the `host:port` in `readiness_target` succeeds
+pub const READINESS_TCP: u8 = 1;
+/// The path in `readiness_target` exists (relative to the merged binary's
+/// directory unless absolute)
+pub const READINESS_FILE: u8 = 2;
+/// The overload marks its health slot ready (`Health::set_ready`)
+pub const READINESS_FLAG: u8 = 3;
+
+// What happens when the probe hasn't passed in `readiness_timeout` seconds
+// (`ConfigFooter::readiness_on_timeout`)
+
+/// Kill the overload and exit with code 1 without starting base
+pub const READINESS_ABORT: u8 = 0;
+/// Log it and start base anyway
+pub const READINESS_START_BASE: u8 = 1;
+
+// How base and the overload are stored behind the stub (`ConfigFooter::packing`)
+
+/// As they were uploaded
+pub const PACKING_NONE: u8 = 0;
+/// As LZ4 blocks (see `crate::pack`) where `*_unpacked_size` is set
+pub const PACKING_LZ4: u8 = 1;
+
+// Payloads whose stdout and stderr are teed into rotating logs
+// (`ConfigFooter::output_log`)
+
+/// Tee base's output into `base.log` in `output_log_dir`
+pub const OUTPUT_LOG_BASE: u8 = 1 << 0;
+/// Tee the overload's output into `overload.log` in `output_log_dir`
+pub const OUTPUT_LOG_OVERLOAD: u8 = 1 << 1;
+
+/// Most files `ConfigFooter::output_log_max_files` can keep per payload
+pub const MAX_OUTPUT_LOG_FILES: u8 = 100;
+
+/// Set in `ConfigFooter::base_umask`/`overload_umask` when the low bits are
+/// a umask to apply; 0 leaves the inherited umask alone
+pub const UMASK_SET: u16 = 1 << 15;
+
+/// Set in `ConfigFooter::base_priority`/`overload_priority` when the low
+/// bits are a nice value offset by 20; 0 leaves the priority alone
+pub const PRIORITY_SET: u8 = 1 << 7;
+/// Nice values a payload can be given, highest priority first; Windows maps
+/// them to priority classes
+pub const MIN_NICE: i8 = -20;
+pub const MAX_NICE: i8 = 19;
+
+/// Longest account name `ConfigFooter::run_as_user` can hold
+pub const RUN_AS_USER_LEN: usize = 32;
+/// Longest path `ConfigFooter::log_path` can hold
+pub const LOG_PATH_LEN: usize = 128;
+/// Longest name `ConfigFooter::product_name` can hold
+pub const PRODUCT_NAME_LEN: usize = 32;
+/// Longest path `ConfigFooter::base_cwd`/`overload_cwd` can hold
+pub const CWD_PATH_LEN: usize = 128;
+/// Longest URL `ConfigFooter::revocation_url` can hold
+pub const REVOCATION_URL_LEN: usize = 128;
+/// Size of the Ed25519 public key in `ConfigFooter::revocation_key`
+pub const REVOCATION_KEY_LEN: usize = 32;
+/// Most machines `ConfigFooter::machine_fingerprints` can list
+pub const MAX_MACHINE_FINGERPRINTS: usize = 16;
+/// Longest path `ConfigFooter::audit_path` can hold
+pub const AUDIT_PATH_LEN: usize = 128;
+/// Longest URL `ConfigFooter::audit_url` can hold
+pub const AUDIT_URL_LEN: usize = 128;
+/// Longest URL `ConfigFooter::telemetry_url` can hold
+pub const TELEMETRY_URL_LEN: usize = 128;
+/// Longest URL `ConfigFooter::crash_report_url` can hold
+pub const CRASH_REPORT_URL_LEN: usize = 128;
+/// Longest path `ConfigFooter::output_log_dir` can hold
+pub const OUTPUT_LOG_DIR_LEN: usize = 128;
+/// Longest address or path `ConfigFooter::readiness_target` can hold
+pub const READINESS_TARGET_LEN: usize = 128;
+/// Longest path `ConfigFooter::install_dir` can hold
+pub const INSTALL_DIR_LEN: usize = 128;
+/// Longest name `ConfigFooter::install_name` can hold
+pub const INSTALL_NAME_LEN: usize = 32;
+
+/// Encode a nice value for `ConfigFooter::base_priority`/`overload_priority`
+///
+/// Returns `None` if it is outside `MIN_NICE..=MAX_NICE`.
+pub fn encode_priority(nice: i8) -> Option<u8> {
+    (MIN_NICE..=MAX_NICE).contains(&nice).then(|| PRIORITY_SET | (nice - MIN_NICE) as u8)
+}
+
+/// Encode a string for a NUL-padded footer field of `N` bytes, such as
+/// `encode_padded::<LOG_PATH_LEN>(path)` for `ConfigFooter::log_path`
+///
+/// Returns `None` if it is empty, longer than `N` or contains a NUL byte.
+pub fn encode_padded<const N: usize>(value: &str) -> Option<[u8; N]> {
+    let bytes = value.as_bytes();
+    if bytes.is_empty() || bytes.len() > N || bytes.contains(&0) {
+        return None;
+    }
+    let mut field = [0u8; N];
+    field[..bytes.len()].copy_from_slice(bytes);
+    Some(field)
+}
+
+fn decode_padded(field: &[u8]) -> Option<&str> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).ok().filter(|value| !value.is_empty())
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigFooter {
+    pub magic: [u8; 8],
+    /// `FOOTER_VERSION` the footer was written with
+    pub version: u32,
+    /// `ConfigFooter::SIZE` the footer was written with
+    pub size: u32,
+    pub base_offset: u64,
+    pub base_size: u64,
+    pub overload_offset: u64,
+    pub overload_size: u64,
+    pub grace_period: u32,
+    pub sync_mode: u8, // 0 or 1
+    pub network_failure_kill_count: u32,
+    pub overload_jail: u32, // JAIL_* flags
+    pub order: u8,          // ORDER_* value
+    /// Seconds after base starts before the overload is first run (0 = immediately)
+    pub overload_start_delay: u32,
+    /// Re-run the overload every this many seconds while base runs (0 = once)
+    pub overload_interval: u32,
+    /// Seconds between health checks (0 = loader default)
+    pub health_check_interval: u32,
+    /// Milliseconds between SIGTERM and SIGKILL when killing base (0 = loader default)
+    pub kill_delay_ms: u32,
+    /// Seconds without an overload heartbeat before base is killed (0 = disabled)
+    pub heartbeat_timeout: u32,
+    /// Seconds children get to exit after the stub is asked to stop (0 = loader default)
+    pub shutdown_grace: u32,
+    /// Memory cap for base and everything it starts, in MiB (0 = unlimited)
+    pub base_memory_limit_mb: u32,
+    /// CPU cap for base, in percent of the whole machine (0 = unlimited)
+    pub base_cpu_percent: u32,
+    /// Memory cap for the overload and everything it starts, in MiB (0 = unlimited)
+    pub overload_memory_limit_mb: u32,
+    /// CPU cap for the overload, in percent of the whole machine (0 = unlimited)
+    pub overload_cpu_percent: u32,
+    /// Open file limit for base (0 = inherited, Linux only)
+    pub base_nofile_limit: u32,
+    /// Open file limit for the overload (0 = inherited, Linux only)
+    pub overload_nofile_limit: u32,
+    /// Account payloads switch to when the stub runs as root, NUL-padded
+    /// (all zero = unchanged; Linux and macOS)
+    pub run_as_user: [u8; RUN_AS_USER_LEN],
+    /// Without `run_as_user`, strip root's privileges from payloads anyway
+    /// (Linux: drop all capabilities, macOS: run as `nobody`)
+    pub drop_privileges: u8,
+    /// Where the seccomp profile starts (see `crate::seccomp`)
+    pub seccomp_offset: u64,
+    /// Size of the seccomp profile (0 = none)
+    pub seccomp_size: u64,
+    /// `HARDEN_*` flags
+    pub hardening: u8,
+    /// [`ImageChecksum`] of the whole merged binary, for `HARDEN_CHECKSUM`
+    pub image_checksum: u64,
+    /// `LOG_*` verbosity of the stub's own messages
+    pub log_level: u8,
+    /// `LOG_TARGET_*` destination of the stub's own messages
+    pub log_target: u8,
+    /// File for `LOG_TARGET_FILE`, NUL-padded
+    pub log_path: [u8; LOG_PATH_LEN],
+    /// Name used instead of "KillCode" in stub logs and for the payload
+    /// processes, NUL-padded (all zero = unbranded)
+    pub product_name: [u8; PRODUCT_NAME_LEN],
+    /// `EXEC_*` value (Linux only)
+    pub exec_strategy: u8,
+    /// `CWD_*` value for base
+    pub base_cwd_mode: u8,
+    /// Directory for `CWD_PATH`, NUL-padded
+    pub base_cwd: [u8; CWD_PATH_LEN],
+    /// `CWD_*` value for the overload
+    pub overload_cwd_mode: u8,
+    /// Directory for `CWD_PATH`, NUL-padded
+    pub overload_cwd: [u8; CWD_PATH_LEN],
+    /// `UMASK_SET | mask` for base (0 = inherited; Linux and macOS)
+    pub base_umask: u16,
+    /// `UMASK_SET | mask` for the overload (0 = inherited; Linux and macOS)
+    pub overload_umask: u16,
+    /// `SINGLE_INSTANCE_*` value
+    pub single_instance: u8,
+    /// Identifies copies of the same merged binary for `single_instance`
+    pub instance_id: u64,
+    /// Open the local control socket (0 or 1)
+    pub control_socket: u8,
+    /// `http://` URL polled for a signed revocation, NUL-padded (all zero = off)
+    pub revocation_url: [u8; REVOCATION_URL_LEN],
+    /// Seconds between revocation checks (0 = default)
+    pub revocation_interval: u32,
+    /// Ed25519 key a revocation must be signed with
+    pub revocation_key: [u8; REVOCATION_KEY_LEN],
+    /// Unix time from which the payloads may no longer run (0 = never)
+    pub expires_at: i64,
+    /// Don't trust a system clock that was set back: check file timestamps
+    /// and the monotonic clock too (0 or 1)
+    pub expiry_clock_checks: u8,
+    /// `BIND_*` flags hashed into the local fingerprint (0 = runs on any machine)
+    pub machine_binding: u8,
+    /// Fingerprints of the machines allowed to run the payloads, unused slots 0
+    pub machine_fingerprints: [u64; MAX_MACHINE_FINGERPRINTS],
+    /// File audit records are appended to, NUL-padded (all zero = none)
+    pub audit_path: [u8; AUDIT_PATH_LEN],
+    /// `http://` URL audit records are POSTed to, NUL-padded (all zero = none)
+    pub audit_url: [u8; AUDIT_URL_LEN],
+    /// Weaver `/telemetry/{binary_id}` URL health events are POSTed to, NUL-padded (all zero = none)
+    pub telemetry_url: [u8; TELEMETRY_URL_LEN],
+    /// Where the merge request's labels start, as a JSON object; the stub never reads them
+    pub labels_offset: u64,
+    /// Size of the labels (0 = none)
+    pub labels_size: u64,
+    /// `OVERLOAD_*` value
+    pub overload_kind: u8,
+    /// Where the interpreter bundled to run a script overload starts
+    pub interpreter_offset: u64,
+    /// Size of the bundled interpreter (0 = the script's own `#!` line picks one)
+    pub interpreter_size: u64,
+    /// Directory base is installed to on first run, NUL-padded (all zero = not an installer)
+    pub install_dir: [u8; INSTALL_DIR_LEN],
+    /// File name base is installed under, and of the unit and shortcut, NUL-padded
+    pub install_name: [u8; INSTALL_NAME_LEN],
+    /// `INSTALL_*` flags
+    pub install_flags: u8,
+    /// Identifies what gets installed; a different one in the install dir is upgraded
+    pub install_id: u64,
+    /// Where the install bundle starts (see `crate::bundle`)
+    pub install_files_offset: u64,
+    /// Size of the install bundle (0 = base only)
+    pub install_files_size: u64,
+    /// Identifies the assets; each distinct set is extracted to a directory of its own
+    pub assets_id: u64,
+    /// Where the assets bundle starts (see `crate::bundle`)
+    pub assets_offset: u64,
+    /// Size of the assets bundle (0 = no assets)
+    pub assets_size: u64,
+    /// Times the overload has to run to completion and exit 0 before base
+    /// starts (0 = once in sync mode, otherwise not at all)
+    pub prerequisite_runs: u32,
+    /// `PREREQ_*` value
+    pub prerequisite_on_failure: u8,
+    /// Failed runs tolerated under `PREREQ_RETRY`/`PREREQ_RESTART` before giving up
+    pub prerequisite_max_failures: u32,
+    /// Seconds between a failed run and the next one
+    pub prerequisite_retry_delay: u32,
+    /// Connect base and the overload with a two-way channel (0 or 1)
+    pub ipc_channel: u8,
+    /// Packed `PolicyOp`s the health monitor runs instead of the fixed
+    /// threshold checks; all zero when there is none
+    pub kill_policy: [u64; MAX_KILL_POLICY_OPS],
+    /// Seconds after base starts before the health monitor counts ages and
+    /// failures (0 = right away)
+    pub monitor_warmup: u32,
+    /// Wait for each payload's first successful check after base starts
+    /// before counting (0 or 1); `monitor_warmup` then caps the wait
+    pub arm_after_success: u8,
+    /// `CRASH_REPORT_*` flags
+    pub crash_report: u8,
+    /// Last lines of base's stderr kept for a crash report (0 = stderr is
+    /// left alone)
+    pub crash_stderr_lines: u16,
+    /// http:// URL crash reports are POSTed to (NUL-padded, empty = none)
+    pub crash_report_url: [u8; CRASH_REPORT_URL_LEN],
+    /// `OUTPUT_LOG_*` flags
+    pub output_log: u8,
+    /// Files kept per payload, the current one included (0 counts as 1)
+    pub output_log_max_files: u8,
+    /// Size in MiB at which a payload's log is rotated (0 = never)
+    pub output_log_max_size: u32,
+    /// Directory the logs are written to, relative to the merged binary's
+    /// unless absolute (NUL-padded, empty = the merged binary's)
+    pub output_log_dir: [u8; OUTPUT_LOG_DIR_LEN],
+    /// `PRIORITY_SET | (nice + 20)` for base (0 = inherited)
+    pub base_priority: u8,
+    /// `PRIORITY_SET | (nice + 20)` for the overload (0 = inherited)
+    pub overload_priority: u8,
+    /// CPUs base may run on, bit N for CPU N (0 = any; Linux and Windows)
+    pub base_cpu_affinity: u64,
+    /// CPUs the overload may run on, bit N for CPU N (0 = any; Linux and Windows)
+    pub overload_cpu_affinity: u64,
+    /// Seconds to wait for the readiness probe (0 = forever)
+    pub readiness_timeout: u32,
+    /// `READINESS_*` probe run before base starts (sidecar mode, overload first)
+    pub readiness_probe: u8,
+    /// `READINESS_ABORT` or `READINESS_START_BASE`
+    pub readiness_on_timeout: u8,
+    /// `host:port` for `READINESS_TCP`, a path for `READINESS_FILE` (NUL-padded)
+    pub readiness_target: [u8; READINESS_TARGET_LEN],
+    /// `PACKING_*` value; written after assembly, when the payloads are packed
+    pub packing: u8,
+    /// Size of base once unpacked; `base_size` is then the packed size (0 =
+    /// stored as is, it didn't get smaller)
+    pub base_unpacked_size: u64,
+    /// Size of the overload once unpacked, like `base_unpacked_size`
+    pub overload_unpacked_size: u64,
+}
+
+/// FNV-1a over a merged binary, footer included with `image_checksum` zeroed
+///
+/// Catches patched payloads or footers; it is not a signature, so anyone
+/// rewriting the binary can also rewrite the checksum.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageChecksum(u64);
+
+impl Default for ImageChecksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ImageChecksum {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub fn new() -> Self {
+        ImageChecksum(Self::OFFSET_BASIS)
+    }
+
+    /// Feed the bytes in front of the footer
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Feed the raw footer bytes as stored, skipping the checksum itself
+    pub fn update_footer(&mut self, footer: &[u8; ConfigFooter::SIZE]) {
+        let field = mem::offset_of!(ConfigFooter, image_checksum);
+        self.update(&footer[..field]);
+        self.update(&[0; 8]);
+        self.update(&footer[field + 8..]);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+
+    /// Store the result in raw footer bytes, without touching anything else
+    pub fn write_into(&self, footer: &mut [u8; ConfigFooter::SIZE]) {
+        let field = mem::offset_of!(ConfigFooter, image_checksum);
+        footer[field..field + 8].copy_from_slice(&self.0.to_ne_bytes());
+    }
+}
+
+/// Resource caps for one payload, read from the footer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub memory_mb: u32,
+    pub cpu_percent: u32,
+    pub nofile: u32,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl ConfigFooter {
+    /// Size of the serialized footer in bytes
+    pub const SIZE: usize = mem::size_of::<ConfigFooter>();
+
+    /// The overload runs on a delay/interval schedule instead of as a sidecar
+    pub fn is_scheduled(&self) -> bool {
+        self.order == ORDER_OVERLOAD_FIRST && (self.overload_start_delay > 0 || self.overload_interval > 0)
+    }
+
+    /// Successful overload runs base waits for; 0 when the overload runs alongside base
+    pub fn prerequisite_runs(&self) -> u32 {
+        if self.prerequisite_runs > 0 {
+            self.prerequisite_runs
+        } else {
+            self.sync_mode as u32
+        }
+    }
+
+    /// Whether the merge came with a kill policy of its own
+    pub fn has_kill_policy(&self) -> bool {
+        self.kill_policy[0] != 0
+    }
+
+    /// Ops of the merge's kill policy, empty when it has none
+    pub fn kill_policy(&self) -> impl Iterator<Item = PolicyOp> + '_ {
+        kill_policy::ops(&self.kill_policy)
+    }
+
+    /// Resource caps for base (`true`) or the overload (`false`)
+    pub fn limits(&self, is_base: bool) -> ResourceLimits {
+        if is_base {
+            ResourceLimits {
+                memory_mb: self.base_memory_limit_mb,
+                cpu_percent: self.base_cpu_percent,
+                nofile: self.base_nofile_limit,
+            }
+        } else {
+            ResourceLimits {
+                memory_mb: self.overload_memory_limit_mb,
+                cpu_percent: self.overload_cpu_percent,
+                nofile: self.overload_nofile_limit,
+            }
+        }
+    }
+
+    /// Account payloads should run as, if one is set
+    pub fn run_as_user(&self) -> Option<&str> {
+        decode_padded(&self.run_as_user)
+    }
+
+    /// Log file for `LOG_TARGET_FILE`, if one is set
+    pub fn log_path(&self) -> Option<&str> {
+        decode_padded(&self.log_path)
+    }
+
+    /// Product name the merged binary is branded with, if one is set
+    pub fn product_name(&self) -> Option<&str> {
+        decode_padded(&self.product_name)
+    }
+
+    /// `CWD_*` mode and, for `CWD_PATH`, the directory of base (`true`) or the overload (`false`)
+    pub fn cwd(&self, is_base: bool) -> (u8, Option<&str>) {
+        if is_base {
+            (self.base_cwd_mode, decode_padded(&self.base_cwd))
+        } else {
+            (self.overload_cwd_mode, decode_padded(&self.overload_cwd))
+        }
+    }
+
+    /// URL polled for revocation, if one is set
+    pub fn revocation_url(&self) -> Option<&str> {
+        decode_padded(&self.revocation_url)
+    }
+
+    /// File audit records are appended to, if one is set
+    pub fn audit_path(&self) -> Option<&str> {
+        decode_padded(&self.audit_path)
+    }
+
+    /// URL audit records are POSTed to, if one is set
+    pub fn audit_url(&self) -> Option<&str> {
+        decode_padded(&self.audit_url)
+    }
+
+    /// URL health events are reported to, if one is set
+    pub fn telemetry_url(&self) -> Option<&str> {
+        decode_padded(&self.telemetry_url)
+    }
+
+    /// URL crash reports are POSTed to, if one is set
+    pub fn crash_report_url(&self) -> Option<&str> {
+        decode_padded(&self.crash_report_url)
+    }
+
+    /// Whether a crash of base is reported anywhere
+    pub fn reports_crashes(&self) -> bool {
+        self.crash_report & CRASH_REPORT_FILE != 0 || self.crash_report_url().is_some()
+    }
+
+    /// Whether base's (or the overload's) output is teed into a log
+    pub fn logs_output(&self, is_base: bool) -> bool {
+        self.output_log & if is_base { OUTPUT_LOG_BASE } else { OUTPUT_LOG_OVERLOAD } != 0
+    }
+
+    /// Directory payload output logs go to, if one is set
+    pub fn output_log_dir(&self) -> Option<&str> {
+        decode_padded(&self.output_log_dir)
+    }
+
+    /// Directory base is installed to, if the merged binary is an installer
+    pub fn install_dir(&self) -> Option<&str> {
+        decode_padded(&self.install_dir)
+    }
+
+    /// File name base is installed under
+    pub fn install_name(&self) -> Option<&str> {
+        decode_padded(&self.install_name)
+    }
+
+    /// umask for base (`true`) or the overload (`false`), if one is set
+    pub fn umask(&self, is_base: bool) -> Option<u16> {
+        let umask = if is_base { self.base_umask } else { self.overload_umask };
+        (umask & UMASK_SET != 0).then_some(umask & 0o777)
+    }
+
+    /// Nice value for base (`true`) or the overload (`false`), if one is set
+    pub fn priority(&self, is_base: bool) -> Option<i8> {
+        let priority = if is_base { self.base_priority } else { self.overload_priority };
+        (priority & PRIORITY_SET != 0).then(|| ((priority & !PRIORITY_SET) as i8 + MIN_NICE).min(MAX_NICE))
+    }
+
+    /// Address or path the readiness probe checks, if it needs one
+    pub fn readiness_target(&self) -> Option<&str> {
+        decode_padded(&self.readiness_target)
+    }
+
+    /// Size base (`true`) or the overload (`false`) unpacks to, `None` when it is stored as is
+    pub fn unpacked_size(&self, is_base: bool) -> Option<u64> {
+        let size = if is_base { self.base_unpacked_size } else { self.overload_unpacked_size };
+        (self.packing == PACKING_LZ4 && size > 0).then_some(size)
+    }
+
+    /// CPU mask for base (`true`) or the overload (`false`), 0 for any CPU
+    pub fn cpu_affinity(&self, is_base: bool) -> u64 {
+        if is_base {
+            self.base_cpu_affinity
+        } else {
+            self.overload_cpu_affinity
+        }
+    }
+
+    /// Raw bytes as written to the end of the merged binary
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const ConfigFooter as *const u8, Self::SIZE) }
+    }
+
+    /// Parse a footer, returning `None` if the magic bytes don't match or it
+    /// was written for another layout
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<ConfigFooter> {
+        let footer: ConfigFooter = unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const ConfigFooter) };
+        if &footer.magic != MAGIC_BYTES || footer.version != FOOTER_VERSION || footer.size != Self::SIZE as u32 {
+            return None;
+        }
+        Some(footer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_footer_round_trip() {
+        let footer = ConfigFooter {
+            magic: *MAGIC_BYTES,
+            version: FOOTER_VERSION,
+            size: ConfigFooter::SIZE as u32,
+            base_offset: 100,
+            base_size: 200,
+            overload_offset: 300,
+            overload_size: 400,
+            grace_period: 60,
+            sync_mode: 1,
+            network_failure_kill_count: 3,
+            overload_jail: JAIL_FILESYSTEM | JAIL_NETWORK,
+            order: ORDER_BASE_FIRST,
+            overload_start_delay: 10,
+            overload_interval: 3600,
+            health_check_interval: 1,
+            kill_delay_ms: 2500,
+            heartbeat_timeout: 30,
+            shutdown_grace: 20,
+            base_memory_limit_mb: 0,
+            base_cpu_percent: 0,
+            overload_memory_limit_mb: 256,
+            overload_cpu_percent: 25,
+            base_nofile_limit: 0,
+            overload_nofile_limit: 1024,
+            run_as_user: encode_padded::<RUN_AS_USER_LEN>("daemon").unwrap(),
+            drop_privileges: 1,
+            seccomp_offset: 500,
+            seccomp_size: 28,
+            hardening: HARDEN_CHECKSUM | HARDEN_ANTI_DEBUG,
+            image_checksum: 0x1234,
+            log_level: LOG_ERRORS,
+            log_target: LOG_TARGET_FILE,
+            log_path: encode_padded::<LOG_PATH_LEN>("/var/log/app.log").unwrap(),
+            product_name: encode_padded::<PRODUCT_NAME_LEN>("Acme Agent").unwrap(),
+            exec_strategy: EXEC_TMPFILE,
+            base_cwd_mode: CWD_BINARY_DIR,
+            base_cwd: [0; CWD_PATH_LEN],
+            overload_cwd_mode: CWD_PATH,
+            overload_cwd: encode_padded::<CWD_PATH_LEN>("/var/lib/app").unwrap(),
+            base_umask: UMASK_SET | 0o027,
+            overload_umask: 0,
+            single_instance: SINGLE_INSTANCE_SIGNAL,
+            instance_id: 0xfeed,
+            control_socket: 1,
+            revocation_url: encode_padded::<REVOCATION_URL_LEN>("http://licenses.example/acme").unwrap(),
+            revocation_interval: 600,
+            revocation_key: [7; REVOCATION_KEY_LEN],
+            expires_at: 1_900_000_000,
+            expiry_clock_checks: 1,
+            machine_binding: BIND_MACHINE_ID | BIND_MAC,
+            machine_fingerprints: [0xabcd; MAX_MACHINE_FINGERPRINTS],
+            audit_path: encode_padded::<AUDIT_PATH_LEN>("/var/log/app-audit.jsonl").unwrap(),
+            audit_url: [0; AUDIT_URL_LEN],
+            telemetry_url: encode_padded::<TELEMETRY_URL_LEN>("http://weaver.example/telemetry/1234").unwrap(),
+            labels_offset: 528,
+            labels_size: 17,
+            overload_kind: OVERLOAD_SCRIPT,
+            interpreter_offset: 545,
+            interpreter_size: 4096,
+            install_dir: encode_padded::<INSTALL_DIR_LEN>("/opt/acme").unwrap(),
+            install_name: encode_padded::<INSTALL_NAME_LEN>("acme-agent").unwrap(),
+            install_flags: INSTALL_SYSTEMD_UNIT,
+            install_id: 0xbeef,
+            install_files_offset: 4641,
+            install_files_size: 64,
+            assets_id: 0xa55e7,
+            assets_offset: 4705,
+            assets_size: 40,
+            prerequisite_runs: 0,
+            prerequisite_on_failure: PREREQ_RESTART,
+            prerequisite_max_failures: 5,
+            prerequisite_retry_delay: 30,
+            ipc_channel: 1,
+            kill_policy: [PolicyOp::new(kill_policy::OP_FLAGS, 0, 1).encode(); MAX_KILL_POLICY_OPS],
+            monitor_warmup: 120,
+            arm_after_success: 1,
+            crash_report: CRASH_REPORT_CORE_DUMP,
+            crash_stderr_lines: 50,
+            crash_report_url: encode_padded::<CRASH_REPORT_URL_LEN>("http://crash.example/reports").unwrap(),
+            output_log: OUTPUT_LOG_OVERLOAD,
+            output_log_max_files: 5,
+            output_log_max_size: 10,
+            output_log_dir: encode_padded::<OUTPUT_LOG_DIR_LEN>("logs").unwrap(),
+            base_priority: 0,
+            overload_priority: encode_priority(MAX_NICE).unwrap(),
+            base_cpu_affinity: 0,
+            overload_cpu_affinity: 0b1100,
+            readiness_timeout: 30,
+            readiness_probe: READINESS_TCP,
+            readiness_on_timeout: READINESS_START_BASE,
+            readiness_target: encode_padded::<READINESS_TARGET_LEN>("127.0.0.1:8080").unwrap(),
+            packing: PACKING_LZ4,
+            base_unpacked_size: 0,
+            overload_unpacked_size: 4096,
+        };
+
+        let mut bytes = [0u8; ConfigFooter::SIZE];
+        bytes.copy_from_slice(footer.as_bytes());
+        let parsed = ConfigFooter::from_bytes(&bytes).unwrap();
+
+        assert_eq!(parsed.overload_offset, 300);
+        assert_eq!(parsed.network_failure_kill_count, 3);
+        assert_eq!(parsed.overload_jail, JAIL_FILESYSTEM | JAIL_NETWORK);
+        assert_eq!(parsed.order, ORDER_BASE_FIRST);
+        assert_eq!(parsed.overload_interval, 3600);
+        assert_eq!(parsed.health_check_interval, 1);
+        assert_eq!(parsed.kill_delay_ms, 2500);
+        assert_eq!(parsed.heartbeat_timeout, 30);
+        assert_eq!(parsed.shutdown_grace, 20);
+        assert!(parsed.limits(true).is_empty());
+        assert_eq!(parsed.limits(false), ResourceLimits { memory_mb: 256, cpu_percent: 25, nofile: 1024 });
+        assert_eq!(parsed.run_as_user(), Some("daemon"));
+        assert_eq!(parsed.drop_privileges, 1);
+        assert_eq!((parsed.seccomp_offset, parsed.seccomp_size), (500, 28));
+        assert_eq!(parsed.hardening, HARDEN_CHECKSUM | HARDEN_ANTI_DEBUG);
+        assert_eq!(parsed.image_checksum, 0x1234);
+        assert_eq!((parsed.log_level, parsed.log_target), (LOG_ERRORS, LOG_TARGET_FILE));
+        assert_eq!(parsed.log_path(), Some("/var/log/app.log"));
+        assert_eq!(parsed.product_name(), Some("Acme Agent"));
+        assert_eq!(parsed.exec_strategy, EXEC_TMPFILE);
+        assert_eq!(parsed.cwd(true), (CWD_BINARY_DIR, None));
+        assert_eq!(parsed.cwd(false), (CWD_PATH, Some("/var/lib/app")));
+        assert_eq!(parsed.umask(true), Some(0o027));
+        assert_eq!(parsed.umask(false), None);
+        assert_eq!((parsed.single_instance, parsed.instance_id), (SINGLE_INSTANCE_SIGNAL, 0xfeed));
+        assert_eq!(parsed.control_socket, 1);
+        assert_eq!(parsed.revocation_url(), Some("http://licenses.example/acme"));
+        assert_eq!((parsed.revocation_interval, parsed.revocation_key), (600, [7; REVOCATION_KEY_LEN]));
+        assert_eq!((parsed.expires_at, parsed.expiry_clock_checks), (1_900_000_000, 1));
+        assert_eq!(parsed.machine_binding, BIND_MACHINE_ID | BIND_MAC);
+        assert_eq!(parsed.machine_fingerprints, [0xabcd; MAX_MACHINE_FINGERPRINTS]);
+        assert_eq!((parsed.audit_path(), parsed.audit_url()), (Some("/var/log/app-audit.jsonl"), None));
+        assert_eq!(parsed.telemetry_url(), Some("http://weaver.example/telemetry/1234"));
+        assert_eq!((parsed.labels_offset, parsed.labels_size), (528, 17));
+        assert_eq!((parsed.overload_kind, parsed.interpreter_offset, parsed.interpreter_size), (OVERLOAD_SCRIPT, 545, 4096));
+        assert_eq!((parsed.install_dir(), parsed.install_name()), (Some("/opt/acme"), Some("acme-agent")));
+        assert_eq!((parsed.install_flags, parsed.install_id), (INSTALL_SYSTEMD_UNIT, 0xbeef));
+        assert_eq!((parsed.install_files_offset, parsed.install_files_size), (4641, 64));
+        assert_eq!((parsed.assets_id, parsed.assets_offset, parsed.assets_size), (0xa55e7, 4705, 40));
+        assert_eq!((parsed.prerequisite_on_failure, parsed.prerequisite_max_failures), (PREREQ_RESTART, 5));
+        assert_eq!((parsed.prerequisite_retry_delay, parsed.ipc_channel), (30, 1));
+        assert!(parsed.has_kill_policy());
+        assert_eq!(parsed.kill_policy().count(), MAX_KILL_POLICY_OPS);
+        assert_eq!(ConfigFooter { kill_policy: [0; MAX_KILL_POLICY_OPS], ..parsed }.kill_policy().count(), 0);
+        assert_eq!((parsed.monitor_warmup, parsed.arm_after_success), (120, 1));
+        assert_eq!((parsed.crash_report, parsed.crash_stderr_lines), (CRASH_REPORT_CORE_DUMP, 50));
+        assert_eq!(parsed.crash_report_url(), Some("http://crash.example/reports"));
+        assert!(parsed.reports_crashes());
+        assert_eq!((parsed.logs_output(true), parsed.logs_output(false)), (false, true));
+        assert_eq!((parsed.output_log_max_files, parsed.output_log_max_size), (5, 10));
+        assert_eq!(parsed.output_log_dir(), Some("logs"));
+        assert_eq!((parsed.priority(true), parsed.priority(false)), (None, Some(MAX_NICE)));
+        assert_eq!((parsed.cpu_affinity(true), parsed.cpu_affinity(false)), (0, 0b1100));
+        let highest = ConfigFooter { base_priority: encode_priority(MIN_NICE).unwrap(), ..parsed };
+        assert_eq!(highest.priority(true), Some(MIN_NICE));
+        assert_eq!(encode_priority(MAX_NICE + 1), None);
+        assert_eq!((parsed.readiness_probe, parsed.readiness_on_timeout), (READINESS_TCP, READINESS_START_BASE));
+        assert_eq!((parsed.readiness_timeout, parsed.readiness_target()), (30, Some("127.0.0.1:8080")));
+        assert_eq!((parsed.unpacked_size(true), parsed.unpacked_size(false)), (None, Some(4096)));
+        assert_eq!(ConfigFooter { packing: PACKING_NONE, ..parsed }.unpacked_size(false), None);
+        // Sync mode is a single prerequisite run
+        assert_eq!(parsed.prerequisite_runs(), 1);
+        assert_eq!(ConfigFooter { prerequisite_runs: 3, ..parsed }.prerequisite_runs(), 3);
+        assert_eq!(ConfigFooter { sync_mode: 0, ..parsed }.prerequisite_runs(), 0);
+        // Schedules only apply when the overload goes first
+        assert!(!parsed.is_scheduled());
+    }
+
+    #[test]
+    fn test_run_as_user_encoding() {
+        let longest = [b'a'; RUN_AS_USER_LEN];
+        let longest = core::str::from_utf8(&longest).unwrap();
+        let too_long = [b'a'; RUN_AS_USER_LEN + 1];
+        assert!(encode_padded::<RUN_AS_USER_LEN>("").is_none());
+        assert!(encode_padded::<RUN_AS_USER_LEN>("nul\0name").is_none());
+        assert!(encode_padded::<RUN_AS_USER_LEN>(core::str::from_utf8(&too_long).unwrap()).is_none());
+
+        // No terminator needed when the name fills the field
+        let mut footer: ConfigFooter = unsafe { mem::zeroed() };
+        assert_eq!(footer.run_as_user(), None);
+        footer.run_as_user = encode_padded::<RUN_AS_USER_LEN>(longest).unwrap();
+        assert_eq!(footer.run_as_user(), Some(longest));
+    }
+
+    #[test]
+    fn test_image_checksum_ignores_its_own_field() {
+        let mut footer: ConfigFooter = unsafe { mem::zeroed() };
+        footer.magic = *MAGIC_BYTES;
+        footer.version = FOOTER_VERSION;
+        footer.size = ConfigFooter::SIZE as u32;
+        let mut bytes = [0u8; ConfigFooter::SIZE];
+        bytes.copy_from_slice(footer.as_bytes());
+
+        let checksum = |payloads: &[u8], footer: &[u8; ConfigFooter::SIZE]| {
+            let mut checksum = ImageChecksum::new();
+            checksum.update(payloads);
+            checksum.update_footer(footer);
+            checksum.finish()
+        };
+        let expected = checksum(b"stub+payloads", &bytes);
+
+        // Storing the checksum doesn't change it, anything else does
+        let mut stored = ImageChecksum::new();
+        stored.update(b"stub+payloads");
+        stored.update_footer(&bytes);
+        stored.write_into(&mut bytes);
+        assert_eq!(ConfigFooter::from_bytes(&bytes).unwrap().image_checksum, expected);
+        assert_eq!(checksum(b"stub+payloads", &bytes), expected);
+        assert_ne!(checksum(b"stub+payloadz", &bytes), expected);
+        bytes[8] ^= 1;
+        assert_ne!(checksum(b"stub+payloads", &bytes), expected);
+    }
+
+    #[test]
+    fn test_footer_rejects_bad_magic() {
+        let bytes = [0u8; ConfigFooter::SIZE];
+        assert!(ConfigFooter::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn test_footer_rejects_other_layouts() {
+        let mut footer: ConfigFooter = unsafe { mem::zeroed() };
+        footer.magic = *MAGIC_BYTES;
+        footer.version = FOOTER_VERSION;
+        footer.size = ConfigFooter::SIZE as u32;
+        let mut bytes = [0u8; ConfigFooter::SIZE];
+        bytes.copy_from_slice(footer.as_bytes());
+        assert!(ConfigFooter::from_bytes(&bytes).is_some());
+
+        footer.version = FOOTER_VERSION + 1;
+        bytes.copy_from_slice(footer.as_bytes());
+        assert!(ConfigFooter::from_bytes(&bytes).is_none());
+        footer.version = FOOTER_VERSION;
+        footer.size -= 8;
+        bytes.copy_from_slice(footer.as_bytes());
+        assert!(ConfigFooter::from_bytes(&bytes).is_none());
+    }
+
+    #[test]
+    fn test_stub_marker_names_the_layout() {
+        let mut stub = [0u8; 64];
+        assert_eq!(stub_layout(&stub), None);
+        stub[20..20 + STUB_MARKER_LEN].copy_from_slice(&stub_marker());
+        assert_eq!(stub_layout(&stub), Some((FOOTER_VERSION, ConfigFooter::SIZE as u32)));
+        // A marker cut off by the end of the file doesn't count
+        assert_eq!(stub_layout(&stub[..30]), None);
+    }
+}
@@ -0,0 +1,689 @@
+//! Health status shared between the loader stub and the overload.
+//!
+//! The stub creates the region (`shm_open` + `mmap` on Unix, a named file
+//! mapping on Windows) and publishes its name in `KILLCODE_HEALTH_SHM`; the
+//! overload maps the same region and reports into it.
+//!
+//! # Memory ordering
+//!
+//! Both processes touch the region concurrently, so every field is an atomic
+//! of explicit width. Plain `i64` accesses are not single-copy atomic on
+//! 32-bit targets and the stub could otherwise observe a torn `last_success`.
+//!
+//! * Writers publish with `Release`. `record_success` resets
+//!   `consecutive_failures` before storing `last_success`, so a reader that
+//!   observes the new timestamp also observes the reset counter.
+//! * Readers load with `Acquire`. Two fields are never read at the same
+//!   instant; [`HealthStatus::snapshot`] is a best-effort view, not a
+//!   transaction.
+//! * The kill flags are one-way: once set to 1 they are never cleared by the
+//!   other side.
+//!
+//! `AtomicI64` is 8-byte aligned on every target, so the struct is 56 bytes
+//! on both 32- and 64-bit platforms.
+//!
+//! # Header
+//!
+//! The region starts with [`HEALTH_MAGIC`], [`PROTOCOL_VERSION`] and
+//! [`LAYOUT_CHECKSUM`], written by the stub in [`HealthStatus::init`]. The
+//! checksum is derived from the size and field offsets, so two builds that
+//! disagree on the layout disagree on the checksum even if nobody bumped the
+//! version. Either side refuses a region whose header doesn't match its own
+//! (see [`HealthStatus::check_header`]).
+//!
+//! `stub_pid` took over the word after the header that used to be reserved.
+//! It is left out of the checksum so overloads built before it still accept
+//! the region; they read it as padding.
+//!
+//! # Heartbeat
+//!
+//! `is_alive` only catches an overload that says it is dead. An overload that
+//! hangs is caught by `last_heartbeat`, which it is expected to bump with
+//! [`HealthStatus::heartbeat`] more often than the footer's
+//! `heartbeat_timeout`. Overloads that never bump it keep working as long as
+//! no timeout is configured.
+//!
+//! # Slots
+//!
+//! The region is a [`HealthTable`] of up to [`MAX_HEALTH_SLOTS`] named
+//! [`HealthSlot`]s, one `HealthStatus` per payload the stub monitors. The
+//! stub names its slots in [`HealthTable::init`] before any payload starts
+//! and tells each payload its slot in `KILLCODE_HEALTH_SLOT`. The first
+//! slot's status starts at offset 0, so an overload that maps a single
+//! `HealthStatus` reports into the first slot.
+//!
+//! Each slot also carries 32 custom flags the payload sets and clears as it
+//! likes. They mean nothing to the stub unless the footer's kill policy
+//! tests them (see [`crate::kill_policy`]). A ready marker tells a stub
+//! waiting on the footer's `ready_flag` readiness probe that base can start.
+
+use core::fmt;
+use core::mem;
+use core::sync::atomic::{AtomicI32, AtomicI64, AtomicU32, AtomicU8, Ordering};
+
+/// How far in the future `last_success` may be before a snapshot is rejected.
+/// Covers small clock differences between the stub and the overload.
+pub const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+/// First word of every health region ("WVHS" little-endian)
+pub const HEALTH_MAGIC: u32 = u32::from_le_bytes(*b"WVHS");
+
+/// Bumped whenever the meaning of a field changes
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// FNV-1a over the struct size and every field offset
+pub const LAYOUT_CHECKSUM: u32 = layout_checksum();
+
+/// Payloads one region can monitor
+pub const MAX_HEALTH_SLOTS: usize = 8;
+
+/// Longest slot name in bytes
+pub const HEALTH_SLOT_NAME_LEN: usize = 24;
+
+/// Slot of the merge's overload, always the first one
+pub const OVERLOAD_SLOT: &str = "overload";
+
+#[repr(C)]
+pub struct HealthStatus {
+    magic: AtomicU32,                // HEALTH_MAGIC
+    version: AtomicU32,              // PROTOCOL_VERSION
+    layout_checksum: AtomicU32,      // LAYOUT_CHECKSUM
+    stub_pid: AtomicI32,             // PID of the stub that created the region
+    last_success: AtomicI64,         // Timestamp of last successful check (time_t)
+    consecutive_failures: AtomicI32, // Counter of network failures
+    is_alive: AtomicI32,             // Heartbeat flag (1=alive, 0=dead)
+    should_kill_base: AtomicI32,     // Signal from overload to kill base
+    parent_requests_kill: AtomicI32, // Signal from parent: kill yourself now
+    base_pid: AtomicI32,             // PID of the base process
+    last_heartbeat: AtomicI64,       // Timestamp of the overload's last heartbeat (time_t)
+}
+
+impl HealthStatus {
+    /// Size of the shared-memory region in bytes
+    pub const SIZE: usize = mem::size_of::<HealthStatus>();
+
+    /// Borrow a mapped region as a `HealthStatus`.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least [`HealthStatus::SIZE`] bytes of shared
+    /// memory that stay mapped for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u8) -> Result<&'a HealthStatus, HealthError> {
+        if ptr.is_null() {
+            return Err(HealthError::NullPointer);
+        }
+        if !(ptr as usize).is_multiple_of(mem::align_of::<HealthStatus>()) {
+            return Err(HealthError::Misaligned);
+        }
+        Ok(&*(ptr as *const HealthStatus))
+    }
+
+    /// Reset the region to its initial state and write the header. Called by
+    /// the stub before the overload is started.
+    pub fn init(&self, now: i64) {
+        self.magic.store(HEALTH_MAGIC, Ordering::Relaxed);
+        self.version.store(PROTOCOL_VERSION, Ordering::Relaxed);
+        self.layout_checksum.store(LAYOUT_CHECKSUM, Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.is_alive.store(1, Ordering::Relaxed);
+        self.should_kill_base.store(0, Ordering::Relaxed);
+        self.parent_requests_kill.store(0, Ordering::Relaxed);
+        self.base_pid.store(0, Ordering::Relaxed);
+        self.stub_pid.store(0, Ordering::Relaxed);
+        self.last_heartbeat.store(now, Ordering::Relaxed);
+        self.last_success.store(now, Ordering::Release);
+    }
+
+    /// Check the header matches this build of the protocol
+    pub fn check_header(&self) -> Result<(), HealthError> {
+        check_header(
+            self.magic.load(Ordering::Acquire),
+            self.version.load(Ordering::Acquire),
+            self.layout_checksum.load(Ordering::Acquire),
+        )
+    }
+
+    /// Protocol version written in the region header
+    pub fn version(&self) -> u32 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    // Overload side
+
+    /// Record a successful check at `now`, clearing the failure counter
+    pub fn record_success(&self, now: i64) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.last_success.store(now, Ordering::Release);
+    }
+
+    /// Record a failed check, returning the new consecutive failure count
+    pub fn record_failure(&self) -> i32 {
+        self.consecutive_failures.fetch_add(1, Ordering::Release) + 1
+    }
+
+    pub fn set_alive(&self, alive: bool) {
+        self.is_alive.store(alive as i32, Ordering::Release);
+    }
+
+    /// Prove the overload is still making progress at `now`
+    pub fn heartbeat(&self, now: i64) {
+        self.last_heartbeat.store(now, Ordering::Release);
+    }
+
+    /// Ask the stub to terminate the base
+    pub fn request_base_kill(&self) {
+        self.should_kill_base.store(1, Ordering::Release);
+    }
+
+    // Stub side
+
+    /// Ask the overload to run its kill method
+    pub fn request_overload_kill(&self) {
+        self.parent_requests_kill.store(1, Ordering::Release);
+    }
+
+    pub fn set_base_pid(&self, pid: i32) {
+        self.base_pid.store(pid, Ordering::Release);
+    }
+
+    pub fn set_stub_pid(&self, pid: i32) {
+        self.stub_pid.store(pid, Ordering::Release);
+    }
+
+    // Readers
+
+    pub fn last_success(&self) -> i64 {
+        self.last_success.load(Ordering::Acquire)
+    }
+
+    pub fn consecutive_failures(&self) -> i32 {
+        self.consecutive_failures.load(Ordering::Acquire)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.is_alive.load(Ordering::Acquire) != 0
+    }
+
+    pub fn base_kill_requested(&self) -> bool {
+        self.should_kill_base.load(Ordering::Acquire) != 0
+    }
+
+    pub fn overload_kill_requested(&self) -> bool {
+        self.parent_requests_kill.load(Ordering::Acquire) != 0
+    }
+
+    pub fn base_pid(&self) -> i32 {
+        self.base_pid.load(Ordering::Acquire)
+    }
+
+    pub fn stub_pid(&self) -> i32 {
+        self.stub_pid.load(Ordering::Acquire)
+    }
+
+    pub fn last_heartbeat(&self) -> i64 {
+        self.last_heartbeat.load(Ordering::Acquire)
+    }
+
+    /// Copy every field out of shared memory
+    pub fn snapshot(&self) -> HealthSnapshot {
+        HealthSnapshot {
+            magic: self.magic.load(Ordering::Acquire),
+            version: self.version.load(Ordering::Acquire),
+            layout_checksum: self.layout_checksum.load(Ordering::Acquire),
+            last_success: self.last_success.load(Ordering::Acquire),
+            consecutive_failures: self.consecutive_failures.load(Ordering::Acquire),
+            is_alive: self.is_alive.load(Ordering::Acquire),
+            should_kill_base: self.should_kill_base.load(Ordering::Acquire),
+            parent_requests_kill: self.parent_requests_kill.load(Ordering::Acquire),
+            base_pid: self.base_pid.load(Ordering::Acquire),
+            stub_pid: self.stub_pid.load(Ordering::Acquire),
+            last_heartbeat: self.last_heartbeat.load(Ordering::Acquire),
+        }
+    }
+}
+
+/// Plain copy of a [`HealthStatus`], safe to inspect without further
+/// synchronization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthSnapshot {
+    pub magic: u32,
+    pub version: u32,
+    pub layout_checksum: u32,
+    pub last_success: i64,
+    pub consecutive_failures: i32,
+    pub is_alive: i32,
+    pub should_kill_base: i32,
+    pub parent_requests_kill: i32,
+    pub base_pid: i32,
+    pub stub_pid: i32,
+    pub last_heartbeat: i64,
+}
+
+impl HealthSnapshot {
+    /// Reject values a well-behaved writer can never produce. A region that
+    /// fails validation was scribbled on by something other than the
+    /// protocol and its contents should not drive kill decisions.
+    pub fn validate(&self, now: i64) -> Result<(), HealthError> {
+        check_header(self.magic, self.version, self.layout_checksum)?;
+        for (field, value) in [
+            ("is_alive", self.is_alive),
+            ("should_kill_base", self.should_kill_base),
+            ("parent_requests_kill", self.parent_requests_kill),
+        ] {
+            if value != 0 && value != 1 {
+                return Err(HealthError::InvalidFlag { field, value });
+            }
+        }
+        if self.consecutive_failures < 0 {
+            return Err(HealthError::NegativeFailureCount(self.consecutive_failures));
+        }
+        for (field, pid) in [("base_pid", self.base_pid), ("stub_pid", self.stub_pid)] {
+            if pid < 0 {
+                return Err(HealthError::InvalidPid { field, pid });
+            }
+        }
+        if self.last_success > now + MAX_CLOCK_SKEW_SECS {
+            return Err(HealthError::TimestampInFuture {
+                last_success: self.last_success,
+                now,
+            });
+        }
+        if self.last_heartbeat > now + MAX_CLOCK_SKEW_SECS {
+            return Err(HealthError::HeartbeatInFuture {
+                last_heartbeat: self.last_heartbeat,
+                now,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// One monitored payload's [`HealthStatus`] and the name it goes by
+#[repr(C)]
+pub struct HealthSlot {
+    status: HealthStatus,
+    name: [AtomicU8; HEALTH_SLOT_NAME_LEN], // NUL-padded; all zero = free
+    flags: AtomicU32,
+    ready: AtomicU32,
+}
+
+impl HealthSlot {
+    pub fn status(&self) -> &HealthStatus {
+        &self.status
+    }
+
+    pub fn name(&self) -> SlotName {
+        let mut name = [0; HEALTH_SLOT_NAME_LEN];
+        for (byte, stored) in name.iter_mut().zip(&self.name) {
+            *byte = stored.load(Ordering::Acquire);
+        }
+        SlotName(name)
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.name[0].load(Ordering::Acquire) == 0
+    }
+
+    /// Custom flags set by the payload
+    pub fn flags(&self) -> u32 {
+        self.flags.load(Ordering::Acquire)
+    }
+
+    pub fn set_flags(&self, mask: u32) {
+        self.flags.fetch_or(mask, Ordering::Release);
+    }
+
+    pub fn clear_flags(&self, mask: u32) {
+        self.flags.fetch_and(!mask, Ordering::Release);
+    }
+
+    /// The payload finished starting up
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire) != 0
+    }
+
+    pub fn set_ready(&self) {
+        self.ready.store(1, Ordering::Release);
+    }
+
+    fn set_name(&self, name: &str) {
+        let mut bytes = name.bytes();
+        for stored in &self.name {
+            stored.store(bytes.next().unwrap_or(0), Ordering::Release);
+        }
+    }
+}
+
+/// Copy of a slot name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotName([u8; HEALTH_SLOT_NAME_LEN]);
+
+impl SlotName {
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(self.0.len());
+        core::str::from_utf8(&self.0[..len]).unwrap_or("")
+    }
+}
+
+impl fmt::Display for SlotName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The whole shared region: one slot per monitored payload
+#[repr(C)]
+pub struct HealthTable {
+    slots: [HealthSlot; MAX_HEALTH_SLOTS],
+}
+
+impl HealthTable {
+    /// Size of the shared-memory region in bytes
+    pub const SIZE: usize = mem::size_of::<HealthTable>();
+
+    /// Borrow a mapped region as a `HealthTable`.
+    ///
+    /// # Safety
+    /// `ptr` must point to at least [`HealthTable::SIZE`] bytes of shared
+    /// memory that stay mapped for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *mut u8) -> Result<&'a HealthTable, HealthError> {
+        HealthStatus::from_ptr(ptr)?;
+        Ok(&*(ptr as *const HealthTable))
+    }
+
+    /// Free every slot, then give each of `names` one, in order. Called by
+    /// the stub before any payload is started.
+    pub fn init(&self, names: &[&str], now: i64) -> Result<(), HealthError> {
+        if names.len() > MAX_HEALTH_SLOTS {
+            return Err(HealthError::TooManySlots(names.len()));
+        }
+        if let Some(name) = names.iter().find(|name| name.is_empty() || name.len() > HEALTH_SLOT_NAME_LEN) {
+            return Err(HealthError::InvalidSlotName(name.len()));
+        }
+        for (i, slot) in self.slots.iter().enumerate() {
+            match names.get(i) {
+                Some(name) => {
+                    slot.status.init(now);
+                    slot.flags.store(0, Ordering::Release);
+                    slot.ready.store(0, Ordering::Release);
+                    slot.set_name(name);
+                }
+                None => slot.set_name(""),
+            }
+        }
+        Ok(())
+    }
+
+    /// Slots in use, in the order they were named
+    pub fn slots(&self) -> impl Iterator<Item = &HealthSlot> {
+        self.slots.iter().take_while(|slot| !slot.is_free())
+    }
+
+    pub fn slot(&self, name: &str) -> Option<&HealthSlot> {
+        self.slots().find(|slot| slot.name().as_str() == name)
+    }
+
+    pub fn set_base_pid(&self, pid: i32) {
+        self.slots().for_each(|slot| slot.status.set_base_pid(pid));
+    }
+
+    pub fn set_stub_pid(&self, pid: i32) {
+        self.slots().for_each(|slot| slot.status.set_stub_pid(pid));
+    }
+}
+
+fn check_header(magic: u32, version: u32, layout_checksum: u32) -> Result<(), HealthError> {
+    if magic != HEALTH_MAGIC {
+        return Err(HealthError::BadMagic(magic));
+    }
+    if version != PROTOCOL_VERSION {
+        return Err(HealthError::UnsupportedVersion(version));
+    }
+    if layout_checksum != LAYOUT_CHECKSUM {
+        return Err(HealthError::LayoutMismatch(layout_checksum));
+    }
+    Ok(())
+}
+
+const fn layout_checksum() -> u32 {
+    let words = [
+        mem::size_of::<HealthStatus>(),
+        mem::offset_of!(HealthStatus, magic),
+        mem::offset_of!(HealthStatus, version),
+        mem::offset_of!(HealthStatus, layout_checksum),
+        mem::offset_of!(HealthStatus, last_success),
+        mem::offset_of!(HealthStatus, consecutive_failures),
+        mem::offset_of!(HealthStatus, is_alive),
+        mem::offset_of!(HealthStatus, should_kill_base),
+        mem::offset_of!(HealthStatus, parent_requests_kill),
+        mem::offset_of!(HealthStatus, base_pid),
+        mem::offset_of!(HealthStatus, last_heartbeat),
+    ];
+
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut i = 0;
+    while i < words.len() {
+        let bytes = (words[i] as u32).to_le_bytes();
+        let mut j = 0;
+        while j < bytes.len() {
+            hash ^= bytes[j] as u32;
+            hash = hash.wrapping_mul(0x0100_0193);
+            j += 1;
+        }
+        i += 1;
+    }
+    hash
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthError {
+    NullPointer,
+    Misaligned,
+    BadMagic(u32),
+    UnsupportedVersion(u32),
+    LayoutMismatch(u32),
+    InvalidFlag { field: &'static str, value: i32 },
+    NegativeFailureCount(i32),
+    InvalidPid { field: &'static str, pid: i32 },
+    TimestampInFuture { last_success: i64, now: i64 },
+    HeartbeatInFuture { last_heartbeat: i64, now: i64 },
+    TooManySlots(usize),
+    InvalidSlotName(usize),
+}
+
+impl fmt::Display for HealthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthError::NullPointer => write!(f, "health region pointer is null"),
+            HealthError::Misaligned => write!(f, "health region is not 8-byte aligned"),
+            HealthError::BadMagic(magic) => write!(f, "bad health region magic: {:#010x}", magic),
+            HealthError::UnsupportedVersion(version) => write!(
+                f,
+                "health protocol version {} is not supported (expected {})",
+                version, PROTOCOL_VERSION
+            ),
+            HealthError::LayoutMismatch(checksum) => write!(
+                f,
+                "health region layout checksum {:#010x} does not match {:#010x}",
+                checksum, LAYOUT_CHECKSUM
+            ),
+            HealthError::InvalidFlag { field, value } => {
+                write!(f, "{} must be 0 or 1, found {}", field, value)
+            }
+            HealthError::NegativeFailureCount(n) => write!(f, "negative failure count: {}", n),
+            HealthError::InvalidPid { field, pid } => write!(f, "invalid {}: {}", field, pid),
+            HealthError::TimestampInFuture { last_success, now } => write!(
+                f,
+                "last_success {} is ahead of current time {}",
+                last_success, now
+            ),
+            HealthError::HeartbeatInFuture { last_heartbeat, now } => write!(
+                f,
+                "last_heartbeat {} is ahead of current time {}",
+                last_heartbeat, now
+            ),
+            HealthError::TooManySlots(count) => {
+                write!(f, "{} health slots requested, at most {} fit", count, MAX_HEALTH_SLOTS)
+            }
+            HealthError::InvalidSlotName(len) => write!(
+                f,
+                "health slot names are 1 to {} bytes, got {}",
+                HEALTH_SLOT_NAME_LEN, len
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_layout_is_stable() {
+        assert_eq!(HealthStatus::SIZE, 56);
+        assert_eq!(mem::align_of::<HealthStatus>(), 8);
+        assert_eq!(mem::offset_of!(HealthStatus, magic), 0);
+        assert_eq!(mem::offset_of!(HealthStatus, version), 4);
+        assert_eq!(mem::offset_of!(HealthStatus, layout_checksum), 8);
+        assert_eq!(mem::offset_of!(HealthStatus, stub_pid), 12);
+        assert_eq!(mem::offset_of!(HealthStatus, last_success), 16);
+        assert_eq!(mem::offset_of!(HealthStatus, consecutive_failures), 24);
+        assert_eq!(mem::offset_of!(HealthStatus, is_alive), 28);
+        assert_eq!(mem::offset_of!(HealthStatus, should_kill_base), 32);
+        assert_eq!(mem::offset_of!(HealthStatus, parent_requests_kill), 36);
+        assert_eq!(mem::offset_of!(HealthStatus, base_pid), 40);
+        assert_eq!(mem::offset_of!(HealthStatus, last_heartbeat), 48);
+
+        // The first slot's status is where a single HealthStatus used to be
+        assert_eq!(mem::size_of::<HealthSlot>(), 88);
+        assert_eq!(mem::offset_of!(HealthSlot, status), 0);
+        assert_eq!(mem::offset_of!(HealthSlot, name), 56);
+        assert_eq!(mem::offset_of!(HealthSlot, flags), 80);
+        assert_eq!(HealthTable::SIZE, 88 * MAX_HEALTH_SLOTS);
+    }
+
+    #[test]
+    fn test_slots_are_named_in_order() {
+        let table = unsafe { mem::zeroed::<HealthTable>() };
+        assert_eq!(table.slots().count(), 0);
+
+        table.init(&[OVERLOAD_SLOT, "dongle"], 0).unwrap();
+        let names: std::vec::Vec<_> = table.slots().map(|slot| slot.name()).collect();
+        assert_eq!(names[0].as_str(), OVERLOAD_SLOT);
+        assert_eq!(names[1].as_str(), "dongle");
+        assert_eq!(table.slot("dongle").unwrap().status().check_header(), Ok(()));
+        assert!(table.slot("missing").is_none());
+
+        table.set_base_pid(42);
+        assert!(table.slots().all(|slot| slot.status().base_pid() == 42));
+
+        let dongle = table.slot("dongle").unwrap();
+        dongle.set_flags(0b101);
+        dongle.clear_flags(0b001);
+        assert_eq!(dongle.flags(), 0b100);
+        assert!(!dongle.is_ready());
+        dongle.set_ready();
+        assert!(dongle.is_ready());
+        table.slot(OVERLOAD_SLOT).unwrap().set_ready();
+
+        // Re-initializing frees slots that are no longer named
+        table.init(&[OVERLOAD_SLOT], 0).unwrap();
+        assert_eq!(table.slots().count(), 1);
+        assert!(!table.slot(OVERLOAD_SLOT).unwrap().is_ready());
+        assert_eq!(table.init(&[""], 0), Err(HealthError::InvalidSlotName(0)));
+        assert_eq!(
+            table.init(&["x"; MAX_HEALTH_SLOTS + 1], 0),
+            Err(HealthError::TooManySlots(MAX_HEALTH_SLOTS + 1))
+        );
+    }
+
+    #[test]
+    fn test_validation_rejects_garbage() {
+        let mut snapshot = HealthSnapshot {
+            magic: HEALTH_MAGIC,
+            version: PROTOCOL_VERSION,
+            layout_checksum: LAYOUT_CHECKSUM,
+            last_success: 1_000,
+            consecutive_failures: 0,
+            is_alive: 1,
+            should_kill_base: 0,
+            parent_requests_kill: 0,
+            base_pid: 42,
+            stub_pid: 41,
+            last_heartbeat: 1_000,
+        };
+        assert_eq!(snapshot.validate(1_000), Ok(()));
+
+        snapshot.is_alive = 7;
+        assert_eq!(
+            snapshot.validate(1_000),
+            Err(HealthError::InvalidFlag { field: "is_alive", value: 7 })
+        );
+
+        snapshot.is_alive = 1;
+        snapshot.stub_pid = -1;
+        assert_eq!(
+            snapshot.validate(1_000),
+            Err(HealthError::InvalidPid { field: "stub_pid", pid: -1 })
+        );
+
+        snapshot.stub_pid = 41;
+        snapshot.last_success = 1_000 + MAX_CLOCK_SKEW_SECS + 1;
+        assert!(matches!(
+            snapshot.validate(1_000),
+            Err(HealthError::TimestampInFuture { .. })
+        ));
+
+        snapshot.last_success = 1_000;
+        snapshot.last_heartbeat = 1_000 + MAX_CLOCK_SKEW_SECS + 1;
+        assert!(matches!(
+            snapshot.validate(1_000),
+            Err(HealthError::HeartbeatInFuture { .. })
+        ));
+    }
+
+    #[test]
+    fn test_header_is_written_and_checked() {
+        let status = unsafe { mem::zeroed::<HealthStatus>() };
+        assert_eq!(status.check_header(), Err(HealthError::BadMagic(0)));
+
+        status.init(0);
+        assert_eq!(status.check_header(), Ok(()));
+        assert_eq!(status.version(), PROTOCOL_VERSION);
+
+        status.version.store(PROTOCOL_VERSION + 1, Ordering::Relaxed);
+        assert_eq!(
+            status.snapshot().validate(0),
+            Err(HealthError::UnsupportedVersion(PROTOCOL_VERSION + 1))
+        );
+
+        status.version.store(PROTOCOL_VERSION, Ordering::Relaxed);
+        status.layout_checksum.store(LAYOUT_CHECKSUM ^ 1, Ordering::Relaxed);
+        assert_eq!(status.check_header(), Err(HealthError::LayoutMismatch(LAYOUT_CHECKSUM ^ 1)));
+    }
+
+    #[test]
+    fn test_concurrent_failures_are_not_lost() {
+        let status = Arc::new(unsafe { mem::zeroed::<HealthStatus>() });
+        status.init(0);
+
+        let workers: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let status = status.clone();
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        status.record_failure();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(status.consecutive_failures(), 40_000);
+    }
+}
@@ -0,0 +1,201 @@
+//! Kill policy the stub's health monitor evaluates (`ConfigFooter::kill_policy`).
+//!
+//! A policy is a list of rules. Each rule is a condition in postfix form
+//! followed by [`OP_RULE`], which pops the condition and names the action to
+//! take when it holds:
+//!
+//! `since_success > 300 || failures >= 3` → kill base is stored as
+//! `[GREATER since_success 300] [AT_LEAST failures 3] [OR] [RULE kill_base]`.
+//!
+//! Every health check runs the rules in order against each slot of the
+//! health table. `log` rules are reported and the next rule runs; the first
+//! other rule that holds is acted on. A zeroed policy (the first op is
+//! [`OP_END`]) means the stub builds the classic checks from `grace_period`,
+//! `network_failure_kill_count` and `heartbeat_timeout` instead.
+//!
+//! Ops are packed into a `u64` each: opcode in bits 0-7, field in bits 8-15,
+//! operand in bits 32-63.
+
+/// Most ops a policy can hold
+pub const MAX_KILL_POLICY_OPS: usize = 32;
+/// Deepest the evaluation stack may get within one rule
+pub const MAX_KILL_POLICY_DEPTH: usize = 8;
+
+// Opcodes (`PolicyOp::code`)
+
+/// End of the policy; everything after it is ignored
+pub const OP_END: u8 = 0;
+/// Push whether the field is greater than the operand
+pub const OP_GREATER: u8 = 1;
+/// Push whether the field is at least the operand
+pub const OP_AT_LEAST: u8 = 2;
+/// Push whether any of the custom flags in the operand mask are set
+pub const OP_FLAGS: u8 = 3;
+/// Pop two values and push whether both hold
+pub const OP_AND: u8 = 4;
+/// Pop two values and push whether either holds
+pub const OP_OR: u8 = 5;
+/// Negate the top value
+pub const OP_NOT: u8 = 6;
+/// Pop the rule's condition; the operand is the `ACTION_*` to take
+pub const OP_RULE: u8 = 7;
+
+// What `OP_GREATER`/`OP_AT_LEAST` compare (`PolicyOp::field`)
+
+/// Seconds since the payload last reported a successful check
+pub const FIELD_SINCE_SUCCESS: u8 = 0;
+/// Consecutive failed checks the payload has reported
+pub const FIELD_FAILURES: u8 = 1;
+/// Seconds since the payload last bumped its heartbeat
+pub const FIELD_SINCE_HEARTBEAT: u8 = 2;
+/// 1 once the payload has asked for base to be killed
+pub const FIELD_KILL_REQUESTED: u8 = 3;
+/// 1 while the payload says it is alive
+pub const FIELD_ALIVE: u8 = 4;
+
+// What a matching rule does (`OP_RULE` operand)
+
+/// Log that the rule matched and keep checking
+pub const ACTION_LOG: u32 = 0;
+/// Terminate base
+pub const ACTION_KILL_BASE: u32 = 1;
+/// Ask the payload to run its kill method, then terminate base if it hasn't exited
+pub const ACTION_KILL_OVERLOAD: u32 = 2;
+/// Shut the merged binary down as if it had received a termination request
+pub const ACTION_EXIT: u32 = 3;
+
+/// One decoded policy op
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolicyOp {
+    /// `OP_*` value
+    pub code: u8,
+    /// `FIELD_*` value for comparisons, 0 otherwise
+    pub field: u8,
+    /// Threshold, flag mask or action
+    pub operand: u32,
+}
+
+impl PolicyOp {
+    pub const fn new(code: u8, field: u8, operand: u32) -> Self {
+        PolicyOp { code, field, operand }
+    }
+
+    pub const fn encode(self) -> u64 {
+        self.code as u64 | (self.field as u64) << 8 | (self.operand as u64) << 32
+    }
+
+    pub const fn decode(op: u64) -> Self {
+        PolicyOp {
+            code: op as u8,
+            field: (op >> 8) as u8,
+            operand: (op >> 32) as u32,
+        }
+    }
+}
+
+/// Ops of a stored policy, up to its [`OP_END`]
+pub fn ops(policy: &[u64]) -> impl Iterator<Item = PolicyOp> + '_ {
+    policy.iter().map(|&op| PolicyOp::decode(op)).take_while(|op| op.code != OP_END)
+}
+
+/// Why a policy can't be evaluated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyError {
+    /// More than [`MAX_KILL_POLICY_OPS`] ops
+    TooLong,
+    /// An op at this index has an unknown opcode, field or action
+    InvalidOp(usize),
+    /// The op at this index needs more values than are on the stack, or
+    /// pushes past [`MAX_KILL_POLICY_DEPTH`]
+    BadStack(usize),
+    /// Ops after the last `OP_RULE` that no rule uses
+    Unterminated,
+}
+
+impl core::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PolicyError::TooLong => write!(f, "kill policy has more than {} ops", MAX_KILL_POLICY_OPS),
+            PolicyError::InvalidOp(index) => write!(f, "kill policy op {} is invalid", index),
+            PolicyError::BadStack(index) => write!(f, "kill policy op {} over- or underflows the stack", index),
+            PolicyError::Unterminated => write!(f, "kill policy ends in the middle of a rule"),
+        }
+    }
+}
+
+/// Check that every rule leaves exactly one value for its `OP_RULE`, so
+/// evaluating the policy can't fail halfway
+pub fn validate(policy: &[PolicyOp]) -> Result<(), PolicyError> {
+    if policy.len() > MAX_KILL_POLICY_OPS {
+        return Err(PolicyError::TooLong);
+    }
+    let mut depth = 0;
+    for (index, op) in policy.iter().enumerate() {
+        let (pops, pushes) = match op.code {
+            OP_GREATER | OP_AT_LEAST if op.field <= FIELD_ALIVE => (0, 1),
+            OP_FLAGS => (0, 1),
+            OP_AND | OP_OR => (2, 1),
+            OP_NOT => (1, 1),
+            OP_RULE if op.operand <= ACTION_EXIT => (1, 0),
+            _ => return Err(PolicyError::InvalidOp(index)),
+        };
+        if depth < pops || depth - pops + pushes > MAX_KILL_POLICY_DEPTH {
+            return Err(PolicyError::BadStack(index));
+        }
+        depth = depth - pops + pushes;
+        if op.code == OP_RULE && depth != 0 {
+            return Err(PolicyError::BadStack(index));
+        }
+    }
+    if depth != 0 {
+        return Err(PolicyError::Unterminated);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ops_round_trip() {
+        let op = PolicyOp::new(OP_GREATER, FIELD_SINCE_HEARTBEAT, u32::MAX);
+        assert_eq!(PolicyOp::decode(op.encode()), op);
+
+        let stored = [
+            PolicyOp::new(OP_FLAGS, 0, 1 << 3).encode(),
+            PolicyOp::new(OP_RULE, 0, ACTION_LOG).encode(),
+            0,
+            PolicyOp::new(OP_NOT, 0, 0).encode(),
+        ];
+        let mut decoded = ops(&stored);
+        assert_eq!(decoded.next(), Some(PolicyOp::new(OP_FLAGS, 0, 1 << 3)));
+        assert_eq!(decoded.next(), Some(PolicyOp::new(OP_RULE, 0, ACTION_LOG)));
+        assert_eq!(decoded.next(), None);
+    }
+
+    #[test]
+    fn test_validation_tracks_the_stack() {
+        let rule = [
+            PolicyOp::new(OP_GREATER, FIELD_SINCE_SUCCESS, 300),
+            PolicyOp::new(OP_AT_LEAST, FIELD_FAILURES, 3),
+            PolicyOp::new(OP_OR, 0, 0),
+            PolicyOp::new(OP_RULE, 0, ACTION_KILL_BASE),
+        ];
+        assert_eq!(validate(&rule), Ok(()));
+        assert_eq!(validate(&[]), Ok(()));
+
+        // A dangling value before OP_RULE, an OR short of operands, no OP_RULE at all
+        assert_eq!(validate(&[rule[0], rule[1], rule[3]]), Err(PolicyError::BadStack(2)));
+        assert_eq!(validate(&[rule[0], rule[2]]), Err(PolicyError::BadStack(1)));
+        assert_eq!(validate(&rule[..3]), Err(PolicyError::Unterminated));
+
+        assert_eq!(validate(&[PolicyOp::new(OP_GREATER, 9, 0)]), Err(PolicyError::InvalidOp(0)));
+        assert_eq!(validate(&[rule[0], PolicyOp::new(OP_RULE, 0, 9)]), Err(PolicyError::InvalidOp(1)));
+        assert_eq!(
+            validate(&[rule[0]; MAX_KILL_POLICY_DEPTH + 1]),
+            Err(PolicyError::BadStack(MAX_KILL_POLICY_DEPTH))
+        );
+        assert_eq!(validate(&[rule[0]; MAX_KILL_POLICY_OPS + 1]), Err(PolicyError::TooLong));
+    }
+}
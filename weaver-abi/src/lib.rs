@@ -0,0 +1,19 @@
+//! Binary layouts shared between the Weaver service, the loader stub and
+//! overload payloads.
+//!
+//! Everything in this crate is `#[repr(C)]` and is read or written by more
+//! than one process, so field order and sizes are part of the contract.
+#![no_std]
+
+pub mod bundle;
+pub mod footer;
+pub mod health;
+pub mod kill_policy;
+pub mod pack;
+pub mod seccomp;
+
+pub use bundle::BundleEntry;
+pub use footer::{ConfigFooter, ImageChecksum, ResourceLimits};
+pub use health::{HealthError, HealthSlot, HealthSnapshot, HealthStatus, HealthTable, PROTOCOL_VERSION};
+pub use kill_policy::{PolicyError, PolicyOp};
+pub use seccomp::SeccompHeader;
@@ -0,0 +1,229 @@
+//! Payload compression for packed merged binaries (`ConfigFooter::packing`).
+//!
+//! A packed payload is one raw LZ4 block: no frame header and no checksum, the
+//! footer keeps the unpacked size instead. Both directions work on buffers the
+//! caller provides, so neither side needs an allocator here; the service
+//! sizes its output with `max_packed_len`, the stub with the unpacked size.
+
+/// Shortest match a sequence can encode
+const MIN_MATCH: usize = 4;
+/// A match may not start in the last 12 bytes of the input
+const MF_LIMIT: usize = 12;
+/// The last 5 bytes of the input are always literals
+const LAST_LITERALS: usize = 5;
+/// Furthest back a match can refer to
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_LOG: u32 = 12;
+
+/// Why a packed payload can't be unpacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnpackError {
+    /// The block ends in the middle of a sequence
+    Truncated,
+    /// A match refers to bytes before the start of the output
+    BadOffset,
+    /// The block unpacks to a different size than the footer says
+    SizeMismatch,
+}
+
+/// Bytes an input of `len` bytes can take once packed, at most
+pub const fn max_packed_len(len: usize) -> usize {
+    len + len / 255 + 16
+}
+
+/// Pack `input` into `out` and return the packed length
+///
+/// `out` has to hold at least `max_packed_len(input.len())` bytes.
+pub fn pack(input: &[u8], out: &mut [u8]) -> usize {
+    assert!(out.len() >= max_packed_len(input.len()), "output buffer too small");
+    // Last position + 1 of each 4-byte hash, 0 for none yet
+    let mut table = [0usize; 1 << HASH_LOG];
+    let mut written = 0;
+    let mut anchor = 0;
+    let mut pos = 0;
+    if input.len() > MF_LIMIT {
+        let match_limit = input.len() - MF_LIMIT;
+        let extend_limit = input.len() - LAST_LITERALS;
+        while pos < match_limit {
+            let sequence = read_u32(input, pos);
+            let slot = &mut table[hash(sequence)];
+            let candidate = slot.checked_sub(1);
+            *slot = pos + 1;
+            let found = candidate.filter(|&candidate| pos - candidate <= MAX_OFFSET && read_u32(input, candidate) == sequence);
+            let Some(candidate) = found else {
+                // Skip ahead faster the longer nothing matched
+                pos += 1 + ((pos - anchor) >> 6);
+                continue;
+            };
+            let mut len = MIN_MATCH;
+            while pos + len < extend_limit && input[candidate + len] == input[pos + len] {
+                len += 1;
+            }
+            written = write_sequence(out, written, &input[anchor..pos], Some((pos - candidate, len)));
+            pos += len;
+            anchor = pos;
+        }
+    }
+    write_sequence(out, written, &input[anchor..], None)
+}
+
+/// Unpack the block in `packed` into `out`, which it has to fill exactly
+pub fn unpack(packed: &[u8], out: &mut [u8]) -> Result<(), UnpackError> {
+    let mut read = 0;
+    let mut written = 0;
+    loop {
+        let token = *packed.get(read).ok_or(UnpackError::Truncated)?;
+        read += 1;
+
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(packed, &mut read)?;
+        }
+        let end = read.checked_add(literals).ok_or(UnpackError::Truncated)?;
+        let source = packed.get(read..end).ok_or(UnpackError::Truncated)?;
+        let target = written + literals;
+        out.get_mut(written..target).ok_or(UnpackError::SizeMismatch)?.copy_from_slice(source);
+        read = end;
+        written = target;
+        // The last sequence is literals only
+        if read == packed.len() {
+            break;
+        }
+
+        let offset = packed.get(read..read + 2).ok_or(UnpackError::Truncated)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        read += 2;
+        if offset == 0 || offset > written {
+            return Err(UnpackError::BadOffset);
+        }
+        let mut len = (token & 0xf) as usize + MIN_MATCH;
+        if token & 0xf == 0xf {
+            len += read_length(packed, &mut read)?;
+        }
+        let target = written.checked_add(len).filter(|&target| target <= out.len()).ok_or(UnpackError::SizeMismatch)?;
+        if offset >= len {
+            out.copy_within(written - offset..target - offset, written);
+        } else {
+            // Overlapping match: repeats the last `offset` bytes
+            for i in written..target {
+                out[i] = out[i - offset];
+            }
+        }
+        written = target;
+    }
+    if written != out.len() {
+        return Err(UnpackError::SizeMismatch);
+    }
+    Ok(())
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+/// Write `literals` and then the match `(offset, len)`, if any; returns the new end of `out`
+fn write_sequence(out: &mut [u8], mut written: usize, literals: &[u8], matched: Option<(usize, usize)>) -> usize {
+    let token = written;
+    let match_nibble = matched.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    out[token] = ((literals.len().min(15) as u8) << 4) | match_nibble as u8;
+    written += 1;
+    if literals.len() >= 15 {
+        written = write_length(out, written, literals.len() - 15);
+    }
+    out[written..written + literals.len()].copy_from_slice(literals);
+    written += literals.len();
+    if let Some((offset, len)) = matched {
+        out[written..written + 2].copy_from_slice(&(offset as u16).to_le_bytes());
+        written += 2;
+        if len - MIN_MATCH >= 15 {
+            written = write_length(out, written, len - MIN_MATCH - 15);
+        }
+    }
+    written
+}
+
+fn write_length(out: &mut [u8], mut written: usize, mut len: usize) -> usize {
+    while len >= 255 {
+        out[written] = 255;
+        written += 1;
+        len -= 255;
+    }
+    out[written] = len as u8;
+    written + 1
+}
+
+fn read_length(packed: &[u8], read: &mut usize) -> Result<usize, UnpackError> {
+    let mut len = 0usize;
+    loop {
+        let byte = *packed.get(*read).ok_or(UnpackError::Truncated)?;
+        *read += 1;
+        len = len.checked_add(byte as usize).ok_or(UnpackError::SizeMismatch)?;
+        if byte != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 70_000;
+
+    fn round_trip(input: &[u8]) -> usize {
+        let mut packed = [0u8; max_packed_len(LEN)];
+        let packed_len = pack(input, &mut packed);
+        let mut unpacked = [0u8; LEN];
+        unpack(&packed[..packed_len], &mut unpacked[..input.len()]).unwrap();
+        assert_eq!(&unpacked[..input.len()], input);
+        packed_len
+    }
+
+    #[test]
+    fn test_pack_round_trips() {
+        let mut noise = [0u8; LEN];
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for byte in noise.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        let mut text = [0u8; LEN];
+        for (i, byte) in text.iter_mut().enumerate() {
+            *byte = b"overload payload "[i % 17] ^ (i / 5000) as u8;
+        }
+
+        for len in [0, 1, 5, 12, 13, 20, 300] {
+            round_trip(&noise[..len]);
+            round_trip(&[7; 300][..len]);
+        }
+        assert!(round_trip(&noise) <= max_packed_len(LEN));
+        // Long runs need extended match lengths, repeated text back references
+        assert!(round_trip(&[0; LEN]) < 300);
+        assert!(round_trip(&text) < LEN / 10);
+        // Noise with a long repeat further back than a match can reach
+        noise[LEN - 1000..].copy_from_slice(&text[..1000]);
+        noise[..1000].copy_from_slice(&text[..1000]);
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn test_unpack_rejects_damaged_blocks() {
+        let input = [b'a'; 100];
+        let mut packed = [0u8; max_packed_len(100)];
+        let packed_len = pack(&input, &mut packed);
+        let mut out = [0u8; 100];
+
+        assert_eq!(unpack(&packed[..packed_len], &mut out[..99]), Err(UnpackError::SizeMismatch));
+        assert_eq!(unpack(&packed[..packed_len], &mut [0u8; 101]), Err(UnpackError::SizeMismatch));
+        assert_eq!(unpack(&packed[..3], &mut out), Err(UnpackError::Truncated));
+        assert_eq!(unpack(&[], &mut out), Err(UnpackError::Truncated));
+        // One literal, then a match 2 bytes back
+        assert_eq!(unpack(&[0x10, b'a', 2, 0], &mut out), Err(UnpackError::BadOffset));
+    }
+}
@@ -0,0 +1,110 @@
+//! Seccomp allowlist stored after the payloads (Linux only).
+//!
+//! Layout of a merged binary with a profile:
+//! `[Stub] + [Base] + [Overload] + [SeccompHeader + syscall numbers] + [ConfigFooter]`
+//!
+//! Weaver resolves syscall names for the target architecture at merge time,
+//! so the stub only has to turn the numbers into a BPF filter.
+
+use core::mem;
+
+pub const SECCOMP_MAGIC: &[u8; 4] = b"WVSC";
+
+/// Most syscalls a profile may allow; keeps the filter under the kernel's
+/// 4096 instruction limit
+pub const MAX_SECCOMP_SYSCALLS: usize = 1000;
+
+// What a syscall outside the allowlist does (`SeccompHeader::default_action`)
+
+/// Fails with EPERM
+pub const SECCOMP_DEFAULT_ERRNO: u32 = 0;
+/// Kills the whole process
+pub const SECCOMP_DEFAULT_KILL: u32 = 1;
+/// Is allowed but logged by the kernel (for building a profile)
+pub const SECCOMP_DEFAULT_LOG: u32 = 2;
+
+// Payloads the filter is installed in (`SeccompHeader::apply_to`)
+
+pub const SECCOMP_APPLY_OVERLOAD: u32 = 1 << 0;
+pub const SECCOMP_APPLY_BASE: u32 = 1 << 1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeccompHeader {
+    pub magic: [u8; 4],
+    /// `AUDIT_ARCH_*` value the syscall numbers belong to
+    pub audit_arch: u32,
+    /// `SECCOMP_DEFAULT_*` value
+    pub default_action: u32,
+    /// `SECCOMP_APPLY_*` flags
+    pub apply_to: u32,
+    /// Number of little-endian `u32` syscall numbers following the header
+    pub count: u32,
+}
+
+impl SeccompHeader {
+    pub const SIZE: usize = mem::size_of::<SeccompHeader>();
+
+    /// Raw bytes as written in front of the syscall numbers
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const SeccompHeader as *const u8, Self::SIZE) }
+    }
+
+    /// Split a stored profile into its header and syscall numbers
+    ///
+    /// Returns `None` if the magic, count or length don't add up.
+    pub fn parse(bytes: &[u8]) -> Option<(SeccompHeader, &[u8])> {
+        if bytes.len() < Self::SIZE {
+            return None;
+        }
+        let header: SeccompHeader = unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const SeccompHeader) };
+        let count = header.count as usize;
+        if &header.magic != SECCOMP_MAGIC || count > MAX_SECCOMP_SYSCALLS || bytes.len() != Self::SIZE + count * 4 {
+            return None;
+        }
+        Some((header, &bytes[Self::SIZE..]))
+    }
+
+    pub fn applies_to(&self, is_base: bool) -> bool {
+        let flag = if is_base { SECCOMP_APPLY_BASE } else { SECCOMP_APPLY_OVERLOAD };
+        self.apply_to & flag != 0
+    }
+}
+
+/// Syscall numbers as returned by [`SeccompHeader::parse`]
+pub fn syscalls(numbers: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    numbers.chunks_exact(4).map(|n| u32::from_le_bytes([n[0], n[1], n[2], n[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_round_trip() {
+        let header = SeccompHeader {
+            magic: *SECCOMP_MAGIC,
+            audit_arch: 0xC000_003E,
+            default_action: SECCOMP_DEFAULT_KILL,
+            apply_to: SECCOMP_APPLY_OVERLOAD,
+            count: 2,
+        };
+        let mut bytes = [0u8; SeccompHeader::SIZE + 8];
+        bytes[..SeccompHeader::SIZE].copy_from_slice(header.as_bytes());
+        bytes[SeccompHeader::SIZE..SeccompHeader::SIZE + 4].copy_from_slice(&0u32.to_le_bytes());
+        bytes[SeccompHeader::SIZE + 4..].copy_from_slice(&231u32.to_le_bytes());
+
+        let (parsed, numbers) = SeccompHeader::parse(&bytes).unwrap();
+        assert_eq!(parsed, header);
+        assert!(parsed.applies_to(false));
+        assert!(!parsed.applies_to(true));
+        let mut syscalls = syscalls(numbers);
+        assert_eq!(syscalls.next(), Some(0));
+        assert_eq!(syscalls.next(), Some(231));
+        assert_eq!(syscalls.next(), None);
+
+        // Truncated or padded profiles are rejected
+        assert!(SeccompHeader::parse(&bytes[..bytes.len() - 1]).is_none());
+        assert!(SeccompHeader::parse(&[0u8; SeccompHeader::SIZE]).is_none());
+    }
+}
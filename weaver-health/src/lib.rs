@@ -0,0 +1,291 @@
+//! Health reporting for overload payloads.
+//!
+//! When a merged binary runs with health monitoring, the loader stub creates
+//! a shared-memory region holding a [`weaver_abi::HealthTable`] and passes
+//! its name to the overload in `KILLCODE_HEALTH_SHM`, and the name of the
+//! overload's slot in it in `KILLCODE_HEALTH_SLOT`:
+//!
+//! * Unix: a POSIX shm object (`/overload_health_{stub_pid}`)
+//! * Windows: a named file mapping (`Local\OverloadHealth_{stub_pid}`)
+//!
+//! The stub kills base when the overload stops reporting success for longer
+//! than the grace period, fails too many checks in a row, asks for it with
+//! [`Health::request_base_kill`], or (with a heartbeat timeout) stops calling
+//! [`Health::heartbeat`]. When the failure threshold is hit the stub first
+//! asks the overload to run its own kill method, which it sees through
+//! [`Health::should_self_destruct`]. A merge with a kill policy replaces
+//! these checks with its own rules, which can also test the custom flags set
+//! with [`Health::set_flags`]. An overload the stub waits for before starting
+//! base says it is up with [`Health::set_ready`].
+//!
+//! The layout itself lives in `weaver-abi`; this crate only maps it and
+//! refuses regions whose header doesn't match its protocol version and layout.
+//!
+//! ```no_run
+//! let health = weaver_health::Health::from_env().expect("health region");
+//! if let Some(health) = health {
+//!     health.report_success();
+//!     if health.should_self_destruct() {
+//!         std::process::exit(1);
+//!     }
+//! }
+//! ```
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use weaver_abi::{HealthError, HealthSlot, HealthStatus, HealthTable};
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+use unix::Mapping;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "windows")]
+use windows::Mapping;
+
+/// Environment variable the stub uses to publish the region name
+pub const HEALTH_SHM_ENV: &str = "KILLCODE_HEALTH_SHM";
+
+/// Environment variable the stub uses to publish the payload's slot
+pub const HEALTH_SLOT_ENV: &str = "KILLCODE_HEALTH_SLOT";
+
+/// Mapped health slot shared with the loader stub
+pub struct Health {
+    mapping: Mapping,
+    /// Where the slot's status starts in the mapping
+    offset: usize,
+    /// Whether the mapping is a whole table, so the slot around the status exists
+    in_table: bool,
+}
+
+impl Health {
+    /// Open the region named in `KILLCODE_HEALTH_SHM`
+    ///
+    /// Returns `Ok(None)` when the variable is unset: the merge was made
+    /// without health monitoring (or in sync mode) and there is nothing to
+    /// report to.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        let name = match std::env::var(HEALTH_SHM_ENV) {
+            Ok(name) if !name.is_empty() => name,
+            _ => return Ok(None),
+        };
+        match std::env::var(HEALTH_SLOT_ENV) {
+            Ok(slot) if !slot.is_empty() => Self::open_slot(&name, &slot).map(Some),
+            // Stubs that predate slots publish a single status
+            _ => Self::open(&name).map(Some),
+        }
+    }
+
+    /// Open the first slot of a region by name
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let mapping = Mapping::open(name, HealthStatus::SIZE)?;
+        // Validates alignment; the mapping outlives every borrow handed out below
+        let status = unsafe { HealthStatus::from_ptr(mapping.as_ptr()) }.map_err(Error::Region)?;
+        // A stub speaking another protocol version would misread every write
+        status.check_header().map_err(Error::Region)?;
+        Ok(Self { mapping, offset: 0, in_table: false })
+    }
+
+    /// Open the slot called `slot` of a region by name
+    pub fn open_slot(name: &str, slot: &str) -> Result<Self, Error> {
+        let mapping = Mapping::open(name, HealthTable::SIZE)?;
+        let table = unsafe { HealthTable::from_ptr(mapping.as_ptr()) }.map_err(Error::Region)?;
+        let status = table.slot(slot).ok_or_else(|| Error::UnknownSlot(slot.to_string()))?.status();
+        status.check_header().map_err(Error::Region)?;
+        let offset = status as *const HealthStatus as usize - mapping.as_ptr() as usize;
+        Ok(Self { mapping, offset, in_table: true })
+    }
+
+    fn status(&self) -> &HealthStatus {
+        unsafe { &*(self.mapping.as_ptr().add(self.offset) as *const HealthStatus) }
+    }
+
+    fn slot(&self) -> Option<&HealthSlot> {
+        // A slot's status is its first field
+        self.in_table
+            .then(|| unsafe { &*(self.mapping.as_ptr().add(self.offset) as *const HealthSlot) })
+    }
+
+    /// A license/network check succeeded just now
+    pub fn report_success(&self) {
+        let now = current_time();
+        self.status().record_success(now);
+        self.status().heartbeat(now);
+    }
+
+    /// A check failed; returns the consecutive failure count
+    pub fn report_failure(&self) -> i32 {
+        self.status().heartbeat(current_time());
+        self.status().record_failure()
+    }
+
+    /// Prove the overload is still running without reporting a check result
+    pub fn heartbeat(&self) {
+        self.status().heartbeat(current_time());
+    }
+
+    /// Ask the stub to terminate base
+    pub fn request_base_kill(&self) {
+        self.status().request_base_kill();
+    }
+
+    /// Set custom flags for the stub's kill policy to test
+    ///
+    /// Does nothing when the region was opened with [`Health::open`], which
+    /// only maps a single status.
+    pub fn set_flags(&self, mask: u32) {
+        if let Some(slot) = self.slot() {
+            slot.set_flags(mask);
+        }
+    }
+
+    pub fn clear_flags(&self, mask: u32) {
+        if let Some(slot) = self.slot() {
+            slot.clear_flags(mask);
+        }
+    }
+
+    /// Tell a stub waiting on a ready flag readiness probe to start base
+    ///
+    /// Like [`Health::set_flags`], needs a region opened by slot.
+    pub fn set_ready(&self) {
+        if let Some(slot) = self.slot() {
+            slot.set_ready();
+        }
+    }
+
+    /// The stub asked the overload to run its kill method and exit
+    pub fn should_self_destruct(&self) -> bool {
+        self.status().overload_kill_requested()
+    }
+
+    /// PID of base once the stub has started it (0 before that)
+    pub fn base_pid(&self) -> i32 {
+        self.status().base_pid()
+    }
+
+    /// PID of the stub that started the overload and base
+    pub fn stub_pid(&self) -> i32 {
+        self.status().stub_pid()
+    }
+}
+
+fn current_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The region name contains a NUL byte
+    InvalidName(String),
+    /// The region could not be opened or mapped
+    Map(String),
+    /// The mapped region is unusable
+    Region(HealthError),
+    /// The region has no slot by this name
+    UnknownSlot(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidName(name) => write!(f, "invalid health region name: {:?}", name),
+            Error::Map(e) => write!(f, "failed to map health region: {}", e),
+            Error::Region(e) => write!(f, "unusable health region: {}", e),
+            Error::UnknownSlot(slot) => write!(f, "no health slot named {:?}", slot),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use nix::fcntl::OFlag;
+    use nix::sys::mman::{shm_open, shm_unlink};
+    use nix::sys::stat::Mode;
+
+    /// Create and initialize a region the way the stub does
+    fn stub_region(name: &str) -> Health {
+        let fd = shm_open(name, OFlag::O_CREAT | OFlag::O_RDWR, Mode::from_bits_truncate(0o600)).unwrap();
+        nix::unistd::ftruncate(&fd, HealthStatus::SIZE as nix::libc::off_t).unwrap();
+        let mapping = Mapping::open(name, HealthStatus::SIZE).unwrap();
+        unsafe { HealthStatus::from_ptr(mapping.as_ptr()) }.unwrap().init(0);
+        Health { mapping, offset: 0, in_table: false }
+    }
+
+    #[test]
+    fn test_reports_reach_the_shared_region() {
+        let name = format!("/weaver_health_test_{}", std::process::id());
+        let health = stub_region(&name);
+        let stub_view = Health::open(&name).unwrap();
+        let _ = shm_unlink(name.as_str());
+
+        assert_eq!(health.report_failure(), 1);
+        assert_eq!(health.report_failure(), 2);
+        health.report_success();
+        assert_eq!(stub_view.status().consecutive_failures(), 0);
+        assert!(stub_view.status().last_success() > 0);
+        assert!(stub_view.status().last_heartbeat() > 0);
+
+        assert!(!health.should_self_destruct());
+        stub_view.status().request_overload_kill();
+        assert!(health.should_self_destruct());
+
+        health.request_base_kill();
+        assert!(stub_view.status().base_kill_requested());
+
+        assert_eq!((health.base_pid(), health.stub_pid()), (0, 0));
+        stub_view.status().set_stub_pid(100);
+        stub_view.status().set_base_pid(101);
+        assert_eq!((health.base_pid(), health.stub_pid()), (101, 100));
+    }
+
+    #[test]
+    fn test_named_slot_is_opened_at_its_offset() {
+        let name = format!("/weaver_health_slots_{}", std::process::id());
+        let fd = shm_open(name.as_str(), OFlag::O_CREAT | OFlag::O_RDWR, Mode::from_bits_truncate(0o600)).unwrap();
+        nix::unistd::ftruncate(&fd, HealthTable::SIZE as nix::libc::off_t).unwrap();
+        let stub_view = Mapping::open(&name, HealthTable::SIZE).unwrap();
+        let table = unsafe { HealthTable::from_ptr(stub_view.as_ptr()) }.unwrap();
+        table.init(&["overload", "sidecar"], 0).unwrap();
+        let sidecar = Health::open_slot(&name, "sidecar");
+        let missing = Health::open_slot(&name, "absent");
+        let _ = shm_unlink(name.as_str());
+
+        let sidecar = sidecar.unwrap();
+        assert_eq!(sidecar.report_failure(), 1);
+        assert_eq!(table.slot("sidecar").unwrap().status().consecutive_failures(), 1);
+        assert_eq!(table.slot("overload").unwrap().status().consecutive_failures(), 0);
+        sidecar.set_flags(0b11);
+        sidecar.clear_flags(0b01);
+        assert_eq!(table.slot("sidecar").unwrap().flags(), 0b10);
+        sidecar.set_ready();
+        assert!(table.slot("sidecar").unwrap().is_ready());
+        assert!(!table.slot("overload").unwrap().is_ready());
+        assert!(matches!(missing, Err(Error::UnknownSlot(slot)) if slot == "absent"));
+    }
+
+    #[test]
+    fn test_uninitialized_region_is_refused() {
+        let name = format!("/weaver_health_blank_{}", std::process::id());
+        let fd = shm_open(name.as_str(), OFlag::O_CREAT | OFlag::O_RDWR, Mode::from_bits_truncate(0o600)).unwrap();
+        nix::unistd::ftruncate(&fd, HealthStatus::SIZE as nix::libc::off_t).unwrap();
+        let result = Health::open(&name);
+        let _ = shm_unlink(name.as_str());
+
+        assert!(matches!(result, Err(Error::Region(HealthError::BadMagic(0)))));
+    }
+
+    #[test]
+    fn test_missing_region_is_an_error() {
+        assert!(matches!(Health::open("/weaver_health_missing"), Err(Error::Map(_))));
+    }
+}
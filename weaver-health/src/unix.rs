@@ -0,0 +1,53 @@
+use std::num::NonZeroUsize;
+use std::os::fd::OwnedFd;
+use std::ptr::NonNull;
+
+use nix::fcntl::OFlag;
+use nix::sys::mman::{mmap, munmap, shm_open, MapFlags, ProtFlags};
+use nix::sys::stat::Mode;
+
+use crate::Error;
+
+/// POSIX shm object mapped read/write
+pub(crate) struct Mapping {
+    ptr: NonNull<std::ffi::c_void>,
+    len: NonZeroUsize,
+    _fd: OwnedFd,
+}
+
+impl Mapping {
+    pub(crate) fn open(name: &str, len: usize) -> Result<Self, Error> {
+        if name.contains('\0') {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+        let len = NonZeroUsize::new(len).ok_or_else(|| Error::Map("empty region".to_string()))?;
+
+        // Never create: a missing region means the stub isn't monitoring us
+        let fd = shm_open(name, OFlag::O_RDWR, Mode::empty()).map_err(|e| Error::Map(format!("shm_open {}: {}", name, e)))?;
+        let size = nix::sys::stat::fstat(&fd).map_err(|e| Error::Map(e.to_string()))?.st_size;
+        if (size as usize) < len.get() {
+            return Err(Error::Map(format!("region is {} bytes, expected at least {}", size, len)));
+        }
+
+        let ptr = unsafe {
+            mmap(None, len, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE, MapFlags::MAP_SHARED, &fd, 0)
+        }
+        .map_err(|e| Error::Map(format!("mmap: {}", e)))?;
+
+        Ok(Self { ptr, len, _fd: fd })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr() as *mut u8
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.ptr, self.len.get()) };
+    }
+}
+
+// The region is only accessed through atomics
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
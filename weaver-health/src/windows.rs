@@ -0,0 +1,53 @@
+use std::ffi::CString;
+
+use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+use windows_sys::Win32::System::Memory::{
+    MapViewOfFile, OpenFileMappingA, UnmapViewOfFile, FILE_MAP_ALL_ACCESS, MEMORY_MAPPED_VIEW_ADDRESS,
+};
+
+use crate::Error;
+
+/// Named file mapping opened read/write
+pub(crate) struct Mapping {
+    handle: HANDLE,
+    view: MEMORY_MAPPED_VIEW_ADDRESS,
+}
+
+impl Mapping {
+    pub(crate) fn open(name: &str, len: usize) -> Result<Self, Error> {
+        let name_c = CString::new(name).map_err(|_| Error::InvalidName(name.to_string()))?;
+
+        unsafe {
+            let handle = OpenFileMappingA(FILE_MAP_ALL_ACCESS, 0, name_c.as_ptr() as *const u8);
+            if handle.is_null() {
+                return Err(Error::Map(format!("OpenFileMappingA {}: {}", name, std::io::Error::last_os_error())));
+            }
+
+            let view = MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, len);
+            if view.Value.is_null() {
+                let error = std::io::Error::last_os_error();
+                CloseHandle(handle);
+                return Err(Error::Map(format!("MapViewOfFile: {}", error)));
+            }
+
+            Ok(Self { handle, view })
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.view.Value as *mut u8
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            UnmapViewOfFile(self.view);
+            CloseHandle(self.handle);
+        }
+    }
+}
+
+// The region is only accessed through atomics
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}